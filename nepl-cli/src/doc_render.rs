@@ -0,0 +1,75 @@
+//! `//:` ドキュメントコメントを stdlib の nm（`nm/parser` + `nm/html_gen`）で HTML にする。
+//!
+//! `analysis::hover::DOC_RENDERER_SOURCE` を wasm target でコンパイルし、wasmi 上で
+//! `nm_render` を呼ぶ。`str` は `[len: i32][bytes]` の形で線形メモリに置く。
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use nepl_core::analysis::hover::DOC_RENDERER_SOURCE;
+use nepl_core::loader::Loader;
+use nepl_core::{compile_module, CompileOptions, CompileTarget};
+use wasmi::{Engine, Linker, Memory, Module, Store, TypedFunc};
+
+pub struct DocRenderer {
+    store: Store<()>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    render: TypedFunc<i32, i32>,
+}
+
+impl DocRenderer {
+    pub fn new(stdlib_root: &Path) -> Result<Self> {
+        let mut loader = Loader::new(stdlib_root.to_path_buf());
+        let loaded = loader
+            .load_inline(PathBuf::from("<nm-render>"), DOC_RENDERER_SOURCE.to_string())
+            .map_err(|e| anyhow!("failed to load the nm renderer: {e}"))?;
+        let options = CompileOptions {
+            target: Some(CompileTarget::Wasm),
+            ..CompileOptions::default()
+        };
+        let artifact = compile_module(loaded.module, options)
+            .map_err(|e| anyhow!("failed to compile the nm renderer: {e}"))?;
+
+        let engine = Engine::default();
+        let module = Module::new(&engine, artifact.wasm.as_slice())
+            .context("failed to compile the nm renderer wasm")?;
+        let mut store = Store::new(&engine, ());
+        let instance = Linker::<()>::new(&engine)
+            .instantiate_and_start(&mut store, &module)
+            .context("failed to instantiate the nm renderer")?;
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or_else(|| anyhow!("the nm renderer does not export memory"))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&store, "nm_alloc")?;
+        let render = instance.get_typed_func::<i32, i32>(&store, "nm_render")?;
+        Ok(Self {
+            store,
+            memory,
+            alloc,
+            render,
+        })
+    }
+
+    /// markdown を HTML に変換する。
+    pub fn render(&mut self, markdown: &str) -> Result<String> {
+        let len = markdown.len();
+        let ptr = self.alloc.call(&mut self.store, 4 + len as i32)?;
+        let mut bytes = (len as i32).to_le_bytes().to_vec();
+        bytes.extend_from_slice(markdown.as_bytes());
+        self.memory
+            .write(&mut self.store, ptr as usize, &bytes)
+            .map_err(|_| anyhow!("nm_alloc returned an address out of memory"))?;
+
+        let out = self.render.call(&mut self.store, ptr)? as usize;
+        let data = self.memory.data(&self.store);
+        let len_bytes = data
+            .get(out..out + 4)
+            .ok_or_else(|| anyhow!("nm_render returned an address out of memory"))?;
+        let len = i32::from_le_bytes(len_bytes.try_into()?) as usize;
+        let html = data
+            .get(out + 4..out + 4 + len)
+            .ok_or_else(|| anyhow!("nm_render returned a string out of memory"))?;
+        Ok(String::from_utf8_lossy(html).into_owned())
+    }
+}
@@ -16,6 +16,8 @@ use wasmi::{Caller, Engine, Linker, Module, Store};
 use wasmprinter::print_bytes;
 
 mod codegen_llvm;
+mod doc_render;
+mod repl;
 
 #[derive(Default)]
 struct AllocState {
@@ -52,7 +54,7 @@ struct Cli {
         value_enum,
         value_delimiter = ',',
         default_value = "wasm",
-        help = "Output formats: wasm, wat, wat-min, all"
+        help = "Output formats: wasm, wat, wat-min, dts, rs, all (dts/rs describe #export functions)"
     )]
     emit: Vec<Emit>,
 
@@ -91,6 +93,10 @@ enum Emit {
     Wat,
     #[value(name = "wat-min")]
     WatMin,
+    /// `#export` された関数の TypeScript 宣言
+    Dts,
+    /// `#export` された関数の Rust バインディングスタブ
+    Rs,
     All,
 }
 
@@ -103,6 +109,14 @@ enum ProfileArg {
 #[derive(Subcommand, Debug)]
 enum Command {
     Test(TestArgs),
+    /// エディタ向けの解析結果（名前解決・推論型など）を出力する
+    Analyze(AnalyzeArgs),
+    /// 指定行の前置記法の式を、型検査で決まった呼び出しの区切りどおりに括弧を付けて出力する
+    ExplainExpr(ExplainExprArgs),
+    /// 関数・変数の名前を、import しているファイルの参照箇所も含めて変更する
+    Rename(RenameArgs),
+    /// 定義を溜めながら式を 1 行ずつ評価する対話環境を起動する
+    Repl(ReplArgs),
 }
 
 #[derive(Args, Debug)]
@@ -113,14 +127,92 @@ struct TestArgs {
     dir: String,
 }
 
+#[derive(Args, Debug)]
+struct AnalyzeArgs {
+    #[arg(value_name = "FILE", help = "Source file to analyze (reads stdin if omitted)")]
+    file: Option<String>,
+    #[arg(long, help = "Print the analysis as JSON (same shape as the nepl-web API)")]
+    json: bool,
+    #[arg(long, value_enum, default_value = "semantics", help = "Analysis stage")]
+    stage: AnalyzeStage,
+    #[arg(long, help = "Analyze the file alone, without loading its imports")]
+    single_file: bool,
+    #[arg(
+        long,
+        value_name = "LINE:COL|OFFSET",
+        help = "Cursor position for --stage complete, hover and references (1-based line and column, or a byte offset)"
+    )]
+    at: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct ExplainExprArgs {
+    #[arg(value_name = "FILE", help = "Source file (reads stdin if omitted)")]
+    file: Option<String>,
+    #[arg(long, value_name = "LINE", help = "1-based line to explain")]
+    line: u32,
+}
+
+#[derive(Args, Debug)]
+struct RenameArgs {
+    #[arg(value_name = "FILE", help = "Entry source file; the files it imports are searched too")]
+    file: String,
+    #[arg(
+        long,
+        value_name = "LINE:COL|OFFSET",
+        help = "Position of the name in FILE (1-based line and column, or a byte offset)"
+    )]
+    at: String,
+    #[arg(long, value_name = "NAME", help = "New name")]
+    to: String,
+    #[arg(long, help = "Write the edits to the files instead of printing them")]
+    write: bool,
+    #[arg(long, help = "Print the result as JSON (same shape as the nepl-web API)")]
+    json: bool,
+}
+
+#[derive(Args, Debug)]
+struct ReplArgs {
+    #[arg(long, value_name = "MODULE", help = "Import a module before the first line (repeatable)")]
+    import: Vec<String>,
+    #[arg(long, help = "Do not import core/math and std/stdio at startup")]
+    no_prelude: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum AnalyzeStage {
+    Lex,
+    Resolve,
+    Semantics,
+    Complete,
+    Hover,
+    Inlay,
+    References,
+    Tokens,
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
     execute(cli)
 }
 
 fn execute(cli: Cli) -> Result<()> {
-    if let Some(Command::Test(args)) = cli.command {
-        return run_tests(args, cli.verbose);
+    // Loader のログは -v のときだけ出す
+    nepl_core::log::set_verbose(cli.verbose);
+    match cli.command {
+        Some(Command::Test(args)) => return run_tests(args, cli.verbose),
+        Some(Command::Analyze(args)) => return run_analyze(args),
+        Some(Command::ExplainExpr(args)) => return run_explain_expr(args),
+        Some(Command::Rename(args)) => return run_rename(args),
+        Some(Command::Repl(args)) => {
+            let mut imports: Vec<String> = Vec::new();
+            if !args.no_prelude {
+                imports.extend(repl::PRELUDE.iter().map(|m| m.to_string()));
+            }
+            imports.extend(args.import);
+            return repl::run(&imports);
+        }
+        None => {}
     }
     if !cli.run && cli.output.is_none() {
         return Err(anyhow::anyhow!("Either --run or --output is required"));
@@ -225,8 +317,9 @@ fn execute(cli: Cli) -> Result<()> {
             eprintln!("DEBUG: compile_module returned Ok");
             a
         },
-        Err(CoreError::Diagnostics(diags)) => {
+        Err(CoreError::Diagnostics(mut diags)) => {
             eprintln!("DEBUG: compile_module returned Diagnostics");
+            annotate_stdlib_exports(&mut diags, &source_map);
             render_diagnostics(&diags, &source_map);
             return Err(anyhow::anyhow!("compilation failed"));
         }
@@ -255,6 +348,7 @@ fn execute(cli: Cli) -> Result<()> {
             &base,
             &artifact.wasm,
             &artifact.wat_comments,
+            &artifact.exports,
             &emits,
             attached_source.as_ref(),
         )?;
@@ -276,6 +370,440 @@ fn execute(cli: Cli) -> Result<()> {
     Ok(())
 }
 
+fn run_analyze(args: AnalyzeArgs) -> Result<()> {
+    use nepl_core::analysis::{
+        self, DiagnosticInfo, FileInfo, LoadedSources, NameResolution, NameResolutionOptions,
+        TextRange,
+    };
+
+    let source = match &args.file {
+        Some(path) => fs::read_to_string(path).with_context(|| format!("failed to read {path}"))?,
+        None => {
+            let mut buffer = String::new();
+            io::stdin().read_to_string(&mut buffer)?;
+            buffer
+        }
+    };
+    // 名前解決・型情報は import 先を Loader で読み込んだ上で entry ファイルについて出す
+    let load = || -> Result<LoadedSources> {
+        let mut loader = Loader::new(stdlib_root()?);
+        let result = match &args.file {
+            Some(path) => loader.load(&PathBuf::from(path)),
+            None => loader.load_inline(PathBuf::from("<stdin>"), source.clone()),
+        };
+        Ok(LoadedSources::new(result, &loader))
+    };
+    let cursor = || -> Result<u32> {
+        let at = args.at.as_deref().ok_or_else(|| {
+            anyhow::anyhow!("--stage complete, hover and references require --at")
+        })?;
+        cursor_offset(&source, at)
+    };
+    // 補完では編集中の行を伏せたソースを entry として読み込む
+    let load_text = |text: String| -> Result<LoadedSources> {
+        let mut loader = Loader::new(stdlib_root()?);
+        let path = match &args.file {
+            Some(path) => fs::canonicalize(path)?,
+            None => PathBuf::from("<stdin>"),
+        };
+        let result = loader.load_inline(path, text);
+        Ok(LoadedSources::new(result, &loader))
+    };
+
+    // entry ファイル以外の位置にはパスを付ける
+    fn location(files: &[FileInfo], range: &TextRange) -> String {
+        let pos = format!("{}:{}", range.start_line + 1, range.start_col + 1);
+        match files.get(range.file_id as usize) {
+            Some(file) if range.file_id != 0 => format!("{}:{}", file.path, pos),
+            _ => pos,
+        }
+    }
+    fn print_diagnostics(files: &[FileInfo], diags: &[DiagnosticInfo]) {
+        for d in diags {
+            println!("{}: {}: {}", location(files, &d.primary), d.severity, d.message);
+        }
+    }
+    fn print_names(res: &NameResolution) {
+        for d in res.definitions.iter().filter(|d| d.span.file_id == 0) {
+            println!(
+                "def #{} {} ({}) at {}:{}",
+                d.id,
+                d.name,
+                d.kind.as_str(),
+                d.span.start_line + 1,
+                d.span.start_col + 1
+            );
+        }
+        for r in &res.references {
+            let target = match &r.resolved_def {
+                Some(d) if d.span.file_id != 0 => {
+                    format!("#{} ({})", d.id, location(&res.files, &d.span))
+                }
+                Some(d) => format!("#{}", d.id),
+                None => "<unresolved>".to_string(),
+            };
+            println!(
+                "ref {} at {}:{} -> {}",
+                r.name,
+                r.span.start_line + 1,
+                r.span.start_col + 1,
+                target
+            );
+        }
+        for sh in &res.shadow_diagnostics {
+            println!(
+                "{}:{}: {}: {}",
+                sh.span.start_line + 1,
+                sh.span.start_col + 1,
+                sh.severity.as_str(),
+                sh.message
+            );
+        }
+    }
+
+    let ok = match args.stage {
+        AnalyzeStage::Lex => {
+            let result = analysis::analyze_lex(&source);
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&result)?);
+            } else {
+                for t in &result.tokens {
+                    println!("{}:{} {}", t.span.start_line + 1, t.span.start_col + 1, t.debug);
+                }
+                print_diagnostics(&[], &result.diagnostics);
+            }
+            result.ok
+        }
+        AnalyzeStage::Resolve => {
+            let options = NameResolutionOptions::default();
+            let result = if args.single_file {
+                analysis::analyze_name_resolution(&source, options)
+            } else {
+                analysis::analyze_loaded_name_resolution(&load()?, options)
+            };
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&result)?);
+            } else {
+                print_diagnostics(&result.resolution.files, &result.diagnostics);
+                print_names(&result.resolution);
+            }
+            result.ok
+        }
+        AnalyzeStage::Semantics => {
+            let result = if args.single_file {
+                analysis::analyze_semantics(&source)
+            } else {
+                analysis::analyze_loaded_semantics(&load()?)
+            };
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&result)?);
+            } else {
+                print_diagnostics(&result.files, &result.diagnostics);
+                for f in &result.functions {
+                    println!("fn {} : {}", f.name, f.signature);
+                }
+                for e in &result.expressions {
+                    println!(
+                        "expr #{} {} at {}:{} : {}",
+                        e.id,
+                        e.kind,
+                        e.span.start_line + 1,
+                        e.span.start_col + 1,
+                        e.inferred_type
+                    );
+                }
+                if let Some(res) = &result.name_resolution {
+                    print_names(res);
+                }
+            }
+            result.ok
+        }
+        AnalyzeStage::Complete => {
+            let offset = cursor()?;
+            let modules = nepl_core::suggest::StdlibIndex::scan(&stdlib_root()?)
+                .modules()
+                .to_vec();
+            let result = if args.single_file {
+                analysis::complete(&source, offset, &modules)
+            } else {
+                let blanked = analysis::completion::blank_cursor_line(&source, offset);
+                analysis::complete_loaded(&source, offset, &load_text(blanked)?, &modules)
+            };
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&result)?);
+            } else {
+                for item in &result.items {
+                    let mut line = format!("{} {}", item.kind.as_str(), item.label);
+                    if let Some(detail) = &item.detail {
+                        line.push_str(&format!(" {detail}"));
+                    }
+                    if let Some(doc) = &item.documentation {
+                        line.push_str(&format!(" -- {doc}"));
+                    }
+                    println!("{line}");
+                }
+            }
+            true
+        }
+        AnalyzeStage::Hover => {
+            let offset = cursor()?;
+            let mut result = if args.single_file {
+                analysis::hover(&source, offset)
+            } else {
+                analysis::hover_loaded(&load()?, offset)
+            };
+            if args.json {
+                // doc comment があるときだけ nm の renderer をコンパイルして HTML を添える
+                let has_docs = result
+                    .definition
+                    .iter()
+                    .any(|d| d.documentation.is_some())
+                    || result
+                        .signature_help
+                        .iter()
+                        .any(|s| s.documentation.is_some());
+                if has_docs {
+                    match doc_render::DocRenderer::new(&stdlib_root()?) {
+                        Ok(mut renderer) => result.render_documentation(|md| {
+                            renderer
+                                .render(md)
+                                .map_err(|e| eprintln!("warning: {e:#}"))
+                                .ok()
+                        }),
+                        Err(e) => eprintln!("warning: {e:#}"),
+                    }
+                }
+                println!("{}", serde_json::to_string_pretty(&result)?);
+            } else {
+                print_diagnostics(&[], &result.diagnostics);
+                if let Some(e) = &result.expression {
+                    println!("expr {} : {}", e.kind, e.inferred_type);
+                }
+                if let Some(d) = &result.definition {
+                    let at = match d.span.file_id {
+                        0 => format!("{}:{}", d.span.start_line + 1, d.span.start_col + 1),
+                        _ => format!("{}:{}", d.path, d.span.start_line + 1),
+                    };
+                    println!("def {} ({}) at {}", d.name, d.kind.as_str(), at);
+                    if let Some(sig) = &d.signature {
+                        println!("  {}", sig.label);
+                    }
+                    if let Some(doc) = &d.documentation {
+                        for line in doc.markdown.lines() {
+                            println!("  | {line}");
+                        }
+                    }
+                }
+                if let Some(help) = &result.signature_help {
+                    let active = match help.active_parameter {
+                        Some(i) => format!("argument {} of {}", i + 1, help.argument_ranges.len()),
+                        None => "callee".to_string(),
+                    };
+                    println!("call {} -- {}", help.signature.label, active);
+                }
+            }
+            result.ok
+        }
+        AnalyzeStage::Inlay => {
+            let result = if args.single_file {
+                analysis::inlay_hints(&source)
+            } else {
+                analysis::inlay_hints_loaded(&load()?)
+            };
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&result)?);
+            } else {
+                print_diagnostics(&[], &result.diagnostics);
+                for h in &result.hints {
+                    println!(
+                        "{}:{} {} {}",
+                        h.position.start_line + 1,
+                        h.position.start_col + 1,
+                        h.kind.as_str(),
+                        h.label
+                    );
+                }
+            }
+            result.ok
+        }
+        AnalyzeStage::References => {
+            let offset = cursor()?;
+            let result = if args.single_file {
+                analysis::find_references(&source, offset)
+            } else {
+                analysis::find_references_loaded(&load()?, offset)
+            };
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&result)?);
+            } else {
+                print_diagnostics(&[], &result.diagnostics);
+                for r in &result.references {
+                    let pos = format!("{}:{}", r.range.start_line + 1, r.range.start_col + 1);
+                    match r.range.file_id {
+                        0 => println!("{} {}", pos, r.kind.as_str()),
+                        _ => println!("{}:{} {}", r.path, pos, r.kind.as_str()),
+                    }
+                }
+            }
+            result.ok
+        }
+        AnalyzeStage::Tokens => {
+            let result = if args.single_file {
+                analysis::semantic_tokens(&source)
+            } else {
+                let root = stdlib_root()?;
+                let root = fs::canonicalize(&root).unwrap_or(root);
+                analysis::semantic_tokens_loaded(&load()?, &root.display().to_string())
+            };
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&result)?);
+            } else {
+                print_diagnostics(&[], &result.diagnostics);
+                for t in &result.tokens {
+                    let r = &t.range;
+                    let modifiers = t.modifiers.names();
+                    println!(
+                        "{}:{}-{} {}{}{}",
+                        r.start_line + 1,
+                        r.start_col + 1,
+                        r.end_col + 1,
+                        t.kind.as_str(),
+                        if modifiers.is_empty() { "" } else { " " },
+                        modifiers.join(",")
+                    );
+                }
+            }
+            result.ok
+        }
+    };
+    if !ok {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn run_explain_expr(args: ExplainExprArgs) -> Result<()> {
+    use nepl_core::analysis::{self, LoadedSources};
+
+    let mut loader = Loader::new(stdlib_root()?);
+    let (result, source) = match &args.file {
+        Some(path) => {
+            let source =
+                fs::read_to_string(path).with_context(|| format!("failed to read {path}"))?;
+            (loader.load(&PathBuf::from(path)), source)
+        }
+        None => {
+            let mut buffer = String::new();
+            io::stdin().read_to_string(&mut buffer)?;
+            (
+                loader.load_inline(PathBuf::from("<stdin>"), buffer.clone()),
+                buffer,
+            )
+        }
+    };
+    let loaded = LoadedSources::new(result, &loader);
+    let semantics = analysis::analyze_loaded_semantics(&loaded);
+    if !semantics.ok {
+        for d in &semantics.diagnostics {
+            eprintln!(
+                "{}:{}: {}: {}",
+                d.primary.start_line + 1,
+                d.primary.start_col + 1,
+                d.severity,
+                d.message
+            );
+        }
+        return Err(anyhow::anyhow!(
+            "cannot explain expressions of a program that does not typecheck"
+        ));
+    }
+    let line = analysis::parenthesize_line(&semantics, &source, args.line.saturating_sub(1))
+        .ok_or_else(|| anyhow::anyhow!("line {} is out of range", args.line))?;
+    println!("{}", line.trim());
+    Ok(())
+}
+
+fn run_rename(args: RenameArgs) -> Result<()> {
+    use nepl_core::analysis::{self, LoadedSources};
+    use nepl_core::span::FileId;
+
+    let source =
+        fs::read_to_string(&args.file).with_context(|| format!("failed to read {}", args.file))?;
+    let mut loader = Loader::new(stdlib_root()?);
+    let result = loader.load(&PathBuf::from(&args.file));
+    let loaded = LoadedSources::new(result, &loader);
+    let result = analysis::rename_loaded(&loaded, cursor_offset(&source, &args.at)?, &args.to);
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    }
+    if !result.ok {
+        for d in &result.diagnostics {
+            eprintln!(
+                "{}:{}: {}: {}",
+                d.primary.start_line + 1,
+                d.primary.start_col + 1,
+                d.severity,
+                d.message
+            );
+        }
+        for c in &result.conflicts {
+            eprintln!(
+                "{}:{}:{}: conflict: {}",
+                c.path,
+                c.range.start_line + 1,
+                c.range.start_col + 1,
+                c.message
+            );
+        }
+        return Err(anyhow::anyhow!("rename to `{}` was not applied", args.to));
+    }
+    let old = result.symbol.as_ref().map_or("", |s| s.name.as_str());
+    for file in &result.edit.files {
+        if args.write {
+            // 読み込み時点の内容に編集を当てて書き戻す
+            let text = loaded
+                .source_map
+                .get(FileId(file.file_id))
+                .ok_or_else(|| anyhow::anyhow!("{} is not loaded", file.path))?;
+            fs::write(&file.path, result.edit.apply(file.file_id, text))
+                .with_context(|| format!("failed to write {}", file.path))?;
+            eprintln!("{}: {} edit(s)", file.path, file.edits.len());
+        } else if !args.json {
+            for e in &file.edits {
+                println!(
+                    "{}:{}:{}: {} -> {}",
+                    file.path,
+                    e.range.start_line + 1,
+                    e.range.start_col + 1,
+                    old,
+                    e.new_text
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `LINE:COL`（1 始まり）または byte offset を byte offset に変換する。
+fn cursor_offset(source: &str, at: &str) -> Result<u32> {
+    let Some((line, col)) = at.split_once(':') else {
+        return at.parse().with_context(|| format!("invalid cursor position {at}"));
+    };
+    let line: usize = line.parse().with_context(|| format!("invalid line in {at}"))?;
+    let col: usize = col.parse().with_context(|| format!("invalid column in {at}"))?;
+    let start: usize = source
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    let text = source[start..].split('\n').next().unwrap_or_default();
+    let byte = text
+        .char_indices()
+        .nth(col.saturating_sub(1))
+        .map_or(text.len(), |(i, _)| i);
+    Ok((start + byte) as u32)
+}
+
 fn run_tests(args: TestArgs, verbose: bool) -> Result<()> {
     const ANSI_RESET: &str = "\x1b[0m";
     const ANSI_GREEN: &str = "\x1b[32m";
@@ -353,7 +881,8 @@ fn run_test_file(path: &Path, std_root: &Path, verbose: bool) -> Result<()> {
         },
     ) {
         Ok(a) => a,
-        Err(CoreError::Diagnostics(diags)) => {
+        Err(CoreError::Diagnostics(mut diags)) => {
+            annotate_stdlib_exports(&mut diags, loader.source_map());
             render_diagnostics(&diags, loader.source_map());
             return Err(anyhow::anyhow!("compilation failed"));
         }
@@ -497,6 +1026,8 @@ fn output_path(base: &Path, emit: Emit) -> PathBuf {
         Emit::Wasm => base.with_extension("wasm"),
         Emit::Wat => base.with_extension("wat"),
         Emit::WatMin => PathBuf::from(format!("{}.min.wat", base.display())),
+        Emit::Dts => PathBuf::from(format!("{}.d.ts", base.display())),
+        Emit::Rs => base.with_extension("rs"),
         Emit::All => base.to_path_buf(),
     }
 }
@@ -505,6 +1036,7 @@ fn write_outputs(
     base: &Path,
     wasm: &[u8],
     wat_debug: &str,
+    exports: &nepl_core::bindings::ExportBindings,
     emits: &BTreeSet<Emit>,
     attached_source: Option<&AttachedSource>,
 ) -> Result<()> {
@@ -531,6 +1063,14 @@ fn write_outputs(
         wat_text = prepend_compiler_info_as_wat_comment(&wat_text);
         write_bytes(&path, wat_text.as_bytes())?;
     }
+    if emits.contains(&Emit::Dts) {
+        let path = output_path(base, Emit::Dts);
+        write_bytes(&path, nepl_core::bindings::typescript(exports).as_bytes())?;
+    }
+    if emits.contains(&Emit::Rs) {
+        let path = output_path(base, Emit::Rs);
+        write_bytes(&path, nepl_core::bindings::rust(exports).as_bytes())?;
+    }
     Ok(())
 }
 fn write_bytes(path: &Path, bytes: &[u8]) -> Result<()> {
@@ -693,284 +1233,238 @@ fn run_wasm(
         }
     }
     if matches!(target, CompileTarget::Wasi) {
-        linker.func_wrap(
-            "wasi_snapshot_preview1",
-            "args_sizes_get",
-            |mut caller: Caller<'_, AllocState>, argc_ptr: i32, argv_buf_size_ptr: i32| -> i32 {
-                let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    Some(m) => m,
-                    None => return 21,
-                };
-                if argc_ptr < 0 || argv_buf_size_ptr < 0 {
-                    return 21;
-                }
-                let argc = caller.data().args.len() as u32;
-                let buf_size: u32 = caller
-                    .data()
-                    .args
-                    .iter()
-                    .map(|a| a.len() as u32)
-                    .sum();
-                let mem_len = memory.data(&caller).len();
-                let argc_offset = argc_ptr as usize;
-                let buf_offset = argv_buf_size_ptr as usize;
-                if argc_offset + 4 > mem_len || buf_offset + 4 > mem_len {
+        link_wasi(&mut linker)?;
+    }
+    let mut store = Store::new(
+        &engine,
+        AllocState {
+            free_head: 0,
+            stdin: Vec::new(),
+            stdin_pos: 0,
+            stdin_eof: false,
+            args: args_bytes,
+            files: BTreeMap::new(),
+            next_fd: 4,
+        },
+    );
+    let instance = linker
+        .instantiate_and_start(&mut store, &module)
+        .context("failed to instantiate module")?;
+    if let Ok(main) = instance.get_typed_func::<(), i32>(&store, "main") {
+        main.call(&mut store, ()).context("failed to execute main")
+    } else if let Ok(main_unit) = instance.get_typed_func::<(), ()>(&store, "main") {
+        main_unit
+            .call(&mut store, ())
+            .context("failed to execute main")?;
+        Ok(0)
+    } else {
+        Err(anyhow::anyhow!(
+            "exported main function missing or has wrong type"
+        ))
+    }
+}
+
+/// `wasi_snapshot_preview1` のホスト関数（引数・標準入出力・ファイル読み込み）を登録する。
+fn link_wasi(linker: &mut Linker<AllocState>) -> Result<()> {
+    linker.func_wrap(
+        "wasi_snapshot_preview1",
+        "args_sizes_get",
+        |mut caller: Caller<'_, AllocState>, argc_ptr: i32, argv_buf_size_ptr: i32| -> i32 {
+            let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
+                Some(m) => m,
+                None => return 21,
+            };
+            if argc_ptr < 0 || argv_buf_size_ptr < 0 {
+                return 21;
+            }
+            let argc = caller.data().args.len() as u32;
+            let buf_size: u32 = caller
+                .data()
+                .args
+                .iter()
+                .map(|a| a.len() as u32)
+                .sum();
+            let mem_len = memory.data(&caller).len();
+            let argc_offset = argc_ptr as usize;
+            let buf_offset = argv_buf_size_ptr as usize;
+            if argc_offset + 4 > mem_len || buf_offset + 4 > mem_len {
+                return 21;
+            }
+            if memory
+                .write(&mut caller, argc_offset, &argc.to_le_bytes())
+                .is_err()
+            {
+                return 21;
+            }
+            if memory
+                .write(&mut caller, buf_offset, &buf_size.to_le_bytes())
+                .is_err()
+            {
+                return 21;
+            }
+            0
+        },
+    )?;
+    linker.func_wrap(
+        "wasi_snapshot_preview1",
+        "args_get",
+        |mut caller: Caller<'_, AllocState>, argv: i32, argv_buf: i32| -> i32 {
+            let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
+                Some(m) => m,
+                None => return 21,
+            };
+            if argv < 0 || argv_buf < 0 {
+                return 21;
+            }
+            let mem_len = memory.data(&caller).len();
+            let args = caller.data().args.clone();
+            let mut argv_offset = argv as usize;
+            let mut buf_offset = argv_buf as usize;
+            for arg in args.iter() {
+                if argv_offset + 4 > mem_len {
                     return 21;
                 }
+                let ptr_bytes = (buf_offset as u32).to_le_bytes();
                 if memory
-                    .write(&mut caller, argc_offset, &argc.to_le_bytes())
+                    .write(&mut caller, argv_offset, &ptr_bytes)
                     .is_err()
                 {
                     return 21;
                 }
-                if memory
-                    .write(&mut caller, buf_offset, &buf_size.to_le_bytes())
-                    .is_err()
-                {
+                if buf_offset + arg.len() > mem_len {
                     return 21;
                 }
-                0
-            },
-        )?;
-        linker.func_wrap(
-            "wasi_snapshot_preview1",
-            "args_get",
-            |mut caller: Caller<'_, AllocState>, argv: i32, argv_buf: i32| -> i32 {
-                let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    Some(m) => m,
-                    None => return 21,
-                };
-                if argv < 0 || argv_buf < 0 {
+                if memory.write(&mut caller, buf_offset, arg).is_err() {
                     return 21;
                 }
-                let mem_len = memory.data(&caller).len();
-                let args = caller.data().args.clone();
-                let mut argv_offset = argv as usize;
-                let mut buf_offset = argv_buf as usize;
-                for arg in args.iter() {
-                    if argv_offset + 4 > mem_len {
-                        return 21;
+                argv_offset += 4;
+                buf_offset += arg.len();
+            }
+            0
+        },
+    )?;
+    linker.func_wrap(
+        "wasi_snapshot_preview1",
+        "path_open",
+        |mut caller: Caller<'_, AllocState>,
+         _dirfd: i32,
+         _dirflags: i32,
+         path_ptr: i32,
+         path_len: i32,
+         _oflags: i32,
+         _rights_base: i64,
+         _rights_inherit: i64,
+         _fdflags: i32,
+         fd_out: i32|
+         -> i32 {
+            let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
+                Some(m) => m,
+                None => return 21,
+            };
+            if path_ptr < 0 || path_len < 0 || fd_out < 0 {
+                return 21;
+            }
+            let mem = memory.data(&caller);
+            let start = path_ptr as usize;
+            let end = start.saturating_add(path_len as usize);
+            if end > mem.len() || (fd_out as usize) + 4 > mem.len() {
+                return 21;
+            }
+            let path = std::str::from_utf8(&mem[start..end]).unwrap_or("");
+            let data = match fs::read(path) {
+                Ok(d) => d,
+                Err(_) => return 44,
+            };
+            let fd = caller.data().next_fd;
+            caller.data_mut().next_fd += 1;
+            caller
+                .data_mut()
+                .files
+                .insert(fd, FileState { data, pos: 0 });
+            let fd_bytes = (fd as u32).to_le_bytes();
+            if memory
+                .write(&mut caller, fd_out as usize, &fd_bytes)
+                .is_err()
+            {
+                return 21;
+            }
+            0
+        },
+    )?;
+    linker.func_wrap(
+        "wasi_snapshot_preview1",
+        "fd_read",
+        |mut caller: Caller<'_, AllocState>,
+         fd: i32,
+         iovs: i32,
+         iovs_len: i32,
+         nread: i32|
+         -> i32 {
+            let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
+                Some(m) => m,
+                None => return 21,
+            };
+            let data_snapshot = memory.data(&caller).to_vec();
+            let mut total = 0usize;
+            let mut offset = iovs as usize;
+            let count = if iovs_len > 0 { iovs_len as usize } else { 0 };
+            if fd == 0 {
+                if caller.data().stdin_pos >= caller.data().stdin.len()
+                    && !caller.data().stdin_eof
+                {
+                    let mut buf = vec![0u8; 4096];
+                    let read = match io::stdin().read(&mut buf) {
+                        Ok(n) => n,
+                        Err(_) => 0,
+                    };
+                    if read == 0 {
+                        caller.data_mut().stdin_eof = true;
+                        caller.data_mut().stdin.clear();
+                        caller.data_mut().stdin_pos = 0;
+                    } else {
+                        caller.data_mut().stdin = buf[..read].to_vec();
+                        caller.data_mut().stdin_pos = 0;
                     }
-                    let ptr_bytes = (buf_offset as u32).to_le_bytes();
-                    if memory
-                        .write(&mut caller, argv_offset, &ptr_bytes)
-                        .is_err()
-                    {
+                }
+                let stdin_snapshot = caller.data().stdin.clone();
+                let mut pos = caller.data().stdin_pos;
+                for _ in 0..count {
+                    if offset + 8 > data_snapshot.len() {
                         return 21;
                     }
-                    if buf_offset + arg.len() > mem_len {
+                    let base = u32::from_le_bytes(
+                        data_snapshot[offset..offset + 4].try_into().unwrap(),
+                    ) as usize;
+                    let len = u32::from_le_bytes(
+                        data_snapshot[offset + 4..offset + 8].try_into().unwrap(),
+                    ) as usize;
+                    offset += 8;
+                    if base + len > data_snapshot.len() {
                         return 21;
                     }
-                    if memory.write(&mut caller, buf_offset, arg).is_err() {
-                        return 21;
+                    if pos >= stdin_snapshot.len() {
+                        break;
                     }
-                    argv_offset += 4;
-                    buf_offset += arg.len();
-                }
-                0
-            },
-        )?;
-        linker.func_wrap(
-            "wasi_snapshot_preview1",
-            "path_open",
-            |mut caller: Caller<'_, AllocState>,
-             _dirfd: i32,
-             _dirflags: i32,
-             path_ptr: i32,
-             path_len: i32,
-             _oflags: i32,
-             _rights_base: i64,
-             _rights_inherit: i64,
-             _fdflags: i32,
-             fd_out: i32|
-             -> i32 {
-                let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    Some(m) => m,
-                    None => return 21,
-                };
-                if path_ptr < 0 || path_len < 0 || fd_out < 0 {
-                    return 21;
-                }
-                let mem = memory.data(&caller);
-                let start = path_ptr as usize;
-                let end = start.saturating_add(path_len as usize);
-                if end > mem.len() || (fd_out as usize) + 4 > mem.len() {
-                    return 21;
-                }
-                let path = std::str::from_utf8(&mem[start..end]).unwrap_or("");
-                let data = match fs::read(path) {
-                    Ok(d) => d,
-                    Err(_) => return 44,
-                };
-                let fd = caller.data().next_fd;
-                caller.data_mut().next_fd += 1;
-                caller
-                    .data_mut()
-                    .files
-                    .insert(fd, FileState { data, pos: 0 });
-                let fd_bytes = (fd as u32).to_le_bytes();
-                if memory
-                    .write(&mut caller, fd_out as usize, &fd_bytes)
-                    .is_err()
-                {
-                    return 21;
-                }
-                0
-            },
-        )?;
-        linker.func_wrap(
-            "wasi_snapshot_preview1",
-            "fd_read",
-            |mut caller: Caller<'_, AllocState>,
-             fd: i32,
-             iovs: i32,
-             iovs_len: i32,
-             nread: i32|
-             -> i32 {
-                let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    Some(m) => m,
-                    None => return 21,
-                };
-                let data_snapshot = memory.data(&caller).to_vec();
-                let mut total = 0usize;
-                let mut offset = iovs as usize;
-                let count = if iovs_len > 0 { iovs_len as usize } else { 0 };
-                if fd == 0 {
-                    if caller.data().stdin_pos >= caller.data().stdin.len()
-                        && !caller.data().stdin_eof
-                    {
-                        let mut buf = vec![0u8; 4096];
-                        let read = match io::stdin().read(&mut buf) {
-                            Ok(n) => n,
-                            Err(_) => 0,
-                        };
-                        if read == 0 {
-                            caller.data_mut().stdin_eof = true;
-                            caller.data_mut().stdin.clear();
-                            caller.data_mut().stdin_pos = 0;
-                        } else {
-                            caller.data_mut().stdin = buf[..read].to_vec();
-                            caller.data_mut().stdin_pos = 0;
-                        }
-                    }
-                    let stdin_snapshot = caller.data().stdin.clone();
-                    let mut pos = caller.data().stdin_pos;
-                    for _ in 0..count {
-                        if offset + 8 > data_snapshot.len() {
-                            return 21;
-                        }
-                        let base = u32::from_le_bytes(
-                            data_snapshot[offset..offset + 4].try_into().unwrap(),
-                        ) as usize;
-                        let len = u32::from_le_bytes(
-                            data_snapshot[offset + 4..offset + 8].try_into().unwrap(),
-                        ) as usize;
-                        offset += 8;
-                        if base + len > data_snapshot.len() {
-                            return 21;
-                        }
-                        if pos >= stdin_snapshot.len() {
-                            break;
-                        }
-                        let avail = stdin_snapshot.len() - pos;
-                        let take = if len < avail { len } else { avail };
-                        if take == 0 {
-                            break;
-                        }
-                        memory
-                            .write(&mut caller, base, &stdin_snapshot[pos..pos + take])
-                            .ok();
-                        pos += take;
-                        total += take;
-                    }
-                    caller.data_mut().stdin_pos = pos;
-                } else {
-                    for _ in 0..count {
-                        if offset + 8 > data_snapshot.len() {
-                            return 21;
-                        }
-                        let base = u32::from_le_bytes(
-                            data_snapshot[offset..offset + 4].try_into().unwrap(),
-                        ) as usize;
-                        let len = u32::from_le_bytes(
-                            data_snapshot[offset + 4..offset + 8].try_into().unwrap(),
-                        ) as usize;
-                        offset += 8;
-                        if base + len > data_snapshot.len() {
-                            return 21;
-                        }
-                        let (take, chunk) = {
-                            let file = match caller.data_mut().files.get_mut(&fd) {
-                                Some(f) => f,
-                                None => return 8,
-                            };
-                            if file.pos >= file.data.len() {
-                                (0, Vec::new())
-                            } else {
-                                let avail = file.data.len() - file.pos;
-                                let take = if len < avail { len } else { avail };
-                                let chunk = file.data[file.pos..file.pos + take].to_vec();
-                                file.pos += take;
-                                (take, chunk)
-                            }
-                        };
-                        if take == 0 {
-                            break;
-                        }
-                        memory.write(&mut caller, base, &chunk).ok();
-                        total += take;
-                    }
-                }
-                if let Some(mem) = caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    let bytes = (total as u32).to_le_bytes();
-                    if (nread as usize) + 4 <= mem.data(&caller).len() {
-                        mem.write(&mut caller, nread as usize, &bytes).ok();
+                    let avail = stdin_snapshot.len() - pos;
+                    let take = if len < avail { len } else { avail };
+                    if take == 0 {
+                        break;
                     }
+                    memory
+                        .write(&mut caller, base, &stdin_snapshot[pos..pos + take])
+                        .ok();
+                    pos += take;
+                    total += take;
                 }
-                0
-            },
-        )?;
-        linker.func_wrap(
-            "wasi_snapshot_preview1",
-            "fd_close",
-            |mut caller: Caller<'_, AllocState>, fd: i32| -> i32 {
-                if fd <= 2 {
-                    return 0;
-                }
-                if caller.data_mut().files.remove(&fd).is_none() {
-                    return 8;
-                }
-                0
-            },
-        )?;
-        // Minimal wasi fd_write implementation for stdout (fd 1)
-        linker.func_wrap(
-            "wasi_snapshot_preview1",
-            "fd_write",
-            |mut caller: Caller<'_, AllocState>,
-             fd: i32,
-             iovs: i32,
-             iovs_len: i32,
-             nwritten: i32|
-             -> i32 {
-                if fd != 1 {
-                    return 8; // badf
-                }
-                let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    Some(m) => m,
-                    None => return 21, // enomem-ish
-                };
-                let data_snapshot = memory.data(&caller).to_vec(); // snapshot to avoid alias issues
-                let mut total = 0usize;
-                let mut offset = iovs as usize;
-                let mut stdout = io::stdout().lock();
-                for _ in 0..iovs_len {
+                caller.data_mut().stdin_pos = pos;
+            } else {
+                for _ in 0..count {
                     if offset + 8 > data_snapshot.len() {
                         return 21;
                     }
-                    let base =
-                        u32::from_le_bytes(data_snapshot[offset..offset + 4].try_into().unwrap())
-                            as usize;
+                    let base = u32::from_le_bytes(
+                        data_snapshot[offset..offset + 4].try_into().unwrap(),
+                    ) as usize;
                     let len = u32::from_le_bytes(
                         data_snapshot[offset + 4..offset + 8].try_into().unwrap(),
                     ) as usize;
@@ -978,54 +1472,103 @@ fn run_wasm(
                     if base + len > data_snapshot.len() {
                         return 21;
                     }
-                    let slice = &data_snapshot[base..base + len];
-                    if stdout.write_all(slice).is_err() {
-                        return 21;
+                    let (take, chunk) = {
+                        let file = match caller.data_mut().files.get_mut(&fd) {
+                            Some(f) => f,
+                            None => return 8,
+                        };
+                        if file.pos >= file.data.len() {
+                            (0, Vec::new())
+                        } else {
+                            let avail = file.data.len() - file.pos;
+                            let take = if len < avail { len } else { avail };
+                            let chunk = file.data[file.pos..file.pos + take].to_vec();
+                            file.pos += take;
+                            (take, chunk)
+                        }
+                    };
+                    if take == 0 {
+                        break;
                     }
-                    total += len;
+                    memory.write(&mut caller, base, &chunk).ok();
+                    total += take;
                 }
-                let _ = stdout.flush();
-                // write nwritten
-                if let Some(mem) = caller.get_export("memory").and_then(|e| e.into_memory()) {
-                    let bytes = (total as u32).to_le_bytes();
-                    if (nwritten as usize) + 4 <= mem.data(&caller).len() {
-                        mem.write(&mut caller, nwritten as usize, &bytes).ok();
-                    }
+            }
+            if let Some(mem) = caller.get_export("memory").and_then(|e| e.into_memory()) {
+                let bytes = (total as u32).to_le_bytes();
+                if (nread as usize) + 4 <= mem.data(&caller).len() {
+                    mem.write(&mut caller, nread as usize, &bytes).ok();
                 }
-                0
-            },
-        )?;
-    }
-    let mut store = Store::new(
-        &engine,
-        AllocState {
-            free_head: 0,
-            stdin: Vec::new(),
-            stdin_pos: 0,
-            stdin_eof: false,
-            args: args_bytes,
-            files: BTreeMap::new(),
-            next_fd: 4,
+            }
+            0
         },
-    );
-    let instance_pre = linker
-        .instantiate(&mut store, &module)
-        .context("failed to instantiate module")?;
-    let instance = instance_pre
-        .start(&mut store)
-        .context("failed to start module")?;
-    if let Ok(main) = instance.get_typed_func::<(), i32>(&store, "main") {
-        main.call(&mut store, ()).context("failed to execute main")
-    } else if let Ok(main_unit) = instance.get_typed_func::<(), ()>(&store, "main") {
-        main_unit
-            .call(&mut store, ())
-            .context("failed to execute main")?;
-        Ok(0)
-    } else {
-        Err(anyhow::anyhow!(
-            "exported main function missing or has wrong type"
-        ))
-    }
+    )?;
+    linker.func_wrap(
+        "wasi_snapshot_preview1",
+        "fd_close",
+        |mut caller: Caller<'_, AllocState>, fd: i32| -> i32 {
+            if fd <= 2 {
+                return 0;
+            }
+            if caller.data_mut().files.remove(&fd).is_none() {
+                return 8;
+            }
+            0
+        },
+    )?;
+    // Minimal wasi fd_write implementation for stdout (fd 1)
+    linker.func_wrap(
+        "wasi_snapshot_preview1",
+        "fd_write",
+        |mut caller: Caller<'_, AllocState>,
+         fd: i32,
+         iovs: i32,
+         iovs_len: i32,
+         nwritten: i32|
+         -> i32 {
+            if fd != 1 {
+                return 8; // badf
+            }
+            let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
+                Some(m) => m,
+                None => return 21, // enomem-ish
+            };
+            let data_snapshot = memory.data(&caller).to_vec(); // snapshot to avoid alias issues
+            let mut total = 0usize;
+            let mut offset = iovs as usize;
+            let mut stdout = io::stdout().lock();
+            for _ in 0..iovs_len {
+                if offset + 8 > data_snapshot.len() {
+                    return 21;
+                }
+                let base =
+                    u32::from_le_bytes(data_snapshot[offset..offset + 4].try_into().unwrap())
+                        as usize;
+                let len = u32::from_le_bytes(
+                    data_snapshot[offset + 4..offset + 8].try_into().unwrap(),
+                ) as usize;
+                offset += 8;
+                if base + len > data_snapshot.len() {
+                    return 21;
+                }
+                let slice = &data_snapshot[base..base + len];
+                if stdout.write_all(slice).is_err() {
+                    return 21;
+                }
+                total += len;
+            }
+            let _ = stdout.flush();
+            // write nwritten
+            if let Some(mem) = caller.get_export("memory").and_then(|e| e.into_memory()) {
+                let bytes = (total as u32).to_le_bytes();
+                if (nwritten as usize) + 4 <= mem.data(&caller).len() {
+                    mem.write(&mut caller, nwritten as usize, &bytes).ok();
+                }
+            }
+            0
+        },
+    )?;
+    Ok(())
 }
 
 fn detect_module_target(module: &nepl_core::ast::Module) -> Option<CompileTarget> {
@@ -1068,6 +1611,19 @@ fn stdlib_root() -> Result<PathBuf> {
         .context(format!("stdlib directory not found at {}", path.display()))
 }
 
+/// 未定義識別子の診断に、その名前を定義している stdlib モジュールを添える。
+fn annotate_stdlib_exports(diags: &mut [Diagnostic], sm: &SourceMap) {
+    if !diags
+        .iter()
+        .any(|d| d.code == Some(nepl_core::suggest::UNDEFINED_IDENTIFIER))
+    {
+        return;
+    }
+    if let Ok(root) = stdlib_root() {
+        nepl_core::suggest::StdlibIndex::scan(&root).annotate(diags, sm);
+    }
+}
+
 fn render_diagnostics(diags: &[Diagnostic], sm: &SourceMap) {
     for d in diags {
         let severity = match d.severity {
@@ -1122,6 +1678,9 @@ fn render_diagnostics(diags: &[Diagnostic], sm: &SourceMap) {
             let msg = label.message.as_ref().map(|m| m.as_str()).unwrap_or("");
             eprintln!(" note: {p}:{line}:{col}: {msg}", line = l + 1, col = c + 1);
         }
+        for note in &d.notes {
+            eprintln!(" help: {note}");
+        }
         eprintln!();
     }
 }
@@ -1144,6 +1703,12 @@ mod tests {
         assert_eq!(cli.emit, vec![Emit::Wasm, Emit::WatMin]);
     }
 
+    #[test]
+    fn cli_parses_binding_emits() {
+        let cli = Cli::parse_from(["nepl-cli", "--emit", "wasm,dts,rs"]);
+        assert_eq!(cli.emit, vec![Emit::Wasm, Emit::Dts, Emit::Rs]);
+    }
+
     #[test]
     fn cli_parses_profile() {
         let cli = Cli::parse_from(["nepl-cli", "--run", "--profile", "debug"]);
@@ -1197,8 +1762,11 @@ mod tests {
         emits.insert(Emit::Wasm);
         emits.insert(Emit::Wat);
         emits.insert(Emit::WatMin);
+        emits.insert(Emit::Dts);
+        emits.insert(Emit::Rs);
 
-        write_outputs(&base, wasm, "", &emits, None).expect("write outputs");
+        let exports = nepl_core::bindings::ExportBindings::default();
+        write_outputs(&base, wasm, "", &exports, &emits, None).expect("write outputs");
 
         let wasm_path = base.with_extension("wasm");
         let wat_path = base.with_extension("wat");
@@ -1206,6 +1774,8 @@ mod tests {
         assert!(wasm_path.exists());
         assert!(wat_path.exists());
         assert!(wat_min_path.exists());
+        assert!(PathBuf::from(format!("{}.d.ts", base.display())).exists());
+        assert!(base.with_extension("rs").exists());
         assert!(!fs::read_to_string(wat_path).unwrap_or_default().is_empty());
         assert!(!fs::read_to_string(wat_min_path).unwrap_or_default().is_empty());
     }
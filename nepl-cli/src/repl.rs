@@ -0,0 +1,1083 @@
+//! `nepl-cli repl`: 式を 1 行ずつコンパイルして実行する対話環境。
+//!
+//! 入力した定義（`fn` / `struct` / `static` など）と `#import` はセッションに溜めておき、
+//! 式の行はそれらと一緒に `#export "__repl_N"` の関数で包んでコンパイルする。
+//! 戻り値の型と値は `CompilationArtifact::exports` の ABI 記述から読み取る。
+//!
+//! 各行は新しい wasmi インスタンスで実行するが、線形メモリのヒープ（`REPL_HEAP_BASE` 以降）と
+//! アロケータの状態（アドレス 0 / 4）、WASI のホスト状態は前の行から引き継ぐ。
+//! 静的データとシャドウスタックは行ごとに配置が変わるので `REPL_HEAP_BASE` より下に置く。
+//! インスタンスを使い回さないのは、行ごとに溜めた定義ごとモジュールをコンパイルし直すため
+//! （関数の番号も静的データの配置も変わり、wasm のインスタンスには後からコードを足せない）。
+//! `static mut` の値は `__repl_get_NAME` / `__repl_set_NAME` の export で行の後に読み、
+//! 次の行の前に書き戻して引き継ぐ。`let` で残す値や `static mut` の値のうち静的データを
+//! 指すもの（文字列リテラルなど）はヒープへ複製する。
+
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use nepl_core::bindings::{abi_label, AbiLayout, AbiType, ExportedFn};
+use nepl_core::diagnostic::Diagnostic;
+use nepl_core::error::CoreError;
+use nepl_core::lexer::{self, TokenKind};
+use nepl_core::loader::{Loader, LoaderError, SourceMap};
+use nepl_core::span::{FileId, Span};
+use nepl_core::{compile_module, CompilationArtifact, CompileOptions, CompileTarget};
+use wasmi::{Engine, Instance, Linker, Memory, Module, Store, TypedFunc, Val, WasmResults};
+
+use crate::{
+    annotate_stdlib_exports, link_wasi, make_wat_pretty, render_diagnostics, stdlib_root,
+    AllocState,
+};
+
+/// 行をまたいで残るヒープの先頭。セッションの静的データはこれより下に収まる必要がある。
+const REPL_HEAP_BASE: u32 = 0x10_0000;
+
+/// 値を複製・表示するときにたどる入れ子の深さの上限（循環するリストなどへの備え）。
+const MAX_DEPTH: u32 = 8;
+
+/// `--no-prelude` を付けない限り最初に import するモジュール。
+pub(crate) const PRELUDE: &[&str] = &["core/math", "std/stdio"];
+
+const HELP: &str = "\
+Enter an expression to evaluate it, or a definition (fn, struct, enum, trait, impl,
+static, #import) to keep it for the following lines. A line ending with ':' starts a
+block; finish it with an empty line.
+
+  let NAME [<T>] EXPR     evaluate EXPR and keep the value as NAME
+                          (static mut values also carry over to the following lines)
+  :type EXPR              show the inferred type of EXPR without running it
+  :wat EXPR               show the wasm function compiled for EXPR
+  :import MODULE [as A]   import a module (default alias: *)
+  :help                   show this help
+  :quit                   leave the REPL";
+
+/// REPL を起動し、標準入力が尽きるか `:quit` まで 1 入力ずつ評価する。
+pub(crate) fn run(imports: &[String]) -> Result<()> {
+    let mut session = Session::new()?;
+    for path in imports {
+        session.import(path)?;
+    }
+    let stdin = io::stdin();
+    let interactive = stdin.is_terminal();
+    if interactive {
+        println!("NEPLg2 REPL (:help for commands, :quit to exit)");
+    }
+    while let Some(line) = read_line(interactive, "nepl> ")? {
+        let mut text = line.trim_end().to_string();
+        if text.trim().is_empty() {
+            continue;
+        }
+        // `:` で終わる行はブロックの始まりなので、空行までを続きとして読む
+        if text.ends_with(':') {
+            while let Some(next) = read_line(interactive, "  ... ")? {
+                if next.trim().is_empty() {
+                    break;
+                }
+                text.push('\n');
+                text.push_str(next.trim_end());
+            }
+        }
+        match session.handle(&text) {
+            Ok(Flow::Continue) => {}
+            Ok(Flow::Quit) => break,
+            Err(e) => eprintln!("error: {e:#}"),
+        }
+    }
+    Ok(())
+}
+
+fn read_line(interactive: bool, prompt: &str) -> Result<Option<String>> {
+    if interactive {
+        print!("{prompt}");
+        io::stdout().flush()?;
+    }
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line))
+}
+
+enum Flow {
+    Continue,
+    Quit,
+}
+
+/// wasm の戻り値。`str` や集成体はメモリ上のポインタ（`I32`）になる。
+#[derive(Debug, Clone, Copy)]
+enum Value {
+    Unit,
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+}
+
+/// `let` で残した値。以降の行では `let NAME <TY> __repl_let_NAME;` として見える。
+struct Binding {
+    name: String,
+    ty: String,
+    value: Value,
+}
+
+impl Binding {
+    /// 値をそのまま返す関数。ポインタは REPL ヒープを指すので行をまたいでも有効。
+    fn getter(&self) -> String {
+        let head = format!("fn __repl_let_{} <()->{}> ():\n", self.name, self.ty);
+        let body = match self.value {
+            Value::Unit => return format!("{head}    ()\n"),
+            Value::I32(v) => format!("i32.const {v}"),
+            Value::I64(v) => format!("i64.const {v}"),
+            Value::F32(v) => format!(
+                "i32.const {}\n        f32.reinterpret_i32",
+                v.to_bits() as i32
+            ),
+            Value::F64(v) => format!(
+                "i64.const {}\n        f64.reinterpret_i64",
+                v.to_bits() as i64
+            ),
+        };
+        format!("{head}    #wasm:\n        {body}\n")
+    }
+}
+
+/// 行をまたいで値を引き継ぐ `static mut`。
+struct Carried {
+    name: String,
+    ty: String,
+    /// 前の行を実行し終えたときの値。まだ 1 行も実行していなければ `None`。
+    value: Option<Value>,
+}
+
+impl Carried {
+    /// 値を読み書きする export。静的データの配置は行ごとに変わるので、名前で値を受け渡す。
+    fn accessors(&self) -> String {
+        format!(
+            "\n#export \"__repl_get_{0}\"\nfn __repl_get_{0} <()*>{1}> ():\n    {0}\n\
+             \n#export \"__repl_set_{0}\"\nfn __repl_set_{0} <({1})*>()> (v):\n    set {0} v\n",
+            self.name, self.ty
+        )
+    }
+}
+
+/// 入力したままのテキストと、文字列リテラルを `__repl_str_K` に置き換えたテキスト。
+struct Input {
+    typed: String,
+    lifted: String,
+    /// 置き換えた箇所: (lifted 上の位置, lifted 上の長さ, typed 上の長さ)
+    edits: Vec<(usize, usize, usize)>,
+}
+
+impl Input {
+    fn plain(text: &str) -> Input {
+        Input {
+            typed: text.to_string(),
+            lifted: text.to_string(),
+            edits: Vec::new(),
+        }
+    }
+
+    /// lifted 上の位置を typed 上の位置に戻す。置き換えた名前の中はリテラルの先頭にする。
+    fn typed_offset(&self, pos: usize) -> usize {
+        let mut shift = 0isize;
+        for &(at, lifted_len, typed_len) in &self.edits {
+            if pos < at {
+                break;
+            }
+            if pos < at + lifted_len {
+                return (at as isize + shift) as usize;
+            }
+            shift += typed_len as isize - lifted_len as isize;
+        }
+        (pos as isize + shift) as usize
+    }
+}
+
+/// 合成したソースの中に置いた入力。診断の位置を入力した行へ戻すのに使う。
+struct Placed<'a> {
+    input: &'a Input,
+    /// 合成したソース上の先頭位置。
+    start: usize,
+    /// 各行の先頭に足した空白の幅。
+    indent: usize,
+}
+
+impl Placed<'_> {
+    /// 合成したソース上の位置 `pos` を、入力したテキスト上の位置にする。
+    fn typed_offset(&self, pos: usize) -> Option<usize> {
+        let (mut synth, mut lifted) = (self.start, 0);
+        for line in self.input.lifted.lines() {
+            let len = self.indent + line.len() + 1;
+            if (synth..synth + len).contains(&pos) {
+                let col = (pos - synth).saturating_sub(self.indent).min(line.len());
+                return Some(self.input.typed_offset(lifted + col));
+            }
+            synth += len;
+            lifted += line.len() + 1;
+        }
+        None
+    }
+}
+
+/// コンパイルに渡すソースと、その中に置いた入力。
+struct Source<'a> {
+    text: String,
+    placed: Vec<Placed<'a>>,
+}
+
+struct Session {
+    imports: Vec<String>,
+    defs: Vec<Input>,
+    bindings: Vec<Binding>,
+    statics: Vec<Carried>,
+    /// REPL ヒープに置いた文字列リテラルとそのアドレス。`__repl_str_K` で参照する。
+    literals: Vec<(String, u32)>,
+    lines: u32,
+    engine: Engine,
+    linker: Linker<AllocState>,
+    /// 前の行から引き継ぐ WASI のホスト状態（stdin のバッファや開いたファイル）。
+    host: Option<AllocState>,
+    /// 前の行を実行し終えたときの線形メモリ。最初に使うときに `REPL_HEAP_BASE` まで確保する。
+    memory: Vec<u8>,
+}
+
+impl Session {
+    fn new() -> Result<Self> {
+        let engine = Engine::default();
+        let mut linker = Linker::new(&engine);
+        link_wasi(&mut linker)?;
+        Ok(Session {
+            imports: Vec::new(),
+            defs: Vec::new(),
+            bindings: Vec::new(),
+            statics: Vec::new(),
+            literals: Vec::new(),
+            lines: 0,
+            engine,
+            linker,
+            host: None,
+            memory: Vec::new(),
+        })
+    }
+
+    fn handle(&mut self, text: &str) -> Result<Flow> {
+        if let Some(command) = text.strip_prefix(':') {
+            let (name, arg) = command
+                .split_once(char::is_whitespace)
+                .unwrap_or((command, ""));
+            let arg = arg.trim();
+            match name {
+                "q" | "quit" => return Ok(Flow::Quit),
+                "h" | "help" => println!("{HELP}"),
+                "t" | "type" => self.show_type(arg)?,
+                "wat" => self.show_wat(arg)?,
+                "import" => self.import(arg)?,
+                _ => anyhow::bail!("unknown command :{name} (see :help)"),
+            }
+        } else if is_definition(text) {
+            self.define(text)?;
+        } else if let Some(rest) = text.strip_prefix("let ") {
+            self.bind(rest)?;
+        } else {
+            self.eval(text, None)?;
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// `:import core/option as opt` を `#import "core/option" as opt` として追加する。
+    fn import(&mut self, arg: &str) -> Result<()> {
+        let (path, alias) = match arg.split_once(" as ") {
+            Some((path, alias)) => (path.trim(), alias.trim()),
+            None => (arg.trim(), "*"),
+        };
+        let path = path.trim_matches('"');
+        if path.is_empty() || alias.is_empty() {
+            anyhow::bail!("usage: :import MODULE [as ALIAS]");
+        }
+        self.define(&format!("#import \"{path}\" as {alias}"))
+    }
+
+    /// 定義をセッションに加える。コンパイルできなければ加えない。
+    fn define(&mut self, text: &str) -> Result<()> {
+        let mut imports = self.imports.clone();
+        if text.starts_with("#import") {
+            if imports.iter().any(|i| i == text) {
+                return Ok(());
+            }
+            imports.push(text.to_string());
+            if self.compile(self.source(&imports, &self.defs, None), true)?.is_some() {
+                self.imports = imports;
+            }
+            return Ok(());
+        }
+        let input = if text.split_whitespace().any(|w| w == "static") {
+            // static の初期化式は const 評価されるので、リテラルはそのまま残す
+            Input::plain(text)
+        } else {
+            self.lift_literals(text)
+        };
+        let carried = static_mut_signature(text).map(|(name, ty)| Carried {
+            name,
+            ty,
+            value: None,
+        });
+        if let Some(carried) = carried {
+            // 読み書きの export ごとコンパイルできるものだけ引き継ぐ
+            self.statics.push(carried);
+            self.defs.push(input);
+            if self.compile(self.source(&imports, &self.defs, None), false)?.is_some() {
+                return Ok(());
+            }
+            let carried = self.statics.pop().expect("pushed above");
+            if self.compile(self.source(&imports, &self.defs, None), true)?.is_some() {
+                eprintln!(
+                    "note: `static mut {}` cannot be exported, so it starts from its initializer on every line",
+                    carried.name
+                );
+            } else {
+                self.defs.pop();
+            }
+            return Ok(());
+        }
+        self.defs.push(input);
+        if self.compile(self.source(&imports, &self.defs, None), true)?.is_none() {
+            self.defs.pop();
+        }
+        Ok(())
+    }
+
+    /// `let NAME [<T>] EXPR` を評価し、値を NAME として残す。
+    fn bind(&mut self, rest: &str) -> Result<()> {
+        let rest = rest.trim_start();
+        if rest.starts_with("mut ") {
+            anyhow::bail!(
+                "`let mut` cannot be kept between lines; bind the new value with `let` again"
+            );
+        }
+        let name = rest
+            .split(|c: char| c.is_whitespace() || c == '<')
+            .next()
+            .unwrap_or("");
+        let valid = name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+        if !valid {
+            anyhow::bail!("expected a name after `let`");
+        }
+        let name = name.to_string();
+        self.eval(&format!("let {rest}\n{name}"), Some(&name))
+    }
+
+    fn eval(&mut self, body: &str, bind: Option<&str>) -> Result<()> {
+        let Some((artifact, export)) = self.compile_line(body)? else {
+            return Ok(());
+        };
+        let ty = abi_label(&export.result);
+        if bind.is_some() && matches!(export.result, AbiType::Tuple(_) | AbiType::Opaque(_)) {
+            anyhow::bail!("`let` cannot keep a value of type {ty} between lines");
+        }
+        let layouts = &artifact.exports.layouts;
+        let value = self.execute(&artifact, &export, bind.is_some())?;
+        let shown = show(&self.memory, layouts, &export.result, value, 0);
+        match bind {
+            Some(name) => {
+                println!("{name} : {ty} = {shown}");
+                self.bindings.retain(|b| b.name != name);
+                self.bindings.push(Binding {
+                    name: name.to_string(),
+                    ty,
+                    value,
+                });
+            }
+            None if !matches!(export.result, AbiType::Unit) => println!("{shown} : {ty}"),
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn show_type(&mut self, expr: &str) -> Result<()> {
+        if expr.is_empty() {
+            anyhow::bail!("usage: :type EXPR");
+        }
+        if let Some((_, export)) = self.compile_line(expr)? {
+            println!("{}", abi_label(&export.result));
+        }
+        Ok(())
+    }
+
+    fn show_wat(&mut self, expr: &str) -> Result<()> {
+        if expr.is_empty() {
+            anyhow::bail!("usage: :wat EXPR");
+        }
+        if let Some((artifact, export)) = self.compile_line(expr)? {
+            print!("{}", export_wat(&artifact.wasm, &export.name)?);
+        }
+        Ok(())
+    }
+
+    /// `body` を `__repl_N` として包んでコンパイルする。診断は表示して `None` を返す。
+    fn compile_line(&mut self, body: &str) -> Result<Option<(CompilationArtifact, ExportedFn)>> {
+        let body = self.lift_literals(body);
+        self.lines += 1;
+        let name = format!("__repl_{}", self.lines);
+        let source = self.source(&self.imports, &self.defs, Some((&name, &body)));
+        let Some(artifact) = self.compile(source, true)? else {
+            return Ok(None);
+        };
+        let export = artifact
+            .exports
+            .functions
+            .iter()
+            .find(|f| f.name == name)
+            .cloned()
+            .with_context(|| format!("{name} was not exported"))?;
+        Ok(Some((artifact, export)))
+    }
+
+    fn source<'a>(
+        &self,
+        imports: &[String],
+        defs: &'a [Input],
+        line: Option<(&str, &'a Input)>,
+    ) -> Source<'a> {
+        let mut src = String::from("#target wasi\n#indent 4\n");
+        let mut placed = Vec::new();
+        for import in imports {
+            src.push_str(import);
+            src.push('\n');
+        }
+        for def in defs {
+            src.push('\n');
+            placed.push(Placed {
+                input: def,
+                start: src.len(),
+                indent: 0,
+            });
+            src.push_str(&def.lifted);
+            src.push('\n');
+        }
+        for carried in &self.statics {
+            src.push_str(&carried.accessors());
+        }
+        for (id, (_, addr)) in self.literals.iter().enumerate() {
+            src.push_str(&format!(
+                "\nfn __repl_str_{id} <()->str> ():\n    #wasm:\n        i32.const {addr}\n"
+            ));
+        }
+        for binding in &self.bindings {
+            src.push('\n');
+            src.push_str(&binding.getter());
+        }
+        if let Some((name, body)) = line {
+            src.push_str(&format!("\n#export \"{name}\"\nfn {name} <()*>.> ():\n"));
+            for binding in self.bindings.iter().filter(|b| mentions(&body.lifted, &b.name)) {
+                src.push_str(&format!(
+                    "    let {0} <{1}> __repl_let_{0};\n",
+                    binding.name, binding.ty
+                ));
+            }
+            placed.push(Placed {
+                input: body,
+                start: src.len(),
+                indent: 4,
+            });
+            for l in body.lifted.lines() {
+                src.push_str("    ");
+                src.push_str(l);
+                src.push('\n');
+            }
+        }
+        Source { text: src, placed }
+    }
+
+    /// 文字列リテラルを、REPL ヒープに置いた同じ文字列を返す `__repl_str_K` に置き換える。
+    /// 静的データは行ごとに配置が変わるので、`Vec<str>` の要素のように型からはたどれない
+    /// 場所に入ったリテラルも次の行で壊れないようにする。`#` で始まる行は書き換えない。
+    fn lift_literals(&mut self, text: &str) -> Input {
+        let mut out = String::new();
+        let mut edits = Vec::new();
+        let mut last = 0;
+        for token in lexer::lex(FileId(0), text).tokens {
+            let TokenKind::StringLiteral(value) = &token.kind else {
+                continue;
+            };
+            let (start, end) = (token.span.start as usize, token.span.end as usize);
+            let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+            if text[line_start..].trim_start().starts_with('#') {
+                continue;
+            }
+            let id = self.literal(value);
+            out.push_str(&text[last..start]);
+            let name = format!("__repl_str_{id}");
+            edits.push((out.len(), name.len(), end - start));
+            out.push_str(&name);
+            last = end;
+        }
+        out.push_str(&text[last..]);
+        Input {
+            typed: text.to_string(),
+            lifted: out,
+            edits,
+        }
+    }
+
+    fn literal(&mut self, value: &str) -> usize {
+        if let Some(id) = self.literals.iter().position(|(s, _)| s == value) {
+            return id;
+        }
+        let len = value.len() as u32;
+        let addr = self.bump(4 + len);
+        let at = addr as usize;
+        self.memory[at..at + 4].copy_from_slice(&len.to_le_bytes());
+        self.memory[at + 4..at + 4 + value.len()].copy_from_slice(value.as_bytes());
+        self.literals.push((value.to_string(), addr));
+        self.literals.len() - 1
+    }
+
+    /// 前の行のメモリ上で、`core/mem` の `alloc` と同じ形（8 バイトのヘッダ + 本体）で
+    /// ヒープの末尾から `size` バイトを確保する。
+    fn bump(&mut self, size: u32) -> u32 {
+        self.reserve(0);
+        let start = align8(read_u32(&self.memory, 0).unwrap_or(REPL_HEAP_BASE));
+        let total = align8(size + 8);
+        self.reserve((start + total) as usize);
+        self.memory[..4].copy_from_slice(&(start + total).to_le_bytes());
+        self.memory[start as usize..start as usize + 4].copy_from_slice(&total.to_le_bytes());
+        start + 8
+    }
+
+    /// 保存しているメモリを `len` バイト以上（ページ単位）にする。
+    /// 最初に呼ばれたときはヒープの先頭を `REPL_HEAP_BASE` にする。
+    fn reserve(&mut self, len: usize) {
+        if self.memory.is_empty() {
+            self.memory = vec![0; REPL_HEAP_BASE as usize];
+            self.memory[..4].copy_from_slice(&REPL_HEAP_BASE.to_le_bytes());
+        }
+        if len > self.memory.len() {
+            self.memory.resize(len.div_ceil(0x10000) * 0x10000, 0);
+        }
+    }
+
+    /// `source` をコンパイルする。失敗したら `report` のときだけ診断を表示して `None` を返す。
+    fn compile(&self, source: Source, report: bool) -> Result<Option<CompilationArtifact>> {
+        let mut loader = Loader::new(stdlib_root()?);
+        // 相対 import はカレントディレクトリから解決する
+        let path = std::env::current_dir()?.join("<repl>");
+        let module = match loader.load_inline(path.clone(), source.text) {
+            Ok(res) => res.module,
+            Err(LoaderError::Core(CoreError::Diagnostics(diags))) => {
+                if report {
+                    render_input_diagnostics(diags, loader.source_map(), &path, &source.placed);
+                }
+                return Ok(None);
+            }
+            Err(e) => return Err(anyhow::anyhow!(e.to_string())),
+        };
+        let options = CompileOptions {
+            target: Some(CompileTarget::Wasi),
+            verbose: false,
+            profile: None,
+        };
+        match compile_module(module, options) {
+            Ok(artifact) => Ok(Some(artifact)),
+            Err(CoreError::Diagnostics(mut diags)) => {
+                if report {
+                    annotate_stdlib_exports(&mut diags, loader.source_map());
+                    render_input_diagnostics(diags, loader.source_map(), &path, &source.placed);
+                }
+                Ok(None)
+            }
+            Err(e) => Err(anyhow::anyhow!(e.to_string())),
+        }
+    }
+
+    /// 前の行のヒープと `static mut` の値を復元してから `export` を呼ぶ。
+    /// 失敗した行のメモリと `static mut` の値は捨てる。
+    fn execute(
+        &mut self,
+        artifact: &CompilationArtifact,
+        export: &ExportedFn,
+        keep: bool,
+    ) -> Result<Value> {
+        let module = Module::new(&self.engine, artifact.wasm.as_slice())
+            .context("failed to compile wasm artifact")?;
+        let host = self.host.take().unwrap_or_else(|| AllocState {
+            args: vec![b"repl\0".to_vec()],
+            next_fd: 4,
+            ..AllocState::default()
+        });
+        let mut store = Store::new(&self.engine, host);
+        let result = self.run_in(&mut store, &module, artifact, export, keep);
+        self.host = Some(store.into_data());
+        result
+    }
+
+    fn run_in(
+        &mut self,
+        store: &mut Store<AllocState>,
+        module: &Module,
+        artifact: &CompilationArtifact,
+        export: &ExportedFn,
+        keep: bool,
+    ) -> Result<Value> {
+        let instance = self
+            .linker
+            .instantiate_and_start(&mut *store, module)
+            .context("failed to instantiate module")?;
+        let memory = instance
+            .get_memory(&*store, "memory")
+            .context("module does not export its memory")?;
+        let static_end = read_u32(memory.data(&*store), 0).unwrap_or(0);
+        if static_end > REPL_HEAP_BASE {
+            anyhow::bail!(
+                "static data of the session ({static_end} bytes) does not fit below the REPL heap at {REPL_HEAP_BASE:#x}"
+            );
+        }
+        self.reserve(0);
+        let wanted = self.memory.len();
+        let current = memory.data(&*store).len();
+        if wanted > current {
+            let pages = (wanted - current).div_ceil(0x10000) as u64;
+            memory
+                .grow(&mut *store, pages)
+                .map_err(|e| anyhow::anyhow!("failed to grow memory: {e}"))?;
+        }
+        let heap = REPL_HEAP_BASE as usize;
+        memory
+            .write(&mut *store, 0, &self.memory[..8])
+            .and_then(|_| memory.write(&mut *store, heap, &self.memory[heap..]))
+            .map_err(|e| anyhow::anyhow!("failed to restore memory: {e}"))?;
+
+        for carried in &self.statics {
+            let Some(value) = carried.value else {
+                continue;
+            };
+            let name = format!("__repl_set_{}", carried.name);
+            let setter = instance
+                .get_func(&*store, &name)
+                .with_context(|| format!("{name} was not exported"))?;
+            let arg = match value {
+                Value::Unit => continue,
+                Value::I32(v) => Val::from(v),
+                Value::I64(v) => Val::from(v),
+                Value::F32(v) => Val::from(v),
+                Value::F64(v) => Val::from(v),
+            };
+            setter
+                .call(&mut *store, &[arg], &mut [])
+                .with_context(|| format!("failed to restore static {}", carried.name))?;
+        }
+
+        let value = invoke(store, &instance, &export.name, &export.result)?;
+        let mut statics = Vec::new();
+        for carried in &self.statics {
+            let name = format!("__repl_get_{}", carried.name);
+            let getter = artifact
+                .exports
+                .functions
+                .iter()
+                .find(|f| f.name == name)
+                .with_context(|| format!("{name} was not exported"))?;
+            let value = invoke(store, &instance, &name, &getter.result)?;
+            statics.push((getter.result.clone(), value));
+        }
+        if !keep && statics.is_empty() {
+            self.memory = memory.data(&*store).to_vec();
+            return Ok(value);
+        }
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&*store, "__nepl_alloc")
+            .context("module does not export __nepl_alloc")?;
+        let mut heap = Heap {
+            store,
+            memory,
+            alloc,
+            layouts: &artifact.exports.layouts,
+        };
+        let value = if keep {
+            heap.keep(&export.result, value)?
+        } else {
+            value
+        };
+        let mut kept = Vec::new();
+        for (ty, value) in statics {
+            kept.push(heap.keep(&ty, value)?);
+        }
+        for (carried, value) in self.statics.iter_mut().zip(kept) {
+            carried.value = Some(value);
+        }
+        self.memory = memory.data(&*store).to_vec();
+        Ok(value)
+    }
+}
+
+/// 戻り値の ABI に合わせて export `name` を呼ぶ。
+fn invoke(
+    store: &mut Store<AllocState>,
+    instance: &Instance,
+    name: &str,
+    ty: &AbiType,
+) -> Result<Value> {
+    Ok(match ty {
+        AbiType::Unit => {
+            call::<()>(store, instance, name)?;
+            Value::Unit
+        }
+        AbiType::I64 => Value::I64(call(store, instance, name)?),
+        AbiType::F32 => Value::F32(call(store, instance, name)?),
+        AbiType::F64 => Value::F64(call(store, instance, name)?),
+        _ => Value::I32(call(store, instance, name)?),
+    })
+}
+
+fn call<R: WasmResults>(
+    store: &mut Store<AllocState>,
+    instance: &Instance,
+    name: &str,
+) -> Result<R> {
+    instance
+        .get_typed_func::<(), R>(&*store, name)
+        .with_context(|| format!("{name} has an unexpected wasm signature"))?
+        .call(&mut *store, ())
+        .context("execution failed")
+}
+
+fn align8(x: u32) -> u32 {
+    (x + 7) & !7
+}
+
+/// `text` が識別子 `name` を含むか。
+fn mentions(text: &str, name: &str) -> bool {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(name).any(|(i, _)| {
+        !text[..i].ends_with(is_ident) && !text[i + name.len()..].starts_with(is_ident)
+    })
+}
+
+/// `static mut NAME <T> EXPR` の NAME と T。
+fn static_mut_signature(text: &str) -> Option<(String, String)> {
+    let text = text.trim_start();
+    let rest = text.strip_prefix("pub ").unwrap_or(text).trim_start();
+    let rest = rest.strip_prefix("static ")?.trim_start().strip_prefix("mut ")?.trim_start();
+    let name_end = rest.find(|c: char| c.is_whitespace() || c == '<')?;
+    let (name, rest) = rest.split_at(name_end);
+    let rest = rest.trim_start().strip_prefix('<')?;
+    // `->` と `*>` の `>` は型引数の閉じではない
+    let mut depth = 1;
+    let mut prev = ' ';
+    for (i, c) in rest.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' if prev != '-' && prev != '*' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((name.to_string(), rest[..i].trim().to_string()));
+                }
+            }
+            _ => {}
+        }
+        prev = c;
+    }
+    None
+}
+
+fn is_definition(text: &str) -> bool {
+    let first = text.split_whitespace().next().unwrap_or("");
+    text.starts_with('#')
+        || matches!(
+            first,
+            "fn" | "struct" | "enum" | "trait" | "impl" | "static" | "pub"
+        )
+}
+
+/// 合成したソース上の位置を入力した行へ戻してから診断を表示する。
+/// 入力はそれぞれ `<repl>` という名前のファイルとして見せる。
+fn render_input_diagnostics(
+    mut diags: Vec<Diagnostic>,
+    sm: &SourceMap,
+    path: &Path,
+    placed: &[Placed],
+) {
+    let mut sm = sm.clone();
+    let repl = (0..)
+        .map(FileId)
+        .take_while(|id| sm.path(*id).is_some())
+        .find(|id| sm.path(*id).is_some_and(|p| p == path));
+    if let Some(repl) = repl {
+        let files: Vec<FileId> = placed
+            .iter()
+            .map(|p| sm.add(PathBuf::from("<repl>"), p.input.typed.clone()))
+            .collect();
+        let remap = |span: Span| -> Option<Span> {
+            let start = span.start as usize;
+            let (idx, p) = placed
+                .iter()
+                .enumerate()
+                .find(|(_, p)| p.typed_offset(start).is_some())?;
+            let from = p.typed_offset(start)?;
+            let to = p.typed_offset(span.end as usize).unwrap_or(from).max(from);
+            Some(Span::new(files[idx], from as u32, to as u32))
+        };
+        for d in &mut diags {
+            for label in std::iter::once(&mut d.primary).chain(d.secondary.iter_mut()) {
+                if label.span.file_id == repl {
+                    if let Some(span) = remap(label.span) {
+                        label.span = span;
+                    }
+                }
+            }
+        }
+    }
+    render_diagnostics(&diags, &sm);
+}
+
+/// `let` で残す値を、静的データから REPL ヒープへ移すための実行中インスタンスへの参照。
+struct Heap<'a> {
+    store: &'a mut Store<AllocState>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    layouts: &'a [AbiLayout],
+}
+
+impl Heap<'_> {
+    /// 行をまたいで残す値にする。ポインタは `promote` でヒープへ移す。
+    fn keep(&mut self, ty: &AbiType, value: Value) -> Result<Value> {
+        match value {
+            Value::I32(ptr) => Ok(Value::I32(self.promote(ty, ptr as u32, 0)? as i32)),
+            other => Ok(other),
+        }
+    }
+
+    /// `ptr` が指す値（とそこから指される値）のうち静的データにあるものを複製し、
+    /// 複製後のポインタを返す。既にヒープにあるものはその場で中身だけ直す。
+    fn promote(&mut self, ty: &AbiType, ptr: u32, depth: u32) -> Result<u32> {
+        if ptr == 0 || depth > MAX_DEPTH {
+            return Ok(ptr);
+        }
+        let (size, fields) = match ty {
+            AbiType::Str => {
+                if ptr >= REPL_HEAP_BASE {
+                    return Ok(ptr);
+                }
+                let len = self.read(ptr)?;
+                return self.copy(ptr, 4 + len);
+            }
+            AbiType::Tuple(items) => {
+                let mut fields = Vec::new();
+                let mut offset = 0;
+                for item in items {
+                    fields.push((item.clone(), offset));
+                    offset += item.size();
+                }
+                (offset, fields)
+            }
+            AbiType::Named(name) => match self.layouts.iter().find(|l| layout_name(l) == name) {
+                Some(AbiLayout::Struct { size, fields, .. }) => (
+                    *size,
+                    fields.iter().map(|f| (f.ty.clone(), f.offset)).collect(),
+                ),
+                Some(AbiLayout::Enum { variants, .. }) => {
+                    let tag = self.read(ptr)?;
+                    match variants
+                        .iter()
+                        .find(|v| v.tag == tag)
+                        .and_then(|v| v.payload.clone())
+                    {
+                        Some((payload, offset)) => {
+                            (offset + payload.size(), vec![(payload, offset)])
+                        }
+                        None => (4, Vec::new()),
+                    }
+                }
+                None => return Ok(ptr),
+            },
+            _ => return Ok(ptr),
+        };
+        let target = if ptr < REPL_HEAP_BASE {
+            self.copy(ptr, size)?
+        } else {
+            ptr
+        };
+        for (field, offset) in fields {
+            if matches!(field, AbiType::Str | AbiType::Named(_) | AbiType::Tuple(_)) {
+                let old = self.read(target + offset)?;
+                let new = self.promote(&field, old, depth + 1)?;
+                if new != old {
+                    self.write(target + offset, &new.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(target)
+    }
+
+    fn read(&self, at: u32) -> Result<u32> {
+        read_u32(self.memory.data(&*self.store), at)
+            .with_context(|| format!("value points outside memory ({at:#x})"))
+    }
+
+    fn copy(&mut self, ptr: u32, size: u32) -> Result<u32> {
+        let start = ptr as usize;
+        let bytes = self
+            .memory
+            .data(&*self.store)
+            .get(start..start + size as usize)
+            .with_context(|| format!("value points outside memory ({ptr:#x})"))?
+            .to_vec();
+        let new = self.alloc.call(&mut *self.store, size as i32)? as u32;
+        if new == 0 {
+            anyhow::bail!("failed to allocate {size} bytes on the REPL heap");
+        }
+        self.write(new, &bytes)?;
+        Ok(new)
+    }
+
+    fn write(&mut self, at: u32, bytes: &[u8]) -> Result<()> {
+        self.memory
+            .write(&mut *self.store, at as usize, bytes)
+            .map_err(|e| anyhow::anyhow!("failed to write memory at {at:#x}: {e}"))
+    }
+}
+
+fn layout_name(layout: &AbiLayout) -> &str {
+    match layout {
+        AbiLayout::Struct { name, .. } | AbiLayout::Enum { name, .. } => name,
+    }
+}
+
+fn read_u32(mem: &[u8], at: u32) -> Option<u32> {
+    let at = at as usize;
+    let bytes = mem.get(at..at.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn read_value(mem: &[u8], ty: &AbiType, at: u32) -> Option<Value> {
+    let start = at as usize;
+    let wide = || -> Option<[u8; 8]> { mem.get(start..start.checked_add(8)?)?.try_into().ok() };
+    Some(match ty {
+        AbiType::Unit => Value::Unit,
+        AbiType::I64 => Value::I64(i64::from_le_bytes(wide()?)),
+        AbiType::F64 => Value::F64(f64::from_le_bytes(wide()?)),
+        AbiType::F32 => Value::F32(f32::from_bits(read_u32(mem, at)?)),
+        _ => Value::I32(read_u32(mem, at)? as i32),
+    })
+}
+
+/// `ty` の値を NEPL の値らしく表示する。集成体は `mem` 上のレイアウトをたどる。
+fn show(mem: &[u8], layouts: &[AbiLayout], ty: &AbiType, value: Value, depth: u32) -> String {
+    let ptr = match value {
+        Value::Unit => return "()".to_string(),
+        Value::I64(v) => return v.to_string(),
+        Value::F32(v) => return format!("{v:?}"),
+        Value::F64(v) => return format!("{v:?}"),
+        Value::I32(v) => match ty {
+            AbiType::Bool => return (v != 0).to_string(),
+            AbiType::U8 => return (v as u8).to_string(),
+            AbiType::I32 => return v.to_string(),
+            _ => v as u32,
+        },
+    };
+    if depth > MAX_DEPTH {
+        return "..".to_string();
+    }
+    let invalid = || format!("<invalid pointer {ptr:#x}>");
+    let field = |fty: &AbiType, offset: u32| match read_value(mem, fty, ptr.wrapping_add(offset)) {
+        Some(v) => show(mem, layouts, fty, v, depth + 1),
+        None => invalid(),
+    };
+    match ty {
+        AbiType::Str => {
+            let Some(len) = read_u32(mem, ptr) else {
+                return invalid();
+            };
+            let start = ptr as usize + 4;
+            match mem.get(start..start + len as usize) {
+                Some(bytes) => format!("{:?}", String::from_utf8_lossy(bytes)),
+                None => invalid(),
+            }
+        }
+        AbiType::Tuple(items) => {
+            let mut offset = 0;
+            let mut parts = Vec::new();
+            for item in items {
+                parts.push(field(item, offset));
+                offset += item.size();
+            }
+            format!("({})", parts.join(", "))
+        }
+        AbiType::Named(name) => match layouts.iter().find(|l| layout_name(l) == name) {
+            Some(AbiLayout::Struct { fields, .. }) => {
+                let parts: Vec<String> = fields
+                    .iter()
+                    .map(|f| format!("{}: {}", f.name, field(&f.ty, f.offset)))
+                    .collect();
+                format!("{name} {{ {} }}", parts.join(", "))
+            }
+            Some(AbiLayout::Enum { variants, .. }) => {
+                let Some(tag) = read_u32(mem, ptr) else {
+                    return invalid();
+                };
+                match variants.iter().find(|v| v.tag == tag) {
+                    Some(v) => match &v.payload {
+                        Some((payload, offset)) => {
+                            format!("{}({})", v.name, field(payload, *offset))
+                        }
+                        None => v.name.clone(),
+                    },
+                    None => format!("<{name} with unknown tag {tag}>"),
+                }
+            }
+            None => format!("<{name} {ptr:#x}>"),
+        },
+        AbiType::Opaque(name) => format!("<{name} {ptr:#x}>"),
+        _ => ptr.to_string(),
+    }
+}
+
+/// WAT から `name` として export された関数を抜き出す。
+/// ポインタ ABI の thunk は本体を呼ぶだけなので、先頭で呼ぶ関数も続けて出す。
+fn export_wat(wasm: &[u8], name: &str) -> Result<String> {
+    let wat = make_wat_pretty(wasm)?;
+    let prefix = format!("(export \"{name}\" (func ");
+    let index: u32 = wat
+        .lines()
+        .find_map(|l| {
+            l.trim()
+                .strip_prefix(&prefix)?
+                .strip_suffix("))")?
+                .parse()
+                .ok()
+        })
+        .with_context(|| format!("{name} is not exported"))?;
+    let body = func_wat(&wat, index).with_context(|| format!("function {index} not found"))?;
+    let callee = body
+        .lines()
+        .skip(1)
+        .map(str::trim)
+        .find(|l| !l.starts_with("(local"))
+        .and_then(|l| l.strip_prefix("call "))
+        .and_then(|n| n.parse::<u32>().ok());
+    let mut out = body;
+    if let Some(callee) = callee.and_then(|i| func_wat(&wat, i)) {
+        out.push_str(&callee);
+    }
+    Ok(out)
+}
+
+fn func_wat(wat: &str, index: u32) -> Option<String> {
+    let head = format!("(func (;{index};)");
+    let mut lines = wat
+        .lines()
+        .skip_while(|l| !l.trim_start().starts_with(&head));
+    let mut out = String::new();
+    let mut depth = 0i32;
+    for line in lines.by_ref() {
+        out.push_str(line);
+        out.push('\n');
+        depth += line.matches('(').count() as i32 - line.matches(')').count() as i32;
+        if depth <= 0 {
+            return Some(out);
+        }
+    }
+    None
+}
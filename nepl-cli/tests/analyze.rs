@@ -0,0 +1,241 @@
+use std::fs;
+use std::process::Command;
+
+use anyhow::{ensure, Result};
+use tempfile::tempdir;
+
+#[test]
+fn analyze_json_matches_web_shape() -> Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("main.nepl");
+    fs::write(&path, "fn main <()->i32> ():\n    let x 1;\n    x\n")?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_nepl-cli"))
+        .args(["analyze", "--json"])
+        .arg(&path)
+        .output()?;
+    ensure!(output.status.success(), "analyze failed: {:?}", output);
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    ensure!(json["stage"] == "semantics");
+    ensure!(json["ok"] == true);
+    let refs = json["name_resolution"]["references"]
+        .as_array()
+        .expect("references");
+    let x_ref = refs.iter().find(|r| r["name"] == "x").expect("x reference");
+    ensure!(x_ref["resolved_def"]["kind"] == "let_hoisted");
+    ensure!(json["expressions"]
+        .as_array()
+        .expect("expressions")
+        .iter()
+        .any(|e| e["kind"] == "Var" && e["inferred_type"] == "i32"));
+    Ok(())
+}
+
+#[test]
+fn analyze_fails_on_type_errors() -> Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("bad.nepl");
+    fs::write(&path, "fn main <()->i32> ():\n    missing\n")?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_nepl-cli"))
+        .args(["analyze", "--stage", "semantics"])
+        .arg(&path)
+        .output()?;
+    ensure!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    ensure!(stdout.contains("error: undefined identifier 'missing'"), "{stdout}");
+    Ok(())
+}
+
+#[test]
+fn analyze_resolves_imports() -> Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("main.nepl");
+    fs::write(
+        &path,
+        "#import \"./util\" as *\n\nfn main <()->i32> ():\n    one\n",
+    )?;
+    fs::write(dir.path().join("util.nepl"), "fn one <()->i32> ():\n    1\n")?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_nepl-cli"))
+        .args(["analyze", "--json", "--stage", "resolve"])
+        .arg(&path)
+        .output()?;
+    ensure!(output.status.success(), "analyze failed: {:?}", output);
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let refs = json["references"].as_array().expect("references");
+    let one = refs.iter().find(|r| r["name"] == "one").expect("one");
+    let def_path = one["resolved_def"]["path"].as_str().expect("path");
+    ensure!(def_path.ends_with("util.nepl"), "{def_path}");
+
+    let single = Command::new(env!("CARGO_BIN_EXE_nepl-cli"))
+        .args(["analyze", "--single-file", "--stage", "resolve"])
+        .arg(&path)
+        .output()?;
+    let stdout = String::from_utf8(single.stdout)?;
+    ensure!(stdout.contains("ref one at 4:5 -> <unresolved>"), "{stdout}");
+    Ok(())
+}
+
+#[test]
+fn analyze_completes_imported_names() -> Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("main.nepl");
+    fs::write(
+        &path,
+        "#import \"core/math\" as *\n\nfn main <()->i32> ():\n    ad\n",
+    )?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_nepl-cli"))
+        .args(["analyze", "--stage", "complete", "--at", "4:7"])
+        .arg(&path)
+        .output()?;
+    ensure!(output.status.success(), "analyze failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout)?;
+    ensure!(stdout.contains("function add <(i32, i32)->i32>"), "{stdout}");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_nepl-cli"))
+        .args(["analyze", "--json", "--stage", "complete", "--at", "1:15"])
+        .arg(&path)
+        .output()?;
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    ensure!(json["context"] == "module_path");
+    ensure!(json["prefix"] == "core/");
+    ensure!(json["items"]
+        .as_array()
+        .expect("items")
+        .iter()
+        .any(|i| i["label"] == "core/math"));
+    Ok(())
+}
+
+#[test]
+fn analyze_hover_renders_docs_with_nm() -> Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("main.nepl");
+    fs::write(
+        &path,
+        "//: add3: 3 つの和\nfn add3 <(i32, i32, i32)->i32> (a, b, c):\n    a\n\nfn main <()->i32> ():\n    add3 1 2 3\n",
+    )?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_nepl-cli"))
+        .args(["analyze", "--stage", "hover", "--at", "6:12"])
+        .arg(&path)
+        .output()?;
+    ensure!(output.status.success(), "analyze failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout)?;
+    ensure!(
+        stdout.contains("call fn add3 <(i32, i32, i32)->i32> (a, b, c) -- argument 2 of 3"),
+        "{stdout}"
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_nepl-cli"))
+        .args(["analyze", "--json", "--stage", "hover", "--at", "6:6"])
+        .arg(&path)
+        .output()?;
+    ensure!(output.status.success(), "analyze failed: {:?}", output);
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    ensure!(json["stage"] == "hover");
+    let def = &json["definition"];
+    ensure!(def["signature"]["effect"] == "pure");
+    ensure!(def["documentation"]["markdown"] == "add3: 3 つの和");
+    let html = def["documentation"]["html"].as_str().unwrap_or_default();
+    ensure!(html.contains("<p>add3: 3 つの和"), "{html}");
+    Ok(())
+}
+
+#[test]
+fn explain_expr_parenthesizes_a_line() -> Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("main.nepl");
+    fs::write(
+        &path,
+        "#import \"core/math\" as *\n\nfn main <()->i32> ():\n    let x 4;\n    add 1 mul 2 sub x 3\n",
+    )?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_nepl-cli"))
+        .args(["explain-expr", "--line", "5"])
+        .arg(&path)
+        .output()?;
+    ensure!(output.status.success(), "explain-expr failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout)?;
+    ensure!(stdout == "(add 1 (mul 2 (sub x 3)))\n", "{stdout}");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_nepl-cli"))
+        .args(["analyze", "--stage", "inlay"])
+        .arg(&path)
+        .output()?;
+    ensure!(output.status.success(), "analyze failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout)?;
+    ensure!(stdout.contains("4:10 type <i32>"), "{stdout}");
+    ensure!(stdout.contains("5:11 paren ("), "{stdout}");
+    Ok(())
+}
+
+#[test]
+fn rename_writes_edits_across_imported_files() -> Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("main.nepl");
+    fs::write(
+        &path,
+        "#import \"./util\" as { one }\n\nfn main <()->i32> ():\n    one\n",
+    )?;
+    let util = dir.path().join("util.nepl");
+    fs::write(&util, "fn one <()->i32> ():\n    1\n\nfn two <()->i32> ():\n    one\n")?;
+
+    // 変更箇所の一覧だけを出す
+    let output = Command::new(env!("CARGO_BIN_EXE_nepl-cli"))
+        .args(["rename", "--at", "4:5", "--to", "uno"])
+        .arg(&path)
+        .output()?;
+    ensure!(output.status.success(), "rename failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout)?;
+    ensure!(stdout.lines().count() == 4, "{stdout}");
+    ensure!(stdout.contains("util.nepl:5:5: one -> uno"), "{stdout}");
+
+    // 衝突があれば書き込まずに失敗する
+    let output = Command::new(env!("CARGO_BIN_EXE_nepl-cli"))
+        .args(["rename", "--at", "4:5", "--to", "two", "--write"])
+        .arg(&path)
+        .output()?;
+    ensure!(!output.status.success());
+    ensure!(String::from_utf8(output.stderr)?.contains("conflict"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_nepl-cli"))
+        .args(["rename", "--at", "4:5", "--to", "uno", "--write"])
+        .arg(&path)
+        .output()?;
+    ensure!(output.status.success(), "rename failed: {:?}", output);
+    ensure!(fs::read_to_string(&path)?.contains("as { uno }\n\nfn main <()->i32> ():\n    uno\n"));
+    ensure!(fs::read_to_string(&util)?.matches("uno").count() == 2);
+    Ok(())
+}
+
+#[test]
+fn analyze_tokens_marks_stdlib_functions() -> Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("main.nepl");
+    fs::write(
+        &path,
+        "#import \"core/math\" as *\n\nfn main <()->i32> ():\n    let mut x 4;\n    add x 1\n",
+    )?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_nepl-cli"))
+        .args(["analyze", "--stage", "tokens"])
+        .arg(&path)
+        .output()?;
+    ensure!(output.status.success(), "analyze failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout)?;
+    ensure!(stdout.contains("4:13-14 variable declaration,mutable"), "{stdout}");
+    ensure!(stdout.contains("5:5-8 function default_library"), "{stdout}");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_nepl-cli"))
+        .args(["analyze", "--json", "--stage", "tokens"])
+        .arg(&path)
+        .output()?;
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    ensure!(json["stage"] == "semantic_tokens");
+    ensure!(json["data"].as_array().expect("data").len() % 5 == 0);
+    Ok(())
+}
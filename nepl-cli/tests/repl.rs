@@ -0,0 +1,161 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use anyhow::{ensure, Result};
+
+fn repl(args: &[&str], input: &str) -> Result<Output> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_nepl-cli"))
+        .arg("repl")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .expect("stdin")
+        .write_all(input.as_bytes())?;
+    Ok(child.wait_with_output()?)
+}
+
+#[test]
+fn repl_prints_values_with_types() -> Result<()> {
+    let input = "add 1 2
+:type mul 2 3
+struct P:
+    x <i32>
+    y <i32>
+
+fn sq <(i32)->i32> (x):
+    mul x x
+
+let n <i32> sq 6
+let p P n 4
+p.x
+p
+lt 1 2
+\"hi\"
+println \"side effect\"
+";
+    let output = repl(&[], input)?;
+    ensure!(output.status.success(), "repl failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout)?;
+    let expected = "3 : i32
+i32
+n : i32 = 36
+p : P = P { x: 36, y: 4 }
+36 : i32
+P { x: 36, y: 4 } : P
+true : bool
+\"hi\" : str
+side effect
+";
+    ensure!(stdout == expected, "{stdout}");
+    Ok(())
+}
+
+#[test]
+fn repl_keeps_heap_state_between_lines() -> Result<()> {
+    let input = "let v vec_push vec_new<str> \"first\"
+vec_set v 0 \"second\"
+vec_get v 0
+vec_len v
+:import core/option
+option_unwrap_or vec_get v 3 \"none\"
+";
+    let output = repl(&["--import", "alloc/vec"], input)?;
+    ensure!(output.status.success(), "repl failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout)?;
+    let lines: Vec<&str> = stdout.lines().collect();
+    ensure!(
+        lines[0].starts_with("v : Vec<str> = Vec<str> { len: 1"),
+        "{stdout}"
+    );
+    ensure!(
+        lines[1..]
+            == [
+                "Some(\"second\") : Option<str>",
+                "1 : i32",
+                "\"none\" : str"
+            ],
+        "{stdout}"
+    );
+    Ok(())
+}
+
+#[test]
+fn repl_keeps_static_mut_between_lines() -> Result<()> {
+    let input = "static mut COUNT <i32> 0
+fn bump <()*>i32> ():
+    set COUNT add COUNT 1
+    COUNT
+
+bump
+bump
+bump
+COUNT
+static mut NAME <str> \"first\"
+set NAME \"second\"
+let other \"third\"
+NAME
+";
+    let output = repl(&[], input)?;
+    ensure!(output.status.success(), "repl failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout)?;
+    let expected = "1 : i32
+2 : i32
+3 : i32
+3 : i32
+other : str = \"third\"
+\"second\" : str
+";
+    ensure!(stdout == expected, "{stdout}");
+    Ok(())
+}
+
+#[test]
+fn repl_continues_after_errors() -> Result<()> {
+    let input = "missing
+div_s 1 0
+let mut x 1
+let w add v 2
+fn broken <()->i32> ():
+    nope
+
+broken
+:wat add 1 2
+add 40 2
+";
+    let output = repl(&[], input)?;
+    ensure!(output.status.success(), "repl failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout)?;
+    let stderr = String::from_utf8(output.stderr)?;
+    for message in [
+        "undefined identifier 'missing'",
+        "integer divide by zero",
+        "`let mut` cannot be kept between lines",
+        "undefined identifier 'v'",
+        "undefined identifier 'nope'",
+        "undefined identifier 'broken'",
+        " --> <repl>:1:1\n     1 | missing\n",
+        " --> <repl>:2:5\n     2 |     nope\n",
+    ] {
+        ensure!(stderr.contains(message), "{message}: {stderr}");
+    }
+    ensure!(!stderr.contains("did you mean `w`?"), "{stderr}");
+    ensure!(stdout.contains("(func "), "{stdout}");
+    ensure!(stdout.contains("i32.const 1\n"), "{stdout}");
+    ensure!(stdout.ends_with("42 : i32\n"), "{stdout}");
+    Ok(())
+}
+
+#[test]
+fn repl_runs_simd() -> Result<()> {
+    let input = "i32x4_sum i32x4_replace_lane i32x4_splat 5 3 1\n";
+    let output = repl(&["--import", "core/simd"], input)?;
+    ensure!(output.status.success(), "repl failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout)?;
+    ensure!(stdout.ends_with("16 : i32\n"), "{stdout}");
+    Ok(())
+}
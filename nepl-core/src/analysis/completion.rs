@@ -0,0 +1,780 @@
+//! Completion candidates at a cursor position.
+//!
+//! What to complete is read from the text before the cursor: inside
+//! `#import "` / `#include "` stdlib module paths, after `#` directive names,
+//! after `recv.` struct fields, after `Enum::` and at the start of a `match`
+//! arm enum variants, and elsewhere the names in scope. The line under the
+//! cursor is usually incomplete, so the module is parsed (or loaded) with
+//! that line blanked out by [`blank_cursor_line`]; candidates then come from
+//! the AST and the [`names`](super::names) resolution of the rest of the
+//! file, without typechecking.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use serde::Serialize;
+
+use super::names::{
+    resolve_names, NameDefKind, NameDefinition, NameResolution, NameResolutionOptions,
+};
+use super::project::LoadedSources;
+use super::{LineIndex, SourceIndex, TextRange, INPUT_PATH};
+use crate::ast::{
+    Block, EnumDef, FnBody, FnDef, Module, PrefixExpr, PrefixItem, Stmt, StructDef, Symbol,
+    TypeExpr, TypeParam,
+};
+use crate::lexer::lex;
+use crate::parser::parse_tokens;
+use crate::span::{FileId, Span};
+
+/// What the text before the cursor asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompletionContext {
+    Identifier,
+    Field,
+    Variant,
+    Directive,
+    ModulePath,
+    /// Inside a comment, a string or a number.
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompletionKind {
+    Variable,
+    Function,
+    Field,
+    Variant,
+    Directive,
+    Module,
+}
+
+impl CompletionKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Variable => "variable",
+            Self::Function => "function",
+            Self::Field => "field",
+            Self::Variant => "variant",
+            Self::Directive => "directive",
+            Self::Module => "module",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CompletionItem {
+    pub label: String,
+    pub kind: CompletionKind,
+    /// Signature of a function, type of a variable or field, payload of a
+    /// variant, or usage of a directive.
+    pub detail: Option<String>,
+    /// First line of the `//:` doc comment.
+    pub documentation: Option<String>,
+    /// Defining file of names from other files.
+    pub path: Option<String>,
+}
+
+/// Result of [`complete`].
+#[derive(Debug, Clone, Serialize)]
+pub struct CompletionAnalysis {
+    pub stage: &'static str,
+    pub context: CompletionContext,
+    /// Text between `range.start` and the cursor; items start with it.
+    pub prefix: String,
+    /// Range an accepted item replaces.
+    pub range: TextRange,
+    pub items: Vec<CompletionItem>,
+}
+
+/// Directive names with their usage.
+const DIRECTIVES: &[(&str, &str)] = &[
+    ("entry", "#entry main"),
+    ("target", "#target core|std|wasm|wasi|llvm"),
+    ("import", "#import \"path\" as *"),
+    ("include", "#include \"path\""),
+    ("use", "#use path::*"),
+    ("prelude", "#prelude path"),
+    ("no_prelude", "#no_prelude"),
+    ("indent", "#indent 4"),
+    ("export", "#export [\"name\"]"),
+    ("extern", "#extern \"module\" \"name\" fn name <(T)->R>"),
+    ("intrinsic", "#intrinsic \"name\" <T> (args)"),
+    ("if[target=", "#if[target=wasm]"),
+    ("if[profile=", "#if[profile=debug]"),
+    ("if[const=", "#if[const=NAME]"),
+    ("wasm", "#wasm:"),
+    ("llvmir", "#llvmir:"),
+];
+
+/// `source` with the line containing `offset` replaced by a `_`
+/// placeholder at its indentation, so that a block whose only line is being
+/// typed still parses. Byte offsets of the other lines are unchanged.
+pub fn blank_cursor_line(source: &str, offset: u32) -> String {
+    let offset = char_floor(source, offset as usize);
+    let start = line_start(source, offset);
+    let end = source[offset..]
+        .find('\n')
+        .map_or(source.len(), |i| offset + i);
+    let line = &source[start..end];
+    let indent = indent_width(line);
+    let mut out = String::with_capacity(source.len());
+    out.push_str(&source[..start]);
+    out.push_str(&line[..indent]);
+    let mut rest = line.len() - indent;
+    if !line.trim().is_empty() && !line.trim_start().starts_with("//") {
+        out.push('_');
+        rest -= 1;
+    }
+    out.extend(core::iter::repeat_n(' ', rest));
+    out.push_str(&source[end..]);
+    out
+}
+
+/// Completions at byte `offset` of `source` (file 0). `modules` are the
+/// module paths offered inside `#import "..."`, for example the
+/// [`StdlibIndex::modules`](crate::suggest::StdlibIndex::modules).
+pub fn complete(source: &str, offset: u32, modules: &[String]) -> CompletionAnalysis {
+    let blanked = blank_cursor_line(source, offset);
+    let index = SourceIndex::single(INPUT_PATH, &blanked);
+    let module = parse_tokens(FileId(0), lex(FileId(0), &blanked)).module;
+    completion_of(source, offset, &index, module.as_ref(), modules)
+}
+
+/// Completions at byte `offset` of the entry file, with the names of its
+/// imports. `loaded` must come from loading
+/// `blank_cursor_line(source, offset)` as the entry.
+pub fn complete_loaded(
+    source: &str,
+    offset: u32,
+    loaded: &LoadedSources,
+    modules: &[String],
+) -> CompletionAnalysis {
+    let index = SourceIndex::from_source_map(&loaded.source_map);
+    completion_of(source, offset, &index, loaded.module.as_ref(), modules)
+}
+
+fn completion_of(
+    source: &str,
+    offset: u32,
+    index: &SourceIndex,
+    module: Option<&Module>,
+    modules: &[String],
+) -> CompletionAnalysis {
+    let offset = char_floor(source, offset as usize);
+    let (site, prefix_start) = cursor_site(source, offset);
+    let prefix = &source[prefix_start..offset];
+    let scope = ScopeView::new(index, module, source, offset as u32);
+
+    let (context, mut items) = match site {
+        Site::ModulePath => (
+            CompletionContext::ModulePath,
+            modules
+                .iter()
+                .map(|m| item(m, CompletionKind::Module, None))
+                .collect(),
+        ),
+        Site::Directive => (
+            CompletionContext::Directive,
+            DIRECTIVES
+                .iter()
+                .map(|(name, usage)| item(name, CompletionKind::Directive, Some(usage.to_string())))
+                .collect(),
+        ),
+        Site::Field(chain) => (CompletionContext::Field, scope.fields(&chain)),
+        Site::Path(qualifier) => (CompletionContext::Variant, scope.variants(&qualifier)),
+        Site::MatchArm(scrutinee) => (CompletionContext::Variant, scope.arm_patterns(scrutinee)),
+        Site::Ident => (CompletionContext::Identifier, scope.names()),
+        Site::None => (CompletionContext::None, Vec::new()),
+    };
+    items.retain(|i| i.label.starts_with(prefix));
+    let mut seen = Vec::<(String, Option<String>)>::new();
+    items.retain(|i| {
+        let key = (i.label.clone(), i.detail.clone());
+        let fresh = !seen.contains(&key);
+        if fresh {
+            seen.push(key);
+        }
+        fresh
+    });
+
+    CompletionAnalysis {
+        stage: "completion",
+        context,
+        prefix: prefix.into(),
+        range: LineIndex::new(source).range(Span::new(
+            FileId(0),
+            prefix_start as u32,
+            offset as u32,
+        )),
+        items,
+    }
+}
+
+fn item(label: &str, kind: CompletionKind, detail: Option<String>) -> CompletionItem {
+    CompletionItem {
+        label: label.into(),
+        kind,
+        detail,
+        documentation: None,
+        path: None,
+    }
+}
+
+enum Site {
+    ModulePath,
+    Directive,
+    /// `a.b.` with the receiver segments.
+    Field(Vec<String>),
+    /// `Name::` with the qualifier.
+    Path(String),
+    /// The pattern of a `match` arm, with the scrutinee if it is a name.
+    MatchArm(Option<Vec<String>>),
+    Ident,
+    None,
+}
+
+/// Classify the cursor and find where the text to replace starts.
+fn cursor_site(source: &str, offset: usize) -> (Site, usize) {
+    let start = line_start(source, offset);
+    let line = &source[start..offset];
+    let trimmed = line.trim_start();
+    let quotes = line.matches('"').count();
+
+    if trimmed.starts_with("#import") || trimmed.starts_with("#include") {
+        return match line.rfind('"') {
+            Some(q) if quotes % 2 == 1 => (Site::ModulePath, start + q + 1),
+            _ => (Site::None, offset),
+        };
+    }
+    if quotes % 2 == 1 || line.contains("//") {
+        return (Site::None, offset);
+    }
+    if let Some(rest) = trimmed.strip_prefix('#') {
+        if rest.contains(char::is_whitespace) {
+            return (Site::None, offset);
+        }
+        return (Site::Directive, offset - rest.len());
+    }
+
+    let word = line.len() - trailing_len(line, is_ident_byte);
+    let prefix_start = start + word;
+    if line.as_bytes().get(word).is_some_and(u8::is_ascii_digit) {
+        return (Site::None, offset);
+    }
+    let before = &line[..word];
+    if let Some(qualified) = before.strip_suffix("::") {
+        let q = &qualified[qualified.len() - trailing_len(qualified, is_ident_byte)..];
+        return (Site::Path(q.into()), prefix_start);
+    }
+    if let Some(receiver) = before.strip_suffix('.') {
+        return match trailing_chain(receiver) {
+            Some(chain) => (Site::Field(chain), prefix_start),
+            None => (Site::None, offset),
+        };
+    }
+    if before.trim().is_empty() {
+        if let Some(scrutinee) = enclosing_match(source, start, before.len()) {
+            return (Site::MatchArm(scrutinee), prefix_start);
+        }
+    }
+    (Site::Ident, prefix_start)
+}
+
+/// If the nearest less indented line above `line_start` opens a `match`,
+/// the scrutinee written there (when it is a plain name).
+fn enclosing_match(source: &str, line_start: usize, indent: usize) -> Option<Option<Vec<String>>> {
+    for line in source[..line_start].lines().rev() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with("//") {
+            continue;
+        }
+        if indent_width(line) >= indent {
+            continue;
+        }
+        let scrutinee = trimmed.strip_prefix("match ")?.strip_suffix(':')?;
+        return Some(name_chain(scrutinee.trim()));
+    }
+    None
+}
+
+/// `text` split at the dots if it is a name chain such as `a.b.c`.
+fn name_chain(text: &str) -> Option<Vec<String>> {
+    let segments: Vec<String> = text.split('.').map(String::from).collect();
+    let valid = segments.iter().all(|s| {
+        s.bytes()
+            .next()
+            .is_some_and(|b| b == b'_' || b.is_ascii_alphabetic())
+            && s.bytes().all(is_ident_byte)
+    });
+    valid.then_some(segments)
+}
+
+/// The name chain at the end of `text`, if any.
+fn trailing_chain(text: &str) -> Option<Vec<String>> {
+    name_chain(&text[text.len() - trailing_len(text, |b| is_ident_byte(b) || b == b'.')..])
+}
+
+/// Names, types and definitions visible at the cursor.
+struct ScopeView<'a, 'm> {
+    index: &'a SourceIndex<'a>,
+    items: ItemTable<'m>,
+    resolution: Option<NameResolution>,
+    source: &'a str,
+    offset: u32,
+}
+
+impl<'a, 'm> ScopeView<'a, 'm> {
+    fn new(
+        index: &'a SourceIndex<'a>,
+        module: Option<&'m Module>,
+        source: &'a str,
+        offset: u32,
+    ) -> Self {
+        let mut items = ItemTable::default();
+        let resolution = module.map(|m| {
+            items.block(&m.root);
+            resolve_names(m, index, FileId(0), NameResolutionOptions::default())
+        });
+        Self {
+            index,
+            items,
+            resolution,
+            source,
+            offset,
+        }
+    }
+
+    /// Definitions visible at the cursor, nearest first. Variables hidden
+    /// by a nearer one with the same name are left out.
+    fn visible(&self) -> Vec<&NameDefinition> {
+        let Some(res) = &self.resolution else {
+            return Vec::new();
+        };
+        let innermost = res
+            .scopes
+            .iter()
+            .filter(|s| scope_covers(self.source, s, self.offset))
+            .max_by_key(|s| (s.depth, s.span.start))
+            .map_or(0, |s| s.id);
+        let mut chain = alloc::vec![innermost];
+        while let Some(parent) = res.scopes[*chain.last().unwrap()].parent {
+            chain.push(parent);
+        }
+
+        let mut defs: Vec<(usize, &NameDefinition)> = res
+            .definitions
+            .iter()
+            .filter(|d| {
+                matches!(d.kind, NameDefKind::Fn | NameDefKind::LetHoisted)
+                    || d.span.end <= self.offset
+            })
+            .filter_map(|d| {
+                chain
+                    .iter()
+                    .position(|s| *s == d.scope_id)
+                    .map(|pos| (pos, d))
+            })
+            .collect();
+        defs.sort_by_key(|(pos, d)| (*pos, u32::MAX - d.span.start));
+
+        let mut out: Vec<&NameDefinition> = Vec::new();
+        for (_, d) in defs {
+            if d.kind.is_variable() && out.iter().any(|o| o.kind.is_variable() && o.name == d.name)
+            {
+                continue;
+            }
+            out.push(d);
+        }
+        out
+    }
+
+    fn names(&self) -> Vec<CompletionItem> {
+        let mut vars = Vec::new();
+        let mut fns = Vec::new();
+        for d in self.visible() {
+            let path = (d.span.file_id != 0).then(|| d.path.clone());
+            if d.kind.is_variable() {
+                let detail = self.items.binding(d).map(|ty| type_expr_text(&ty));
+                vars.push(CompletionItem {
+                    path,
+                    ..item(&d.name, CompletionKind::Variable, detail)
+                });
+                continue;
+            }
+            let def = self.items.fns.get(&(d.span.file_id, d.span.start));
+            fns.push(CompletionItem {
+                label: d.name.clone(),
+                kind: CompletionKind::Function,
+                detail: def.map(|f| fn_signature_text(f)),
+                documentation: def.and_then(|_| self.doc_summary(d)),
+                path,
+            });
+        }
+        fns.sort_by(|a, b| a.label.cmp(&b.label));
+        vars.extend(fns);
+        vars
+    }
+
+    fn doc_summary(&self, d: &NameDefinition) -> Option<String> {
+        let source = self.index.line_index(FileId(d.span.file_id))?.source();
+        let lines = doc_comment_lines(source, d.span.start_line as usize);
+        let first = lines.first()?.trim();
+        let summary = first
+            .strip_prefix(d.name.as_str())
+            .and_then(|s| s.strip_prefix(':'))
+            .unwrap_or(first)
+            .trim();
+        (!summary.is_empty()).then(|| summary.into())
+    }
+
+    /// Type of the receiver `chain` (`a.b` is `a`, then its field `b`).
+    fn chain_type(&self, chain: &[String]) -> Option<TypeExpr> {
+        let (root, fields) = chain.split_first()?;
+        let def = self
+            .visible()
+            .into_iter()
+            .find(|d| d.kind.is_variable() && d.name == *root)?;
+        let mut ty = self.items.binding(def)?;
+        for field in fields {
+            let s = self.items.struct_of(&ty)?;
+            ty = s.fields.iter().find(|(f, _)| f.name == *field)?.1.clone();
+        }
+        Some(ty)
+    }
+
+    fn fields(&self, chain: &[String]) -> Vec<CompletionItem> {
+        let Some(s) = self
+            .chain_type(chain)
+            .and_then(|ty| self.items.struct_of(&ty))
+        else {
+            return Vec::new();
+        };
+        s.fields
+            .iter()
+            .map(|(name, ty)| item(&name.name, CompletionKind::Field, Some(type_expr_text(ty))))
+            .collect()
+    }
+
+    fn variants(&self, qualifier: &str) -> Vec<CompletionItem> {
+        let ty = TypeExpr::Named(qualifier.into());
+        match self.items.enum_of(&ty) {
+            Some(e) => variant_items(e, false),
+            None => Vec::new(),
+        }
+    }
+
+    fn arm_patterns(&self, scrutinee: Option<Vec<String>>) -> Vec<CompletionItem> {
+        let known = scrutinee
+            .and_then(|chain| self.chain_type(&chain))
+            .and_then(|ty| self.items.enum_of(&ty));
+        match known {
+            Some(e) => variant_items(e, true),
+            None => self
+                .items
+                .enums
+                .values()
+                .flat_map(|e| variant_items(e, true))
+                .collect(),
+        }
+    }
+}
+
+fn variant_items(e: &EnumDef, qualified: bool) -> Vec<CompletionItem> {
+    e.variants
+        .iter()
+        .map(|v| {
+            let label = if qualified {
+                format!("{}::{}", e.name.name, v.name.name)
+            } else {
+                v.name.name.clone()
+            };
+            item(
+                &label,
+                CompletionKind::Variant,
+                v.payload.as_ref().map(type_expr_text),
+            )
+        })
+        .collect()
+}
+
+/// Does `scope` contain `offset`? Past its parsed end (typically a new line
+/// at the end of the block) the cursor still belongs to it while every line
+/// in between is indented deeper than the line that opened it.
+fn scope_covers(source: &str, scope: &super::names::ScopeInfo, offset: u32) -> bool {
+    let span = &scope.span;
+    if scope.parent.is_none() {
+        return true;
+    }
+    if span.file_id != 0 || span.start > offset {
+        return false;
+    }
+    if offset <= span.end {
+        return true;
+    }
+    let Some(between) = source.get(span.end as usize..offset as usize) else {
+        return false;
+    };
+    let opener = &source[line_start(source, span.start as usize)..];
+    let base = indent_width(opener);
+    let lines: Vec<&str> = between.split('\n').collect();
+    let Some((cursor_line, middle)) = lines[1..].split_last() else {
+        return true;
+    };
+    let deeper = |line: &str| line.trim().is_empty() || indent_width(line) > base;
+    let cursor_indent = if cursor_line.trim().is_empty() {
+        cursor_line.len()
+    } else {
+        indent_width(cursor_line)
+    };
+    middle.iter().all(|l| deeper(l)) && cursor_indent > base
+}
+
+/// Binding types known from the source, without typechecking.
+#[derive(Debug, Clone)]
+enum BindingType {
+    /// A parameter type or `let x <T> ...`.
+    Annotated(TypeExpr),
+    /// `let x Name ...`: a struct constructor or `Enum::Variant`.
+    Constructed(String),
+}
+
+/// Top-level items of the module and the annotated bindings.
+#[derive(Default)]
+pub(super) struct ItemTable<'m> {
+    /// By file and start of the name.
+    pub(super) fns: BTreeMap<(u32, u32), &'m FnDef>,
+    pub(super) structs: BTreeMap<&'m str, &'m StructDef>,
+    enums: BTreeMap<&'m str, &'m EnumDef>,
+    aliases: BTreeMap<&'m str, &'m TypeExpr>,
+    bindings: BTreeMap<(u32, u32), BindingType>,
+}
+
+impl<'m> ItemTable<'m> {
+    pub(super) fn block(&mut self, block: &'m Block) {
+        for stmt in &block.items {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &'m Stmt) {
+        match stmt {
+            Stmt::FnDef(def) => {
+                self.fns.insert(key(def.name.span), def);
+                if let TypeExpr::Function { params, .. } = &def.signature {
+                    if params.len() == def.params.len() {
+                        for (p, ty) in def.params.iter().zip(params) {
+                            self.bindings
+                                .insert(key(p.span), BindingType::Annotated(ty.clone()));
+                        }
+                    }
+                }
+                if let FnBody::Parsed(body) = &def.body {
+                    self.block(body);
+                }
+            }
+            Stmt::StructDef(s) => {
+                self.structs.entry(&s.name.name).or_insert(s);
+            }
+            Stmt::EnumDef(e) => {
+                self.enums.entry(&e.name.name).or_insert(e);
+            }
+            Stmt::TypeAlias(a) => {
+                self.aliases.entry(&a.name.name).or_insert(&a.target);
+            }
+            Stmt::Expr(expr) | Stmt::ExprSemi(expr, _) => self.expr(expr),
+            _ => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &'m PrefixExpr) {
+        if let [PrefixItem::Symbol(Symbol::Let { name, .. }), value, ..] = expr.items.as_slice() {
+            let ty = match value {
+                PrefixItem::TypeAnnotation(ty, _) => Some(BindingType::Annotated(ty.clone())),
+                PrefixItem::Symbol(Symbol::Ident(id, _, _)) => {
+                    let ty_name = id.name.split("::").next().unwrap_or_default();
+                    Some(BindingType::Constructed(ty_name.into()))
+                }
+                _ => None,
+            };
+            if let Some(ty) = ty {
+                self.bindings.insert(key(name.span), ty);
+            }
+        }
+        for item in &expr.items {
+            match item {
+                PrefixItem::Block(block, _) => self.block(block),
+                PrefixItem::Match(m, _) => {
+                    self.expr(&m.scrutinee);
+                    for arm in &m.arms {
+                        self.block(&arm.body);
+                    }
+                }
+                PrefixItem::For(f, _) => {
+                    self.expr(&f.iter);
+                    self.block(&f.body);
+                }
+                PrefixItem::Tuple(items, _) => {
+                    for e in items {
+                        self.expr(e);
+                    }
+                }
+                PrefixItem::Group(inner, _) => self.expr(inner),
+                _ => {}
+            }
+        }
+    }
+
+    fn binding(&self, d: &NameDefinition) -> Option<TypeExpr> {
+        match self.bindings.get(&(d.span.file_id, d.span.start))? {
+            BindingType::Annotated(ty) => Some(ty.clone()),
+            BindingType::Constructed(name) => {
+                let known = self.structs.contains_key(name.as_str())
+                    || self.enums.contains_key(name.as_str());
+                known.then(|| TypeExpr::Named(name.clone()))
+            }
+        }
+    }
+
+    /// Name of the struct or enum behind `ty`, through references, type
+    /// applications and aliases.
+    fn type_name<'t>(&'t self, mut ty: &'t TypeExpr) -> Option<&'t str> {
+        for _ in 0..16 {
+            match ty {
+                TypeExpr::Named(name) => match self.aliases.get(name.as_str()) {
+                    Some(target) => ty = target,
+                    None => return Some(name),
+                },
+                TypeExpr::Apply(base, _) | TypeExpr::Boxed(base) | TypeExpr::Reference(base, _) => {
+                    ty = base
+                }
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    fn struct_of(&self, ty: &TypeExpr) -> Option<&'m StructDef> {
+        self.structs.get(self.type_name(ty)?).copied()
+    }
+
+    fn enum_of(&self, ty: &TypeExpr) -> Option<&'m EnumDef> {
+        self.enums.get(self.type_name(ty)?).copied()
+    }
+}
+
+fn key(span: Span) -> (u32, u32) {
+    (span.file_id.0, span.start)
+}
+
+/// Lines of the `//:` doc comment above line `line` (0-based), without the
+/// marker. Directive lines (`#if[...]`, `#export`) between the comment and
+/// the definition are skipped.
+pub(super) fn doc_comment_lines(source: &str, line: usize) -> Vec<&str> {
+    let mut out = Vec::new();
+    for text in source
+        .lines()
+        .take(line)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+    {
+        let trimmed = text.trim_start();
+        if let Some(doc) = trimmed.strip_prefix("//:") {
+            out.push(doc.strip_prefix(' ').unwrap_or(doc));
+        } else if out.is_empty() && trimmed.starts_with('#') {
+            continue;
+        } else {
+            break;
+        }
+    }
+    out.reverse();
+    out
+}
+
+/// A function signature as written in source, e.g. `<.T> <(.T)->.T>`.
+pub(super) fn fn_signature_text(def: &FnDef) -> String {
+    let sig = format!("<{}>", type_expr_text(&def.signature));
+    if def.type_params.is_empty() {
+        return sig;
+    }
+    let params: Vec<String> = def.type_params.iter().map(type_param_text).collect();
+    format!("<{}> {}", params.join(", "), sig)
+}
+
+pub(super) fn type_param_text(p: &TypeParam) -> String {
+    let name = format!(".{}", p.name.name.trim_start_matches('.'));
+    if p.bounds.is_empty() {
+        name
+    } else {
+        format!("{}: {}", name, p.bounds.join(" + "))
+    }
+}
+
+/// A type expression in source syntax.
+pub(super) fn type_expr_text(ty: &TypeExpr) -> String {
+    match ty {
+        TypeExpr::Unit => "()".into(),
+        TypeExpr::I32 => "i32".into(),
+        TypeExpr::U8 => "u8".into(),
+        TypeExpr::F32 => "f32".into(),
+        TypeExpr::Bool => "bool".into(),
+        TypeExpr::Never => "never".into(),
+        TypeExpr::Str => "str".into(),
+        TypeExpr::Label(None) => ".".into(),
+        TypeExpr::Label(Some(l)) => format!(".{}", l),
+        TypeExpr::Named(n) => n.clone(),
+        TypeExpr::Apply(base, args) => format!("{}<{}>", type_expr_text(base), type_list(args)),
+        TypeExpr::Boxed(inner) => format!("Box<{}>", type_expr_text(inner)),
+        TypeExpr::Reference(inner, true) => format!("&mut {}", type_expr_text(inner)),
+        TypeExpr::Reference(inner, false) => format!("&{}", type_expr_text(inner)),
+        TypeExpr::Tuple(items) => format!("({})", type_list(items)),
+        TypeExpr::Function {
+            params,
+            result,
+            effect,
+        } => {
+            let arrow = match effect {
+                crate::ast::Effect::Pure => "->",
+                crate::ast::Effect::Impure => "*>",
+            };
+            format!("({}){}{}", type_list(params), arrow, type_expr_text(result))
+        }
+    }
+}
+
+fn type_list(items: &[TypeExpr]) -> String {
+    items
+        .iter()
+        .map(type_expr_text)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn is_ident_byte(b: u8) -> bool {
+    b == b'_' || b.is_ascii_alphanumeric()
+}
+
+/// Length of the longest suffix of `text` whose bytes satisfy `pred`.
+fn trailing_len(text: &str, pred: impl Fn(u8) -> bool) -> usize {
+    text.bytes().rev().take_while(|b| pred(*b)).count()
+}
+
+fn indent_width(line: &str) -> usize {
+    line.len() - line.trim_start_matches([' ', '\t']).len()
+}
+
+fn line_start(source: &str, offset: usize) -> usize {
+    source[..offset].rfind('\n').map_or(0, |i| i + 1)
+}
+
+fn char_floor(source: &str, offset: usize) -> usize {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
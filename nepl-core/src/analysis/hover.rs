@@ -0,0 +1,425 @@
+//! Hover and signature help at a cursor position.
+//!
+//! Both are read off the [`semantics`](super::semantics) of the file: the
+//! innermost typechecked expression under the cursor gives the inferred
+//! type, the name under the cursor gives the definition (for a call, the
+//! overload the typechecker picked), and the innermost call whose callee or
+//! arguments cover the cursor gives the signature help. Prefix calls have
+//! no parentheses or commas, so the active parameter is counted from the
+//! argument ranges of that call.
+//!
+//! Doc comments (`//:`) are returned as markdown. Rendering them to HTML
+//! takes the stdlib `nm` pipeline compiled to wasm, which this crate cannot
+//! run; front-ends compile [`DOC_RENDERER_SOURCE`] and fill the HTML in with
+//! [`HoverAnalysis::render_documentation`].
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::Serialize;
+
+use super::completion::{doc_comment_lines, type_expr_text, type_param_text, ItemTable};
+use super::names::{NameDefKind, NameDefinition};
+use super::project::LoadedSources;
+use super::semantics::{checked_semantics_of, innermost, CheckedSignature, ExprInfo};
+use super::{DiagnosticInfo, SourceIndex, TextRange, TokenInfo, INPUT_PATH};
+use crate::ast::{Effect, FnDef, Module, TypeExpr};
+use crate::diagnostic::Diagnostic;
+use crate::lexer::lex;
+use crate::parser::parse_tokens;
+use crate::span::{FileId, Span};
+
+/// NEPL program that renders markdown with `nm/parser` and `nm/html_gen`.
+///
+/// Compiled for `#target wasm`, it exports `memory`,
+/// `nm_alloc <(i32)*>i32>` and `nm_render <(str)*>str>`. A `str` is the
+/// address of a little-endian `i32` byte length followed by the UTF-8 bytes.
+pub const DOC_RENDERER_SOURCE: &str = "#target wasm
+#import \"alloc/string\" as *
+#import \"nm/parser\" as *
+#import \"nm/html_gen\" as *
+
+#export
+fn nm_alloc <(i32)*>i32> (size):
+    alloc size
+
+#export
+fn nm_render <(str)*>str> (markdown):
+    render_document parse_markdown markdown
+
+fn main <()->i32> ():
+    0
+";
+
+/// The typechecked expression under the cursor.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HoverExpression {
+    pub kind: &'static str,
+    pub range: TextRange,
+    pub inferred_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ParameterInfo {
+    pub name: String,
+    pub ty: String,
+    /// Char offsets of the name within [`SignatureInfo::label`].
+    pub label_offsets: [u32; 2],
+}
+
+/// A function signature. Types written as `.` holes in source are shown as
+/// the typechecker inferred them.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SignatureInfo {
+    /// The declaration header, e.g. `fn add <(i32, i32)->i32> (a, b)`.
+    pub label: String,
+    pub type_params: Vec<String>,
+    pub parameters: Vec<ParameterInfo>,
+    pub result: String,
+    /// `pure` or `impure`.
+    pub effect: &'static str,
+}
+
+/// A `//:` doc comment.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Documentation {
+    /// The comment with the `//:` markers removed.
+    pub markdown: String,
+    /// Filled in by [`HoverAnalysis::render_documentation`].
+    pub html: Option<String>,
+}
+
+/// The definition of the name under the cursor.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HoverDefinition {
+    pub name: String,
+    pub kind: NameDefKind,
+    pub path: String,
+    pub span: TextRange,
+    /// For functions.
+    pub signature: Option<SignatureInfo>,
+    pub documentation: Option<Documentation>,
+}
+
+/// The call the cursor is in.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SignatureHelp {
+    pub signature: SignatureInfo,
+    /// Index into `argument_ranges`; `None` on the callee itself.
+    pub active_parameter: Option<usize>,
+    /// From the callee to the end of the last argument.
+    pub call_range: TextRange,
+    pub argument_ranges: Vec<TextRange>,
+    /// Name of the called function.
+    pub definition: TextRange,
+    pub documentation: Option<Documentation>,
+}
+
+/// Result of [`hover`].
+#[derive(Debug, Clone, Serialize)]
+pub struct HoverAnalysis {
+    pub stage: &'static str,
+    pub ok: bool,
+    pub diagnostics: Vec<DiagnosticInfo>,
+    /// The token under the cursor.
+    pub range: Option<TextRange>,
+    pub expression: Option<HoverExpression>,
+    pub definition: Option<HoverDefinition>,
+    pub signature_help: Option<SignatureHelp>,
+}
+
+impl HoverAnalysis {
+    /// Set [`Documentation::html`] to `render(markdown)` where it is unset,
+    /// typically by calling `nm_render` of the compiled
+    /// [`DOC_RENDERER_SOURCE`].
+    pub fn render_documentation(&mut self, mut render: impl FnMut(&str) -> Option<String>) {
+        let docs = self
+            .definition
+            .iter_mut()
+            .filter_map(|d| d.documentation.as_mut())
+            .chain(
+                self.signature_help
+                    .iter_mut()
+                    .filter_map(|s| s.documentation.as_mut()),
+            );
+        for doc in docs {
+            if doc.html.is_none() {
+                doc.html = render(&doc.markdown);
+            }
+        }
+    }
+}
+
+/// Hover and signature help at byte `offset` of `source` (file 0).
+pub fn hover(source: &str, offset: u32) -> HoverAnalysis {
+    let index = SourceIndex::single(INPUT_PATH, source);
+    let lex_result = lex(FileId(0), source);
+    let tokens: Vec<TokenInfo> = lex_result
+        .tokens
+        .iter()
+        .map(|t| TokenInfo::new(&index, t))
+        .collect();
+    let parse_result = parse_tokens(FileId(0), lex_result);
+    hover_of(
+        &index,
+        tokens,
+        parse_result.module.as_ref(),
+        parse_result.diagnostics,
+        offset,
+    )
+}
+
+/// Hover and signature help at byte `offset` of the entry file, with
+/// definitions and doc comments from its imports.
+pub fn hover_loaded(loaded: &LoadedSources, offset: u32) -> HoverAnalysis {
+    let index = SourceIndex::from_source_map(&loaded.source_map);
+    let tokens = loaded.entry_tokens(&index);
+    let mut out = hover_of(
+        &index,
+        tokens,
+        loaded.module.as_ref(),
+        loaded.diagnostics.clone(),
+        offset,
+    );
+    out.ok &= loaded.merged;
+    out
+}
+
+fn hover_of(
+    index: &SourceIndex,
+    tokens: Vec<TokenInfo>,
+    module: Option<&Module>,
+    diagnostics: Vec<Diagnostic>,
+    offset: u32,
+) -> HoverAnalysis {
+    let (sem, signatures) = checked_semantics_of(index, FileId(0), tokens, module, diagnostics);
+    let mut items = ItemTable::default();
+    if let Some(m) = module {
+        items.block(&m.root);
+    }
+    let functions = Functions {
+        index,
+        items,
+        signatures,
+    };
+
+    let token = token_at(&sem.tokens, offset);
+    let target = match token {
+        Some(i) => sem.tokens[i].span,
+        None => index.range(Span::new(FileId(0), offset, offset)),
+    };
+    let expr = innermost(sem.expressions.iter().map(|e| (&e.span, e)), &target).map(|(_, e)| e);
+
+    let definitions = sem
+        .name_resolution
+        .as_ref()
+        .map_or(&[][..], |r| r.definitions.as_slice());
+    let by_span = |span: &TextRange| {
+        definitions
+            .iter()
+            .find(|d| d.span.file_id == span.file_id && d.span.start == span.start)
+    };
+    let definition = token.and_then(|i| {
+        let called = expr
+            .filter(|e| e.span == target)
+            .and_then(|e| e.callee.as_ref())
+            .and_then(by_span);
+        let referenced = sem
+            .token_resolution
+            .get(i)
+            .and_then(|r| r.resolved_def_id)
+            .and_then(|id| definitions.iter().find(|d| d.id == id));
+        let declared = || definitions.iter().find(|d| d.span.contains(&target));
+        called.or(referenced).or_else(declared)
+    });
+    let definition = definition.map(|d| functions.definition(d));
+    let expression = expr.map(|e| HoverExpression {
+        kind: e.kind,
+        range: e.span,
+        inferred_type: e.inferred_type.clone(),
+    });
+    let signature_help = signature_help(&sem.expressions, offset, &functions);
+
+    HoverAnalysis {
+        stage: "hover",
+        ok: sem.ok,
+        diagnostics: sem.diagnostics,
+        range: token.map(|_| target),
+        expression,
+        definition,
+        signature_help,
+    }
+}
+
+/// Index of the token under `offset`, or of the one ending there.
+fn token_at(tokens: &[TokenInfo], offset: u32) -> Option<usize> {
+    let candidates = || {
+        tokens.iter().enumerate().filter(|(_, t)| {
+            !matches!(t.kind, "Indent" | "Dedent" | "Newline" | "Eof") && t.span.file_id == 0
+        })
+    };
+    candidates()
+        .find(|(_, t)| t.span.start <= offset && offset < t.span.end)
+        .or_else(|| candidates().find(|(_, t)| t.span.end == offset))
+        .map(|(i, _)| i)
+}
+
+/// The innermost resolved call whose callee or arguments cover `offset`.
+fn signature_help(exprs: &[ExprInfo], offset: u32, functions: &Functions) -> Option<SignatureHelp> {
+    let mut best: Option<(TextRange, &ExprInfo, Vec<TextRange>)> = None;
+    for e in exprs {
+        if e.kind != "Call" || e.callee.is_none() {
+            continue;
+        }
+        // captured variables are passed at the callee's range
+        let args: Vec<TextRange> = e
+            .argument_ranges
+            .iter()
+            .filter(|r| **r != e.span)
+            .copied()
+            .collect();
+        let mut call_range = e.span;
+        if let Some(last) = args.last() {
+            call_range.end = last.end;
+            call_range.end_line = last.end_line;
+            call_range.end_col = last.end_col;
+        }
+        if !(call_range.start <= offset && offset <= call_range.end) {
+            continue;
+        }
+        if best
+            .as_ref()
+            .is_none_or(|(prev, _, _)| call_range.width() < prev.width())
+        {
+            best = Some((call_range, e, args));
+        }
+    }
+
+    let (call_range, call, args) = best?;
+    let callee = call.callee?;
+    let (signature, documentation) = functions.function(&callee)?;
+    let active_parameter = if offset <= call.span.end {
+        None
+    } else {
+        args.iter().position(|r| offset <= r.end)
+    };
+    Some(SignatureHelp {
+        signature,
+        active_parameter,
+        call_range,
+        argument_ranges: args,
+        definition: callee,
+        documentation,
+    })
+}
+
+/// Function definitions of the module with their typechecked signatures.
+struct Functions<'a, 'm> {
+    index: &'a SourceIndex<'a>,
+    items: ItemTable<'m>,
+    signatures: Vec<CheckedSignature>,
+}
+
+impl Functions<'_, '_> {
+    fn definition(&self, d: &NameDefinition) -> HoverDefinition {
+        let (signature, documentation) = match d.kind {
+            NameDefKind::Fn => match self.function(&d.span) {
+                Some((sig, doc)) => (Some(sig), doc),
+                None => (None, None),
+            },
+            NameDefKind::FnAlias => (None, self.documentation(&d.span)),
+            _ => (None, None),
+        };
+        HoverDefinition {
+            name: d.name.clone(),
+            kind: d.kind,
+            path: d.path.clone(),
+            span: d.span,
+            signature,
+            documentation,
+        }
+    }
+
+    /// Signature and doc comment of the function named at `name`.
+    fn function(&self, name: &TextRange) -> Option<(SignatureInfo, Option<Documentation>)> {
+        let def = self.items.fns.get(&(name.file_id, name.start))?;
+        let checked = self
+            .signatures
+            .iter()
+            .find(|s| s.span.file_id == name.file_id && s.span.start == name.start);
+        Some((signature_info(def, checked), self.documentation(name)))
+    }
+
+    fn documentation(&self, name: &TextRange) -> Option<Documentation> {
+        let source = self.index.line_index(FileId(name.file_id))?.source();
+        let markdown = doc_comment_lines(source, name.start_line as usize).join("\n");
+        (!markdown.trim().is_empty()).then_some(Documentation {
+            markdown,
+            html: None,
+        })
+    }
+}
+
+fn signature_info(def: &FnDef, checked: Option<&CheckedSignature>) -> SignatureInfo {
+    let (declared, declared_result, declared_effect) = match &def.signature {
+        TypeExpr::Function {
+            params,
+            result,
+            effect,
+        } => (params.as_slice(), Some(&**result), Some(*effect)),
+        _ => (&[][..], None, None),
+    };
+    // captured variables come first in the typechecked parameters
+    let checked_params = checked.map_or(&[][..], |c| {
+        &c.params[c.params.len().saturating_sub(def.params.len())..]
+    });
+    let shown = |declared: Option<&TypeExpr>, checked: Option<&String>| match declared {
+        Some(TypeExpr::Label(None)) | None => checked.cloned().unwrap_or_else(|| ".".into()),
+        Some(ty) => type_expr_text(ty),
+    };
+    let types: Vec<String> = (0..def.params.len())
+        .map(|i| shown(declared.get(i), checked_params.get(i)))
+        .collect();
+    let result = shown(declared_result, checked.map(|c| &c.result));
+    let effect = match (def.effect_inferred, declared_effect, checked) {
+        (true, _, Some(c)) | (false, None, Some(c)) => c.effect,
+        (_, Some(effect), _) => effect,
+        _ => Effect::Pure,
+    };
+    let type_params: Vec<String> = def.type_params.iter().map(type_param_text).collect();
+
+    let mut label = format!("fn {} ", def.name.name);
+    if !type_params.is_empty() {
+        label.push_str(&format!("<{}> ", type_params.join(", ")));
+    }
+    let arrow = match effect {
+        Effect::Pure => "->",
+        Effect::Impure => "*>",
+    };
+    label.push_str(&format!("<({}){}{}> (", types.join(", "), arrow, result));
+    let mut parameters = Vec::new();
+    for (i, (p, ty)) in def.params.iter().zip(types).enumerate() {
+        if i > 0 {
+            label.push_str(", ");
+        }
+        let start = label.chars().count() as u32;
+        label.push_str(&p.name);
+        parameters.push(ParameterInfo {
+            name: p.name.clone(),
+            ty,
+            label_offsets: [start, start + p.name.chars().count() as u32],
+        });
+    }
+    label.push(')');
+
+    SignatureInfo {
+        label,
+        type_params,
+        parameters,
+        result,
+        effect: match effect {
+            Effect::Pure => "pure",
+            Effect::Impure => "impure",
+        },
+    }
+}
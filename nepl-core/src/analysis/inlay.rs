@@ -0,0 +1,339 @@
+//! Inlay hints that make the grouping of prefix calls visible.
+//!
+//! The typechecker groups `add 1 mul 2 sub x 3` by the arity of each
+//! callee; the resulting HIR calls and their argument ranges (from
+//! [`semantics`](super::semantics)) are turned into hints: the parameter
+//! name before each argument, implicit parentheses around calls nested in
+//! other expressions, and the inferred type after a `let` name without an
+//! annotation. [`parenthesize_line`] applies the same grouping to one line
+//! of source text.
+//!
+//! Expressions the typechecker synthesizes (`for` loops, for example) carry
+//! the range of the construct they come from and are skipped: only calls
+//! whose callee is written in front of its arguments get hints.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::Serialize;
+
+use super::completion::ItemTable;
+use super::names::NameDefKind;
+use super::project::LoadedSources;
+use super::semantics::{checked_semantics_of, ExprInfo, SemanticAnalysis};
+use super::{DiagnosticInfo, SourceIndex, TextRange, TokenInfo, INPUT_PATH};
+use crate::ast::Module;
+use crate::diagnostic::Diagnostic;
+use crate::lexer::lex;
+use crate::parser::parse_tokens;
+use crate::span::{FileId, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InlayHintKind {
+    /// `a:` before an argument.
+    Parameter,
+    /// `(` or `)` around a nested call.
+    Paren,
+    /// `<i32>` after a `let` name.
+    Type,
+}
+
+impl InlayHintKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Parameter => "parameter",
+            Self::Paren => "paren",
+            Self::Type => "type",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InlayHint {
+    pub kind: InlayHintKind,
+    pub label: String,
+    /// Empty range where the label is shown.
+    pub position: TextRange,
+    /// The argument, call or binding the hint is about.
+    pub target: TextRange,
+    pub padding_left: bool,
+    pub padding_right: bool,
+}
+
+/// Result of [`inlay_hints`].
+#[derive(Debug, Clone, Serialize)]
+pub struct InlayHintAnalysis {
+    pub stage: &'static str,
+    pub ok: bool,
+    pub diagnostics: Vec<DiagnosticInfo>,
+    /// Sorted by position.
+    pub hints: Vec<InlayHint>,
+}
+
+/// Inlay hints for `source` (file 0).
+pub fn inlay_hints(source: &str) -> InlayHintAnalysis {
+    let index = SourceIndex::single(INPUT_PATH, source);
+    let lex_result = lex(FileId(0), source);
+    let tokens: Vec<TokenInfo> = lex_result
+        .tokens
+        .iter()
+        .map(|t| TokenInfo::new(&index, t))
+        .collect();
+    let parse_result = parse_tokens(FileId(0), lex_result);
+    hints_of(
+        &index,
+        tokens,
+        parse_result.module.as_ref(),
+        parse_result.diagnostics,
+    )
+}
+
+/// Inlay hints for the entry file, with parameter names from its imports.
+pub fn inlay_hints_loaded(loaded: &LoadedSources) -> InlayHintAnalysis {
+    let index = SourceIndex::from_source_map(&loaded.source_map);
+    let tokens = loaded.entry_tokens(&index);
+    let mut out = hints_of(
+        &index,
+        tokens,
+        loaded.module.as_ref(),
+        loaded.diagnostics.clone(),
+    );
+    out.ok &= loaded.merged;
+    out
+}
+
+/// Line `line` (0-based) of `source` with every call and constructor that
+/// lies on it wrapped in parentheses, e.g. `add 1 mul 2 3` becomes
+/// `(add 1 (mul 2 3))`. `semantics` must be the analysis of `source`.
+/// `None` when the line does not exist.
+pub fn parenthesize_line(semantics: &SemanticAnalysis, source: &str, line: u32) -> Option<String> {
+    let start = match line {
+        0 => 0,
+        _ => source.match_indices('\n').nth(line as usize - 1)?.0 + 1,
+    };
+    let end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |i| start + i);
+    let end = if source[..end].ends_with('\r') {
+        end - 1
+    } else {
+        end
+    };
+    let (start, end) = (start as u32, end as u32);
+
+    let tree = ExprTree::new(&semantics.expressions);
+    let on_line = |r: &Extent| start <= r.0 && r.1 <= end;
+    let roots: Vec<usize> = (0..tree.exprs.len())
+        .filter(|&id| {
+            on_line(&tree.extents[id])
+                && tree.exprs[id]
+                    .parent_id
+                    .is_none_or(|p| !on_line(&tree.extents[p]))
+        })
+        .collect();
+    Some(tree.splice(source, (start, end), &roots))
+}
+
+/// Byte range of an expression together with everything below it.
+type Extent = (u32, u32);
+
+/// Focus-file expressions with their children and extents.
+struct ExprTree<'a> {
+    exprs: &'a [ExprInfo],
+    children: Vec<Vec<usize>>,
+    extents: Vec<Extent>,
+}
+
+impl<'a> ExprTree<'a> {
+    fn new(exprs: &'a [ExprInfo]) -> Self {
+        let mut children = alloc::vec![Vec::new(); exprs.len()];
+        for e in exprs {
+            if let Some(p) = e.parent_id {
+                children[p].push(e.id);
+            }
+        }
+        let mut extents: Vec<Extent> = exprs.iter().map(|e| (e.span.start, e.span.end)).collect();
+        // children always come after their parent
+        for e in exprs.iter().rev() {
+            if let Some(p) = e.parent_id {
+                let (start, end) = extents[e.id];
+                extents[p] = (extents[p].0.min(start), extents[p].1.max(end));
+            }
+        }
+        Self {
+            exprs,
+            children,
+            extents,
+        }
+    }
+
+    /// Arguments of a call or constructor written after its head, without
+    /// captured variables. `None` for other expressions and for calls the
+    /// typechecker synthesized.
+    fn written_args(&self, id: usize) -> Option<Vec<&'a TextRange>> {
+        let e = &self.exprs[id];
+        if !matches!(
+            e.kind,
+            "Call" | "StructConstruct" | "EnumConstruct" | "TupleConstruct"
+        ) {
+            return None;
+        }
+        let args: Vec<&TextRange> = e.argument_ranges.iter().filter(|r| **r != e.span).collect();
+        let written = !args.is_empty() && args.iter().all(|r| r.start >= e.span.end);
+        written.then_some(args)
+    }
+
+    /// Whether `id` is an operand whose grouping is not obvious, that is not
+    /// the value of a binding or of `return`.
+    fn is_nested(&self, id: usize) -> bool {
+        self.exprs[id].parent_id.is_some_and(|p| {
+            !matches!(
+                self.exprs[p].kind,
+                "Let" | "Set" | "GlobalSet" | "Return" | "Block"
+            )
+        })
+    }
+
+    /// `source[range]` with the expressions `ids` (inside `range`) replaced
+    /// by their parenthesized form.
+    fn splice(&self, source: &str, range: Extent, ids: &[usize]) -> String {
+        let mut ids: Vec<usize> = ids.to_vec();
+        ids.sort_by_key(|&id| self.extents[id]);
+        let mut out = String::new();
+        let mut pos = range.0;
+        for id in ids {
+            let (start, end) = self.extents[id];
+            if start < pos || end > range.1 {
+                continue;
+            }
+            out.push_str(&source[pos as usize..start as usize]);
+            out.push_str(&self.render(source, id));
+            pos = end;
+        }
+        out.push_str(&source[pos as usize..range.1 as usize]);
+        out
+    }
+
+    fn render(&self, source: &str, id: usize) -> String {
+        let inner = self.splice(source, self.extents[id], &self.children[id]);
+        if self.written_args(id).is_some() {
+            format!("({})", inner)
+        } else {
+            inner
+        }
+    }
+}
+
+fn hints_of(
+    index: &SourceIndex,
+    tokens: Vec<TokenInfo>,
+    module: Option<&Module>,
+    diagnostics: Vec<Diagnostic>,
+) -> InlayHintAnalysis {
+    let (sem, _) = checked_semantics_of(index, FileId(0), tokens, module, diagnostics);
+    let mut items = ItemTable::default();
+    if let Some(m) = module {
+        items.block(&m.root);
+    }
+    let source = index.line_index(FileId(0)).map_or("", |l| l.source());
+    let point = |offset: u32| index.range(Span::new(FileId(0), offset, offset));
+    let text = |r: &TextRange| source.get(r.start as usize..r.end as usize).unwrap_or("");
+
+    let tree = ExprTree::new(&sem.expressions);
+    let mut hints = Vec::new();
+    for e in &sem.expressions {
+        let Some(args) = tree.written_args(e.id) else {
+            continue;
+        };
+        if tree.is_nested(e.id) {
+            let (start, end) = tree.extents[e.id];
+            let target = index.range(Span::new(FileId(0), start, end));
+            hints.push(hint(InlayHintKind::Paren, "(", point(start), target));
+            hints.push(hint(InlayHintKind::Paren, ")", point(end), target));
+        }
+        let names: Vec<&str> = match e.kind {
+            "Call" => e
+                .callee
+                .and_then(|c| items.fns.get(&(c.file_id, c.start)))
+                .map(|def| def.params.iter().map(|p| p.name.as_str()).collect())
+                .unwrap_or_default(),
+            "StructConstruct" => items
+                .structs
+                .get(text(&e.span))
+                .map(|s| s.fields.iter().map(|(f, _)| f.name.as_str()).collect())
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        if names.len() != args.len() {
+            continue;
+        }
+        for (name, arg) in names.into_iter().zip(args) {
+            if name.starts_with('_') || text(arg) == name {
+                continue;
+            }
+            hints.push(InlayHint {
+                padding_right: true,
+                ..hint(
+                    InlayHintKind::Parameter,
+                    &format!("{}:", name),
+                    point(arg.start),
+                    *arg,
+                )
+            });
+        }
+    }
+    if let Some(resolution) = &sem.name_resolution {
+        let lets = resolution.definitions.iter().filter(|d| {
+            d.span.file_id == 0 && matches!(d.kind, NameDefKind::LetHoisted | NameDefKind::LetMut)
+        });
+        for d in lets {
+            let annotated = source[d.span.end as usize..].trim_start().starts_with('<');
+            let binding = sem
+                .expressions
+                .iter()
+                .filter(|e| e.kind == "Let" && e.span.contains(&d.span))
+                .min_by_key(|e| e.span.width());
+            let value = binding.and_then(|b| tree.children[b.id].first());
+            if let (false, Some(&value)) = (annotated, value) {
+                hints.push(InlayHint {
+                    padding_left: true,
+                    ..hint(
+                        InlayHintKind::Type,
+                        &format!("<{}>", sem.expressions[value].inferred_type),
+                        point(d.span.end),
+                        d.span,
+                    )
+                });
+            }
+        }
+    }
+    // at one offset: `)` of the previous call, the parameter name, then `(`
+    hints.sort_by_key(|h| {
+        let rank = match (h.kind, h.label.as_str()) {
+            (InlayHintKind::Paren, ")") => 0,
+            (InlayHintKind::Type, _) => 1,
+            (InlayHintKind::Parameter, _) => 2,
+            (InlayHintKind::Paren, _) => 3,
+        };
+        (h.position.start, rank)
+    });
+
+    InlayHintAnalysis {
+        stage: "inlay_hints",
+        ok: sem.ok,
+        diagnostics: sem.diagnostics,
+        hints,
+    }
+}
+
+fn hint(kind: InlayHintKind, label: &str, position: TextRange, target: TextRange) -> InlayHint {
+    InlayHint {
+        kind,
+        label: label.into(),
+        position,
+        target,
+        padding_left: false,
+        padding_right: false,
+    }
+}
@@ -0,0 +1,430 @@
+//! Editor-facing analysis shared by `nepl-web`, `nepl-cli analyze` and
+//! other native tools.
+//!
+//! Every result type here is plain data that derives `serde::Serialize`, so
+//! front-ends only have to pick a serializer (`serde_json` in the CLI,
+//! `serde-wasm-bindgen` in the web crate). Ranges carry both byte offsets and
+//! 0-based line / column (in chars) so that consumers need not keep the
+//! source around.
+//!
+//! - [`analyze_lex`]: tokens and lexer diagnostics
+//! - [`analyze_name_resolution`]: definitions, references, scopes and shadowing ([`names`])
+//! - [`analyze_semantics`]: the above plus typechecked expressions ([`semantics`])
+//! - [`complete`]: completion candidates at a cursor ([`completion`])
+//! - [`hover`]: type, definition, doc comment and signature help at a cursor ([`hover`](mod@hover))
+//! - [`inlay_hints`]: parameter names, implicit parentheses and `let` types ([`inlay`])
+//! - [`find_references`] and [`rename`](rename()): every use of a name and the edits that rename it ([`rename`](mod@rename))
+//! - [`semantic_tokens`]: identifiers classified for highlighting, with range and delta updates ([`tokens`])
+//!
+//! These work on a single source string (file 0). The `*_loaded` variants in
+//! [`project`] take the output of the [`Loader`](crate::loader::Loader)
+//! instead, so that names brought in by `#import` resolve to their
+//! definitions in other files.
+
+pub mod completion;
+pub mod hover;
+pub mod inlay;
+pub mod names;
+pub mod project;
+pub mod rename;
+pub mod semantics;
+pub mod tokens;
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use serde::Serialize;
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::lexer::{lex, Token, TokenKind};
+use crate::loader::SourceMap;
+use crate::span::{FileId, Span};
+
+pub use completion::{complete, complete_loaded, CompletionAnalysis};
+pub use hover::{hover, hover_loaded, HoverAnalysis};
+pub use inlay::{inlay_hints, inlay_hints_loaded, parenthesize_line, InlayHintAnalysis};
+pub use names::{analyze_name_resolution, NameResolution, NameResolutionAnalysis, NameResolutionOptions};
+pub use project::{analyze_loaded_name_resolution, analyze_loaded_semantics, LoadedSources};
+pub use rename::{
+    find_references, find_references_loaded, rename, rename_loaded, ReferencesAnalysis,
+    RenameAnalysis,
+};
+pub use semantics::{analyze_semantics, SemanticAnalysis};
+pub use tokens::{
+    semantic_tokens, semantic_tokens_delta, semantic_tokens_loaded, SemanticTokensAnalysis,
+};
+
+/// Path given to the source of the single-file analyses.
+pub const INPUT_PATH: &str = "<input>";
+
+/// Byte range of a span together with its line / column position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TextRange {
+    pub file_id: u32,
+    pub start: u32,
+    pub end: u32,
+    pub start_line: u32,
+    pub start_col: u32,
+    pub end_line: u32,
+    pub end_col: u32,
+}
+
+impl TextRange {
+    /// Whether `inner` lies within `self` (same file, inclusive bounds).
+    pub fn contains(&self, inner: &TextRange) -> bool {
+        self.file_id == inner.file_id && self.start <= inner.start && inner.end <= self.end
+    }
+
+    pub fn width(&self) -> u32 {
+        self.end.saturating_sub(self.start)
+    }
+}
+
+/// Line start table for one source text.
+#[derive(Debug, Clone)]
+pub struct LineIndex<'a> {
+    source: &'a str,
+    line_starts: Vec<u32>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let mut line_starts = alloc::vec![0];
+        for (i, b) in source.bytes().enumerate() {
+            if b == b'\n' {
+                line_starts.push(i as u32 + 1);
+            }
+        }
+        Self {
+            source,
+            line_starts,
+        }
+    }
+
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    /// 0-based line and column (in chars) of a byte offset. An offset inside
+    /// a multi-byte char points at that char.
+    pub fn line_col(&self, pos: u32) -> (u32, u32) {
+        let pos = pos.min(self.source.len() as u32);
+        let line = match self.line_starts.binary_search(&pos) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        let start = self.line_starts[line] as usize;
+        let col = self.source[start..]
+            .char_indices()
+            .take_while(|(i, c)| start + i + c.len_utf8() <= pos as usize)
+            .count();
+        (line as u32, col as u32)
+    }
+
+    pub fn range(&self, span: Span) -> TextRange {
+        let (start_line, start_col) = self.line_col(span.start);
+        let (end_line, end_col) = self.line_col(span.end);
+        TextRange {
+            file_id: span.file_id.0,
+            start: span.start,
+            end: span.end,
+            start_line,
+            start_col,
+            end_line,
+            end_col,
+        }
+    }
+
+    /// Range covering the whole source of `file_id`.
+    pub fn full_range(&self, file_id: FileId) -> TextRange {
+        self.range(Span::new(file_id, 0, self.source.len() as u32))
+    }
+}
+
+/// A source file taking part in an analysis.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileInfo {
+    pub file_id: u32,
+    pub path: String,
+}
+
+/// Line indexes of every file of an analysis, by [`FileId`].
+#[derive(Debug, Clone)]
+pub struct SourceIndex<'a> {
+    files: Vec<(String, LineIndex<'a>)>,
+}
+
+impl<'a> SourceIndex<'a> {
+    /// A single file with id 0.
+    pub fn single(path: &str, source: &'a str) -> Self {
+        Self {
+            files: alloc::vec![(path.to_string(), LineIndex::new(source))],
+        }
+    }
+
+    pub fn from_source_map(source_map: &'a SourceMap) -> Self {
+        let mut files = Vec::new();
+        let mut id = 0;
+        while let (Some(path), Some(source)) =
+            (source_map.path(FileId(id)), source_map.get(FileId(id)))
+        {
+            files.push((path.display().to_string(), LineIndex::new(source)));
+            id += 1;
+        }
+        Self { files }
+    }
+
+    pub fn path(&self, file_id: FileId) -> Option<&str> {
+        self.files.get(file_id.0 as usize).map(|(p, _)| p.as_str())
+    }
+
+    pub fn line_index(&self, file_id: FileId) -> Option<&LineIndex<'a>> {
+        self.files.get(file_id.0 as usize).map(|(_, l)| l)
+    }
+
+    /// Range of `span` in its own file. Spans of unknown files keep their
+    /// byte offsets with zero line / column.
+    pub fn range(&self, span: Span) -> TextRange {
+        match self.line_index(span.file_id) {
+            Some(index) => index.range(span),
+            None => TextRange {
+                file_id: span.file_id.0,
+                start: span.start,
+                end: span.end,
+                start_line: 0,
+                start_col: 0,
+                end_line: 0,
+                end_col: 0,
+            },
+        }
+    }
+
+    pub fn full_range(&self, file_id: FileId) -> TextRange {
+        match self.line_index(file_id) {
+            Some(index) => index.full_range(file_id),
+            None => self.range(Span::new(file_id, 0, 0)),
+        }
+    }
+
+    pub fn files(&self) -> Vec<FileInfo> {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(id, (path, _))| FileInfo {
+                file_id: id as u32,
+                path: path.clone(),
+            })
+            .collect()
+    }
+}
+
+/// One lexer token.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TokenInfo {
+    pub kind: &'static str,
+    pub debug: String,
+    pub span: TextRange,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+impl TokenInfo {
+    pub fn new(index: &SourceIndex, token: &Token) -> Self {
+        Self {
+            kind: token_kind_name(&token.kind),
+            debug: format!("{:?}", token.kind),
+            span: index.range(token.span),
+            value: token_extra(&token.kind),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LabelInfo {
+    pub span: TextRange,
+    pub message: Option<String>,
+}
+
+/// A [`Diagnostic`] with resolved ranges.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiagnosticInfo {
+    pub severity: &'static str,
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub primary: TextRange,
+    pub secondary: Vec<LabelInfo>,
+    pub notes: Vec<String>,
+}
+
+impl DiagnosticInfo {
+    pub fn new(index: &SourceIndex, d: &Diagnostic) -> Self {
+        Self {
+            severity: match d.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            },
+            code: d.code,
+            message: d.message.clone(),
+            primary: index.range(d.primary.span),
+            secondary: d
+                .secondary
+                .iter()
+                .map(|l| LabelInfo {
+                    span: index.range(l.span),
+                    message: l.message.clone(),
+                })
+                .collect(),
+            notes: d.notes.clone(),
+        }
+    }
+
+    pub fn from_all(index: &SourceIndex, diagnostics: &[Diagnostic]) -> Vec<Self> {
+        diagnostics.iter().map(|d| Self::new(index, d)).collect()
+    }
+}
+
+fn has_error(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|d| d.severity == Severity::Error)
+}
+
+/// Result of [`analyze_lex`].
+#[derive(Debug, Clone, Serialize)]
+pub struct LexAnalysis {
+    pub stage: &'static str,
+    pub ok: bool,
+    pub tokens: Vec<TokenInfo>,
+    pub indent_width: usize,
+    pub diagnostics: Vec<DiagnosticInfo>,
+}
+
+/// Lex `source` as file 0.
+pub fn analyze_lex(source: &str) -> LexAnalysis {
+    let index = SourceIndex::single(INPUT_PATH, source);
+    let lex_result = lex(FileId(0), source);
+    LexAnalysis {
+        stage: "lex",
+        ok: !has_error(&lex_result.diagnostics),
+        tokens: lex_result
+            .tokens
+            .iter()
+            .map(|t| TokenInfo::new(&index, t))
+            .collect(),
+        indent_width: lex_result.indent_width,
+        diagnostics: DiagnosticInfo::from_all(&index, &lex_result.diagnostics),
+    }
+}
+
+/// Variant name of a token kind, without its payload.
+pub fn token_kind_name(kind: &TokenKind) -> &'static str {
+    match kind {
+        TokenKind::Indent => "Indent",
+        TokenKind::Dedent => "Dedent",
+        TokenKind::Newline => "Newline",
+        TokenKind::Eof => "Eof",
+        TokenKind::Colon => "Colon",
+        TokenKind::Semicolon => "Semicolon",
+        TokenKind::Pipe => "Pipe",
+        TokenKind::LParen => "LParen",
+        TokenKind::RParen => "RParen",
+        TokenKind::Comma => "Comma",
+        TokenKind::LAngle => "LAngle",
+        TokenKind::RAngle => "RAngle",
+        TokenKind::Arrow(_) => "Arrow",
+        TokenKind::PathSep => "PathSep",
+        TokenKind::At => "At",
+        TokenKind::Dot => "Dot",
+        TokenKind::Ampersand => "Ampersand",
+        TokenKind::Question => "Question",
+        TokenKind::Star => "Star",
+        TokenKind::Minus => "Minus",
+        TokenKind::Equals => "Equals",
+        TokenKind::Ident(_) => "Ident",
+        TokenKind::IntLiteral(_) => "IntLiteral",
+        TokenKind::FloatLiteral(_) => "FloatLiteral",
+        TokenKind::BoolLiteral(_) => "BoolLiteral",
+        TokenKind::StringLiteral(_) => "StringLiteral",
+        TokenKind::UnitLiteral => "UnitLiteral",
+        TokenKind::KwFn => "KwFn",
+        TokenKind::KwLet => "KwLet",
+        TokenKind::KwMut => "KwMut",
+        TokenKind::KwNoShadow => "KwNoShadow",
+        TokenKind::KwSet => "KwSet",
+        TokenKind::KwIf => "KwIf",
+        TokenKind::KwWhile => "KwWhile",
+        TokenKind::KwReturn => "KwReturn",
+        TokenKind::KwBreak => "KwBreak",
+        TokenKind::KwContinue => "KwContinue",
+        TokenKind::KwCond => "KwCond",
+        TokenKind::KwThen => "KwThen",
+        TokenKind::KwElse => "KwElse",
+        TokenKind::KwDo => "KwDo",
+        TokenKind::KwStruct => "KwStruct",
+        TokenKind::KwEnum => "KwEnum",
+        TokenKind::KwMatch => "KwMatch",
+        TokenKind::KwTrait => "KwTrait",
+        TokenKind::KwImpl => "KwImpl",
+        TokenKind::KwFor => "KwFor",
+        TokenKind::KwPub => "KwPub",
+        TokenKind::KwBlock => "KwBlock",
+        TokenKind::KwTuple => "KwTuple",
+        TokenKind::KwMlstr => "KwMlstr",
+        TokenKind::KwConst => "KwConst",
+        TokenKind::KwStatic => "KwStatic",
+        TokenKind::KwType => "KwType",
+        TokenKind::KwNewtype => "KwNewtype",
+        TokenKind::DirEntry(_) => "DirEntry",
+        TokenKind::DirTarget(_) => "DirTarget",
+        TokenKind::DirImport(_) => "DirImport",
+        TokenKind::DirUse(_) => "DirUse",
+        TokenKind::DirIfTarget(_) => "DirIfTarget",
+        TokenKind::DirIfProfile(_) => "DirIfProfile",
+        TokenKind::DirIfConst(_) => "DirIfConst",
+        TokenKind::DirWasm => "DirWasm",
+        TokenKind::DirLlvmIr => "DirLlvmIr",
+        TokenKind::DirIndentWidth(_) => "DirIndentWidth",
+        TokenKind::DirInclude(_) => "DirInclude",
+        TokenKind::DirExport(_) => "DirExport",
+        TokenKind::DirExtern { .. } => "DirExtern",
+        TokenKind::DirIntrinsic => "DirIntrinsic",
+        TokenKind::DirPrelude(_) => "DirPrelude",
+        TokenKind::DirNoPrelude => "DirNoPrelude",
+        TokenKind::WasmText(_) => "WasmText",
+        TokenKind::LlvmIrText(_) => "LlvmIrText",
+        TokenKind::MlstrLine(_) => "MlstrLine",
+    }
+}
+
+/// Payload of a token kind rendered as text, if it has one.
+pub fn token_extra(kind: &TokenKind) -> Option<String> {
+    match kind {
+        TokenKind::Arrow(e) => Some(format!("{:?}", e)),
+        TokenKind::Ident(v)
+        | TokenKind::IntLiteral(v)
+        | TokenKind::FloatLiteral(v)
+        | TokenKind::StringLiteral(v)
+        | TokenKind::DirEntry(v)
+        | TokenKind::DirTarget(v)
+        | TokenKind::DirImport(v)
+        | TokenKind::DirUse(v)
+        | TokenKind::DirIfTarget(v)
+        | TokenKind::DirIfProfile(v)
+        | TokenKind::DirIfConst(v)
+        | TokenKind::DirInclude(v)
+        | TokenKind::DirExport(v)
+        | TokenKind::DirPrelude(v)
+        | TokenKind::WasmText(v)
+        | TokenKind::LlvmIrText(v)
+        | TokenKind::MlstrLine(v) => Some(v.clone()),
+        TokenKind::BoolLiteral(v) => Some(v.to_string()),
+        TokenKind::DirIndentWidth(v) => Some(v.to_string()),
+        TokenKind::DirExtern {
+            module,
+            name,
+            func,
+            signature,
+        } => Some(format!(
+            "module={module}, name={name}, func={func}, signature={signature}"
+        )),
+        _ => None,
+    }
+}
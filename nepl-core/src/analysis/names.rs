@@ -0,0 +1,601 @@
+//! Lexical name resolution over the AST.
+//!
+//! This mirrors the scoping rules of the typechecker closely enough for
+//! editors: `fn` and non-`mut` `let` are hoisted to the top of their block,
+//! `let mut`, parameters, match bindings and `for` variables are defined
+//! where they appear, and a reference picks the nearest definition.
+//! Overloads are not resolved here; every same-named definition in scope is
+//! kept as a candidate.
+//!
+//! For a module merged by the loader, the items of imported files are
+//! hoisted like local ones but only the bodies of the focus file are walked,
+//! so references and scopes are reported for that file alone.
+//! [`resolve_workspace_names`] walks every file instead, for refactorings
+//! that have to see all uses of a name.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::Serialize;
+
+use super::{has_error, DiagnosticInfo, FileInfo, SourceIndex, TextRange, INPUT_PATH};
+use crate::ast::{Block, FnBody, Ident, MatchArm, Module, PrefixExpr, PrefixItem, Stmt, Symbol};
+use crate::lexer::lex;
+use crate::parser::parse_tokens;
+use crate::span::{FileId, Span};
+
+/// What introduced a name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NameDefKind {
+    Fn,
+    FnAlias,
+    LetHoisted,
+    LetMut,
+    Param,
+    MatchBind,
+    ForVar,
+}
+
+impl NameDefKind {
+    /// Value bindings, as opposed to functions.
+    pub fn is_variable(self) -> bool {
+        matches!(
+            self,
+            Self::LetHoisted | Self::LetMut | Self::Param | Self::MatchBind | Self::ForVar
+        )
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Fn => "fn",
+            Self::FnAlias => "fn_alias",
+            Self::LetHoisted => "let_hoisted",
+            Self::LetMut => "let_mut",
+            Self::Param => "param",
+            Self::MatchBind => "match_bind",
+            Self::ForVar => "for_var",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NameDefinition {
+    pub id: usize,
+    pub name: String,
+    pub kind: NameDefKind,
+    pub scope_id: usize,
+    pub scope_depth: usize,
+    pub span: TextRange,
+    /// Path of the defining file, for jumps into imported modules.
+    pub path: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NameReference {
+    pub name: String,
+    pub scope_id: usize,
+    pub scope_depth: usize,
+    pub span: TextRange,
+    pub resolved_def_id: Option<usize>,
+    /// Every visible definition with this name, nearest first.
+    pub candidate_def_ids: Vec<usize>,
+    pub resolved_def: Option<NameDefinition>,
+    pub candidate_definitions: Vec<NameDefinition>,
+}
+
+/// A lexical scope: the module root, a function, a block, a match arm or a
+/// `for` body.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScopeInfo {
+    pub id: usize,
+    pub parent: Option<usize>,
+    pub depth: usize,
+    pub span: TextRange,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShadowEventKind {
+    /// A definition hides an outer one with the same name.
+    DefinitionShadow,
+    /// A variable takes the name of a commonly used stdlib function.
+    ImportantName,
+    /// A reference had more than one candidate.
+    ReferenceShadow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShadowSeverity {
+    Warning,
+    Info,
+}
+
+impl ShadowSeverity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Warning => "warning",
+            Self::Info => "info",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ShadowEvent {
+    pub name: String,
+    pub event_kind: ShadowEventKind,
+    pub scope_depth: usize,
+    pub span: TextRange,
+    pub selected_def_id: Option<usize>,
+    pub shadowed_def_ids: Vec<usize>,
+    pub severity: ShadowSeverity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct NameIndex {
+    pub definitions: Vec<usize>,
+    pub references: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResolutionPolicy {
+    pub selection: &'static str,
+    pub hoist: &'static str,
+    pub warn_important_shadow: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NameResolutionOptions {
+    /// Report variables that take the name of common stdlib functions
+    /// (`add`, `print`, ...) as warnings.
+    pub warn_important_shadow: bool,
+}
+
+impl Default for NameResolutionOptions {
+    fn default() -> Self {
+        Self {
+            warn_important_shadow: true,
+        }
+    }
+}
+
+/// Name resolution of one module.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NameResolution {
+    pub files: Vec<FileInfo>,
+    pub definitions: Vec<NameDefinition>,
+    /// Indexed by reference id (its position in this list).
+    pub references: Vec<NameReference>,
+    pub scopes: Vec<ScopeInfo>,
+    pub shadows: Vec<ShadowEvent>,
+    pub shadow_diagnostics: Vec<ShadowEvent>,
+    pub by_name: BTreeMap<String, NameIndex>,
+    pub policy: ResolutionPolicy,
+}
+
+/// Result of [`analyze_name_resolution`].
+#[derive(Debug, Clone, Serialize)]
+pub struct NameResolutionAnalysis {
+    pub stage: &'static str,
+    pub ok: bool,
+    pub diagnostics: Vec<DiagnosticInfo>,
+    #[serde(flatten)]
+    pub resolution: NameResolution,
+}
+
+/// Parse `source` as file 0 and resolve its names.
+pub fn analyze_name_resolution(
+    source: &str,
+    options: NameResolutionOptions,
+) -> NameResolutionAnalysis {
+    let index = SourceIndex::single(INPUT_PATH, source);
+    let file_id = FileId(0);
+    let parse_result = parse_tokens(file_id, lex(file_id, source));
+    let diagnostics = DiagnosticInfo::from_all(&index, &parse_result.diagnostics);
+    NameResolutionAnalysis {
+        stage: "name_resolution",
+        ok: parse_result.module.is_some() && !has_error(&parse_result.diagnostics),
+        diagnostics,
+        resolution: resolve_optional(parse_result.module.as_ref(), &index, file_id, options),
+    }
+}
+
+/// Resolve the names of `module`, whose sources are indexed by `index`.
+/// Only the bodies of file `focus` are walked.
+pub fn resolve_names(
+    module: &Module,
+    index: &SourceIndex,
+    focus: FileId,
+    options: NameResolutionOptions,
+) -> NameResolution {
+    let mut resolver = NameResolver::new(index, Some(focus), options);
+    resolver.block(&module.root);
+    resolver.finish()
+}
+
+/// [`resolve_names`] over the bodies of every file of `module`.
+pub fn resolve_workspace_names(
+    module: &Module,
+    index: &SourceIndex,
+    options: NameResolutionOptions,
+) -> NameResolution {
+    let mut resolver = NameResolver::new(index, None, options);
+    resolver.block(&module.root);
+    resolver.finish()
+}
+
+/// [`resolve_names`], or an empty resolution when there is no module.
+pub(super) fn resolve_optional(
+    module: Option<&Module>,
+    index: &SourceIndex,
+    focus: FileId,
+    options: NameResolutionOptions,
+) -> NameResolution {
+    match module {
+        Some(module) => resolve_names(module, index, focus, options),
+        None => NameResolver::new(index, Some(focus), options).finish(),
+    }
+}
+
+struct NameResolver<'a, 'src> {
+    index: &'a SourceIndex<'src>,
+    /// `None` walks every file.
+    focus: Option<FileId>,
+    options: NameResolutionOptions,
+    defs: Vec<NameDefinition>,
+    refs: Vec<NameReference>,
+    scopes: Vec<ScopeInfo>,
+    shadows: Vec<ShadowEvent>,
+    /// Open scopes, innermost last: scope id and the names defined in it.
+    stack: Vec<(usize, BTreeMap<String, Vec<usize>>)>,
+}
+
+impl<'a, 'src> NameResolver<'a, 'src> {
+    fn new(
+        index: &'a SourceIndex<'src>,
+        focus: Option<FileId>,
+        options: NameResolutionOptions,
+    ) -> Self {
+        let root = ScopeInfo {
+            id: 0,
+            parent: None,
+            depth: 0,
+            span: index.full_range(focus.unwrap_or(FileId(0))),
+        };
+        Self {
+            index,
+            focus,
+            options,
+            defs: Vec::new(),
+            refs: Vec::new(),
+            scopes: alloc::vec![root],
+            shadows: Vec::new(),
+            stack: alloc::vec![(0, BTreeMap::new())],
+        }
+    }
+
+    fn depth(&self) -> usize {
+        self.stack.len() - 1
+    }
+
+    fn scope_id(&self) -> usize {
+        self.stack.last().map(|(id, _)| *id).unwrap_or(0)
+    }
+
+    fn push_scope(&mut self, span: Span) {
+        let id = self.scopes.len();
+        self.scopes.push(ScopeInfo {
+            id,
+            parent: Some(self.scope_id()),
+            depth: self.depth() + 1,
+            span: self.index.range(span),
+        });
+        self.stack.push((id, BTreeMap::new()));
+    }
+
+    fn pop_scope(&mut self) {
+        if self.stack.len() > 1 {
+            self.stack.pop();
+        }
+    }
+
+    fn lookup_candidates(&self, name: &str) -> Vec<usize> {
+        let mut out = Vec::new();
+        for (_, scope) in self.stack.iter().rev() {
+            if let Some(ids) = scope.get(name) {
+                out.extend(ids.iter().rev().copied());
+            }
+        }
+        out
+    }
+
+    fn define(&mut self, name: &str, kind: NameDefKind, span: Span) -> usize {
+        let existing = self.lookup_candidates(name);
+        let id = self.defs.len();
+        let depth = self.depth();
+        let range = self.index.range(span);
+        self.defs.push(NameDefinition {
+            id,
+            name: name.into(),
+            kind,
+            scope_id: self.scope_id(),
+            scope_depth: depth,
+            span: range,
+            path: self.index.path(span.file_id).unwrap_or_default().into(),
+        });
+
+        let important = self.options.warn_important_shadow
+            && is_important_shadow_symbol(name)
+            && kind.is_variable();
+        if !existing.is_empty() {
+            let (severity, message) = if important {
+                (
+                    ShadowSeverity::Warning,
+                    format!(
+                        "important symbol '{}' is shadowed by {} definition",
+                        name,
+                        kind.as_str()
+                    ),
+                )
+            } else {
+                (
+                    ShadowSeverity::Info,
+                    format!("'{}' shadows an outer definition", name),
+                )
+            };
+            self.shadows.push(ShadowEvent {
+                name: name.into(),
+                event_kind: ShadowEventKind::DefinitionShadow,
+                scope_depth: depth,
+                span: range,
+                selected_def_id: Some(id),
+                shadowed_def_ids: existing,
+                severity,
+                message,
+            });
+        } else if important {
+            self.shadows.push(ShadowEvent {
+                name: name.into(),
+                event_kind: ShadowEventKind::ImportantName,
+                scope_depth: depth,
+                span: range,
+                selected_def_id: Some(id),
+                shadowed_def_ids: Vec::new(),
+                severity: ShadowSeverity::Warning,
+                message: format!("definition '{}' may shadow important stdlib symbol", name),
+            });
+        }
+
+        if let Some((_, scope)) = self.stack.last_mut() {
+            scope.entry(name.into()).or_default().push(id);
+        }
+        id
+    }
+
+    fn reference(&mut self, name: &str, span: Span) {
+        let candidates = self.lookup_candidates(name);
+        let resolved = candidates.first().copied();
+        let range = self.index.range(span);
+        if candidates.len() > 1 {
+            self.shadows.push(ShadowEvent {
+                name: name.into(),
+                event_kind: ShadowEventKind::ReferenceShadow,
+                scope_depth: self.depth(),
+                span: range,
+                selected_def_id: resolved,
+                shadowed_def_ids: candidates[1..].to_vec(),
+                severity: ShadowSeverity::Info,
+                message: format!(
+                    "'{}' resolved to nearest definition with {} shadowed candidate(s)",
+                    name,
+                    candidates.len() - 1
+                ),
+            });
+        }
+        self.refs.push(NameReference {
+            name: name.into(),
+            scope_id: self.scope_id(),
+            scope_depth: self.depth(),
+            span: range,
+            resolved_def_id: resolved,
+            resolved_def: resolved.map(|id| self.defs[id].clone()),
+            candidate_definitions: candidates.iter().map(|id| self.defs[*id].clone()).collect(),
+            candidate_def_ids: candidates,
+        });
+    }
+
+    /// A reference through a field path: `p.x` refers to `p`.
+    fn reference_path(&mut self, id: &Ident) {
+        match id.name.split_once('.') {
+            Some((head, _)) if !head.is_empty() && !head.contains("::") => {
+                let end = id.span.start + head.len() as u32;
+                self.reference(head, Span::new(id.span.file_id, id.span.start, end));
+            }
+            _ => self.reference(&id.name, id.span),
+        }
+    }
+
+    fn hoist(&mut self, block: &Block) {
+        for stmt in &block.items {
+            match stmt {
+                Stmt::FnDef(def) => {
+                    self.define(&def.name.name, NameDefKind::Fn, def.name.span);
+                }
+                Stmt::FnAlias(alias) => {
+                    self.define(&alias.name.name, NameDefKind::FnAlias, alias.name.span);
+                }
+                Stmt::Expr(expr) | Stmt::ExprSemi(expr, _) => {
+                    if let Some(PrefixItem::Symbol(Symbol::Let { name, mutable, .. })) =
+                        expr.items.first()
+                    {
+                        if !*mutable {
+                            self.define(&name.name, NameDefKind::LetHoisted, name.span);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.hoist(block);
+        for stmt in &block.items {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        if stmt_file(stmt).is_some_and(|f| self.focus.is_some_and(|focus| f != focus)) {
+            return;
+        }
+        match stmt {
+            Stmt::FnDef(def) => {
+                if let FnBody::Parsed(body) = &def.body {
+                    self.push_scope(def.name.span.join(body.span).unwrap_or(body.span));
+                    for param in &def.params {
+                        self.define(&param.name, NameDefKind::Param, param.span);
+                    }
+                    self.block(body);
+                    self.pop_scope();
+                }
+            }
+            Stmt::FnAlias(alias) => self.reference(&alias.target.name, alias.target.span),
+            Stmt::Expr(expr) | Stmt::ExprSemi(expr, _) => self.expr(expr),
+            _ => {}
+        }
+    }
+
+    fn match_arm(&mut self, arm: &MatchArm) {
+        self.push_scope(arm.span);
+        if let Some(bind) = &arm.bind {
+            self.define(&bind.name, NameDefKind::MatchBind, bind.span);
+        }
+        self.block(&arm.body);
+        self.pop_scope();
+    }
+
+    fn expr(&mut self, expr: &PrefixExpr) {
+        for (idx, item) in expr.items.iter().enumerate() {
+            match item {
+                PrefixItem::Symbol(Symbol::Let { name, mutable, .. }) => {
+                    if *mutable {
+                        self.define(&name.name, NameDefKind::LetMut, name.span);
+                    }
+                    if idx != 0 {
+                        self.reference(&name.name, name.span);
+                    }
+                }
+                PrefixItem::Symbol(Symbol::Set { name }) => self.reference_path(name),
+                PrefixItem::Symbol(Symbol::Ident(id, _, _)) => {
+                    if !is_layout_marker(&id.name) {
+                        self.reference_path(id);
+                    }
+                }
+                PrefixItem::Block(block, _) => {
+                    self.push_scope(block.span);
+                    self.block(block);
+                    self.pop_scope();
+                }
+                PrefixItem::Match(m, _) => {
+                    self.expr(&m.scrutinee);
+                    for arm in &m.arms {
+                        self.match_arm(arm);
+                    }
+                }
+                PrefixItem::For(f, _) => {
+                    self.expr(&f.iter);
+                    self.push_scope(f.span);
+                    self.define(&f.var.name, NameDefKind::ForVar, f.var.span);
+                    self.block(&f.body);
+                    self.pop_scope();
+                }
+                PrefixItem::Tuple(items, _) => {
+                    for item_expr in items {
+                        self.expr(item_expr);
+                    }
+                }
+                PrefixItem::Group(inner, _) => self.expr(inner),
+                PrefixItem::Intrinsic(intr, _) => {
+                    for arg in &intr.args {
+                        self.expr(arg);
+                    }
+                }
+                PrefixItem::Literal(_, _)
+                | PrefixItem::TypeAnnotation(_, _)
+                | PrefixItem::Pipe(_)
+                | PrefixItem::Error(_) => {}
+                PrefixItem::Symbol(Symbol::If(_))
+                | PrefixItem::Symbol(Symbol::While(..))
+                | PrefixItem::Symbol(Symbol::Return(_))
+                | PrefixItem::Symbol(Symbol::Break(..))
+                | PrefixItem::Symbol(Symbol::Continue(..))
+                | PrefixItem::Symbol(Symbol::AddrOf(_))
+                | PrefixItem::Symbol(Symbol::Deref(_))
+                | PrefixItem::Symbol(Symbol::Try(_)) => {}
+            }
+        }
+    }
+
+    fn finish(self) -> NameResolution {
+        let mut by_name = BTreeMap::<String, NameIndex>::new();
+        for d in &self.defs {
+            by_name.entry(d.name.clone()).or_default().definitions.push(d.id);
+        }
+        for (idx, r) in self.refs.iter().enumerate() {
+            by_name.entry(r.name.clone()).or_default().references.push(idx);
+        }
+        NameResolution {
+            files: self.index.files(),
+            shadow_diagnostics: self.shadows.clone(),
+            definitions: self.defs,
+            references: self.refs,
+            scopes: self.scopes,
+            shadows: self.shadows,
+            by_name,
+            policy: ResolutionPolicy {
+                selection: "nearest_scope_first",
+                hoist: "fn, fn alias and non-mut let",
+                warn_important_shadow: self.options.warn_important_shadow,
+            },
+        }
+    }
+}
+
+/// File of the statements the resolver walks into.
+fn stmt_file(stmt: &Stmt) -> Option<FileId> {
+    match stmt {
+        Stmt::FnDef(def) => Some(def.name.span.file_id),
+        Stmt::FnAlias(alias) => Some(alias.name.span.file_id),
+        Stmt::Expr(expr) | Stmt::ExprSemi(expr, _) => Some(expr.span.file_id),
+        _ => None,
+    }
+}
+
+fn is_layout_marker(name: &str) -> bool {
+    matches!(name, "cond" | "then" | "else" | "do" | "block")
+}
+
+fn is_important_shadow_symbol(name: &str) -> bool {
+    matches!(
+        name,
+        "print"
+            | "println"
+            | "print_i32"
+            | "println_i32"
+            | "add"
+            | "sub"
+            | "mul"
+            | "div"
+            | "eq"
+            | "lt"
+            | "le"
+            | "gt"
+            | "ge"
+            | "map"
+            | "len"
+    )
+}
@@ -0,0 +1,115 @@
+//! Analyses over the output of the [`Loader`](crate::loader::Loader).
+//!
+//! The loader merges every imported and included file into one module while
+//! keeping per-file spans. The entry file is always file 0 and is the focus
+//! of the analysis: its tokens, references, scopes and expressions are
+//! reported, while imported files only contribute definitions (with their
+//! own paths and ranges, so editors can jump into them).
+
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use super::names::{resolve_optional, NameResolutionAnalysis, NameResolutionOptions};
+use super::semantics::{semantics_of, SemanticAnalysis};
+use super::{has_error, DiagnosticInfo, SourceIndex, TokenInfo};
+use crate::ast::Module;
+use crate::diagnostic::Diagnostic;
+use crate::error::CoreError;
+use crate::lexer::lex;
+use crate::loader::{LoadResult, Loader, LoaderError, SourceMap};
+use crate::parser::parse_tokens;
+use crate::span::{FileId, Span};
+
+/// What the loader produced for an entry file, whether or not it succeeded.
+#[derive(Debug, Clone)]
+pub struct LoadedSources {
+    pub source_map: SourceMap,
+    /// The merged module. When files only had parse errors this is merged
+    /// from their recovered ASTs; after any other failure it is the entry
+    /// file parsed on its own, if it parses at all.
+    pub module: Option<Module>,
+    /// Loader diagnostics (missing files, cycles, parse errors).
+    pub diagnostics: Vec<Diagnostic>,
+    /// Whether `module` includes the imported files.
+    pub merged: bool,
+}
+
+impl LoadedSources {
+    /// Wrap the result of `Loader::load*`. `loader` provides the source map
+    /// after the call, which also covers the files read before a failure,
+    /// and the recovered module when only parsing failed.
+    pub fn new(result: Result<LoadResult, LoaderError>, loader: &Loader) -> Self {
+        match result {
+            Ok(loaded) => Self {
+                source_map: loaded.source_map,
+                module: Some(loaded.module),
+                diagnostics: Vec::new(),
+                merged: true,
+            },
+            Err(err) => {
+                let diagnostics = match err {
+                    LoaderError::Core(CoreError::Diagnostics(diags)) => diags,
+                    other => alloc::vec![Diagnostic::error(other.to_string(), Span::dummy())],
+                };
+                let source_map = loader.source_map().clone();
+                if let Some(module) = loader.recovered_module() {
+                    return Self {
+                        source_map,
+                        module: Some(module.clone()),
+                        diagnostics,
+                        merged: true,
+                    };
+                }
+                let module = source_map
+                    .get(FileId(0))
+                    .and_then(|src| parse_tokens(FileId(0), lex(FileId(0), src)).module);
+                Self {
+                    source_map,
+                    module,
+                    diagnostics,
+                    merged: false,
+                }
+            }
+        }
+    }
+
+    pub(super) fn entry_tokens(&self, index: &SourceIndex) -> Vec<TokenInfo> {
+        match self.source_map.get(FileId(0)) {
+            Some(src) => lex(FileId(0), src)
+                .tokens
+                .iter()
+                .map(|t| TokenInfo::new(index, t))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Name resolution of the entry file against everything it imports.
+pub fn analyze_loaded_name_resolution(
+    loaded: &LoadedSources,
+    options: NameResolutionOptions,
+) -> NameResolutionAnalysis {
+    let index = SourceIndex::from_source_map(&loaded.source_map);
+    NameResolutionAnalysis {
+        stage: "name_resolution",
+        ok: loaded.merged && !has_error(&loaded.diagnostics),
+        diagnostics: DiagnosticInfo::from_all(&index, &loaded.diagnostics),
+        resolution: resolve_optional(loaded.module.as_ref(), &index, FileId(0), options),
+    }
+}
+
+/// Semantic analysis of the entry file against everything it imports.
+pub fn analyze_loaded_semantics(loaded: &LoadedSources) -> SemanticAnalysis {
+    let index = SourceIndex::from_source_map(&loaded.source_map);
+    let tokens = loaded.entry_tokens(&index);
+    let mut out = semantics_of(
+        &index,
+        FileId(0),
+        tokens,
+        loaded.module.as_ref(),
+        loaded.diagnostics.clone(),
+    );
+    out.ok &= loaded.merged;
+    out
+}
@@ -0,0 +1,712 @@
+//! Find-references and rename across every file of a program.
+//!
+//! Names are resolved with [`resolve_workspace_names`], which walks the
+//! bodies of the imported files too, so a stdlib function is found at all
+//! of its call sites and not only in the entry file. On top of the lexical
+//! references this module finds the places where a top-level function is
+//! named outside expressions: the items of selective imports
+//! (`#import "x" as { name as alias }`), `#use` paths, qualified paths
+//! through an import alias (`math::add`) and `#entry`.
+//!
+//! Overloads share their name, and the typechecker picks one per call site
+//! by type, so every same-named function of a scope is one symbol: renaming
+//! one overload renames the whole set (an `fn` alias of that name included).
+//! An `fn` alias is its own symbol; the name it points at is a reference to
+//! its target. References that resolve to a nearer variable of the same name
+//! are not part of the symbol, and a rename whose new name would change what
+//! some reference resolves to is reported as a conflict.
+
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::Serialize;
+
+use super::names::{
+    resolve_workspace_names, NameDefKind, NameDefinition, NameReference, NameResolution,
+    NameResolutionOptions,
+};
+use super::project::LoadedSources;
+use super::{has_error, DiagnosticInfo, SourceIndex, TextRange, INPUT_PATH};
+use crate::ast::Module;
+use crate::diagnostic::Diagnostic;
+use crate::lexer::{lex, TokenKind};
+use crate::parser::parse_tokens;
+use crate::span::{FileId, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferenceKind {
+    /// The name of a definition (one entry per overload).
+    Definition,
+    /// A use in an expression, a `set` or the target of an `fn` alias.
+    Reference,
+    /// An item of a selective import, `#import "x" as { name }`.
+    ImportItem,
+    /// The last segment of `#use path::name`.
+    UsePath,
+    /// The last segment of `alias::name` through an import alias.
+    QualifiedPath,
+    /// `#entry name`.
+    Entry,
+}
+
+impl ReferenceKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Definition => "definition",
+            Self::Reference => "reference",
+            Self::ImportItem => "import_item",
+            Self::UsePath => "use_path",
+            Self::QualifiedPath => "qualified_path",
+            Self::Entry => "entry",
+        }
+    }
+}
+
+/// One place where the symbol is named. `range` covers the name alone.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SymbolReference {
+    pub kind: ReferenceKind,
+    pub path: String,
+    pub range: TextRange,
+}
+
+/// The symbol under the cursor.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RenameSymbol {
+    pub name: String,
+    pub kind: NameDefKind,
+    /// Every overload for functions, the one binding for variables.
+    pub definitions: Vec<NameDefinition>,
+}
+
+/// Result of [`find_references`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ReferencesAnalysis {
+    pub stage: &'static str,
+    pub ok: bool,
+    pub diagnostics: Vec<DiagnosticInfo>,
+    pub symbol: Option<RenameSymbol>,
+    /// Sorted by file and position, definitions included.
+    pub references: Vec<SymbolReference>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TextEdit {
+    pub range: TextRange,
+    pub new_text: String,
+}
+
+/// The edits of one file, sorted and non-overlapping.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileEdit {
+    pub file_id: u32,
+    pub path: String,
+    pub edits: Vec<TextEdit>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct WorkspaceEdit {
+    pub files: Vec<FileEdit>,
+}
+
+impl WorkspaceEdit {
+    /// `source` of file `file_id` with its edits applied.
+    pub fn apply(&self, file_id: u32, source: &str) -> String {
+        let mut out = String::from(source);
+        if let Some(file) = self.files.iter().find(|f| f.file_id == file_id) {
+            for edit in file.edits.iter().rev() {
+                out.replace_range(
+                    edit.range.start as usize..edit.range.end as usize,
+                    &edit.new_text,
+                );
+            }
+        }
+        out
+    }
+}
+
+/// A place where the new name would change what a name resolves to.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RenameConflict {
+    pub path: String,
+    pub range: TextRange,
+    pub message: String,
+}
+
+/// Result of [`rename`]. `edit` is filled in even when there are conflicts,
+/// for previews; `ok` is false then.
+#[derive(Debug, Clone, Serialize)]
+pub struct RenameAnalysis {
+    pub stage: &'static str,
+    pub ok: bool,
+    pub diagnostics: Vec<DiagnosticInfo>,
+    pub symbol: Option<RenameSymbol>,
+    pub new_name: String,
+    pub edit: WorkspaceEdit,
+    pub conflicts: Vec<RenameConflict>,
+}
+
+/// References of the name at byte `offset` of `source` (file 0).
+pub fn find_references(source: &str, offset: u32) -> ReferencesAnalysis {
+    let index = SourceIndex::single(INPUT_PATH, source);
+    let parse_result = parse_tokens(FileId(0), lex(FileId(0), source));
+    references_of(
+        &index,
+        parse_result.module.as_ref(),
+        parse_result.diagnostics,
+        offset,
+    )
+}
+
+/// References of the name at byte `offset` of the entry file, in the entry
+/// file and in everything it imports.
+pub fn find_references_loaded(loaded: &LoadedSources, offset: u32) -> ReferencesAnalysis {
+    let index = SourceIndex::from_source_map(&loaded.source_map);
+    let mut out = references_of(
+        &index,
+        loaded.module.as_ref(),
+        loaded.diagnostics.clone(),
+        offset,
+    );
+    out.ok &= loaded.merged;
+    out
+}
+
+/// Rename the name at byte `offset` of `source` (file 0) to `new_name`.
+pub fn rename(source: &str, offset: u32, new_name: &str) -> RenameAnalysis {
+    let index = SourceIndex::single(INPUT_PATH, source);
+    let parse_result = parse_tokens(FileId(0), lex(FileId(0), source));
+    rename_of(
+        &index,
+        parse_result.module.as_ref(),
+        parse_result.diagnostics,
+        offset,
+        new_name,
+    )
+}
+
+/// Rename the name at byte `offset` of the entry file to `new_name`, in the
+/// entry file and in everything it imports.
+pub fn rename_loaded(loaded: &LoadedSources, offset: u32, new_name: &str) -> RenameAnalysis {
+    let index = SourceIndex::from_source_map(&loaded.source_map);
+    let mut out = rename_of(
+        &index,
+        loaded.module.as_ref(),
+        loaded.diagnostics.clone(),
+        offset,
+        new_name,
+    );
+    out.ok &= loaded.merged;
+    out
+}
+
+fn references_of(
+    index: &SourceIndex,
+    module: Option<&Module>,
+    mut diagnostics: Vec<Diagnostic>,
+    offset: u32,
+) -> ReferencesAnalysis {
+    let workspace = module.map(|m| Workspace::new(index, m));
+    let symbol = workspace.as_ref().and_then(|w| w.symbol_at(offset));
+    if symbol.is_none() {
+        diagnostics.push(no_symbol(offset));
+    }
+    let references = match (&workspace, &symbol) {
+        (Some(w), Some(set)) => w.references(set),
+        _ => Vec::new(),
+    };
+    ReferencesAnalysis {
+        stage: "references",
+        ok: !has_error(&diagnostics),
+        diagnostics: DiagnosticInfo::from_all(index, &diagnostics),
+        symbol: workspace
+            .as_ref()
+            .zip(symbol.as_ref())
+            .map(|(w, s)| w.describe(s)),
+        references,
+    }
+}
+
+fn rename_of(
+    index: &SourceIndex,
+    module: Option<&Module>,
+    mut diagnostics: Vec<Diagnostic>,
+    offset: u32,
+    new_name: &str,
+) -> RenameAnalysis {
+    let workspace = module.map(|m| Workspace::new(index, m));
+    let symbol = workspace.as_ref().and_then(|w| w.symbol_at(offset));
+    if symbol.is_none() {
+        diagnostics.push(no_symbol(offset));
+    }
+    if !is_identifier(new_name) {
+        diagnostics.push(Diagnostic::error(
+            format!("`{}` is not a valid name", new_name),
+            Span::new(FileId(0), offset, offset),
+        ));
+    }
+    let mut edit = WorkspaceEdit::default();
+    let mut conflicts = Vec::new();
+    if let (Some(w), Some(set)) = (&workspace, &symbol) {
+        if w.resolution.definitions[set[0]].name != new_name {
+            for r in w.references(set) {
+                let text = TextEdit {
+                    range: r.range,
+                    new_text: new_name.into(),
+                };
+                match edit.files.last_mut() {
+                    Some(file) if file.file_id == r.range.file_id => file.edits.push(text),
+                    _ => edit.files.push(FileEdit {
+                        file_id: r.range.file_id,
+                        path: r.path,
+                        edits: alloc::vec![text],
+                    }),
+                }
+            }
+            conflicts = w.conflicts(set, new_name);
+        }
+    }
+    RenameAnalysis {
+        stage: "rename",
+        ok: !has_error(&diagnostics) && conflicts.is_empty(),
+        diagnostics: DiagnosticInfo::from_all(index, &diagnostics),
+        symbol: workspace
+            .as_ref()
+            .zip(symbol.as_ref())
+            .map(|(w, s)| w.describe(s)),
+        new_name: new_name.into(),
+        edit,
+        conflicts,
+    }
+}
+
+fn no_symbol(offset: u32) -> Diagnostic {
+    Diagnostic::error(
+        "no variable or function name at the cursor",
+        Span::new(FileId(0), offset, offset),
+    )
+}
+
+/// Whether `name` lexes as a single identifier (and so is not a keyword).
+fn is_identifier(name: &str) -> bool {
+    let tokens = lex(FileId(0), name).tokens;
+    let mut kinds = tokens
+        .iter()
+        .map(|t| &t.kind)
+        .filter(|k| !matches!(k, TokenKind::Newline | TokenKind::Eof));
+    let single = matches!(kinds.next(), Some(TokenKind::Ident(n)) if n == name);
+    single && kinds.next().is_none() && !name.contains(['.', ':'])
+}
+
+/// `#import` of one file. `alias` is the name of the module in
+/// `alias::name` paths; `items` are the names picked by a selective import.
+struct ImportDirective {
+    file_id: u32,
+    spec: String,
+    alias: Option<String>,
+    items: Vec<(String, Span)>,
+}
+
+/// `#use module::path::name`.
+struct UseDirective {
+    module: Vec<String>,
+    name: String,
+    span: Span,
+}
+
+struct Workspace<'a, 'src> {
+    index: &'a SourceIndex<'src>,
+    resolution: NameResolution,
+    imports: Vec<ImportDirective>,
+    uses: Vec<UseDirective>,
+    entries: Vec<(String, Span)>,
+}
+
+impl<'a, 'src> Workspace<'a, 'src> {
+    fn new(index: &'a SourceIndex<'src>, module: &Module) -> Self {
+        let mut workspace = Self {
+            index,
+            resolution: resolve_workspace_names(module, index, NameResolutionOptions::default()),
+            imports: Vec::new(),
+            uses: Vec::new(),
+            entries: Vec::new(),
+        };
+        for file in index.files() {
+            let file_id = FileId(file.file_id);
+            let Some(source) = index.line_index(file_id).map(|l| l.source()) else {
+                continue;
+            };
+            for token in lex(file_id, source).tokens {
+                let start = token.span.start as usize;
+                let line = source[start..].lines().next().unwrap_or_default();
+                let words = directive_words(line);
+                let at = |(offset, word): (usize, &str)| {
+                    let start = (start + offset) as u32;
+                    Span::new(file_id, start, start + word.len() as u32)
+                };
+                match token.kind {
+                    TokenKind::DirImport(_) => {
+                        if let Some((spec, alias, items)) = parse_import(&words) {
+                            workspace.imports.push(ImportDirective {
+                                file_id: file.file_id,
+                                spec,
+                                alias,
+                                items: items.into_iter().map(|w| (w.1.into(), at(w))).collect(),
+                            });
+                        }
+                    }
+                    TokenKind::DirUse(_) => {
+                        let Some(&(offset, path)) = words.get(1) else {
+                            continue;
+                        };
+                        let mut module: Vec<String> = path.split("::").map(String::from).collect();
+                        let name = module.pop().unwrap_or_default();
+                        let name_offset = offset + path.len() - name.len();
+                        workspace.uses.push(UseDirective {
+                            module,
+                            span: at((name_offset, &name)),
+                            name,
+                        });
+                    }
+                    TokenKind::DirEntry(_) => {
+                        if let Some(&word) = words.get(1) {
+                            workspace.entries.push((word.1.into(), at(word)));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        workspace
+    }
+}
+
+impl Workspace<'_, '_> {
+    fn defs(&self) -> &[NameDefinition] {
+        &self.resolution.definitions
+    }
+
+    /// Definition ids of the symbol at `offset` of file 0: the overloads of
+    /// a function, or a single variable.
+    fn symbol_at(&self, offset: u32) -> Option<Vec<usize>> {
+        let hit = |r: &TextRange| r.file_id == 0 && r.start <= offset && offset <= r.end;
+        let span_hit = |s: &Span| s.file_id.0 == 0 && s.start <= offset && offset <= s.end;
+        let top_level = |name: &str, file: &dyn Fn(&str) -> bool| {
+            self.defs()
+                .iter()
+                .find(|d| {
+                    d.scope_id == 0 && !d.kind.is_variable() && d.name == name && file(&d.path)
+                })
+                .map(|d| d.id)
+        };
+        let named_by_directive = || {
+            let import = self.imports.iter().find_map(|i| {
+                let (name, _) = i.items.iter().find(|(_, s)| span_hit(s))?;
+                top_level(name, &|path| spec_matches(&i.spec, path))
+            });
+            let used = || {
+                let u = self.uses.iter().find(|u| span_hit(&u.span))?;
+                let module = u.module.last().map(String::as_str);
+                top_level(&u.name, &|path| module == Some(file_stem(path)))
+            };
+            let entry = || {
+                let (name, _) = self.entries.iter().find(|(_, s)| span_hit(s))?;
+                top_level(name, &|_| true)
+            };
+            import.or_else(used).or_else(entry)
+        };
+        let referenced = || {
+            self.resolution
+                .references
+                .iter()
+                .filter_map(|r| match r.resolved_def_id {
+                    Some(id) => Some((id, r.span)),
+                    None => self.qualified_def(r),
+                })
+                .find(|(_, range)| hit(range))
+                .map(|(id, _)| id)
+        };
+        let id = self
+            .defs()
+            .iter()
+            .find(|d| hit(&d.span))
+            .map(|d| d.id)
+            .or_else(referenced)
+            .or_else(named_by_directive)?;
+        let def = &self.defs()[id];
+        if def.kind.is_variable() {
+            return Some(alloc::vec![id]);
+        }
+        let overloads = self
+            .defs()
+            .iter()
+            .filter(|o| !o.kind.is_variable() && o.name == def.name && o.scope_id == def.scope_id);
+        Some(overloads.map(|o| o.id).collect())
+    }
+
+    fn describe(&self, set: &[usize]) -> RenameSymbol {
+        let first = &self.defs()[set[0]];
+        RenameSymbol {
+            name: first.name.clone(),
+            kind: first.kind,
+            definitions: set.iter().map(|&id| self.defs()[id].clone()).collect(),
+        }
+    }
+
+    /// For `alias::name`, the top-level function `name` of the file the
+    /// alias imports, with the range of `name`.
+    fn qualified_def(&self, r: &NameReference) -> Option<(usize, TextRange)> {
+        let (alias, name) = r.name.rsplit_once("::")?;
+        let imports: Vec<&ImportDirective> = self
+            .imports
+            .iter()
+            .filter(|i| i.file_id == r.span.file_id && i.alias.as_deref() == Some(alias))
+            .collect();
+        let def = self.defs().iter().find(|d| {
+            d.scope_id == 0
+                && !d.kind.is_variable()
+                && d.name == name
+                && imports.iter().any(|i| spec_matches(&i.spec, &d.path))
+        })?;
+        let start = r.span.end - name.len() as u32;
+        Some((def.id, self.range(r.span.file_id, start, r.span.end)))
+    }
+
+    fn range(&self, file_id: u32, start: u32, end: u32) -> TextRange {
+        self.index.range(Span::new(FileId(file_id), start, end))
+    }
+
+    fn reference(&self, kind: ReferenceKind, range: TextRange) -> SymbolReference {
+        SymbolReference {
+            kind,
+            path: self
+                .index
+                .path(FileId(range.file_id))
+                .unwrap_or_default()
+                .into(),
+            range,
+        }
+    }
+
+    fn references(&self, set: &[usize]) -> Vec<SymbolReference> {
+        let defs: Vec<&NameDefinition> = set.iter().map(|&id| &self.defs()[id]).collect();
+        let name = defs[0].name.as_str();
+        let mut out: Vec<SymbolReference> = defs
+            .iter()
+            .map(|d| self.reference(ReferenceKind::Definition, d.span))
+            .collect();
+        for r in &self.resolution.references {
+            if r.resolved_def_id.is_some_and(|id| set.contains(&id)) {
+                out.push(self.reference(ReferenceKind::Reference, r.span));
+            } else if let Some((id, range)) = self.qualified_def(r) {
+                if set.contains(&id) {
+                    out.push(self.reference(ReferenceKind::QualifiedPath, range));
+                }
+            }
+        }
+        // only top-level functions are named by directives
+        if defs
+            .iter()
+            .all(|d| d.scope_id == 0 && !d.kind.is_variable())
+        {
+            let defined_in = |matches: &dyn Fn(&str) -> bool| defs.iter().any(|d| matches(&d.path));
+            for import in &self.imports {
+                if !defined_in(&|path| spec_matches(&import.spec, path)) {
+                    continue;
+                }
+                for (item, span) in &import.items {
+                    if item == name {
+                        out.push(
+                            self.reference(ReferenceKind::ImportItem, self.index.range(*span)),
+                        );
+                    }
+                }
+            }
+            for u in &self.uses {
+                let module = u.module.last().map(String::as_str);
+                if u.name == name && defined_in(&|path| module == Some(file_stem(path))) {
+                    out.push(self.reference(ReferenceKind::UsePath, self.index.range(u.span)));
+                }
+            }
+            for (entry, span) in &self.entries {
+                if entry == name {
+                    out.push(self.reference(ReferenceKind::Entry, self.index.range(*span)));
+                }
+            }
+        }
+        out.sort_by_key(|r| (r.range.file_id, r.range.start));
+        out.dedup_by_key(|r| (r.range.file_id, r.range.start));
+        out
+    }
+
+    fn conflicts(&self, set: &[usize], new_name: &str) -> Vec<RenameConflict> {
+        let defs = self.defs();
+        let symbol = &defs[set[0]];
+        let uses: Vec<&NameReference> = self
+            .resolution
+            .references
+            .iter()
+            .filter(|r| r.resolved_def_id.is_some_and(|id| set.contains(&id)))
+            .collect();
+        let mut out = Vec::new();
+        let mut conflict = |range: TextRange, message: String| {
+            out.push(RenameConflict {
+                path: self
+                    .index
+                    .path(FileId(range.file_id))
+                    .unwrap_or_default()
+                    .into(),
+                range,
+                message,
+            });
+        };
+        for other in defs.iter().filter(|d| d.name == new_name) {
+            if other.scope_id == symbol.scope_id {
+                let message = if other.kind.is_variable() || symbol.kind.is_variable() {
+                    format!("`{}` is already defined in the same scope", new_name)
+                } else {
+                    format!(
+                        "`{}` already has overloads here; `{}` would be merged into them",
+                        new_name, symbol.name
+                    )
+                };
+                conflict(other.span, message);
+            } else if other.scope_depth > symbol.scope_depth
+                && uses.iter().any(|r| self.visible(other, r))
+            {
+                conflict(
+                    other.span,
+                    format!(
+                        "this `{}` would shadow the renamed `{}`",
+                        new_name, symbol.name
+                    ),
+                );
+            }
+        }
+        for r in self
+            .resolution
+            .references
+            .iter()
+            .filter(|r| r.name == new_name)
+        {
+            let nearer = r
+                .resolved_def_id
+                .is_some_and(|id| defs[id].scope_depth >= symbol.scope_depth);
+            if !nearer && set.iter().any(|&id| self.visible(&defs[id], r)) {
+                conflict(
+                    r.span,
+                    format!(
+                        "this `{}` would refer to the renamed `{}`",
+                        new_name, symbol.name
+                    ),
+                );
+            }
+        }
+        let mut seen = BTreeSet::new();
+        out.retain(|c| seen.insert((c.range.file_id, c.range.start)));
+        out
+    }
+
+    /// Whether `def` is in scope at the reference `r`.
+    fn visible(&self, def: &NameDefinition, r: &NameReference) -> bool {
+        let hoisted = matches!(
+            def.kind,
+            NameDefKind::Fn | NameDefKind::FnAlias | NameDefKind::LetHoisted
+        );
+        let before = def.span.file_id == r.span.file_id && def.span.start <= r.span.start;
+        let mut scope = Some(r.scope_id);
+        while let Some(id) = scope {
+            if id == def.scope_id {
+                return hoisted || before;
+            }
+            scope = self.resolution.scopes.get(id).and_then(|s| s.parent);
+        }
+        false
+    }
+}
+
+/// Words of a directive line with their byte offsets. `{`, `}` and `,` are
+/// words of their own, a quoted path is one word and `//` ends the line.
+fn directive_words(line: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        let separator = !quoted && (c.is_whitespace() || matches!(c, '{' | '}' | ','));
+        if separator || (!quoted && line[i..].starts_with("//")) {
+            if let Some(s) = start.take() {
+                words.push((s, &line[s..i]));
+            }
+            if line[i..].starts_with("//") && !quoted {
+                return words;
+            }
+            if !c.is_whitespace() {
+                words.push((i, &line[i..i + 1]));
+            }
+            continue;
+        }
+        if c == '"' {
+            quoted = !quoted;
+        }
+        start.get_or_insert(i);
+    }
+    if let Some(s) = start {
+        words.push((s, &line[s..]));
+    }
+    words
+}
+
+type ImportWords<'l> = (String, Option<String>, Vec<(usize, &'l str)>);
+
+/// The path, the alias and the selected items of `#import` words.
+fn parse_import<'l>(words: &[(usize, &'l str)]) -> Option<ImportWords<'l>> {
+    let mut rest = words.get(1..)?;
+    if rest.first().is_some_and(|w| w.1 == "pub") {
+        rest = &rest[1..];
+    }
+    let spec = rest.first()?.1.trim_matches('"');
+    let default_alias = spec.rsplit(['/', '\\']).next().unwrap_or(spec);
+    let clause = match rest.get(1) {
+        Some((_, "as")) => &rest[2..],
+        _ => return Some((spec.into(), Some(default_alias.into()), Vec::new())),
+    };
+    match clause.first().map(|w| w.1) {
+        Some("{") => {
+            // `name`, `name as alias` or `name::*` between commas
+            let mut items = Vec::new();
+            for item in clause[1..]
+                .split(|w| w.1 == "," || w.1 == "}")
+                .filter_map(|item| item.first())
+            {
+                if !item.1.ends_with("::*") {
+                    items.push(*item);
+                }
+            }
+            Some((spec.into(), None, items))
+        }
+        Some(alias) if alias != "*" && !alias.starts_with('@') => {
+            Some((spec.into(), Some(alias.into()), Vec::new()))
+        }
+        _ => Some((spec.into(), None, Vec::new())),
+    }
+}
+
+/// Whether the import path `spec` (`"./util"`, `"core/math"`) names the file
+/// at `path`.
+fn spec_matches(spec: &str, path: &str) -> bool {
+    let spec = spec.replace('\\', "/");
+    let mut spec = spec.trim_end_matches(".nepl");
+    while let Some(rest) = spec.strip_prefix("./").or_else(|| spec.strip_prefix("../")) {
+        spec = rest;
+    }
+    let path = path.replace('\\', "/");
+    let path = path.trim_end_matches(".nepl");
+    !spec.is_empty() && (path == spec || path.ends_with(&format!("/{}", spec)))
+}
+
+fn file_stem(path: &str) -> &str {
+    let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    name.strip_suffix(".nepl").unwrap_or(name)
+}
@@ -0,0 +1,426 @@
+//! Typechecked view of a single source file.
+//!
+//! Expressions are collected from the HIR before monomorphization, so generic
+//! functions report their declared type variables. Token-level tables pick
+//! the innermost expression, argument and name reference that covers each
+//! token.
+//!
+//! For a module merged by the loader, only the functions of the focus file
+//! are reported; definitions in imported files are reachable through
+//! [`TokenResolution::definition`].
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use serde::Serialize;
+
+use super::names::{resolve_names, NameDefKind, NameResolution, NameResolutionOptions};
+use super::{has_error, DiagnosticInfo, FileInfo, SourceIndex, TextRange, TokenInfo, INPUT_PATH};
+use crate::ast::{Effect, Module};
+use crate::diagnostic::Diagnostic;
+use crate::compiler::{scan_target_directives, BuildProfile};
+use crate::hir::{FuncRef, HirBlock, HirBody, HirExpr, HirExprKind};
+use crate::lexer::lex;
+use crate::parser::parse_tokens;
+use crate::span::FileId;
+use crate::typecheck::typecheck;
+use crate::types::TypeCtx;
+
+/// One HIR expression with its inferred type.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExprInfo {
+    pub id: usize,
+    pub function_name: String,
+    pub kind: &'static str,
+    pub span: TextRange,
+    pub inferred_type: String,
+    pub parent_id: Option<usize>,
+    /// Ranges of the direct operands (call arguments, `if` parts, ...).
+    pub argument_ranges: Vec<TextRange>,
+    /// For a `Call`, the name of the function it resolved to after
+    /// overload resolution. Captured variables passed to a nested function
+    /// come first in `argument_ranges` and share the callee's range.
+    pub callee: Option<TextRange>,
+}
+
+/// The innermost expression and argument position covering a token.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TokenSemantic {
+    pub token_index: usize,
+    pub inferred_expr_id: Option<usize>,
+    pub inferred_type: Option<String>,
+    pub expression_range: Option<TextRange>,
+    pub arg_index: Option<usize>,
+    pub arg_range: Option<TextRange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FunctionInfo {
+    pub name: String,
+    pub span: TextRange,
+    pub signature: String,
+}
+
+/// Where go-to-definition should jump to.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JumpTarget {
+    pub def_id: usize,
+    pub kind: NameDefKind,
+    pub path: String,
+    pub span: TextRange,
+}
+
+/// The name reference covering a token, if any.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TokenResolution {
+    pub token_index: usize,
+    pub name: Option<String>,
+    pub ref_span: Option<TextRange>,
+    pub resolved_def_id: Option<usize>,
+    pub candidate_def_ids: Vec<usize>,
+    pub definition: Option<JumpTarget>,
+}
+
+/// Result of [`analyze_semantics`].
+#[derive(Debug, Clone, Serialize)]
+pub struct SemanticAnalysis {
+    pub stage: &'static str,
+    pub ok: bool,
+    pub files: Vec<FileInfo>,
+    /// Tokens of the focus file.
+    pub tokens: Vec<TokenInfo>,
+    pub diagnostics: Vec<DiagnosticInfo>,
+    pub expressions: Vec<ExprInfo>,
+    pub token_semantics: Vec<TokenSemantic>,
+    pub functions: Vec<FunctionInfo>,
+    /// `None` when the source did not parse.
+    pub name_resolution: Option<NameResolution>,
+    pub token_resolution: Vec<TokenResolution>,
+}
+
+/// Lex, parse, resolve and typecheck `source` as file 0.
+pub fn analyze_semantics(source: &str) -> SemanticAnalysis {
+    let index = SourceIndex::single(INPUT_PATH, source);
+    let file_id = FileId(0);
+    let lex_result = lex(file_id, source);
+    let tokens: Vec<TokenInfo> = lex_result
+        .tokens
+        .iter()
+        .map(|t| TokenInfo::new(&index, t))
+        .collect();
+    let parse_result = parse_tokens(file_id, lex_result);
+    semantics_of(
+        &index,
+        file_id,
+        tokens,
+        parse_result.module.as_ref(),
+        parse_result.diagnostics,
+    )
+}
+
+/// Resolve and typecheck an already parsed (or loaded) `module`.
+/// `diagnostics` are those of the earlier stages.
+pub(super) fn semantics_of(
+    index: &SourceIndex,
+    focus: FileId,
+    tokens: Vec<TokenInfo>,
+    module: Option<&Module>,
+    diagnostics: Vec<Diagnostic>,
+) -> SemanticAnalysis {
+    checked_semantics_of(index, focus, tokens, module, diagnostics).0
+}
+
+/// The typechecked signature of a function in any file, in surface syntax.
+#[derive(Debug, Clone)]
+pub(super) struct CheckedSignature {
+    /// Range of the function name.
+    pub span: TextRange,
+    /// Parameter types, including leading captured variables.
+    pub params: Vec<String>,
+    pub result: String,
+    pub effect: Effect,
+}
+
+/// [`semantics_of`] together with the signatures of every typechecked
+/// function.
+pub(super) fn checked_semantics_of(
+    index: &SourceIndex,
+    focus: FileId,
+    tokens: Vec<TokenInfo>,
+    module: Option<&Module>,
+    diagnostics: Vec<Diagnostic>,
+) -> (SemanticAnalysis, Vec<CheckedSignature>) {
+    let mut all_diags = diagnostics;
+    let mut out = SemanticAnalysis {
+        stage: "semantics",
+        ok: false,
+        files: index.files(),
+        tokens,
+        diagnostics: Vec::new(),
+        expressions: Vec::new(),
+        token_semantics: Vec::new(),
+        functions: Vec::new(),
+        name_resolution: None,
+        token_resolution: Vec::new(),
+    };
+
+    let mut signatures = Vec::new();
+    let Some(module) = module else {
+        out.diagnostics = DiagnosticInfo::from_all(index, &all_diags);
+        return (out, signatures);
+    };
+
+    let resolution = resolve_names(module, index, focus, NameResolutionOptions::default());
+    let (target, target_diags) = scan_target_directives(module);
+    all_diags.extend(target_diags);
+    let tc = typecheck(module, target, BuildProfile::Debug);
+    all_diags.extend(tc.diagnostics.iter().cloned());
+    out.diagnostics = DiagnosticInfo::from_all(index, &all_diags);
+
+    if let Some(hir_module) = tc.module.as_ref().or(tc.partial.as_ref()) {
+        let mut collector = ExprCollector {
+            index,
+            types: &tc.types,
+            function_name: String::new(),
+            function_spans: BTreeMap::new(),
+            out: Vec::new(),
+        };
+        for f in &hir_module.functions {
+            collector.function_spans.insert(f.name.as_str(), index.range(f.span));
+            signatures.push(CheckedSignature {
+                span: index.range(f.span),
+                params: f
+                    .params
+                    .iter()
+                    .map(|p| tc.types.type_to_display(p.ty))
+                    .collect(),
+                result: tc.types.type_to_display(f.result),
+                effect: f.effect,
+            });
+        }
+        for f in &hir_module.functions {
+            if f.span.file_id != focus {
+                continue;
+            }
+            out.functions.push(FunctionInfo {
+                name: f.name.clone(),
+                span: index.range(f.span),
+                signature: tc.types.type_to_string(f.func_ty),
+            });
+            if let HirBody::Block(b) = &f.body {
+                collector.function_name = f.name.clone();
+                collector.block(b, None);
+            }
+        }
+        out.expressions = collector.out;
+        out.token_resolution = token_resolution(&out.tokens, &resolution);
+        out.token_semantics = token_semantics(&out.tokens, &out.expressions);
+        out.ok = !has_error(&all_diags);
+    }
+    out.name_resolution = Some(resolution);
+    (out, signatures)
+}
+
+/// The narrowest of `candidates` that contains `inner`.
+pub(super) fn innermost<'a, T>(
+    candidates: impl Iterator<Item = (&'a TextRange, T)>,
+    inner: &TextRange,
+) -> Option<(&'a TextRange, T)> {
+    let mut best: Option<(&TextRange, T)> = None;
+    for (range, item) in candidates {
+        if !range.contains(inner) {
+            continue;
+        }
+        if best.as_ref().is_none_or(|(prev, _)| range.width() < prev.width()) {
+            best = Some((range, item));
+        }
+    }
+    best
+}
+
+fn token_resolution(tokens: &[TokenInfo], resolution: &NameResolution) -> Vec<TokenResolution> {
+    tokens
+        .iter()
+        .enumerate()
+        .map(|(token_index, token)| {
+            let hit = innermost(
+                resolution.references.iter().map(|r| (&r.span, r)),
+                &token.span,
+            );
+            match hit {
+                Some((_, r)) => TokenResolution {
+                    token_index,
+                    name: Some(r.name.clone()),
+                    ref_span: Some(r.span),
+                    resolved_def_id: r.resolved_def_id,
+                    candidate_def_ids: r.candidate_def_ids.clone(),
+                    definition: r.resolved_def.as_ref().map(|d| JumpTarget {
+                        def_id: d.id,
+                        kind: d.kind,
+                        path: d.path.clone(),
+                        span: d.span,
+                    }),
+                },
+                None => TokenResolution {
+                    token_index,
+                    name: None,
+                    ref_span: None,
+                    resolved_def_id: None,
+                    candidate_def_ids: Vec::new(),
+                    definition: None,
+                },
+            }
+        })
+        .collect()
+}
+
+fn token_semantics(tokens: &[TokenInfo], exprs: &[ExprInfo]) -> Vec<TokenSemantic> {
+    tokens
+        .iter()
+        .enumerate()
+        .map(|(token_index, token)| {
+            let expr = innermost(exprs.iter().map(|e| (&e.span, e)), &token.span).map(|(_, e)| e);
+            let arg = innermost(
+                exprs.iter().flat_map(|e| e.argument_ranges.iter().enumerate().map(|(i, r)| (r, i))),
+                &token.span,
+            );
+            TokenSemantic {
+                token_index,
+                inferred_expr_id: expr.map(|e| e.id),
+                inferred_type: expr.map(|e| e.inferred_type.clone()),
+                expression_range: expr.map(|e| e.span),
+                arg_index: arg.map(|(_, i)| i),
+                arg_range: arg.map(|(r, _)| *r),
+            }
+        })
+        .collect()
+}
+
+struct ExprCollector<'a, 'src> {
+    index: &'a SourceIndex<'src>,
+    types: &'a TypeCtx,
+    function_name: String,
+    /// Name ranges by (possibly mangled) function symbol.
+    function_spans: BTreeMap<&'a str, TextRange>,
+    out: Vec<ExprInfo>,
+}
+
+impl ExprCollector<'_, '_> {
+    fn block(&mut self, block: &HirBlock, parent_id: Option<usize>) {
+        for line in &block.lines {
+            self.expr(&line.expr, parent_id);
+        }
+    }
+
+    fn expr(&mut self, expr: &HirExpr, parent_id: Option<usize>) -> usize {
+        let id = self.out.len();
+        self.out.push(ExprInfo {
+            id,
+            function_name: self.function_name.to_string(),
+            kind: hir_kind_name(&expr.kind),
+            span: self.index.range(expr.span),
+            inferred_type: self.types.type_to_string(expr.ty),
+            parent_id,
+            argument_ranges: Vec::new(),
+            callee: match &expr.kind {
+                HirExprKind::Call {
+                    callee: FuncRef::User(name, _),
+                    ..
+                } => self.function_spans.get(name.as_str()).copied(),
+                _ => None,
+            },
+        });
+
+        let mut args: Vec<&HirExpr> = Vec::new();
+        match &expr.kind {
+            HirExprKind::Call { args: call_args, .. } => args.extend(call_args),
+            HirExprKind::CallIndirect {
+                callee,
+                args: call_args,
+                ..
+            } => {
+                self.expr(callee, Some(id));
+                args.extend(call_args);
+            }
+            HirExprKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => args.extend([&**cond, &**then_branch, &**else_branch]),
+            HirExprKind::While { cond, body, .. } => args.extend([&**cond, &**body]),
+            HirExprKind::Match { scrutinee, arms } => {
+                args.push(scrutinee);
+                args.extend(arms.iter().map(|arm| &arm.body));
+            }
+            HirExprKind::EnumConstruct { payload, .. } => args.extend(payload.as_deref()),
+            HirExprKind::StructConstruct { fields, .. } => args.extend(fields),
+            HirExprKind::TupleConstruct { items } => args.extend(items),
+            HirExprKind::Block(b) => self.block(b, Some(id)),
+            HirExprKind::Let { value, .. }
+            | HirExprKind::Set { value, .. }
+            | HirExprKind::GlobalSet { value, .. } => args.push(value),
+            HirExprKind::Intrinsic { args: intr_args, .. } => args.extend(intr_args),
+            HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) | HirExprKind::Return(inner) => {
+                args.push(inner)
+            }
+            HirExprKind::LiteralI32(_)
+            | HirExprKind::LiteralF32(_)
+            | HirExprKind::LiteralI64(_)
+            | HirExprKind::LiteralF64(_)
+            | HirExprKind::LiteralBool(_)
+            | HirExprKind::LiteralStr(_)
+            | HirExprKind::StaticAddr(_)
+            | HirExprKind::GlobalGet(_)
+            | HirExprKind::Unit
+            | HirExprKind::Var(_)
+            | HirExprKind::FnValue(_)
+            | HirExprKind::Break(_)
+            | HirExprKind::Continue(_)
+            | HirExprKind::Drop { .. } => {}
+        }
+
+        let mut ranges = Vec::with_capacity(args.len());
+        for a in args {
+            ranges.push(self.index.range(a.span));
+            self.expr(a, Some(id));
+        }
+        self.out[id].argument_ranges = ranges;
+        id
+    }
+}
+
+/// Variant name of a HIR expression, as shown to editors.
+pub fn hir_kind_name(kind: &HirExprKind) -> &'static str {
+    match kind {
+        HirExprKind::LiteralI32(_) => "LiteralI32",
+        HirExprKind::LiteralF32(_) => "LiteralF32",
+        HirExprKind::LiteralI64(_) => "LiteralI64",
+        HirExprKind::LiteralF64(_) => "LiteralF64",
+        HirExprKind::LiteralBool(_) => "LiteralBool",
+        HirExprKind::LiteralStr(_) => "LiteralStr",
+        HirExprKind::StaticAddr(_) => "StaticAddr",
+        HirExprKind::Unit => "Unit",
+        HirExprKind::Var(_) => "Var",
+        HirExprKind::FnValue(_) => "FnValue",
+        HirExprKind::Call { .. } => "Call",
+        HirExprKind::CallIndirect { .. } => "CallIndirect",
+        HirExprKind::If { .. } => "If",
+        HirExprKind::While { .. } => "While",
+        HirExprKind::Match { .. } => "Match",
+        HirExprKind::EnumConstruct { .. } => "EnumConstruct",
+        HirExprKind::StructConstruct { .. } => "StructConstruct",
+        HirExprKind::TupleConstruct { .. } => "TupleConstruct",
+        HirExprKind::Block(_) => "Block",
+        HirExprKind::Let { .. } => "Let",
+        HirExprKind::Set { .. } => "Set",
+        HirExprKind::GlobalGet(_) => "GlobalGet",
+        HirExprKind::GlobalSet { .. } => "GlobalSet",
+        HirExprKind::Intrinsic { .. } => "Intrinsic",
+        HirExprKind::AddrOf(_) => "AddrOf",
+        HirExprKind::Deref(_) => "Deref",
+        HirExprKind::Return(_) => "Return",
+        HirExprKind::Break(_) => "Break",
+        HirExprKind::Continue(_) => "Continue",
+        HirExprKind::Drop { .. } => "Drop",
+    }
+}
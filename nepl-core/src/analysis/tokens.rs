@@ -0,0 +1,602 @@
+//! Semantic token classification for syntax highlighting.
+//!
+//! The lexer only knows that `add`, `x` and `T` are identifiers. After
+//! typechecking, every identifier of the focus file is classified by what it
+//! refers to: the definition kind from [`names`](super::names) (with the
+//! overload chosen by the typechecker for calls), struct / enum / trait
+//! declarations from the AST, and the surrounding tokens for the names the
+//! resolver does not track (`Enum::Variant`, `p.x`, `.T`, `<.label>`).
+//!
+//! Modifiers mark declarations, `let mut` bindings, definitions from the
+//! standard library and definitions whose doc comment has a `[非推奨]` (or
+//! `deprecated`) line.
+//!
+//! [`SemanticTokensAnalysis::data`] uses the relative encoding of the LSP
+//! (`deltaLine, deltaStartChar, length, tokenType, tokenModifiers`) with
+//! columns in chars. [`SemanticTokensAnalysis::lines`] restricts the result
+//! to the visible lines and [`semantic_tokens_delta`] turns two encodings into
+//! the edits of a delta response.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use serde::Serialize;
+
+use super::completion::doc_comment_lines;
+use super::names::{NameDefKind, NameReference};
+use super::project::LoadedSources;
+use super::semantics::checked_semantics_of;
+use super::{DiagnosticInfo, SourceIndex, TextRange, TokenInfo, INPUT_PATH};
+use crate::ast::{Block, FnBody, FnDef, Module, Stmt, TypeParam};
+use crate::diagnostic::Diagnostic;
+use crate::lexer::lex;
+use crate::parser::parse_tokens;
+use crate::span::FileId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SemanticTokenType {
+    Function,
+    Parameter,
+    /// `let`, `match` and `for` bindings.
+    Variable,
+    /// Structs, enums, aliases, newtypes and primitive types.
+    Type,
+    /// `.T`, including the dot.
+    TypeParameter,
+    EnumMember,
+    Trait,
+    /// Struct fields, declared or accessed with `p.x`.
+    Property,
+    /// The module alias in `alias::name`.
+    Namespace,
+    /// `.name` in `while <.name>`, `break <.name>` and `continue <.name>`.
+    Label,
+}
+
+impl SemanticTokenType {
+    /// Every type, in the order of the legend.
+    pub const ALL: [Self; 10] = [
+        Self::Function,
+        Self::Parameter,
+        Self::Variable,
+        Self::Type,
+        Self::TypeParameter,
+        Self::EnumMember,
+        Self::Trait,
+        Self::Property,
+        Self::Namespace,
+        Self::Label,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Function => "function",
+            Self::Parameter => "parameter",
+            Self::Variable => "variable",
+            Self::Type => "type",
+            Self::TypeParameter => "type_parameter",
+            Self::EnumMember => "enum_member",
+            Self::Trait => "trait",
+            Self::Property => "property",
+            Self::Namespace => "namespace",
+            Self::Label => "label",
+        }
+    }
+
+    /// Index in [`SemanticTokensLegend::token_types`].
+    pub fn index(self) -> u32 {
+        Self::ALL.iter().position(|t| *t == self).unwrap_or(0) as u32
+    }
+}
+
+/// Bit set of [`SemanticTokenModifiers::ALL`], in the order of the legend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SemanticTokenModifiers(pub u32);
+
+impl SemanticTokenModifiers {
+    /// The name of a definition.
+    pub const DECLARATION: Self = Self(1);
+    /// A `let mut` binding.
+    pub const MUTABLE: Self = Self(2);
+    /// Defined in the standard library (primitive types included).
+    pub const DEFAULT_LIBRARY: Self = Self(4);
+    pub const DEPRECATED: Self = Self(8);
+
+    pub const ALL: [(Self, &'static str); 4] = [
+        (Self::DECLARATION, "declaration"),
+        (Self::MUTABLE, "mutable"),
+        (Self::DEFAULT_LIBRARY, "default_library"),
+        (Self::DEPRECATED, "deprecated"),
+    ];
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn names(self) -> Vec<&'static str> {
+        Self::ALL
+            .iter()
+            .filter(|(m, _)| self.contains(*m))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+impl core::ops::BitOr for SemanticTokenModifiers {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SemanticToken {
+    /// Always on a single line.
+    pub range: TextRange,
+    pub kind: SemanticTokenType,
+    pub modifiers: SemanticTokenModifiers,
+}
+
+/// Names of the token types and modifiers, indexed by the numbers in
+/// [`SemanticTokensAnalysis::data`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SemanticTokensLegend {
+    pub token_types: Vec<&'static str>,
+    pub token_modifiers: Vec<&'static str>,
+}
+
+impl Default for SemanticTokensLegend {
+    fn default() -> Self {
+        Self {
+            token_types: SemanticTokenType::ALL.iter().map(|t| t.as_str()).collect(),
+            token_modifiers: SemanticTokenModifiers::ALL
+                .iter()
+                .map(|(_, n)| *n)
+                .collect(),
+        }
+    }
+}
+
+/// Result of [`semantic_tokens`].
+#[derive(Debug, Clone, Serialize)]
+pub struct SemanticTokensAnalysis {
+    pub stage: &'static str,
+    pub ok: bool,
+    pub diagnostics: Vec<DiagnosticInfo>,
+    pub legend: SemanticTokensLegend,
+    /// Sorted by position.
+    pub tokens: Vec<SemanticToken>,
+    /// `tokens` in the relative encoding of the LSP, five numbers per token.
+    pub data: Vec<u32>,
+}
+
+impl SemanticTokensAnalysis {
+    /// The tokens that start on lines `start_line..end_line` (0-based), for
+    /// a range request. `data` is encoded from the first of them.
+    pub fn lines(&self, start_line: u32, end_line: u32) -> Self {
+        let tokens: Vec<SemanticToken> = self
+            .tokens
+            .iter()
+            .filter(|t| (start_line..end_line).contains(&t.range.start_line))
+            .cloned()
+            .collect();
+        Self {
+            data: encode(&tokens),
+            tokens,
+            ..self.clone()
+        }
+    }
+}
+
+/// Replace `delete_count` numbers of the previous `data` from `start` by
+/// `data`, like `SemanticTokensEdit` of the LSP.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SemanticTokensEdit {
+    pub start: u32,
+    pub delete_count: u32,
+    pub data: Vec<u32>,
+}
+
+/// Semantic tokens of `source` (file 0).
+pub fn semantic_tokens(source: &str) -> SemanticTokensAnalysis {
+    let index = SourceIndex::single(INPUT_PATH, source);
+    let lex_result = lex(FileId(0), source);
+    let tokens: Vec<TokenInfo> = lex_result
+        .tokens
+        .iter()
+        .map(|t| TokenInfo::new(&index, t))
+        .collect();
+    let parse_result = parse_tokens(FileId(0), lex_result);
+    tokens_of(
+        &index,
+        tokens,
+        parse_result.module.as_ref(),
+        parse_result.diagnostics,
+        None,
+    )
+}
+
+/// Semantic tokens of the entry file. Definitions in files under
+/// `stdlib_root` get the `default_library` modifier.
+pub fn semantic_tokens_loaded(loaded: &LoadedSources, stdlib_root: &str) -> SemanticTokensAnalysis {
+    let index = SourceIndex::from_source_map(&loaded.source_map);
+    let tokens = loaded.entry_tokens(&index);
+    let mut out = tokens_of(
+        &index,
+        tokens,
+        loaded.module.as_ref(),
+        loaded.diagnostics.clone(),
+        Some(stdlib_root),
+    );
+    out.ok &= loaded.merged;
+    out
+}
+
+/// The edits that turn `previous` into `current` (both `data` arrays): at
+/// most one, replacing the tokens between the common prefix and suffix.
+pub fn semantic_tokens_delta(previous: &[u32], current: &[u32]) -> Vec<SemanticTokensEdit> {
+    let whole = |n: usize| n - n % 5;
+    let prefix = whole(
+        previous
+            .iter()
+            .zip(current)
+            .take_while(|(a, b)| a == b)
+            .count(),
+    );
+    let rest = previous.len().min(current.len()) - prefix;
+    let suffix = whole(
+        previous
+            .iter()
+            .rev()
+            .zip(current.iter().rev())
+            .take(rest)
+            .take_while(|(a, b)| a == b)
+            .count(),
+    );
+    if prefix == previous.len() && prefix == current.len() {
+        return Vec::new();
+    }
+    alloc::vec![SemanticTokensEdit {
+        start: prefix as u32,
+        delete_count: (previous.len() - prefix - suffix) as u32,
+        data: current[prefix..current.len() - suffix].to_vec(),
+    }]
+}
+
+fn encode(tokens: &[SemanticToken]) -> Vec<u32> {
+    let mut data = Vec::with_capacity(tokens.len() * 5);
+    let (mut line, mut col) = (0, 0);
+    for t in tokens {
+        let r = &t.range;
+        if r.start_line != line {
+            col = 0;
+        }
+        data.extend([
+            r.start_line - line,
+            r.start_col - col,
+            r.end_col - r.start_col,
+            t.kind.index(),
+            t.modifiers.0,
+        ]);
+        (line, col) = (r.start_line, r.start_col);
+    }
+    data
+}
+
+const PRIMITIVE_TYPES: &[&str] = &[
+    "i32", "u8", "f32", "i64", "f64", "v128", "bool", "never", "str", "Box",
+];
+
+/// `(file, start)` of a definition name.
+type DefKey = (u32, u32);
+
+/// Declarations the name resolver does not track, from the whole module.
+#[derive(Default)]
+struct Declarations<'m> {
+    kinds: BTreeMap<DefKey, SemanticTokenType>,
+    /// Struct, enum, alias and newtype names.
+    types: BTreeMap<&'m str, DefKey>,
+    /// Variant names by enum.
+    enums: BTreeMap<&'m str, Vec<&'m str>>,
+    traits: BTreeMap<&'m str, DefKey>,
+}
+
+impl<'m> Declarations<'m> {
+    fn block(&mut self, block: &'m Block) {
+        for stmt in &block.items {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &'m Stmt) {
+        match stmt {
+            Stmt::FnDef(def) => self.function(def),
+            Stmt::StructDef(s) => {
+                self.ty(&s.name.name, key(&s.name.span), &s.type_params);
+                for (field, _) in &s.fields {
+                    self.kinds
+                        .insert(key(&field.span), SemanticTokenType::Property);
+                }
+            }
+            Stmt::EnumDef(e) => {
+                self.ty(&e.name.name, key(&e.name.span), &e.type_params);
+                for v in &e.variants {
+                    self.kinds
+                        .insert(key(&v.name.span), SemanticTokenType::EnumMember);
+                }
+                self.enums
+                    .entry(&e.name.name)
+                    .or_insert_with(|| e.variants.iter().map(|v| v.name.name.as_str()).collect());
+            }
+            Stmt::TypeAlias(a) => self.ty(&a.name.name, key(&a.name.span), &a.type_params),
+            Stmt::Newtype(n) => self.ty(&n.name.name, key(&n.name.span), &[]),
+            Stmt::Trait(t) => {
+                self.kinds
+                    .insert(key(&t.name.span), SemanticTokenType::Trait);
+                self.traits.entry(&t.name.name).or_insert(key(&t.name.span));
+                self.type_params(&t.type_params);
+                for m in &t.methods {
+                    self.function(m);
+                }
+            }
+            Stmt::Impl(i) => {
+                self.type_params(&i.type_params);
+                for m in &i.methods {
+                    self.function(m);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn function(&mut self, def: &'m FnDef) {
+        self.kinds
+            .insert(key(&def.name.span), SemanticTokenType::Function);
+        self.type_params(&def.type_params);
+        if let FnBody::Parsed(body) = &def.body {
+            self.block(body);
+        }
+    }
+
+    fn ty(&mut self, name: &'m str, at: DefKey, params: &[TypeParam]) {
+        self.kinds.insert(at, SemanticTokenType::Type);
+        self.types.entry(name).or_insert(at);
+        self.type_params(params);
+    }
+
+    fn type_params(&mut self, params: &[TypeParam]) {
+        for p in params {
+            self.kinds
+                .insert(key(&p.name.span), SemanticTokenType::TypeParameter);
+        }
+    }
+}
+
+fn key(span: &crate::span::Span) -> DefKey {
+    (span.file_id.0, span.start)
+}
+
+/// Modifiers that come from where a name is defined.
+struct Origins<'a, 'src> {
+    index: &'a SourceIndex<'src>,
+    stdlib_root: Option<&'a str>,
+}
+
+impl Origins<'_, '_> {
+    fn of(&self, at: DefKey) -> SemanticTokenModifiers {
+        let file = FileId(at.0);
+        let mut out = SemanticTokenModifiers::default();
+        let in_stdlib = self
+            .stdlib_root
+            .zip(self.index.path(file))
+            .is_some_and(|(root, path)| path.starts_with(root));
+        if in_stdlib {
+            out = out | SemanticTokenModifiers::DEFAULT_LIBRARY;
+        }
+        if let Some(lines) = self.index.line_index(file) {
+            let line = lines.line_col(at.1).0 as usize;
+            let deprecated = doc_comment_lines(lines.source(), line).iter().any(|l| {
+                let l = l.trim_start();
+                l.starts_with("[非推奨") || l.to_ascii_lowercase().starts_with("deprecated")
+            });
+            if deprecated {
+                out = out | SemanticTokenModifiers::DEPRECATED;
+            }
+        }
+        out
+    }
+}
+
+fn def_kind_type(kind: NameDefKind) -> SemanticTokenType {
+    match kind {
+        NameDefKind::Fn | NameDefKind::FnAlias => SemanticTokenType::Function,
+        NameDefKind::Param => SemanticTokenType::Parameter,
+        NameDefKind::LetHoisted
+        | NameDefKind::LetMut
+        | NameDefKind::MatchBind
+        | NameDefKind::ForVar => SemanticTokenType::Variable,
+    }
+}
+
+fn def_kind_modifiers(kind: NameDefKind) -> SemanticTokenModifiers {
+    match kind {
+        NameDefKind::LetMut => SemanticTokenModifiers::MUTABLE,
+        _ => SemanticTokenModifiers::default(),
+    }
+}
+
+fn tokens_of(
+    index: &SourceIndex,
+    tokens: Vec<TokenInfo>,
+    module: Option<&Module>,
+    diagnostics: Vec<Diagnostic>,
+    stdlib_root: Option<&str>,
+) -> SemanticTokensAnalysis {
+    let (sem, _) = checked_semantics_of(index, FileId(0), tokens, module, diagnostics);
+    let mut decls = Declarations::default();
+    if let Some(m) = module {
+        decls.block(&m.root);
+    }
+    let origins = Origins { index, stdlib_root };
+    let definitions = sem
+        .name_resolution
+        .as_ref()
+        .map_or(&[][..], |r| r.definitions.as_slice());
+    // references are taken from the resolver, which also runs when the
+    // typechecker fails; by the end of the name, as the span of `set x`
+    // starts at `set`
+    let references: BTreeMap<u32, &NameReference> = sem
+        .name_resolution
+        .as_ref()
+        .map_or(&[][..], |r| r.references.as_slice())
+        .iter()
+        .filter(|r| r.span.file_id == 0)
+        .map(|r| (r.span.end, r))
+        .collect();
+    let names: BTreeMap<u32, NameDefKind> = definitions
+        .iter()
+        .filter(|d| d.span.file_id == 0)
+        .map(|d| (d.span.start, d.kind))
+        .collect();
+    let functions: BTreeMap<&str, DefKey> = definitions
+        .iter()
+        .rev()
+        .filter(|d| !d.kind.is_variable())
+        .map(|d| (d.name.as_str(), (d.span.file_id, d.span.start)))
+        .collect();
+    // the overload each call resolved to, by the start of the callee name
+    let callees: BTreeMap<u32, DefKey> = sem
+        .expressions
+        .iter()
+        .filter_map(|e| Some((e.span.start, e.callee.map(|c| (c.file_id, c.start))?)))
+        .collect();
+
+    let toks = &sem.tokens;
+    let text = |i: usize| toks[i].value.as_deref().unwrap_or("");
+    let kind_at = |i: Option<usize>| i.map_or("", |i| toks[i].kind);
+    let joined = |a: usize, b: usize| toks[a].span.end == toks[b].span.start;
+
+    let mut out = Vec::new();
+    for (i, tok) in toks.iter().enumerate() {
+        if tok.kind != "Ident" {
+            continue;
+        }
+        let at = (0, tok.span.start);
+        let prev = i.checked_sub(1);
+        let after_dot = prev.filter(|&p| toks[p].kind == "Dot" && joined(p, i));
+        let after_sep = prev.filter(|&p| toks[p].kind == "PathSep");
+        let before_sep = kind_at(Some(i + 1).filter(|&n| n < toks.len())) == "PathSep";
+        let mut range = tok.span;
+
+        let resolved = references
+            .get(&tok.span.end)
+            .filter(|r| r.span.contains(&tok.span) && r.name == text(i))
+            .and_then(|r| r.resolved_def.as_ref());
+        let (kind, modifiers) = if let Some(&kind) = names.get(&tok.span.start) {
+            let mut modifiers = def_kind_modifiers(kind) | SemanticTokenModifiers::DECLARATION;
+            if !kind.is_variable() {
+                modifiers = modifiers | origins.of(at);
+            }
+            (def_kind_type(kind), modifiers)
+        } else if let Some(&kind) = decls.kinds.get(&at) {
+            let modifiers = match kind {
+                SemanticTokenType::TypeParameter => {
+                    range = with_dot(range, after_dot.map(|p| &toks[p]));
+                    SemanticTokenModifiers::default()
+                }
+                _ => origins.of(at),
+            };
+            (kind, SemanticTokenModifiers::DECLARATION | modifiers)
+        } else if let Some(def) = resolved {
+            let target = match def.kind {
+                NameDefKind::Fn => callees.get(&tok.span.start).copied(),
+                _ => None,
+            }
+            .unwrap_or((def.span.file_id, def.span.start));
+            let mut modifiers = def_kind_modifiers(def.kind);
+            if !def.kind.is_variable() {
+                modifiers = modifiers | origins.of(target);
+            }
+            (def_kind_type(def.kind), modifiers)
+        } else if let Some(dot) = after_dot {
+            let field = dot
+                .checked_sub(1)
+                .is_some_and(|p| matches!(toks[p].kind, "Ident" | "RParen") && joined(p, dot));
+            range = with_dot(range, Some(&toks[dot]));
+            let loop_label = dot >= 2
+                && toks[dot - 1].kind == "LAngle"
+                && matches!(toks[dot - 2].kind, "KwWhile" | "KwBreak" | "KwContinue");
+            match (field, loop_label) {
+                (true, _) => {
+                    range = tok.span;
+                    (
+                        SemanticTokenType::Property,
+                        SemanticTokenModifiers::default(),
+                    )
+                }
+                (false, true) => (SemanticTokenType::Label, SemanticTokenModifiers::default()),
+                (false, false) => (
+                    SemanticTokenType::TypeParameter,
+                    SemanticTokenModifiers::default(),
+                ),
+            }
+        } else if let Some(sep) = after_sep {
+            let head = sep.checked_sub(1).filter(|&h| toks[h].kind == "Ident");
+            let variant = head
+                .and_then(|h| decls.enums.get(text(h)))
+                .is_some_and(|vs| vs.contains(&text(i)));
+            if variant {
+                let owner = decls.types[text(head.unwrap_or(i))];
+                (SemanticTokenType::EnumMember, origins.of(owner))
+            } else if let Some(&def) = functions.get(text(i)) {
+                (SemanticTokenType::Function, origins.of(def))
+            } else {
+                continue;
+            }
+        } else if let Some(&def) = decls.types.get(text(i)) {
+            (SemanticTokenType::Type, origins.of(def))
+        } else if let Some(&def) = decls.traits.get(text(i)) {
+            (SemanticTokenType::Trait, origins.of(def))
+        } else if PRIMITIVE_TYPES.contains(&text(i)) {
+            (
+                SemanticTokenType::Type,
+                SemanticTokenModifiers::DEFAULT_LIBRARY,
+            )
+        } else if before_sep {
+            (
+                SemanticTokenType::Namespace,
+                SemanticTokenModifiers::default(),
+            )
+        } else {
+            continue;
+        };
+        out.push(SemanticToken {
+            range,
+            kind,
+            modifiers,
+        });
+    }
+
+    SemanticTokensAnalysis {
+        stage: "semantic_tokens",
+        ok: sem.ok,
+        diagnostics: sem.diagnostics,
+        legend: SemanticTokensLegend::default(),
+        data: encode(&out),
+        tokens: out,
+    }
+}
+
+/// `range` extended to the start of the `.` right before it.
+fn with_dot(range: TextRange, dot: Option<&TokenInfo>) -> TextRange {
+    match dot {
+        Some(d) => TextRange {
+            start: d.span.start,
+            start_line: d.span.start_line,
+            start_col: d.span.start_col,
+            ..range
+        },
+        None => range,
+    }
+}
@@ -77,10 +77,14 @@ pub enum PrefixItem {
     TypeAnnotation(TypeExpr, Span),
     Block(Block, Span),
     Match(MatchExpr, Span),
+    For(ForExpr, Span),
     Pipe(Span),
     Tuple(Vec<PrefixExpr>, Span),
     Group(PrefixExpr, Span),
     Intrinsic(IntrinsicExpr, Span),
+    /// Placeholder for tokens the parser could not read as an expression.
+    /// The parser has already reported them.
+    Error(Span),
 }
 
 /// Special symbols in the language.
@@ -95,9 +99,19 @@ pub enum Symbol {
     },
     Set { name: Ident },
     If(Span),
-    While(Span),
+    /// `while [<.label>] cond body`; the label names the loop for `break` / `continue`.
+    While(Span, Option<String>),
+    /// `return expr`: leaves the enclosing function with `expr`.
+    Return(Span),
+    /// `break [<.label>]`: leaves the innermost (or the labeled) loop.
+    Break(Span, Option<String>),
+    /// `continue [<.label>]`: starts the next iteration of the innermost (or the labeled) loop.
+    Continue(Span, Option<String>),
     AddrOf(Span),
     Deref(Span),
+    /// `? expr`: unwraps `Option::Some` / `Result::Ok`, or returns the
+    /// `None` / `Err` from the enclosing function.
+    Try(Span),
 }
 
 /// A block of statements (introduced by `:` or the file root).
@@ -117,6 +131,30 @@ pub struct FnDef {
     pub signature: TypeExpr,
     pub params: Vec<Ident>,
     pub body: FnBody,
+    /// `true` when the signature was omitted; the effect is then inferred from the body.
+    pub effect_inferred: bool,
+    pub constness: Constness,
+    /// Set by a preceding `#export` directive.
+    pub export: Option<FnExport>,
+}
+
+/// `#export ["name"]`: expose the next function from the wasm module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FnExport {
+    /// External name; defaults to the function name.
+    pub name: String,
+    pub span: Span,
+}
+
+/// Compile-time evaluation marker of a function definition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Constness {
+    /// Ordinary function.
+    Runtime,
+    /// `const fn`: must be pure so that const initializers can call it.
+    ConstFn,
+    /// `const NAME <T> expr`: a zero-arg pure function folded at compile time.
+    ConstItem,
 }
 
 /// Function alias definition.
@@ -147,11 +185,49 @@ pub struct TraitDef {
 pub struct ImplDef {
     pub type_params: Vec<TypeParam>,
     pub trait_name: Option<Ident>, // None for inherent impl
+    pub trait_args: Vec<TypeExpr>,   // `impl Trait<A> for T`
     pub target_ty: TypeExpr,
     pub methods: Vec<FnDef>,
     pub span: Span,
 }
 
+/// Module-level storage: `static [mut] NAME <T> expr`.
+///
+/// The initializer is evaluated at compile time, in declaration order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticDef {
+    pub vis: Visibility,
+    pub name: Ident,
+    pub mutable: bool,
+    pub ty: TypeExpr,
+    pub value: PrefixExpr,
+    pub span: Span,
+}
+
+/// Type alias: `type Name <.T> = TypeExpr`.
+///
+/// Aliases are transparent; they are expanded wherever the name is used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeAliasDef {
+    pub vis: Visibility,
+    pub name: Ident,
+    pub type_params: Vec<TypeParam>,
+    pub target: TypeExpr,
+    pub span: Span,
+}
+
+/// Newtype: `newtype Name = TypeExpr`.
+///
+/// A distinct type for the typechecker that shares the representation of
+/// its inner type. `Name v` wraps and `Name::inner v` unwraps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewtypeDef {
+    pub vis: Visibility,
+    pub name: Ident,
+    pub inner: TypeExpr,
+    pub span: Span,
+}
+
 /// Function body kind.
 #[derive(Debug, Clone, PartialEq)]
 pub enum FnBody {
@@ -203,6 +279,11 @@ pub enum Directive {
         profile: String,
         span: Span,
     },
+    /// `#if[const=NAME]`: the next item is kept when the const evaluates to true/non-zero.
+    IfConst {
+        name: String,
+        span: Span,
+    },
     IndentWidth {
         width: usize,
         span: Span,
@@ -239,8 +320,14 @@ pub enum Stmt {
     LlvmIr(LlvmIrBlock),
     Trait(TraitDef),
     Impl(ImplDef),
+    Static(StaticDef),
+    TypeAlias(TypeAliasDef),
+    Newtype(NewtypeDef),
     Expr(PrefixExpr),
     ExprSemi(PrefixExpr, Option<Span>),
+    /// Placeholder for a statement the parser skipped while recovering from
+    /// an error; the span covers the skipped tokens.
+    Error(Span),
 }
 
 /// Parsed module.
@@ -321,6 +408,19 @@ pub struct MatchExpr {
     pub span: Span,
 }
 
+/// `for [<.label>] var in iter: body`.
+///
+/// `iter` is driven through the iterator protocol (`next &it` returning `Option`);
+/// the `Range` of core/iter becomes a counting loop.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForExpr {
+    pub label: Option<String>,
+    pub var: Ident,
+    pub iter: PrefixExpr,
+    pub body: Block,
+    pub span: Span,
+}
+
 /// Intrinsic expression: `#intrinsic "name" <Args...> (Exprs...)`
 #[derive(Debug, Clone, PartialEq)]
 pub struct IntrinsicExpr {
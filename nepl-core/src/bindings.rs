@@ -0,0 +1,491 @@
+//! Host binding stubs for `#export`ed functions.
+//!
+//! Exported functions take and return plain wasm values. `str` and aggregates
+//! cross the boundary as `i32` pointers into the exported `memory`, laid out
+//! the way `codegen_wasm` builds them:
+//!
+//! - `str`: `[len: u32 LE][len bytes of UTF-8]`
+//! - struct / tuple: fields in order, 4 bytes each (8 for i64 / f64), no padding
+//! - enum: `[tag: i32][payload at +4, or +8 for an i64 / f64 payload]`
+//!
+//! `typescript` and `rust` render that description as `.d.ts` / `.rs` stubs.
+
+extern crate alloc;
+
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::hir::HirModule;
+use crate::types::{TypeCtx, TypeId, TypeKind};
+
+/// How a value crosses the export boundary.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AbiType {
+    Unit,
+    Bool,
+    U8,
+    I32,
+    I64,
+    F32,
+    F64,
+    Str,
+    /// Pointer to the struct or enum described by the layout of that name.
+    Named(String),
+    /// Pointer to the items in order.
+    Tuple(Vec<AbiType>),
+    /// A value the host should treat as an opaque `i32`.
+    Opaque(String),
+}
+
+impl AbiType {
+    /// Bytes the value occupies inside an aggregate.
+    pub fn size(&self) -> u32 {
+        match self {
+            AbiType::I64 | AbiType::F64 => 8,
+            _ => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AbiField {
+    pub name: String,
+    pub ty: AbiType,
+    pub offset: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AbiVariant {
+    pub name: String,
+    pub tag: u32,
+    /// Payload type and its offset from the enum pointer.
+    pub payload: Option<(AbiType, u32)>,
+}
+
+/// Memory layout of a struct or enum reachable from an export.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AbiLayout {
+    Struct {
+        name: String,
+        size: u32,
+        fields: Vec<AbiField>,
+    },
+    Enum {
+        name: String,
+        variants: Vec<AbiVariant>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportedFn {
+    /// External name.
+    pub name: String,
+    /// NEPL signature such as `<(Point,i32)->i32>`, for documentation.
+    pub signature: String,
+    pub params: Vec<(String, AbiType)>,
+    pub result: AbiType,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ExportBindings {
+    pub functions: Vec<ExportedFn>,
+    pub layouts: Vec<AbiLayout>,
+}
+
+/// Describes every `#export` of `module`.
+pub fn collect(ctx: &TypeCtx, module: &HirModule) -> ExportBindings {
+    let mut c = Collector {
+        ctx,
+        seen: BTreeSet::new(),
+        layouts: Vec::new(),
+    };
+    let mut functions = Vec::new();
+    for ex in &module.exports {
+        let Some(func) = module.functions.iter().find(|f| f.name == ex.func) else {
+            continue;
+        };
+        let params: Vec<(String, AbiType)> = func
+            .params
+            .iter()
+            .map(|p| (p.name.clone(), c.abi(p.ty)))
+            .collect();
+        let result = c.abi(func.result);
+        let labels: Vec<String> = params.iter().map(|(_, t)| abi_label(t)).collect();
+        functions.push(ExportedFn {
+            name: ex.name.clone(),
+            signature: format!("<({})->{}>", labels.join(","), abi_label(&result)),
+            params,
+            result,
+        });
+    }
+    ExportBindings {
+        functions,
+        layouts: c.layouts,
+    }
+}
+
+struct Collector<'a> {
+    ctx: &'a TypeCtx,
+    seen: BTreeSet<String>,
+    layouts: Vec<AbiLayout>,
+}
+
+impl Collector<'_> {
+    fn abi(&mut self, ty: TypeId) -> AbiType {
+        let ty = self.ctx.resolve_id(ty);
+        match self.ctx.get(ty) {
+            TypeKind::Unit | TypeKind::Never => AbiType::Unit,
+            TypeKind::Bool => AbiType::Bool,
+            TypeKind::U8 => AbiType::U8,
+            TypeKind::I32 => AbiType::I32,
+            TypeKind::F32 => AbiType::F32,
+            TypeKind::Str => AbiType::Str,
+            TypeKind::Named(n) if n == "i64" => AbiType::I64,
+            TypeKind::Named(n) if n == "f64" => AbiType::F64,
+            TypeKind::Tuple { items } => {
+                AbiType::Tuple(items.iter().map(|t| self.abi(*t)).collect())
+            }
+            TypeKind::Struct { .. } | TypeKind::Enum { .. } => self.named(ty, ty, &[]),
+            TypeKind::Apply { base, args } => self.named(ty, base, &args),
+            _ => AbiType::Opaque(self.ctx.type_to_display(ty)),
+        }
+    }
+
+    /// Records the layout of `base` instantiated with `args` under the name of `ty`.
+    fn named(&mut self, ty: TypeId, base: TypeId, args: &[TypeId]) -> AbiType {
+        let base = self.ctx.resolve_id(base);
+        let name = match self.ctx.get(base) {
+            TypeKind::Struct { name, .. } | TypeKind::Enum { name, .. } if !args.is_empty() => {
+                let args: Vec<String> = args.iter().map(|a| self.ctx.type_to_display(*a)).collect();
+                format!("{}<{}>", name, args.join(","))
+            }
+            _ => self.ctx.type_to_display(ty),
+        };
+        if !self.seen.insert(name.clone()) {
+            return AbiType::Named(name);
+        }
+        let subst = |params: &[TypeId], t: TypeId| -> TypeId {
+            match params.iter().position(|p| *p == t) {
+                Some(i) => args.get(i).copied().unwrap_or(t),
+                None => t,
+            }
+        };
+        let layout = match self.ctx.get(base) {
+            TypeKind::Struct {
+                type_params,
+                fields,
+                field_names,
+                ..
+            } => {
+                let mut offset = 0;
+                let mut out = Vec::new();
+                for (field_name, fty) in field_names.iter().zip(fields.iter()) {
+                    let fty = self.abi(subst(&type_params, *fty));
+                    let size = fty.size();
+                    out.push(AbiField {
+                        name: field_name.clone(),
+                        ty: fty,
+                        offset,
+                    });
+                    offset += size;
+                }
+                AbiLayout::Struct {
+                    name: name.clone(),
+                    size: offset,
+                    fields: out,
+                }
+            }
+            TypeKind::Enum {
+                type_params,
+                variants,
+                ..
+            } => {
+                let mut out = Vec::new();
+                for (tag, v) in variants.iter().enumerate() {
+                    let payload = v.payload.map(|p| {
+                        let pty = self.abi(subst(&type_params, p));
+                        let offset = pty.size();
+                        (pty, offset)
+                    });
+                    out.push(AbiVariant {
+                        name: v.name.clone(),
+                        tag: tag as u32,
+                        payload,
+                    });
+                }
+                AbiLayout::Enum {
+                    name: name.clone(),
+                    variants: out,
+                }
+            }
+            _ => return AbiType::Opaque(name),
+        };
+        self.layouts.push(layout);
+        AbiType::Named(name)
+    }
+}
+
+const LAYOUT_NOTE: &[&str] = &[
+    "`str` and aggregates are i32 pointers into `memory`:",
+    "  str     [len: u32 LE][len bytes of UTF-8]",
+    "  struct  fields in order, 4 bytes each (8 for i64 / f64), no padding",
+    "  tuple   same as struct",
+    "  enum    [tag: i32][payload at +4, or +8 for i64 / f64]",
+    "Allocate argument memory with the exported `__nepl_alloc(size)`.",
+];
+
+/// Renders a TypeScript declaration file for the exports.
+pub fn typescript(b: &ExportBindings) -> String {
+    let mut out = String::new();
+    out.push_str("// NEPL wasm exports.\n");
+    out.push_str("// Numbers are wasm values (i64 as bigint, bool as 0 / 1).\n");
+    for line in LAYOUT_NOTE {
+        out.push_str(&format!("// {}\n", line));
+    }
+    out.push_str("\n/** Pointer into `memory`. */\nexport type Ptr = number;\n");
+    out.push_str("/** `[len: u32][UTF-8 bytes]` */\nexport type Str = Ptr;\n");
+    for layout in &b.layouts {
+        out.push('\n');
+        match layout {
+            AbiLayout::Struct { name, size, fields } => {
+                out.push_str(&format!("/**\n * struct {} ({} bytes)\n", name, size));
+                for f in fields {
+                    out.push_str(&format!(
+                        " * - {}: {} @{}\n",
+                        f.name,
+                        abi_label(&f.ty),
+                        f.offset
+                    ));
+                }
+                out.push_str(&format!(" */\nexport type {} = Ptr;\n", type_ident(name)));
+            }
+            AbiLayout::Enum { name, variants } => {
+                out.push_str(&format!("/**\n * enum {}\n", name));
+                for v in variants {
+                    match &v.payload {
+                        Some((ty, offset)) => out.push_str(&format!(
+                            " * - tag {}: {}({} @{})\n",
+                            v.tag,
+                            v.name,
+                            abi_label(ty),
+                            offset
+                        )),
+                        None => out.push_str(&format!(" * - tag {}: {}\n", v.tag, v.name)),
+                    }
+                }
+                out.push_str(&format!(" */\nexport type {} = Ptr;\n", type_ident(name)));
+            }
+        }
+    }
+    out.push_str("\nexport interface NeplExports {\n");
+    out.push_str("  memory: WebAssembly.Memory;\n");
+    out.push_str("  __nepl_alloc(size: number): Ptr;\n");
+    for f in &b.functions {
+        let params = f
+            .params
+            .iter()
+            .map(|(n, t)| format!("{}: {}", n, ts_type(t)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        out.push_str(&format!("  /** `{}` */\n", f.signature));
+        out.push_str(&format!(
+            "  {}({}): {};\n",
+            f.name,
+            params,
+            ts_type(&f.result)
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Renders a Rust module describing the exports and their memory layouts.
+pub fn rust(b: &ExportBindings) -> String {
+    let mut out = String::new();
+    out.push_str("//! NEPL wasm exports.\n//!\n");
+    for line in LAYOUT_NOTE {
+        out.push_str(&format!("//! {}\n", line));
+    }
+    out.push_str("\n#![allow(non_camel_case_types, non_snake_case, dead_code)]\n");
+    out.push_str("\n/// Pointer into the module's `memory`.\npub type Ptr = u32;\n");
+    out.push_str("/// `[len: u32][UTF-8 bytes]`\npub type Str = Ptr;\n");
+    for layout in &b.layouts {
+        out.push('\n');
+        match layout {
+            AbiLayout::Struct { name, size, fields } => {
+                out.push_str(&format!(
+                    "/// `struct {}` ({} bytes), as stored in `memory`.\n",
+                    name, size
+                ));
+                out.push_str("#[repr(C, packed)]\n#[derive(Debug, Clone, Copy)]\n");
+                out.push_str(&format!("pub struct {} {{\n", type_ident(name)));
+                for f in fields {
+                    out.push_str(&format!(
+                        "    pub {}: {},\n",
+                        f.name,
+                        rust_field_type(&f.ty)
+                    ));
+                }
+                out.push_str("}\n");
+            }
+            AbiLayout::Enum { name, variants } => {
+                let ident = type_ident(name);
+                out.push_str(&format!(
+                    "/// `enum {}`: pointer to `[tag: i32][payload]`.\npub type {} = Ptr;\n",
+                    name, ident
+                ));
+                out.push_str(&format!(
+                    "/// Tags of `enum {}`.\npub mod {}_tag {{\n",
+                    name, ident
+                ));
+                for v in variants {
+                    match &v.payload {
+                        Some((ty, offset)) => out.push_str(&format!(
+                            "    /// `{}({})`, payload at offset {}.\n",
+                            v.name,
+                            abi_label(ty),
+                            offset
+                        )),
+                        None => out.push_str(&format!("    /// `{}`\n", v.name)),
+                    }
+                    out.push_str(&format!(
+                        "    pub const {}: i32 = {};\n",
+                        const_ident(&v.name),
+                        v.tag
+                    ));
+                }
+                out.push_str("}\n");
+            }
+        }
+    }
+    out.push_str(
+        "\n/// Export names and wasm signatures, e.g. for\n/// `wasmtime::Instance::get_typed_func::<Params, Results>`.\npub mod exports {\n    #[allow(unused_imports)]\n    use super::*;\n",
+    );
+    for f in &b.functions {
+        let camel = camel_ident(&f.name);
+        let params: Vec<String> = f.params.iter().map(|(_, t)| rust_value_type(t)).collect();
+        let params = if params.len() == 1 {
+            format!("({},)", params[0])
+        } else {
+            format!("({})", params.join(", "))
+        };
+        out.push_str(&format!("\n    /// `{}`\n", f.signature));
+        out.push_str(&format!(
+            "    pub const {}: &str = \"{}\";\n",
+            const_ident(&f.name),
+            f.name
+        ));
+        out.push_str(&format!("    pub type {}Params = {};\n", camel, params));
+        out.push_str(&format!(
+            "    pub type {}Results = {};\n",
+            camel,
+            rust_value_type(&f.result)
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// NEPL spelling of `ty`, as used in [`ExportedFn::signature`].
+pub fn abi_label(ty: &AbiType) -> String {
+    match ty {
+        AbiType::Unit => String::from("()"),
+        AbiType::Bool => String::from("bool"),
+        AbiType::U8 => String::from("u8"),
+        AbiType::I32 => String::from("i32"),
+        AbiType::I64 => String::from("i64"),
+        AbiType::F32 => String::from("f32"),
+        AbiType::F64 => String::from("f64"),
+        AbiType::Str => String::from("str"),
+        AbiType::Named(n) | AbiType::Opaque(n) => n.clone(),
+        AbiType::Tuple(items) => format!(
+            "({})",
+            items.iter().map(abi_label).collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+
+fn ts_type(ty: &AbiType) -> String {
+    match ty {
+        AbiType::Unit => String::from("void"),
+        AbiType::I64 => String::from("bigint"),
+        AbiType::Bool | AbiType::U8 | AbiType::I32 | AbiType::F32 | AbiType::F64 => {
+            String::from("number")
+        }
+        AbiType::Str => String::from("Str"),
+        AbiType::Named(n) => type_ident(n),
+        AbiType::Tuple(_) => format!("Ptr /* {} */", abi_label(ty)),
+        AbiType::Opaque(_) => String::from("number"),
+    }
+}
+
+/// Type of a wasm parameter / result as seen by a Rust host.
+fn rust_value_type(ty: &AbiType) -> String {
+    match ty {
+        AbiType::Unit => String::from("()"),
+        AbiType::Bool | AbiType::U8 | AbiType::I32 | AbiType::Opaque(_) => String::from("i32"),
+        AbiType::I64 => String::from("i64"),
+        AbiType::F32 => String::from("f32"),
+        AbiType::F64 => String::from("f64"),
+        AbiType::Str => String::from("Str"),
+        AbiType::Named(_) | AbiType::Tuple(_) => String::from("Ptr"),
+    }
+}
+
+/// Type of an aggregate field; every field but i64 / f64 takes 4 bytes.
+fn rust_field_type(ty: &AbiType) -> String {
+    match ty {
+        AbiType::Unit => String::from("u32"),
+        AbiType::Named(n) => format!("Ptr /* {} */", n),
+        AbiType::Tuple(_) => format!("Ptr /* {} */", abi_label(ty)),
+        other => rust_value_type(other),
+    }
+}
+
+/// `Option<i32>` -> `Option_i32`.
+fn type_ident(name: &str) -> String {
+    let mut out = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            out.push(c);
+        } else if !out.ends_with('_') {
+            out.push('_');
+        }
+    }
+    out.trim_end_matches('_').into()
+}
+
+fn const_ident(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 && !out.ends_with('_') {
+            out.push('_');
+        }
+        if c.is_ascii_alphanumeric() {
+            out.push(c.to_ascii_uppercase());
+        } else if !out.ends_with('_') {
+            out.push('_');
+        }
+    }
+    out
+}
+
+fn camel_ident(name: &str) -> String {
+    let mut out = String::new();
+    let mut upper = true;
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            if upper {
+                out.push(c.to_ascii_uppercase());
+            } else {
+                out.push(c);
+            }
+            upper = false;
+        } else {
+            upper = true;
+        }
+    }
+    out
+}
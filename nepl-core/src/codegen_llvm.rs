@@ -13,7 +13,10 @@ use alloc::vec::Vec;
 use crate::ast::{Block, FnBody, Ident, Literal, Module, PrefixExpr, PrefixItem, Stmt, TypeExpr};
 use crate::compiler::{BuildProfile, CompileTarget};
 use crate::ast::Directive;
-use crate::hir::{FuncRef, HirBlock, HirBody, HirExpr, HirExprKind, HirFunction, HirModule};
+use crate::hir::{
+    FuncRef, HirBlock, HirBody, HirExpr, HirExprKind, HirFunction, HirGlobal, HirGlobalInit, HirModule,
+};
+use crate::simd;
 use crate::types::{TypeCtx, TypeId, TypeKind};
 
 /// LLVM IR 生成時のエラー。
@@ -90,6 +93,21 @@ pub fn emit_ll_from_module_for_target(
     target: CompileTarget,
     profile: BuildProfile,
 ) -> Result<String, LlvmCodegenError> {
+    // const items are not folded on this path (they stay zero-arg functions),
+    // but `#if[const=...]` gates still have to be decided up front.
+    let gated;
+    let module = if crate::consteval::has_const_gates(module) {
+        let mut m = module.clone();
+        crate::consteval::resolve_const_gates(&mut m, target, profile).map_err(|diags| {
+            LlvmCodegenError::TypecheckFailed {
+                reason: summarize_diagnostics_for_message(&diags),
+            }
+        })?;
+        gated = m;
+        &gated
+    } else {
+        module
+    };
     let mut out = String::new();
     let entry_names = collect_active_entry_names(module, target, profile);
     let mut emitted_functions: Vec<String> = Vec::new();
@@ -202,6 +220,7 @@ enum LlTy {
     I64,
     F32,
     F64,
+    V128,
 }
 
 impl LlTy {
@@ -212,6 +231,7 @@ impl LlTy {
             LlTy::I64 => "i64",
             LlTy::F32 => "float",
             LlTy::F64 => "double",
+            LlTy::V128 => "<4 x i32>",
         }
     }
 }
@@ -238,22 +258,35 @@ struct LowerCtx<'a> {
     function_name: &'a str,
     sigs: &'a BTreeMap<String, FnSig>,
     strings: &'a [String],
+    globals: &'a [HirGlobal],
     out: String,
     tmp_seq: usize,
     label_seq: usize,
     scopes: Vec<BTreeMap<String, LocalBinding>>,
+    /// Return type of the function being lowered.
+    ret_ty: LlTy,
+    /// Enclosing loops: label, continue target and break target.
+    loops: Vec<(Option<String>, String, String)>,
 }
 
 impl<'a> LowerCtx<'a> {
-    fn new(function_name: &'a str, sigs: &'a BTreeMap<String, FnSig>, strings: &'a [String]) -> Self {
+    fn new(
+        function_name: &'a str,
+        sigs: &'a BTreeMap<String, FnSig>,
+        strings: &'a [String],
+        globals: &'a [HirGlobal],
+    ) -> Self {
         Self {
             function_name,
             sigs,
             strings,
+            globals,
             out: String::new(),
             tmp_seq: 0,
             label_seq: 0,
             scopes: Vec::new(),
+            ret_ty: LlTy::Void,
+            loops: Vec::new(),
         }
     }
 
@@ -274,6 +307,13 @@ impl<'a> LowerCtx<'a> {
         label
     }
 
+    /// Emits a terminator and opens an unreachable block for any code after it.
+    fn terminate(&mut self, line: &str) {
+        self.push_line(line);
+        let dead = self.next_label("dead");
+        self.push_line(&format!("{}:", dead));
+    }
+
     fn begin_scope(&mut self) {
         self.scopes.push(BTreeMap::new());
     }
@@ -307,6 +347,15 @@ fn try_lower_entry_from_hir(
     emitted_functions: &mut Vec<String>,
 ) -> Result<String, LlvmCodegenError> {
     let (mut types, mut hir) = build_hir_for_llvm_lowering(module, target, profile)?;
+    let static_diags = crate::consteval::eval_globals(&mut hir, &types);
+    if static_diags
+        .iter()
+        .any(|d| matches!(d.severity, crate::diagnostic::Severity::Error))
+    {
+        return Err(LlvmCodegenError::TypecheckFailed {
+            reason: summarize_diagnostics_for_message(&static_diags),
+        });
+    }
     crate::passes::insert_drops(&mut hir, types.unit());
 
     let mut function_map: BTreeMap<String, &HirFunction> = BTreeMap::new();
@@ -354,6 +403,16 @@ fn try_lower_entry_from_hir(
             }
         }
     }
+    for g in &hir.globals {
+        let symbol = format!("static.{}", g.name);
+        if emitted_functions.iter().any(|n| n == &symbol) {
+            continue;
+        }
+        if let Some(line) = ll_global_definition(&types, g) {
+            out.push_str(&line);
+            emitted_functions.push(symbol);
+        }
+    }
     if !reachable.is_empty() {
         out.push('\n');
     }
@@ -384,6 +443,16 @@ fn try_lower_entry_from_hir(
         }
     }
 
+    for (callee, decl) in [
+        ("@llvm.memmove.p0.i64(", LL_MEMMOVE_DECL),
+        ("@llvm.memset.p0.i64(", LL_MEMSET_DECL),
+    ] {
+        if out.contains(callee) && !out.contains(decl) {
+            out.push_str(decl);
+            out.push_str("\n\n");
+        }
+    }
+
     if resolved_entry == "main" && emitted_functions.iter().any(|n| n == "__nepl_entry_main") {
         out.push_str("define i32 @main() {\nentry:\n  call void @__nepl_entry_main()\n  ret i32 0\n}\n\n");
         emitted_functions.push(String::from("main"));
@@ -497,12 +566,14 @@ fn collect_callees_in_expr(expr: &HirExpr, out: &mut BTreeSet<String>) {
             collect_callees_in_expr(then_branch, out);
             collect_callees_in_expr(else_branch, out);
         }
-        HirExprKind::While { cond, body } => {
+        HirExprKind::While { cond, body, .. } => {
             collect_callees_in_expr(cond, out);
             collect_callees_in_expr(body, out);
         }
         HirExprKind::Block(b) => collect_callees_in_block(b, out),
-        HirExprKind::Let { value, .. } | HirExprKind::Set { value, .. } => {
+        HirExprKind::Let { value, .. }
+        | HirExprKind::Set { value, .. }
+        | HirExprKind::GlobalSet { value, .. } => {
             collect_callees_in_expr(value, out);
         }
         HirExprKind::Intrinsic { args, .. } => {
@@ -510,7 +581,9 @@ fn collect_callees_in_expr(expr: &HirExpr, out: &mut BTreeSet<String>) {
                 collect_callees_in_expr(a, out);
             }
         }
-        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) => collect_callees_in_expr(inner, out),
+        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) | HirExprKind::Return(inner) => {
+            collect_callees_in_expr(inner, out)
+        }
         HirExprKind::Match { scrutinee, arms } => {
             collect_callees_in_expr(scrutinee, out);
             for arm in arms {
@@ -535,11 +608,17 @@ fn collect_callees_in_expr(expr: &HirExpr, out: &mut BTreeSet<String>) {
         }
         HirExprKind::LiteralI32(_)
         | HirExprKind::LiteralF32(_)
+        | HirExprKind::LiteralI64(_)
+        | HirExprKind::LiteralF64(_)
         | HirExprKind::LiteralBool(_)
         | HirExprKind::LiteralStr(_)
+        | HirExprKind::StaticAddr(_)
+        | HirExprKind::GlobalGet(_)
         | HirExprKind::Unit
         | HirExprKind::Var(_)
         | HirExprKind::FnValue(_)
+        | HirExprKind::Break(_)
+        | HirExprKind::Continue(_)
         | HirExprKind::Drop { .. } => {}
     }
 }
@@ -558,7 +637,7 @@ fn lower_hir_function(
         ret_ty = LlTy::Void;
     }
 
-    let mut ctx = LowerCtx::new(func.name.as_str(), sigs, &module.string_literals);
+    let mut ctx = LowerCtx::new(func.name.as_str(), sigs, &module.string_literals, &module.globals);
     let mut params = Vec::new();
     for (idx, p) in func.params.iter().enumerate() {
         let pty = llty_for_type(types, p.ty);
@@ -571,6 +650,7 @@ fn lower_hir_function(
         params.join(", ")
     ));
     ctx.push_line("entry:");
+    ctx.ret_ty = ret_ty;
 
     ctx.begin_scope();
     for (idx, p) in func.params.iter().enumerate() {
@@ -608,6 +688,7 @@ fn lower_hir_function(
                     LlTy::I64 => "0",
                     LlTy::F32 => "0.0",
                     LlTy::F64 => "0.0",
+                    LlTy::V128 => "zeroinitializer",
                     LlTy::Void => "",
                 };
                 ctx.push_line(&format!("  ret {} {}", ret_ty.ir(), zero));
@@ -650,11 +731,23 @@ fn lower_hir_expr(
             ty: LlTy::F32,
             repr: format!("{}", v),
         })),
+        HirExprKind::LiteralI64(v) => Ok(Some(LlValue {
+            ty: LlTy::I64,
+            repr: format!("{}", v),
+        })),
+        HirExprKind::LiteralF64(v) => Ok(Some(LlValue {
+            ty: LlTy::F64,
+            repr: format!("0x{:016X}", v.to_bits()),
+        })),
         HirExprKind::LiteralBool(v) => Ok(Some(LlValue {
             ty: LlTy::I32,
             repr: if *v { String::from("1") } else { String::from("0") },
         })),
         HirExprKind::LiteralStr(id) => lower_hir_string_literal(types, ctx, *id as usize),
+        HirExprKind::StaticAddr(_) => Err(LlvmCodegenError::UnsupportedHirLowering {
+            function: ctx.function_name.to_string(),
+            reason: String::from("static data is not supported by the llvm backend yet"),
+        }),
         HirExprKind::Unit => Ok(None),
         HirExprKind::Var(name) => {
             let Some(binding) = ctx.lookup_local(name.as_str()) else {
@@ -719,6 +812,29 @@ fn lower_hir_expr(
             ));
             Ok(None)
         }
+        HirExprKind::GlobalGet(index) => {
+            let (symbol, ty) = ll_global_access(types, ctx, *index)?;
+            if matches!(ty, LlTy::Void) {
+                return Ok(None);
+            }
+            let tmp = ctx.next_tmp();
+            ctx.push_line(&format!("  {} = load {}, {}* {}", tmp, ty.ir(), ty.ir(), symbol));
+            Ok(Some(LlValue { ty, repr: tmp }))
+        }
+        HirExprKind::GlobalSet { index, value } => {
+            let (symbol, ty) = ll_global_access(types, ctx, *index)?;
+            let Some(v) = lower_hir_expr(types, ctx, value)? else {
+                return Ok(None);
+            };
+            if v.ty != ty {
+                return Err(LlvmCodegenError::UnsupportedHirLowering {
+                    function: ctx.function_name.to_string(),
+                    reason: format!("static set type mismatch {:?} -> {:?}", v.ty, ty),
+                });
+            }
+            ctx.push_line(&format!("  store {} {}, {}* {}", v.ty.ir(), v.repr, ty.ir(), symbol));
+            Ok(None)
+        }
         HirExprKind::Call { callee, args } => {
             let callee_name = match callee {
                 FuncRef::Builtin(name) | FuncRef::User(name, _) => name.as_str(),
@@ -872,7 +988,7 @@ fn lower_hir_expr(
                 Ok(None)
             }
         }
-        HirExprKind::While { cond, body } => {
+        HirExprKind::While { cond, body, label } => {
             let cond_label = ctx.next_label("while_cond");
             let body_label = ctx.next_label("while_body");
             let end_label = ctx.next_label("while_end");
@@ -897,7 +1013,10 @@ fn lower_hir_expr(
                 cmp, body_label, end_label
             ));
             ctx.push_line(&format!("{}:", body_label));
+            ctx.loops
+                .push((label.clone(), cond_label.clone(), end_label.clone()));
             let _ = lower_hir_expr(types, ctx, body)?;
+            ctx.loops.pop();
             ctx.push_line(&format!("  br label %{}", cond_label));
             ctx.push_line(&format!("{}:", end_label));
             Ok(None)
@@ -906,13 +1025,14 @@ fn lower_hir_expr(
         HirExprKind::Intrinsic {
             name,
             type_args,
-            args: _,
+            args,
         } => {
             if name == "size_of" || name == "align_of" {
                 if let Some(ty) = type_args.first() {
                     let size = match types.get(types.resolve_id(*ty)) {
                         TypeKind::U8 => 1,
                         TypeKind::Named(ref n) if n == "i64" || n == "f64" => 8,
+                        TypeKind::Named(ref n) if n == "v128" => 16,
                         TypeKind::Unit => 0,
                         _ => 4,
                     };
@@ -926,12 +1046,54 @@ fn lower_hir_expr(
                 ctx.push_line("  unreachable");
                 return Ok(None);
             }
+            if name == "mem_copy" || name == "mem_fill" {
+                return lower_hir_mem_intrinsic(types, ctx, name, args);
+            }
+            if let Some(op) = simd::lookup(name) {
+                return lower_hir_simd_intrinsic(types, ctx, name, op, args);
+            }
             Err(LlvmCodegenError::UnsupportedHirLowering {
                 function: ctx.function_name.to_string(),
                 reason: format!("unsupported intrinsic '{}'", name),
             })
         }
         HirExprKind::Drop { .. } => Ok(None),
+        HirExprKind::Return(value) => {
+            let v = lower_hir_expr(types, ctx, value)?;
+            match (ctx.ret_ty, v) {
+                (LlTy::Void, _) => ctx.terminate("  ret void"),
+                (ty, Some(v)) if v.ty == ty => {
+                    ctx.terminate(&format!("  ret {} {}", ty.ir(), v.repr))
+                }
+                (ty, v) => {
+                    return Err(LlvmCodegenError::UnsupportedHirLowering {
+                        function: ctx.function_name.to_string(),
+                        reason: format!("return type mismatch {:?} -> {:?}", v.map(|v| v.ty), ty),
+                    })
+                }
+            }
+            Ok(None)
+        }
+        HirExprKind::Break(target) | HirExprKind::Continue(target) => {
+            let Some((_, cond_label, end_label)) = ctx
+                .loops
+                .iter()
+                .rev()
+                .find(|(label, _, _)| target.is_none() || target == label)
+                .cloned()
+            else {
+                return Err(LlvmCodegenError::UnsupportedHirLowering {
+                    function: ctx.function_name.to_string(),
+                    reason: String::from("loop exit outside of a loop"),
+                });
+            };
+            let dest = match expr.kind {
+                HirExprKind::Break(_) => end_label,
+                _ => cond_label,
+            };
+            ctx.terminate(&format!("  br label %{}", dest));
+            Ok(None)
+        }
         other => Err(LlvmCodegenError::UnsupportedHirLowering {
             function: ctx.function_name.to_string(),
             reason: format!("unsupported expression kind {:?}", other),
@@ -939,6 +1101,49 @@ fn lower_hir_expr(
     }
 }
 
+fn ll_global_definition(types: &TypeCtx, g: &HirGlobal) -> Option<String> {
+    let ty = llty_for_type(types, g.ty);
+    let init = match (g.init.as_ref()?, ty) {
+        (_, LlTy::Void) | (HirGlobalInit::Slot(_), _) => return None,
+        (HirGlobalInit::I32(v), _) => format!("{}", v),
+        (HirGlobalInit::I64(v), _) => format!("{}", v),
+        // LLVM spells float constants as the bit pattern of the widened double.
+        (HirGlobalInit::F32(v), _) => format!("0x{:016X}", (*v as f64).to_bits()),
+        (HirGlobalInit::F64(v), _) => format!("0x{:016X}", v.to_bits()),
+    };
+    let kind = if g.mutable { "global" } else { "constant" };
+    Some(format!(
+        "{} = internal {} {} {}\n",
+        ll_symbol(&format!("static.{}", g.name)),
+        kind,
+        ty.ir(),
+        init
+    ))
+}
+
+fn ll_global_access(
+    types: &TypeCtx,
+    ctx: &LowerCtx<'_>,
+    index: u32,
+) -> Result<(String, LlTy), LlvmCodegenError> {
+    let Some(g) = ctx.globals.get(index as usize) else {
+        return Err(LlvmCodegenError::UnsupportedHirLowering {
+            function: ctx.function_name.to_string(),
+            reason: format!("static index {} was out of bounds", index),
+        });
+    };
+    match g.init {
+        Some(HirGlobalInit::Slot(_)) | None => Err(LlvmCodegenError::UnsupportedHirLowering {
+            function: ctx.function_name.to_string(),
+            reason: String::from("static data is not supported by the llvm backend yet"),
+        }),
+        Some(_) => Ok((
+            ll_symbol(&format!("static.{}", g.name)),
+            llty_for_type(types, g.ty),
+        )),
+    }
+}
+
 fn lower_hir_string_literal(
     _types: &TypeCtx,
     ctx: &mut LowerCtx<'_>,
@@ -991,6 +1196,192 @@ fn lower_hir_string_literal(
     }))
 }
 
+const LL_MEMMOVE_DECL: &str = "declare void @llvm.memmove.p0.i64(ptr, ptr, i64, i1)";
+const LL_MEMSET_DECL: &str = "declare void @llvm.memset.p0.i64(ptr, i8, i64, i1)";
+
+/// `mem_copy` / `mem_fill` on the pseudo linear memory `@__nepl_mem` of `core/mem`.
+/// `mem_copy` becomes `llvm.memmove` because wasm `memory.copy` allows overlap.
+fn lower_hir_mem_intrinsic(
+    types: &TypeCtx,
+    ctx: &mut LowerCtx<'_>,
+    name: &str,
+    args: &[HirExpr],
+) -> Result<Option<LlValue>, LlvmCodegenError> {
+    let mut ops = Vec::new();
+    for arg in args {
+        let Some(v) = lower_hir_expr(types, ctx, arg)? else {
+            return Err(LlvmCodegenError::UnsupportedHirLowering {
+                function: ctx.function_name.to_string(),
+                reason: format!("intrinsic '{}' expects i32 arguments", name),
+            });
+        };
+        ops.push(v.repr);
+    }
+    if ops.len() != 3 {
+        return Err(LlvmCodegenError::UnsupportedHirLowering {
+            function: ctx.function_name.to_string(),
+            reason: format!("intrinsic '{}' expects 3 arguments", name),
+        });
+    }
+    let addr = |ctx: &mut LowerCtx<'_>, offset: &str| {
+        let idx = ctx.next_tmp();
+        ctx.push_line(&format!("  {} = zext i32 {} to i64", idx, offset));
+        let ptr = ctx.next_tmp();
+        ctx.push_line(&format!(
+            "  {} = getelementptr inbounds i8, ptr @__nepl_mem, i64 {}",
+            ptr, idx
+        ));
+        ptr
+    };
+    let dst = addr(ctx, &ops[0]);
+    let len = ctx.next_tmp();
+    ctx.push_line(&format!("  {} = zext i32 {} to i64", len, ops[2]));
+    if name == "mem_copy" {
+        let src = addr(ctx, &ops[1]);
+        ctx.push_line(&format!(
+            "  call void @llvm.memmove.p0.i64(ptr {}, ptr {}, i64 {}, i1 false)",
+            dst, src, len
+        ));
+    } else {
+        let byte = ctx.next_tmp();
+        ctx.push_line(&format!("  {} = trunc i32 {} to i8", byte, ops[1]));
+        ctx.push_line(&format!(
+            "  call void @llvm.memset.p0.i64(ptr {}, i8 {}, i64 {}, i1 false)",
+            dst, byte, len
+        ));
+    }
+    Ok(None)
+}
+
+/// Scalar fallback for the `simd` intrinsics: `v128` is a `<4 x i32>` and every
+/// op is spelled lane by lane with extractelement / insertelement, f32 lanes
+/// going through bitcasts.
+fn lower_hir_simd_intrinsic(
+    types: &TypeCtx,
+    ctx: &mut LowerCtx<'_>,
+    name: &str,
+    op: simd::SimdOp,
+    args: &[HirExpr],
+) -> Result<Option<LlValue>, LlvmCodegenError> {
+    use simd::{BinOp, Lane, Operand, SimdOp};
+    let mut ops = Vec::new();
+    let mut lanes = Vec::new();
+    for (arg, operand) in args.iter().zip(op.operands()) {
+        if let Operand::LaneIndex(_) = operand {
+            if let HirExprKind::LiteralI32(v) = arg.kind {
+                lanes.push(v as u32);
+                continue;
+            }
+        }
+        let Some(v) = lower_hir_expr(types, ctx, arg)? else {
+            return Err(LlvmCodegenError::UnsupportedHirLowering {
+                function: ctx.function_name.to_string(),
+                reason: format!("intrinsic '{}' expects value arguments", name),
+            });
+        };
+        ops.push(v);
+    }
+    let extract = |ctx: &mut LowerCtx<'_>, v: &str, lane: u32| {
+        let t = ctx.next_tmp();
+        ctx.push_line(&format!("  {} = extractelement <4 x i32> {}, i32 {}", t, v, lane));
+        t
+    };
+    let insert = |ctx: &mut LowerCtx<'_>, acc: &str, x: &str, lane: u32| {
+        let t = ctx.next_tmp();
+        ctx.push_line(&format!(
+            "  {} = insertelement <4 x i32> {}, i32 {}, i32 {}",
+            t, acc, x, lane
+        ));
+        t
+    };
+    let bitcast = |ctx: &mut LowerCtx<'_>, v: &str, from: &str, to: &str| {
+        let t = ctx.next_tmp();
+        ctx.push_line(&format!("  {} = bitcast {} {} to {}", t, from, v, to));
+        t
+    };
+    let lane_bits = |ctx: &mut LowerCtx<'_>, v: &LlValue| match v.ty {
+        LlTy::F32 => bitcast(ctx, &v.repr, "float", "i32"),
+        _ => v.repr.clone(),
+    };
+    let result = match op {
+        SimdOp::Splat(_) => {
+            let x = lane_bits(ctx, &ops[0]);
+            let mut acc = String::from("poison");
+            for lane in 0..simd::LANES as u32 {
+                acc = insert(ctx, &acc, &x, lane);
+            }
+            LlValue {
+                ty: LlTy::V128,
+                repr: acc,
+            }
+        }
+        SimdOp::ExtractLane(lane) => {
+            let x = extract(ctx, &ops[0].repr, lanes[0]);
+            match lane {
+                Lane::I32 => LlValue {
+                    ty: LlTy::I32,
+                    repr: x,
+                },
+                Lane::F32 => LlValue {
+                    ty: LlTy::F32,
+                    repr: bitcast(ctx, &x, "i32", "float"),
+                },
+            }
+        }
+        SimdOp::ReplaceLane(_) => {
+            let x = lane_bits(ctx, &ops[1]);
+            LlValue {
+                ty: LlTy::V128,
+                repr: insert(ctx, &ops[0].repr, &x, lanes[0]),
+            }
+        }
+        SimdOp::Binary(lane_ty, bin) => {
+            let (inst, scalar) = match (lane_ty, bin) {
+                (Lane::I32, BinOp::Add) => ("add", "i32"),
+                (Lane::I32, BinOp::Sub) => ("sub", "i32"),
+                (Lane::I32, BinOp::Mul) => ("mul", "i32"),
+                (Lane::I32, BinOp::Div) => ("sdiv", "i32"),
+                (Lane::F32, BinOp::Add) => ("fadd", "float"),
+                (Lane::F32, BinOp::Sub) => ("fsub", "float"),
+                (Lane::F32, BinOp::Mul) => ("fmul", "float"),
+                (Lane::F32, BinOp::Div) => ("fdiv", "float"),
+            };
+            let mut acc = String::from("poison");
+            for lane in 0..simd::LANES as u32 {
+                let mut a = extract(ctx, &ops[0].repr, lane);
+                let mut b = extract(ctx, &ops[1].repr, lane);
+                if lane_ty == Lane::F32 {
+                    a = bitcast(ctx, &a, "i32", "float");
+                    b = bitcast(ctx, &b, "i32", "float");
+                }
+                let mut r = ctx.next_tmp();
+                ctx.push_line(&format!("  {} = {} {} {}, {}", r, inst, scalar, a, b));
+                if lane_ty == Lane::F32 {
+                    r = bitcast(ctx, &r, "float", "i32");
+                }
+                acc = insert(ctx, &acc, &r, lane);
+            }
+            LlValue {
+                ty: LlTy::V128,
+                repr: acc,
+            }
+        }
+        SimdOp::Shuffle => {
+            let mut acc = String::from("poison");
+            for (i, lane) in lanes.iter().enumerate() {
+                let src = &ops[(*lane / simd::LANES as u32) as usize].repr;
+                let x = extract(ctx, src, lane % simd::LANES as u32);
+                acc = insert(ctx, &acc, &x, i as u32);
+            }
+            LlValue {
+                ty: LlTy::V128,
+                repr: acc,
+            }
+        }
+    };
+    Ok(Some(result))
+}
+
 fn llty_for_type(types: &TypeCtx, ty: TypeId) -> LlTy {
     match types.get(types.resolve_id(ty)) {
         TypeKind::Unit | TypeKind::Never => LlTy::Void,
@@ -998,6 +1389,7 @@ fn llty_for_type(types: &TypeCtx, ty: TypeId) -> LlTy {
         TypeKind::F32 => LlTy::F32,
         TypeKind::Named(name) if name == "i64" => LlTy::I64,
         TypeKind::Named(name) if name == "f64" => LlTy::F64,
+        TypeKind::Named(name) if name == "v128" => LlTy::V128,
         TypeKind::Reference(_, _) => LlTy::I32,
         TypeKind::Box(_) => LlTy::I32,
         TypeKind::Tuple { .. } => LlTy::I32,
@@ -1005,8 +1397,9 @@ fn llty_for_type(types: &TypeCtx, ty: TypeId) -> LlTy {
         TypeKind::Enum { .. } => LlTy::I32,
         TypeKind::Apply { .. } => LlTy::I32,
         TypeKind::Function { .. } => LlTy::I32,
-        TypeKind::Var(_) => LlTy::I32,
+        TypeKind::Var(_) | TypeKind::Error => LlTy::I32,
         TypeKind::Named(_) => LlTy::I32,
+        TypeKind::Newtype { inner, .. } => llty_for_type(types, inner),
     }
 }
 
@@ -1095,6 +1488,7 @@ fn gate_allows(d: &Directive, target: CompileTarget, profile: BuildProfile) -> O
     match d {
         Directive::IfTarget { target: gate, .. } => Some(target.allows(gate.as_str())),
         Directive::IfProfile { profile: p, .. } => Some(profile_allows(p.as_str(), profile)),
+        Directive::IfConst { .. } => Some(false),
         _ => None,
     }
 }
@@ -1369,4 +1763,95 @@ fn boot <()->i32> ():
         assert!(ll.contains("call i32 @boot()"));
     }
 
+    #[test]
+    fn emit_ll_lowers_mem_intrinsics_to_llvm_builtins() {
+        let src = r#"
+#target llvm
+#entry main
+#indent 4
+
+#llvmir:
+    @__nepl_mem = internal global [65536 x i8] zeroinitializer, align 16
+
+fn main <()*>i32> ():
+    #intrinsic "mem_fill" <> (16, 65, 8);
+    #intrinsic "mem_copy" <> (32, 16, 8);
+    0
+"#;
+        let module = parse_module(src);
+        let ll = emit_ll_from_module(&module).expect("mem intrinsics should lower");
+        assert!(ll.contains("call void @llvm.memset.p0.i64(ptr"));
+        assert!(ll.contains("call void @llvm.memmove.p0.i64(ptr"));
+        assert_eq!(ll.matches("declare void @llvm.memset.p0.i64").count(), 1);
+        assert_eq!(ll.matches("declare void @llvm.memmove.p0.i64").count(), 1);
+    }
+
+    #[test]
+    fn emit_ll_lowers_simd_intrinsics_lane_by_lane() {
+        let src = r#"
+#target llvm
+#entry main
+#indent 4
+
+fn splat <(i32)->v128> (x):
+    #intrinsic "i32x4_splat" <> (x)
+
+fn sum2 <(v128,v128)->v128> (a, b):
+    #intrinsic "i32x4_add" <> (a, b)
+
+fn mix <(v128,v128)->v128> (a, b):
+    #intrinsic "i32x4_shuffle" <> (a, b, 0, 5, 2, 7)
+
+fn lane1 <(v128)->i32> (v):
+    #intrinsic "i32x4_extract_lane" <> (v, 1)
+
+fn scale <(v128,v128)->v128> (v, k):
+    #intrinsic "f32x4_mul" <> (v, k)
+
+fn flane <(v128)->f32> (v):
+    #intrinsic "f32x4_extract_lane" <> (v, 2)
+
+fn main <()->i32> ():
+    let a <v128> splat 3
+    let f <f32> flane scale a a
+    lane1 mix a sum2 a a
+"#;
+        let module = parse_module(src);
+        let ll = emit_ll_from_module(&module).expect("simd intrinsics should lower");
+        assert!(ll.contains("(<4 x i32> %p0, <4 x i32> %p1)"));
+        assert!(ll.contains("insertelement <4 x i32> poison, i32"));
+        assert!(ll.contains("extractelement <4 x i32>"));
+        assert!(ll.contains("fmul float"));
+        assert!(ll.contains("bitcast float"));
+        assert!(ll.contains("bitcast i32"));
+        assert!(!ll.contains("shufflevector"));
+    }
+
+    #[test]
+    fn emit_ll_lowers_loop_exits_and_return_to_branches() {
+        let src = r#"
+#target llvm
+#entry main
+#indent 4
+
+fn pick <(bool)*>i32> (c):
+    while <.outer> true:
+        do:
+            while true:
+                do:
+                    if c then break <.outer> else ()
+                    continue <.outer>
+    return 7
+
+fn main <()*>i32> ():
+    pick true
+"#;
+        let module = parse_module(src);
+        let ll = emit_ll_from_module(&module).expect("loop exits should lower");
+        assert!(ll.contains("br label %while_end_2"), "{}", ll);
+        assert!(ll.contains("br label %while_cond_0"), "{}", ll);
+        assert!(ll.contains("ret i32 7"));
+        // Code after a terminator starts a new (unreachable) block.
+        assert!(ll.contains("dead_"));
+    }
 }
@@ -4,7 +4,7 @@
 extern crate alloc;
 extern crate std;
 
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::format;
 use alloc::string::String;
 use alloc::vec;
@@ -13,13 +13,15 @@ use alloc::borrow::Cow;
 
 use wasm_encoder::{
     CodeSection, ConstExpr, DataSection, ElementMode, ElementSection, ElementSegment, Elements,
-    EntityType, ExportKind, ExportSection, Function, FunctionSection, ImportSection, Instruction,
-    MemArg, MemorySection, MemoryType, Module, RefType, TableSection, TableType, TypeSection,
-    ValType,
+    BlockType, EntityType, ExportKind, ExportSection, Function, FunctionSection, GlobalSection,
+    GlobalType, ImportSection, Instruction, MemArg, MemorySection, MemoryType, Module, RefType,
+    TableSection, TableType, TypeSection, ValType,
 };
 
 use crate::diagnostic::Diagnostic;
 use crate::hir::*;
+use crate::passes::escape::IN_FRAME;
+use crate::simd;
 use crate::types::{TypeCtx, TypeId, TypeKind};
 
 #[derive(Debug)]
@@ -31,9 +33,33 @@ pub struct CodegenResult {
 #[derive(Debug, Clone)]
 struct StringLower {
     offsets: Vec<u32>,
+    static_offsets: Vec<u32>,
     segments: Vec<(u32, Vec<u8>)>,
     min_pages: u32,
     heap_base: u32,
+    /// Storage of each `HirModule::globals` entry.
+    globals: Vec<Option<GlobalStorage>>,
+    /// Shadow stack for aggregates placed in frames by the escape analysis.
+    stack: Option<ShadowStack>,
+}
+
+/// Stack region between the static data and the heap; it grows downwards from
+/// `base + SHADOW_STACK_SIZE`.
+#[derive(Debug, Clone, Copy)]
+struct ShadowStack {
+    /// Index of the mutable stack pointer global.
+    global: u32,
+    base: u32,
+}
+
+const SHADOW_STACK_SIZE: u32 = 0x10000;
+
+#[derive(Debug, Clone, Copy)]
+enum GlobalStorage {
+    /// Index into the wasm global section.
+    Global(u32),
+    /// Address of a 4-byte data slot.
+    Slot(u32),
 }
 
 impl StringLower {
@@ -41,6 +67,14 @@ impl StringLower {
         self.offsets.get(idx as usize).copied()
     }
 
+    fn static_offset(&self, idx: u32) -> Option<u32> {
+        self.static_offsets.get(idx as usize).copied()
+    }
+
+    fn global(&self, idx: u32) -> Option<GlobalStorage> {
+        self.globals.get(idx as usize).copied().flatten()
+    }
+
     fn is_empty(&self) -> bool {
         self.offsets.is_empty()
     }
@@ -66,10 +100,72 @@ fn lower_strings(strings: &[String]) -> StringLower {
     let min_pages = ((heap_base + 0xFFFF) / 0x10000).max(1);
     StringLower {
         offsets,
+        static_offsets: Vec::new(),
         segments,
         min_pages,
         heap_base,
+        globals: Vec::new(),
+        stack: None,
+    }
+}
+
+/// Places static data blocks after the string literals and patches their
+/// relocations; the heap then starts after the last block.
+fn lower_statics(lower: &mut StringLower, statics: &[HirStatic]) {
+    let mut cursor = lower.heap_base;
+    for s in statics {
+        cursor = align_to(cursor, s.align.max(4));
+        lower.static_offsets.push(cursor);
+        cursor = cursor.saturating_add(s.bytes.len() as u32);
+    }
+    for (idx, s) in statics.iter().enumerate() {
+        let mut data = s.bytes.clone();
+        for reloc in &s.relocs {
+            let addr = match reloc.target {
+                StaticRef::Str(i) => lower.offset(i),
+                StaticRef::Static(i) => lower.static_offset(i),
+            }
+            .unwrap_or(0);
+            let at = reloc.offset as usize;
+            data[at..at + 4].copy_from_slice(&addr.to_le_bytes());
+        }
+        lower.segments.push((lower.static_offsets[idx], data));
+    }
+    lower.heap_base = align_to(cursor, 4);
+    lower.min_pages = lower.heap_base.div_ceil(0x10000).max(1);
+}
+
+/// Scalar statics become wasm globals; the others already have a data slot
+/// in `lower.static_offsets`.
+fn lower_globals(lower: &mut StringLower, globals: &[HirGlobal]) -> GlobalSection {
+    let mut section = GlobalSection::new();
+    for g in globals {
+        let (val_type, init) = match g.init {
+            Some(HirGlobalInit::Slot(idx)) => {
+                let slot = lower.static_offset(idx).map(GlobalStorage::Slot);
+                lower.globals.push(slot);
+                continue;
+            }
+            Some(HirGlobalInit::I32(v)) => (ValType::I32, ConstExpr::i32_const(v)),
+            Some(HirGlobalInit::I64(v)) => (ValType::I64, ConstExpr::i64_const(v)),
+            Some(HirGlobalInit::F32(v)) => (ValType::F32, ConstExpr::f32_const(v.into())),
+            Some(HirGlobalInit::F64(v)) => (ValType::F64, ConstExpr::f64_const(v.into())),
+            None => {
+                lower.globals.push(None);
+                continue;
+            }
+        };
+        lower.globals.push(Some(GlobalStorage::Global(section.len())));
+        section.global(
+            GlobalType {
+                val_type,
+                mutable: g.mutable,
+                shared: false,
+            },
+            &init,
+        );
     }
+    section
 }
 
 fn align_to(x: u32, align: u32) -> u32 {
@@ -89,27 +185,8 @@ fn collect_indirect_sigs(
             result,
             args,
         } => {
-            let mut p = Vec::new();
-            let mut ok = true;
-            for ty in params {
-                let kind = ctx.get(ctx.resolve_id(*ty));
-                if let Some(vt) = valtype(&kind) {
-                    p.push(vt);
-                } else {
-                    ok = false;
-                    break;
-                }
-            }
-            if ok {
-                let res_kind = ctx.get(ctx.resolve_id(*result));
-                let r = if let Some(vt) = valtype(&res_kind) {
-                    vec![vt]
-                } else if matches!(res_kind, TypeKind::Unit) {
-                    Vec::new()
-                } else {
-                    Vec::new()
-                };
-                out.push((p, r));
+            if let Some(sig) = wasm_sig_ids(ctx, *result, params) {
+                out.push(sig);
             }
             collect_indirect_sigs(callee, out, ctx);
             for a in args {
@@ -130,7 +207,7 @@ fn collect_indirect_sigs(
             collect_indirect_sigs(then_branch, out, ctx);
             collect_indirect_sigs(else_branch, out, ctx);
         }
-        HirExprKind::While { cond, body } => {
+        HirExprKind::While { cond, body, .. } => {
             collect_indirect_sigs(cond, out, ctx);
             collect_indirect_sigs(body, out, ctx);
         }
@@ -160,7 +237,9 @@ fn collect_indirect_sigs(
                 collect_indirect_sigs(&line.expr, out, ctx);
             }
         }
-        HirExprKind::Let { value, .. } | HirExprKind::Set { value, .. } => {
+        HirExprKind::Let { value, .. }
+        | HirExprKind::Set { value, .. }
+        | HirExprKind::GlobalSet { value, .. } => {
             collect_indirect_sigs(value, out, ctx);
         }
         HirExprKind::Intrinsic { args, .. } => {
@@ -168,50 +247,124 @@ fn collect_indirect_sigs(
                 collect_indirect_sigs(a, out, ctx);
             }
         }
-        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) => {
+        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) | HirExprKind::Return(inner) => {
             collect_indirect_sigs(inner, out, ctx);
         }
         HirExprKind::Unit
         | HirExprKind::LiteralI32(_)
         | HirExprKind::LiteralF32(_)
+        | HirExprKind::LiteralI64(_)
+        | HirExprKind::LiteralF64(_)
         | HirExprKind::LiteralBool(_)
         | HirExprKind::LiteralStr(_)
+        | HirExprKind::StaticAddr(_)
+        | HirExprKind::GlobalGet(_)
         | HirExprKind::Var(_)
         | HirExprKind::FnValue(_)
+        | HirExprKind::Break(_)
+        | HirExprKind::Continue(_)
         | HirExprKind::Drop { .. } => {}
     }
 }
 
 pub fn generate_wasm(ctx: &TypeCtx, module: &HirModule) -> CodegenResult {
+    let inlined = inline_memory_reads(module);
+    let module = &inlined;
     let mut diags = Vec::new();
-    let strings = lower_strings(&module.string_literals);
+    let mut strings = lower_strings(&module.string_literals);
+    lower_statics(&mut strings, &module.statics);
+    let mut global_section = lower_globals(&mut strings, &module.globals);
+    if module.functions.iter().any(uses_frame) {
+        let base = align_to(strings.heap_base, 16);
+        let top = base + SHADOW_STACK_SIZE;
+        strings.stack = Some(ShadowStack {
+            global: global_section.len(),
+            base,
+        });
+        global_section.global(
+            GlobalType {
+                val_type: ValType::I32,
+                mutable: true,
+                shared: false,
+            },
+            &ConstExpr::i32_const(top as i32),
+        );
+        strings.heap_base = top;
+        strings.min_pages = top.div_ceil(0x10000).max(1);
+    }
 
     // Build imports / function list (builtins first)
     let mut imports: Vec<ImportLower> = Vec::new();
     let mut functions: Vec<FuncLower> = Vec::new();
 
-    // Extern imports
+    // Extern imports; the host sees small tuples / structs as pointers, so those go through a thunk
+    let mut thunks: Vec<FuncLower> = Vec::new();
     for ext in &module.externs {
-        if let Some(sig) = wasm_sig_ids(ctx, ext.result, &ext.params) {
-            imports.push(ImportLower::function(
-                ext.module.clone(),
-                ext.name.clone(),
-                ext.local_name.clone(),
-                sig.0,
-                sig.1,
-            ));
-        } else {
-            diags.push(Diagnostic::error(
-                "unsupported extern signature for wasm",
-                ext.span,
-            ));
+        match (
+            host_sig(ctx, ext.result, &ext.params),
+            wasm_sig_ids(ctx, ext.result, &ext.params),
+        ) {
+            (Some(host), Some(sig)) if host == sig => {
+                imports.push(ImportLower::function(
+                    ext.module.clone(),
+                    ext.name.clone(),
+                    ext.local_name.clone(),
+                    sig.0,
+                    sig.1,
+                ));
+            }
+            (Some(host), Some(sig)) => {
+                let host_name = format!("{}$host", ext.local_name);
+                imports.push(ImportLower::function(
+                    ext.module.clone(),
+                    ext.name.clone(),
+                    host_name.clone(),
+                    host.0,
+                    host.1,
+                ));
+                thunks.push(FuncLower::thunk(
+                    ext.local_name.clone(),
+                    sig,
+                    Thunk {
+                        kind: ThunkKind::Import,
+                        target: host_name,
+                        params: ext.params.clone(),
+                        result: ext.result,
+                    },
+                ));
+            }
+            _ => {
+                diags.push(Diagnostic::error(
+                    "unsupported extern signature for wasm",
+                    ext.span,
+                ));
+            }
         }
     }
 
-    // User functions
+    // User functions; `#wasm` bodies see small tuples / structs as pointers, like the host
     for f in &module.functions {
         if let Some(sig) = wasm_sig(ctx, f.result, &f.params) {
-            functions.push(FuncLower::user(f, sig));
+            let params: Vec<TypeId> = f.params.iter().map(|p| p.ty).collect();
+            match host_sig(ctx, f.result, &params) {
+                Some(host) if host != sig && matches!(f.body, HirBody::Wasm(_)) => {
+                    let asm_name = format!("{}$asm", f.name);
+                    let mut asm = FuncLower::user(f, host);
+                    asm.name = asm_name.clone();
+                    functions.push(asm);
+                    thunks.push(FuncLower::thunk(
+                        f.name.clone(),
+                        sig,
+                        Thunk {
+                            kind: ThunkKind::Import,
+                            target: asm_name,
+                            params,
+                            result: f.result,
+                        },
+                    ));
+                }
+                _ => functions.push(FuncLower::user(f, sig)),
+            }
         } else {
             if should_skip_wasm_codegen_for_generic(ctx, f) {
                 continue;
@@ -230,6 +383,40 @@ pub fn generate_wasm(ctx: &TypeCtx, module: &HirModule) -> CodegenResult {
             ));
         }
     }
+    functions.append(&mut thunks);
+
+    // Exported functions keep the pointer ABI towards the host
+    let mut exported: Vec<&str> = module.exports.iter().map(|ex| ex.func.as_str()).collect();
+    exported.extend(module.entry.as_deref());
+    exported.sort_unstable();
+    exported.dedup();
+    for name in exported {
+        let Some(f) = module
+            .functions
+            .iter()
+            .find(|f| f.name == name && !matches!(f.body, HirBody::Wasm(_)))
+        else {
+            continue;
+        };
+        let params: Vec<TypeId> = f.params.iter().map(|p| p.ty).collect();
+        if let (Some(host), Some(sig)) = (
+            host_sig(ctx, f.result, &params),
+            wasm_sig(ctx, f.result, &f.params),
+        ) {
+            if host != sig {
+                functions.push(FuncLower::thunk(
+                    format!("{}$host", f.name),
+                    host,
+                    Thunk {
+                        kind: ThunkKind::Export,
+                        target: f.name.clone(),
+                        params,
+                        result: f.result,
+                    },
+                ));
+            }
+        }
+    }
 
     // Map names to indices
     let mut name_to_index = BTreeMap::new();
@@ -243,6 +430,27 @@ pub fn generate_wasm(ctx: &TypeCtx, module: &HirModule) -> CodegenResult {
     }
     let total_function_slots = next_index + functions.len() as u32;
 
+    let asm_env = WasmAsmEnv {
+        funcs: &name_to_index,
+        func_sigs: imports
+            .iter()
+            .map(|imp| (imp.params.clone(), imp.results.clone()))
+            .chain(functions.iter().map(|f| (f.params.clone(), f.results.clone())))
+            .collect(),
+        globals: module
+            .globals
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, g)| match strings.global(idx as u32)? {
+                GlobalStorage::Global(gidx) => {
+                    let vt = valtype(&ctx.get(g.ty))?;
+                    Some((g.name.clone(), (gidx, vt, g.mutable)))
+                }
+                GlobalStorage::Slot(_) => None,
+            })
+            .collect(),
+    };
+
     // Type section dedup
     let mut type_section = TypeSection::new();
     let mut sig_map: BTreeMap<(Vec<ValType>, Vec<ValType>), u32> = BTreeMap::new();
@@ -295,7 +503,7 @@ pub fn generate_wasm(ctx: &TypeCtx, module: &HirModule) -> CodegenResult {
 
     let mut code_section = CodeSection::new();
     for f in &functions {
-        match lower_body(ctx, f, &name_to_index, &sig_map, &strings) {
+        match lower_body(ctx, f, &name_to_index, &sig_map, &strings, &asm_env) {
             Ok(body) => {
                 code_section.function(&body);
             }
@@ -314,10 +522,16 @@ pub fn generate_wasm(ctx: &TypeCtx, module: &HirModule) -> CodegenResult {
         page_size_log2: None,
     });
 
+    let host_index = |name: &str| {
+        name_to_index
+            .get(&format!("{}$host", name))
+            .or_else(|| name_to_index.get(&format!("{}$asm", name)))
+            .or_else(|| name_to_index.get(name))
+    };
     let mut export_section = ExportSection::new();
     export_section.export("memory", ExportKind::Memory, 0);
     if let Some(entry) = &module.entry {
-        if let Some(idx) = name_to_index.get(entry) {
+        if let Some(idx) = host_index(entry) {
             export_section.export("main", ExportKind::Func, *idx);
             if entry != "main" {
                 export_section.export(entry, ExportKind::Func, *idx);
@@ -325,6 +539,23 @@ pub fn generate_wasm(ctx: &TypeCtx, module: &HirModule) -> CodegenResult {
             export_section.export("_start", ExportKind::Func, *idx);
         }
     }
+    for ex in &module.exports {
+        match host_index(&ex.func) {
+            Some(idx) => {
+                export_section.export(&ex.name, ExportKind::Func, *idx);
+            }
+            None => diags.push(Diagnostic::error(
+                format!("exported function '{}' was not generated", ex.name),
+                ex.span,
+            )),
+        }
+    }
+    // Hosts allocate `str` / aggregate arguments through the module's allocator.
+    if !module.exports.is_empty() {
+        if let Some(idx) = find_alloc_index(&name_to_index) {
+            export_section.export("__nepl_alloc", ExportKind::Func, idx);
+        }
+    }
 
     let mut data_section = DataSection::new();
     // Store initial heap pointer (aligned end of static data) at address 0.
@@ -379,6 +610,9 @@ pub fn generate_wasm(ctx: &TypeCtx, module: &HirModule) -> CodegenResult {
         module_bytes.section(&table_section);
     }
     module_bytes.section(&memory_section);
+    if !global_section.is_empty() {
+        module_bytes.section(&global_section);
+    }
     module_bytes.section(&export_section);
     if need_table {
         module_bytes.section(&element_section);
@@ -473,6 +707,24 @@ struct ImportLower {
 #[derive(Debug, Clone)]
 enum FuncBodyLower<'a> {
     User(&'a HirFunction),
+    Thunk(Thunk),
+}
+
+/// Adapter between the host's pointer ABI and the internal multi-value ABI.
+#[derive(Debug, Clone)]
+struct Thunk {
+    kind: ThunkKind,
+    target: String,
+    params: Vec<TypeId>,
+    result: TypeId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ThunkKind {
+    /// Called by the host with pointers, calls a multi-value function.
+    Export,
+    /// Called with multi-values, calls a host import or `#wasm` body taking pointers.
+    Import,
 }
 
 impl<'a> FuncLower<'a> {
@@ -484,6 +736,15 @@ impl<'a> FuncLower<'a> {
             body: FuncBodyLower::User(func),
         }
     }
+
+    fn thunk(name: String, sig: (Vec<ValType>, Vec<ValType>), thunk: Thunk) -> Self {
+        Self {
+            name,
+            params: sig.0,
+            results: sig.1,
+            body: FuncBodyLower::Thunk(thunk),
+        }
+    }
 }
 
 impl ImportLower {
@@ -508,73 +769,222 @@ fn wasm_sig(
     ctx: &TypeCtx,
     result: TypeId,
     params: &[HirParam],
+) -> Option<(Vec<ValType>, Vec<ValType>)> {
+    let ids: Vec<TypeId> = params.iter().map(|p| p.ty).collect();
+    abi_sig(ctx, result, &ids, true)
+}
+
+fn wasm_sig_ids(
+    ctx: &TypeCtx,
+    result: TypeId,
+    params: &[TypeId],
+) -> Option<(Vec<ValType>, Vec<ValType>)> {
+    abi_sig(ctx, result, params, true)
+}
+
+/// Signature seen by the host: small tuples / structs stay pointers to their fields.
+fn host_sig(
+    ctx: &TypeCtx,
+    result: TypeId,
+    params: &[TypeId],
+) -> Option<(Vec<ValType>, Vec<ValType>)> {
+    abi_sig(ctx, result, params, false)
+}
+
+fn abi_sig(
+    ctx: &TypeCtx,
+    result: TypeId,
+    params: &[TypeId],
+    multi_value: bool,
 ) -> Option<(Vec<ValType>, Vec<ValType>)> {
     let mut param_types = Vec::new();
     for p in params {
-        let vk = ctx.get(ctx.resolve_id(p.ty));
-        if let Some(v) = valtype(&vk) {
-            param_types.push(v);
-        } else {
-            if crate::log::is_verbose() {
-                std::eprintln!("wasm_sig: rejected param {} with type {:?}", p.name, vk);
+        match abi_types(ctx, *p, multi_value) {
+            Some(vts) if !vts.is_empty() => param_types.extend(vts),
+            _ => {
+                if crate::log::is_verbose() {
+                    std::eprintln!("wasm_sig: rejected param type {:?}", ctx.get(*p));
+                }
+                return None;
             }
-            return None;
         }
     }
-    let res_kind = ctx.get(ctx.resolve_id(result));
-    let res = if let Some(v) = valtype(&res_kind) {
-        vec![v]
-    } else {
-        if !matches!(res_kind, TypeKind::Unit) {
+    let res = match abi_types(ctx, result, multi_value) {
+        Some(vts) => vts,
+        None => {
             if crate::log::is_verbose() {
-                std::eprintln!("wasm_sig: rejected result type {:?}", res_kind);
+                std::eprintln!("wasm_sig: rejected result type {:?}", ctx.get(result));
             }
             return None;
         }
-        // unit return is fine in wasm
-        Vec::new()
     };
     Some((param_types, res))
 }
 
-fn wasm_sig_ids(
-    ctx: &TypeCtx,
-    result: TypeId,
-    params: &[TypeId],
-) -> Option<(Vec<ValType>, Vec<ValType>)> {
-    let mut param_types = Vec::new();
-    for p in params {
-        let vk = ctx.get(ctx.resolve_id(*p));
-        if let Some(v) = valtype(&vk) {
-            param_types.push(v);
-        } else {
-            return None;
+/// Wasm values carrying one value of `ty`; unit is carried by none.
+fn abi_types(ctx: &TypeCtx, ty: TypeId, multi_value: bool) -> Option<Vec<ValType>> {
+    if multi_value {
+        if let Some(fields) = multi_value_fields(ctx, ty) {
+            return Some(fields.iter().map(|f| f.vt).collect());
         }
     }
-    let res_kind = ctx.get(ctx.resolve_id(result));
-    if crate::log::is_verbose() {
-        std::eprintln!(
-            "wasm_sig: checking result type {:?} with valtype={:?}",
-            res_kind,
-            valtype(&res_kind)
-        );
+    let kind = ctx.get(ty);
+    match valtype(&kind) {
+        Some(vt) => Some(vec![vt]),
+        None if matches!(kind, TypeKind::Unit) => Some(Vec::new()),
+        None => None,
     }
-    let res = if let Some(v) = valtype(&res_kind) {
-        vec![v]
-    } else {
-        if !matches!(res_kind, TypeKind::Unit) {
-            if crate::log::is_verbose() {
-                std::eprintln!("wasm_sig: REJECTED result type {:?}", res_kind);
+}
+
+/// Tuples and structs with at most this many fields travel as wasm multi-values.
+const MULTI_VALUE_MAX_FIELDS: usize = 4;
+
+/// One field of a tuple / struct kept as a separate wasm value.
+#[derive(Debug, Clone, Copy)]
+struct AggField {
+    ty: TypeId,
+    vt: ValType,
+    /// Byte offset of the field when the aggregate lives in memory.
+    offset: u32,
+}
+
+/// Fields of a small tuple / non-generic struct whose fields all have a wasm type.
+///
+/// Such values are passed and returned as multiple wasm values and kept in one
+/// local per field; everything else stays a pointer to heap memory.
+fn multi_value_fields(ctx: &TypeCtx, ty: TypeId) -> Option<Vec<AggField>> {
+    let items = match ctx.get(ty) {
+        TypeKind::Tuple { items } => items,
+        TypeKind::Struct {
+            fields,
+            type_params,
+            ..
+        } if type_params.is_empty() => fields,
+        TypeKind::Named(name) => {
+            let actual = ctx.lookup_named(&name)?;
+            if ctx.resolve_id(actual) == ctx.resolve_id(ty) {
+                return None;
             }
-            return None;
+            return multi_value_fields(ctx, actual);
         }
-        // unit return is fine in wasm
-        Vec::new()
+        _ => return None,
     };
-    Some((param_types, res))
+    if items.is_empty() || items.len() > MULTI_VALUE_MAX_FIELDS {
+        return None;
+    }
+    let mut fields = Vec::with_capacity(items.len());
+    let mut offset = 0;
+    for ty in items {
+        let vt = valtype(&ctx.get(ty))?;
+        fields.push(AggField { ty, vt, offset });
+        offset += slot_size(Some(vt));
+    }
+    Some(fields)
+}
+
+fn aggregate_size(fields: &[AggField]) -> u32 {
+    fields
+        .last()
+        .map(|f| f.offset + slot_size(Some(f.vt)))
+        .unwrap_or(0)
+}
+
+fn load_inst(vt: ValType, offset: u32) -> Instruction<'static> {
+    let align = match vt {
+        ValType::I64 | ValType::F64 => 3,
+        ValType::V128 => 4,
+        _ => 2,
+    };
+    let arg = MemArg {
+        offset: offset as u64,
+        align,
+        memory_index: 0,
+    };
+    match vt {
+        ValType::F32 => Instruction::F32Load(arg),
+        ValType::I64 => Instruction::I64Load(arg),
+        ValType::F64 => Instruction::F64Load(arg),
+        ValType::V128 => Instruction::V128Load(arg),
+        _ => Instruction::I32Load(arg),
+    }
+}
+
+fn store_inst(vt: ValType, offset: u32) -> Instruction<'static> {
+    let align = match vt {
+        ValType::I64 | ValType::F64 => 3,
+        ValType::V128 => 4,
+        _ => 2,
+    };
+    let arg = MemArg {
+        offset: offset as u64,
+        align,
+        memory_index: 0,
+    };
+    match vt {
+        ValType::F32 => Instruction::F32Store(arg),
+        ValType::I64 => Instruction::I64Store(arg),
+        ValType::F64 => Instruction::F64Store(arg),
+        ValType::V128 => Instruction::V128Store(arg),
+        _ => Instruction::I32Store(arg),
+    }
+}
+
+/// Copies the field values on top of the stack into fresh heap memory and leaves the pointer.
+fn emit_materialize(
+    fields: &[AggField],
+    name_map: &BTreeMap<String, u32>,
+    locals: &mut LocalMap,
+    insts: &mut Vec<Instruction<'static>>,
+) {
+    let temps: Vec<u32> = fields.iter().map(|f| locals.alloc_temp(f.vt)).collect();
+    for temp in temps.iter().rev() {
+        insts.push(Instruction::LocalSet(*temp));
+    }
+    insts.push(Instruction::I32Const(aggregate_size(fields) as i32));
+    emit_alloc_call(name_map, locals, insts);
+    let ptr_local = locals.alloc_temp(ValType::I32);
+    insts.push(Instruction::LocalSet(ptr_local));
+    for (f, temp) in fields.iter().zip(&temps) {
+        insts.push(Instruction::LocalGet(ptr_local));
+        insts.push(Instruction::LocalGet(*temp));
+        insts.push(store_inst(f.vt, f.offset));
+    }
+    insts.push(Instruction::LocalGet(ptr_local));
+}
+
+/// Replaces the pointer on top of the stack with the field values it points to.
+fn emit_split(fields: &[AggField], locals: &mut LocalMap, insts: &mut Vec<Instruction<'static>>) {
+    let ptr_local = locals.alloc_temp(ValType::I32);
+    insts.push(Instruction::LocalSet(ptr_local));
+    for f in fields {
+        insts.push(Instruction::LocalGet(ptr_local));
+        insts.push(load_inst(f.vt, f.offset));
+    }
 }
 
-fn valtype(kind: &TypeKind) -> Option<ValType> {
+/// Size (and alignment) of a value slot as reported by `size_of` / `align_of`.
+pub(crate) fn scalar_size(ctx: &TypeCtx, ty: TypeId) -> i32 {
+    match ctx.get(ty) {
+        TypeKind::U8 => 1,
+        TypeKind::Named(name) if name == "i64" || name == "f64" => 8,
+        TypeKind::Named(name) if name == "v128" => 16,
+        other => match valtype(&other) {
+            Some(_) => 4,
+            None => 0,
+        },
+    }
+}
+
+/// Bytes a value of this wasm type occupies in a struct / tuple field or enum payload.
+pub(crate) fn slot_size(vt: Option<ValType>) -> u32 {
+    match vt {
+        Some(ValType::I64) | Some(ValType::F64) => 8,
+        Some(ValType::V128) => 16,
+        _ => 4,
+    }
+}
+
+pub(crate) fn valtype(kind: &TypeKind) -> Option<ValType> {
     match kind {
         TypeKind::Unit => None,
         TypeKind::I32 | TypeKind::U8 | TypeKind::Bool | TypeKind::Str => Some(ValType::I32),
@@ -588,6 +998,7 @@ fn valtype(kind: &TypeKind) -> Option<ValType> {
         TypeKind::Named(name) => match name.as_str() {
             "i64" => Some(ValType::I64),
             "f64" => Some(ValType::F64),
+            "v128" => Some(ValType::V128),
             _ => Some(ValType::I32),
         },
         TypeKind::Apply { .. } => {
@@ -681,6 +1092,29 @@ fn emit_alloc_call(
     }
 }
 
+/// Leaves the address of `size` fresh bytes: a slot of the current stack frame when
+/// the escape analysis placed the aggregate there, heap memory otherwise.
+fn emit_aggregate_alloc(
+    size: u32,
+    name_map: &BTreeMap<String, u32>,
+    locals: &mut LocalMap,
+    insts: &mut Vec<Instruction<'static>>,
+) {
+    if core::mem::take(&mut locals.frame_request) {
+        let offset = align_to(locals.frame_size, 8);
+        locals.frame_size = offset + size;
+        let fp = locals.frame_pointer();
+        insts.push(Instruction::LocalGet(fp));
+        if offset > 0 {
+            insts.push(Instruction::I32Const(offset as i32));
+            insts.push(Instruction::I32Add);
+        }
+    } else {
+        insts.push(Instruction::I32Const(size as i32));
+        emit_alloc_call(name_map, locals, insts);
+    }
+}
+
 fn find_function_value_index(name_map: &BTreeMap<String, u32>, base: &str) -> Option<u32> {
     if let Some(idx) = name_map.get(base) {
         return Some(*idx);
@@ -705,10 +1139,64 @@ fn lower_body<'a>(
     name_map: &BTreeMap<String, u32>,
     sig_map: &BTreeMap<(Vec<ValType>, Vec<ValType>), u32>,
     strings: &StringLower,
+    asm_env: &WasmAsmEnv<'_>,
 ) -> Result<Function, Vec<Diagnostic>> {
-    match func.body {
-        FuncBodyLower::User(f) => lower_user(ctx, f, name_map, sig_map, strings),
+    match &func.body {
+        FuncBodyLower::User(f) => lower_user(ctx, f, name_map, sig_map, strings, asm_env),
+        FuncBodyLower::Thunk(thunk) => lower_thunk(ctx, thunk, func.params.len(), name_map),
+    }
+}
+
+fn lower_thunk(
+    ctx: &TypeCtx,
+    thunk: &Thunk,
+    param_count: usize,
+    name_map: &BTreeMap<String, u32>,
+) -> Result<Function, Vec<Diagnostic>> {
+    let mut locals = LocalMap::new(param_count);
+    let mut insts: Vec<Instruction<'static>> = Vec::new();
+    let mut param = 0u32;
+    for ty in &thunk.params {
+        match (multi_value_fields(ctx, *ty), thunk.kind) {
+            (Some(fields), ThunkKind::Export) => {
+                for f in &fields {
+                    insts.push(Instruction::LocalGet(param));
+                    insts.push(load_inst(f.vt, f.offset));
+                }
+                param += 1;
+            }
+            (Some(fields), ThunkKind::Import) => {
+                for _ in &fields {
+                    insts.push(Instruction::LocalGet(param));
+                    param += 1;
+                }
+                emit_materialize(&fields, name_map, &mut locals, &mut insts);
+            }
+            (None, _) => {
+                insts.push(Instruction::LocalGet(param));
+                param += 1;
+            }
+        }
+    }
+    let Some(idx) = name_map.get(&thunk.target) else {
+        return Err(vec![Diagnostic::error(
+            format!("unknown function {}", thunk.target),
+            crate::span::Span::dummy(),
+        )]);
+    };
+    insts.push(Instruction::Call(*idx));
+    if let Some(fields) = multi_value_fields(ctx, thunk.result) {
+        match thunk.kind {
+            ThunkKind::Export => emit_materialize(&fields, name_map, &mut locals, &mut insts),
+            ThunkKind::Import => emit_split(&fields, &mut locals, &mut insts),
+        }
+    }
+    let mut wasm_func = Function::new(locals.local_decls());
+    for inst in insts {
+        wasm_func.instruction(&inst);
     }
+    wasm_func.instruction(&Instruction::End);
+    Ok(wasm_func)
 }
 
 // ---------------------------------------------------------------------
@@ -721,16 +1209,61 @@ fn lower_user(
     name_map: &BTreeMap<String, u32>,
     sig_map: &BTreeMap<(Vec<ValType>, Vec<ValType>), u32>,
     strings: &StringLower,
+    asm_env: &WasmAsmEnv<'_>,
 ) -> Result<Function, Vec<Diagnostic>> {
     let mut diags = Vec::new();
-    let mut locals = LocalMap::new(func.params.len());
+    let multi_value = !matches!(func.body, HirBody::Wasm(_));
+    let param_ids: Vec<TypeId> = func.params.iter().map(|p| p.ty).collect();
+    let param_count = abi_sig(ctx, func.result, &param_ids, multi_value)
+        .map(|(params, _)| params.len())
+        .unwrap_or(func.params.len());
+    let mut locals = LocalMap::new(param_count);
+    if let HirBody::Block(block) = &func.body {
+        for line in &block.lines {
+            collect_address_taken(&line.expr, &mut locals.pinned);
+        }
+    }
+    // Every exit restores the stack pointer saved here.
+    if strings.stack.is_some() && uses_frame(func) {
+        locals.frame_base = Some(locals.alloc_temp(ValType::I32));
+    }
+    locals.result_fields = multi_value_fields(ctx, func.result).filter(|_| multi_value);
     for p in &func.params {
-        locals.register_param(p.name.clone(), p.ty);
+        locals.register_param(p.name.clone(), p.ty, multi_value, ctx);
     }
 
     let mut insts: Vec<Instruction<'static>> = Vec::new();
 
+    // A parameter whose address is taken is copied to the heap on entry.
+    for p in &func.params {
+        if !locals.pinned.contains(&p.name) {
+            continue;
+        }
+        if let Some((fields, idxs)) = scalarized_local(ctx, &locals, &p.name, p.ty) {
+            for idx in idxs {
+                insts.push(Instruction::LocalGet(idx));
+            }
+            emit_materialize(&fields, name_map, &mut locals, &mut insts);
+            let ptr = locals.ensure_local(p.name.clone(), p.ty, ctx);
+            insts.push(Instruction::LocalSet(ptr));
+        }
+    }
+
     match &func.body {
+        HirBody::Block(block) if multi_value_fields(ctx, func.result).is_some() => {
+            let fields = multi_value_fields(ctx, func.result).unwrap_or_default();
+            gen_block_multi(
+                ctx,
+                block,
+                &fields,
+                name_map,
+                sig_map,
+                strings,
+                &mut locals,
+                &mut insts,
+                &mut diags,
+            );
+        }
         HirBody::Block(block) => {
             let produced = gen_block(
                 ctx,
@@ -768,17 +1301,23 @@ fn lower_user(
             }
         }
         HirBody::Wasm(wb) => {
+            let results = abi_sig(ctx, func.result, &param_ids, false)
+                .map(|(_, results)| results)
+                .unwrap_or_default();
+            let mut asm = WasmAsm::new(ctx, &mut locals, asm_env, results);
+            // The validation state is meaningless after an error, so stop at the first one.
             for line in &wb.lines {
-                match parse_wasm_line(line, &locals) {
-                    Ok(mut v) => insts.append(&mut v),
-                    Err(msg) => diags.push(Diagnostic::error(msg, func.span)),
+                if let Err(msg) = asm.line(line) {
+                    diags.push(Diagnostic::error(msg, func.span));
+                    break;
                 }
             }
             if diags.is_empty() {
-                if let Err(d) = validate_wasm_stack(ctx, func, &locals, &insts) {
-                    diags.push(d);
+                if let Err(msg) = asm.finish() {
+                    diags.push(Diagnostic::error(msg, func.span));
                 }
             }
+            insts = asm.insts;
         }
         HirBody::LlvmIr(_) => {
             diags.push(Diagnostic::error(
@@ -788,6 +1327,29 @@ fn lower_user(
         }
     }
 
+    if let (Some(base), Some(stack)) = (locals.frame_base, strings.stack) {
+        let fp = locals.frame_pointer();
+        let size = align_to(locals.frame_size, 16) as i32;
+        let mut framed = vec![
+            Instruction::GlobalGet(stack.global),
+            Instruction::LocalTee(base),
+            Instruction::I32Const(size),
+            Instruction::I32Sub,
+            Instruction::LocalTee(fp),
+            Instruction::GlobalSet(stack.global),
+            // stack overflow
+            Instruction::LocalGet(fp),
+            Instruction::I32Const(stack.base as i32),
+            Instruction::I32LtU,
+            Instruction::If(BlockType::Empty),
+            Instruction::Unreachable,
+            Instruction::End,
+        ];
+        framed.append(&mut insts);
+        insts = framed;
+        emit_frame_exit(strings, &locals, &mut insts);
+    }
+
     let mut wasm_func = Function::new(locals.local_decls());
     for inst in insts {
         wasm_func.instruction(&inst);
@@ -846,81 +1408,427 @@ fn gen_block(
 fn predeclare_block_locals(ctx: &TypeCtx, block: &HirBlock, locals: &mut LocalMap) {
     for line in &block.lines {
         if let HirExprKind::Let { name, value, .. } = &line.expr.kind {
-            let _ = locals.ensure_local(name.clone(), value.ty, ctx);
+            let _ = declare_local(ctx, locals, name, value.ty);
         }
     }
 }
 
-fn gen_expr(
+/// Small tuples / structs get one local per field unless their address is taken.
+fn declare_local(ctx: &TypeCtx, locals: &mut LocalMap, name: &str, ty: TypeId) -> u32 {
+    match multi_value_fields(ctx, ty) {
+        Some(fields) if !locals.pinned.contains(name) => {
+            locals.ensure_aggregate(String::from(name), &fields)
+        }
+        _ => locals.ensure_local(String::from(name), ty, ctx),
+    }
+}
+
+/// Field layout and field locals of a scalarized tuple / struct variable.
+fn scalarized_local(
     ctx: &TypeCtx,
-    expr: &HirExpr,
-    name_map: &BTreeMap<String, u32>,
-    sig_map: &BTreeMap<(Vec<ValType>, Vec<ValType>), u32>,
-    strings: &StringLower,
-    locals: &mut LocalMap,
-    insts: &mut Vec<Instruction<'static>>,
-    diags: &mut Vec<Diagnostic>,
-) -> Option<ValType> {
-    match &expr.kind {
-        HirExprKind::LiteralI32(v) => {
-            insts.push(Instruction::I32Const(*v));
-            Some(ValType::I32)
+    locals: &LocalMap,
+    name: &str,
+    ty: TypeId,
+) -> Option<(Vec<AggField>, Vec<u32>)> {
+    let fields = multi_value_fields(ctx, ty)?;
+    let idxs = locals.fields_of(locals.lookup(name)?)?;
+    (idxs.len() == fields.len()).then_some((fields, idxs))
+}
+
+/// The field local addressed by `x` / `add x k` when `x` is scalarized (field access
+/// is lowered to `load` / `store` at an offset by the type checker).
+fn scalarized_field(ctx: &TypeCtx, locals: &LocalMap, addr: &HirExpr, vt: ValType) -> Option<u32> {
+    let (base, offset) = match &addr.kind {
+        HirExprKind::Var(_) => (addr, 0),
+        HirExprKind::Intrinsic { name, args, .. } if name == "add" && args.len() == 2 => {
+            match &args[1].kind {
+                HirExprKind::LiteralI32(k) => (&args[0], *k),
+                _ => return None,
+            }
         }
-        HirExprKind::LiteralF32(v) => {
-            insts.push(Instruction::F32Const((*v).into()));
-            Some(ValType::F32)
+        _ => return None,
+    };
+    let HirExprKind::Var(name) = &base.kind else {
+        return None;
+    };
+    let (fields, idxs) = scalarized_local(ctx, locals, name, base.ty)?;
+    let i = fields
+        .iter()
+        .position(|f| f.offset as i32 == offset && f.vt == vt)?;
+    Some(idxs[i])
+}
+
+/// Replaces calls to `load`-style wrappers (`fn load <.T> (ptr): #intrinsic "load" <.T> (ptr)`)
+/// with the intrinsic itself.
+///
+/// A call returns a small aggregate as a multi-value copy, but `let x load<T> p` must keep
+/// aliasing `p` so that `put x ...` writes through; see [`collect_address_taken`].
+fn inline_memory_reads(module: &HirModule) -> HirModule {
+    let readers: BTreeSet<String> = module
+        .functions
+        .iter()
+        .filter(|f| match (&f.body, f.params.first()) {
+            (HirBody::Block(b), Some(p)) if b.lines.len() == 1 => matches!(
+                &b.lines[0].expr.kind,
+                HirExprKind::Intrinsic { name, args, .. }
+                    if name == "load"
+                        && matches!(args.as_slice(), [HirExpr { kind: HirExprKind::Var(v), .. }] if *v == p.name)
+            ),
+            _ => false,
+        })
+        .map(|f| f.name.clone())
+        .collect();
+    let mut module = module.clone();
+    if readers.is_empty() {
+        return module;
+    }
+    for f in &mut module.functions {
+        if let HirBody::Block(b) = &mut f.body {
+            for line in &mut b.lines {
+                inline_reads_in(&mut line.expr, &readers);
+            }
         }
-        HirExprKind::LiteralBool(b) => {
-            insts.push(Instruction::I32Const(if *b { 1 } else { 0 }));
-            Some(ValType::I32)
+    }
+    module
+}
+
+fn inline_reads_in(expr: &mut HirExpr, readers: &BTreeSet<String>) {
+    match &mut expr.kind {
+        HirExprKind::Call { callee, args } => {
+            for a in args.iter_mut() {
+                inline_reads_in(a, readers);
+            }
+            if let FuncRef::User(name, _) = callee {
+                if args.len() == 1 && readers.contains(name.as_str()) {
+                    expr.kind = HirExprKind::Intrinsic {
+                        name: String::from("load"),
+                        type_args: vec![expr.ty],
+                        args: core::mem::take(args),
+                    };
+                }
+            }
         }
-        HirExprKind::LiteralStr(id) => {
-            if let Some(off) = strings.offset(*id) {
-                insts.push(Instruction::I32Const(off as i32));
-                Some(ValType::I32)
-            } else {
-                diags.push(Diagnostic::error(
-                    "string literal not found during codegen",
-                    expr.span,
-                ));
-                None
+        HirExprKind::CallIndirect { callee, args, .. } => {
+            inline_reads_in(callee, readers);
+            for a in args {
+                inline_reads_in(a, readers);
             }
         }
-        HirExprKind::Unit => None,
-        HirExprKind::Var(name) => {
-            if let Some(idx) = locals.lookup(name) {
-                if valtype(&ctx.get(expr.ty)).is_some() {
-                    insts.push(Instruction::LocalGet(idx));
+        HirExprKind::If {
+            cond,
+            then_branch,
+            else_branch,
+        } => {
+            inline_reads_in(cond, readers);
+            inline_reads_in(then_branch, readers);
+            inline_reads_in(else_branch, readers);
+        }
+        HirExprKind::While { cond, body, .. } => {
+            inline_reads_in(cond, readers);
+            inline_reads_in(body, readers);
+        }
+        HirExprKind::Match { scrutinee, arms } => {
+            inline_reads_in(scrutinee, readers);
+            for arm in arms {
+                inline_reads_in(&mut arm.body, readers);
+            }
+        }
+        HirExprKind::EnumConstruct { payload, .. } => {
+            if let Some(p) = payload {
+                inline_reads_in(p, readers);
+            }
+        }
+        HirExprKind::StructConstruct { fields: items, .. }
+        | HirExprKind::TupleConstruct { items }
+        | HirExprKind::Intrinsic { args: items, .. } => {
+            for item in items {
+                inline_reads_in(item, readers);
+            }
+        }
+        HirExprKind::Block(b) => {
+            for line in &mut b.lines {
+                inline_reads_in(&mut line.expr, readers);
+            }
+        }
+        HirExprKind::Let { value, .. }
+        | HirExprKind::Set { value, .. }
+        | HirExprKind::GlobalSet { value, .. } => inline_reads_in(value, readers),
+        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) | HirExprKind::Return(inner) => {
+            inline_reads_in(inner, readers)
+        }
+        HirExprKind::LiteralI32(_)
+        | HirExprKind::LiteralF32(_)
+        | HirExprKind::LiteralI64(_)
+        | HirExprKind::LiteralF64(_)
+        | HirExprKind::LiteralBool(_)
+        | HirExprKind::LiteralStr(_)
+        | HirExprKind::StaticAddr(_)
+        | HirExprKind::GlobalGet(_)
+        | HirExprKind::Unit
+        | HirExprKind::Var(_)
+        | HirExprKind::FnValue(_)
+        | HirExprKind::Break(_)
+        | HirExprKind::Continue(_)
+        | HirExprKind::Drop { .. } => {}
+    }
+}
+
+/// Whether the escape analysis placed any aggregate of `f` in its stack frame.
+fn uses_frame(f: &HirFunction) -> bool {
+    fn walk(expr: &HirExpr) -> bool {
+        match &expr.kind {
+            HirExprKind::Intrinsic { name, .. } if name == IN_FRAME => true,
+            HirExprKind::Call { args, .. }
+            | HirExprKind::Intrinsic { args, .. }
+            | HirExprKind::StructConstruct { fields: args, .. }
+            | HirExprKind::TupleConstruct { items: args } => args.iter().any(walk),
+            HirExprKind::CallIndirect { callee, args, .. } => walk(callee) || args.iter().any(walk),
+            HirExprKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => walk(cond) || walk(then_branch) || walk(else_branch),
+            HirExprKind::While { cond, body, .. } => walk(cond) || walk(body),
+            HirExprKind::Match { scrutinee, arms } => {
+                walk(scrutinee) || arms.iter().any(|arm| walk(&arm.body))
+            }
+            HirExprKind::EnumConstruct { payload, .. } => payload.as_deref().is_some_and(walk),
+            HirExprKind::Block(b) => b.lines.iter().any(|line| walk(&line.expr)),
+            HirExprKind::Let { value, .. }
+            | HirExprKind::Set { value, .. }
+            | HirExprKind::GlobalSet { value, .. }
+            | HirExprKind::AddrOf(value)
+            | HirExprKind::Deref(value)
+            | HirExprKind::Return(value) => walk(value),
+            HirExprKind::Unit
+            | HirExprKind::LiteralI32(_)
+            | HirExprKind::LiteralF32(_)
+            | HirExprKind::LiteralI64(_)
+            | HirExprKind::LiteralF64(_)
+            | HirExprKind::LiteralBool(_)
+            | HirExprKind::LiteralStr(_)
+            | HirExprKind::StaticAddr(_)
+            | HirExprKind::GlobalGet(_)
+            | HirExprKind::Var(_)
+            | HirExprKind::FnValue(_)
+            | HirExprKind::Break(_)
+            | HirExprKind::Continue(_)
+            | HirExprKind::Drop { .. } => false,
+        }
+    }
+    match &f.body {
+        HirBody::Block(b) => b.lines.iter().any(|line| walk(&line.expr)),
+        _ => false,
+    }
+}
+
+fn collect_address_taken(expr: &HirExpr, out: &mut BTreeSet<String>) {
+    match &expr.kind {
+        HirExprKind::AddrOf(inner) => {
+            if let HirExprKind::Var(name) = &inner.kind {
+                out.insert(name.clone());
+            }
+            collect_address_taken(inner, out);
+        }
+        HirExprKind::Deref(inner) | HirExprKind::Return(inner) => collect_address_taken(inner, out),
+        HirExprKind::Call { args, .. } | HirExprKind::Intrinsic { args, .. } => {
+            for a in args {
+                collect_address_taken(a, out);
+            }
+        }
+        HirExprKind::CallIndirect { callee, args, .. } => {
+            collect_address_taken(callee, out);
+            for a in args {
+                collect_address_taken(a, out);
+            }
+        }
+        HirExprKind::If {
+            cond,
+            then_branch,
+            else_branch,
+        } => {
+            collect_address_taken(cond, out);
+            collect_address_taken(then_branch, out);
+            collect_address_taken(else_branch, out);
+        }
+        HirExprKind::While { cond, body, .. } => {
+            collect_address_taken(cond, out);
+            collect_address_taken(body, out);
+        }
+        HirExprKind::Match { scrutinee, arms } => {
+            collect_address_taken(scrutinee, out);
+            for arm in arms {
+                collect_address_taken(&arm.body, out);
+            }
+        }
+        HirExprKind::EnumConstruct { payload, .. } => {
+            if let Some(p) = payload {
+                collect_address_taken(p, out);
+            }
+        }
+        HirExprKind::StructConstruct { fields: items, .. }
+        | HirExprKind::TupleConstruct { items } => {
+            for item in items {
+                collect_address_taken(item, out);
+            }
+        }
+        HirExprKind::Block(b) => {
+            for line in &b.lines {
+                collect_address_taken(&line.expr, out);
+            }
+        }
+        HirExprKind::Let { name, value, .. } => {
+            // `let it *r` / `let it get obj "f"` / `let it load<T> p` alias memory that `put`
+            // must keep writing through, so they stay as a single pointer local.
+            let aliases = match &value.kind {
+                HirExprKind::Deref(_) => true,
+                HirExprKind::Intrinsic { name, .. } => name == "load",
+                _ => false,
+            };
+            if aliases {
+                out.insert(name.clone());
+            }
+            collect_address_taken(value, out);
+        }
+        HirExprKind::Set { value, .. } | HirExprKind::GlobalSet { value, .. } => {
+            collect_address_taken(value, out)
+        }
+        HirExprKind::Unit
+        | HirExprKind::LiteralI32(_)
+        | HirExprKind::LiteralF32(_)
+        | HirExprKind::LiteralI64(_)
+        | HirExprKind::LiteralF64(_)
+        | HirExprKind::LiteralBool(_)
+        | HirExprKind::LiteralStr(_)
+        | HirExprKind::StaticAddr(_)
+        | HirExprKind::GlobalGet(_)
+        | HirExprKind::Var(_)
+        | HirExprKind::FnValue(_)
+        | HirExprKind::Break(_)
+        | HirExprKind::Continue(_)
+        | HirExprKind::Drop { .. } => {}
+    }
+}
+
+/// Like `gen_block`, but the block's value is left as the fields of a small tuple / struct.
+#[allow(clippy::too_many_arguments)]
+fn gen_block_multi(
+    ctx: &TypeCtx,
+    block: &HirBlock,
+    fields: &[AggField],
+    name_map: &BTreeMap<String, u32>,
+    sig_map: &BTreeMap<(Vec<ValType>, Vec<ValType>), u32>,
+    strings: &StringLower,
+    locals: &mut LocalMap,
+    insts: &mut Vec<Instruction<'static>>,
+    diags: &mut Vec<Diagnostic>,
+) {
+    locals.begin_scope();
+    predeclare_block_locals(ctx, block, locals);
+    let tail = block.lines.iter().rposition(|line| !line.drop_result);
+    for (i, line) in block.lines.iter().enumerate() {
+        if Some(i) == tail {
+            gen_multi(ctx, &line.expr, fields, name_map, sig_map, strings, locals, insts, diags);
+            continue;
+        }
+        let val = gen_expr(ctx, &line.expr, name_map, sig_map, strings, locals, insts, diags);
+        if line.drop_result && val.is_some() {
+            insts.push(Instruction::Drop);
+        }
+    }
+    locals.end_scope();
+}
+
+/// Leaves the field values of a small tuple / struct on the stack instead of a pointer,
+/// so constructing, returning and passing one does not touch the heap.
+#[allow(clippy::too_many_arguments)]
+fn gen_multi(
+    ctx: &TypeCtx,
+    expr: &HirExpr,
+    fields: &[AggField],
+    name_map: &BTreeMap<String, u32>,
+    sig_map: &BTreeMap<(Vec<ValType>, Vec<ValType>), u32>,
+    strings: &StringLower,
+    locals: &mut LocalMap,
+    insts: &mut Vec<Instruction<'static>>,
+    diags: &mut Vec<Diagnostic>,
+) {
+    if matches!(ctx.get(expr.ty), TypeKind::Never) {
+        gen_expr(ctx, expr, name_map, sig_map, strings, locals, insts, diags);
+        insts.push(Instruction::Unreachable);
+        return;
+    }
+    match &expr.kind {
+        HirExprKind::StructConstruct { fields: items, .. }
+        | HirExprKind::TupleConstruct { items }
+            if items.len() == fields.len() =>
+        {
+            for item in items {
+                gen_expr(ctx, item, name_map, sig_map, strings, locals, insts, diags);
+            }
+        }
+        HirExprKind::Call { .. } | HirExprKind::CallIndirect { .. } => {
+            gen_call(ctx, expr, name_map, sig_map, strings, locals, insts, diags);
+        }
+        HirExprKind::Intrinsic { name, args, .. } if name == IN_FRAME => {
+            gen_multi(ctx, &args[0], fields, name_map, sig_map, strings, locals, insts, diags);
+        }
+        HirExprKind::Block(b) => {
+            gen_block_multi(ctx, b, fields, name_map, sig_map, strings, locals, insts, diags);
+        }
+        HirExprKind::If {
+            cond,
+            then_branch,
+            else_branch,
+        } => {
+            gen_expr(ctx, cond, name_map, sig_map, strings, locals, insts, diags);
+            let temps: Vec<u32> = fields.iter().map(|f| locals.alloc_temp(f.vt)).collect();
+            insts.push(Instruction::If(BlockType::Empty));
+            for (i, branch) in [then_branch, else_branch].into_iter().enumerate() {
+                if i == 1 {
+                    insts.push(Instruction::Else);
+                }
+                gen_multi(ctx, branch, fields, name_map, sig_map, strings, locals, insts, diags);
+                for temp in temps.iter().rev() {
+                    insts.push(Instruction::LocalSet(*temp));
                 }
-                valtype(&ctx.get(expr.ty))
-            } else if let Some(fidx) = find_function_value_index(name_map, name) {
-                // Function symbols are first-class values in HIR.
-                // Lower them to table/function index constants.
-                insts.push(Instruction::I32Const(fidx as i32));
-                Some(ValType::I32)
-            } else {
-                diags.push(Diagnostic::error(
-                    format!("unknown variable {}", name),
-                    expr.span,
-                ));
-                None
+            }
+            insts.push(Instruction::End);
+            for temp in &temps {
+                insts.push(Instruction::LocalGet(*temp));
             }
         }
-        HirExprKind::FnValue(name) => {
-            if let Some(fidx) = find_function_value_index(name_map, name) {
-                insts.push(Instruction::I32Const(fidx as i32));
-                Some(ValType::I32)
-            } else {
-                diags.push(Diagnostic::error(
-                    format!("unknown function value {}", name),
-                    expr.span,
-                ));
-                None
+        HirExprKind::Var(name) if scalarized_local(ctx, locals, name, expr.ty).is_some() => {
+            if let Some((_, idxs)) = scalarized_local(ctx, locals, name, expr.ty) {
+                for idx in idxs {
+                    insts.push(Instruction::LocalGet(idx));
+                }
             }
         }
+        _ => {
+            gen_expr(ctx, expr, name_map, sig_map, strings, locals, insts, diags);
+            emit_split(fields, locals, insts);
+        }
+    }
+}
+
+/// Emits a direct or indirect call; small tuple / struct arguments and results travel as
+/// their fields.
+#[allow(clippy::too_many_arguments)]
+fn gen_call(
+    ctx: &TypeCtx,
+    expr: &HirExpr,
+    name_map: &BTreeMap<String, u32>,
+    sig_map: &BTreeMap<(Vec<ValType>, Vec<ValType>), u32>,
+    strings: &StringLower,
+    locals: &mut LocalMap,
+    insts: &mut Vec<Instruction<'static>>,
+    diags: &mut Vec<Diagnostic>,
+) {
+    match &expr.kind {
         HirExprKind::Call { callee, args } => {
             for arg in args {
-                gen_expr(ctx, arg, name_map, sig_map, strings, locals, insts, diags);
+                gen_arg(ctx, arg, arg.ty, name_map, sig_map, strings, locals, insts, diags);
             }
             if let Some(idx) = match callee {
                 FuncRef::Builtin(n) | FuncRef::User(n, _) => name_map.get(n),
@@ -942,7 +1850,6 @@ fn gen_expr(
                     expr.span,
                 ));
             }
-            valtype(&ctx.get(expr.ty))
         }
         HirExprKind::CallIndirect {
             callee,
@@ -950,8 +1857,8 @@ fn gen_expr(
             result,
             args,
         } => {
-            for arg in args {
-                gen_expr(ctx, arg, name_map, sig_map, strings, locals, insts, diags);
+            for (arg, ty) in args.iter().zip(params) {
+                gen_arg(ctx, arg, *ty, name_map, sig_map, strings, locals, insts, diags);
             }
             gen_expr(ctx, callee, name_map, sig_map, strings, locals, insts, diags);
             if let Some(sig) = wasm_sig_ids(ctx, *result, params) {
@@ -972,6 +1879,185 @@ fn gen_expr(
                     expr.span,
                 ));
             }
+        }
+        _ => {
+            gen_expr(ctx, expr, name_map, sig_map, strings, locals, insts, diags);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn gen_arg(
+    ctx: &TypeCtx,
+    arg: &HirExpr,
+    param_ty: TypeId,
+    name_map: &BTreeMap<String, u32>,
+    sig_map: &BTreeMap<(Vec<ValType>, Vec<ValType>), u32>,
+    strings: &StringLower,
+    locals: &mut LocalMap,
+    insts: &mut Vec<Instruction<'static>>,
+    diags: &mut Vec<Diagnostic>,
+) {
+    match multi_value_fields(ctx, param_ty) {
+        Some(fields) => {
+            gen_multi(ctx, arg, &fields, name_map, sig_map, strings, locals, insts, diags)
+        }
+        None => {
+            gen_expr(ctx, arg, name_map, sig_map, strings, locals, insts, diags);
+        }
+    }
+}
+
+fn gen_expr(
+    ctx: &TypeCtx,
+    expr: &HirExpr,
+    name_map: &BTreeMap<String, u32>,
+    sig_map: &BTreeMap<(Vec<ValType>, Vec<ValType>), u32>,
+    strings: &StringLower,
+    locals: &mut LocalMap,
+    insts: &mut Vec<Instruction<'static>>,
+    diags: &mut Vec<Diagnostic>,
+) -> Option<ValType> {
+    match &expr.kind {
+        HirExprKind::LiteralI32(v) => {
+            insts.push(Instruction::I32Const(*v));
+            Some(ValType::I32)
+        }
+        HirExprKind::LiteralF32(v) => {
+            insts.push(Instruction::F32Const((*v).into()));
+            Some(ValType::F32)
+        }
+        HirExprKind::LiteralI64(v) => {
+            insts.push(Instruction::I64Const(*v));
+            Some(ValType::I64)
+        }
+        HirExprKind::LiteralF64(v) => {
+            insts.push(Instruction::F64Const((*v).into()));
+            Some(ValType::F64)
+        }
+        HirExprKind::LiteralBool(b) => {
+            insts.push(Instruction::I32Const(if *b { 1 } else { 0 }));
+            Some(ValType::I32)
+        }
+        HirExprKind::LiteralStr(id) => {
+            if let Some(off) = strings.offset(*id) {
+                insts.push(Instruction::I32Const(off as i32));
+                Some(ValType::I32)
+            } else {
+                diags.push(Diagnostic::error(
+                    "string literal not found during codegen",
+                    expr.span,
+                ));
+                None
+            }
+        }
+        HirExprKind::StaticAddr(idx) => {
+            if let Some(off) = strings.static_offset(*idx) {
+                insts.push(Instruction::I32Const(off as i32));
+                Some(ValType::I32)
+            } else {
+                diags.push(Diagnostic::error(
+                    "static data not found during codegen",
+                    expr.span,
+                ));
+                None
+            }
+        }
+        HirExprKind::GlobalGet(idx) => {
+            let vt = valtype(&ctx.get(expr.ty));
+            match strings.global(*idx) {
+                Some(GlobalStorage::Global(g)) => {
+                    if vt.is_some() {
+                        insts.push(Instruction::GlobalGet(g));
+                    }
+                    vt
+                }
+                Some(GlobalStorage::Slot(addr)) => {
+                    insts.push(Instruction::I32Const(addr as i32));
+                    insts.push(Instruction::I32Load(MemArg {
+                        offset: 0,
+                        align: 2,
+                        memory_index: 0,
+                    }));
+                    vt
+                }
+                None => {
+                    diags.push(Diagnostic::error(
+                        "static storage not found during codegen",
+                        expr.span,
+                    ));
+                    None
+                }
+            }
+        }
+        HirExprKind::GlobalSet { index, value } => {
+            match strings.global(*index) {
+                Some(GlobalStorage::Global(g)) => {
+                    let vt = gen_expr(ctx, value, name_map, sig_map, strings, locals, insts, diags);
+                    if vt.is_some() {
+                        insts.push(Instruction::GlobalSet(g));
+                    }
+                }
+                Some(GlobalStorage::Slot(addr)) => {
+                    insts.push(Instruction::I32Const(addr as i32));
+                    gen_expr(ctx, value, name_map, sig_map, strings, locals, insts, diags);
+                    insts.push(Instruction::I32Store(MemArg {
+                        offset: 0,
+                        align: 2,
+                        memory_index: 0,
+                    }));
+                }
+                None => diags.push(Diagnostic::error(
+                    "static storage not found during codegen",
+                    expr.span,
+                )),
+            }
+            None
+        }
+        HirExprKind::Unit => None,
+        HirExprKind::Var(name) => {
+            if let Some((fields, idxs)) = scalarized_local(ctx, locals, name, expr.ty) {
+                for idx in idxs {
+                    insts.push(Instruction::LocalGet(idx));
+                }
+                emit_materialize(&fields, name_map, locals, insts);
+                Some(ValType::I32)
+            } else if let Some(idx) = locals.lookup(name) {
+                if valtype(&ctx.get(expr.ty)).is_some() {
+                    insts.push(Instruction::LocalGet(idx));
+                }
+                valtype(&ctx.get(expr.ty))
+            } else if let Some(fidx) = find_function_value_index(name_map, name) {
+                // Function symbols are first-class values in HIR.
+                // Lower them to table/function index constants.
+                insts.push(Instruction::I32Const(fidx as i32));
+                Some(ValType::I32)
+            } else {
+                diags.push(Diagnostic::error(
+                    format!("unknown variable {}", name),
+                    expr.span,
+                ));
+                None
+            }
+        }
+        HirExprKind::FnValue(name) => {
+            if let Some(fidx) = find_function_value_index(name_map, name) {
+                insts.push(Instruction::I32Const(fidx as i32));
+                Some(ValType::I32)
+            } else {
+                diags.push(Diagnostic::error(
+                    format!("unknown function value {}", name),
+                    expr.span,
+                ));
+                None
+            }
+        }
+        HirExprKind::Call { .. } | HirExprKind::CallIndirect { .. } => {
+            gen_call(ctx, expr, name_map, sig_map, strings, locals, insts, diags);
+            if let Some(fields) = multi_value_fields(ctx, expr.ty) {
+                emit_materialize(&fields, name_map, locals, insts);
+                return Some(ValType::I32);
+            }
             valtype(&ctx.get(expr.ty))
         }
         HirExprKind::If {
@@ -991,7 +2077,7 @@ fn gen_expr(
             insts.push(Instruction::End);
             result_ty
         }
-        HirExprKind::While { cond, body } => {
+        HirExprKind::While { cond, body, label } => {
             // while cond body:
             // block  ;; break target depth=1
             //   loop ;; continue target depth=0
@@ -1002,6 +2088,7 @@ fn gen_expr(
             //     br 0     ;; continue
             //   end
             // end
+            locals.loops.push((label.clone(), insts.len()));
             insts.push(Instruction::Block(wasm_encoder::BlockType::Empty));
             insts.push(Instruction::Loop(wasm_encoder::BlockType::Empty));
             gen_expr(ctx, cond, name_map, sig_map, strings, locals, insts, diags);
@@ -1009,6 +2096,7 @@ fn gen_expr(
             insts.push(Instruction::BrIf(1));
             gen_expr(ctx, body, name_map, sig_map, strings, locals, insts, diags);
             insts.push(Instruction::Br(0));
+            locals.loops.pop();
             insts.push(Instruction::End);
             insts.push(Instruction::End);
             None
@@ -1021,34 +2109,22 @@ fn gen_expr(
             type_args,
             args,
         } => {
-            if name == "size_of" {
-                let ty = type_args[0];
-                let size = match ctx.get(ty) {
-                    TypeKind::U8 => 1,
-                    TypeKind::Named(name) if name == "i64" || name == "f64" => 8,
-                    _ => match valtype(&ctx.get(ty)) {
-                        Some(_) => 4,
-                        None => 0,
-                    },
-                };
-                insts.push(Instruction::I32Const(size));
-                Some(ValType::I32)
-            } else if name == "align_of" {
-                let ty = type_args[0];
-                let align = match ctx.get(ty) {
-                    TypeKind::U8 => 1,
-                    TypeKind::Named(name) if name == "i64" || name == "f64" => 8,
-                    _ => match valtype(&ctx.get(ty)) {
-                        Some(_) => 4,
-                        None => 0,
-                    },
-                };
-                insts.push(Instruction::I32Const(align));
+            if name == "size_of" || name == "align_of" {
+                insts.push(Instruction::I32Const(scalar_size(ctx, type_args[0])));
                 Some(ValType::I32)
+            } else if name == IN_FRAME {
+                locals.frame_request = true;
+                let vt = gen_expr(ctx, &args[0], name_map, sig_map, strings, locals, insts, diags);
+                locals.frame_request = false;
+                vt
             } else if name == "load" {
                 let ty = type_args[0];
                 let ty_kind = ctx.get(ty);
                 let vt = valtype(&ty_kind);
+                if let Some(local) = vt.and_then(|vt| scalarized_field(ctx, locals, &args[0], vt)) {
+                    insts.push(Instruction::LocalGet(local));
+                    return vt;
+                }
                 // address
                 gen_expr(ctx, &args[0], name_map, sig_map, strings, locals, insts, diags);
                 match vt {
@@ -1092,6 +2168,14 @@ fn gen_expr(
                         }));
                         Some(ValType::F64)
                     }
+                    Some(ValType::V128) => {
+                        insts.push(Instruction::V128Load(MemArg {
+                            offset: 0,
+                            align: 4,
+                            memory_index: 0,
+                        }));
+                        Some(ValType::V128)
+                    }
                     None => {
                         insts.push(Instruction::Drop);
                         None
@@ -1102,7 +2186,12 @@ fn gen_expr(
                 let ty = type_args[0];
                 let ty_kind = ctx.get(ty);
                 let vt = valtype(&ty_kind);
-                
+                if let Some(local) = vt.and_then(|vt| scalarized_field(ctx, locals, &args[0], vt)) {
+                    gen_expr(ctx, &args[1], name_map, sig_map, strings, locals, insts, diags);
+                    insts.push(Instruction::LocalSet(local));
+                    return None;
+                }
+
                 // address
                 gen_expr(ctx, &args[0], name_map, sig_map, strings, locals, insts, diags);
                 // value
@@ -1149,6 +2238,14 @@ fn gen_expr(
                         }));
                         None
                     }
+                    Some(ValType::V128) => {
+                        insts.push(Instruction::V128Store(MemArg {
+                            offset: 0,
+                            align: 4,
+                            memory_index: 0,
+                        }));
+                        None
+                    }
                     None => {
                         insts.push(Instruction::Drop);
                         insts.push(Instruction::Drop);
@@ -1156,6 +2253,22 @@ fn gen_expr(
                     }
                     _ => None,
                 }
+            } else if name == "mem_copy" || name == "mem_fill" {
+                // dst, src|value, len
+                for arg in args {
+                    gen_expr(ctx, arg, name_map, sig_map, strings, locals, insts, diags);
+                }
+                if name == "mem_copy" {
+                    insts.push(Instruction::MemoryCopy {
+                        src_mem: 0,
+                        dst_mem: 0,
+                    });
+                } else {
+                    insts.push(Instruction::MemoryFill(0));
+                }
+                None
+            } else if let Some(op) = simd::lookup(name) {
+                gen_simd_intrinsic(ctx, op, args, name_map, sig_map, strings, locals, insts, diags)
             } else if name == "callsite_span" {
                 let size = 12;
                 insts.push(Instruction::I32Const(size));
@@ -1227,9 +2340,26 @@ fn gen_expr(
                 gen_expr(ctx, &args[1], name_map, sig_map, strings, locals, insts, diags);
                 insts.push(Instruction::I32Add);
                 Some(ValType::I32)
+            } else if name == "lt_s" {
+                // counting `for` loops over a Range
+                gen_expr(ctx, &args[0], name_map, sig_map, strings, locals, insts, diags);
+                gen_expr(ctx, &args[1], name_map, sig_map, strings, locals, insts, diags);
+                insts.push(Instruction::I32LtS);
+                Some(ValType::I32)
             } else if name == "unreachable" {
                 insts.push(Instruction::Unreachable);
                 None
+            } else if name == "const_value" {
+                // monomorphize already replaced every use that names a const item
+                diags.push(Diagnostic::error(
+                    format!(
+                        "const_value needs a const item of type '{}', found '{}'",
+                        ctx.type_to_string(type_args[1]),
+                        ctx.type_to_string(type_args[0])
+                    ),
+                    expr.span,
+                ));
+                None
             } else {
                 diags.push(Diagnostic::error("unknown codegen intrinsic", expr.span));
                 None
@@ -1242,13 +2372,15 @@ fn gen_expr(
             type_args: _,
         } => {
             let payload_vt = payload.as_ref().and_then(|p| valtype(&ctx.get(p.ty)));
+            // [tag][payload], the tag slot padded to the payload slot size
             let (payload_offset, size) = match payload_vt {
-                Some(ValType::I64) | Some(ValType::F64) => (8i32, 16i32),
-                Some(_) => (4i32, 8i32),
+                Some(_) => {
+                    let slot = slot_size(payload_vt) as i32;
+                    (slot, 2 * slot)
+                }
                 None => (0i32, 4i32),
             };
-            insts.push(Instruction::I32Const(size as i32));
-            emit_alloc_call(name_map, locals, insts);
+            emit_aggregate_alloc(size as u32, name_map, locals, insts);
             let ptr_local = locals.alloc_temp(ValType::I32);
             insts.push(Instruction::LocalTee(ptr_local));
             // store tag
@@ -1288,6 +2420,11 @@ fn gen_expr(
                                 align: 3,
                                 memory_index: 0,
                             })),
+                            ValType::V128 => insts.push(Instruction::V128Store(MemArg {
+                                offset: 0,
+                                align: 4,
+                                memory_index: 0,
+                            })),
                             _ => {
                                 diags.push(Diagnostic::error(
                                     "unsupported enum payload type",
@@ -1311,21 +2448,14 @@ fn gen_expr(
             name: _,
             fields,
             type_args: _,
-        } => {
-            let field_size = |vt: Option<ValType>| -> u32 {
-                match vt {
-                    Some(ValType::I64) | Some(ValType::F64) => 8,
-                    _ => 4,
-                }
-            };
+        } => {
             let mut offsets: Vec<u32> = Vec::with_capacity(fields.len());
             let mut size: u32 = 0;
             for f in fields.iter() {
                 offsets.push(size);
-                size += field_size(valtype(&ctx.get(f.ty)));
+                size += slot_size(valtype(&ctx.get(f.ty)));
             }
-            insts.push(Instruction::I32Const(size as i32));
-            emit_alloc_call(name_map, locals, insts);
+            emit_aggregate_alloc(size, name_map, locals, insts);
             let ptr_local = locals.alloc_temp(ValType::I32);
             insts.push(Instruction::LocalTee(ptr_local));
             for (i, f) in fields.iter().enumerate() {
@@ -1372,6 +2502,14 @@ fn gen_expr(
                                     memory_index: 0,
                                 }))
                             }
+                            ValType::V128 => {
+                                insts.push(Instruction::LocalGet(temp));
+                                insts.push(Instruction::V128Store(MemArg {
+                                    offset: 0,
+                                    align: 4,
+                                    memory_index: 0,
+                                }))
+                            }
                             _ => {
                                 diags.push(Diagnostic::error(
                                     "unsupported struct field type for codegen",
@@ -1399,20 +2537,13 @@ fn gen_expr(
             Some(ValType::I32)
         }
         HirExprKind::TupleConstruct { items } => {
-            let item_size = |vt: Option<ValType>| -> u32 {
-                match vt {
-                    Some(ValType::I64) | Some(ValType::F64) => 8,
-                    _ => 4,
-                }
-            };
             let mut offsets: Vec<u32> = Vec::with_capacity(items.len());
             let mut size: u32 = 0;
             for item in items.iter() {
                 offsets.push(size);
-                size += item_size(valtype(&ctx.get(item.ty)));
+                size += slot_size(valtype(&ctx.get(item.ty)));
             }
-            insts.push(Instruction::I32Const(size as i32));
-            emit_alloc_call(name_map, locals, insts);
+            emit_aggregate_alloc(size, name_map, locals, insts);
             let ptr_local = locals.alloc_temp(ValType::I32);
             insts.push(Instruction::LocalTee(ptr_local));
             for (i, item) in items.iter().enumerate() {
@@ -1459,6 +2590,14 @@ fn gen_expr(
                                     memory_index: 0,
                                 }))
                             }
+                            ValType::V128 => {
+                                insts.push(Instruction::LocalGet(temp));
+                                insts.push(Instruction::V128Store(MemArg {
+                                    offset: 0,
+                                    align: 4,
+                                    memory_index: 0,
+                                }))
+                            }
                             _ => {
                                 diags.push(Diagnostic::error(
                                     "unsupported tuple element type for codegen",
@@ -1524,10 +2663,7 @@ fn gen_expr(
                     if let Some(payload_ty) = enum_variant_payload(ctx, scrutinee.ty, &arm.variant) {
                         let lidx = locals.ensure_local(bind.clone(), payload_ty, ctx);
                         if let Some(vt) = valtype(&ctx.get(payload_ty)) {
-                            let payload_offset = match vt {
-                                ValType::I64 | ValType::F64 => 8i32,
-                                _ => 4i32,
-                            };
+                            let payload_offset = slot_size(Some(vt)) as i32;
                             insts.push(Instruction::LocalGet(ptr_local));
                             insts.push(Instruction::I32Const(payload_offset));
                             insts.push(Instruction::I32Add);
@@ -1552,6 +2688,11 @@ fn gen_expr(
                                     align: 3,
                                     memory_index: 0,
                                 })),
+                                ValType::V128 => insts.push(Instruction::V128Load(MemArg {
+                                    offset: 0,
+                                    align: 4,
+                                    memory_index: 0,
+                                })),
                                 _ => diags.push(Diagnostic::error(
                                     "unsupported enum payload type",
                                     arm.body.span,
@@ -1578,7 +2719,16 @@ fn gen_expr(
             result_ty
         }
         HirExprKind::Let { name, value, .. } => {
-            let idx = locals.ensure_local(name.clone(), value.ty, ctx);
+            let idx = declare_local(ctx, locals, name, value.ty);
+            if let (Some(fields), Some(idxs)) =
+                (multi_value_fields(ctx, value.ty), locals.fields_of(idx))
+            {
+                gen_multi(ctx, value, &fields, name_map, sig_map, strings, locals, insts, diags);
+                for idx in idxs.iter().rev() {
+                    insts.push(Instruction::LocalSet(*idx));
+                }
+                return None;
+            }
             gen_expr(ctx, value, name_map, sig_map, strings, locals, insts, diags);
             if valtype(&ctx.get(value.ty)).is_some() {
                 insts.push(Instruction::LocalSet(idx));
@@ -1586,7 +2736,12 @@ fn gen_expr(
             None
         }
         HirExprKind::Set { name, value } => {
-            if let Some(idx) = locals.lookup(name) {
+            if let Some((fields, idxs)) = scalarized_local(ctx, locals, name, value.ty) {
+                gen_multi(ctx, value, &fields, name_map, sig_map, strings, locals, insts, diags);
+                for idx in idxs.iter().rev() {
+                    insts.push(Instruction::LocalSet(*idx));
+                }
+            } else if let Some(idx) = locals.lookup(name) {
                 gen_expr(ctx, value, name_map, sig_map, strings, locals, insts, diags);
                 if valtype(&ctx.get(value.ty)).is_some() {
                     insts.push(Instruction::LocalSet(idx));
@@ -1608,6 +2763,114 @@ fn gen_expr(
             gen_expr(ctx, inner, name_map, sig_map, strings, locals, insts, diags);
             valtype(&ctx.get(expr.ty))
         }
+        HirExprKind::Return(value) => {
+            match locals.result_fields.clone() {
+                Some(fields) => {
+                    gen_multi(ctx, value, &fields, name_map, sig_map, strings, locals, insts, diags)
+                }
+                None => {
+                    gen_expr(ctx, value, name_map, sig_map, strings, locals, insts, diags);
+                }
+            }
+            emit_frame_exit(strings, locals, insts);
+            insts.push(Instruction::Return);
+            None
+        }
+        HirExprKind::Break(target) | HirExprKind::Continue(target) => {
+            let Some(&(_, start)) = locals
+                .loops
+                .iter()
+                .rev()
+                .find(|(label, _)| target.is_none() || target == label)
+            else {
+                diags.push(Diagnostic::error("loop exit outside of a loop", expr.span));
+                return None;
+            };
+            // Count the structured blocks opened since the loop's outer `block`:
+            // that block is the break target, the `loop` inside it the continue target.
+            let open = insts[start..].iter().fold(0u32, |depth, inst| match inst {
+                Instruction::Block(_) | Instruction::Loop(_) | Instruction::If(_) => depth + 1,
+                Instruction::End => depth - 1,
+                _ => depth,
+            });
+            let to_block = open - 1;
+            insts.push(Instruction::Br(match expr.kind {
+                HirExprKind::Break(_) => to_block,
+                _ => to_block - 1,
+            }));
+            None
+        }
+    }
+}
+
+/// Restores the stack pointer saved on entry, if the function has a frame.
+fn emit_frame_exit(strings: &StringLower, locals: &LocalMap, insts: &mut Vec<Instruction<'static>>) {
+    if let (Some(base), Some(stack)) = (locals.frame_base, strings.stack) {
+        insts.push(Instruction::LocalGet(base));
+        insts.push(Instruction::GlobalSet(stack.global));
+    }
+}
+
+/// Lowers a `simd` intrinsic to the matching wasm SIMD instruction.
+#[allow(clippy::too_many_arguments)]
+fn gen_simd_intrinsic(
+    ctx: &TypeCtx,
+    op: simd::SimdOp,
+    args: &[HirExpr],
+    name_map: &BTreeMap<String, u32>,
+    sig_map: &BTreeMap<(Vec<ValType>, Vec<ValType>), u32>,
+    strings: &StringLower,
+    locals: &mut LocalMap,
+    insts: &mut Vec<Instruction<'static>>,
+    diags: &mut Vec<Diagnostic>,
+) -> Option<ValType> {
+    use simd::{BinOp, Lane, Operand, SimdOp};
+    // lane indices are immediates; typecheck already rejected non-literals
+    let mut lanes: Vec<u8> = Vec::new();
+    for (arg, operand) in args.iter().zip(op.operands()) {
+        if let Operand::LaneIndex(_) = operand {
+            match arg.kind {
+                HirExprKind::LiteralI32(v) => lanes.push(v as u8),
+                _ => {
+                    diags.push(Diagnostic::error("lane index must be a literal", arg.span));
+                    return None;
+                }
+            }
+        } else {
+            gen_expr(ctx, arg, name_map, sig_map, strings, locals, insts, diags);
+        }
+    }
+    let inst = match op {
+        SimdOp::Splat(Lane::I32) => Instruction::I32x4Splat,
+        SimdOp::Splat(Lane::F32) => Instruction::F32x4Splat,
+        SimdOp::ExtractLane(Lane::I32) => Instruction::I32x4ExtractLane(lanes[0]),
+        SimdOp::ExtractLane(Lane::F32) => Instruction::F32x4ExtractLane(lanes[0]),
+        SimdOp::ReplaceLane(Lane::I32) => Instruction::I32x4ReplaceLane(lanes[0]),
+        SimdOp::ReplaceLane(Lane::F32) => Instruction::F32x4ReplaceLane(lanes[0]),
+        SimdOp::Binary(Lane::I32, BinOp::Add) => Instruction::I32x4Add,
+        SimdOp::Binary(Lane::I32, BinOp::Sub) => Instruction::I32x4Sub,
+        SimdOp::Binary(Lane::I32, BinOp::Mul) => Instruction::I32x4Mul,
+        SimdOp::Binary(Lane::F32, BinOp::Add) => Instruction::F32x4Add,
+        SimdOp::Binary(Lane::F32, BinOp::Sub) => Instruction::F32x4Sub,
+        SimdOp::Binary(Lane::F32, BinOp::Mul) => Instruction::F32x4Mul,
+        SimdOp::Binary(Lane::F32, BinOp::Div) => Instruction::F32x4Div,
+        SimdOp::Binary(Lane::I32, BinOp::Div) => unreachable!("simd::lookup has no i32x4_div"),
+        SimdOp::Shuffle => {
+            // i32 lane l covers bytes 4l..4l+3 of the 32-byte concatenation a ++ b
+            let mut bytes = [0u8; 16];
+            for (i, lane) in lanes.iter().enumerate() {
+                for b in 0..4 {
+                    bytes[i * 4 + b] = lane * 4 + b as u8;
+                }
+            }
+            Instruction::I8x16Shuffle(bytes)
+        }
+    };
+    insts.push(inst);
+    match op.scalar_result() {
+        Some(Lane::I32) => Some(ValType::I32),
+        Some(Lane::F32) => Some(ValType::F32),
+        None => Some(ValType::V128),
     }
 }
 
@@ -1628,8 +2891,24 @@ struct LocalMap {
     locals: Vec<LocalInfo>,
     map: BTreeMap<String, Vec<u32>>,
     scopes: Vec<Vec<String>>,
+    next_param: u32,
     next_idx: u32,
     decls: Vec<ValType>,
+    /// Scalarized tuples / structs: first field local -> every field local.
+    aggregates: BTreeMap<u32, Vec<(u32, ValType)>>,
+    /// Names whose address is taken; they stay pointers to heap memory.
+    pinned: BTreeSet<String>,
+    /// The next aggregate allocation goes into the stack frame.
+    frame_request: bool,
+    /// Bytes of stack frame used so far.
+    frame_size: u32,
+    frame_ptr: Option<u32>,
+    /// Stack pointer on entry, when the function has a frame.
+    frame_base: Option<u32>,
+    /// Field layout of a small tuple / struct result returned as multi-value.
+    result_fields: Option<Vec<AggField>>,
+    /// Enclosing `while` loops: label and the position of their outer `block`.
+    loops: Vec<(Option<String>, usize)>,
 }
 
 impl LocalMap {
@@ -1638,24 +2917,105 @@ impl LocalMap {
             locals: Vec::new(),
             map: BTreeMap::new(),
             scopes: vec![Vec::new()],
+            next_param: 0,
             next_idx: param_count as u32,
             decls: Vec::new(),
+            aggregates: BTreeMap::new(),
+            pinned: BTreeSet::new(),
+            frame_request: false,
+            frame_size: 0,
+            frame_ptr: None,
+            frame_base: None,
+            result_fields: None,
+            loops: Vec::new(),
         }
     }
 
-    fn register_param(&mut self, name: String, ty: TypeId) {
-        let idx = self.locals.len() as u32;
-        self.locals.push(LocalInfo {
-            name: name.clone(),
-            idx,
-            ty: Some(ty),
-            is_param: true,
-        });
+    /// Local holding the base address of this function's stack frame.
+    fn frame_pointer(&mut self) -> u32 {
+        match self.frame_ptr {
+            Some(fp) => fp,
+            None => {
+                let fp = self.alloc_temp(ValType::I32);
+                self.frame_ptr = Some(fp);
+                fp
+            }
+        }
+    }
+
+    fn register_param(&mut self, name: String, ty: TypeId, multi_value: bool, ctx: &TypeCtx) {
+        let idx = self.next_param;
+        match multi_value_fields(ctx, ty).filter(|_| multi_value) {
+            Some(fields) => {
+                let mut slots = Vec::with_capacity(fields.len());
+                for (i, f) in fields.iter().enumerate() {
+                    let field_idx = idx + i as u32;
+                    self.locals.push(LocalInfo {
+                        name: format!("{}.{}", name, i),
+                        idx: field_idx,
+                        ty: Some(f.ty),
+                        is_param: true,
+                    });
+                    slots.push((field_idx, f.vt));
+                }
+                self.next_param += slots.len() as u32;
+                self.aggregates.insert(idx, slots);
+            }
+            None => {
+                self.locals.push(LocalInfo {
+                    name: name.clone(),
+                    idx,
+                    ty: Some(ty),
+                    is_param: true,
+                });
+                self.next_param += 1;
+            }
+        }
         self.bind_name(name, idx);
     }
 
-    fn ensure_local(&mut self, name: String, ty: TypeId, ctx: &TypeCtx) -> u32 {
+    /// Binds `name` to one local per field of a small tuple / struct.
+    fn ensure_aggregate(&mut self, name: String, fields: &[AggField]) -> u32 {
         if let Some(idx) = self.lookup_current(&name) {
+            let same_shape = self.aggregates.get(&idx).is_some_and(|slots| {
+                slots.len() == fields.len()
+                    && slots.iter().zip(fields).all(|((_, vt), f)| *vt == f.vt)
+            });
+            if same_shape {
+                return idx;
+            }
+        }
+        let mut slots = Vec::with_capacity(fields.len());
+        for (i, f) in fields.iter().enumerate() {
+            let field_idx = self.next_idx;
+            self.next_idx += 1;
+            self.decls.push(f.vt);
+            self.locals.push(LocalInfo {
+                name: format!("{}.{}", name, i),
+                idx: field_idx,
+                ty: Some(f.ty),
+                is_param: false,
+            });
+            slots.push((field_idx, f.vt));
+        }
+        let idx = slots[0].0;
+        self.aggregates.insert(idx, slots);
+        self.bind_name(name, idx);
+        idx
+    }
+
+    /// Field locals when `idx` is a scalarized tuple / struct.
+    fn fields_of(&self, idx: u32) -> Option<Vec<u32>> {
+        self.aggregates
+            .get(&idx)
+            .map(|slots| slots.iter().map(|(i, _)| *i).collect())
+    }
+
+    fn ensure_local(&mut self, name: String, ty: TypeId, ctx: &TypeCtx) -> u32 {
+        if let Some(idx) = self
+            .lookup_current(&name)
+            .filter(|idx| !self.aggregates.contains_key(idx))
+        {
             idx
         } else {
             let vt = valtype(&ctx.get(ty));
@@ -1701,10 +3061,15 @@ impl LocalMap {
     }
 
     fn valtype_of(&self, idx: u32, ctx: &TypeCtx) -> Option<ValType> {
-        self.locals
-            .iter()
-            .find(|l| l.idx == idx)
-            .and_then(|l| l.ty.and_then(|t| valtype(&ctx.get(t))))
+        match self.locals.iter().find(|l| l.idx == idx) {
+            Some(LocalInfo { ty: Some(t), .. }) => valtype(&ctx.get(*t)),
+            _ => {
+                // Temporaries carry no TypeId; read their declared valtype.
+                let first_decl = self.next_idx - self.decls.len() as u32;
+                idx.checked_sub(first_decl)
+                    .and_then(|i| self.decls.get(i as usize).copied())
+            }
+        }
     }
 
     fn begin_scope(&mut self) {
@@ -1745,9 +3110,580 @@ impl LocalMap {
 }
 
 // ---------------------------------------------------------------------
-// Minimal wasm text parser for #wasm blocks
+// Wasm text assembler for #wasm blocks
 // ---------------------------------------------------------------------
 
+/// Module-level names visible to `#wasm` bodies.
+struct WasmAsmEnv<'a> {
+    funcs: &'a BTreeMap<String, u32>,
+    /// Signature of each function index (imports first).
+    func_sigs: Vec<(Vec<ValType>, Vec<ValType>)>,
+    /// `static` items stored in wasm globals: name -> (global index, type, mutable).
+    globals: BTreeMap<String, (u32, ValType, bool)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AsmFrameKind {
+    Func,
+    Block,
+    Loop,
+    If,
+    Else,
+}
+
+#[derive(Debug)]
+struct AsmFrame {
+    kind: AsmFrameKind,
+    label: Option<String>,
+    results: Vec<ValType>,
+    height: usize,
+    unreachable: bool,
+}
+
+impl AsmFrame {
+    /// Values a branch to this frame carries (a loop branches back to its start).
+    fn label_types(&self) -> Vec<ValType> {
+        if self.kind == AsmFrameKind::Loop {
+            Vec::new()
+        } else {
+            self.results.clone()
+        }
+    }
+}
+
+/// Assembles one `#wasm` body line by line while validating it.
+///
+/// The accepted text is a line-oriented WAT subset: one instruction per line,
+/// `block` / `loop` / `if` (with an optional `$label` and `(result T)`),
+/// `else`, `end`, branches by label or depth, `call` by NEPL function name,
+/// `global.get` / `global.set` on `static` items and `local $name T`
+/// declarations before the first instruction. The operand stack is tracked
+/// per the wasm validation rules, so arity and type errors are reported here
+/// instead of by the runtime.
+struct WasmAsm<'a> {
+    ctx: &'a TypeCtx,
+    locals: &'a mut LocalMap,
+    env: &'a WasmAsmEnv<'a>,
+    insts: Vec<Instruction<'static>>,
+    /// `None` is a value of unknown type produced in unreachable code.
+    stack: Vec<Option<ValType>>,
+    frames: Vec<AsmFrame>,
+}
+
+impl<'a> WasmAsm<'a> {
+    fn new(
+        ctx: &'a TypeCtx,
+        locals: &'a mut LocalMap,
+        env: &'a WasmAsmEnv<'a>,
+        results: Vec<ValType>,
+    ) -> Self {
+        Self {
+            ctx,
+            locals,
+            env,
+            insts: Vec::new(),
+            stack: Vec::new(),
+            frames: vec![AsmFrame {
+                kind: AsmFrameKind::Func,
+                label: None,
+                results,
+                height: 0,
+                unreachable: false,
+            }],
+        }
+    }
+
+    fn line(&mut self, line: &str) -> Result<(), String> {
+        let code = line.split(";;").next().unwrap_or("");
+        let parts: Vec<&str> = code.split_whitespace().collect();
+        let Some(&op) = parts.first() else {
+            return Ok(());
+        };
+        let operand = parts.get(1).copied();
+        match op {
+            "local" => self.declare_local(&parts),
+            "block" | "loop" | "if" => {
+                let (label, result) = parse_block_header(&parts[1..])?;
+                if op == "if" {
+                    self.pop_expect(ValType::I32, op)?;
+                }
+                let ty = match result {
+                    Some(vt) => BlockType::Result(vt),
+                    None => BlockType::Empty,
+                };
+                let kind = match op {
+                    "block" => AsmFrameKind::Block,
+                    "loop" => AsmFrameKind::Loop,
+                    _ => AsmFrameKind::If,
+                };
+                self.frames.push(AsmFrame {
+                    kind,
+                    label,
+                    results: result.into_iter().collect(),
+                    height: self.stack.len(),
+                    unreachable: false,
+                });
+                self.insts.push(match kind {
+                    AsmFrameKind::Block => Instruction::Block(ty),
+                    AsmFrameKind::Loop => Instruction::Loop(ty),
+                    _ => Instruction::If(ty),
+                });
+                Ok(())
+            }
+            "else" => {
+                let frame = self.frames.last().expect("function frame");
+                if frame.kind != AsmFrameKind::If {
+                    return Err(String::from("`else` without a matching `if` in #wasm"));
+                }
+                let results = frame.results.clone();
+                self.check_frame_end(&results, "`else`")?;
+                let frame = self.frames.last_mut().expect("function frame");
+                frame.kind = AsmFrameKind::Else;
+                frame.unreachable = false;
+                self.insts.push(Instruction::Else);
+                Ok(())
+            }
+            "end" => {
+                if self.frames.len() == 1 {
+                    return Err(String::from("`end` without a matching block in #wasm"));
+                }
+                let frame = self.frames.last().expect("block frame");
+                if frame.kind == AsmFrameKind::If && !frame.results.is_empty() {
+                    return Err(String::from(
+                        "`if` with a result requires an `else` branch in #wasm",
+                    ));
+                }
+                let results = frame.results.clone();
+                self.check_frame_end(&results, "`end`")?;
+                self.frames.pop();
+                self.stack.extend(results.into_iter().map(Some));
+                self.insts.push(Instruction::End);
+                Ok(())
+            }
+            "br" | "br_if" => {
+                let depth = self.resolve_label(operand, op)?;
+                let types = self.frame_at(depth).label_types();
+                if op == "br_if" {
+                    self.pop_expect(ValType::I32, op)?;
+                    self.pop_types(&types, op)?;
+                    self.stack.extend(types.into_iter().map(Some));
+                    self.insts.push(Instruction::BrIf(depth));
+                } else {
+                    self.pop_types(&types, op)?;
+                    self.set_unreachable();
+                    self.insts.push(Instruction::Br(depth));
+                }
+                Ok(())
+            }
+            "br_table" => {
+                if parts.len() < 2 {
+                    return Err(String::from("`br_table` requires at least a default label"));
+                }
+                let mut depths = Vec::new();
+                for text in &parts[1..] {
+                    depths.push(self.resolve_label(Some(text), op)?);
+                }
+                let default = depths.pop().expect("default label");
+                let types = self.frame_at(default).label_types();
+                for depth in &depths {
+                    if self.frame_at(*depth).label_types() != types {
+                        return Err(String::from(
+                            "`br_table` targets must all carry the same values in #wasm",
+                        ));
+                    }
+                }
+                self.pop_expect(ValType::I32, op)?;
+                self.pop_types(&types, op)?;
+                self.set_unreachable();
+                self.insts.push(Instruction::BrTable(Cow::Owned(depths), default));
+                Ok(())
+            }
+            "return" => {
+                let results = self.frames[0].results.clone();
+                self.pop_types(&results, op)?;
+                self.set_unreachable();
+                self.insts.push(Instruction::Return);
+                Ok(())
+            }
+            "unreachable" => {
+                self.set_unreachable();
+                self.insts.push(Instruction::Unreachable);
+                Ok(())
+            }
+            "nop" => {
+                self.insts.push(Instruction::Nop);
+                Ok(())
+            }
+            "call" if parts.len() == 2 => {
+                let idx = self.resolve_function(parts[1])?;
+                let Some((params, results)) = self.env.func_sigs.get(idx as usize).cloned() else {
+                    return Err(format!("unknown function in #wasm: {}", parts[1]));
+                };
+                self.pop_types(&params, op)?;
+                self.stack.extend(results.into_iter().map(Some));
+                self.insts.push(Instruction::Call(idx));
+                Ok(())
+            }
+            "select" => {
+                self.pop_expect(ValType::I32, op)?;
+                let b = self.pop_any(op)?;
+                let a = self.pop_any(op)?;
+                if let (Some(a), Some(b)) = (a, b) {
+                    if a != b {
+                        return Err(format!(
+                            "type mismatch in #wasm at `select`: operands are {} and {}",
+                            valtype_name(a),
+                            valtype_name(b)
+                        ));
+                    }
+                }
+                self.stack.push(a.or(b));
+                self.insts.push(Instruction::Select);
+                Ok(())
+            }
+            "drop" => {
+                self.pop_any(op)?;
+                self.insts.push(Instruction::Drop);
+                Ok(())
+            }
+            "local.get" | "local.set" | "local.tee" if parts.len() == 2 => {
+                let Some(idx) = parse_local(parts[1], self.locals) else {
+                    return Err(format!("unknown local in #wasm: {}", parts[1]));
+                };
+                let Some(vt) = self.locals.valtype_of(idx, self.ctx) else {
+                    return Err(format!("local {} has no wasm value type", parts[1]));
+                };
+                match op {
+                    "local.get" => {
+                        self.stack.push(Some(vt));
+                        self.insts.push(Instruction::LocalGet(idx));
+                    }
+                    "local.set" => {
+                        self.pop_expect(vt, op)?;
+                        self.insts.push(Instruction::LocalSet(idx));
+                    }
+                    _ => {
+                        self.pop_expect(vt, op)?;
+                        self.stack.push(Some(vt));
+                        self.insts.push(Instruction::LocalTee(idx));
+                    }
+                }
+                Ok(())
+            }
+            "global.get" | "global.set" if parts.len() == 2 => {
+                let name = parts[1].strip_prefix('$').unwrap_or(parts[1]);
+                let Some(&(idx, vt, mutable)) = self.env.globals.get(name) else {
+                    return Err(format!("unknown global in #wasm: {}", parts[1]));
+                };
+                if op == "global.get" {
+                    self.stack.push(Some(vt));
+                    self.insts.push(Instruction::GlobalGet(idx));
+                } else {
+                    if !mutable {
+                        return Err(format!("cannot global.set immutable static '{}'", name));
+                    }
+                    self.pop_expect(vt, op)?;
+                    self.insts.push(Instruction::GlobalSet(idx));
+                }
+                Ok(())
+            }
+            _ => {
+                let mut decoded = parse_wasm_line(code, self.locals)?;
+                let Some((params, results)) = wasm_op_signature(op) else {
+                    return Err(format!("unsupported wasm instruction: {}", op));
+                };
+                self.pop_types(&params, op)?;
+                self.stack.extend(results.into_iter().map(Some));
+                self.insts.append(&mut decoded);
+                Ok(())
+            }
+        }
+    }
+
+    /// Checks that every block is closed and the body leaves exactly the
+    /// function results on the stack.
+    fn finish(&mut self) -> Result<(), String> {
+        if self.frames.len() > 1 {
+            return Err(format!(
+                "{} unclosed block(s) at the end of #wasm body",
+                self.frames.len() - 1
+            ));
+        }
+        let results = self.frames[0].results.clone();
+        self.check_frame_end(&results, "the end of the function")
+    }
+
+    fn declare_local(&mut self, parts: &[&str]) -> Result<(), String> {
+        if !self.insts.is_empty() {
+            return Err(String::from(
+                "`local` declarations must come before instructions in #wasm",
+            ));
+        }
+        let (Some(name), Some(ty), 3) = (parts.get(1), parts.get(2), parts.len()) else {
+            return Err(String::from("expected `local $name <valtype>` in #wasm"));
+        };
+        let Some(name) = name.strip_prefix('$') else {
+            return Err(format!("local name must start with `$`: {}", name));
+        };
+        let Some(vt) = parse_valtype(ty) else {
+            return Err(format!("unknown value type in #wasm: {}", ty));
+        };
+        let idx = self.locals.alloc_temp(vt);
+        self.locals.bind_name(String::from(name), idx);
+        Ok(())
+    }
+
+    fn frame_at(&self, depth: u32) -> &AsmFrame {
+        &self.frames[self.frames.len() - 1 - depth as usize]
+    }
+
+    fn resolve_label(&self, text: Option<&str>, op: &str) -> Result<u32, String> {
+        let Some(text) = text else {
+            return Err(format!("`{}` requires a label", op));
+        };
+        if let Some(name) = text.strip_prefix('$') {
+            return self
+                .frames
+                .iter()
+                .rev()
+                .position(|f| f.label.as_deref() == Some(name))
+                .map(|d| d as u32)
+                .ok_or_else(|| format!("unknown label in #wasm: {}", text));
+        }
+        match text.parse::<u32>() {
+            Ok(depth) if (depth as usize) < self.frames.len() => Ok(depth),
+            Ok(_) => Err(format!("branch depth {} is out of range in #wasm", text)),
+            Err(_) => Err(format!("invalid label in #wasm: {}", text)),
+        }
+    }
+
+    fn resolve_function(&self, text: &str) -> Result<u32, String> {
+        let name = text.strip_prefix('$').unwrap_or(text);
+        if let Ok(idx) = name.parse::<u32>() {
+            return Ok(idx);
+        }
+        if let Some(idx) = self.env.funcs.get(name) {
+            return Ok(*idx);
+        }
+        let prefix = format!("{}__", name);
+        let mut found = self
+            .env
+            .funcs
+            .iter()
+            .filter(|(n, _)| n.starts_with(&prefix))
+            .map(|(_, idx)| *idx);
+        match (found.next(), found.next()) {
+            (Some(idx), None) => Ok(idx),
+            (Some(_), Some(_)) => Err(format!(
+                "call target '{}' is overloaded; #wasm can only call a unique function",
+                name
+            )),
+            (None, _) => Err(format!("unknown function in #wasm: {}", text)),
+        }
+    }
+
+    fn set_unreachable(&mut self) {
+        let frame = self.frames.last_mut().expect("function frame");
+        self.stack.truncate(frame.height);
+        frame.unreachable = true;
+    }
+
+    fn pop_any(&mut self, op: &str) -> Result<Option<ValType>, String> {
+        let frame = self.frames.last().expect("function frame");
+        if self.stack.len() == frame.height {
+            if frame.unreachable {
+                return Ok(None);
+            }
+            return Err(format!("stack underflow in #wasm at `{}`", op));
+        }
+        Ok(self.stack.pop().flatten())
+    }
+
+    fn pop_expect(&mut self, expected: ValType, op: &str) -> Result<(), String> {
+        match self.pop_any(op)? {
+            Some(found) if found != expected => Err(format!(
+                "type mismatch in #wasm at `{}`: expected {}, found {}",
+                op,
+                valtype_name(expected),
+                valtype_name(found)
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn pop_types(&mut self, types: &[ValType], op: &str) -> Result<(), String> {
+        for vt in types.iter().rev() {
+            self.pop_expect(*vt, op)?;
+        }
+        Ok(())
+    }
+
+    fn check_frame_end(&mut self, results: &[ValType], at: &str) -> Result<(), String> {
+        self.pop_types(results, at)?;
+        let height = self.frames.last().expect("function frame").height;
+        if self.stack.len() != height {
+            let extra = self.stack.len() - height;
+            return Err(format!(
+                "{} extra value(s) left on the stack at {} in #wasm",
+                extra, at
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn parse_block_header(parts: &[&str]) -> Result<(Option<String>, Option<ValType>), String> {
+    let mut label = None;
+    let mut rest = parts;
+    if let Some(name) = rest.first().and_then(|p| p.strip_prefix('$')) {
+        label = Some(String::from(name));
+        rest = &rest[1..];
+    }
+    let text = rest.join(" ");
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok((label, None));
+    }
+    let inner = text
+        .strip_prefix('(')
+        .and_then(|t| t.strip_suffix(')'))
+        .map(str::trim)
+        .ok_or_else(|| format!("invalid block type in #wasm: {}", text))?;
+    let mut words = inner.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some("result"), Some(ty), None) => match parse_valtype(ty) {
+            Some(vt) => Ok((label, Some(vt))),
+            None => Err(format!("unknown value type in #wasm: {}", ty)),
+        },
+        (Some("result"), Some(_), Some(_)) => Err(String::from(
+            "blocks with more than one result are not supported in #wasm",
+        )),
+        (Some("param"), _, _) => Err(String::from(
+            "block parameters are not supported in #wasm",
+        )),
+        _ => Err(format!("invalid block type in #wasm: {}", text)),
+    }
+}
+
+fn parse_valtype(text: &str) -> Option<ValType> {
+    match text {
+        "i32" => Some(ValType::I32),
+        "i64" => Some(ValType::I64),
+        "f32" => Some(ValType::F32),
+        "f64" => Some(ValType::F64),
+        "v128" => Some(ValType::V128),
+        _ => None,
+    }
+}
+
+fn valtype_name(vt: ValType) -> &'static str {
+    match vt {
+        ValType::I32 => "i32",
+        ValType::I64 => "i64",
+        ValType::F32 => "f32",
+        ValType::F64 => "f64",
+        ValType::V128 => "v128",
+        ValType::Ref(_) => "ref",
+    }
+}
+
+/// Stack signature `[params] -> [results]` of a non-control instruction.
+fn wasm_op_signature(op: &str) -> Option<(Vec<ValType>, Vec<ValType>)> {
+    match op {
+        "memory.grow" => return Some((vec![ValType::I32], vec![ValType::I32])),
+        "memory.size" => return Some((Vec::new(), vec![ValType::I32])),
+        "memory.copy" | "memory.fill" => {
+            return Some((vec![ValType::I32, ValType::I32, ValType::I32], Vec::new()))
+        }
+        "i32x4.splat" => return Some((vec![ValType::I32], vec![ValType::V128])),
+        "f32x4.splat" => return Some((vec![ValType::F32], vec![ValType::V128])),
+        "i32x4.add" | "i32x4.sub" | "i32x4.mul" | "f32x4.add" | "f32x4.sub" | "f32x4.mul"
+        | "f32x4.div" => return Some((vec![ValType::V128, ValType::V128], vec![ValType::V128])),
+        _ => {}
+    }
+    let (ty, name) = op.split_once('.')?;
+    let t = parse_valtype(ty)?;
+    let sig = match name {
+        "const" => (Vec::new(), vec![t]),
+        "eqz" => (vec![t], vec![ValType::I32]),
+        "eq" | "ne" | "lt" | "gt" | "le" | "ge" | "lt_s" | "lt_u" | "gt_s" | "gt_u" | "le_s"
+        | "le_u" | "ge_s" | "ge_u" => (vec![t, t], vec![ValType::I32]),
+        "add" | "sub" | "mul" | "div" | "div_s" | "div_u" | "rem_s" | "rem_u" | "and" | "or"
+        | "xor" | "shl" | "shr_s" | "shr_u" | "rotl" | "rotr" | "min" | "max" | "copysign" => {
+            (vec![t, t], vec![t])
+        }
+        "clz" | "ctz" | "popcnt" | "abs" | "neg" | "ceil" | "floor" | "trunc" | "nearest"
+        | "sqrt" | "extend8_s" | "extend16_s" | "extend32_s" => (vec![t], vec![t]),
+        _ if name.starts_with("load") => (vec![ValType::I32], vec![t]),
+        _ if name.starts_with("store") => (vec![ValType::I32, t], Vec::new()),
+        _ => {
+            // Conversions name their source type: `wrap_i64`, `trunc_sat_f32_s`, ...
+            let src = name.split('_').skip(1).find_map(parse_valtype)?;
+            (vec![src], vec![t])
+        }
+    };
+    Some(sig)
+}
+
+/// `offset=N` / `align=N` immediates of a load or store in a `#wasm` line.
+struct MemImm<'a> {
+    op: &'a str,
+    offset: u64,
+    /// log2 of an explicit `align=`
+    align: Option<u32>,
+}
+
+impl<'a> MemImm<'a> {
+    fn parse(op: &'a str, imms: &[&str]) -> Result<Self, String> {
+        let mut mem = MemImm {
+            op,
+            offset: 0,
+            align: None,
+        };
+        let is_access = op.split_once('.').is_some_and(|(ty, name)| {
+            ty != "memory" && (name.starts_with("load") || name.starts_with("store"))
+        });
+        if !is_access {
+            return Ok(mem);
+        }
+        let parse = |v: &str| match v.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => v.parse::<u32>().ok(),
+        };
+        for imm in imms {
+            if let Some(v) = imm.strip_prefix("offset=") {
+                let offset =
+                    parse(v).ok_or_else(|| format!("invalid memory offset in #wasm: {}", imm))?;
+                mem.offset = offset as u64;
+            } else if let Some(v) = imm.strip_prefix("align=") {
+                match parse(v) {
+                    Some(a) if a.is_power_of_two() => mem.align = Some(a.trailing_zeros()),
+                    _ => return Err(format!("alignment must be a power of two in #wasm: {}", imm)),
+                }
+            } else {
+                return Err(format!("unexpected immediate for {} in #wasm: {}", op, imm));
+            }
+        }
+        Ok(mem)
+    }
+
+    /// The memarg for an access whose natural alignment is `2^natural` bytes.
+    fn arg(&self, natural: u32) -> Result<MemArg, String> {
+        match self.align {
+            Some(a) if a > natural => Err(format!(
+                "alignment of {} must not be larger than {}",
+                self.op,
+                1u32 << natural
+            )),
+            a => Ok(MemArg {
+                offset: self.offset,
+                align: a.unwrap_or(natural),
+                memory_index: 0,
+            }),
+        }
+    }
+}
+
 fn parse_wasm_line(line: &str, locals: &LocalMap) -> Result<Vec<Instruction<'static>>, String> {
     let mut insts = Vec::new();
     let parts: Vec<&str> = line.split_whitespace().collect();
@@ -1757,6 +3693,7 @@ fn parse_wasm_line(line: &str, locals: &LocalMap) -> Result<Vec<Instruction<'sta
     if parts[0].starts_with(";;") {
         return Ok(insts);
     }
+    let mem = MemImm::parse(parts[0], &parts[1..])?;
     match parts[0] {
         "local.get" if parts.len() == 2 => {
             if let Some(idx) = parse_local(parts[1], locals) {
@@ -1809,46 +3746,14 @@ fn parse_wasm_line(line: &str, locals: &LocalMap) -> Result<Vec<Instruction<'sta
         "i32.gt_u" => insts.push(Instruction::I32GtU),
         "i32.ge_s" => insts.push(Instruction::I32GeS),
         "i32.ge_u" => insts.push(Instruction::I32GeU),
-        "i32.load" => insts.push(Instruction::I32Load(MemArg {
-            offset: 0,
-            align: 2,
-            memory_index: 0,
-        })),
-        "i32.store" => insts.push(Instruction::I32Store(MemArg {
-            offset: 0,
-            align: 2,
-            memory_index: 0,
-        })),
-        "i32.load8_s" => insts.push(Instruction::I32Load8S(MemArg {
-            offset: 0,
-            align: 0,
-            memory_index: 0,
-        })),
-        "i32.load8_u" => insts.push(Instruction::I32Load8U(MemArg {
-            offset: 0,
-            align: 0,
-            memory_index: 0,
-        })),
-        "i32.load16_s" => insts.push(Instruction::I32Load16S(MemArg {
-            offset: 0,
-            align: 1,
-            memory_index: 0,
-        })),
-        "i32.load16_u" => insts.push(Instruction::I32Load16U(MemArg {
-            offset: 0,
-            align: 1,
-            memory_index: 0,
-        })),
-        "i32.store8" => insts.push(Instruction::I32Store8(MemArg {
-            offset: 0,
-            align: 0,
-            memory_index: 0,
-        })),
-        "i32.store16" => insts.push(Instruction::I32Store16(MemArg {
-            offset: 0,
-            align: 1,
-            memory_index: 0,
-        })),
+        "i32.load" => insts.push(Instruction::I32Load(mem.arg(2)?)),
+        "i32.store" => insts.push(Instruction::I32Store(mem.arg(2)?)),
+        "i32.load8_s" => insts.push(Instruction::I32Load8S(mem.arg(0)?)),
+        "i32.load8_u" => insts.push(Instruction::I32Load8U(mem.arg(0)?)),
+        "i32.load16_s" => insts.push(Instruction::I32Load16S(mem.arg(1)?)),
+        "i32.load16_u" => insts.push(Instruction::I32Load16U(mem.arg(1)?)),
+        "i32.store8" => insts.push(Instruction::I32Store8(mem.arg(0)?)),
+        "i32.store16" => insts.push(Instruction::I32Store16(mem.arg(1)?)),
         "i32.extend8_s" => insts.push(Instruction::I32Extend8S),
         "i32.extend16_s" => insts.push(Instruction::I32Extend16S),
         // i64 operations
@@ -1888,61 +3793,17 @@ fn parse_wasm_line(line: &str, locals: &LocalMap) -> Result<Vec<Instruction<'sta
         "i64.gt_u" => insts.push(Instruction::I64GtU),
         "i64.ge_s" => insts.push(Instruction::I64GeS),
         "i64.ge_u" => insts.push(Instruction::I64GeU),
-        "i64.load" => insts.push(Instruction::I64Load(MemArg {
-            offset: 0,
-            align: 3,
-            memory_index: 0,
-        })),
-        "i64.store" => insts.push(Instruction::I64Store(MemArg {
-            offset: 0,
-            align: 3,
-            memory_index: 0,
-        })),
-        "i64.load8_s" => insts.push(Instruction::I64Load8S(MemArg {
-            offset: 0,
-            align: 0,
-            memory_index: 0,
-        })),
-        "i64.load8_u" => insts.push(Instruction::I64Load8U(MemArg {
-            offset: 0,
-            align: 0,
-            memory_index: 0,
-        })),
-        "i64.load16_s" => insts.push(Instruction::I64Load16S(MemArg {
-            offset: 0,
-            align: 1,
-            memory_index: 0,
-        })),
-        "i64.load16_u" => insts.push(Instruction::I64Load16U(MemArg {
-            offset: 0,
-            align: 1,
-            memory_index: 0,
-        })),
-        "i64.load32_s" => insts.push(Instruction::I64Load32S(MemArg {
-            offset: 0,
-            align: 2,
-            memory_index: 0,
-        })),
-        "i64.load32_u" => insts.push(Instruction::I64Load32U(MemArg {
-            offset: 0,
-            align: 2,
-            memory_index: 0,
-        })),
-        "i64.store8" => insts.push(Instruction::I64Store8(MemArg {
-            offset: 0,
-            align: 0,
-            memory_index: 0,
-        })),
-        "i64.store16" => insts.push(Instruction::I64Store16(MemArg {
-            offset: 0,
-            align: 1,
-            memory_index: 0,
-        })),
-        "i64.store32" => insts.push(Instruction::I64Store32(MemArg {
-            offset: 0,
-            align: 2,
-            memory_index: 0,
-        })),
+        "i64.load" => insts.push(Instruction::I64Load(mem.arg(3)?)),
+        "i64.store" => insts.push(Instruction::I64Store(mem.arg(3)?)),
+        "i64.load8_s" => insts.push(Instruction::I64Load8S(mem.arg(0)?)),
+        "i64.load8_u" => insts.push(Instruction::I64Load8U(mem.arg(0)?)),
+        "i64.load16_s" => insts.push(Instruction::I64Load16S(mem.arg(1)?)),
+        "i64.load16_u" => insts.push(Instruction::I64Load16U(mem.arg(1)?)),
+        "i64.load32_s" => insts.push(Instruction::I64Load32S(mem.arg(2)?)),
+        "i64.load32_u" => insts.push(Instruction::I64Load32U(mem.arg(2)?)),
+        "i64.store8" => insts.push(Instruction::I64Store8(mem.arg(0)?)),
+        "i64.store16" => insts.push(Instruction::I64Store16(mem.arg(1)?)),
+        "i64.store32" => insts.push(Instruction::I64Store32(mem.arg(2)?)),
         // f32 operations
         "f32.const" if parts.len() == 2 => {
             if let Ok(v) = parts[1].parse::<f32>() {
@@ -1971,16 +3832,8 @@ fn parse_wasm_line(line: &str, locals: &LocalMap) -> Result<Vec<Instruction<'sta
         "f32.le" => insts.push(Instruction::F32Le),
         "f32.gt" => insts.push(Instruction::F32Gt),
         "f32.ge" => insts.push(Instruction::F32Ge),
-        "f32.load" => insts.push(Instruction::F32Load(MemArg {
-            offset: 0,
-            align: 2,
-            memory_index: 0,
-        })),
-        "f32.store" => insts.push(Instruction::F32Store(MemArg {
-            offset: 0,
-            align: 2,
-            memory_index: 0,
-        })),
+        "f32.load" => insts.push(Instruction::F32Load(mem.arg(2)?)),
+        "f32.store" => insts.push(Instruction::F32Store(mem.arg(2)?)),
         // f64 operations
         "f64.const" if parts.len() == 2 => {
             if let Ok(v) = parts[1].parse::<f64>() {
@@ -2009,16 +3862,8 @@ fn parse_wasm_line(line: &str, locals: &LocalMap) -> Result<Vec<Instruction<'sta
         "f64.le" => insts.push(Instruction::F64Le),
         "f64.gt" => insts.push(Instruction::F64Gt),
         "f64.ge" => insts.push(Instruction::F64Ge),
-        "f64.load" => insts.push(Instruction::F64Load(MemArg {
-            offset: 0,
-            align: 3,
-            memory_index: 0,
-        })),
-        "f64.store" => insts.push(Instruction::F64Store(MemArg {
-            offset: 0,
-            align: 3,
-            memory_index: 0,
-        })),
+        "f64.load" => insts.push(Instruction::F64Load(mem.arg(3)?)),
+        "f64.store" => insts.push(Instruction::F64Store(mem.arg(3)?)),
         // Type conversions
         "i32.wrap_i64" => insts.push(Instruction::I32WrapI64),
         "i64.extend_i32_s" => insts.push(Instruction::I64ExtendI32S),
@@ -2056,6 +3901,23 @@ fn parse_wasm_line(line: &str, locals: &LocalMap) -> Result<Vec<Instruction<'sta
         // Memory operations
         "memory.grow" => insts.push(Instruction::MemoryGrow(0)),
         "memory.size" => insts.push(Instruction::MemorySize(0)),
+        "memory.copy" => insts.push(Instruction::MemoryCopy {
+            src_mem: 0,
+            dst_mem: 0,
+        }),
+        "memory.fill" => insts.push(Instruction::MemoryFill(0)),
+        // SIMD (v128)
+        "v128.load" => insts.push(Instruction::V128Load(mem.arg(4)?)),
+        "v128.store" => insts.push(Instruction::V128Store(mem.arg(4)?)),
+        "i32x4.splat" => insts.push(Instruction::I32x4Splat),
+        "i32x4.add" => insts.push(Instruction::I32x4Add),
+        "i32x4.sub" => insts.push(Instruction::I32x4Sub),
+        "i32x4.mul" => insts.push(Instruction::I32x4Mul),
+        "f32x4.splat" => insts.push(Instruction::F32x4Splat),
+        "f32x4.add" => insts.push(Instruction::F32x4Add),
+        "f32x4.sub" => insts.push(Instruction::F32x4Sub),
+        "f32x4.mul" => insts.push(Instruction::F32x4Mul),
+        "f32x4.div" => insts.push(Instruction::F32x4Div),
         "drop" => insts.push(Instruction::Drop),
         other => return Err(format!("unsupported wasm instruction: {}", other)),
     }
@@ -2075,7 +3937,7 @@ fn parse_local(text: &str, locals: &LocalMap) -> Option<u32> {
     }
 }
 
-fn enum_variant_tag(ctx: &TypeCtx, enum_ty: TypeId, variant: &str) -> u32 {
+pub(crate) fn enum_variant_tag(ctx: &TypeCtx, enum_ty: TypeId, variant: &str) -> u32 {
     let name = if let Some(pos) = variant.rfind("::") {
         &variant[pos + 2..]
     } else {
@@ -2092,7 +3954,7 @@ fn enum_variant_tag(ctx: &TypeCtx, enum_ty: TypeId, variant: &str) -> u32 {
     }
 }
 
-fn enum_variant_payload(ctx: &TypeCtx, enum_ty: TypeId, variant: &str) -> Option<TypeId> {
+pub(crate) fn enum_variant_payload(ctx: &TypeCtx, enum_ty: TypeId, variant: &str) -> Option<TypeId> {
     let name = if let Some(pos) = variant.rfind("::") {
         &variant[pos + 2..]
     } else {
@@ -2127,16 +3989,3 @@ fn enum_variant_payload(ctx: &TypeCtx, enum_ty: TypeId, variant: &str) -> Option
         _ => None,
     }
 }
-
-fn validate_wasm_stack(
-    ctx: &TypeCtx,
-    func: &HirFunction,
-    locals: &LocalMap,
-    insts: &[Instruction<'static>],
-) -> Result<(), Diagnostic> {
-    // For now, we skip strict stack validation for #wasm blocks
-    // The WASM runtime will validate the instructions
-    // This allows us to support all WASM instructions without implementing
-    // full stack validation logic for every instruction
-    Ok(())
-}
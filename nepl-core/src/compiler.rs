@@ -8,6 +8,7 @@ use alloc::vec::Vec;
 
 use crate::ast;
 use crate::codegen_wasm;
+use crate::consteval;
 use crate::diagnostic::Diagnostic;
 use crate::error::CoreError;
 use crate::lexer;
@@ -97,19 +98,24 @@ pub struct CompilationArtifact {
     /// WAT 向けの補助情報（関数・ローカル変数・型）。
     /// 先頭コメントとして付与することを想定し、プレーンテキストで保持する。
     pub wat_comments: String,
+    /// `#export` された関数の ABI 記述（`.d.ts` / `.rs` スタブの元）。
+    pub exports: crate::bindings::ExportBindings,
 }
 
 /// 解析済みモジュールを最終成果物へ変換する。
 ///
 /// この関数はコンパイルパイプラインの中核であり、以下の段階を順番に実行する。
 /// 1. target/profile の確定
-/// 2. typecheck
-/// 3. monomorphize
-/// 4. move check
-/// 5. drop 挿入
-/// 6. wasm 生成と妥当性検証
+/// 2. `#if[const=...]` の解決
+/// 3. typecheck
+/// 4. monomorphize
+/// 5. const 評価と畳み込み
+/// 6. move check
+/// 7. drop 挿入
+/// 8. escape 解析（関数外へ出ない集成体をスタックフレームへ配置）
+/// 9. wasm 生成と妥当性検証
 pub fn compile_module(
-    module: ast::Module,
+    mut module: ast::Module,
     options: CompileOptions,
 ) -> Result<CompilationArtifact, CoreError> {
     crate::log::set_verbose(options.verbose);
@@ -123,13 +129,17 @@ pub fn compile_module(
         return Err(CoreError::from_diagnostics(diags));
     }
     let profile = options.profile.unwrap_or(BuildProfile::detect());
+    consteval::resolve_const_gates(&mut module, target, profile)
+        .map_err(CoreError::from_diagnostics)?;
     let tc = run_typecheck(&module, target, profile)?;
     let mut types = tc.types;
     let mut hir_module = monomorphize::monomorphize(&mut types, tc.module);
 
     let mut diagnostics = tc.diagnostics;
+    run_const_eval(&mut hir_module, &types, &mut diagnostics)?;
     run_move_check(&hir_module, &types, &mut diagnostics)?;
     passes::insert_drops(&mut hir_module, types.unit());
+    passes::place_frame_aggregates(&mut hir_module);
 
     emit_wasm(&types, &hir_module, diagnostics)
 }
@@ -203,6 +213,19 @@ fn run_move_check(
     Err(CoreError::from_diagnostics(diagnostics.clone()))
 }
 
+fn run_const_eval(
+    hir_module: &mut crate::hir::HirModule,
+    types: &crate::types::TypeCtx,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<(), CoreError> {
+    let const_errors = consteval::fold_consts(hir_module, types);
+    if const_errors.is_empty() {
+        return Ok(());
+    }
+    diagnostics.extend(const_errors);
+    Err(CoreError::from_diagnostics(diagnostics.clone()))
+}
+
 fn emit_wasm(
     types: &crate::types::TypeCtx,
     hir_module: &crate::hir::HirModule,
@@ -239,6 +262,7 @@ fn emit_wasm(
     Ok(CompilationArtifact {
         wasm: bytes,
         wat_comments: build_wat_comments(types, hir_module),
+        exports: crate::bindings::collect(types, hir_module),
     })
 }
 
@@ -372,7 +396,8 @@ fn collect_expr_locals(
             locals.entry(name.clone()).or_insert(value.ty);
             collect_expr_locals(value, locals);
         }
-        crate::hir::HirExprKind::Set { value, .. } => {
+        crate::hir::HirExprKind::Set { value, .. }
+        | crate::hir::HirExprKind::GlobalSet { value, .. } => {
             collect_expr_locals(value, locals);
         }
         crate::hir::HirExprKind::Call { args, .. } => {
@@ -395,7 +420,7 @@ fn collect_expr_locals(
             collect_expr_locals(then_branch, locals);
             collect_expr_locals(else_branch, locals);
         }
-        crate::hir::HirExprKind::While { cond, body } => {
+        crate::hir::HirExprKind::While { cond, body, .. } => {
             collect_expr_locals(cond, locals);
             collect_expr_locals(body, locals);
         }
@@ -426,7 +451,8 @@ fn collect_expr_locals(
             }
         }
         crate::hir::HirExprKind::AddrOf(inner)
-        | crate::hir::HirExprKind::Deref(inner) => {
+        | crate::hir::HirExprKind::Deref(inner)
+        | crate::hir::HirExprKind::Return(inner) => {
             collect_expr_locals(inner, locals);
         }
         crate::hir::HirExprKind::Block(block) => {
@@ -436,9 +462,15 @@ fn collect_expr_locals(
         | crate::hir::HirExprKind::FnValue(_)
         | crate::hir::HirExprKind::LiteralI32(_)
         | crate::hir::HirExprKind::LiteralF32(_)
+        | crate::hir::HirExprKind::LiteralI64(_)
+        | crate::hir::HirExprKind::LiteralF64(_)
         | crate::hir::HirExprKind::LiteralBool(_)
         | crate::hir::HirExprKind::LiteralStr(_)
+        | crate::hir::HirExprKind::StaticAddr(_)
+        | crate::hir::HirExprKind::GlobalGet(_)
         | crate::hir::HirExprKind::Unit
+        | crate::hir::HirExprKind::Break(_)
+        | crate::hir::HirExprKind::Continue(_)
         | crate::hir::HirExprKind::Drop { .. } => {}
     }
 }
@@ -450,6 +482,16 @@ fn resolve_target(
     if let Some(t) = options.target {
         return Ok(t);
     }
+    let (target, diags) = scan_target_directives(module);
+    if !diags.is_empty() {
+        return Err(CoreError::from_diagnostics(diags));
+    }
+    Ok(target)
+}
+
+/// Find the `#target` of `module`, defaulting to wasm. Unknown or duplicate
+/// directives are reported but do not abort the scan.
+pub(crate) fn scan_target_directives(module: &ast::Module) -> (CompileTarget, Vec<Diagnostic>) {
     let mut found: Option<(CompileTarget, Span)> = None;
     let mut diags = Vec::new();
     // First, check explicit module-level directives parsed into module.directives
@@ -493,10 +535,7 @@ fn resolve_target(
             }
         }
     }
-    if !diags.is_empty() {
-        return Err(CoreError::from_diagnostics(diags));
-    }
-    Ok(found.map(|(t, _)| t).unwrap_or(CompileTarget::Wasm))
+    (found.map(|(t, _)| t).unwrap_or(CompileTarget::Wasm), diags)
 }
 
 fn parse_target_name(name: &str) -> Option<CompileTarget> {
@@ -0,0 +1,1511 @@
+//! Compile-time evaluation of `const` items.
+//!
+//! A const item (`const NAME <T> expr`) is parsed as a zero-arg pure function
+//! marked `Constness::ConstItem`. After monomorphization the functions are run
+//! by a small HIR interpreter over a sandbox linear memory that mirrors the
+//! wasm layout (struct fields in 4/8-byte slots, enums as `[tag][payload]`,
+//! strings as `[len][bytes]`).
+//!
+//! - scalar results (including `i64` / `f64`) are folded into literals at
+//!   every call site
+//! - `str` results are folded into the existing string literal
+//! - struct/enum/tuple results are serialized into `HirModule::statics` and
+//!   referenced through `HirExprKind::StaticAddr`; the backend lays them out
+//!   right after the string literals
+//!
+//! `static` initializers run on the same interpreter, in declaration order,
+//! before the consts. Scalar results become the initial value of a global;
+//! `str` and aggregate results are placed in static data and the static
+//! itself becomes a 4-byte slot pointing at them.
+//!
+//! Only pure functions with HIR or `#wasm` bodies can be evaluated. The
+//! interpreter is bounded by a step and call-depth limit.
+
+extern crate alloc;
+extern crate std;
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use wasm_encoder::ValType;
+
+use crate::ast::{
+    Block, Directive, Effect, FnBody, Module, PrefixExpr, PrefixItem, Stmt, Symbol, WasmBlock,
+};
+use crate::codegen_wasm::{
+    enum_variant_payload, enum_variant_tag, scalar_size, slot_size, valtype,
+};
+use crate::compiler::{BuildProfile, CompileTarget};
+use crate::diagnostic::Diagnostic;
+use crate::hir::*;
+use crate::span::Span;
+use crate::types::{TypeCtx, TypeId, TypeKind};
+
+const STEP_LIMIT: u64 = 10_000_000;
+const DEPTH_LIMIT: usize = 256;
+
+/// A runtime value of the interpreter (aggregates are sandbox addresses).
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    Unit,
+}
+
+impl Value {
+    fn as_i32(self) -> Option<i32> {
+        match self {
+            Value::I32(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct EvalError {
+    message: String,
+    span: Span,
+}
+
+fn err<T>(message: impl Into<String>, span: Span) -> Result<T, EvalError> {
+    Err(EvalError {
+        message: message.into(),
+        span,
+    })
+}
+
+/// An allocation made while evaluating, with the types of its value slots
+/// so that pointers inside it can be relocated when it is serialized.
+struct Allocation {
+    size: u32,
+    slots: Vec<(u32, TypeId)>,
+}
+
+struct Interp<'a> {
+    ctx: &'a TypeCtx,
+    funcs: BTreeMap<&'a str, &'a HirFunction>,
+    strings: &'a [String],
+    globals: &'a [HirGlobal],
+    /// Values of the statics initialized so far.
+    global_values: Vec<Option<Value>>,
+    mem: Vec<u8>,
+    allocs: BTreeMap<u32, Allocation>,
+    /// sandbox address -> string literal index
+    str_addrs: BTreeMap<u32, u32>,
+    /// string literal index -> sandbox address
+    str_cache: BTreeMap<u32, u32>,
+    steps: u64,
+    depth: usize,
+    /// `Return` / `Break` / `Continue` unwinding to its target.
+    unwind: Option<Unwind>,
+}
+
+type Frame = BTreeMap<String, Value>;
+
+/// Control transfer carried by an `EvalError` until the target catches it.
+enum Unwind {
+    Return(Value),
+    Break(Option<String>),
+    Continue(Option<String>),
+}
+
+/// Whether an unlabeled or `target`-labeled exit stops at the loop `label`.
+fn exits_loop(target: &Option<String>, label: &Option<String>) -> bool {
+    target.is_none() || target == label
+}
+
+impl<'a> Interp<'a> {
+    fn new(ctx: &'a TypeCtx, module: &'a HirModule) -> Self {
+        let mut funcs = BTreeMap::new();
+        for f in &module.functions {
+            funcs.insert(f.name.as_str(), f);
+        }
+        Self {
+            ctx,
+            funcs,
+            strings: &module.string_literals,
+            globals: &module.globals,
+            global_values: Vec::new(),
+            // Address 0 stays unused so that null pointers are never valid.
+            mem: vec![0; 8],
+            allocs: BTreeMap::new(),
+            str_addrs: BTreeMap::new(),
+            str_cache: BTreeMap::new(),
+            steps: 0,
+            depth: 0,
+            unwind: None,
+        }
+    }
+
+    fn step(&mut self, span: Span) -> Result<(), EvalError> {
+        self.steps += 1;
+        if self.steps > STEP_LIMIT {
+            return err("const evaluation exceeded the step limit", span);
+        }
+        Ok(())
+    }
+
+    // ---------------------------------------------------------------
+    // sandbox memory
+    // ---------------------------------------------------------------
+
+    fn alloc(&mut self, size: u32, slots: Vec<(u32, TypeId)>) -> u32 {
+        let addr = (self.mem.len() as u32 + 7) & !7;
+        self.mem.resize((addr + size.max(4)) as usize, 0);
+        self.allocs.insert(addr, Allocation { size, slots });
+        addr
+    }
+
+    fn bytes(&self, addr: i32, len: u32, span: Span) -> Result<&[u8], EvalError> {
+        let start = addr as u32 as usize;
+        let end = start.saturating_add(len as usize);
+        if addr <= 0 || end > self.mem.len() {
+            return err(
+                format!("out-of-bounds memory access at address {} during const evaluation", addr),
+                span,
+            );
+        }
+        Ok(&self.mem[start..end])
+    }
+
+    fn bytes_mut(&mut self, addr: i32, len: u32, span: Span) -> Result<&mut [u8], EvalError> {
+        let start = addr as u32 as usize;
+        let end = start.saturating_add(len as usize);
+        if addr <= 0 || end > self.mem.len() {
+            return err(
+                format!("out-of-bounds memory access at address {} during const evaluation", addr),
+                span,
+            );
+        }
+        Ok(&mut self.mem[start..end])
+    }
+
+    fn read_u32(&self, addr: i32, span: Span) -> Result<u32, EvalError> {
+        let b = self.bytes(addr, 4, span)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn read_u64(&self, addr: i32, span: Span) -> Result<u64, EvalError> {
+        let b = self.bytes(addr, 8, span)?;
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(b);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    /// Loads a value of type `ty` the same way `codegen_wasm` would.
+    fn load(&self, addr: i32, ty: TypeId, span: Span) -> Result<Value, EvalError> {
+        let kind = self.ctx.get(ty);
+        match valtype(&kind) {
+            Some(ValType::I32) if matches!(kind, TypeKind::U8) => {
+                Ok(Value::I32(self.bytes(addr, 1, span)?[0] as i32))
+            }
+            Some(ValType::I32) => Ok(Value::I32(self.read_u32(addr, span)? as i32)),
+            Some(ValType::F32) => Ok(Value::F32(f32::from_bits(self.read_u32(addr, span)?))),
+            Some(ValType::I64) => Ok(Value::I64(self.read_u64(addr, span)? as i64)),
+            Some(ValType::F64) => Ok(Value::F64(f64::from_bits(self.read_u64(addr, span)?))),
+            Some(ValType::V128) => err("v128 values are not supported in const evaluation", span),
+            _ => Ok(Value::Unit),
+        }
+    }
+
+    fn store(&mut self, addr: i32, value: Value, ty: TypeId, span: Span) -> Result<(), EvalError> {
+        if matches!(self.ctx.get(ty), TypeKind::U8) {
+            let v = value.as_i32().unwrap_or(0);
+            self.bytes_mut(addr, 1, span)?[0] = v as u8;
+            return Ok(());
+        }
+        self.store_value(addr, value, span)
+    }
+
+    fn store_value(&mut self, addr: i32, value: Value, span: Span) -> Result<(), EvalError> {
+        match value {
+            Value::I32(v) => self.bytes_mut(addr, 4, span)?.copy_from_slice(&v.to_le_bytes()),
+            Value::F32(v) => self
+                .bytes_mut(addr, 4, span)?
+                .copy_from_slice(&v.to_bits().to_le_bytes()),
+            Value::I64(v) => self.bytes_mut(addr, 8, span)?.copy_from_slice(&v.to_le_bytes()),
+            Value::F64(v) => self
+                .bytes_mut(addr, 8, span)?
+                .copy_from_slice(&v.to_bits().to_le_bytes()),
+            Value::Unit => self.bytes_mut(addr, 4, span)?.copy_from_slice(&[0; 4]),
+        }
+        Ok(())
+    }
+
+    fn intern_str(&mut self, idx: u32, span: Span) -> Result<i32, EvalError> {
+        if let Some(addr) = self.str_cache.get(&idx) {
+            return Ok(*addr as i32);
+        }
+        let Some(s) = self.strings.get(idx as usize) else {
+            return err("string literal not found during const evaluation", span);
+        };
+        let bytes = s.as_bytes().to_vec();
+        let addr = (self.mem.len() as u32 + 3) & !3;
+        self.mem.resize(addr as usize, 0);
+        self.mem.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        self.mem.extend_from_slice(&bytes);
+        self.str_cache.insert(idx, addr);
+        self.str_addrs.insert(addr, idx);
+        Ok(addr as i32)
+    }
+
+    // ---------------------------------------------------------------
+    // HIR evaluation
+    // ---------------------------------------------------------------
+
+    fn call(&mut self, name: &str, args: Vec<Value>, span: Span) -> Result<Value, EvalError> {
+        let Some(func) = self.funcs.get(name).copied() else {
+            return err(format!("'{}' cannot be called at compile time", name), span);
+        };
+        if func.effect == Effect::Impure {
+            return err(
+                format!("cannot call impure function '{}' at compile time", name),
+                span,
+            );
+        }
+        if self.depth >= DEPTH_LIMIT {
+            return err("const evaluation exceeded the call depth limit", span);
+        }
+        self.depth += 1;
+        let out = match &func.body {
+            HirBody::Block(b) => {
+                let mut frame = Frame::new();
+                for (p, v) in func.params.iter().zip(args) {
+                    frame.insert(p.name.clone(), v);
+                }
+                self.block(b, &mut frame).or_else(|e| match self.unwind.take() {
+                    Some(Unwind::Return(v)) => Ok(v),
+                    _ => Err(e),
+                })
+            }
+            HirBody::Wasm(w) => self.wasm(func, w, args),
+            HirBody::LlvmIr(_) => err(
+                format!("raw LLVM IR body of '{}' cannot be evaluated at compile time", name),
+                func.span,
+            ),
+        };
+        self.depth -= 1;
+        out
+    }
+
+    fn block(&mut self, block: &HirBlock, frame: &mut Frame) -> Result<Value, EvalError> {
+        let mut last = Value::Unit;
+        for line in &block.lines {
+            let v = self.expr(&line.expr, frame)?;
+            last = if line.drop_result { Value::Unit } else { v };
+        }
+        Ok(last)
+    }
+
+    fn truthy(&mut self, expr: &HirExpr, frame: &mut Frame) -> Result<bool, EvalError> {
+        match self.expr(expr, frame)? {
+            Value::I32(v) => Ok(v != 0),
+            _ => err("condition is not a bool", expr.span),
+        }
+    }
+
+    /// Allocates an aggregate whose fields are laid out like `StructConstruct`.
+    fn construct(&mut self, items: &[HirExpr], frame: &mut Frame) -> Result<Value, EvalError> {
+        let mut values = Vec::with_capacity(items.len());
+        for item in items {
+            values.push(self.expr(item, frame)?);
+        }
+        let mut slots = Vec::with_capacity(items.len());
+        let mut size = 0u32;
+        for item in items {
+            slots.push((size, item.ty));
+            size += slot_size(valtype(&self.ctx.get(item.ty)));
+        }
+        let addr = self.alloc(size, slots.clone());
+        for ((offset, ty), v) in slots.iter().zip(values) {
+            self.store((addr + offset) as i32, v, *ty, Span::dummy())?;
+        }
+        Ok(Value::I32(addr as i32))
+    }
+
+    fn expr(&mut self, expr: &HirExpr, frame: &mut Frame) -> Result<Value, EvalError> {
+        self.step(expr.span)?;
+        match &expr.kind {
+            HirExprKind::LiteralI32(v) => Ok(Value::I32(*v)),
+            HirExprKind::LiteralF32(v) => Ok(Value::F32(*v)),
+            HirExprKind::LiteralI64(v) => Ok(Value::I64(*v)),
+            HirExprKind::LiteralF64(v) => Ok(Value::F64(*v)),
+            HirExprKind::LiteralBool(b) => Ok(Value::I32(*b as i32)),
+            HirExprKind::LiteralStr(idx) => Ok(Value::I32(self.intern_str(*idx, expr.span)?)),
+            HirExprKind::StaticAddr(_) => err("static data cannot be read at compile time", expr.span),
+            HirExprKind::GlobalGet(idx) => match self.global_values.get(*idx as usize) {
+                Some(Some(v)) => Ok(*v),
+                _ => {
+                    let name = self.globals.get(*idx as usize).map_or("?", |g| g.name.as_str());
+                    err(
+                        format!("static '{}' is read before it is initialized", name),
+                        expr.span,
+                    )
+                }
+            },
+            HirExprKind::GlobalSet { .. } => {
+                err("statics cannot be assigned at compile time", expr.span)
+            }
+            HirExprKind::Unit | HirExprKind::Drop { .. } => Ok(Value::Unit),
+            HirExprKind::Var(name) => match frame.get(name) {
+                Some(v) => Ok(*v),
+                None => err(
+                    format!("'{}' is not available at compile time", name),
+                    expr.span,
+                ),
+            },
+            HirExprKind::FnValue(_) | HirExprKind::CallIndirect { .. } => err(
+                "function values are not supported in const evaluation",
+                expr.span,
+            ),
+            HirExprKind::Call { callee, args } => {
+                let mut values = Vec::with_capacity(args.len());
+                for a in args {
+                    values.push(self.expr(a, frame)?);
+                }
+                match callee {
+                    FuncRef::Builtin(name) | FuncRef::User(name, _) => {
+                        self.call(name, values, expr.span)
+                    }
+                    FuncRef::Trait {
+                        trait_name, method, ..
+                    } => err(
+                        format!(
+                            "trait method '{}::{}' cannot be called at compile time",
+                            trait_name, method
+                        ),
+                        expr.span,
+                    ),
+                }
+            }
+            HirExprKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                if self.truthy(cond, frame)? {
+                    self.expr(then_branch, frame)
+                } else {
+                    self.expr(else_branch, frame)
+                }
+            }
+            HirExprKind::While { cond, body, label } => {
+                while self.truthy(cond, frame)? {
+                    if let Err(e) = self.expr(body, frame) {
+                        match self.unwind.take() {
+                            Some(Unwind::Break(t)) if exits_loop(&t, label) => break,
+                            Some(Unwind::Continue(t)) if exits_loop(&t, label) => continue,
+                            other => {
+                                self.unwind = other;
+                                return Err(e);
+                            }
+                        }
+                    }
+                }
+                Ok(Value::Unit)
+            }
+            HirExprKind::Match { scrutinee, arms } => {
+                let Some(ptr) = self.expr(scrutinee, frame)?.as_i32() else {
+                    return err("match scrutinee is not an enum", scrutinee.span);
+                };
+                let tag = self.read_u32(ptr, scrutinee.span)?;
+                for arm in arms {
+                    if enum_variant_tag(self.ctx, scrutinee.ty, &arm.variant) != tag {
+                        continue;
+                    }
+                    if let Some(bind) = &arm.bind_local {
+                        if let Some(pty) = enum_variant_payload(self.ctx, scrutinee.ty, &arm.variant) {
+                            let offset = slot_size(valtype(&self.ctx.get(pty))) as i32;
+                            let v = self.load(ptr + offset, pty, arm.body.span)?;
+                            frame.insert(bind.clone(), v);
+                        }
+                    }
+                    return self.expr(&arm.body, frame);
+                }
+                err("no match arm for the enum value", expr.span)
+            }
+            HirExprKind::EnumConstruct {
+                variant, payload, ..
+            } => {
+                let payload_val = match payload {
+                    Some(p) => Some((self.expr(p, frame)?, p.ty)),
+                    None => None,
+                };
+                let payload_vt = payload.as_ref().and_then(|p| valtype(&self.ctx.get(p.ty)));
+                let (offset, size) = match payload_vt {
+                    Some(_) => (slot_size(payload_vt), 2 * slot_size(payload_vt)),
+                    None => (0, 4),
+                };
+                let slots = match (payload_vt, &payload_val) {
+                    (Some(_), Some((_, ty))) => vec![(offset, *ty)],
+                    _ => Vec::new(),
+                };
+                let addr = self.alloc(size, slots);
+                let tag = enum_variant_tag(self.ctx, expr.ty, variant);
+                self.store_value(addr as i32, Value::I32(tag as i32), expr.span)?;
+                if let (Some(_), Some((v, ty))) = (payload_vt, payload_val) {
+                    self.store((addr + offset) as i32, v, ty, expr.span)?;
+                }
+                Ok(Value::I32(addr as i32))
+            }
+            HirExprKind::StructConstruct { fields, .. } => self.construct(fields, frame),
+            HirExprKind::TupleConstruct { items } => self.construct(items, frame),
+            HirExprKind::Block(b) => self.block(b, frame),
+            HirExprKind::Let { name, value, .. } | HirExprKind::Set { name, value } => {
+                let v = self.expr(value, frame)?;
+                frame.insert(name.clone(), v);
+                Ok(Value::Unit)
+            }
+            HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) => self.expr(inner, frame),
+            HirExprKind::Return(value) => {
+                // Unwinds as an error until `call` turns it back into the result.
+                self.unwind = Some(Unwind::Return(self.expr(value, frame)?));
+                err("return outside of a function", expr.span)
+            }
+            HirExprKind::Break(target) => {
+                self.unwind = Some(Unwind::Break(target.clone()));
+                err("break outside of a loop", expr.span)
+            }
+            HirExprKind::Continue(target) => {
+                self.unwind = Some(Unwind::Continue(target.clone()));
+                err("continue outside of a loop", expr.span)
+            }
+            HirExprKind::Intrinsic {
+                name,
+                type_args,
+                args,
+            } => self.intrinsic(name, type_args, args, expr.span, frame),
+        }
+    }
+
+    fn intrinsic(
+        &mut self,
+        name: &str,
+        type_args: &[TypeId],
+        args: &[HirExpr],
+        span: Span,
+        frame: &mut Frame,
+    ) -> Result<Value, EvalError> {
+        let mut values = Vec::with_capacity(args.len());
+        for a in args {
+            values.push(self.expr(a, frame)?);
+        }
+        let arg_i32 = |i: usize| values.get(i).and_then(|v| v.as_i32()).unwrap_or(0);
+        match name {
+            "size_of" | "align_of" => Ok(Value::I32(scalar_size(self.ctx, type_args[0]))),
+            "load" => self.load(arg_i32(0), type_args[0], span),
+            "store" => {
+                self.store(arg_i32(0), values[1], type_args[0], span)?;
+                Ok(Value::Unit)
+            }
+            "mem_copy" | "mem_fill" => {
+                let len = arg_i32(2) as u32;
+                if len == 0 {
+                    return Ok(Value::Unit);
+                }
+                if name == "mem_copy" {
+                    let src = self.bytes(arg_i32(1), len, span)?.to_vec();
+                    self.bytes_mut(arg_i32(0), len, span)?.copy_from_slice(&src);
+                } else {
+                    let value = arg_i32(1) as u8;
+                    self.bytes_mut(arg_i32(0), len, span)?.fill(value);
+                }
+                Ok(Value::Unit)
+            }
+            "add" => Ok(Value::I32(arg_i32(0).wrapping_add(arg_i32(1)))),
+            "lt_s" => Ok(Value::I32((arg_i32(0) < arg_i32(1)) as i32)),
+            "i32_to_f32" => Ok(Value::F32(arg_i32(0) as f32)),
+            "i32_to_u8" => Ok(Value::I32(arg_i32(0) & 255)),
+            "u8_to_i32" => Ok(Value::I32(arg_i32(0))),
+            "f32_to_i32" => match values[0] {
+                Value::F32(v) => trunc_f32_s(v).map(Value::I32).ok_or(EvalError {
+                    message: String::from("float to int conversion overflow at compile time"),
+                    span,
+                }),
+                _ => err("f32_to_i32 expects an f32", span),
+            },
+            "reinterpret_i32_f32" => Ok(Value::F32(f32::from_bits(arg_i32(0) as u32))),
+            "reinterpret_f32_i32" => match values[0] {
+                Value::F32(v) => Ok(Value::I32(v.to_bits() as i32)),
+                _ => err("reinterpret_f32_i32 expects an f32", span),
+            },
+            "callsite_span" => {
+                let slots = vec![(0, self.ctx.i32()), (4, self.ctx.i32()), (8, self.ctx.i32())];
+                let addr = self.alloc(12, slots) as i32;
+                self.store_value(addr, Value::I32(span.file_id.0 as i32), span)?;
+                self.store_value(addr + 4, Value::I32(span.start as i32), span)?;
+                self.store_value(addr + 8, Value::I32(span.end as i32), span)?;
+                Ok(Value::I32(addr))
+            }
+            "unreachable" => err("const evaluation reached `unreachable`", span),
+            other => err(
+                format!("intrinsic '{}' is not supported in const evaluation", other),
+                span,
+            ),
+        }
+    }
+
+    // ---------------------------------------------------------------
+    // `#wasm` bodies (straight-line subset accepted by codegen_wasm)
+    // ---------------------------------------------------------------
+
+    fn wasm(&mut self, func: &HirFunction, block: &WasmBlock, args: Vec<Value>) -> Result<Value, EvalError> {
+        let span = block.span;
+        let mut locals = args;
+        let mut stack: Vec<Value> = Vec::new();
+        for raw in &block.lines {
+            let line = raw.split(";;").next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            self.step(span)?;
+            let mut parts = line.split_whitespace();
+            let op = parts.next().unwrap_or("");
+            let operand = parts.next();
+            match op {
+                "local.get" | "local.set" | "local.tee" => {
+                    let Some(idx) = operand.and_then(|t| wasm_local_index(func, t)) else {
+                        return err(format!("unknown local in `{}`", line), span);
+                    };
+                    if idx >= locals.len() {
+                        locals.resize(idx + 1, Value::I32(0));
+                    }
+                    if op == "local.get" {
+                        stack.push(locals[idx]);
+                    } else {
+                        let v = pop(&mut stack, span)?;
+                        locals[idx] = v;
+                        if op == "local.tee" {
+                            stack.push(v);
+                        }
+                    }
+                }
+                "i32.const" | "i64.const" | "f32.const" | "f64.const" => {
+                    let text = operand.unwrap_or("");
+                    let v = match op {
+                        "i32.const" => parse_int(text).map(|v| Value::I32(v as i32)),
+                        "i64.const" => parse_int(text).map(Value::I64),
+                        "f32.const" => text.parse::<f32>().ok().map(Value::F32),
+                        _ => text.parse::<f64>().ok().map(Value::F64),
+                    };
+                    match v {
+                        Some(v) => stack.push(v),
+                        None => return err(format!("invalid constant in `{}`", line), span),
+                    }
+                }
+                "drop" => {
+                    pop(&mut stack, span)?;
+                }
+                "nop" => {}
+                "unreachable" => return err("const evaluation reached `unreachable`", span),
+                "select" => {
+                    let c = pop_i32(&mut stack, span)?;
+                    let b = pop(&mut stack, span)?;
+                    let a = pop(&mut stack, span)?;
+                    stack.push(if c != 0 { a } else { b });
+                }
+                _ if op.contains(".load") => {
+                    let addr = pop_i32(&mut stack, span)?;
+                    let v = self.wasm_load(op, addr, span)?;
+                    stack.push(v);
+                }
+                _ => {
+                    let v = wasm_numeric(op, &mut stack, span)?;
+                    stack.push(v);
+                }
+            }
+        }
+        if valtype(&self.ctx.get(func.result)).is_none() {
+            Ok(Value::Unit)
+        } else {
+            pop(&mut stack, span)
+        }
+    }
+
+    fn wasm_load(&self, op: &str, addr: i32, span: Span) -> Result<Value, EvalError> {
+        let b = |len: u32| self.bytes(addr, len, span);
+        Ok(match op {
+            "i32.load" => Value::I32(self.read_u32(addr, span)? as i32),
+            "i32.load8_u" => Value::I32(b(1)?[0] as i32),
+            "i32.load8_s" => Value::I32(b(1)?[0] as i8 as i32),
+            "i32.load16_u" => Value::I32(u16::from_le_bytes([b(2)?[0], b(2)?[1]]) as i32),
+            "i32.load16_s" => Value::I32(i16::from_le_bytes([b(2)?[0], b(2)?[1]]) as i32),
+            "i64.load" => Value::I64(self.read_u64(addr, span)? as i64),
+            "f32.load" => Value::F32(f32::from_bits(self.read_u32(addr, span)?)),
+            "f64.load" => Value::F64(f64::from_bits(self.read_u64(addr, span)?)),
+            _ => return err(format!("`{}` is not supported in const evaluation", op), span),
+        })
+    }
+
+    // ---------------------------------------------------------------
+    // folding
+    // ---------------------------------------------------------------
+
+    /// Converts an evaluated value into the expression that replaces calls
+    /// to the const. `None` means the value has no literal form.
+    fn fold_value(
+        &self,
+        value: Value,
+        ty: TypeId,
+        span: Span,
+        statics: &mut StaticBuilder,
+    ) -> Result<Option<HirExprKind>, EvalError> {
+        let kind = self.ctx.get(ty);
+        Ok(match (kind, value) {
+            (TypeKind::Unit, _) => Some(HirExprKind::Unit),
+            (TypeKind::Bool, Value::I32(v)) => Some(HirExprKind::LiteralBool(v != 0)),
+            (TypeKind::I32 | TypeKind::U8, Value::I32(v)) => Some(HirExprKind::LiteralI32(v)),
+            (TypeKind::F32, Value::F32(v)) => Some(HirExprKind::LiteralF32(v)),
+            (TypeKind::Named(_), Value::I64(v)) => Some(HirExprKind::LiteralI64(v)),
+            (TypeKind::Named(_), Value::F64(v)) => Some(HirExprKind::LiteralF64(v)),
+            (TypeKind::Str, Value::I32(addr)) => match self.str_addrs.get(&(addr as u32)) {
+                Some(idx) => Some(HirExprKind::LiteralStr(*idx)),
+                None => return err("const string must come from a string literal", span),
+            },
+            (k, Value::I32(addr)) if is_aggregate(&k) => {
+                Some(HirExprKind::StaticAddr(self.serialize(addr as u32, span, statics)?))
+            }
+            _ => {
+                return err(
+                    format!(
+                        "a const of type '{}' cannot be placed in static data",
+                        self.ctx.type_to_string(ty)
+                    ),
+                    span,
+                )
+            }
+        })
+    }
+
+    /// Converts the evaluated initializer of a static into its storage.
+    fn global_init(
+        &self,
+        value: Value,
+        ty: TypeId,
+        span: Span,
+        statics: &mut StaticBuilder,
+    ) -> Result<HirGlobalInit, EvalError> {
+        let kind = self.ctx.get(ty);
+        let target = match (&kind, value) {
+            (TypeKind::Str, Value::I32(addr)) => match self.str_addrs.get(&(addr as u32)) {
+                Some(idx) => StaticRef::Str(*idx),
+                None => return err("static string must come from a string literal", span),
+            },
+            (k, Value::I32(addr)) if is_aggregate(k) => {
+                StaticRef::Static(self.serialize(addr as u32, span, statics)?)
+            }
+            (TypeKind::Function { .. } | TypeKind::Reference(..) | TypeKind::Box(_), _) => {
+                return err(
+                    format!(
+                        "a static of type '{}' cannot be initialized at compile time",
+                        self.ctx.type_to_string(ty)
+                    ),
+                    span,
+                )
+            }
+            (_, Value::I32(v)) => return Ok(HirGlobalInit::I32(v)),
+            (_, Value::I64(v)) => return Ok(HirGlobalInit::I64(v)),
+            (_, Value::F32(v)) => return Ok(HirGlobalInit::F32(v)),
+            (_, Value::F64(v)) => return Ok(HirGlobalInit::F64(v)),
+            (_, Value::Unit) => return Ok(HirGlobalInit::I32(0)),
+        };
+        let idx = statics.base + statics.blocks.len() as u32;
+        statics.blocks.push(HirStatic {
+            bytes: vec![0; 4],
+            align: 4,
+            relocs: vec![HirReloc { offset: 0, target }],
+        });
+        Ok(HirGlobalInit::Slot(idx))
+    }
+
+    /// Runs the static initializers in declaration order. Each one can read
+    /// the statics before it.
+    fn eval_globals(
+        &mut self,
+        statics: &mut StaticBuilder,
+        diags: &mut Vec<Diagnostic>,
+    ) -> Vec<Option<HirGlobalInit>> {
+        let mut inits = Vec::with_capacity(self.globals.len());
+        for g in self.globals {
+            let value = self.call(&g.func, Vec::new(), g.span);
+            let init = value.and_then(|v| Ok((v, self.global_init(v, g.ty, g.span, statics)?)));
+            match init {
+                Ok((v, init)) => {
+                    self.global_values.push(Some(v));
+                    inits.push(Some(init));
+                }
+                Err(e) => {
+                    diags.push(
+                        Diagnostic::error(
+                            format!("cannot evaluate the initializer of static '{}'", g.name),
+                            g.span,
+                        )
+                        .with_secondary_label(e.span, Some(e.message)),
+                    );
+                    self.global_values.push(None);
+                    inits.push(None);
+                }
+            }
+        }
+        inits
+    }
+
+    /// Copies the allocation at `addr` (and everything it points to) into
+    /// static data blocks and returns the index of the root block.
+    fn serialize(&self, addr: u32, span: Span, statics: &mut StaticBuilder) -> Result<u32, EvalError> {
+        if let Some(idx) = statics.memo.get(&addr) {
+            return Ok(*idx);
+        }
+        let Some(alloc) = self.allocs.get(&addr) else {
+            return err("const value refers to memory that cannot be placed in static data", span);
+        };
+        let idx = statics.base + statics.blocks.len() as u32;
+        statics.memo.insert(addr, idx);
+        statics.blocks.push(HirStatic {
+            bytes: Vec::new(),
+            align: 8,
+            relocs: Vec::new(),
+        });
+        let mut bytes = self.bytes(addr as i32, alloc.size, span)?.to_vec();
+        let mut relocs = Vec::new();
+        for (offset, ty) in &alloc.slots {
+            let kind = self.ctx.get(*ty);
+            let target = match kind {
+                TypeKind::Str => {
+                    let p = self.read_u32((addr + offset) as i32, span)?;
+                    match self.str_addrs.get(&p) {
+                        Some(s) => StaticRef::Str(*s),
+                        None => return err("const string must come from a string literal", span),
+                    }
+                }
+                ref k if is_aggregate(k) => {
+                    let p = self.read_u32((addr + offset) as i32, span)?;
+                    StaticRef::Static(self.serialize(p, span, statics)?)
+                }
+                TypeKind::Function { .. } | TypeKind::Reference(..) | TypeKind::Box(_) => {
+                    return err(
+                        format!(
+                            "a value of type '{}' cannot be placed in static data",
+                            self.ctx.type_to_string(*ty)
+                        ),
+                        span,
+                    )
+                }
+                _ => continue,
+            };
+            let at = *offset as usize;
+            bytes[at..at + 4].copy_from_slice(&[0; 4]);
+            relocs.push(HirReloc {
+                offset: *offset,
+                target,
+            });
+        }
+        let block = &mut statics.blocks[(idx - statics.base) as usize];
+        block.bytes = bytes;
+        block.relocs = relocs;
+        Ok(idx)
+    }
+}
+
+struct StaticBuilder {
+    base: u32,
+    blocks: Vec<HirStatic>,
+    memo: BTreeMap<u32, u32>,
+}
+
+fn is_aggregate(kind: &TypeKind) -> bool {
+    matches!(
+        kind,
+        TypeKind::Struct { .. } | TypeKind::Enum { .. } | TypeKind::Tuple { .. } | TypeKind::Apply { .. }
+    )
+}
+
+fn wasm_local_index(func: &HirFunction, text: &str) -> Option<usize> {
+    match text.strip_prefix('$') {
+        Some(name) => func.params.iter().position(|p| p.name == name),
+        None => text.parse().ok(),
+    }
+}
+
+fn parse_int(text: &str) -> Option<i64> {
+    let (neg, body) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let body = body.replace('_', "");
+    let v = match body.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()? as i64,
+        None => body.parse::<u64>().ok()? as i64,
+    };
+    Some(if neg { v.wrapping_neg() } else { v })
+}
+
+fn pop(stack: &mut Vec<Value>, span: Span) -> Result<Value, EvalError> {
+    match stack.pop() {
+        Some(v) => Ok(v),
+        None => err("wasm stack underflow during const evaluation", span),
+    }
+}
+
+fn pop_i32(stack: &mut Vec<Value>, span: Span) -> Result<i32, EvalError> {
+    match pop(stack, span)? {
+        Value::I32(v) => Ok(v),
+        _ => err("wasm type mismatch during const evaluation: expected i32", span),
+    }
+}
+
+fn pop_i64(stack: &mut Vec<Value>, span: Span) -> Result<i64, EvalError> {
+    match pop(stack, span)? {
+        Value::I64(v) => Ok(v),
+        _ => err("wasm type mismatch during const evaluation: expected i64", span),
+    }
+}
+
+fn pop_f32(stack: &mut Vec<Value>, span: Span) -> Result<f32, EvalError> {
+    match pop(stack, span)? {
+        Value::F32(v) => Ok(v),
+        _ => err("wasm type mismatch during const evaluation: expected f32", span),
+    }
+}
+
+fn pop_f64(stack: &mut Vec<Value>, span: Span) -> Result<f64, EvalError> {
+    match pop(stack, span)? {
+        Value::F64(v) => Ok(v),
+        _ => err("wasm type mismatch during const evaluation: expected f64", span),
+    }
+}
+
+fn trunc_f32_s(v: f32) -> Option<i32> {
+    let t = v.trunc();
+    (-2147483648.0..2147483648.0).contains(&t).then_some(t as i32)
+}
+
+fn trunc_f64_s(v: f64) -> Option<i32> {
+    let t = v.trunc();
+    (t > -2147483649.0 && t < 2147483648.0).then_some(t as i32)
+}
+
+/// Evaluates a numeric wasm instruction (everything except locals, consts,
+/// loads and parametric ops).
+fn wasm_numeric(op: &str, stack: &mut Vec<Value>, span: Span) -> Result<Value, EvalError> {
+    let Some((ty, name)) = op.split_once('.') else {
+        return err(format!("`{}` is not supported in const evaluation", op), span);
+    };
+    let trap = |what: &str| err(format!("{} during const evaluation", what), span);
+    let unsupported = || err(format!("`{}` is not supported in const evaluation", op), span);
+    match ty {
+        "i32" => {
+            let unary = matches!(
+                name,
+                "eqz" | "clz" | "ctz" | "popcnt" | "extend8_s" | "extend16_s"
+            );
+            if unary {
+                let a = pop_i32(stack, span)?;
+                return Ok(Value::I32(match name {
+                    "eqz" => (a == 0) as i32,
+                    "clz" => a.leading_zeros() as i32,
+                    "ctz" => a.trailing_zeros() as i32,
+                    "popcnt" => a.count_ones() as i32,
+                    "extend8_s" => a as i8 as i32,
+                    _ => a as i16 as i32,
+                }));
+            }
+            match name {
+                "wrap_i64" => return Ok(Value::I32(pop_i64(stack, span)? as i32)),
+                "reinterpret_f32" => return Ok(Value::I32(pop_f32(stack, span)?.to_bits() as i32)),
+                "trunc_f32_s" => {
+                    return trunc_f32_s(pop_f32(stack, span)?)
+                        .map(Value::I32)
+                        .map_or_else(|| trap("float to int conversion overflow"), Ok)
+                }
+                "trunc_f64_s" => {
+                    return trunc_f64_s(pop_f64(stack, span)?)
+                        .map(Value::I32)
+                        .map_or_else(|| trap("float to int conversion overflow"), Ok)
+                }
+                "trunc_sat_f32_s" => return Ok(Value::I32(pop_f32(stack, span)? as i32)),
+                "trunc_sat_f32_u" => return Ok(Value::I32(pop_f32(stack, span)? as u32 as i32)),
+                "trunc_sat_f64_s" => return Ok(Value::I32(pop_f64(stack, span)? as i32)),
+                "trunc_sat_f64_u" => return Ok(Value::I32(pop_f64(stack, span)? as u32 as i32)),
+                _ => {}
+            }
+            let b = pop_i32(stack, span)?;
+            let a = pop_i32(stack, span)?;
+            let (ua, ub) = (a as u32, b as u32);
+            Ok(Value::I32(match name {
+                "add" => a.wrapping_add(b),
+                "sub" => a.wrapping_sub(b),
+                "mul" => a.wrapping_mul(b),
+                "div_s" | "rem_s" | "div_u" | "rem_u" if b == 0 => {
+                    return trap("integer division by zero")
+                }
+                "div_s" => match a.checked_div(b) {
+                    Some(v) => v,
+                    None => return trap("integer overflow"),
+                },
+                "rem_s" => a.wrapping_rem(b),
+                "div_u" => (ua / ub) as i32,
+                "rem_u" => (ua % ub) as i32,
+                "and" => a & b,
+                "or" => a | b,
+                "xor" => a ^ b,
+                "shl" => a.wrapping_shl(ub),
+                "shr_s" => a.wrapping_shr(ub),
+                "shr_u" => ua.wrapping_shr(ub) as i32,
+                "rotl" => ua.rotate_left(ub % 32) as i32,
+                "rotr" => ua.rotate_right(ub % 32) as i32,
+                "eq" => (a == b) as i32,
+                "ne" => (a != b) as i32,
+                "lt_s" => (a < b) as i32,
+                "lt_u" => (ua < ub) as i32,
+                "le_s" => (a <= b) as i32,
+                "le_u" => (ua <= ub) as i32,
+                "gt_s" => (a > b) as i32,
+                "gt_u" => (ua > ub) as i32,
+                "ge_s" => (a >= b) as i32,
+                "ge_u" => (ua >= ub) as i32,
+                _ => return unsupported(),
+            }))
+        }
+        "i64" => {
+            match name {
+                "eqz" => return Ok(Value::I32((pop_i64(stack, span)? == 0) as i32)),
+                "clz" => return Ok(Value::I64(pop_i64(stack, span)?.leading_zeros() as i64)),
+                "ctz" => return Ok(Value::I64(pop_i64(stack, span)?.trailing_zeros() as i64)),
+                "popcnt" => return Ok(Value::I64(pop_i64(stack, span)?.count_ones() as i64)),
+                "extend_i32_s" => return Ok(Value::I64(pop_i32(stack, span)? as i64)),
+                "extend_i32_u" => return Ok(Value::I64(pop_i32(stack, span)? as u32 as i64)),
+                "reinterpret_f64" => return Ok(Value::I64(pop_f64(stack, span)?.to_bits() as i64)),
+                "trunc_sat_f32_s" => return Ok(Value::I64(pop_f32(stack, span)? as i64)),
+                "trunc_sat_f32_u" => return Ok(Value::I64(pop_f32(stack, span)? as u64 as i64)),
+                "trunc_sat_f64_s" => return Ok(Value::I64(pop_f64(stack, span)? as i64)),
+                "trunc_sat_f64_u" => return Ok(Value::I64(pop_f64(stack, span)? as u64 as i64)),
+                _ => {}
+            }
+            let b = pop_i64(stack, span)?;
+            let a = pop_i64(stack, span)?;
+            let (ua, ub) = (a as u64, b as u64);
+            let cmp = |c: bool| Ok(Value::I32(c as i32));
+            Ok(Value::I64(match name {
+                "add" => a.wrapping_add(b),
+                "sub" => a.wrapping_sub(b),
+                "mul" => a.wrapping_mul(b),
+                "div_s" | "rem_s" | "div_u" | "rem_u" if b == 0 => {
+                    return trap("integer division by zero")
+                }
+                "div_s" => match a.checked_div(b) {
+                    Some(v) => v,
+                    None => return trap("integer overflow"),
+                },
+                "rem_s" => a.wrapping_rem(b),
+                "div_u" => (ua / ub) as i64,
+                "rem_u" => (ua % ub) as i64,
+                "and" => a & b,
+                "or" => a | b,
+                "xor" => a ^ b,
+                "shl" => a.wrapping_shl(ub as u32),
+                "shr_s" => a.wrapping_shr(ub as u32),
+                "shr_u" => ua.wrapping_shr(ub as u32) as i64,
+                "rotl" => ua.rotate_left((ub % 64) as u32) as i64,
+                "rotr" => ua.rotate_right((ub % 64) as u32) as i64,
+                "eq" => return cmp(a == b),
+                "ne" => return cmp(a != b),
+                "lt_s" => return cmp(a < b),
+                "lt_u" => return cmp(ua < ub),
+                "le_s" => return cmp(a <= b),
+                "le_u" => return cmp(ua <= ub),
+                "gt_s" => return cmp(a > b),
+                "gt_u" => return cmp(ua > ub),
+                "ge_s" => return cmp(a >= b),
+                "ge_u" => return cmp(ua >= ub),
+                _ => return unsupported(),
+            }))
+        }
+        "f32" => {
+            match name {
+                "convert_i32_s" => return Ok(Value::F32(pop_i32(stack, span)? as f32)),
+                "convert_i32_u" => return Ok(Value::F32(pop_i32(stack, span)? as u32 as f32)),
+                "convert_i64_s" => return Ok(Value::F32(pop_i64(stack, span)? as f32)),
+                "convert_i64_u" => return Ok(Value::F32(pop_i64(stack, span)? as u64 as f32)),
+                "demote_f64" => return Ok(Value::F32(pop_f64(stack, span)? as f32)),
+                "reinterpret_i32" => {
+                    return Ok(Value::F32(f32::from_bits(pop_i32(stack, span)? as u32)))
+                }
+                "abs" | "neg" | "ceil" | "floor" | "trunc" | "nearest" | "sqrt" => {
+                    let a = pop_f32(stack, span)?;
+                    return Ok(Value::F32(match name {
+                        "abs" => a.abs(),
+                        "neg" => -a,
+                        "ceil" => a.ceil(),
+                        "floor" => a.floor(),
+                        "trunc" => a.trunc(),
+                        "nearest" => a.round_ties_even(),
+                        _ => a.sqrt(),
+                    }));
+                }
+                _ => {}
+            }
+            let b = pop_f32(stack, span)?;
+            let a = pop_f32(stack, span)?;
+            let cmp = |c: bool| Ok(Value::I32(c as i32));
+            Ok(Value::F32(match name {
+                "add" => a + b,
+                "sub" => a - b,
+                "mul" => a * b,
+                "div" => a / b,
+                "min" => a.min(b),
+                "max" => a.max(b),
+                "copysign" => a.copysign(b),
+                "eq" => return cmp(a == b),
+                "ne" => return cmp(a != b),
+                "lt" => return cmp(a < b),
+                "le" => return cmp(a <= b),
+                "gt" => return cmp(a > b),
+                "ge" => return cmp(a >= b),
+                _ => return unsupported(),
+            }))
+        }
+        "f64" => {
+            match name {
+                "convert_i32_s" => return Ok(Value::F64(pop_i32(stack, span)? as f64)),
+                "convert_i32_u" => return Ok(Value::F64(pop_i32(stack, span)? as u32 as f64)),
+                "convert_i64_s" => return Ok(Value::F64(pop_i64(stack, span)? as f64)),
+                "convert_i64_u" => return Ok(Value::F64(pop_i64(stack, span)? as u64 as f64)),
+                "promote_f32" => return Ok(Value::F64(pop_f32(stack, span)? as f64)),
+                "reinterpret_i64" => {
+                    return Ok(Value::F64(f64::from_bits(pop_i64(stack, span)? as u64)))
+                }
+                "abs" | "neg" | "ceil" | "floor" | "trunc" | "nearest" | "sqrt" => {
+                    let a = pop_f64(stack, span)?;
+                    return Ok(Value::F64(match name {
+                        "abs" => a.abs(),
+                        "neg" => -a,
+                        "ceil" => a.ceil(),
+                        "floor" => a.floor(),
+                        "trunc" => a.trunc(),
+                        "nearest" => a.round_ties_even(),
+                        _ => a.sqrt(),
+                    }));
+                }
+                _ => {}
+            }
+            let b = pop_f64(stack, span)?;
+            let a = pop_f64(stack, span)?;
+            let cmp = |c: bool| Ok(Value::I32(c as i32));
+            Ok(Value::F64(match name {
+                "add" => a + b,
+                "sub" => a - b,
+                "mul" => a * b,
+                "div" => a / b,
+                "min" => a.min(b),
+                "max" => a.max(b),
+                "copysign" => a.copysign(b),
+                "eq" => return cmp(a == b),
+                "ne" => return cmp(a != b),
+                "lt" => return cmp(a < b),
+                "le" => return cmp(a <= b),
+                "gt" => return cmp(a > b),
+                "ge" => return cmp(a >= b),
+                _ => return unsupported(),
+            }))
+        }
+        _ => unsupported(),
+    }
+}
+
+// ---------------------------------------------------------------------
+// Public entry points
+// ---------------------------------------------------------------------
+
+fn const_error(c: &HirConst, e: EvalError) -> Diagnostic {
+    Diagnostic::error(
+        format!("cannot evaluate const '{}' at compile time", c.name),
+        c.span,
+    )
+    .with_secondary_label(e.span, Some(e.message))
+}
+
+/// Evaluates the static initializers and every const item of `module`, and
+/// replaces calls to a const with the folded value. Aggregate values are
+/// appended to `module.statics`.
+pub fn fold_consts(module: &mut HirModule, ctx: &TypeCtx) -> Vec<Diagnostic> {
+    evaluate(module, ctx, true)
+}
+
+/// Evaluates only the static initializers (consts stay zero-arg functions).
+pub fn eval_globals(module: &mut HirModule, ctx: &TypeCtx) -> Vec<Diagnostic> {
+    evaluate(module, ctx, false)
+}
+
+fn evaluate(module: &mut HirModule, ctx: &TypeCtx, with_consts: bool) -> Vec<Diagnostic> {
+    let mut diags = Vec::new();
+    if module.globals.is_empty() && (!with_consts || module.consts.is_empty()) {
+        return diags;
+    }
+    let mut folded: BTreeMap<String, HirExprKind> = BTreeMap::new();
+    let mut statics = StaticBuilder {
+        base: module.statics.len() as u32,
+        blocks: Vec::new(),
+        memo: BTreeMap::new(),
+    };
+    let inits = {
+        let mut interp = Interp::new(ctx, module);
+        let inits = interp.eval_globals(&mut statics, &mut diags);
+        let consts: &[HirConst] = if with_consts { &module.consts } else { &[] };
+        for c in consts {
+            let value = interp.call(&c.func, Vec::new(), c.span);
+            match value.and_then(|v| interp.fold_value(v, c.ty, c.span, &mut statics)) {
+                Ok(Some(kind)) => {
+                    folded.insert(c.func.clone(), kind);
+                }
+                Ok(None) => {}
+                Err(e) => diags.push(const_error(c, e)),
+            }
+        }
+        inits
+    };
+    module.statics.extend(statics.blocks);
+    for (g, init) in module.globals.iter_mut().zip(inits) {
+        g.init = init;
+    }
+    for f in &mut module.functions {
+        if let HirBody::Block(b) = &mut f.body {
+            fold_block(b, &folded);
+        }
+    }
+    diags
+}
+
+fn fold_block(block: &mut HirBlock, folded: &BTreeMap<String, HirExprKind>) {
+    for line in &mut block.lines {
+        fold_expr(&mut line.expr, folded);
+    }
+}
+
+fn fold_expr(expr: &mut HirExpr, folded: &BTreeMap<String, HirExprKind>) {
+    match &mut expr.kind {
+        HirExprKind::Call { callee, args } => {
+            if let FuncRef::User(name, _) = callee {
+                if args.is_empty() {
+                    if let Some(kind) = folded.get(name.as_str()) {
+                        expr.kind = kind.clone();
+                        return;
+                    }
+                }
+            }
+            for a in args {
+                fold_expr(a, folded);
+            }
+        }
+        HirExprKind::CallIndirect { callee, args, .. } => {
+            fold_expr(callee, folded);
+            for a in args {
+                fold_expr(a, folded);
+            }
+        }
+        HirExprKind::If {
+            cond,
+            then_branch,
+            else_branch,
+        } => {
+            fold_expr(cond, folded);
+            fold_expr(then_branch, folded);
+            fold_expr(else_branch, folded);
+        }
+        HirExprKind::While { cond, body, .. } => {
+            fold_expr(cond, folded);
+            fold_expr(body, folded);
+        }
+        HirExprKind::Match { scrutinee, arms } => {
+            fold_expr(scrutinee, folded);
+            for arm in arms {
+                fold_expr(&mut arm.body, folded);
+            }
+        }
+        HirExprKind::EnumConstruct { payload, .. } => {
+            if let Some(p) = payload {
+                fold_expr(p, folded);
+            }
+        }
+        HirExprKind::StructConstruct { fields: items, .. }
+        | HirExprKind::TupleConstruct { items }
+        | HirExprKind::Intrinsic { args: items, .. } => {
+            for item in items {
+                fold_expr(item, folded);
+            }
+        }
+        HirExprKind::Block(b) => fold_block(b, folded),
+        HirExprKind::Let { value, .. }
+        | HirExprKind::Set { value, .. }
+        | HirExprKind::GlobalSet { value, .. } => fold_expr(value, folded),
+        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) | HirExprKind::Return(inner) => {
+            fold_expr(inner, folded)
+        }
+        HirExprKind::LiteralI32(_)
+        | HirExprKind::LiteralF32(_)
+        | HirExprKind::LiteralI64(_)
+        | HirExprKind::LiteralF64(_)
+        | HirExprKind::LiteralBool(_)
+        | HirExprKind::LiteralStr(_)
+        | HirExprKind::StaticAddr(_)
+        | HirExprKind::GlobalGet(_)
+        | HirExprKind::Unit
+        | HirExprKind::Var(_)
+        | HirExprKind::FnValue(_)
+        | HirExprKind::Break(_)
+        | HirExprKind::Continue(_)
+        | HirExprKind::Drop { .. } => {}
+    }
+}
+
+/// Resolves `#if[const=NAME]` gates of `module` in place.
+///
+/// The consts named by the gates are checked and evaluated on a slice of the
+/// module that holds only the items they (transitively) refer to, with every
+/// const gate closed. Gates whose const is true (or non-zero) are then removed
+/// so that the following item is compiled; closed gates are left in place and
+/// keep excluding their item.
+pub fn resolve_const_gates(
+    module: &mut Module,
+    target: CompileTarget,
+    profile: BuildProfile,
+) -> Result<(), Vec<Diagnostic>> {
+    let mut names = Vec::new();
+    collect_const_gates(&module.root, &mut names);
+    if names.is_empty() {
+        return Ok(());
+    }
+    let roots: Vec<String> = names.iter().map(|(n, _)| n.clone()).collect();
+    let slice = dependency_slice(module, &roots);
+    let tc = crate::typecheck::typecheck(&slice, target, profile);
+    let Some(hir) = tc.module else {
+        return Err(tc.diagnostics);
+    };
+    let mut types = tc.types;
+    let hir = crate::monomorphize::monomorphize(&mut types, hir);
+
+    let mut diags = Vec::new();
+    let mut open: BTreeMap<String, bool> = BTreeMap::new();
+    {
+        let mut interp = Interp::new(&types, &hir);
+        let mut statics = StaticBuilder {
+            base: 0,
+            blocks: Vec::new(),
+            memo: BTreeMap::new(),
+        };
+        interp.eval_globals(&mut statics, &mut diags);
+        for (name, span) in &names {
+            if open.contains_key(name) {
+                continue;
+            }
+            let Some(c) = hir.consts.iter().find(|c| &c.name == name) else {
+                diags.push(Diagnostic::error(
+                    format!("unknown const '{}' in `#if[const=...]`", name),
+                    *span,
+                ));
+                continue;
+            };
+            if !matches!(types.get(c.ty), TypeKind::Bool | TypeKind::I32) {
+                diags.push(Diagnostic::error(
+                    format!("const '{}' used in `#if` must be bool or i32", name),
+                    *span,
+                ));
+                continue;
+            }
+            match interp.call(&c.func, Vec::new(), c.span) {
+                Ok(Value::I32(v)) => {
+                    open.insert(name.clone(), v != 0);
+                }
+                Ok(_) => {}
+                Err(e) => diags.push(const_error(c, e)),
+            }
+        }
+    }
+    if !diags.is_empty() {
+        return Err(diags);
+    }
+    open_const_gates(&mut module.root, &open);
+    Ok(())
+}
+
+/// Builds a copy of `module` without `#entry` and without the functions and
+/// statics that `roots` do not reach by name. Type definitions, impls and directives are
+/// kept as they are; gates are kept together with the item they guard.
+fn dependency_slice(module: &Module, roots: &[String]) -> Module {
+    let mut wanted: BTreeSet<String> = roots.iter().cloned().collect();
+    let mut included: BTreeSet<usize> = BTreeSet::new();
+    loop {
+        let before = included.len();
+        for (idx, stmt) in module.root.items.iter().enumerate() {
+            if included.contains(&idx) {
+                continue;
+            }
+            match stmt {
+                Stmt::FnDef(f) if wanted.contains(&f.name.name) => {
+                    included.insert(idx);
+                    if let FnBody::Parsed(b) = &f.body {
+                        collect_block_idents(b, &mut wanted);
+                    }
+                }
+                Stmt::FnAlias(a) if wanted.contains(&a.name.name) => {
+                    included.insert(idx);
+                    wanted.insert(a.target.name.clone());
+                }
+                Stmt::Static(s) if wanted.contains(&s.name.name) => {
+                    included.insert(idx);
+                    collect_expr_idents(&s.value, &mut wanted);
+                }
+                _ => {}
+            }
+        }
+        if included.len() == before {
+            break;
+        }
+    }
+
+    let mut items = Vec::new();
+    let mut gates = Vec::new();
+    for (idx, stmt) in module.root.items.iter().enumerate() {
+        let keep = match stmt {
+            Stmt::Directive(
+                d @ (Directive::IfTarget { .. }
+                | Directive::IfProfile { .. }
+                | Directive::IfConst { .. }),
+            ) => {
+                gates.push(Stmt::Directive(d.clone()));
+                continue;
+            }
+            Stmt::Directive(Directive::Entry { .. }) => false,
+            Stmt::FnDef(_) | Stmt::FnAlias(_) | Stmt::Static(_) => included.contains(&idx),
+            Stmt::Expr(_) | Stmt::ExprSemi(..) => false,
+            _ => true,
+        };
+        if keep {
+            items.append(&mut gates);
+            items.push(stmt.clone());
+        } else {
+            gates.clear();
+        }
+    }
+    Module {
+        indent_width: module.indent_width,
+        directives: module
+            .directives
+            .iter()
+            .filter(|d| !matches!(d, Directive::Entry { .. }))
+            .cloned()
+            .collect(),
+        root: Block {
+            items,
+            span: module.root.span,
+        },
+    }
+}
+
+fn collect_block_idents(block: &Block, out: &mut BTreeSet<String>) {
+    for stmt in &block.items {
+        match stmt {
+            Stmt::Expr(e) | Stmt::ExprSemi(e, _) => collect_expr_idents(e, out),
+            Stmt::FnDef(f) => {
+                if let FnBody::Parsed(b) = &f.body {
+                    collect_block_idents(b, out);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn collect_expr_idents(expr: &PrefixExpr, out: &mut BTreeSet<String>) {
+    for item in &expr.items {
+        match item {
+            PrefixItem::Symbol(Symbol::Ident(id, _, _)) => {
+                if let Some((_, last)) = id.name.rsplit_once("::") {
+                    out.insert(String::from(last));
+                }
+                out.insert(id.name.clone());
+            }
+            PrefixItem::Block(b, _) => collect_block_idents(b, out),
+            PrefixItem::Match(m, _) => {
+                collect_expr_idents(&m.scrutinee, out);
+                for arm in &m.arms {
+                    collect_block_idents(&arm.body, out);
+                }
+            }
+            PrefixItem::For(f, _) => {
+                out.insert(String::from("next"));
+                collect_expr_idents(&f.iter, out);
+                collect_block_idents(&f.body, out);
+            }
+            PrefixItem::Tuple(items, _) => {
+                for e in items {
+                    collect_expr_idents(e, out);
+                }
+            }
+            PrefixItem::Group(e, _) => collect_expr_idents(e, out),
+            PrefixItem::Intrinsic(i, _) => {
+                for e in &i.args {
+                    collect_expr_idents(e, out);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns `true` when `module` contains any `#if[const=...]` gate.
+pub fn has_const_gates(module: &Module) -> bool {
+    let mut names = Vec::new();
+    collect_const_gates(&module.root, &mut names);
+    !names.is_empty()
+}
+
+fn collect_const_gates(block: &Block, out: &mut Vec<(String, Span)>) {
+    for stmt in &block.items {
+        match stmt {
+            Stmt::Directive(Directive::IfConst { name, span }) => out.push((name.clone(), *span)),
+            Stmt::FnDef(f) => {
+                if let FnBody::Parsed(b) = &f.body {
+                    collect_const_gates(b, out);
+                }
+            }
+            Stmt::Expr(e) | Stmt::ExprSemi(e, _) => {
+                for item in &e.items {
+                    if let PrefixItem::Block(b, _) = item {
+                        collect_const_gates(b, out);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn open_const_gates(block: &mut Block, open: &BTreeMap<String, bool>) {
+    block.items.retain(|stmt| match stmt {
+        Stmt::Directive(Directive::IfConst { name, .. }) => !open.get(name).copied().unwrap_or(false),
+        _ => true,
+    });
+    for stmt in &mut block.items {
+        match stmt {
+            Stmt::FnDef(f) => {
+                if let FnBody::Parsed(b) = &mut f.body {
+                    open_const_gates(b, open);
+                }
+            }
+            Stmt::Expr(e) | Stmt::ExprSemi(e, _) => {
+                for item in &mut e.items {
+                    if let PrefixItem::Block(b, _) = item {
+                        open_const_gates(b, open);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -30,6 +30,8 @@ pub struct Label {
 /// A diagnostic has a main message, a primary label indicating the
 /// main source location, and zero or more secondary labels for
 /// related locations (for example, “defined here”, “required here”).
+/// Notes are free-form hints without a location (for example,
+/// “did you mean `print`?”).
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Diagnostic {
     pub severity: Severity,
@@ -37,6 +39,7 @@ pub struct Diagnostic {
     pub message: String,
     pub primary: Label,
     pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
 }
 
 impl Diagnostic {
@@ -51,6 +54,7 @@ impl Diagnostic {
                 message: None,
             },
             secondary: Vec::new(),
+            notes: Vec::new(),
         }
     }
 
@@ -65,6 +69,7 @@ impl Diagnostic {
                 message: None,
             },
             secondary: Vec::new(),
+            notes: Vec::new(),
         }
     }
 
@@ -86,4 +91,10 @@ impl Diagnostic {
         });
         self
     }
+
+    /// Add a note without a source location.
+    pub fn with_note(mut self, note: impl Into<String>) -> Diagnostic {
+        self.notes.push(note.into());
+        self
+    }
 }
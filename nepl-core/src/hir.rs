@@ -18,6 +18,77 @@ pub struct HirModule {
     pub string_literals: Vec<String>,
     pub traits: Vec<HirTrait>,
     pub impls: Vec<HirImpl>,
+    /// Top-level `const` items, evaluated by `consteval::fold_consts`.
+    pub consts: Vec<HirConst>,
+    /// Pre-initialized data blocks laid out after the string literals.
+    pub statics: Vec<HirStatic>,
+    /// Top-level `static` items in declaration order.
+    pub globals: Vec<HirGlobal>,
+    /// Functions exported from the wasm module by `#export`.
+    pub exports: Vec<HirExport>,
+}
+
+/// An `#export`ed function. `name` is the external name, `func` the symbol.
+#[derive(Debug, Clone)]
+pub struct HirExport {
+    pub name: String,
+    pub func: String,
+    pub span: Span,
+}
+
+/// A `const` item. Its initializer is the zero-arg pure function `func`.
+#[derive(Debug, Clone)]
+pub struct HirConst {
+    pub name: String,
+    pub func: String,
+    pub ty: TypeId,
+    pub span: Span,
+}
+
+/// A block of static data. `relocs` are 4-byte slots patched with the
+/// address of another string literal or static once the layout is known.
+#[derive(Debug, Clone)]
+pub struct HirStatic {
+    pub bytes: Vec<u8>,
+    pub align: u32,
+    pub relocs: Vec<HirReloc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HirReloc {
+    pub offset: u32,
+    pub target: StaticRef,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StaticRef {
+    Str(u32),
+    Static(u32),
+}
+
+/// A `static` item. `func` computes the initial value; `consteval` stores
+/// the result in `init`.
+#[derive(Debug, Clone)]
+pub struct HirGlobal {
+    pub name: String,
+    pub func: String,
+    pub ty: TypeId,
+    pub mutable: bool,
+    pub init: Option<HirGlobalInit>,
+    pub span: Span,
+}
+
+/// Initial value and storage of a static.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HirGlobalInit {
+    /// Scalars live in a wasm global (an LLVM global on the llvm backend).
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    /// Pointer-sized values (`str`, struct, enum, tuple) live in the 4-byte
+    /// data slot `HirModule::statics[idx]`.
+    Slot(u32),
 }
 
 #[derive(Debug, Clone)]
@@ -80,8 +151,19 @@ pub struct HirExpr {
 pub enum HirExprKind {
     LiteralI32(i32),
     LiteralF32(f32),
+    LiteralI64(i64),
+    LiteralF64(f64),
     LiteralBool(bool),
     LiteralStr(u32),
+    /// Address of `HirModule::statics[idx]`.
+    StaticAddr(u32),
+    /// Reads `HirModule::globals[idx]`.
+    GlobalGet(u32),
+    /// Writes `HirModule::globals[idx]`.
+    GlobalSet {
+        index: u32,
+        value: Box<HirExpr>,
+    },
     Unit,
     Var(String),
     /// Explicit function-value reference created by `@fn_name`.
@@ -104,6 +186,8 @@ pub enum HirExprKind {
     While {
         cond: Box<HirExpr>,
         body: Box<HirExpr>,
+        /// `<.label>` naming the loop for `Break` / `Continue`.
+        label: Option<String>,
     },
     Match {
         scrutinee: Box<HirExpr>,
@@ -140,6 +224,12 @@ pub enum HirExprKind {
     },
     AddrOf(Box<HirExpr>),
     Deref(Box<HirExpr>),
+    /// Leaves the enclosing function with the value; has type `Never`.
+    Return(Box<HirExpr>),
+    /// Leaves the innermost loop, or the one with this label; has type `Never`.
+    Break(Option<String>),
+    /// Jumps to the next iteration of the innermost or labeled loop; has type `Never`.
+    Continue(Option<String>),
     Drop {
         name: String,
     },
@@ -34,6 +34,7 @@ pub enum TokenKind {
     At,            // @
     Dot,
     Ampersand, // &
+    Question,  // ?
     Star,      // *
     Minus,     // -
     Equals,    // =
@@ -54,6 +55,9 @@ pub enum TokenKind {
     KwSet,
     KwIf,
     KwWhile,
+    KwReturn,
+    KwBreak,
+    KwContinue,
     KwCond,
     KwThen,
     KwElse,
@@ -68,6 +72,10 @@ pub enum TokenKind {
     KwBlock,
     KwTuple,
     KwMlstr,
+    KwConst,
+    KwStatic,
+    KwType,
+    KwNewtype,
 
     // directives
     DirEntry(String),
@@ -76,6 +84,8 @@ pub enum TokenKind {
     DirUse(String),
     DirIfTarget(String),
     DirIfProfile(String),
+    DirIfConst(String),
+    DirExport(String),
     DirWasm,
     DirLlvmIr,
     DirIndentWidth(usize),
@@ -264,6 +274,9 @@ impl LexState {
                 effective_indent = expected;
             }
         }
+        if in_wasm {
+            effective_indent = self.wasm_base.unwrap_or(effective_indent);
+        }
         if in_llvmir {
             effective_indent = self.llvmir_base.unwrap_or(effective_indent);
         }
@@ -318,9 +331,9 @@ impl LexState {
         }
 
         // Always emit INDENT/DEDENT to keep parser block structure.
-        // Inside #llvmir raw block, internal indentation changes are not NEPL syntax,
+        // Inside #wasm / #llvmir raw blocks, internal indentation changes are not NEPL syntax,
         // so keep indentation fixed to the block base and skip width checks.
-        self.adjust_indent(effective_indent, line_start, in_llvmir);
+        self.adjust_indent(effective_indent, line_start, in_wasm || in_llvmir);
 
         let line_offset = line_start + (content.len() - rest.len());
 
@@ -498,6 +511,17 @@ impl LexState {
                 kind: TokenKind::DirUse(arg.to_string()),
                 span,
             });
+        } else if body.starts_with("export") {
+            let arg = body.strip_prefix("export").unwrap().trim();
+            let span = Span::new(
+                self.file_id,
+                line_offset as u32,
+                (line_offset + body.len()) as u32,
+            );
+            self.tokens.push(Token {
+                kind: TokenKind::DirExport(arg.to_string()),
+                span,
+            });
         } else if body.starts_with("if[target=") {
             if let Some(end) = body.find(']') {
                 let target = &body[10..end];
@@ -524,6 +548,19 @@ impl LexState {
                     span,
                 });
             }
+        } else if body.starts_with("if[const=") {
+            if let Some(end) = body.find(']') {
+                let name = &body[9..end];
+                let span = Span::new(
+                    self.file_id,
+                    line_offset as u32,
+                    (line_offset + end + 1) as u32,
+                );
+                self.tokens.push(Token {
+                    kind: TokenKind::DirIfConst(name.to_string()),
+                    span,
+                });
+            }
         } else if body.starts_with("extern") {
             // format: extern "env" "sym" fn name <signature>
             let span = Span::new(
@@ -633,6 +670,10 @@ impl LexState {
                     self.push_token(TokenKind::Ampersand, offset + i, offset + i + 1);
                     i += 1;
                 }
+                b'?' => {
+                    self.push_token(TokenKind::Question, offset + i, offset + i + 1);
+                    i += 1;
+                }
                 b',' => {
                     self.push_token(TokenKind::Comma, offset + i, offset + i + 1);
                     i += 1;
@@ -914,6 +955,9 @@ fn keyword_token(lexeme: &str) -> Option<TokenKind> {
         "set" => Some(TokenKind::KwSet),
         "if" => Some(TokenKind::KwIf),
         "while" => Some(TokenKind::KwWhile),
+        "return" => Some(TokenKind::KwReturn),
+        "break" => Some(TokenKind::KwBreak),
+        "continue" => Some(TokenKind::KwContinue),
         "cond" => Some(TokenKind::KwCond),
         "then" => Some(TokenKind::KwThen),
         "else" => Some(TokenKind::KwElse),
@@ -928,6 +972,10 @@ fn keyword_token(lexeme: &str) -> Option<TokenKind> {
         "block" => Some(TokenKind::KwBlock),
         "Tuple" => Some(TokenKind::KwTuple),
         "mlstr" => Some(TokenKind::KwMlstr),
+        "const" => Some(TokenKind::KwConst),
+        "static" => Some(TokenKind::KwStatic),
+        "type" => Some(TokenKind::KwType),
+        "newtype" => Some(TokenKind::KwNewtype),
         "true" => Some(TokenKind::BoolLiteral(true)),
         "false" => Some(TokenKind::BoolLiteral(false)),
         _ => None,
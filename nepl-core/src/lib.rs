@@ -17,11 +17,14 @@ pub mod diagnostic;
 pub mod error;
 pub mod span;
 
+pub mod analysis;
 pub mod ast;
+pub mod bindings;
 pub mod builtins;
 pub mod codegen_llvm;
 pub mod codegen_wasm;
 pub mod compiler;
+pub mod consteval;
 pub mod hir;
 pub mod lexer;
 pub mod loader;
@@ -31,6 +34,8 @@ pub mod module_graph;
 pub mod parser;
 pub mod passes;
 pub mod resolve;
+pub mod simd;
+pub mod suggest;
 pub mod typecheck;
 pub mod types;
 
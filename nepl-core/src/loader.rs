@@ -1,9 +1,10 @@
 use crate::ast::{Directive, Module, Stmt};
-use crate::diagnostic::Severity;
+use crate::diagnostic::{Diagnostic, Severity};
 use crate::error::CoreError;
 use crate::lexer;
 use crate::parser;
-use crate::span::FileId;
+use crate::span::{FileId, Span};
+use crate::suggest;
 use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::format;
 use alloc::string::String;
@@ -12,7 +13,7 @@ use alloc::vec::Vec;
 use core::result::Result;
 #[cfg(not(target_arch = "wasm32"))]
 use std::fs;
-use std::path::{Component, PathBuf};
+use std::path::{Component, Path, PathBuf};
 extern crate std;
 
 #[derive(Debug)]
@@ -108,6 +109,8 @@ pub struct LoadResult {
 pub struct Loader {
     stdlib_root: PathBuf,
     source_map: SourceMap,
+    /// Merged module of the last load that failed only with parse errors.
+    recovered: Option<Module>,
 }
 
 impl Loader {
@@ -115,6 +118,7 @@ impl Loader {
         Self {
             stdlib_root,
             source_map: SourceMap::new(),
+            recovered: None,
         }
     }
 
@@ -122,31 +126,29 @@ impl Loader {
         &self.source_map
     }
 
+    /// When the last load failed only because some files had parse errors,
+    /// the module merged from their recovered ASTs, imports included.
+    pub fn recovered_module(&self) -> Option<&Module> {
+        self.recovered.as_ref()
+    }
+
     /// Load an already-provided source string as a pseudo file (for stdin use).
     pub fn load_inline(&mut self, path: PathBuf, src: String) -> Result<LoadResult, LoaderError> {
         let mut sm = SourceMap::new();
         let mut cache: BTreeMap<PathBuf, Module> = BTreeMap::new();
         let mut processing: BTreeSet<PathBuf> = BTreeSet::new();
         let mut imported: BTreeSet<PathBuf> = BTreeSet::new();
-        let module = match self.load_from_contents(
+        let mut parse_errors = Vec::new();
+        let result = self.load_from_contents(
             path,
             src,
             &mut sm,
             &mut cache,
             &mut processing,
             &mut imported,
-        ) {
-            Ok(m) => m,
-            Err(e) => {
-                self.source_map = sm.clone();
-                return Err(e);
-            }
-        };
-        self.source_map = sm.clone();
-        Ok(LoadResult {
-            module,
-            source_map: sm,
-        })
+            &mut parse_errors,
+        );
+        self.finish(result, sm, parse_errors)
     }
 
     pub fn load_inline_with_provider(
@@ -159,26 +161,18 @@ impl Loader {
         let mut cache: BTreeMap<PathBuf, Module> = BTreeMap::new();
         let mut processing: BTreeSet<PathBuf> = BTreeSet::new();
         let mut imported: BTreeSet<PathBuf> = BTreeSet::new();
-        let module = match self.load_from_contents_with(
+        let mut parse_errors = Vec::new();
+        let result = self.load_from_contents_with(
             path,
             src,
             &mut sm,
             &mut cache,
             &mut processing,
             &mut imported,
+            &mut parse_errors,
             provider,
-        ) {
-            Ok(m) => m,
-            Err(e) => {
-                self.source_map = sm.clone();
-                return Err(e);
-            }
-        };
-        self.source_map = sm.clone();
-        Ok(LoadResult {
-            module,
-            source_map: sm,
-        })
+        );
+        self.finish(result, sm, parse_errors)
     }
 
     pub fn load(&mut self, entry: &PathBuf) -> Result<LoadResult, LoaderError> {
@@ -186,24 +180,43 @@ impl Loader {
         let mut cache: BTreeMap<PathBuf, Module> = BTreeMap::new();
         let mut processing: BTreeSet<PathBuf> = BTreeSet::new();
         let mut imported: BTreeSet<PathBuf> = BTreeSet::new();
-        let module = match self.load_file(
+        let mut parse_errors = Vec::new();
+        let result = self.load_file(
             entry,
             &mut sm,
             &mut cache,
             &mut processing,
             &mut imported,
-        ) {
-            Ok(m) => m,
-            Err(e) => {
-                self.source_map = sm.clone();
-                return Err(e);
-            }
-        };
+            &mut parse_errors,
+        );
+        self.finish(result, sm, parse_errors)
+    }
+
+    /// Store the source map and turn parse errors collected along the way
+    /// into the load error, keeping the merged module for `recovered_module`.
+    fn finish(
+        &mut self,
+        result: Result<Module, LoaderError>,
+        sm: SourceMap,
+        mut parse_errors: Vec<Diagnostic>,
+    ) -> Result<LoadResult, LoaderError> {
         self.source_map = sm.clone();
-        Ok(LoadResult {
-            module,
-            source_map: sm,
-        })
+        self.recovered = None;
+        match result {
+            Ok(module) if parse_errors.is_empty() => Ok(LoadResult {
+                module,
+                source_map: sm,
+            }),
+            Ok(module) => {
+                self.recovered = Some(module);
+                Err(CoreError::from_diagnostics(parse_errors).into())
+            }
+            Err(LoaderError::Core(CoreError::Diagnostics(mut diags))) => {
+                parse_errors.append(&mut diags);
+                Err(CoreError::from_diagnostics(parse_errors).into())
+            }
+            Err(e) => Err(e),
+        }
     }
 
     fn load_from_contents(
@@ -214,6 +227,7 @@ impl Loader {
         cache: &mut BTreeMap<PathBuf, Module>,
         processing: &mut BTreeSet<PathBuf>,
         imported_once: &mut BTreeSet<PathBuf>,
+        parse_errors: &mut Vec<Diagnostic>,
     ) -> Result<Module, LoaderError> {
         // For pseudo files (stdin) canonicalize may fail; fall back to provided path.
         let canon = canonicalize_path(&path);
@@ -227,9 +241,16 @@ impl Loader {
             )));
         }
         let file_id = sm.add(canon.clone(), src.clone());
-        let module = self.parse_module(file_id, src)?;
-        let module =
-            self.process_directives(canon.clone(), module, sm, cache, processing, imported_once)?;
+        let module = self.parse_module(file_id, src, parse_errors)?;
+        let module = self.process_directives(
+            canon.clone(),
+            module,
+            sm,
+            cache,
+            processing,
+            imported_once,
+            parse_errors,
+        )?;
         processing.remove(&canon);
         cache.insert(canon.clone(), module.clone());
         Ok(module)
@@ -243,6 +264,7 @@ impl Loader {
         cache: &mut BTreeMap<PathBuf, Module>,
         processing: &mut BTreeSet<PathBuf>,
         imported_once: &mut BTreeSet<PathBuf>,
+        parse_errors: &mut Vec<Diagnostic>,
         provider: &mut dyn FnMut(&PathBuf) -> Result<String, LoaderError>,
     ) -> Result<Module, LoaderError> {
         let canon = canonicalize_path(&path);
@@ -256,7 +278,7 @@ impl Loader {
             )));
         }
         let file_id = sm.add(canon.clone(), src.clone());
-        let module = self.parse_module(file_id, src)?;
+        let module = self.parse_module(file_id, src, parse_errors)?;
         let module = self.process_directives_with(
             canon.clone(),
             module,
@@ -264,6 +286,7 @@ impl Loader {
             cache,
             processing,
             imported_once,
+            parse_errors,
             provider,
         )?;
         processing.remove(&canon);
@@ -279,6 +302,7 @@ impl Loader {
         cache: &mut BTreeMap<PathBuf, Module>,
         processing: &mut BTreeSet<PathBuf>,
         imported_once: &mut BTreeSet<PathBuf>,
+        parse_errors: &mut Vec<Diagnostic>,
     ) -> Result<Module, LoaderError> {
         let canon = canonicalize_path(&path);
         if let Some(m) = cache.get(&canon) {
@@ -290,15 +314,31 @@ impl Loader {
                 canon
             )));
         }
-        std::eprintln!("[Loader] Loading file: {:?}", canon);
+        let verbose = crate::log::is_verbose();
+        if verbose {
+            std::eprintln!("[Loader] Loading file: {:?}", canon);
+        }
         let src = read_file_to_string(&canon)?;
         let file_id = sm.add(canon.clone(), src.clone());
-        std::eprintln!("[Loader] Parsing module: {:?}", canon);
-        let module = self.parse_module(file_id, src)?;
-        std::eprintln!("[Loader] Processing directives for: {:?}", canon);
-        let module =
-            self.process_directives(canon.clone(), module, sm, cache, processing, imported_once)?;
-        std::eprintln!("[Loader] Finished loading: {:?}", canon);
+        if verbose {
+            std::eprintln!("[Loader] Parsing module: {:?}", canon);
+        }
+        let module = self.parse_module(file_id, src, parse_errors)?;
+        if verbose {
+            std::eprintln!("[Loader] Processing directives for: {:?}", canon);
+        }
+        let module = self.process_directives(
+            canon.clone(),
+            module,
+            sm,
+            cache,
+            processing,
+            imported_once,
+            parse_errors,
+        )?;
+        if verbose {
+            std::eprintln!("[Loader] Finished loading: {:?}", canon);
+        }
         processing.remove(&canon);
         cache.insert(canon.clone(), module.clone());
         Ok(module)
@@ -311,6 +351,7 @@ impl Loader {
         cache: &mut BTreeMap<PathBuf, Module>,
         processing: &mut BTreeSet<PathBuf>,
         imported_once: &mut BTreeSet<PathBuf>,
+        parse_errors: &mut Vec<Diagnostic>,
         provider: &mut dyn FnMut(&PathBuf) -> Result<String, LoaderError>,
     ) -> Result<Module, LoaderError> {
         let canon = canonicalize_path(&path);
@@ -325,7 +366,7 @@ impl Loader {
         }
         let src = provider(&canon)?;
         let file_id = sm.add(canon.clone(), src.clone());
-        let module = self.parse_module(file_id, src)?;
+        let module = self.parse_module(file_id, src, parse_errors)?;
         let module = self.process_directives_with(
             canon.clone(),
             module,
@@ -333,6 +374,7 @@ impl Loader {
             cache,
             processing,
             imported_once,
+            parse_errors,
             provider,
         )?;
         processing.remove(&canon);
@@ -348,16 +390,24 @@ impl Loader {
         cache: &mut BTreeMap<PathBuf, Module>,
         processing: &mut BTreeSet<PathBuf>,
         imported_once: &mut BTreeSet<PathBuf>,
+        parse_errors: &mut Vec<Diagnostic>,
     ) -> Result<Module, LoaderError> {
         let mut directives = module.directives.clone();
         let mut items = Vec::new();
         for stmt in module.root.items.clone() {
             match &stmt {
-                Stmt::Directive(Directive::Import { path, .. }) => {
+                Stmt::Directive(Directive::Import { path, span, .. }) => {
                     let target = self.resolve_path(&base, path);
+                    self.check_module_exists(path, &target, *span)?;
                     if imported_once.insert(target.clone()) {
-                        let imp_mod =
-                            self.load_file(&target, sm, cache, processing, imported_once)?;
+                        let imp_mod = self.load_file(
+                            &target,
+                            sm,
+                            cache,
+                            processing,
+                            imported_once,
+                            parse_errors,
+                        )?;
                         // Propagate non-file-scoped directives (e.g., externs) so
                         // symbols declared in stdlib become visible to the parent
                         // module during later compilation phases.
@@ -388,9 +438,17 @@ impl Loader {
                         }
                     }
                 }
-                Stmt::Directive(Directive::Include { path, .. }) => {
+                Stmt::Directive(Directive::Include { path, span }) => {
                     let target = self.resolve_path(&base, path);
-                    let inc_mod = self.load_file(&target, sm, cache, processing, imported_once)?;
+                    self.check_module_exists(path, &target, *span)?;
+                    let inc_mod = self.load_file(
+                        &target,
+                        sm,
+                        cache,
+                        processing,
+                        imported_once,
+                        parse_errors,
+                    )?;
                     // Propagate non-file-scoped directives from included modules as well.
                     for d in inc_mod.directives.clone() {
                         if let Directive::Entry { .. } = d {
@@ -434,6 +492,7 @@ impl Loader {
         cache: &mut BTreeMap<PathBuf, Module>,
         processing: &mut BTreeSet<PathBuf>,
         imported_once: &mut BTreeSet<PathBuf>,
+        parse_errors: &mut Vec<Diagnostic>,
         provider: &mut dyn FnMut(&PathBuf) -> Result<String, LoaderError>,
     ) -> Result<Module, LoaderError> {
         let mut directives = module.directives.clone();
@@ -449,6 +508,7 @@ impl Loader {
                             cache,
                             processing,
                             imported_once,
+                            parse_errors,
                             provider,
                         )?;
                         for d in imp_mod.directives.clone() {
@@ -485,6 +545,7 @@ impl Loader {
                         cache,
                         processing,
                         imported_once,
+                        parse_errors,
                         provider,
                     )?;
                     for d in inc_mod.directives.clone() {
@@ -521,32 +582,89 @@ impl Loader {
         Ok(module)
     }
 
-    fn parse_module(&self, file_id: FileId, src: String) -> Result<Module, CoreError> {
-        let lex = lexer::lex(file_id, &src);
-        if lex
-            .diagnostics
-            .iter()
-            .any(|d| d.severity == Severity::Error)
-        {
-            return Err(CoreError::from_diagnostics(lex.diagnostics));
-        }
-        let parse = parser::parse_tokens(file_id, lex);
+    /// Parse one file. Files with parse errors still yield their recovered
+    /// module so that its directives are processed; the errors are collected
+    /// into `parse_errors` and reported when the load finishes.
+    fn parse_module(
+        &self,
+        file_id: FileId,
+        src: String,
+        parse_errors: &mut Vec<Diagnostic>,
+    ) -> Result<Module, CoreError> {
+        let parse = parser::parse_tokens(file_id, lexer::lex(file_id, &src));
+        let Some(module) = parse.module else {
+            return Err(CoreError::from_diagnostics(parse.diagnostics));
+        };
         if parse
             .diagnostics
             .iter()
             .any(|d| d.severity == Severity::Error)
         {
-            return Err(CoreError::from_diagnostics(parse.diagnostics));
+            parse_errors.extend(parse.diagnostics);
+        }
+        Ok(module)
+    }
+
+    /// Report a missing `#import`/`#include` target as a diagnostic at the
+    /// directive, with suggestions from the stdlib tree or sibling files.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn check_module_exists(
+        &self,
+        spec: &str,
+        target: &Path,
+        span: Span,
+    ) -> Result<(), LoaderError> {
+        if target.is_file() {
+            return Ok(());
         }
-        if let Some(module) = parse.module {
-            Ok(module)
+        let mut diag = Diagnostic::error(format!("cannot find module '{}'", spec), span);
+        let candidates = if is_std_import(spec) {
+            suggest::StdlibIndex::scan(&self.stdlib_root).suggest_modules(spec)
         } else {
-            Err(CoreError::from_diagnostics(parse.diagnostics))
+            // 相対 import は同じディレクトリの .nepl ファイル名から候補を出す
+            let (dir_part, leaf) = match spec.rfind('/') {
+                Some(pos) => (&spec[..=pos], &spec[pos + 1..]),
+                None => ("", spec),
+            };
+            let leaf = leaf.trim_end_matches(".nepl");
+            let mut stems: Vec<String> = Vec::new();
+            if let Some(Ok(entries)) = target.parent().map(fs::read_dir) {
+                for entry in entries.flatten() {
+                    let p = entry.path();
+                    if p.extension().and_then(|s| s.to_str()) != Some("nepl") {
+                        continue;
+                    }
+                    if let Some(stem) = p.file_stem().and_then(|s| s.to_str()) {
+                        stems.push(stem.to_string());
+                    }
+                }
+            }
+            let limit = suggest::MAX_SUGGESTIONS;
+            suggest::closest_matches(leaf, stems.iter().map(|s| s.as_str()), limit)
+                .into_iter()
+                .map(|stem| format!("{}{}", dir_part, stem))
+                .collect()
+        };
+        if let Some(note) = suggest::did_you_mean(&candidates) {
+            diag = diag.with_note(note);
         }
+        Err(LoaderError::Core(CoreError::from_diagnostics(alloc::vec![
+            diag
+        ])))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn check_module_exists(
+        &self,
+        _spec: &str,
+        _target: &Path,
+        _span: Span,
+    ) -> Result<(), LoaderError> {
+        Ok(())
     }
 
     fn resolve_path(&self, base: &PathBuf, spec: &str) -> PathBuf {
-        let is_std_import = !spec.starts_with('.') && !spec.starts_with('/');
+        let is_std_import = is_std_import(spec);
         let mut p = if is_std_import {
             self.stdlib_root.join(spec)
         } else {
@@ -561,6 +679,11 @@ impl Loader {
     }
 }
 
+/// `#import "core/math"` のように `.` や `/` で始まらない指定は stdlib を指す。
+fn is_std_import(spec: &str) -> bool {
+    !spec.starts_with('.') && !spec.starts_with('/')
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 fn read_file_to_string(path: &PathBuf) -> Result<String, LoaderError> {
     fs::read_to_string(path).map_err(|e| LoaderError::Io(e.to_string()))
@@ -72,6 +72,8 @@ pub enum ExportKind {
     Function,
     Struct,
     Enum,
+    TypeAlias,
+    Newtype,
 }
 
 #[derive(Debug, Clone)]
@@ -186,6 +188,22 @@ impl ModuleGraphBuilder {
                             node.id,
                         )?;
                     }
+                    crate::ast::Stmt::TypeAlias(t) if t.vis == crate::ast::Visibility::Pub => {
+                        Self::insert_export(
+                            &mut exports,
+                            &t.name.name,
+                            ExportKind::TypeAlias,
+                            node.id,
+                        )?;
+                    }
+                    crate::ast::Stmt::Newtype(n) if n.vis == crate::ast::Visibility::Pub => {
+                        Self::insert_export(
+                            &mut exports,
+                            &n.name.name,
+                            ExportKind::Newtype,
+                            node.id,
+                        )?;
+                    }
                     _ => {}
                 }
             }
@@ -10,16 +10,24 @@ use crate::hir::*;
 use crate::types::{TypeCtx, TypeId, TypeKind};
 
 pub fn monomorphize(ctx: &mut TypeCtx, module: HirModule) -> HirModule {
-    let mut impl_map: BTreeMap<(String, String, TypeId), String> = BTreeMap::new();
+    let mut impl_map: BTreeMap<(String, String, TypeId), ImplMethod> = BTreeMap::new();
     for imp in &module.impls {
         let ty = ctx.resolve_id(imp.target_ty);
         for m in &imp.methods {
             impl_map.insert(
                 (imp.trait_name.clone(), m.name.clone(), ty),
-                m.func.name.clone(),
+                ImplMethod {
+                    func: m.func.name.clone(),
+                    type_params: imp.type_args.clone(),
+                },
             );
         }
     }
+    let consts = module
+        .consts
+        .iter()
+        .map(|c| (c.name.clone(), (c.func.clone(), c.ty)))
+        .collect();
     let mut mono = Monomorphizer {
         ctx,
         funcs: BTreeMap::new(),
@@ -27,6 +35,7 @@ pub fn monomorphize(ctx: &mut TypeCtx, module: HirModule) -> HirModule {
         worklist: Vec::new(),
         queued: BTreeSet::new(),
         impl_map,
+        consts,
     };
 
     for f in module.functions {
@@ -64,6 +73,20 @@ pub fn monomorphize(ctx: &mut TypeCtx, module: HirModule) -> HirModule {
         }
     }
 
+    // Const and static initializers are evaluated even when nothing references
+    // them, and exported functions are reachable from the host.
+    for func in module
+        .consts
+        .iter()
+        .map(|c| &c.func)
+        .chain(module.globals.iter().map(|g| &g.func))
+        .chain(module.exports.iter().map(|e| &e.func))
+    {
+        if !initial.iter().any(|n| n == func) {
+            initial.push(func.clone());
+        }
+    }
+
     for name in initial {
         if crate::log::is_verbose() {
             std::eprintln!("monomorphize: initial function {}", name);
@@ -75,6 +98,10 @@ pub fn monomorphize(ctx: &mut TypeCtx, module: HirModule) -> HirModule {
         mono.process_instantiation(orig_name, args);
     }
 
+    // Trait dispatch above still tells newtypes apart; everything after
+    // monomorphization only sees their inner representation.
+    mono.ctx.erase_newtypes();
+
     let mut new_functions = Vec::new();
     for (_, f) in mono.specialized {
         new_functions.push(f);
@@ -87,16 +114,29 @@ pub fn monomorphize(ctx: &mut TypeCtx, module: HirModule) -> HirModule {
         string_literals: module.string_literals,
         traits: module.traits,
         impls: module.impls,
+        consts: module.consts,
+        statics: module.statics,
+        globals: module.globals,
+        exports: module.exports,
     }
 }
 
+/// A trait method implementation; generic impls carry their type parameters.
+#[derive(Clone)]
+struct ImplMethod {
+    func: String,
+    type_params: Vec<TypeId>,
+}
+
 struct Monomorphizer<'a> {
     ctx: &'a mut TypeCtx,
     funcs: BTreeMap<String, HirFunction>,
     specialized: BTreeMap<String, HirFunction>,
     worklist: Vec<(String, Vec<TypeId>)>,
     queued: BTreeSet<String>,
-    impl_map: BTreeMap<(String, String, TypeId), String>,
+    impl_map: BTreeMap<(String, String, TypeId), ImplMethod>,
+    /// const item name -> (initializer function, type)
+    consts: BTreeMap<String, (String, TypeId)>,
 }
 
 fn find_runtime_helper_name(
@@ -124,7 +164,38 @@ fn find_runtime_helper_name(
     None
 }
 
+/// Names used by `call` instructions in a `#wasm` body.
+fn wasm_call_targets(wb: &crate::ast::WasmBlock) -> Vec<&str> {
+    let mut out = Vec::new();
+    for line in &wb.lines {
+        let code = line.split(";;").next().unwrap_or("");
+        let mut parts = code.split_whitespace();
+        if let (Some("call"), Some(target)) = (parts.next(), parts.next()) {
+            out.push(target.strip_prefix('$').unwrap_or(target));
+        }
+    }
+    out
+}
+
 impl<'a> Monomorphizer<'a> {
+    /// Type arguments of a generic impl for the concrete `self_ty`, found by
+    /// unifying a fresh copy of its target with it.
+    fn impl_type_args(&mut self, imp: &ImplMethod, target_ty: TypeId, self_ty: TypeId) -> Vec<TypeId> {
+        if imp.type_params.is_empty() {
+            return Vec::new();
+        }
+        let mut mapping = BTreeMap::new();
+        let mut fresh = Vec::new();
+        for tp in &imp.type_params {
+            let var = self.ctx.fresh_var(None);
+            mapping.insert(self.ctx.resolve_id(*tp), var);
+            fresh.push(var);
+        }
+        let target = self.ctx.substitute(target_ty, &mapping);
+        let _ = self.ctx.unify(target, self_ty);
+        fresh.into_iter().map(|v| self.ctx.resolve_id(v)).collect()
+    }
+
     fn request_instantiation(&mut self, name: String, args: Vec<TypeId>) -> String {
         let mut resolved_args = Vec::new();
         for arg in &args {
@@ -199,7 +270,22 @@ impl<'a> Monomorphizer<'a> {
 
         match &mut f.body {
             HirBody::Block(b) => self.substitute_block(b, &mapping),
-            HirBody::Wasm(_) => {} // Wasm blocks don't hold TypeIds usually
+            HirBody::Wasm(wb) => {
+                // Functions called from raw wasm are only reachable through the text.
+                for target in wasm_call_targets(wb) {
+                    let mut prefix = String::from(target);
+                    prefix.push_str("__");
+                    let names: Vec<String> = self
+                        .funcs
+                        .keys()
+                        .filter(|n| *n == target || n.starts_with(&prefix))
+                        .cloned()
+                        .collect();
+                    for name in names {
+                        self.request_instantiation(name, Vec::new());
+                    }
+                }
+            }
             HirBody::LlvmIr(_) => {} // LLVM IR blocks don't hold TypeIds usually
         }
 
@@ -215,12 +301,24 @@ impl<'a> Monomorphizer<'a> {
 
     fn substitute_expr(&mut self, expr: &mut HirExpr, mapping: &BTreeMap<TypeId, TypeId>) {
         expr.ty = self.ctx.substitute(expr.ty, mapping);
+        // A local that shadows a function (e.g. a `len` parameter next to `fn len`)
+        // keeps its value type and must not be turned into a function reference.
+        let maybe_fn_value = matches!(
+            self.ctx.get(self.ctx.resolve_id(expr.ty)),
+            TypeKind::Function { .. } | TypeKind::Var(_)
+        );
+        let mut const_call = None;
         match &mut expr.kind {
             HirExprKind::Unit
             | HirExprKind::LiteralI32(_)
             | HirExprKind::LiteralF32(_)
+            | HirExprKind::LiteralI64(_)
+            | HirExprKind::LiteralF64(_)
             | HirExprKind::LiteralBool(_)
-            | HirExprKind::LiteralStr(_) => {}
+            | HirExprKind::LiteralStr(_)
+            | HirExprKind::StaticAddr(_)
+            | HirExprKind::GlobalGet(_) => {}
+            HirExprKind::Var(_) if !maybe_fn_value => {}
             HirExprKind::Var(name) => {
                 if self.funcs.contains_key(name) {
                     *name = self.request_instantiation(name.clone(), Vec::new());
@@ -288,10 +386,13 @@ impl<'a> Monomorphizer<'a> {
                             method.clone(),
                             resolved,
                         );
-                        let mut selected = self.impl_map.get(&key).cloned();
+                        let mut selected = self
+                            .impl_map
+                            .get(&key)
+                            .map(|m| (m.clone(), resolved));
                         if selected.is_none() {
-                            let mut fallback: Option<String> = None;
-                            for ((tr, meth, target_ty), func_name) in self.impl_map.iter() {
+                            let mut fallback = None;
+                            for ((tr, meth, target_ty), imp) in self.impl_map.iter() {
                                 if tr != trait_name || meth != method {
                                     continue;
                                 }
@@ -301,13 +402,14 @@ impl<'a> Monomorphizer<'a> {
                                         fallback = None;
                                         break;
                                     }
-                                    fallback = Some(func_name.clone());
+                                    fallback = Some((imp.clone(), *target_ty));
                                 }
                             }
                             selected = fallback;
                         }
-                        if let Some(func_name) = selected {
-                            let inst = self.request_instantiation(func_name, Vec::new());
+                        if let Some((imp, target_ty)) = selected {
+                            let args = self.impl_type_args(&imp, target_ty, resolved);
+                            let inst = self.request_instantiation(imp.func, args);
                             *callee = FuncRef::User(inst, Vec::new());
                         }
                     }
@@ -338,7 +440,7 @@ impl<'a> Monomorphizer<'a> {
                 self.substitute_expr(then_branch, mapping);
                 self.substitute_expr(else_branch, mapping);
             }
-            HirExprKind::While { cond, body } => {
+            HirExprKind::While { cond, body, .. } => {
                 self.substitute_expr(cond, mapping);
                 self.substitute_expr(body, mapping);
             }
@@ -378,14 +480,17 @@ impl<'a> Monomorphizer<'a> {
             }
             HirExprKind::Block(b) => self.substitute_block(b, mapping),
             HirExprKind::Let { value, .. } => self.substitute_expr(value, mapping),
-            HirExprKind::Set { value, .. } => self.substitute_expr(value, mapping),
+            HirExprKind::Set { value, .. } | HirExprKind::GlobalSet { value, .. } => {
+                self.substitute_expr(value, mapping)
+            }
             HirExprKind::AddrOf(inner) => self.substitute_expr(inner, mapping),
             HirExprKind::Deref(inner) => self.substitute_expr(inner, mapping),
-            HirExprKind::Drop { .. } => {}
+            HirExprKind::Return(inner) => self.substitute_expr(inner, mapping),
+            HirExprKind::Break(_) | HirExprKind::Continue(_) | HirExprKind::Drop { .. } => {}
             HirExprKind::Intrinsic {
                 type_args,
                 args,
-                name: _,
+                name,
             } => {
                 for arg in type_args.iter_mut() {
                     *arg = self.ctx.substitute(*arg, mapping);
@@ -393,7 +498,34 @@ impl<'a> Monomorphizer<'a> {
                 for arg in args {
                     self.substitute_expr(arg, mapping);
                 }
+                if name == "const_value" {
+                    const_call = self.const_argument(type_args);
+                }
             }
         }
+        // `const_value<N, T>` becomes a call to the const item `N`, which
+        // const evaluation then folds like any other use of the const.
+        if let Some(func) = const_call {
+            expr.kind = HirExprKind::Call {
+                callee: FuncRef::User(func, Vec::new()),
+                args: Vec::new(),
+            };
+        }
+    }
+
+    /// The initializer of the const item named by the first type argument of
+    /// `const_value<N, T>`, when that const has type `T`.
+    fn const_argument(&mut self, type_args: &[TypeId]) -> Option<String> {
+        let [konst, ty] = type_args else {
+            return None;
+        };
+        let TypeKind::Named(name) = self.ctx.get(self.ctx.resolve_id(*konst)) else {
+            return None;
+        };
+        let (func, const_ty) = self.consts.get(&name)?.clone();
+        if self.ctx.type_to_string(const_ty) != self.ctx.type_to_string(*ty) {
+            return None;
+        }
+        Some(self.request_instantiation(func, Vec::new()))
     }
 }
@@ -148,6 +148,9 @@ impl Parser {
             signature: Self::infer_signature_from_params(params.len()),
             params,
             body: FnBody::Parsed(body),
+            effect_inferred: true,
+            constness: Constness::Runtime,
+            export: None,
         };
         let value_expr = PrefixExpr {
             items: vec![PrefixItem::Symbol(Symbol::Ident(
@@ -169,6 +172,27 @@ impl Parser {
     fn peek_kind_at(&self, offset: usize) -> Option<&TokenKind> {
         self.tokens.get(self.pos + offset).map(|t| &t.kind)
     }
+
+    /// Optional `<.label>` after `while` / `break` / `continue`.
+    fn parse_loop_label(&mut self) -> Option<String> {
+        if !matches!(self.peek_kind_at(0), Some(TokenKind::LAngle))
+            || !matches!(self.peek_kind_at(1), Some(TokenKind::Dot))
+        {
+            return None;
+        }
+        self.next();
+        let label = match self.parse_type_expr() {
+            Some(TypeExpr::Label(Some(name))) => Some(name),
+            _ => {
+                let span = self.peek_span().unwrap_or_else(Span::dummy);
+                self.diagnostics
+                    .push(Diagnostic::error("expected a loop label like <.name>", span));
+                None
+            }
+        };
+        self.expect(&TokenKind::RAngle)?;
+        label
+    }
     fn parse_visibility(&mut self) -> Visibility {
         if self.consume_if(&TokenKind::KwPub) {
             Visibility::Pub
@@ -214,6 +238,8 @@ impl Parser {
     fn parse_block_until_internal(&mut self, end: TokenEnd) -> Option<Block> {
         let mut items = Vec::new();
         let mut start_span = self.peek_span().unwrap_or_else(Span::dummy);
+        // `#export` attaches to the next function; `#if[...]` gates may sit in between.
+        let mut pending_export: Option<(Option<String>, Span)> = None;
         // O(1) flag: tracks if the previous statement contains an 'if' expression
         let mut prev_has_if = false;
         let mut last_pos = usize::MAX;
@@ -249,27 +275,46 @@ impl Parser {
             {
                 break;
             }
+            if let Some(TokenKind::DirExport(_)) = self.peek_kind() {
+                let tok = self.next().unwrap();
+                let TokenKind::DirExport(arg) = tok.kind else {
+                    unreachable!()
+                };
+                if let Some((_, prev)) = pending_export.replace((None, tok.span)) {
+                    self.diagnostics.push(Diagnostic::error(
+                        "`#export` must be followed by a function definition",
+                        prev,
+                    ));
+                }
+                if arg.is_empty() {
+                    continue;
+                }
+                match arg.strip_prefix('"').and_then(|a| a.strip_suffix('"')) {
+                    Some(name) if !name.is_empty() && !name.contains('"') => {
+                        pending_export = Some((Some(name.to_string()), tok.span));
+                    }
+                    _ => self.diagnostics.push(Diagnostic::error(
+                        "`#export` expects a quoted name, e.g. `#export \"add\"`",
+                        tok.span,
+                    )),
+                }
+                continue;
+            }
 
             let mut stmt = match self.parse_stmt() {
                 Some(s) => s,
                 None => {
                     // Error recovery: skip tokens until a statement boundary and continue parsing
                     // the rest of the block so multiple diagnostics can be reported.
+                    // The skipped tokens are kept as a `Stmt::Error` placeholder.
                     prev_has_if = false;
-                    loop {
-                        if self.is_eof() {
-                            break;
-                        }
-                        match self.peek_kind() {
-                            Some(TokenKind::Newline) | Some(TokenKind::Semicolon) => {
-                                self.next();
-                                break;
-                            }
-                            Some(TokenKind::Dedent) if matches!(end, TokenEnd::Dedent) => break,
-                            _ => {
-                                self.next();
-                            }
-                        }
+                    if let Some(span) = self.skip_rest_of_stmt() {
+                        items.push(Stmt::Error(span));
+                    }
+                    self.consume_if(&TokenKind::Semicolon);
+                    if self.check(&TokenKind::Dedent) && !matches!(end, TokenEnd::Dedent) {
+                        // stray dedent left behind by a broken nested block
+                        self.next();
                     }
                     continue;
                 }
@@ -319,11 +364,37 @@ impl Parser {
                 _ => false,
             };
 
+            if let Some((name, span)) = pending_export.take() {
+                match &mut stmt {
+                    Stmt::FnDef(f) => {
+                        f.export = Some(FnExport {
+                            name: name.unwrap_or_else(|| f.name.name.clone()),
+                            span,
+                        });
+                    }
+                    Stmt::Directive(
+                        Directive::IfTarget { .. }
+                        | Directive::IfProfile { .. }
+                        | Directive::IfConst { .. },
+                    ) => pending_export = Some((name, span)),
+                    _ => self.diagnostics.push(Diagnostic::error(
+                        "`#export` must be followed by a function definition",
+                        span,
+                    )),
+                }
+            }
+
             if let Stmt::Directive(dir) = &stmt {
                 self.directives.push(dir.clone());
             }
             items.push(stmt);
         }
+        if let Some((_, span)) = pending_export {
+            self.diagnostics.push(Diagnostic::error(
+                "`#export` must be followed by a function definition",
+                span,
+            ));
+        }
 
         let end_span = if let Some(last) = items.last() {
             self.stmt_span(last)
@@ -548,6 +619,19 @@ impl Parser {
                 };
                 Some(Stmt::Directive(Directive::IfProfile { profile, span }))
             }
+            TokenKind::DirIfConst(_) => {
+                let (name, span) = match self.next() {
+                    Some(tok) => {
+                        if let TokenKind::DirIfConst(n) = tok.kind.clone() {
+                            (n, tok.span)
+                        } else {
+                            unreachable!()
+                        }
+                    }
+                    None => return None,
+                };
+                Some(Stmt::Directive(Directive::IfConst { name, span }))
+            }
             TokenKind::DirIndentWidth(width) => {
                 let span = self.next().unwrap().span;
                 Some(Stmt::Directive(Directive::IndentWidth { width: width, span }))
@@ -595,6 +679,10 @@ impl Parser {
                     Some(TokenKind::KwStruct) => self.parse_struct(),
                     Some(TokenKind::KwEnum) => self.parse_enum(),
                     Some(TokenKind::KwFn) => self.parse_fn(),
+                    Some(TokenKind::KwConst) => self.parse_const(),
+                    Some(TokenKind::KwStatic) => self.parse_static(),
+                    Some(TokenKind::KwType) => self.parse_type_alias(),
+                    Some(TokenKind::KwNewtype) => self.parse_newtype(),
                     Some(TokenKind::KwTrait) => self.parse_trait(),
                     Some(TokenKind::KwImpl) => self.parse_impl(),
                     _ => {
@@ -611,6 +699,10 @@ impl Parser {
             TokenKind::KwStruct => self.parse_struct(),
             TokenKind::KwEnum => self.parse_enum(),
             TokenKind::KwFn => self.parse_fn(),
+            TokenKind::KwConst => self.parse_const(),
+            TokenKind::KwStatic => self.parse_static(),
+            TokenKind::KwType => self.parse_type_alias(),
+            TokenKind::KwNewtype => self.parse_newtype(),
             TokenKind::KwTrait => self.parse_trait(),
             TokenKind::KwImpl => self.parse_impl(),
             TokenKind::KwLet => {
@@ -752,6 +844,9 @@ impl Parser {
             name: Ident { name, span: nspan },
             no_shadow,
             type_params: Vec::new(),
+            effect_inferred: signature.is_none(),
+            constness: Constness::Runtime,
+            export: None,
             signature: signature
                 .unwrap_or_else(|| Self::infer_signature_from_params(params.len())),
             params,
@@ -764,41 +859,29 @@ impl Parser {
         let kw_span = self.expect_with_span(&TokenKind::KwStruct)?;
         let (name, nspan) = self.expect_ident()?;
         let type_params = self.parse_generic_params();
-        self.expect(&TokenKind::Colon)?;
+        self.expect_block_colon()?;
         let mut fields = Vec::new();
         if self.consume_if(&TokenKind::Newline) {
+            while self.consume_if(&TokenKind::Newline) {}
             self.expect(&TokenKind::Indent)?;
             while !self.check(&TokenKind::Dedent) && !self.is_eof() {
                 if self.consume_if(&TokenKind::Newline) {
                     continue;
                 }
-                let (fname, fspan) = self.expect_ident()?;
-                self.expect(&TokenKind::LAngle)?;
-                let fty = self.parse_type_expr()?;
-                self.expect(&TokenKind::RAngle)?;
-                fields.push((
-                    Ident {
-                        name: fname,
-                        span: fspan,
-                    },
-                    fty,
-                ));
+                // a broken field is reported and skipped; the other fields are kept
+                match self.parse_struct_field() {
+                    Some(field) => fields.push(field),
+                    None => {
+                        self.skip_rest_of_stmt();
+                        self.consume_if(&TokenKind::Semicolon);
+                    }
+                }
                 self.consume_if(&TokenKind::Newline);
             }
             self.expect(&TokenKind::Dedent)?;
         } else {
             while !self.is_end(&TokenEnd::Line) {
-                let (fname, fspan) = self.expect_ident()?;
-                self.expect(&TokenKind::LAngle)?;
-                let fty = self.parse_type_expr()?;
-                self.expect(&TokenKind::RAngle)?;
-                fields.push((
-                    Ident {
-                        name: fname,
-                        span: fspan,
-                    },
-                    fty,
-                ));
+                fields.push(self.parse_struct_field()?);
                 if !self.consume_if(&TokenKind::Semicolon) {
                     break;
                 }
@@ -817,49 +900,29 @@ impl Parser {
         let kw_span = self.expect_with_span(&TokenKind::KwEnum)?;
         let (name, nspan) = self.expect_ident()?;
         let type_params = self.parse_generic_params();
-        self.expect(&TokenKind::Colon)?;
+        self.expect_block_colon()?;
         let mut variants = Vec::new();
         if self.consume_if(&TokenKind::Newline) {
+            while self.consume_if(&TokenKind::Newline) {}
             self.expect(&TokenKind::Indent)?;
             while !self.check(&TokenKind::Dedent) && !self.is_eof() {
                 if self.consume_if(&TokenKind::Newline) {
                     continue;
                 }
-                let (vname, vspan) = self.expect_ident()?;
-                let payload = if self.consume_if(&TokenKind::LAngle) {
-                    let ty = self.parse_type_expr()?;
-                    self.expect(&TokenKind::RAngle)?;
-                    Some(ty)
-                } else {
-                    None
-                };
-                variants.push(EnumVariant {
-                    name: Ident {
-                        name: vname,
-                        span: vspan,
-                    },
-                    payload,
-                });
+                // a broken variant is reported and skipped; the others are kept
+                match self.parse_enum_variant() {
+                    Some(variant) => variants.push(variant),
+                    None => {
+                        self.skip_rest_of_stmt();
+                        self.consume_if(&TokenKind::Semicolon);
+                    }
+                }
                 self.consume_if(&TokenKind::Newline);
             }
             self.expect(&TokenKind::Dedent)?;
         } else {
             while !self.is_end(&TokenEnd::Line) {
-                let (vname, vspan) = self.expect_ident()?;
-                let payload = if self.consume_if(&TokenKind::LAngle) {
-                    let ty = self.parse_type_expr()?;
-                    self.expect(&TokenKind::RAngle)?;
-                    Some(ty)
-                } else {
-                    None
-                };
-                variants.push(EnumVariant {
-                    name: Ident {
-                        name: vname,
-                        span: vspan,
-                    },
-                    payload,
-                });
+                variants.push(self.parse_enum_variant()?);
                 if !self.consume_if(&TokenKind::Semicolon) {
                     break;
                 }
@@ -873,6 +936,31 @@ impl Parser {
         }))
     }
 
+    /// `name <Type>` inside a struct body.
+    fn parse_struct_field(&mut self) -> Option<(Ident, TypeExpr)> {
+        let (name, span) = self.expect_ident()?;
+        self.expect(&TokenKind::LAngle)?;
+        let ty = self.parse_type_expr()?;
+        self.expect(&TokenKind::RAngle)?;
+        Some((Ident { name, span }, ty))
+    }
+
+    /// `Name` or `Name <Payload>` inside an enum body.
+    fn parse_enum_variant(&mut self) -> Option<EnumVariant> {
+        let (name, span) = self.expect_ident()?;
+        let payload = if self.consume_if(&TokenKind::LAngle) {
+            let ty = self.parse_type_expr()?;
+            self.expect(&TokenKind::RAngle)?;
+            Some(ty)
+        } else {
+            None
+        };
+        Some(EnumVariant {
+            name: Ident { name, span },
+            payload,
+        })
+    }
+
     fn parse_fn(&mut self) -> Option<Stmt> {
         let vis = self.parse_visibility();
         let _fn_span = self.expect_with_span(&TokenKind::KwFn)?;
@@ -885,7 +973,11 @@ impl Parser {
 
 
         if (matches!(self.peek_kind(), Some(TokenKind::Ident(_)))
-            && matches!(self.peek_kind_at(1), Some(TokenKind::Semicolon)))
+            && matches!(
+                self.peek_kind_at(1),
+                Some(TokenKind::Semicolon | TokenKind::Newline | TokenKind::Eof)
+            )
+            && !matches!(self.peek_kind_at(2), Some(TokenKind::Indent)))
             || (matches!(self.peek_kind(), Some(TokenKind::At))
                 && matches!(self.peek_kind_at(1), Some(TokenKind::Ident(_)))
                 && matches!(self.peek_kind_at(2), Some(TokenKind::Semicolon)))
@@ -896,7 +988,13 @@ impl Parser {
                 name: target_tok.0,
                 span: target_tok.1,
             };
-            self.expect(&TokenKind::Semicolon)?;
+            if !self.consume_if(&TokenKind::Semicolon) {
+                let span = target.span;
+                self.diagnostics.push(Diagnostic::error(
+                    "expected ';' after function alias",
+                    span,
+                ));
+            }
             return Some(Stmt::FnAlias(FnAlias {
                 vis,
                 name,
@@ -928,10 +1026,18 @@ impl Parser {
             }
         }
 
+        let mut effect_inferred = !self.check(&TokenKind::LAngle);
         let signature = if self.consume_if(&TokenKind::LAngle) {
-            let sig = self.parse_type_expr()?;
-            self.expect(&TokenKind::RAngle)?;
-            sig
+            match self.parse_type_expr() {
+                Some(sig) if self.expect(&TokenKind::RAngle).is_some() => sig,
+                // A broken signature is skipped up to its `>`; the parameter
+                // list decides the arity as if the signature were omitted.
+                _ => {
+                    self.skip_to_closing_angle();
+                    effect_inferred = true;
+                    Self::infer_signature_from_params(0)
+                }
+            }
         } else {
             Self::infer_signature_from_params(0)
         };
@@ -955,7 +1061,7 @@ impl Parser {
             }
             other => other,
         };
-        self.expect(&TokenKind::Colon)?;
+        self.expect_block_colon()?;
         let body = self.parse_block_after_colon()?;
 
         let fn_body = match body.items.first() {
@@ -972,6 +1078,157 @@ impl Parser {
             signature,
             params,
             body: fn_body,
+            effect_inferred,
+            constness: Constness::Runtime,
+            export: None,
+        }))
+    }
+
+    /// Parses `const NAME <T> expr` and `const fn ...`.
+    ///
+    /// A const item is desugared into a pure zero-arg function marked
+    /// `Constness::ConstItem`; the value is folded after monomorphization.
+    fn parse_const(&mut self) -> Option<Stmt> {
+        let vis = self.parse_visibility();
+        self.expect(&TokenKind::KwConst)?;
+        if self.check(&TokenKind::KwFn) {
+            let mut stmt = self.parse_fn()?;
+            match &mut stmt {
+                Stmt::FnDef(def) => {
+                    def.vis = vis;
+                    def.constness = Constness::ConstFn;
+                }
+                Stmt::FnAlias(alias) => {
+                    self.diagnostics.push(Diagnostic::error(
+                        "`const` cannot be applied to a function alias",
+                        alias.name.span,
+                    ));
+                }
+                _ => {}
+            }
+            return Some(stmt);
+        }
+        let name_tok = self.expect_ident()?;
+        let name = Ident {
+            name: name_tok.0,
+            span: name_tok.1,
+        };
+        if !self.consume_if(&TokenKind::LAngle) {
+            let span = self.peek_span().unwrap_or(name.span);
+            self.diagnostics.push(Diagnostic::error(
+                "const item requires a type annotation `<T>`",
+                span,
+            ));
+            return None;
+        }
+        let ty = self.parse_type_expr()?;
+        self.expect(&TokenKind::RAngle)?;
+        let value = self.parse_prefix_expr()?;
+        if value.trailing_semis > 0 {
+            self.diagnostics.push(Diagnostic::error(
+                "const initializer cannot end with `;`",
+                value.trailing_semi_span.unwrap_or(value.span),
+            ));
+        }
+        let span = value.span;
+        Some(Stmt::FnDef(FnDef {
+            vis,
+            name,
+            no_shadow: false,
+            type_params: Vec::new(),
+            signature: TypeExpr::Function {
+                params: Vec::new(),
+                result: Box::new(ty),
+                effect: Effect::Pure,
+            },
+            params: Vec::new(),
+            body: FnBody::Parsed(Block {
+                items: vec![Stmt::Expr(value)],
+                span,
+            }),
+            effect_inferred: false,
+            constness: Constness::ConstItem,
+            export: None,
+        }))
+    }
+
+    fn parse_static(&mut self) -> Option<Stmt> {
+        let vis = self.parse_visibility();
+        let start = self.peek_span().unwrap_or_else(Span::dummy);
+        self.expect(&TokenKind::KwStatic)?;
+        let mutable = self.consume_if(&TokenKind::KwMut);
+        let name_tok = self.expect_ident()?;
+        let name = Ident {
+            name: name_tok.0,
+            span: name_tok.1,
+        };
+        if !self.consume_if(&TokenKind::LAngle) {
+            let span = self.peek_span().unwrap_or(name.span);
+            self.diagnostics.push(Diagnostic::error(
+                "static item requires a type annotation `<T>`",
+                span,
+            ));
+            return None;
+        }
+        let ty = self.parse_type_expr()?;
+        self.expect(&TokenKind::RAngle)?;
+        let value = self.parse_prefix_expr()?;
+        if value.trailing_semis > 0 {
+            self.diagnostics.push(Diagnostic::error(
+                "static initializer cannot end with `;`",
+                value.trailing_semi_span.unwrap_or(value.span),
+            ));
+        }
+        let span = start.join(value.span).unwrap_or(value.span);
+        Some(Stmt::Static(StaticDef {
+            vis,
+            name,
+            mutable,
+            ty,
+            value,
+            span,
+        }))
+    }
+
+    /// `type Name <.T> = TypeExpr`
+    fn parse_type_alias(&mut self) -> Option<Stmt> {
+        let vis = self.parse_visibility();
+        let start = self.expect_with_span(&TokenKind::KwType)?;
+        let (name, nspan) = self.expect_ident()?;
+        let type_params = self.parse_generic_params();
+        self.expect(&TokenKind::Equals)?;
+        let end = self.peek_span().unwrap_or(nspan);
+        let target = self.parse_type_expr()?;
+        Some(Stmt::TypeAlias(TypeAliasDef {
+            vis,
+            name: Ident { name, span: nspan },
+            type_params,
+            target,
+            span: start.join(end).unwrap_or(start),
+        }))
+    }
+
+    /// `newtype Name = TypeExpr`
+    fn parse_newtype(&mut self) -> Option<Stmt> {
+        let vis = self.parse_visibility();
+        let start = self.expect_with_span(&TokenKind::KwNewtype)?;
+        let (name, nspan) = self.expect_ident()?;
+        if self.check(&TokenKind::LAngle) {
+            let span = self.peek_span().unwrap_or(nspan);
+            self.diagnostics.push(Diagnostic::error(
+                "newtype cannot have type parameters",
+                span,
+            ));
+            self.parse_generic_params();
+        }
+        self.expect(&TokenKind::Equals)?;
+        let end = self.peek_span().unwrap_or(nspan);
+        let inner = self.parse_type_expr()?;
+        Some(Stmt::Newtype(NewtypeDef {
+            vis,
+            name: Ident { name, span: nspan },
+            inner,
+            span: start.join(end).unwrap_or(start),
         }))
     }
 
@@ -1069,7 +1326,7 @@ impl Parser {
         let kw_span = self.expect_with_span(&TokenKind::KwTrait)?;
         let (name, nspan) = self.expect_ident()?;
         let type_params = self.parse_generic_params();
-        self.expect(&TokenKind::Colon)?;
+        self.expect_block_colon()?;
         self.consume_if(&TokenKind::Newline);
         self.expect(&TokenKind::Indent)?;
         let mut methods = Vec::new();
@@ -1103,9 +1360,13 @@ impl Parser {
 
         let first_ty = self.parse_type_expr()?;
 
-        let (trait_name, target_ty) = if self.consume_if(&TokenKind::KwFor) {
+        let (trait_name, trait_args, target_ty) = if self.consume_if(&TokenKind::KwFor) {
             let target = self.parse_type_expr()?;
-            let trait_ident = match first_ty {
+            let (trait_ty, trait_args) = match first_ty {
+                TypeExpr::Apply(base, args) => (*base, args),
+                other => (other, Vec::new()),
+            };
+            let trait_ident = match trait_ty {
                 TypeExpr::Named(n) => Some(Ident {
                     name: n,
                     span: kw_span,
@@ -1118,12 +1379,12 @@ impl Parser {
                     None
                 }
             };
-            (trait_ident, target)
+            (trait_ident, trait_args, target)
         } else {
-            (None, first_ty)
+            (None, Vec::new(), first_ty)
         };
 
-        self.expect(&TokenKind::Colon)?;
+        self.expect_block_colon()?;
         self.consume_if(&TokenKind::Newline);
         self.expect(&TokenKind::Indent)?;
         let mut methods = Vec::new();
@@ -1144,6 +1405,7 @@ impl Parser {
         Some(Stmt::Impl(ImplDef {
             type_params,
             trait_name,
+            trait_args,
             target_ty,
             methods,
             span: kw_span.join(end_span).unwrap_or(kw_span),
@@ -1159,7 +1421,7 @@ impl Parser {
         let mut last_pos = usize::MAX;
         let mut no_progress_steps: usize = 0;
 
-        while !self.is_end(&TokenEnd::Line) || self.is_pipe_continuation() {
+        'items: while !self.is_end(&TokenEnd::Line) || self.is_pipe_continuation() {
             if self.pos == last_pos {
                 no_progress_steps += 1;
                 if no_progress_steps >= MAX_NO_PROGRESS_STEPS {
@@ -1184,6 +1446,21 @@ impl Parser {
             if self.is_pipe_continuation() && self.check(&TokenKind::Newline) {
                 self.next(); // skip newline to continue expression
             }
+            let item_start = self.peek_span().unwrap_or(start_span);
+            // `?` for the parts of an item: on failure the rest of the statement
+            // becomes a `PrefixItem::Error` and the items read so far are kept.
+            macro_rules! recover {
+                ($e:expr) => {
+                    match $e {
+                        Some(v) => v,
+                        None => {
+                            let err = self.recover_prefix_item(item_start);
+                            items.push(err);
+                            continue 'items;
+                        }
+                    }
+                };
+            }
             match self.peek_kind()? {
                 TokenKind::Semicolon => {
                     // semicolon marks statement end; collect and ensure nothing follows on same line
@@ -1204,7 +1481,7 @@ impl Parser {
                 TokenKind::Newline | TokenKind::Dedent | TokenKind::Eof => break,
                 TokenKind::Colon => {
                     let colon_span = self.next().unwrap().span;
-                    let block = self.parse_block_after_colon()?;
+                    let block = recover!(self.parse_block_after_colon());
                     let span = colon_span.join(block.span).unwrap_or(colon_span);
 
                     if let Some(last) = items.last() {
@@ -1276,20 +1553,20 @@ impl Parser {
                                                 && diag2.message
                                                     == "missing expression(s) in if-layout block")
                                             {
-                                                self.diagnostics.push(diag2);
+                                                self.diagnostics.push(*diag2);
                                             }
                                             items.push(PrefixItem::Block(block, span));
                                         }
                                     }
                                 } else {
-                                    self.diagnostics.push(diag);
+                                    self.diagnostics.push(*diag);
                                     items.push(PrefixItem::Block(block, span));
                                 }
                             }
                         }
                     } else if items
                         .iter()
-                        .any(|it| matches!(it, PrefixItem::Symbol(Symbol::While(_))))
+                        .any(|it| matches!(it, PrefixItem::Symbol(Symbol::While(..))))
                     {
                         let expected = if Self::while_layout_needs_cond(&items) {
                             2
@@ -1312,7 +1589,7 @@ impl Parser {
                                 }
                             }
                             Err(diag) => {
-                                self.diagnostics.push(diag);
+                                self.diagnostics.push(*diag);
                                 items.push(PrefixItem::Block(block, span));
                             }
                         }
@@ -1333,7 +1610,7 @@ impl Parser {
                                 }
                             }
                             Err(diag) => {
-                                self.diagnostics.push(diag);
+                                self.diagnostics.push(*diag);
                                 items.push(PrefixItem::Block(block, span));
                             }
                         }
@@ -1348,8 +1625,8 @@ impl Parser {
                 }
                 TokenKind::LAngle => {
                     let start = self.next().unwrap().span;
-                    let ty = self.parse_type_expr()?;
-                    self.expect(&TokenKind::RAngle)?;
+                    let ty = recover!(self.parse_type_expr());
+                    recover!(self.expect(&TokenKind::RAngle));
                     let end = self.peek_span().unwrap_or(start);
                     let span = start.join(end).unwrap_or(start);
                     items.push(PrefixItem::TypeAnnotation(ty, span));
@@ -1397,13 +1674,13 @@ impl Parser {
                     items.push(PrefixItem::Literal(lit, tok.span));
                 }
                 TokenKind::LParen => {
-                    let parsed = self.parse_parenthesized_expr_items(false)?;
+                    let parsed = recover!(self.parse_parenthesized_expr_items(false));
                     items.extend(parsed);
                 }
                 TokenKind::KwLet => {
                     let let_span = self.next().unwrap().span;
                     let (is_mut, no_shadow) = self.parse_let_modifiers(let_span);
-                    let (name, span) = self.expect_ident()?;
+                    let (name, span) = recover!(self.expect_ident());
                     self.consume_if(&TokenKind::Equals);
                     items.push(PrefixItem::Symbol(Symbol::Let {
                         name: Ident { name, span },
@@ -1413,7 +1690,7 @@ impl Parser {
                 }
                 TokenKind::KwSet => {
                     let set_tok = self.next().unwrap();
-                    let (name, span) = self.expect_ident()?;
+                    let (name, span) = recover!(self.expect_ident());
                     self.consume_if(&TokenKind::Equals);
                     items.push(PrefixItem::Symbol(Symbol::Set {
                         name: Ident { name, span: set_tok.span.join(span).unwrap_or(span) },
@@ -1434,7 +1711,7 @@ impl Parser {
                                 "expected string literal for intrinsic name",
                                 sp,
                             ));
-                            return None;
+                            recover!(None)
                         }
                     };
 
@@ -1445,12 +1722,12 @@ impl Parser {
                             if self.check(&TokenKind::RAngle) {
                                 break;
                             }
-                            type_args.push(self.parse_type_expr()?);
+                            type_args.push(recover!(self.parse_type_expr()));
                             if !self.consume_if(&TokenKind::Comma) {
                                 break;
                             }
                         }
-                        self.expect(&TokenKind::RAngle)?;
+                        recover!(self.expect(&TokenKind::RAngle));
                     }
 
                     let lp = if self.check(&TokenKind::LParen) {
@@ -1461,14 +1738,14 @@ impl Parser {
                             "expected '(' after intrinsic name/res",
                             sp,
                         ));
-                        return None;
+                        recover!(None)
                     };
 
                     let (args, args_span, _) = if self.consume_if(&TokenKind::RParen) {
                         let rp = self.peek_span().unwrap_or(lp);
                         (Vec::new(), lp.join(rp).unwrap_or(lp), false)
                     } else {
-                        self.parse_tuple_items(lp)?
+                        recover!(self.parse_tuple_items(lp))
                     };
                     
                     let end_span = args_span;
@@ -1488,7 +1765,22 @@ impl Parser {
                 }
                 TokenKind::KwWhile => {
                     let span = self.next().unwrap().span;
-                    items.push(PrefixItem::Symbol(Symbol::While(span)));
+                    let label = self.parse_loop_label();
+                    items.push(PrefixItem::Symbol(Symbol::While(span, label)));
+                }
+                TokenKind::KwReturn => {
+                    let span = self.next().unwrap().span;
+                    items.push(PrefixItem::Symbol(Symbol::Return(span)));
+                }
+                TokenKind::KwBreak => {
+                    let span = self.next().unwrap().span;
+                    let label = self.parse_loop_label();
+                    items.push(PrefixItem::Symbol(Symbol::Break(span, label)));
+                }
+                TokenKind::KwContinue => {
+                    let span = self.next().unwrap().span;
+                    let label = self.parse_loop_label();
+                    items.push(PrefixItem::Symbol(Symbol::Continue(span, label)));
                 }
                 TokenKind::KwCond => {
                     items.push(self.parse_layout_marker_symbol("cond"));
@@ -1506,20 +1798,20 @@ impl Parser {
                     let span = self.next().unwrap().span;
                     if self.consume_if(&TokenKind::Colon) {
                         let block = if self.check(&TokenKind::Newline) {
-                            self.parse_block_after_colon()?
+                            recover!(self.parse_block_after_colon())
                         } else {
                             let err_span = self.peek_span().unwrap_or(span);
                             self.diagnostics.push(Diagnostic::error(
                                 "block: requires newline after ':' (only whitespace/comment is allowed)",
                                 err_span,
                             ));
-                            self.parse_single_line_block(err_span)?
+                            recover!(self.parse_single_line_block(err_span))
                         };
                         let bspan = block.span;
                         items.push(PrefixItem::Block(block, bspan));
                         break;
                     } else {
-                        let block = self.parse_single_line_block(span)?;
+                        let block = recover!(self.parse_single_line_block(span));
                         let bspan = block.span;
                         items.push(PrefixItem::Block(block, bspan));
                         let continue_outer = match self.peek_kind() {
@@ -1545,8 +1837,8 @@ impl Parser {
                 TokenKind::KwTuple => {
                     let span = self.next().unwrap().span;
                     if self.consume_if(&TokenKind::Colon) {
-                        let block = self.parse_block_after_colon()?;
-                        let args = self.extract_arg_layout_exprs(block.clone(), span).ok()?;
+                        let block = recover!(self.parse_block_after_colon());
+                        let args = recover!(self.extract_arg_layout_exprs(block.clone(), span).ok());
                         items.push(PrefixItem::Tuple(
                             args,
                             span.join(block.span).unwrap_or(span),
@@ -1558,13 +1850,19 @@ impl Parser {
                     break;
                 }
                 TokenKind::KwMatch => {
-                    let m = self.parse_match_expr()?;
+                    let m = recover!(self.parse_match_expr());
                     let sp = m.span;
                     items.push(PrefixItem::Match(m, sp));
                     break;
                 }
+                TokenKind::KwFor => {
+                    let f = recover!(self.parse_for_expr());
+                    let sp = f.span;
+                    items.push(PrefixItem::For(f, sp));
+                    break;
+                }
                 TokenKind::At | TokenKind::Ident(_) => {
-                    let ident_item = self.parse_ident_symbol_item(&items, true, true)?;
+                    let ident_item = recover!(self.parse_ident_symbol_item(&items, true, true));
                     items.push(ident_item);
                 }
                 TokenKind::Ampersand => {
@@ -1575,6 +1873,10 @@ impl Parser {
                     let span = self.next().unwrap().span;
                     items.push(PrefixItem::Symbol(Symbol::Deref(span)));
                 }
+                TokenKind::Question => {
+                    let span = self.next().unwrap().span;
+                    items.push(PrefixItem::Symbol(Symbol::Try(span)));
+                }
                 _ => {
                     let span = self.peek_span().unwrap_or_else(Span::dummy);
                     self.diagnostics
@@ -1795,20 +2097,20 @@ impl Parser {
                                                 && diag2.message
                                                     == "missing expression(s) in if-layout block")
                                             {
-                                                self.diagnostics.push(diag2);
+                                                self.diagnostics.push(*diag2);
                                             }
                                             items.push(PrefixItem::Block(block, span));
                                         }
                                     }
                                 } else {
-                                    self.diagnostics.push(diag);
+                                    self.diagnostics.push(*diag);
                                     items.push(PrefixItem::Block(block, span));
                                 }
                             }
                         }
                     } else if items
                         .iter()
-                        .any(|it| matches!(it, PrefixItem::Symbol(Symbol::While(_))))
+                        .any(|it| matches!(it, PrefixItem::Symbol(Symbol::While(..))))
                     {
                         let expected = if Self::while_layout_needs_cond(&items) {
                             2
@@ -1831,7 +2133,7 @@ impl Parser {
                                 }
                             }
                             Err(diag) => {
-                                self.diagnostics.push(diag);
+                                self.diagnostics.push(*diag);
                                 items.push(PrefixItem::Block(block, span));
                             }
                         }
@@ -1852,7 +2154,7 @@ impl Parser {
                                 }
                             }
                             Err(diag) => {
-                                self.diagnostics.push(diag);
+                                self.diagnostics.push(*diag);
                                 items.push(PrefixItem::Block(block, span));
                             }
                         }
@@ -1925,7 +2227,22 @@ impl Parser {
                 }
                 TokenKind::KwWhile => {
                     let span = self.next().unwrap().span;
-                    items.push(PrefixItem::Symbol(Symbol::While(span)));
+                    let label = self.parse_loop_label();
+                    items.push(PrefixItem::Symbol(Symbol::While(span, label)));
+                }
+                TokenKind::KwReturn => {
+                    let span = self.next().unwrap().span;
+                    items.push(PrefixItem::Symbol(Symbol::Return(span)));
+                }
+                TokenKind::KwBreak => {
+                    let span = self.next().unwrap().span;
+                    let label = self.parse_loop_label();
+                    items.push(PrefixItem::Symbol(Symbol::Break(span, label)));
+                }
+                TokenKind::KwContinue => {
+                    let span = self.next().unwrap().span;
+                    let label = self.parse_loop_label();
+                    items.push(PrefixItem::Symbol(Symbol::Continue(span, label)));
                 }
                 TokenKind::KwCond => {
                     items.push(self.parse_layout_marker_symbol("cond"));
@@ -1967,6 +2284,12 @@ impl Parser {
                     items.push(PrefixItem::Match(m, sp));
                     break;
                 }
+                TokenKind::KwFor => {
+                    let f = self.parse_for_expr()?;
+                    let sp = f.span;
+                    items.push(PrefixItem::For(f, sp));
+                    break;
+                }
                 TokenKind::At | TokenKind::Ident(_) => {
                     let ident_item = self.parse_ident_symbol_item(&items, true, true)?;
                     items.push(ident_item);
@@ -1979,6 +2302,10 @@ impl Parser {
                     let span = self.next().unwrap().span;
                     items.push(PrefixItem::Symbol(Symbol::Deref(span)));
                 }
+                TokenKind::Question => {
+                    let span = self.next().unwrap().span;
+                    items.push(PrefixItem::Symbol(Symbol::Try(span)));
+                }
                 _ => {
                     let span = self.peek_span().unwrap_or_else(Span::dummy);
                     self.diagnostics
@@ -2032,6 +2359,37 @@ impl Parser {
         })
     }
 
+    fn parse_for_expr(&mut self) -> Option<ForExpr> {
+        let for_span = self.next()?.span;
+        let label = self.parse_loop_label();
+        let (name, name_span) = self.expect_ident()?;
+        match self.peek_kind() {
+            Some(TokenKind::Ident(kw)) if kw == "in" => {
+                self.next();
+            }
+            _ => {
+                let sp = self.peek_span().unwrap_or(name_span);
+                self.diagnostics
+                    .push(Diagnostic::error("expected 'in' after the for-loop variable", sp));
+                return None;
+            }
+        }
+        let iter = self.parse_prefix_expr_until_colon()?;
+        let colon_span = self.expect_with_span(&TokenKind::Colon)?;
+        let body = self.parse_block_after_colon()?;
+        let span = for_span.join(colon_span).unwrap_or(for_span);
+        Some(ForExpr {
+            label,
+            var: Ident {
+                name,
+                span: name_span,
+            },
+            iter,
+            body,
+            span,
+        })
+    }
+
     fn parse_prefix_expr_until_colon(&mut self) -> Option<PrefixExpr> {
         let start_span = self.peek_span().unwrap_or_else(Span::dummy);
         let mut items = Vec::new();
@@ -2149,7 +2507,22 @@ impl Parser {
                 }
                 TokenKind::KwWhile => {
                     let span = self.next().unwrap().span;
-                    items.push(PrefixItem::Symbol(Symbol::While(span)));
+                    let label = self.parse_loop_label();
+                    items.push(PrefixItem::Symbol(Symbol::While(span, label)));
+                }
+                TokenKind::KwReturn => {
+                    let span = self.next().unwrap().span;
+                    items.push(PrefixItem::Symbol(Symbol::Return(span)));
+                }
+                TokenKind::KwBreak => {
+                    let span = self.next().unwrap().span;
+                    let label = self.parse_loop_label();
+                    items.push(PrefixItem::Symbol(Symbol::Break(span, label)));
+                }
+                TokenKind::KwContinue => {
+                    let span = self.next().unwrap().span;
+                    let label = self.parse_loop_label();
+                    items.push(PrefixItem::Symbol(Symbol::Continue(span, label)));
                 }
                 TokenKind::KwCond => {
                     items.push(self.parse_layout_marker_symbol("cond"));
@@ -2171,6 +2544,10 @@ impl Parser {
                     let span = self.next().unwrap().span;
                     items.push(PrefixItem::Symbol(Symbol::Deref(span)));
                 }
+                TokenKind::Question => {
+                    let span = self.next().unwrap().span;
+                    items.push(PrefixItem::Symbol(Symbol::Try(span)));
+                }
                 _ => {
                     let span = self.peek_span().unwrap_or_else(Span::dummy);
                     self.diagnostics.push(Diagnostic::error(
@@ -2215,7 +2592,7 @@ impl Parser {
         });
         let has_while = items
             .iter()
-            .any(|item| matches!(item, PrefixItem::Symbol(Symbol::While(_))));
+            .any(|item| matches!(item, PrefixItem::Symbol(Symbol::While(..))));
         let mut i = 0;
         while i < items.len() {
             let remove = match &items[i] {
@@ -2444,7 +2821,7 @@ impl Parser {
             tail.pop();
         }
         match tail.as_slice() {
-            [.., PrefixItem::Symbol(Symbol::While(_))] => true,
+            [.., PrefixItem::Symbol(Symbol::While(..))] => true,
             _ => false,
         }
     }
@@ -2454,7 +2831,7 @@ impl Parser {
         block: Block,
         expected: usize,
         header_span: Span,
-    ) -> Result<Vec<PrefixExpr>, Diagnostic> {
+    ) -> Result<Vec<PrefixExpr>, Box<Diagnostic>> {
         let mut branches: Vec<(Option<IfRole>, Vec<Stmt>)> = Vec::new();
         let mut current_branch: Vec<Stmt> = Vec::new();
         let mut current_role: Option<IfRole> = None;
@@ -2547,16 +2924,16 @@ impl Parser {
                 let idx = match role_to_index(r) {
                     Some(i) => i,
                     None => {
-                        return Err(Diagnostic::error("invalid marker in this if-layout form", expr.span));
+                        return Err(Diagnostic::error("invalid marker in this if-layout form", expr.span).into());
                     }
                 };
                 if let Some(prev_idx) = last_role_idx {
                     if idx < prev_idx {
-                        return Err(Diagnostic::error("invalid marker order in if-layout block", expr.span));
+                        return Err(Diagnostic::error("invalid marker order in if-layout block", expr.span).into());
                     }
                 }
                 if slots[idx].is_some() {
-                    return Err(Diagnostic::error("duplicate marker in if-layout block", expr.span));
+                    return Err(Diagnostic::error("duplicate marker in if-layout block", expr.span).into());
                 }
                 slots[idx] = Some(expr);
                 last_role_idx = Some(idx);
@@ -2565,7 +2942,7 @@ impl Parser {
                     next_unfilled += 1;
                 }
                 if next_unfilled >= expected {
-                    return Err(Diagnostic::error("too many expressions in if-layout block", expr.span));
+                    return Err(Diagnostic::error("too many expressions in if-layout block", expr.span).into());
                 }
                 slots[next_unfilled] = Some(expr);
                 next_unfilled += 1;
@@ -2576,7 +2953,7 @@ impl Parser {
             return Err(Diagnostic::error(
                 "missing expression(s) in if-layout block",
                 header_span,
-            ));
+            ).into());
         }
 
         Ok(slots.into_iter().map(|s| s.unwrap()).collect())
@@ -2587,7 +2964,7 @@ impl Parser {
         block: Block,
         expected: usize,
         header_span: Span,
-    ) -> Result<Vec<PrefixExpr>, Diagnostic> {
+    ) -> Result<Vec<PrefixExpr>, Box<Diagnostic>> {
         let mut entries: Vec<(Option<IfRole>, PrefixExpr)> = Vec::new();
         let mut pending_role: Option<IfRole> = None;
 
@@ -2598,14 +2975,14 @@ impl Parser {
                     return Err(Diagnostic::error(
                         "only expressions are allowed in if-layout block",
                         self.stmt_span(&other),
-                    ));
+                    ).into());
                 }
             };
 
             if let Some(role) = Self::take_role_from_expr(&mut expr) {
                 if expr.items.is_empty() {
                     if pending_role.is_some() {
-                        return Err(Diagnostic::error("duplicate marker in if-layout block", expr.span));
+                        return Err(Diagnostic::error("duplicate marker in if-layout block", expr.span).into());
                     }
                     pending_role = Some(role);
                 } else {
@@ -2622,7 +2999,7 @@ impl Parser {
             return Err(Diagnostic::error(
                 "missing expression(s) in if-layout block",
                 header_span,
-            ));
+            ).into());
         }
 
         let mut slots: Vec<Option<PrefixExpr>> = vec![None; expected];
@@ -2644,16 +3021,16 @@ impl Parser {
                 let idx = match role_to_index(r) {
                     Some(i) => i,
                     None => {
-                        return Err(Diagnostic::error("invalid marker in this if-layout form", expr.span));
+                        return Err(Diagnostic::error("invalid marker in this if-layout form", expr.span).into());
                     }
                 };
                 if let Some(prev_idx) = last_role_idx {
                     if idx < prev_idx {
-                        return Err(Diagnostic::error("invalid marker order in if-layout block", expr.span));
+                        return Err(Diagnostic::error("invalid marker order in if-layout block", expr.span).into());
                     }
                 }
                 if slots[idx].is_some() {
-                    return Err(Diagnostic::error("duplicate marker in if-layout block", expr.span));
+                    return Err(Diagnostic::error("duplicate marker in if-layout block", expr.span).into());
                 }
                 slots[idx] = Some(expr);
                 last_role_idx = Some(idx);
@@ -2662,7 +3039,7 @@ impl Parser {
                     next_unfilled += 1;
                 }
                 if next_unfilled >= expected {
-                    return Err(Diagnostic::error("too many expressions in if-layout block", expr.span));
+                    return Err(Diagnostic::error("too many expressions in if-layout block", expr.span).into());
                 }
                 slots[next_unfilled] = Some(expr);
                 next_unfilled += 1;
@@ -2673,7 +3050,7 @@ impl Parser {
             return Err(Diagnostic::error(
                 "missing expression(s) in if-layout block",
                 header_span,
-            ));
+            ).into());
         }
 
         Ok(slots.into_iter().map(|s| s.unwrap()).collect())
@@ -2684,7 +3061,7 @@ impl Parser {
         block: Block,
         expected: usize,
         header_span: Span,
-    ) -> Result<Vec<PrefixExpr>, Diagnostic> {
+    ) -> Result<Vec<PrefixExpr>, Box<Diagnostic>> {
         let mut branches: Vec<(Option<WhileRole>, Vec<Stmt>)> = Vec::new();
         let mut current_branch: Vec<Stmt> = Vec::new();
         let mut current_role: Option<WhileRole> = None;
@@ -2767,11 +3144,11 @@ impl Parser {
                         return Err(Diagnostic::error(
                             "invalid marker in this while-layout form",
                             expr.span,
-                        ));
+                        ).into());
                     }
                 };
                 if slots[idx].is_some() {
-                    return Err(Diagnostic::error("duplicate marker in while-layout block", expr.span));
+                    return Err(Diagnostic::error("duplicate marker in while-layout block", expr.span).into());
                 }
                 slots[idx] = Some(expr);
             } else {
@@ -2782,7 +3159,7 @@ impl Parser {
                     return Err(Diagnostic::error(
                         "too many expressions in while-layout block",
                         expr.span,
-                    ));
+                    ).into());
                 }
                 slots[next_unfilled] = Some(expr);
                 next_unfilled += 1;
@@ -2793,7 +3170,7 @@ impl Parser {
             return Err(Diagnostic::error(
                 "missing expression(s) in while-layout block",
                 header_span,
-            ));
+            ).into());
         }
 
         Ok(slots.into_iter().map(|s| s.unwrap()).collect())
@@ -2803,7 +3180,7 @@ impl Parser {
         &mut self,
         block: Block,
         header_span: Span,
-    ) -> Result<Vec<PrefixExpr>, Diagnostic> {
+    ) -> Result<Vec<PrefixExpr>, Box<Diagnostic>> {
         let mut exprs: Vec<PrefixExpr> = Vec::new();
         for stmt in block.items {
             match stmt {
@@ -2813,7 +3190,7 @@ impl Parser {
                     return Err(Diagnostic::error(
                         "only expressions are allowed in argument layout",
                         sp,
-                    ));
+                    ).into());
                 }
             }
         }
@@ -2821,7 +3198,7 @@ impl Parser {
             return Err(Diagnostic::error(
                 "argument layout block must contain expressions",
                 header_span,
-            ));
+            ).into());
         }
         Ok(exprs)
     }
@@ -3222,7 +3599,7 @@ impl Parser {
         }
         let has_while = current_items
             .iter()
-            .any(|it| matches!(it, PrefixItem::Symbol(Symbol::While(_))));
+            .any(|it| matches!(it, PrefixItem::Symbol(Symbol::While(..))));
         has_while && matches!(name, "cond" | "do")
     }
 
@@ -3243,6 +3620,92 @@ impl Parser {
         }
     }
 
+    /// Expects the `:` that opens an indented body. When the line ends and an
+    /// indented block follows, the `:` is reported as missing and parsing
+    /// continues as if it were there.
+    /// Replaces a prefix item that failed to parse, together with the rest of
+    /// the statement, by a `PrefixItem::Error` covering the skipped tokens.
+    fn recover_prefix_item(&mut self, start: Span) -> PrefixItem {
+        self.skip_rest_of_stmt();
+        let span = match self.tokens[..self.pos].last() {
+            Some(tok) if tok.span.end > start.start => start.join(tok.span).unwrap_or(start),
+            _ => start,
+        };
+        PrefixItem::Error(span)
+    }
+
+    /// Skips the rest of a broken `<...>` up to and including its closing `>`,
+    /// staying on the current line.
+    fn skip_to_closing_angle(&mut self) {
+        let mut depth = 0usize;
+        while let Some(kind) = self.peek_kind() {
+            match kind {
+                TokenKind::Newline | TokenKind::Colon | TokenKind::Eof => break,
+                TokenKind::LAngle => depth += 1,
+                TokenKind::RAngle if depth == 0 => {
+                    self.next();
+                    break;
+                }
+                TokenKind::RAngle => depth -= 1,
+                _ => {}
+            }
+            self.next();
+        }
+    }
+
+    fn expect_block_colon(&mut self) -> Option<()> {
+        if self.consume_if(&TokenKind::Colon) {
+            return Some(());
+        }
+        if self.check(&TokenKind::Newline) && self.indent_follows() {
+            let span = self.peek_span().unwrap_or_else(Span::dummy);
+            self.diagnostics.push(Diagnostic::error(
+                "expected ':' before the indented block",
+                span,
+            ));
+            return Some(());
+        }
+        self.expect(&TokenKind::Colon)
+    }
+
+    /// Whether the next non-blank line is indented deeper than the current one.
+    fn indent_follows(&self) -> bool {
+        let mut i = 0;
+        while matches!(self.peek_kind_at(i), Some(TokenKind::Newline)) {
+            i += 1;
+        }
+        i > 0 && matches!(self.peek_kind_at(i), Some(TokenKind::Indent))
+    }
+
+    /// Skips the rest of a statement after an error: everything up to the next
+    /// line break or `;`, or the `Dedent` closing the current block. An indented
+    /// block following the skipped line belongs to it and is skipped as well.
+    /// Returns the span of the skipped tokens.
+    fn skip_rest_of_stmt(&mut self) -> Option<Span> {
+        let mut skipped: Option<Span> = None;
+        let mut depth = 0usize;
+        while let Some(kind) = self.peek_kind() {
+            match kind {
+                TokenKind::Eof => break,
+                TokenKind::Newline if depth == 0 && !self.indent_follows() => break,
+                TokenKind::Semicolon | TokenKind::Dedent if depth == 0 => break,
+                TokenKind::Indent => depth += 1,
+                TokenKind::Dedent => depth -= 1,
+                _ => {}
+            }
+            let tok = self.next().unwrap();
+            match tok.kind {
+                // the indented block of the skipped line has ended
+                TokenKind::Dedent if depth == 0 => break,
+                TokenKind::Newline | TokenKind::Indent | TokenKind::Dedent => {}
+                _ => {
+                    skipped = Some(skipped.map_or(tok.span, |s| s.join(tok.span).unwrap_or(s)))
+                }
+            }
+        }
+        skipped
+    }
+
     fn expect_ident(&mut self) -> Option<(String, Span)> {
         match self.peek_kind() {
             Some(TokenKind::Ident(name)) => {
@@ -3298,6 +3761,9 @@ impl Parser {
             TokenKind::KwSet => Some("set"),
             TokenKind::KwIf => Some("if"),
             TokenKind::KwWhile => Some("while"),
+            TokenKind::KwReturn => Some("return"),
+            TokenKind::KwBreak => Some("break"),
+            TokenKind::KwContinue => Some("continue"),
             TokenKind::KwCond => Some("cond"),
             TokenKind::KwThen => Some("then"),
             TokenKind::KwElse => Some("else"),
@@ -3312,6 +3778,10 @@ impl Parser {
             TokenKind::KwBlock => Some("block"),
             TokenKind::KwTuple => Some("Tuple"),
             TokenKind::KwMlstr => Some("mlstr"),
+            TokenKind::KwConst => Some("const"),
+            TokenKind::KwStatic => Some("static"),
+            TokenKind::KwType => Some("type"),
+            TokenKind::KwNewtype => Some("newtype"),
             _ => None,
         }
     }
@@ -3555,16 +4025,22 @@ impl Parser {
             PrefixItem::Symbol(Symbol::Let { name, .. }) => name.span,
             PrefixItem::Symbol(Symbol::Set { name }) => name.span,
             PrefixItem::Symbol(Symbol::If(sp)) => *sp,
-            PrefixItem::Symbol(Symbol::While(sp)) => *sp,
+            PrefixItem::Symbol(Symbol::While(sp, _)) => *sp,
+            PrefixItem::Symbol(Symbol::Return(sp)) => *sp,
+            PrefixItem::Symbol(Symbol::Break(sp, _)) => *sp,
+            PrefixItem::Symbol(Symbol::Continue(sp, _)) => *sp,
             PrefixItem::Symbol(Symbol::AddrOf(sp)) => *sp,
             PrefixItem::Symbol(Symbol::Deref(sp)) => *sp,
+            PrefixItem::Symbol(Symbol::Try(sp)) => *sp,
             PrefixItem::TypeAnnotation(_, sp) => *sp,
             PrefixItem::Block(_, sp) => *sp,
             PrefixItem::Match(_, sp) => *sp,
+            PrefixItem::For(_, sp) => *sp,
             PrefixItem::Pipe(sp) => *sp,
             PrefixItem::Tuple(_, sp) => *sp,
             PrefixItem::Group(_, sp) => *sp,
             PrefixItem::Intrinsic(_, sp) => *sp,
+            PrefixItem::Error(sp) => *sp,
         }
     }
 
@@ -3577,6 +4053,7 @@ impl Parser {
                 Directive::Use { span, .. } => *span,
                 Directive::IfTarget { span, .. } => *span,
                 Directive::IfProfile { span, .. } => *span,
+                Directive::IfConst { span, .. } => *span,
                 Directive::IndentWidth { span, .. } => *span,
                 Directive::Extern { span, .. } => *span,
                 Directive::Include { span, .. } => *span,
@@ -3593,6 +4070,10 @@ impl Parser {
             Stmt::ExprSemi(e, _) => e.span,
             Stmt::Trait(t) => t.span,
             Stmt::Impl(i) => i.span,
+            Stmt::Static(s) => s.name.span,
+            Stmt::TypeAlias(t) => t.name.span,
+            Stmt::Newtype(n) => n.name.span,
+            Stmt::Error(sp) => *sp,
         }
     }
 }
@@ -3663,6 +4144,7 @@ fn simple_type_atom(t: &str, span: Span, diags: &mut Vec<Diagnostic>) -> Option<
         "f32" => Some(TypeExpr::F32),
         "i64" => Some(TypeExpr::Named("i64".to_string())),
         "f64" => Some(TypeExpr::Named("f64".to_string())),
+        "v128" => Some(TypeExpr::Named("v128".to_string())),
         "bool" => Some(TypeExpr::Bool),
         "never" => Some(TypeExpr::Never),
         "str" => Some(TypeExpr::Str),
@@ -13,7 +13,10 @@ use crate::types::TypeId;
 /// This pass walks the HIR and inserts `Drop` expressions to deallocate
 /// heap-owned values at scope boundaries. The pass operates lexically:
 /// - At the end of a block, drops are inserted for all live bindings.
-/// - On early returns (If, Match arms), drops are inserted before exit.
+/// - On early returns (`Return`), every live binding that the returned value
+///   does not mention is dropped before leaving the function.
+/// - On `Break` / `Continue`, bindings declared inside the target loop are
+///   dropped before the jump.
 /// - Variable scope is tracked through Let/Set statements.
 pub fn insert_drops(module: &mut HirModule, unit_ty: TypeId) {
     for func in &mut module.functions {
@@ -31,6 +34,8 @@ struct DropInsertionContext {
     live_vars: Vec<String>,
     /// Stack of variable scopes (for nested blocks).
     scopes: Vec<BTreeSet<String>>,
+    /// Enclosing loops: label and the scope depth at their body.
+    loops: Vec<(Option<String>, usize)>,
 }
 
 impl DropInsertionContext {
@@ -39,6 +44,7 @@ impl DropInsertionContext {
             unit_ty,
             live_vars: Vec::new(),
             scopes: Vec::new(),
+            loops: Vec::new(),
         }
     }
 
@@ -122,9 +128,26 @@ fn insert_drops_in_expr(expr: &mut HirExpr, ctx: &mut DropInsertionContext) {
                 ctx.pop_scope();
             }
         }
-        HirExprKind::While { cond, body } => {
+        HirExprKind::While { cond, body, label } => {
             insert_drops_in_expr(cond, ctx);
+            ctx.loops.push((label.clone(), ctx.scopes.len()));
             insert_drops_in_expr(body, ctx);
+            ctx.loops.pop();
+        }
+        HirExprKind::Break(target) | HirExprKind::Continue(target) => {
+            let depth = ctx
+                .loops
+                .iter()
+                .rev()
+                .find(|(label, _)| target.is_none() || target == label)
+                .map_or(ctx.scopes.len(), |(_, depth)| *depth);
+            let live: Vec<String> = ctx.scopes[depth..]
+                .iter()
+                .rev()
+                .flat_map(|scope| scope.iter().rev())
+                .cloned()
+                .collect();
+            drop_before_exit(expr, live, ctx.unit_ty);
         }
         HirExprKind::Match { scrutinee, arms } => {
             insert_drops_in_expr(scrutinee, ctx);
@@ -189,10 +212,26 @@ fn insert_drops_in_expr(expr: &mut HirExpr, ctx: &mut DropInsertionContext) {
         HirExprKind::Block(ref mut block) => {
             insert_drops_in_block(block, ctx);
         }
+        HirExprKind::Return(value) => {
+            insert_drops_in_expr(value, ctx);
+            // The value only reads bindings through `Var`, so the others can
+            // be dropped before it is evaluated.
+            let mut used = BTreeSet::new();
+            collect_vars(value, &mut used);
+            let live: Vec<String> = ctx
+                .scopes
+                .iter()
+                .rev()
+                .flat_map(|scope| scope.iter().rev())
+                .filter(|var| !used.contains(*var))
+                .cloned()
+                .collect();
+            drop_before_exit(expr, live, ctx.unit_ty);
+        }
         HirExprKind::Let { value, .. } => {
             insert_drops_in_expr(value, ctx);
         }
-        HirExprKind::Set { value, .. } => {
+        HirExprKind::Set { value, .. } | HirExprKind::GlobalSet { value, .. } => {
             insert_drops_in_expr(value, ctx);
         }
         HirExprKind::Call { args, .. } => {
@@ -218,3 +257,84 @@ fn insert_drops_in_expr(expr: &mut HirExpr, ctx: &mut DropInsertionContext) {
         _ => {}
     }
 }
+
+/// Wrap the exit `expr` into a block that first drops `live`.
+fn drop_before_exit(expr: &mut HirExpr, live: Vec<String>, unit_ty: TypeId) {
+    if live.is_empty() {
+        return;
+    }
+    let mut lines: Vec<HirLine> = live
+        .into_iter()
+        .map(|name| HirLine {
+            expr: HirExpr {
+                ty: unit_ty,
+                kind: HirExprKind::Drop { name },
+                span: expr.span,
+            },
+            drop_result: true,
+        })
+        .collect();
+    let exit = core::mem::replace(&mut expr.kind, HirExprKind::Unit);
+    lines.push(HirLine {
+        expr: HirExpr {
+            ty: expr.ty,
+            kind: exit,
+            span: expr.span,
+        },
+        drop_result: false,
+    });
+    expr.kind = HirExprKind::Block(HirBlock {
+        lines,
+        ty: expr.ty,
+        span: expr.span,
+    });
+}
+
+fn collect_vars(expr: &HirExpr, out: &mut BTreeSet<String>) {
+    match &expr.kind {
+        HirExprKind::Var(name) => {
+            out.insert(name.clone());
+        }
+        HirExprKind::Call { args, .. } | HirExprKind::Intrinsic { args, .. } => {
+            args.iter().for_each(|a| collect_vars(a, out));
+        }
+        HirExprKind::CallIndirect { callee, args, .. } => {
+            collect_vars(callee, out);
+            args.iter().for_each(|a| collect_vars(a, out));
+        }
+        HirExprKind::If {
+            cond,
+            then_branch,
+            else_branch,
+        } => {
+            collect_vars(cond, out);
+            collect_vars(then_branch, out);
+            collect_vars(else_branch, out);
+        }
+        HirExprKind::While { cond, body, .. } => {
+            collect_vars(cond, out);
+            collect_vars(body, out);
+        }
+        HirExprKind::Match { scrutinee, arms } => {
+            collect_vars(scrutinee, out);
+            arms.iter().for_each(|arm| collect_vars(&arm.body, out));
+        }
+        HirExprKind::Block(b) => b.lines.iter().for_each(|l| collect_vars(&l.expr, out)),
+        HirExprKind::Let { value, .. }
+        | HirExprKind::Set { value, .. }
+        | HirExprKind::GlobalSet { value, .. }
+        | HirExprKind::AddrOf(value)
+        | HirExprKind::Deref(value)
+        | HirExprKind::Return(value) => collect_vars(value, out),
+        HirExprKind::EnumConstruct { payload, .. } => {
+            if let Some(p) = payload {
+                collect_vars(p, out);
+            }
+        }
+        HirExprKind::StructConstruct { fields: items, .. }
+        | HirExprKind::TupleConstruct { items } => {
+            items.iter().for_each(|i| collect_vars(i, out));
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,233 @@
+extern crate alloc;
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::hir::{FuncRef, HirBlock, HirBody, HirExpr, HirExprKind, HirModule};
+
+/// Intrinsic wrapping a struct / tuple / enum construction whose memory may
+/// live in the current function's shadow-stack frame instead of the heap.
+pub const IN_FRAME: &str = "in_frame";
+
+/// Move aggregates that never outlive their function into its stack frame.
+///
+/// Runs on monomorphized HIR. A construction escapes when its value may be
+/// returned, stored into memory, a global or another aggregate, or passed to
+/// a parameter that escapes (externs, `#wasm` bodies and indirect calls are
+/// assumed to keep their arguments). Variables are tracked by name per
+/// function, and parameter summaries are iterated to a fixpoint across the
+/// module. Constructions inside `while` loops stay on the heap because their
+/// frame slot would be reused by the next iteration.
+///
+/// Non-escaping constructions are wrapped in the [`IN_FRAME`] intrinsic; the
+/// number of wrapped sites is returned.
+pub fn place_frame_aggregates(module: &mut HirModule) -> usize {
+    let mut params: BTreeMap<String, Vec<bool>> = module
+        .functions
+        .iter()
+        .map(|f| {
+            let opaque = !matches!(f.body, HirBody::Block(_));
+            (f.name.clone(), vec![opaque; f.params.len()])
+        })
+        .collect();
+    loop {
+        let mut changed = false;
+        for f in &mut module.functions {
+            let HirBody::Block(block) = &mut f.body else {
+                continue;
+            };
+            let vars = escaping_vars(block, &params);
+            let summary: Vec<bool> = f.params.iter().map(|p| vars.contains(&p.name)).collect();
+            if params.get(&f.name) != Some(&summary) {
+                params.insert(f.name.clone(), summary);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut placed = 0;
+    for f in &mut module.functions {
+        let HirBody::Block(block) = &mut f.body else {
+            continue;
+        };
+        let mut esc = Escape {
+            params: &params,
+            vars: escaping_vars(block, &params),
+            grew: false,
+            rewrite: true,
+            placed: 0,
+        };
+        esc.block(block, true, false);
+        placed += esc.placed;
+    }
+    placed
+}
+
+fn escaping_vars(block: &mut HirBlock, params: &BTreeMap<String, Vec<bool>>) -> BTreeSet<String> {
+    let mut esc = Escape {
+        params,
+        vars: BTreeSet::new(),
+        grew: false,
+        rewrite: false,
+        placed: 0,
+    };
+    loop {
+        esc.grew = false;
+        esc.block(block, true, false);
+        if !esc.grew {
+            return esc.vars;
+        }
+    }
+}
+
+struct Escape<'a> {
+    /// Per function, whether each parameter may escape.
+    params: &'a BTreeMap<String, Vec<bool>>,
+    /// Variables of the current function whose value may escape.
+    vars: BTreeSet<String>,
+    /// Set when `vars` grew during the current walk.
+    grew: bool,
+    /// Wrap non-escaping constructions (final walk only).
+    rewrite: bool,
+    placed: usize,
+}
+
+impl Escape<'_> {
+    fn block(&mut self, block: &mut HirBlock, escaping: bool, in_loop: bool) {
+        // Only the last value line flows out of the block.
+        let tail = block.lines.iter().rposition(|l| !l.drop_result);
+        for (i, line) in block.lines.iter_mut().enumerate() {
+            self.expr(&mut line.expr, escaping && Some(i) == tail, in_loop);
+        }
+    }
+
+    fn expr(&mut self, expr: &mut HirExpr, escaping: bool, in_loop: bool) {
+        let construct = match &mut expr.kind {
+            HirExprKind::Var(name) => {
+                if escaping && self.vars.insert(name.clone()) {
+                    self.grew = true;
+                }
+                false
+            }
+            HirExprKind::Let { name, value, .. } | HirExprKind::Set { name, value } => {
+                let esc = self.vars.contains(name.as_str());
+                self.expr(value, esc, in_loop);
+                false
+            }
+            HirExprKind::GlobalSet { value, .. } => {
+                self.expr(value, true, in_loop);
+                false
+            }
+            HirExprKind::Call { callee, args } => {
+                let params = self.params;
+                let summary = match callee {
+                    FuncRef::Builtin(n) | FuncRef::User(n, _) => params.get(n),
+                    FuncRef::Trait { .. } => None,
+                };
+                for (i, arg) in args.iter_mut().enumerate() {
+                    let esc = summary.and_then(|s| s.get(i).copied()).unwrap_or(true);
+                    self.expr(arg, esc, in_loop);
+                }
+                false
+            }
+            HirExprKind::CallIndirect { callee, args, .. } => {
+                self.expr(callee, false, in_loop);
+                for arg in args {
+                    self.expr(arg, true, in_loop);
+                }
+                false
+            }
+            HirExprKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                self.expr(cond, false, in_loop);
+                self.expr(then_branch, escaping, in_loop);
+                self.expr(else_branch, escaping, in_loop);
+                false
+            }
+            HirExprKind::While { cond, body, .. } => {
+                self.expr(cond, false, true);
+                self.expr(body, false, true);
+                false
+            }
+            HirExprKind::Match { scrutinee, arms } => {
+                self.expr(scrutinee, false, in_loop);
+                for arm in arms {
+                    self.expr(&mut arm.body, escaping, in_loop);
+                }
+                false
+            }
+            HirExprKind::EnumConstruct { payload, .. } => {
+                if let Some(p) = payload {
+                    self.expr(p, true, in_loop);
+                }
+                true
+            }
+            HirExprKind::StructConstruct { fields: items, .. }
+            | HirExprKind::TupleConstruct { items } => {
+                for item in items {
+                    self.expr(item, true, in_loop);
+                }
+                true
+            }
+            HirExprKind::Block(b) => {
+                self.block(b, escaping, in_loop);
+                false
+            }
+            HirExprKind::Intrinsic { name, args, .. } => {
+                for (i, arg) in args.iter_mut().enumerate() {
+                    let esc = match name.as_str() {
+                        // Addresses are read / written through, not kept.
+                        "load" | "mem_copy" | "mem_fill" => false,
+                        "store" => i > 0,
+                        "add" => escaping,
+                        _ => true,
+                    };
+                    self.expr(arg, esc, in_loop);
+                }
+                false
+            }
+            HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) => {
+                self.expr(inner, escaping, in_loop);
+                false
+            }
+            HirExprKind::Return(value) => {
+                self.expr(value, true, in_loop);
+                false
+            }
+            HirExprKind::Unit
+            | HirExprKind::LiteralI32(_)
+            | HirExprKind::LiteralF32(_)
+            | HirExprKind::LiteralI64(_)
+            | HirExprKind::LiteralF64(_)
+            | HirExprKind::LiteralBool(_)
+            | HirExprKind::LiteralStr(_)
+            | HirExprKind::StaticAddr(_)
+            | HirExprKind::GlobalGet(_)
+            | HirExprKind::FnValue(_)
+            | HirExprKind::Break(_)
+            | HirExprKind::Continue(_)
+            | HirExprKind::Drop { .. } => false,
+        };
+        if construct && self.rewrite && !escaping && !in_loop {
+            let inner = core::mem::replace(&mut expr.kind, HirExprKind::Unit);
+            expr.kind = HirExprKind::Intrinsic {
+                name: String::from(IN_FRAME),
+                type_args: Vec::new(),
+                args: vec![HirExpr {
+                    ty: expr.ty,
+                    kind: inner,
+                    span: expr.span,
+                }],
+            };
+            self.placed += 1;
+        }
+    }
+}
@@ -1,4 +1,6 @@
 pub mod drop_insertion;
+pub mod escape;
 pub mod move_check;
 
 pub use drop_insertion::insert_drops;
+pub use escape::place_frame_aggregates;
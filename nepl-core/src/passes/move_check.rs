@@ -31,6 +31,17 @@ struct MoveCheckContext {
     scopes: Vec<BTreeSet<String>>,
     /// History of changes for undoing/merging branches
     history: Vec<BTreeMap<String, VarState>>,
+    /// Enclosing loops, innermost last.
+    loops: Vec<LoopScope>,
+}
+
+/// Variable states around a `while` loop, for `break` / `continue`.
+struct LoopScope {
+    label: Option<String>,
+    /// States on entry to the loop body.
+    entry: BTreeMap<String, VarState>,
+    /// Variables moved on some path that breaks out of the loop.
+    moved_at_break: BTreeSet<String>,
 }
 
 impl MoveCheckContext {
@@ -40,9 +51,34 @@ impl MoveCheckContext {
             diagnostics: Vec::new(),
             scopes: Vec::new(),
             history: Vec::new(),
+            loops: Vec::new(),
         }
     }
 
+    fn current_states(&self) -> BTreeMap<String, VarState> {
+        self.var_stacks
+            .iter()
+            .filter_map(|(name, stack)| Some((name.clone(), *stack.last()?)))
+            .collect()
+    }
+
+    /// Variables valid on entry to the target loop but not any more.
+    fn moved_since_loop_entry(&self, target: &Option<String>) -> Option<(usize, Vec<String>)> {
+        let idx = self
+            .loops
+            .iter()
+            .rposition(|l| target.is_none() || *target == l.label)?;
+        let moved = self.loops[idx]
+            .entry
+            .iter()
+            .filter(|(name, state)| {
+                **state == VarState::Valid && self.get_state(name) != Some(VarState::Valid)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        Some((idx, moved))
+    }
+
     fn push_scope(&mut self) {
         self.scopes.push(BTreeSet::new());
     }
@@ -286,20 +322,35 @@ fn visit_expr(expr: &HirExpr, ctx: &mut MoveCheckContext, tctx: &crate::types::T
                 let then_state = then_final.get(&name).copied().unwrap_or(start_state);
                 let else_state = else_final.get(&name).copied().unwrap_or(start_state);
 
-                let merged = match (then_state, else_state) {
-                    (VarState::Valid, VarState::Valid) => VarState::Valid,
-                    (VarState::Moved, VarState::Moved) => VarState::Moved,
-                    _ => VarState::PossiblyMoved,
+                // A branch that leaves via return / break / continue does not reach the join.
+                let merged = match (diverges(then_branch), diverges(else_branch)) {
+                    (true, false) => else_state,
+                    (false, true) => then_state,
+                    _ => match (then_state, else_state) {
+                        (VarState::Valid, VarState::Valid) => VarState::Valid,
+                        (VarState::Moved, VarState::Moved) => VarState::Moved,
+                        _ => VarState::PossiblyMoved,
+                    },
                 };
                 ctx.set_state(&name, merged);
             }
         }
-        HirExprKind::While { cond, body } => {
+        HirExprKind::While { cond, body, label } => {
             visit_expr(cond, ctx, tctx);
+            ctx.loops.push(LoopScope {
+                label: label.clone(),
+                entry: ctx.current_states(),
+                moved_at_break: BTreeSet::new(),
+            });
             ctx.push_history();
             visit_expr(body, ctx, tctx);
             let body_diff = ctx.pop_history();
+            let exits = ctx.loops.pop().map(|l| l.moved_at_break).unwrap_or_default();
 
+            if diverges(body) {
+                // The end of the body is never reached: only the exits matter.
+                ctx.undo_history(&body_diff);
+            }
             for (name, start_state) in body_diff {
                 let end_state = ctx.get_state(&name).unwrap_or(start_state);
                 if end_state != start_state && start_state == VarState::Valid {
@@ -311,12 +362,34 @@ fn visit_expr(expr: &HirExpr, ctx: &mut MoveCheckContext, tctx: &crate::types::T
                 }
             }
             visit_expr(cond, ctx, tctx);
+            for name in exits {
+                if ctx.get_state(&name) == Some(VarState::Valid) {
+                    ctx.set_state(&name, VarState::PossiblyMoved);
+                }
+            }
+        }
+        HirExprKind::Break(target) => {
+            if let Some((idx, moved)) = ctx.moved_since_loop_entry(target) {
+                ctx.loops[idx].moved_at_break.extend(moved);
+            }
+        }
+        HirExprKind::Continue(target) => {
+            // The next iteration starts with these already moved.
+            if let Some((_, moved)) = ctx.moved_since_loop_entry(target) {
+                for name in moved {
+                    ctx.diagnostics.push(Diagnostic::error(
+                        alloc::format!("potentially moved value: `{}`", name),
+                        expr.span,
+                    ));
+                }
+            }
         }
         HirExprKind::Match { scrutinee, arms } => {
             visit_expr(scrutinee, ctx, tctx);
 
             let mut all_branch_diffs = Vec::new();
             let mut all_branch_finals = Vec::new();
+            let all_diverge = arms.iter().all(|arm| diverges(&arm.body));
 
             for arm in arms {
                 ctx.push_history();
@@ -333,8 +406,10 @@ fn visit_expr(expr: &HirExpr, ctx: &mut MoveCheckContext, tctx: &crate::types::T
                     final_states.insert(name.clone(), ctx.get_state(name).unwrap_or(fallback));
                 }
                 ctx.undo_history(&diff);
+                if all_diverge || !diverges(&arm.body) {
+                    all_branch_finals.push(final_states);
+                }
                 all_branch_diffs.push(diff);
-                all_branch_finals.push(final_states);
             }
 
             let mut all_modified = BTreeSet::new();
@@ -387,6 +462,9 @@ fn visit_expr(expr: &HirExpr, ctx: &mut MoveCheckContext, tctx: &crate::types::T
             visit_expr(value, ctx, tctx);
             ctx.set_state(name, VarState::Valid);
         }
+        HirExprKind::GlobalSet { value, .. } => {
+            visit_expr(value, ctx, tctx);
+        }
         HirExprKind::Let { name, value, .. } => {
             visit_expr(value, ctx, tctx);
 
@@ -446,14 +524,18 @@ fn visit_expr(expr: &HirExpr, ctx: &mut MoveCheckContext, tctx: &crate::types::T
         HirExprKind::AddrOf(inner) => {
             visit_borrow(inner, ctx, tctx);
         }
-        HirExprKind::Deref(inner) => {
+        HirExprKind::Deref(inner) | HirExprKind::Return(inner) => {
             visit_expr(inner, ctx, tctx);
         }
         HirExprKind::Drop { .. } => {}
         HirExprKind::LiteralI32(_)
         | HirExprKind::LiteralF32(_)
+        | HirExprKind::LiteralI64(_)
+        | HirExprKind::LiteralF64(_)
         | HirExprKind::LiteralBool(_)
         | HirExprKind::LiteralStr(_)
+        | HirExprKind::StaticAddr(_)
+        | HirExprKind::GlobalGet(_)
         | HirExprKind::Unit => {}
     }
 }
@@ -491,6 +573,24 @@ fn visit_borrow(expr: &HirExpr, ctx: &mut MoveCheckContext, tctx: &crate::types:
     }
 }
 
+/// Whether evaluating `expr` always leaves through `return`, `break` or `continue`.
+fn diverges(expr: &HirExpr) -> bool {
+    match &expr.kind {
+        HirExprKind::Return(_) | HirExprKind::Break(_) | HirExprKind::Continue(_) => true,
+        HirExprKind::Block(b) => b.lines.iter().any(|l| diverges(&l.expr)),
+        HirExprKind::If {
+            cond,
+            then_branch,
+            else_branch,
+        } => diverges(cond) || (diverges(then_branch) && diverges(else_branch)),
+        HirExprKind::Match { scrutinee, arms } => {
+            diverges(scrutinee) || (!arms.is_empty() && arms.iter().all(|a| diverges(&a.body)))
+        }
+        HirExprKind::Let { value, .. } | HirExprKind::Set { value, .. } => diverges(value),
+        _ => false,
+    }
+}
+
 fn get_top(map: &BTreeMap<String, Vec<VarState>>, name: &str) -> Option<VarState> {
     map.get(name).and_then(|s| s.last().copied())
 }
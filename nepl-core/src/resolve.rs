@@ -8,7 +8,7 @@ extern crate alloc;
 use alloc::collections::BTreeMap;
 use alloc::string::String;
 
-use crate::ast::{EnumDef, FnAlias, FnDef, StructDef, Visibility};
+use crate::ast::{EnumDef, FnAlias, FnDef, NewtypeDef, StructDef, TypeAliasDef, Visibility};
 use crate::module_graph::{ExportEntry, ExportKind, ExportTable, ModuleGraph, ModuleId};
 use crate::ast::{ImportClause, ImportItem};
 use alloc::vec::Vec;
@@ -24,6 +24,8 @@ pub enum DefKind {
     Function,
     Struct,
     Enum,
+    TypeAlias,
+    Newtype,
 }
 
 #[derive(Debug, Clone)]
@@ -110,6 +112,34 @@ pub fn collect_defs(graph: &ModuleGraph) -> DefTable {
                         },
                     );
                 }
+                crate::ast::Stmt::TypeAlias(TypeAliasDef { name, vis, .. })
+                    if *vis == Visibility::Pub =>
+                {
+                    let id = DefId(next_id);
+                    next_id += 1;
+                    map.insert(
+                        name.name.clone(),
+                        DefInfo {
+                            id,
+                            kind: DefKind::TypeAlias,
+                            module: node.id,
+                        },
+                    );
+                }
+                crate::ast::Stmt::Newtype(NewtypeDef { name, vis, .. })
+                    if *vis == Visibility::Pub =>
+                {
+                    let id = DefId(next_id);
+                    next_id += 1;
+                    map.insert(
+                        name.name.clone(),
+                        DefInfo {
+                            id,
+                            kind: DefKind::Newtype,
+                            module: node.id,
+                        },
+                    );
+                }
                 _ => {}
             }
         }
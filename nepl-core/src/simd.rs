@@ -0,0 +1,108 @@
+//! Lane-typed WebAssembly SIMD intrinsics over the `v128` primitive.
+//!
+//! Every intrinsic works on four 32-bit lanes (`i32x4` / `f32x4`):
+//!
+//! - `i32x4_splat` / `f32x4_splat`: `(x) -> v128`
+//! - `i32x4_extract_lane` / `f32x4_extract_lane`: `(v, lane) -> i32 | f32`
+//! - `i32x4_replace_lane` / `f32x4_replace_lane`: `(v, lane, x) -> v128`
+//! - `i32x4_add` / `sub` / `mul`, `f32x4_add` / `sub` / `mul` / `div`: `(a, b) -> v128`
+//! - `i32x4_shuffle`: `(a, b, l0, l1, l2, l3) -> v128`, lanes 0..3 pick from `a`, 4..7 from `b`
+//!
+//! Lane indices must be integer literals. Memory access goes through
+//! `load<v128>` / `store<v128>`.
+
+extern crate alloc;
+
+use wasmparser::{Validator, WasmFeatures};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lane {
+    I32,
+    F32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimdOp {
+    Splat(Lane),
+    ExtractLane(Lane),
+    ReplaceLane(Lane),
+    Binary(Lane, BinOp),
+    Shuffle,
+}
+
+/// One operand of a SIMD intrinsic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    V128,
+    Scalar(Lane),
+    /// Integer literal in `0..limit`.
+    LaneIndex(u8),
+}
+
+pub const LANES: u8 = 4;
+
+pub fn lookup(name: &str) -> Option<SimdOp> {
+    let (lane, op) = if let Some(op) = name.strip_prefix("i32x4_") {
+        (Lane::I32, op)
+    } else if let Some(op) = name.strip_prefix("f32x4_") {
+        (Lane::F32, op)
+    } else {
+        return None;
+    };
+    Some(match (lane, op) {
+        (_, "splat") => SimdOp::Splat(lane),
+        (_, "extract_lane") => SimdOp::ExtractLane(lane),
+        (_, "replace_lane") => SimdOp::ReplaceLane(lane),
+        (_, "add") => SimdOp::Binary(lane, BinOp::Add),
+        (_, "sub") => SimdOp::Binary(lane, BinOp::Sub),
+        (_, "mul") => SimdOp::Binary(lane, BinOp::Mul),
+        (Lane::F32, "div") => SimdOp::Binary(lane, BinOp::Div),
+        (Lane::I32, "shuffle") => SimdOp::Shuffle,
+        _ => return None,
+    })
+}
+
+impl SimdOp {
+    pub fn operands(self) -> &'static [Operand] {
+        use Operand::*;
+        match self {
+            SimdOp::Splat(Lane::I32) => &[Scalar(Lane::I32)],
+            SimdOp::Splat(Lane::F32) => &[Scalar(Lane::F32)],
+            SimdOp::ExtractLane(_) => &[V128, LaneIndex(LANES)],
+            SimdOp::ReplaceLane(Lane::I32) => &[V128, LaneIndex(LANES), Scalar(Lane::I32)],
+            SimdOp::ReplaceLane(Lane::F32) => &[V128, LaneIndex(LANES), Scalar(Lane::F32)],
+            SimdOp::Binary(..) => &[V128, V128],
+            SimdOp::Shuffle => &[
+                V128,
+                V128,
+                LaneIndex(2 * LANES),
+                LaneIndex(2 * LANES),
+                LaneIndex(2 * LANES),
+                LaneIndex(2 * LANES),
+            ],
+        }
+    }
+
+    /// `None` means the result is a `v128`.
+    pub fn scalar_result(self) -> Option<Lane> {
+        match self {
+            SimdOp::ExtractLane(lane) => Some(lane),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a valid module needs the SIMD proposal (it only validates with SIMD enabled).
+pub fn module_uses_simd(wasm: &[u8]) -> bool {
+    let scalar = WasmFeatures::default() - WasmFeatures::SIMD - WasmFeatures::RELAXED_SIMD;
+    Validator::new_with_features(scalar).validate_all(wasm).is_err()
+        && Validator::new().validate_all(wasm).is_ok()
+}
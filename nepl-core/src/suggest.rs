@@ -0,0 +1,362 @@
+//! "Did you mean" suggestions for unresolved names and imports.
+//!
+//! - edit_distance / closest_matches: 候補集合から近い綴りを選ぶ
+//! - StdlibIndex: stdlib ツリーを走査し、モジュール一覧と各モジュールの定義表 (DefTable) を保持する
+//!
+//! loader の `#import` は取り込み先のトップレベル定義をすべて展開するため、
+//! ここでは `pub` に限らずトップレベルの fn/struct/enum を「そのモジュールが提供する名前」とみなす。
+
+extern crate alloc;
+extern crate std;
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use std::path::{Path, PathBuf};
+
+use crate::ast::{Module, Stmt};
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::lexer;
+use crate::loader::SourceMap;
+use crate::module_graph::ModuleId;
+use crate::parser;
+use crate::resolve::{DefId, DefInfo, DefKind, DefTable};
+use crate::span::FileId;
+
+/// Diagnostic code attached to "undefined identifier" errors so that
+/// front-ends can enrich them (for example with [`StdlibIndex::annotate`]).
+pub const UNDEFINED_IDENTIFIER: &str = "E0001";
+
+/// Maximum number of names listed in a single suggestion.
+pub const MAX_SUGGESTIONS: usize = 3;
+
+/// Edit distance between two strings, counted in chars.
+///
+/// Insertions, deletions, substitutions and swaps of adjacent chars
+/// (`stdoi` -> `stdio`) each cost 1.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.is_empty() {
+        return b.len();
+    }
+    if b.is_empty() {
+        return a.len();
+    }
+    let width = b.len() + 1;
+    let mut d: Vec<usize> = alloc::vec![0; (a.len() + 1) * width];
+    for i in 0..=a.len() {
+        d[i * width] = i;
+    }
+    for (j, cell) in d.iter_mut().take(width).enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut best = (d[(i - 1) * width + j] + 1)
+                .min(d[i * width + j - 1] + 1)
+                .min(d[(i - 1) * width + j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(d[(i - 2) * width + j - 2] + 1);
+            }
+            d[i * width + j] = best;
+        }
+    }
+    d[a.len() * width + b.len()]
+}
+
+/// Largest distance still considered a plausible typo for `name`.
+fn max_distance(name: &str) -> usize {
+    (name.chars().count() / 3).max(1)
+}
+
+/// Return up to `limit` candidates close to `target`, nearest first.
+///
+/// Exact matches are skipped (they are not typos), and ties are broken
+/// alphabetically so the output is deterministic.
+pub fn closest_matches<'a, I>(target: &str, candidates: I, limit: usize) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let threshold = max_distance(target);
+    let lowered = target.to_lowercase();
+    let mut scored: BTreeMap<(usize, String), ()> = BTreeMap::new();
+    for cand in candidates {
+        if cand == target || cand.is_empty() {
+            continue;
+        }
+        let mut dist = edit_distance(target, cand);
+        if dist > threshold && cand.to_lowercase() == lowered {
+            dist = 1;
+        }
+        if dist <= threshold {
+            scored.insert((dist, cand.to_string()), ());
+        }
+    }
+    let mut out: Vec<String> = Vec::new();
+    for ((_, name), _) in scored {
+        if !out.contains(&name) {
+            out.push(name);
+        }
+        if out.len() >= limit {
+            break;
+        }
+    }
+    out
+}
+
+/// Format a "did you mean" note for the given matches.
+pub fn did_you_mean(matches: &[String]) -> Option<String> {
+    match matches.len() {
+        0 => None,
+        1 => Some(format!("did you mean `{}`?", matches[0])),
+        _ => {
+            let list: Vec<String> = matches.iter().map(|m| format!("`{}`", m)).collect();
+            Some(format!("did you mean one of {}?", list.join(", ")))
+        }
+    }
+}
+
+/// Attach a "did you mean" note to `diag` when `name` has close candidates.
+pub fn with_suggestions<'a, I>(diag: Diagnostic, name: &str, candidates: I) -> Diagnostic
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let matches = closest_matches(name, candidates, MAX_SUGGESTIONS);
+    match did_you_mean(&matches) {
+        Some(note) => diag.with_note(note),
+        None => diag,
+    }
+}
+
+/// Index of the standard library tree used for import and name suggestions.
+#[derive(Debug, Clone)]
+pub struct StdlibIndex {
+    /// Module specs as written in `#import`, for example `std/stdio`.
+    modules: Vec<String>,
+    specs: BTreeMap<ModuleId, String>,
+    defs: DefTable,
+}
+
+impl StdlibIndex {
+    /// Build an index from `(module spec, source)` pairs.
+    ///
+    /// Files that fail to lex or parse are still listed as modules but
+    /// contribute no definitions.
+    pub fn from_sources<I>(sources: I) -> Self
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut modules = Vec::new();
+        let mut specs = BTreeMap::new();
+        let mut defs = DefTable::new();
+        let mut next_id: u32 = 0;
+        for (idx, (spec, src)) in sources.into_iter().enumerate() {
+            let mid = ModuleId(idx as u32);
+            if let Some(module) = parse_quiet(&src) {
+                defs.defs
+                    .insert(mid, collect_top_level_defs(&module, mid, &mut next_id));
+            }
+            specs.insert(mid, spec.clone());
+            modules.push(spec);
+        }
+        modules.sort();
+        modules.dedup();
+        Self {
+            modules,
+            specs,
+            defs,
+        }
+    }
+
+    /// Scan `root` recursively for `.nepl` files.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn scan(root: &Path) -> Self {
+        let mut sources = Vec::new();
+        for entry in walkdir::WalkDir::new(root)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            let path = entry.path();
+            if !entry.file_type().is_file()
+                || path.extension().and_then(|s| s.to_str()) != Some("nepl")
+            {
+                continue;
+            }
+            let Some(spec) = module_spec(root, path) else {
+                continue;
+            };
+            if let Ok(src) = std::fs::read_to_string(path) {
+                sources.push((spec, src));
+            }
+        }
+        Self::from_sources(sources)
+    }
+
+    /// Build an index from absolute paths under `root` (for VFS-backed loaders).
+    pub fn from_paths<I>(root: &Path, files: I) -> Self
+    where
+        I: IntoIterator<Item = (PathBuf, String)>,
+    {
+        Self::from_sources(
+            files
+                .into_iter()
+                .filter_map(|(path, src)| module_spec(root, &path).map(|spec| (spec, src))),
+        )
+    }
+
+    /// All module specs, sorted.
+    pub fn modules(&self) -> &[String] {
+        &self.modules
+    }
+
+    /// Definition table of the indexed modules.
+    pub fn defs(&self) -> &DefTable {
+        &self.defs
+    }
+
+    /// Module specs close to `spec` (for a failed `#import`).
+    pub fn suggest_modules(&self, spec: &str) -> Vec<String> {
+        let spec = spec.trim_end_matches(".nepl");
+        let mut out = closest_matches(
+            spec,
+            self.modules.iter().map(|m| m.as_str()),
+            MAX_SUGGESTIONS,
+        );
+        if out.is_empty() {
+            // 末尾のファイル名だけが合っているケース (`#import "stdio"` など)
+            let leaf = spec.rsplit('/').next().unwrap_or(spec);
+            for m in &self.modules {
+                let m_leaf = m.rsplit('/').next().unwrap_or(m);
+                if edit_distance(leaf, m_leaf) <= max_distance(leaf) && !out.contains(m) {
+                    out.push(m.clone());
+                }
+                if out.len() >= MAX_SUGGESTIONS {
+                    break;
+                }
+            }
+        }
+        out
+    }
+
+    /// Modules that define a function called `name`.
+    pub fn exporters_of(&self, name: &str) -> Vec<String> {
+        let mut out = Vec::new();
+        for (mid, map) in &self.defs.defs {
+            if let Some(info) = map.get(name) {
+                if info.kind == DefKind::Function {
+                    if let Some(spec) = self.specs.get(mid) {
+                        out.push(spec.clone());
+                    }
+                }
+            }
+        }
+        out.sort();
+        out.dedup();
+        out
+    }
+
+    /// Enrich "undefined identifier" diagnostics with the stdlib modules
+    /// that define the missing name.
+    pub fn annotate(&self, diags: &mut [Diagnostic], sm: &SourceMap) {
+        for d in diags.iter_mut() {
+            if d.severity != Severity::Error || d.code != Some(UNDEFINED_IDENTIFIER) {
+                continue;
+            }
+            let Some(src) = sm.get(d.primary.span.file_id) else {
+                continue;
+            };
+            let (start, end) = (d.primary.span.start as usize, d.primary.span.end as usize);
+            let Some(text) = src.get(start..end) else {
+                continue;
+            };
+            let name = text
+                .rsplit("::")
+                .next()
+                .unwrap_or(text)
+                .trim_start_matches('@');
+            let exporters = self.exporters_of(name);
+            if exporters.is_empty() {
+                continue;
+            }
+            let list: Vec<String> = exporters
+                .iter()
+                .take(MAX_SUGGESTIONS)
+                .map(|m| format!("#import \"{}\"", m))
+                .collect();
+            d.notes.push(format!(
+                "`{}` is defined in the standard library; try {}",
+                name,
+                list.join(" or ")
+            ));
+        }
+    }
+}
+
+/// `root/alloc/vec.nepl` -> `alloc/vec`
+fn module_spec(root: &Path, path: &Path) -> Option<String> {
+    let rel = path.strip_prefix(root).ok()?;
+    let mut parts = Vec::new();
+    for comp in rel.with_extension("").components() {
+        parts.push(comp.as_os_str().to_str()?.to_string());
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    Some(parts.join("/"))
+}
+
+fn parse_quiet(src: &str) -> Option<Module> {
+    let lex = lexer::lex(FileId(0), src);
+    if lex
+        .diagnostics
+        .iter()
+        .any(|d| d.severity == Severity::Error)
+    {
+        return None;
+    }
+    let parse = parser::parse_tokens(FileId(0), lex);
+    if parse
+        .diagnostics
+        .iter()
+        .any(|d| d.severity == Severity::Error)
+    {
+        return None;
+    }
+    parse.module
+}
+
+fn collect_top_level_defs(
+    module: &Module,
+    mid: ModuleId,
+    next_id: &mut u32,
+) -> BTreeMap<String, DefInfo> {
+    let mut map = BTreeMap::new();
+    for stmt in &module.root.items {
+        let (name, kind) = match stmt {
+            Stmt::FnDef(def) => (&def.name.name, DefKind::Function),
+            Stmt::FnAlias(alias) => (&alias.name.name, DefKind::Function),
+            Stmt::StructDef(def) => (&def.name.name, DefKind::Struct),
+            Stmt::EnumDef(def) => (&def.name.name, DefKind::Enum),
+            Stmt::TypeAlias(def) => (&def.name.name, DefKind::TypeAlias),
+            Stmt::Newtype(def) => (&def.name.name, DefKind::Newtype),
+            _ => continue,
+        };
+        if map.contains_key(name) {
+            continue;
+        }
+        map.insert(
+            name.clone(),
+            DefInfo {
+                id: DefId(*next_id),
+                kind,
+                module: mid,
+            },
+        );
+        *next_id += 1;
+    }
+    map
+}
@@ -12,9 +12,11 @@ use alloc::vec::Vec;
 use crate::ast::*;
 use crate::builtins::BuiltinKind;
 use crate::compiler::{BuildProfile, CompileTarget};
-use crate::diagnostic::Diagnostic;
+use crate::diagnostic::{Diagnostic, Severity};
 use crate::hir::*;
-use crate::span::Span;
+use crate::simd;
+use crate::span::{FileId, Span};
+use crate::suggest;
 use crate::types::{EnumVariantInfo, TypeCtx, TypeId, TypeKind};
 
 // Helper to gate verbose HIR dumps. Use `dump!(...)` for noisy debug output
@@ -53,6 +55,10 @@ fn print_diagnostics_summary(diags: &alloc::vec::Vec<crate::diagnostic::Diagnost
 #[derive(Debug)]
 pub struct TypeCheckResult {
     pub module: Option<HirModule>,
+    /// When there are errors, the HIR of everything that could be checked,
+    /// with error types where checking failed. For editor analysis only;
+    /// it must not be compiled.
+    pub partial: Option<HirModule>,
     pub diagnostics: Vec<Diagnostic>,
     pub types: TypeCtx,
 }
@@ -63,6 +69,22 @@ struct CheckedFunction {
     diagnostics: Vec<Diagnostic>,
 }
 
+/// A function with errors. Its HIR is kept when the body could be walked.
+#[derive(Debug)]
+struct FailedFunction {
+    function: Option<Box<HirFunction>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl From<Vec<Diagnostic>> for FailedFunction {
+    fn from(diagnostics: Vec<Diagnostic>) -> Self {
+        Self {
+            function: None,
+            diagnostics,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct EnumInfo {
     ty: TypeId,
@@ -76,6 +98,7 @@ struct StructInfo {
     type_params: Vec<TypeId>,
     fields: Vec<TypeId>,
     field_names: Vec<String>,
+    span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -87,11 +110,26 @@ struct TraitInfo {
     span: Span,
 }
 
+impl TraitInfo {
+    /// A fresh copy of a method signature; `Self` and the trait's type
+    /// parameters are generalized per call site.
+    fn instantiate_method(&self, ctx: &mut TypeCtx, sig: TypeId) -> TypeId {
+        let mut mapping = BTreeMap::new();
+        for tp in core::iter::once(self.self_ty).chain(self.type_params.iter().copied()) {
+            let fresh = ctx.fresh_var(None);
+            mapping.insert(ctx.resolve_id(tp), fresh);
+        }
+        ctx.substitute(sig, &mapping)
+    }
+}
+
 #[derive(Debug, Clone)]
 struct ImplInfo {
     trait_name: Option<String>,
+    type_params: Vec<TypeId>,
     target_ty: TypeId,
     methods: BTreeMap<String, (String, TypeId)>, // name -> (mangled_name, type)
+    span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -152,6 +190,121 @@ pub fn typecheck(
     let mut externs: Vec<HirExtern> = Vec::new();
     let mut seen_directive_spans: BTreeSet<(u32, u32, u32)> = BTreeSet::new();
     let mut instantiations: BTreeMap<String, Vec<Vec<TypeId>>> = BTreeMap::new();
+
+    // Type aliases and newtypes come first so that every signature below,
+    // `#extern` included, can name them.
+    let mut newtype_defs: Vec<&NewtypeDef> = Vec::new();
+    let mut pending_if: Option<bool> = None;
+    for item in &module.root.items {
+        if let Stmt::Directive(d) = item {
+            if let Some(allowed) = gate_allows(d, target, profile) {
+                pending_if = Some(allowed);
+                continue;
+            }
+        }
+        let allowed = pending_if.unwrap_or(true);
+        pending_if = None;
+        if !allowed {
+            continue;
+        }
+        match item {
+            Stmt::TypeAlias(a) => {
+                if ctx.lookup_alias(&a.name.name).is_some() {
+                    continue;
+                }
+                let taken = module.root.items.iter().any(|other| match other {
+                    Stmt::StructDef(s) => s.name.name == a.name.name,
+                    Stmt::EnumDef(e) => e.name.name == a.name.name,
+                    Stmt::Newtype(n) => n.name.name == a.name.name,
+                    _ => false,
+                });
+                if taken {
+                    diagnostics.push(Diagnostic::error(
+                        "name already used by another item",
+                        a.name.span,
+                    ));
+                    continue;
+                }
+                for p in &a.type_params {
+                    if !p.bounds.is_empty() {
+                        diagnostics.push(Diagnostic::error(
+                            "type alias parameter bounds are not supported",
+                            p.name.span,
+                        ));
+                    }
+                }
+                ctx.register_alias(
+                    a.name.name.clone(),
+                    a.type_params.iter().map(|p| p.name.name.clone()).collect(),
+                    a.target.clone(),
+                );
+            }
+            Stmt::Newtype(n) => newtype_defs.push(n),
+            _ => {}
+        }
+    }
+    for item in &module.root.items {
+        if let Stmt::TypeAlias(a) = item {
+            let mut visiting = Vec::new();
+            if ctx.lookup_alias(&a.name.name).is_some()
+                && alias_refers_to(&ctx, &a.target, &a.name.name, &mut visiting)
+            {
+                diagnostics.push(Diagnostic::error(
+                    format!("type alias '{}' refers to itself", a.name.name),
+                    a.name.span,
+                ));
+                ctx.remove_alias(&a.name.name);
+            }
+        }
+    }
+    for n in newtype_defs {
+        if let Some(existing) = ctx.lookup_named(&n.name.name) {
+            if !matches!(ctx.get(existing), TypeKind::Named(_)) {
+                if !matches!(ctx.get(existing), TypeKind::Newtype { .. }) {
+                    diagnostics.push(Diagnostic::error(
+                        "name already used by another item",
+                        n.name.span,
+                    ));
+                }
+                continue;
+            }
+        }
+        let inner = type_from_expr(&mut ctx, &mut label_env, &n.inner);
+        let ty = ctx.register_named(
+            n.name.name.clone(),
+            TypeKind::Newtype {
+                name: n.name.name.clone(),
+                inner,
+            },
+        );
+        // `Name v` wraps and `Name::inner v` unwraps; both are lowered to the
+        // argument itself in `apply_function`.
+        let ops = [
+            (n.name.name.clone(), inner, ty),
+            (format!("{}::inner", n.name.name), ty, inner),
+        ];
+        for (fname, param, result) in ops {
+            let func_ty = ctx.function(Vec::new(), vec![param], result, Effect::Pure);
+            env.insert_global(Binding {
+                name: fname.clone(),
+                ty: func_ty,
+                mutable: false,
+                no_shadow: false,
+                defined: true,
+                moved: false,
+                span: n.name.span,
+                kind: BindingKind::Func {
+                    symbol: fname,
+                    effect: Effect::Pure,
+                    arity: 1,
+                    builtin: None,
+                    type_param_bounds: Vec::new(),
+                    captures: Vec::new(),
+                },
+            });
+        }
+    }
+
     let mut apply_directive = |d: &Directive, allowed: bool| {
         if !allowed {
             return;
@@ -164,6 +317,7 @@ pub fn typecheck(
             Directive::Use { span, .. } => *span,
             Directive::IfTarget { span, .. } => *span,
             Directive::IfProfile { span, .. } => *span,
+            Directive::IfConst { span, .. } => *span,
             Directive::IndentWidth { span, .. } => *span,
             Directive::Include { span, .. } => *span,
             Directive::Prelude { span, .. } => *span,
@@ -196,10 +350,22 @@ pub fn typecheck(
             if let TypeKind::Function {
                 params,
                 result,
-                effect,
-                type_params: _,
+                effect: declared,
+                type_params,
             } = ctx.get(ty)
             {
+                // host import は副作用の有無を検査できないため、宣言の矢印によらず常に impure とする
+                if matches!(declared, Effect::Pure) {
+                    diagnostics.push(
+                        Diagnostic::warning(
+                            format!("extern import '{}' is treated as impure", func.name),
+                            *span,
+                        )
+                        .with_note("declare host imports with `*>`"),
+                    );
+                }
+                let effect = Effect::Impure;
+                let ty = ctx.function(type_params, params.clone(), result, effect);
                 env.insert_global(Binding {
                     name: func.name.clone(),
                     ty,
@@ -461,6 +627,7 @@ pub fn typecheck(
                         type_params: tps,
                         fields: fs,
                         field_names: f_names,
+                        span: s.name.span,
                     },
                 );
             }
@@ -468,12 +635,6 @@ pub fn typecheck(
                 let mut f_labels = LabelEnv::new();
                 let (tps, _bounds_vec, _bounds_map) =
                     collect_type_params(&mut ctx, &mut f_labels, &t.type_params, &traits, &mut diagnostics);
-                if !t.type_params.is_empty() {
-                    diagnostics.push(Diagnostic::error(
-                        "trait type parameters are not supported yet",
-                        t.name.span,
-                    ));
-                }
                 let self_ty = ctx.fresh_var(Some(String::from("Self")));
                 f_labels.insert(String::from("Self"), self_ty);
                 let mut methods = BTreeMap::new();
@@ -561,13 +722,6 @@ pub fn typecheck(
                 ));
                 continue;
             }
-            if !i.type_params.is_empty() {
-                diagnostics.push(Diagnostic::error(
-                    "impl type parameters are not supported yet",
-                    i.span,
-                ));
-                continue;
-            }
             let mut f_labels = LabelEnv::new();
             let (tps, _bounds_vec, _bounds_map) =
                 collect_type_params(&mut ctx, &mut f_labels, &i.type_params, &traits, &mut diagnostics);
@@ -583,7 +737,7 @@ pub fn typecheck(
                     continue;
                 }
             }
-            if type_contains_unbound_var(&ctx, target_ty) {
+            if impl_target_is_open(&mut ctx, target_ty, &tps) {
                 diagnostics.push(Diagnostic::error(
                     "impl target type must be concrete",
                     i.target_ty.span(),
@@ -598,8 +752,10 @@ pub fn typecheck(
             }
             impls.push(ImplInfo {
                 trait_name,
+                type_params: tps,
                 target_ty,
                 methods,
+                span: i.span,
             });
         }
     }
@@ -631,6 +787,7 @@ pub fn typecheck(
         }
     }
 
+    let inferred_effects = infer_top_level_effects(&module.root.items, &env, target, profile);
     let mut pending_if: Option<bool> = None;
     for item in &module.root.items {
         if let Stmt::Directive(d) = item {
@@ -666,6 +823,9 @@ pub fn typecheck(
                     ty = ctx.function(tps, params, result, effect);
                 }
             }
+            if let Some(effect) = inferred_effects.get(&(f.name.span.file_id.0, f.name.span.start)) {
+                ty = with_effect(&mut ctx, ty, *effect);
+            }
 
             if let TypeKind::Function {
                 type_params: _,
@@ -740,7 +900,14 @@ pub fn typecheck(
                     continue;
                 }
                 env.remove_duplicate_func(&f.name.name, ty, &ctx);
-                let symbol = if type_contains_unbound_var(&ctx, ty) {
+                // A second generic overload keeps a signature-mangled symbol so that
+                // monomorphization does not merge it with the first one.
+                let symbol = if type_contains_unbound_var(&ctx, ty)
+                    && !env
+                        .lookup_all_callables(&f.name.name)
+                        .iter()
+                        .any(|b| type_contains_unbound_var(&ctx, b.ty))
+                {
                     f.name.name.clone()
                 } else {
                     mangle_function_symbol(&f.name.name, ty, &ctx)
@@ -886,7 +1053,53 @@ pub fn typecheck(
         }
     }
 
+    // Statics are registered once every function is known; their
+    // initializers are checked after the function bodies.
+    let mut static_defs: Vec<(&StaticDef, TypeId)> = Vec::new();
+    let mut pending_if = None;
+    for item in &module.root.items {
+        if let Stmt::Directive(d) = item {
+            if let Some(allowed) = gate_allows(d, target, profile) {
+                pending_if = Some(allowed);
+                continue;
+            }
+        }
+        let allowed = pending_if.unwrap_or(true);
+        pending_if = None;
+        if !allowed {
+            continue;
+        }
+        if let Stmt::Static(s) = item {
+            if !env.lookup_all_any_defined(&s.name.name).is_empty()
+                || enums.contains_key(&s.name.name)
+                || structs.contains_key(&s.name.name)
+            {
+                diagnostics.push(Diagnostic::error(
+                    "name already used by another item",
+                    s.name.span,
+                ));
+                continue;
+            }
+            let ty = type_from_expr(&mut ctx, &mut label_env, &s.ty);
+            env.insert_global(Binding {
+                name: s.name.name.clone(),
+                ty,
+                mutable: s.mutable,
+                no_shadow: false,
+                defined: true,
+                moved: false,
+                span: s.name.span,
+                kind: BindingKind::Static {
+                    index: static_defs.len() as u32,
+                },
+            });
+            static_defs.push((s, ty));
+        }
+    }
+
     let mut functions = Vec::new();
+    let mut consts = Vec::new();
+    let mut exports: Vec<HirExport> = Vec::new();
     let mut pending_if = None;
     for item in &module.root.items {
         if let Stmt::Directive(d) = item {
@@ -916,7 +1129,12 @@ pub fn typecheck(
                         let tv = ctx.fresh_var(Some(tp.name.name.clone()));
                         tmp_labels.insert(tp.name.name.clone(), tv);
                     }
-                    let sig_ty = type_from_expr(&mut ctx, &mut tmp_labels, &f.signature);
+                    let mut sig_ty = type_from_expr(&mut ctx, &mut tmp_labels, &f.signature);
+                    if let Some(effect) =
+                        inferred_effects.get(&(f.name.span.file_id.0, f.name.span.start))
+                    {
+                        sig_ty = with_effect(&mut ctx, sig_ty, *effect);
+                    }
                     let sig_key = function_signature_string(&ctx, sig_ty);
                     let mut matched: Option<TypeId> = None;
                     for binding in funcs.drain(..) {
@@ -968,11 +1186,115 @@ pub fn typecheck(
             ) {
                 Ok(checked) => {
                     diagnostics.extend(checked.diagnostics);
+                    match f.constness {
+                        Constness::ConstItem => consts.push(HirConst {
+                            name: f.name.name.clone(),
+                            func: checked.function.name.clone(),
+                            ty: checked.function.result,
+                            span: f.name.span,
+                        }),
+                        Constness::ConstFn if checked.function.effect == Effect::Impure => {
+                            diagnostics.push(
+                                Diagnostic::error(
+                                    format!("const fn '{}' must be pure", f.name.name),
+                                    f.name.span,
+                                )
+                                .with_note("const fns are evaluated at compile time and cannot have side effects"),
+                            );
+                        }
+                        _ => {}
+                    }
+                    if let Some(export) = &f.export {
+                        if let Some(d) =
+                            check_export(&ctx, f, &checked.function, export, entry.as_deref(), &exports)
+                        {
+                            diagnostics.push(d);
+                        } else {
+                            exports.push(HirExport {
+                                name: export.name.clone(),
+                                func: checked.function.name.clone(),
+                                span: export.span,
+                            });
+                        }
+                    }
                     functions.push(checked.function);
                     functions.extend(nested_functions);
                 }
-                Err(mut diags) => diagnostics.append(&mut diags),
+                Err(mut failed) => {
+                    diagnostics.append(&mut failed.diagnostics);
+                    functions.extend(failed.function.map(|f| *f));
+                    functions.extend(nested_functions);
+                }
+            }
+        }
+    }
+
+    // A static initializer is checked as a zero-arg pure function, so it can
+    // only read consts and immutable statics.
+    let mut globals = Vec::new();
+    for (s, ty) in static_defs {
+        let init = FnDef {
+            vis: Visibility::Private,
+            name: Ident {
+                name: format!("__static_init_{}", s.name.name),
+                span: s.name.span,
+            },
+            no_shadow: false,
+            type_params: Vec::new(),
+            signature: TypeExpr::Function {
+                params: Vec::new(),
+                result: Box::new(s.ty.clone()),
+                effect: Effect::Pure,
+            },
+            params: Vec::new(),
+            body: FnBody::Parsed(Block {
+                items: vec![Stmt::Expr(s.value.clone())],
+                span: s.value.span,
+            }),
+            effect_inferred: false,
+            constness: Constness::ConstItem,
+            export: None,
+        };
+        let init_ty = ctx.function(Vec::new(), Vec::new(), ty, Effect::Pure);
+        let mut nested_functions = Vec::new();
+        match check_function(
+            &init,
+            init_ty,
+            false,
+            target,
+            profile,
+            &[],
+            &mut ctx,
+            &mut env,
+            &mut label_env,
+            &mut strings,
+            &enums,
+            &structs,
+            &mut instantiations,
+            BTreeMap::new(),
+            &traits,
+            &impls,
+            &mut nested_functions,
+        ) {
+            Ok(checked) => {
+                diagnostics.extend(checked.diagnostics.into_iter().map(retarget_static_init_note));
+                globals.push(HirGlobal {
+                    name: s.name.name.clone(),
+                    func: checked.function.name.clone(),
+                    ty,
+                    mutable: s.mutable,
+                    init: None,
+                    span: s.name.span,
+                });
+                functions.push(checked.function);
+                functions.extend(nested_functions);
             }
+            Err(failed) => diagnostics.extend(
+                failed
+                    .diagnostics
+                    .into_iter()
+                    .map(retarget_static_init_note),
+            ),
         }
     }
 
@@ -1021,28 +1343,45 @@ pub fn typecheck(
                     continue;
                 }
             };
-            if !i.type_params.is_empty() {
-                diagnostics.push(Diagnostic::error(
-                    "impl type parameters are not supported yet",
-                    i.span,
-                ));
-                continue;
-            }
 
             let mut impl_methods = Vec::new();
             let mut f_labels = LabelEnv::new();
-            let (tps, _bounds_vec, _bounds_map) =
+            let (tps, _bounds_vec, bounds_map) =
                 collect_type_params(&mut ctx, &mut f_labels, &i.type_params, &traits, &mut diagnostics);
             let target_ty = type_from_expr(&mut ctx, &mut f_labels, &i.target_ty);
-            if type_contains_unbound_var(&ctx, target_ty) {
+            if impl_target_is_open(&mut ctx, target_ty, &tps) {
                 diagnostics.push(Diagnostic::error(
                     "impl target type must be concrete",
                     i.target_ty.span(),
                 ));
                 continue;
             }
+            if i.trait_args.len() != trait_info.type_params.len() {
+                diagnostics.push(Diagnostic::error(
+                    format!(
+                        "trait '{}' expects {} type arguments, found {}",
+                        trait_name,
+                        trait_info.type_params.len(),
+                        i.trait_args.len()
+                    ),
+                    i.span,
+                ));
+                continue;
+            }
+            let mut mapping = BTreeMap::new();
+            mapping.insert(ctx.resolve_id(trait_info.self_ty), ctx.resolve_id(target_ty));
+            for (tp, arg) in trait_info.type_params.iter().zip(&i.trait_args) {
+                let arg_ty = type_from_expr(&mut ctx, &mut f_labels, arg);
+                mapping.insert(ctx.resolve_id(*tp), arg_ty);
+            }
             f_labels.insert(String::from("Self"), target_ty);
             let prev_self = label_env.insert(String::from("Self"), target_ty);
+            let prev_labels: Vec<_> = i
+                .type_params
+                .iter()
+                .zip(&tps)
+                .map(|(p, id)| (p.name.name.clone(), label_env.insert(p.name.name.clone(), *id)))
+                .collect();
 
             let mut seen_methods = BTreeSet::new();
             for m in &i.methods {
@@ -1070,9 +1409,7 @@ pub fn typecheck(
                         continue;
                     }
                 };
-                let mut mapping = BTreeMap::new();
-                mapping.insert(ctx.resolve_id(trait_info.self_ty), ctx.resolve_id(target_ty));
-                let expected_sig = ctx.substitute(trait_sig, &mapping);
+                let mut expected_sig = ctx.substitute(trait_sig, &mapping);
                 let actual_sig = type_from_expr(&mut ctx, &mut f_labels, &m.signature);
                 if !type_signature_matches(&ctx, expected_sig, actual_sig) {
                     diagnostics.push(Diagnostic::error(
@@ -1081,29 +1418,41 @@ pub fn typecheck(
                     ));
                     continue;
                 }
-                let mut nested_functions = Vec::new();
-                let mut checked = match check_function(
-                    m,
-                    expected_sig,
-                    false,
-                    target,
-                    profile,
-                    &[],
-                    &mut ctx,
-                    &mut env,
-                    &mut label_env,
+                // methods of a generic impl are generic over the impl's parameters
+                if let TypeKind::Function {
+                    params,
+                    result,
+                    effect,
+                    ..
+                } = ctx.get(expected_sig)
+                {
+                    if !tps.is_empty() {
+                        expected_sig = ctx.function(tps.clone(), params, result, effect);
+                    }
+                }
+                let mut nested_functions = Vec::new();
+                let checked = match check_function(
+                    m,
+                    expected_sig,
+                    false,
+                    target,
+                    profile,
+                    &[],
+                    &mut ctx,
+                    &mut env,
+                    &mut label_env,
                     &mut strings,
                     &enums,
                     &structs,
                     &mut instantiations,
-                    BTreeMap::new(),
+                    bounds_map.clone(),
                     &traits,
                     &impls,
                     &mut nested_functions,
                 ) {
                     Ok(checked) => checked,
-                    Err(mut diags) => {
-                        diagnostics.append(&mut diags);
+                    Err(mut failed) => {
+                        diagnostics.append(&mut failed.diagnostics);
                         continue;
                     }
                 };
@@ -1128,6 +1477,12 @@ pub fn typecheck(
                 }
             }
 
+            for (name, prev) in prev_labels.into_iter().rev() {
+                match prev {
+                    Some(prev) => label_env.insert(name, prev),
+                    None => label_env.remove(&name),
+                };
+            }
             if let Some(prev) = prev_self {
                 label_env.insert(String::from("Self"), prev);
             } else {
@@ -1172,19 +1527,26 @@ pub fn typecheck(
         print_diagnostics_summary(&diagnostics);
     }
 
+    let hir = HirModule {
+        functions,
+        entry: resolved_entry,
+        externs,
+        string_literals: strings.into_vec(),
+        traits: final_traits,
+        impls: final_impls,
+        consts,
+        statics: Vec::new(),
+        globals,
+        exports,
+    };
+    let (module, partial) = if has_error {
+        (None, Some(hir))
+    } else {
+        (Some(hir), None)
+    };
     TypeCheckResult {
-        module: if has_error {
-            None
-        } else {
-            Some(HirModule {
-                functions,
-                entry: resolved_entry,
-                externs,
-                string_literals: strings.into_vec(),
-                traits: final_traits,
-                impls: final_impls,
-            })
-        },
+        module,
+        partial,
         diagnostics,
         types: ctx,
     }
@@ -1212,7 +1574,7 @@ fn check_function(
     traits: &BTreeMap<String, TraitInfo>,
     impls: &Vec<ImplInfo>,
     generated_functions: &mut Vec<HirFunction>,
-) -> Result<CheckedFunction, Vec<Diagnostic>> {
+) -> Result<CheckedFunction, FailedFunction> {
     let mut diags = Vec::new();
     let (params_ty, result_ty, effect) = match ctx.get(func_ty) {
         TypeKind::Function {
@@ -1226,7 +1588,7 @@ fn check_function(
                 "function signature must be a function type",
                 f.name.span,
             ));
-            return Err(diags);
+            return Err(diags.into());
         }
     };
     if params_ty.len() != captured_params.len() + f.params.len() {
@@ -1234,7 +1596,7 @@ fn check_function(
             "parameter count mismatch with signature",
             f.name.span,
         ));
-        return Err(diags);
+        return Err(diags.into());
     }
 
     env.push_scope();
@@ -1273,6 +1635,8 @@ fn check_function(
             string_table: strings,
             diagnostics: Vec::new(),
             current_effect: if is_entry { Effect::Impure } else { effect },
+            current_result: result_ty,
+            loop_labels: BTreeMap::new(),
             enums,
             structs,
             instantiations,
@@ -1297,10 +1661,13 @@ fn check_function(
                                     f.name.span,
                                 ));
                             }
+                            for line in &blk.lines {
+                                check_loop_exits(&line.expr, &mut Vec::new(), &mut checker.diagnostics);
+                            }
                             HirBody::Block(blk)
                         }
                         None => {
-                            return Err(checker.diagnostics);
+                            return Err(checker.diagnostics.into());
                         }
                     }
                 }
@@ -1308,6 +1675,17 @@ fn check_function(
             FnBody::Wasm(wb) => HirBody::Wasm(wb.clone()),
             FnBody::LlvmIr(lb) => HirBody::LlvmIr(lb.clone()),
         };
+        if matches!(checker.current_effect, Effect::Pure) {
+            if let Some((op, span)) = raw_body_side_effect(&body_res) {
+                checker.diagnostics.push(
+                    Diagnostic::error(
+                        format!("pure function cannot use `{}` in a raw body", op),
+                        span,
+                    )
+                    .with_note(MARK_IMPURE_NOTE),
+                );
+            }
+        }
         (body_res, checker.diagnostics)
     };
 
@@ -1356,7 +1734,10 @@ fn check_function(
             span: f.name.span,
         };
     if has_error {
-        Err(diag_out)
+        Err(FailedFunction {
+            function: Some(Box::new(function)),
+            diagnostics: diag_out,
+        })
     } else {
         Ok(CheckedFunction {
             function,
@@ -1376,6 +1757,10 @@ struct BlockChecker<'a> {
     string_table: &'a mut StringTable,
     diagnostics: Vec<Diagnostic>,
     current_effect: Effect,
+    /// Result type of the function being checked (target of `return` and `?`).
+    current_result: TypeId,
+    /// `<.label>` of each labeled `while`, keyed by the keyword's span start.
+    loop_labels: BTreeMap<u32, String>,
     enums: &'a BTreeMap<String, EnumInfo>,
     structs: &'a BTreeMap<String, StructInfo>,
     instantiations: &'a mut BTreeMap<String, Vec<Vec<TypeId>>>, // new
@@ -1470,6 +1855,10 @@ impl<'a> BlockChecker<'a> {
                         Self::collect_bound_names_from_block(&arm.body, out);
                     }
                 }
+                PrefixItem::For(f, _) => {
+                    out.insert(f.var.name.clone());
+                    Self::collect_bound_names_from_block(&f.body, out);
+                }
                 _ => {}
             }
         }
@@ -1504,6 +1893,11 @@ impl<'a> BlockChecker<'a> {
                         Self::collect_ref_names_from_block(&arm.body, out);
                     }
                 }
+                PrefixItem::For(f, _) => {
+                    out.insert(String::from("next"));
+                    Self::collect_ref_names_from_prefix(&f.iter, out);
+                    Self::collect_ref_names_from_block(&f.body, out);
+                }
                 PrefixItem::Tuple(items, _) => {
                     for it in items {
                         Self::collect_ref_names_from_prefix(it, out);
@@ -1529,6 +1923,35 @@ impl<'a> BlockChecker<'a> {
         }
     }
 
+    /// シグネチャ省略の入れ子関数・関数リテラルの effect を、外側スコープの束縛から推論する。
+    fn infer_nested_effect(&self, f: &FnDef) -> Effect {
+        let is_impure = |name: &str| {
+            let callables = self.env.lookup_all_callables(name);
+            if !callables.is_empty() {
+                return callables.iter().all(|b| {
+                    matches!(
+                        &b.kind,
+                        BindingKind::Func {
+                            effect: Effect::Impure,
+                            ..
+                        }
+                    )
+                });
+            }
+            match self.env.lookup_value(name) {
+                Some(b) => matches!(
+                    self.ctx.get(self.ctx.resolve_id(b.ty)),
+                    TypeKind::Function {
+                        effect: Effect::Impure,
+                        ..
+                    }
+                ),
+                None => false,
+            }
+        };
+        infer_fn_effect(f, &is_impure)
+    }
+
     fn collect_nested_fn_captures(&self, f: &FnDef) -> Vec<(String, TypeId)> {
         let FnBody::Parsed(body) = &f.body else {
             return Vec::new();
@@ -1634,13 +2057,129 @@ impl<'a> BlockChecker<'a> {
         if !self.is_concrete_type(ty) {
             return self.type_param_has_bound(ty, trait_name);
         }
+        self.find_impl(trait_name, ty).is_some()
+    }
+
+    /// The impl of `trait_name` whose target is `ty`; generic impls match by
+    /// unification.
+    fn find_impl(&self, trait_name: &str, ty: TypeId) -> Option<&'a ImplInfo> {
+        let impls: &'a Vec<ImplInfo> = self.impls;
         let ty_name = self.ctx.type_to_string(self.ctx.resolve_id(ty));
-        self.impls.iter().any(|imp| {
-            imp.trait_name.as_deref() == Some(trait_name)
-                && self.ctx.type_to_string(self.ctx.resolve_id(imp.target_ty)) == ty_name
+        let mut candidates = impls
+            .iter()
+            .filter(|imp| imp.trait_name.as_deref() == Some(trait_name));
+        candidates.clone().find(|imp| {
+            self.ctx.type_to_string(self.ctx.resolve_id(imp.target_ty)) == ty_name
+        })
+        .or_else(|| {
+            candidates.find(|imp| {
+                !imp.type_params.is_empty() && {
+                    let mut tmp = self.ctx.clone();
+                    let target = instantiate_impl_type(&mut tmp, imp, imp.target_ty);
+                    tmp.unify(target, ty).is_ok()
+                }
+            })
         })
     }
 
+    /// The `Self` type of a trait call: the receiver's type, seen through
+    /// the reference when the method takes `&Self`.
+    fn trait_receiver(&self, trait_name: &str, method: &str, receiver: &StackEntry) -> TypeId {
+        let ty = self.ctx.resolve_id(receiver.ty);
+        let Some(sig) = self.traits.get(trait_name).and_then(|t| t.methods.get(method)) else {
+            return ty;
+        };
+        let first = match self.ctx.get(*sig) {
+            TypeKind::Function { params, .. } => params.first().copied(),
+            _ => None,
+        };
+        let by_ref = first.is_some_and(|p| {
+            matches!(self.ctx.get(self.ctx.resolve_id(p)), TypeKind::Reference(..))
+        });
+        match self.ctx.get(ty) {
+            TypeKind::Reference(inner, _) if by_ref => self.ctx.resolve_id(inner),
+            _ => ty,
+        }
+    }
+
+    /// Pins the rest of a trait call's signature (the trait's type arguments
+    /// among them) to the impl selected by `self_ty`.
+    fn refine_trait_call(
+        &mut self,
+        trait_name: &str,
+        method: &str,
+        self_ty: TypeId,
+        params: &[TypeId],
+        result: TypeId,
+    ) {
+        if !self.is_concrete_type(self_ty) {
+            return;
+        }
+        let Some(imp) = self.find_impl(trait_name, self_ty) else {
+            return;
+        };
+        let Some((_, sig)) = imp.methods.get(method) else {
+            return;
+        };
+        let target = instantiate_impl_type(self.ctx, imp, imp.target_ty);
+        let sig = instantiate_impl_type(self.ctx, imp, *sig);
+        let _ = self.ctx.unify(target, self_ty);
+        if let TypeKind::Function {
+            params: impl_params,
+            result: impl_result,
+            ..
+        } = self.ctx.get(sig)
+        {
+            for (p, q) in impl_params.iter().zip(params) {
+                let _ = self.ctx.unify(*p, *q);
+            }
+            let _ = self.ctx.unify(impl_result, result);
+        }
+    }
+
+    /// Checks the operands of a `simd` intrinsic; lane indices must be literals in range.
+    fn check_simd_args(&mut self, op: simd::SimdOp, name: &str, args: &[HirExpr], sp: Span) {
+        let operands = op.operands();
+        if args.len() != operands.len() {
+            self.diagnostics.push(Diagnostic::error(
+                format!("intrinsic '{}' expects {} arguments", name, operands.len()),
+                sp,
+            ));
+            return;
+        }
+        for (arg, operand) in args.iter().zip(operands) {
+            let expected = match operand {
+                simd::Operand::V128 => v128_type(self.ctx),
+                simd::Operand::Scalar(simd::Lane::I32) => self.ctx.i32(),
+                simd::Operand::Scalar(simd::Lane::F32) => self.ctx.f32(),
+                simd::Operand::LaneIndex(limit) => {
+                    match arg.kind {
+                        HirExprKind::LiteralI32(v) if (0..*limit as i32).contains(&v) => {}
+                        _ => self.diagnostics.push(Diagnostic::error(
+                            format!(
+                                "lane index of '{}' must be an integer literal in 0..{}",
+                                name, limit
+                            ),
+                            arg.span,
+                        )),
+                    }
+                    continue;
+                }
+            };
+            if self.ctx.unify(arg.ty, expected).is_err() {
+                self.diagnostics.push(Diagnostic::error(
+                    format!(
+                        "intrinsic argument type mismatch (expected {})",
+                        self.ctx.type_to_display(expected)
+                    ),
+                    arg.span,
+                ));
+            }
+        }
+    }
+
+    /// A failed lookup is reported here and yields the error type, so the access does not
+    /// cascade into further diagnostics.
     fn resolve_field_access(
         &mut self,
         base_ty: TypeId,
@@ -1649,6 +2188,7 @@ impl<'a> BlockChecker<'a> {
     ) -> Option<(TypeId, usize)> {
         let resolved_ty = self.ctx.resolve(base_ty);
         match self.ctx.get(resolved_ty) {
+            TypeKind::Error => Some((self.ctx.error(), 0)),
             TypeKind::Struct {
                 fields,
                 field_names,
@@ -1662,18 +2202,19 @@ impl<'a> BlockChecker<'a> {
                             format!("struct index out of bounds: {}", i),
                             span,
                         ));
-                        None
+                        Some((self.ctx.error(), 0))
                     }
                 }
                 FieldIdx::Name(name) => {
                     if let Some(i) = field_names.iter().position(|n| *n == name) {
                         Some((fields[i], composite_field_offset_bytes(self.ctx, &fields, i)))
                     } else {
-                        self.diagnostics.push(Diagnostic::error(
-                            format!("struct has no field {}", name),
-                            span,
+                        self.diagnostics.push(suggest::with_suggestions(
+                            Diagnostic::error(format!("struct has no field {}", name), span),
+                            &name,
+                            field_names.iter().map(|n| n.as_str()),
                         ));
-                        None
+                        Some((self.ctx.error(), 0))
                     }
                 }
             },
@@ -1686,7 +2227,7 @@ impl<'a> BlockChecker<'a> {
                             format!("tuple index out of bounds: {}", i),
                             span,
                         ));
-                        None
+                        Some((self.ctx.error(), 0))
                     }
                 }
                 FieldIdx::Name(name) => {
@@ -1698,14 +2239,14 @@ impl<'a> BlockChecker<'a> {
                                 format!("tuple index out of bounds: {}", i),
                                 span,
                             ));
-                            None
+                            Some((self.ctx.error(), 0))
                         }
                     } else {
                         self.diagnostics.push(Diagnostic::error(
                             format!("invalid tuple field access: {}", name),
                             span,
                         ));
-                        None
+                        Some((self.ctx.error(), 0))
                     }
                 }
             },
@@ -1738,7 +2279,7 @@ impl<'a> BlockChecker<'a> {
                                         format!("generic struct index out of bounds: {}", i),
                                         span,
                                     ));
-                                    None
+                                    Some((self.ctx.error(), 0))
                                 }
                             }
                             FieldIdx::Name(name) => {
@@ -1748,11 +2289,15 @@ impl<'a> BlockChecker<'a> {
                                         composite_field_offset_bytes(self.ctx, &substituted_fields, i),
                                     ))
                                 } else {
-                                    self.diagnostics.push(Diagnostic::error(
-                                        format!("generic struct has no field {}", name),
-                                        span,
+                                    self.diagnostics.push(suggest::with_suggestions(
+                                        Diagnostic::error(
+                                            format!("generic struct has no field {}", name),
+                                            span,
+                                        ),
+                                        &name,
+                                        field_names.iter().map(|n| n.as_str()),
                                     ));
-                                    None
+                                    Some((self.ctx.error(), 0))
                                 }
                             }
                         }
@@ -1760,7 +2305,7 @@ impl<'a> BlockChecker<'a> {
                     _ => {
                         self.diagnostics
                             .push(Diagnostic::error("cannot access field on this type", span));
-                        None
+                        Some((self.ctx.error(), 0))
                     }
                 }
             }
@@ -1769,7 +2314,7 @@ impl<'a> BlockChecker<'a> {
                     "cannot access field on non-composite type",
                     span,
                 ));
-                None
+                Some((self.ctx.error(), 0))
             }
         }
     }
@@ -1781,6 +2326,7 @@ impl<'a> BlockChecker<'a> {
         new_scope: bool,
     ) -> Option<(HirBlock, Option<TypeId>)> {
         let old_effect = self.current_effect;
+        let mut nested_effects: BTreeMap<(u32, u32), Effect> = BTreeMap::new();
 
         if new_scope {
             self.env.push_scope();
@@ -1848,7 +2394,12 @@ impl<'a> BlockChecker<'a> {
                     ));
                     continue;
                 }
-                let base_ty = type_from_expr(self.ctx, self.labels, &f.signature);
+                let mut base_ty = type_from_expr(self.ctx, self.labels, &f.signature);
+                if f.effect_inferred {
+                    let effect = self.infer_nested_effect(f);
+                    nested_effects.insert((f.name.span.file_id.0, f.name.span.start), effect);
+                    base_ty = with_effect(self.ctx, base_ty, effect);
+                }
                 let captures = self.collect_nested_fn_captures(f);
                 let mut ty = base_ty;
                 if let TypeKind::Function {
@@ -1951,7 +2502,7 @@ impl<'a> BlockChecker<'a> {
         let last_expr_idx = block
             .items
             .iter()
-            .rposition(|s| matches!(s, Stmt::Expr(_) | Stmt::ExprSemi(_, _)));
+            .rposition(|s| matches!(s, Stmt::Expr(_) | Stmt::ExprSemi(_, _) | Stmt::Error(_)));
 
         let mut pending_if: Option<bool> = None;
         for (idx, stmt) in block.items.iter().enumerate() {
@@ -1976,6 +2527,7 @@ impl<'a> BlockChecker<'a> {
 
             match stmt {
                 Stmt::Expr(expr) | Stmt::ExprSemi(expr, _) => {
+                    let reported = self.diagnostics.len();
                     match self.check_prefix(expr, base_depth, &mut stack) {
                         Some((typed, dropped_from_prefix)) => {
                             let is_last_expr = Some(idx) == last_expr_idx;
@@ -2024,12 +2576,34 @@ impl<'a> BlockChecker<'a> {
                                 drop_result,
                             });
                         }
+                        // the error has been reported; a failed last line still
+                        // gives the block a value so its users are checked
+                        None if Some(idx) == last_expr_idx
+                            && matches!(stmt, Stmt::Expr(_))
+                            && self.has_errors_since(reported) =>
+                        {
+                            stack.truncate(base_depth);
+                            stack.push(self.error_entry(expr.span));
+                        }
                         None => {}
                     }
                 }
                 Stmt::Directive(_) => {}
                 Stmt::FnAlias(_) => {}
+                // already reported by the parser
+                Stmt::Error(span) => {
+                    if Some(idx) == last_expr_idx {
+                        stack.truncate(base_depth);
+                        stack.push(self.error_entry(*span));
+                    }
+                }
                 Stmt::FnDef(f) => {
+                    if let Some(export) = &f.export {
+                        self.diagnostics.push(Diagnostic::error(
+                            "`#export` is only allowed on top-level functions",
+                            export.span,
+                        ));
+                    }
                     let (f_ty, captures) = {
                         let funcs: Vec<&Binding> = self.env.lookup_all_callables(&f.name.name);
                         if funcs.is_empty() {
@@ -2047,7 +2621,12 @@ impl<'a> BlockChecker<'a> {
                                 let tv = self.ctx.fresh_var(Some(tp.name.name.clone()));
                                 tmp_labels.insert(tp.name.name.clone(), tv);
                             }
-                            let sig_ty = type_from_expr(self.ctx, &mut tmp_labels, &f.signature);
+                            let mut sig_ty = type_from_expr(self.ctx, &mut tmp_labels, &f.signature);
+                            if let Some(effect) =
+                                nested_effects.get(&(f.name.span.file_id.0, f.name.span.start))
+                            {
+                                sig_ty = with_effect(self.ctx, sig_ty, *effect);
+                            }
                             let sig_key = function_signature_string(self.ctx, sig_ty);
                             let mut matched: Option<TypeId> = None;
                             for binding in &funcs {
@@ -2111,7 +2690,10 @@ impl<'a> BlockChecker<'a> {
                             self.diagnostics.extend(checked.diagnostics);
                             self.generated_functions.push(checked.function);
                         }
-                        Err(mut diags) => self.diagnostics.append(&mut diags),
+                        Err(mut failed) => {
+                            self.diagnostics.append(&mut failed.diagnostics);
+                            self.generated_functions.extend(failed.function.map(|f| *f));
+                        }
                     }
                 }
                 Stmt::StructDef(_) => {}
@@ -2129,6 +2711,18 @@ impl<'a> BlockChecker<'a> {
                     ));
                 }
                 Stmt::Trait(_) | Stmt::Impl(_) => {}
+                Stmt::Static(s) => {
+                    self.diagnostics.push(Diagnostic::error(
+                        "static items are only allowed at the top level",
+                        s.name.span,
+                    ));
+                }
+                Stmt::TypeAlias(TypeAliasDef { name, .. }) | Stmt::Newtype(NewtypeDef { name, .. }) => {
+                    self.diagnostics.push(Diagnostic::error(
+                        "type declarations are only allowed at the top level",
+                        name.span,
+                    ));
+                }
             }
         }
 
@@ -2219,6 +2813,7 @@ impl<'a> BlockChecker<'a> {
         }
 
         let mut dropped = false;
+        let reported = self.diagnostics.len();
         let mut last_expr: Option<HirExpr> = None;
         let mut pipe_pending: Option<StackEntry> = None;
         // (target_type, stack_depth_when_annotation_appeared)
@@ -2336,6 +2931,12 @@ impl<'a> BlockChecker<'a> {
                                 BindingKind::Func { .. } if *forced_value => {
                                     HirExprKind::FnValue(id.name.clone())
                                 }
+                                BindingKind::Static { index } => {
+                                    if binding.mutable && self.current_effect == Effect::Pure {
+                                        self.diagnostics.push(static_mut_read_error(&id.name, id.span));
+                                    }
+                                    HirExprKind::GlobalGet(index)
+                                }
                                 _ => HirExprKind::Var(id.name.clone()),
                             };
                             let explicit_args = match binding.kind {
@@ -2399,11 +3000,21 @@ impl<'a> BlockChecker<'a> {
                                         ));
                                     }
                                     let ty = binding.ty;
+                                    let kind = match binding.kind {
+                                        BindingKind::Static { index } => {
+                                            if binding.mutable && self.current_effect == Effect::Pure {
+                                                self.diagnostics
+                                                    .push(static_mut_read_error(&id.name, id.span));
+                                            }
+                                            HirExprKind::GlobalGet(index)
+                                        }
+                                        _ => HirExprKind::Var(lookup_name.clone()),
+                                    };
                                     stack.push(StackEntry {
                                         ty,
                                         expr: HirExpr {
                                             ty,
-                                            kind: HirExprKind::Var(lookup_name.clone()),
+                                            kind,
                                             span: id.span,
                                         },
                                         type_args: Vec::new(),
@@ -2497,7 +3108,7 @@ impl<'a> BlockChecker<'a> {
                                         return None;
                                     }
                                     if let Some(sig) = trait_info.methods.get(method_name) {
-                                        let (inst_ty, args) = self.ctx.instantiate(*sig);
+                                        let inst_ty = trait_info.instantiate_method(self.ctx, *sig);
                                         stack.push(StackEntry {
                                             ty: inst_ty,
                                             expr: HirExpr {
@@ -2509,8 +3120,8 @@ impl<'a> BlockChecker<'a> {
                                                 },
                                                 span: id.span,
                                             },
-                                            type_args: args,
-                                            assign: None,
+                                            type_args: Vec::new(),
+                                            assign: None,
                                             auto_call: !*forced_value,
                                         });
                                         last_expr = Some(stack.last().unwrap().expr.clone());
@@ -2525,12 +3136,14 @@ impl<'a> BlockChecker<'a> {
                                         return None;
                                     }
                                 } else {
-                                    self.diagnostics
-                                        .push(Diagnostic::error("undefined identifier", id.span));
+                                    let diag = self.undefined_identifier(&id.name, id.span);
+                                    self.diagnostics.push(diag);
+                                    stack.push(self.error_entry(id.span));
                                 }
                             } else {
-                                self.diagnostics
-                                    .push(Diagnostic::error("undefined identifier", id.span));
+                                let diag = self.undefined_identifier(&id.name, id.span);
+                                self.diagnostics.push(diag);
+                                stack.push(self.error_entry(id.span));
                             }
                         }
                     }
@@ -2637,10 +3250,15 @@ impl<'a> BlockChecker<'a> {
                             .or_else(|| self.env.lookup_value(&name.name))
                         {
                             if !binding.mutable {
-                                self.diagnostics.push(Diagnostic::error(
-                                    "cannot set immutable variable",
-                                    name.span,
-                                ));
+                                let diag = match binding.kind {
+                                    BindingKind::Static { .. } => Diagnostic::error(
+                                        format!("cannot assign to immutable static '{}'", name.name),
+                                        name.span,
+                                    )
+                                    .with_note("declare it with `static mut` to allow assignment"),
+                                    _ => Diagnostic::error("cannot set immutable variable", name.span),
+                                };
+                                self.diagnostics.push(diag);
                             }
                             let func_ty = self.ctx.function(
                                 Vec::new(),
@@ -2662,8 +3280,12 @@ impl<'a> BlockChecker<'a> {
                             // defer applying ascription until the expression is complete
                             last_expr = Some(stack.last().unwrap().expr.clone());
                         } else {
-                            self.diagnostics
-                                .push(Diagnostic::error("undefined variable", name.span));
+                            let diag = suggest::with_suggestions(
+                                Diagnostic::error("undefined variable", name.span),
+                                &name.name,
+                                self.env.visible_names(),
+                            );
+                            self.diagnostics.push(diag);
                         }
                     }
                     Symbol::AddrOf(span) => {
@@ -2703,6 +3325,23 @@ impl<'a> BlockChecker<'a> {
                         });
                         last_expr = Some(stack.last().unwrap().expr.clone());
                     }
+                    Symbol::Try(span) => {
+                        let a = self.ctx.fresh_var(None);
+                        let b = self.ctx.fresh_var(None);
+                        let func_ty = self.ctx.function(Vec::new(), vec![a], b, Effect::Pure);
+                        stack.push(StackEntry {
+                            ty: func_ty,
+                            expr: HirExpr {
+                                ty: func_ty,
+                                kind: HirExprKind::Var("&&try".to_string()),
+                                span: *span,
+                            },
+                            type_args: Vec::new(),
+                            assign: Some(AssignKind::Try),
+                            auto_call: true,
+                        });
+                        last_expr = Some(stack.last().unwrap().expr.clone());
+                    }
                     Symbol::If(sp) => {
                         let t_cond = self.ctx.bool();
                         let t_branch = self.ctx.fresh_var(None);
@@ -2726,7 +3365,51 @@ impl<'a> BlockChecker<'a> {
                         // defer applying ascription until the expression is complete
                         last_expr = Some(stack.last().unwrap().expr.clone());
                     }
-                    Symbol::While(sp) => {
+                    Symbol::Return(span) => {
+                        let never = self.ctx.never();
+                        let func_ty = self.ctx.function(
+                            Vec::new(),
+                            vec![self.current_result],
+                            never,
+                            Effect::Pure,
+                        );
+                        stack.push(StackEntry {
+                            ty: func_ty,
+                            expr: HirExpr {
+                                ty: func_ty,
+                                kind: HirExprKind::Var("&&return".to_string()),
+                                span: *span,
+                            },
+                            type_args: Vec::new(),
+                            assign: Some(AssignKind::Return),
+                            auto_call: true,
+                        });
+                        last_expr = Some(stack.last().unwrap().expr.clone());
+                    }
+                    Symbol::Break(span, label) | Symbol::Continue(span, label) => {
+                        let never = self.ctx.never();
+                        let kind = if matches!(sym, Symbol::Break(..)) {
+                            HirExprKind::Break(label.clone())
+                        } else {
+                            HirExprKind::Continue(label.clone())
+                        };
+                        stack.push(StackEntry {
+                            ty: never,
+                            expr: HirExpr {
+                                ty: never,
+                                kind,
+                                span: *span,
+                            },
+                            type_args: Vec::new(),
+                            assign: None,
+                            auto_call: true,
+                        });
+                        last_expr = Some(stack.last().unwrap().expr.clone());
+                    }
+                    Symbol::While(sp, label) => {
+                        if let Some(label) = label {
+                            self.loop_labels.insert(sp.start, label.clone());
+                        }
                         let t_cond = self.ctx.bool();
                         let func_ty = self.ctx.function(
                             Vec::new(),
@@ -2765,6 +3448,23 @@ impl<'a> BlockChecker<'a> {
                         }
                     }
                     
+                    if matches!(
+                        intrin.name.as_str(),
+                        "store" | "set_field" | "mem_copy" | "mem_fill"
+                    ) && matches!(self.current_effect, Effect::Pure)
+                    {
+                        self.diagnostics.push(
+                            Diagnostic::error(
+                                format!(
+                                    "pure context cannot use impure intrinsic '{}'",
+                                    intrin.name
+                                ),
+                                *sp,
+                            )
+                            .with_note(MARK_IMPURE_NOTE),
+                        );
+                    }
+
                     let ty = if intrin.name == "size_of" || intrin.name == "align_of" {
                         self.ctx.i32()
                     } else if intrin.name == "load" {
@@ -2773,8 +3473,16 @@ impl<'a> BlockChecker<'a> {
                         } else {
                              self.ctx.unit()
                         }
-                    } else if intrin.name == "store" {
+                    } else if matches!(intrin.name.as_str(), "store" | "mem_copy" | "mem_fill") {
                          self.ctx.unit()
+                    } else if intrin.name == "const_value" {
+                        if type_args.len() == 2 {
+                            type_args[1]
+                        } else {
+                            self.diagnostics
+                                .push(Diagnostic::error("const_value expects 2 type args", *sp));
+                            self.ctx.unit()
+                        }
                     } else if intrin.name == "callsite_span" {
                         if type_args.len() == 1 {
                             type_args[0]
@@ -2803,6 +3511,12 @@ impl<'a> BlockChecker<'a> {
                         self.ctx.fresh_var(None)
                     } else if intrin.name == "set_field" {
                         self.ctx.unit()
+                    } else if let Some(op) = simd::lookup(&intrin.name) {
+                        match op.scalar_result() {
+                            Some(simd::Lane::I32) => self.ctx.i32(),
+                            Some(simd::Lane::F32) => self.ctx.f32(),
+                            None => v128_type(self.ctx),
+                        }
                     } else {
                         self.diagnostics.push(Diagnostic::error("unknown intrinsic", *sp));
                         self.ctx.unit()
@@ -2958,6 +3672,24 @@ impl<'a> BlockChecker<'a> {
                                 *sp,
                             ));
                         }
+                    } else if intrin.name == "mem_copy" || intrin.name == "mem_fill" {
+                        // (dst, src, len) / (dst, value, len), in wasm operand order
+                        if args.len() != 3 {
+                            self.diagnostics.push(Diagnostic::error(
+                                "intrinsic expects 3 arguments",
+                                *sp,
+                            ));
+                        } else if args
+                            .iter()
+                            .any(|a| self.ctx.unify(a.ty, self.ctx.i32()).is_err())
+                        {
+                            self.diagnostics.push(Diagnostic::error(
+                                "intrinsic argument type mismatch (expected i32)",
+                                *sp,
+                            ));
+                        }
+                    } else if let Some(op) = simd::lookup(&intrin.name) {
+                        self.check_simd_args(op, &intrin.name, &args, *sp);
                     } else if intrin.name == "u8_to_i32" {
                         if args.len() != 1 {
                             self.diagnostics.push(Diagnostic::error(
@@ -2994,6 +3726,18 @@ impl<'a> BlockChecker<'a> {
                     // record target type and current stack depth; do NOT treat as an expression
                     pending_ascription = Some((ty, stack.len()));
                 }
+                PrefixItem::For(fexpr, _sp) => {
+                    if let Some(hexpr) = self.check_for_expr(fexpr) {
+                        stack.push(StackEntry {
+                            ty: hexpr.ty,
+                            expr: hexpr,
+                            type_args: Vec::new(),
+                            assign: None,
+                            auto_call: true,
+                        });
+                        last_expr = Some(stack.last().unwrap().expr.clone());
+                    }
+                }
                 PrefixItem::Match(mexpr, _sp) => {
                     if let Some((hexpr, ty)) = self.check_match_expr(mexpr) {
                         stack.push(StackEntry {
@@ -3074,6 +3818,11 @@ impl<'a> BlockChecker<'a> {
                         });
                     }
                 }
+                PrefixItem::Error(sp) => {
+                    // already reported by the parser
+                    stack.push(self.error_entry(*sp));
+                    last_expr = Some(stack.last().unwrap().expr.clone());
+                }
                 PrefixItem::Pipe(sp) => {
                     if pipe_pending.is_some() {
                         self.diagnostics.push(Diagnostic::error(
@@ -3178,6 +3927,22 @@ impl<'a> BlockChecker<'a> {
         );
         // Validate final stack depth. `let` is special-cased because its RHS
         // expression remains on stack until we lower to `HirExprKind::Let`.
+        let value_depth = base_depth + if leading_let { 2 } else { 1 };
+        let had_error = self.has_errors_since(reported)
+            || stack[base_depth.min(stack.len())..]
+                .iter()
+                .any(|e| self.ctx.contains_error(e.ty));
+        if stack.len() > value_depth && had_error {
+            // After an error (e.g. an unknown function, whose arity is unknown)
+            // the values cannot be grouped; the expression becomes one error value.
+            let span = stack[value_depth - 1..]
+                .iter()
+                .fold(stack[value_depth - 1].expr.span, |sp, e| {
+                    sp.join(e.expr.span).unwrap_or(sp)
+                });
+            stack.truncate(value_depth - 1);
+            stack.push(self.error_entry(span));
+        }
         if leading_let {
             if stack.len() > base_depth + 2 {
                 let extras = stack.len() - (base_depth + 2);
@@ -3320,7 +4085,10 @@ impl<'a> BlockChecker<'a> {
                 func_pos = outer;
             }
 
-            let (inst_ty, fresh_args) = if !stack[func_pos].type_args.is_empty() {
+            // Only explicit `<...>` arguments pick an overload; the type args of whichever
+            // binding happened to be looked up must not constrain the others.
+            let explicit_args = stack[func_pos].type_args.clone();
+            let (inst_ty, _) = if !stack[func_pos].type_args.is_empty() {
                 (stack[func_pos].ty, stack[func_pos].type_args.clone())
             } else {
                 self.ctx.instantiate(stack[func_pos].ty)
@@ -3366,20 +4134,27 @@ impl<'a> BlockChecker<'a> {
                 if crate::log::is_verbose() {
                     std::eprintln!("    Reducing: {} at pos {} with {} args, assign={:?}", self.ctx.type_to_string(inst_ty), func_pos, params.len(), func_entry.assign);
                 }
+            let call_span = args.iter().fold(func_entry.expr.span, |sp, a| {
+                sp.join(a.expr.span).unwrap_or(sp)
+            });
+            let reported = self.diagnostics.len();
             let applied = self.apply_function(
                 func_entry,
                 params,
                 result,
                 effect,
                 args,
-                fresh_args,
+                explicit_args,
                 expected_ret,
             );
 
-            if let Some(val) = applied {
-                stack.insert(func_pos, val);
-            } else {
-                break;
+            match applied {
+                Some(val) => stack.insert(func_pos, val),
+                // the failed call has been reported; its value stands in for the result
+                None if self.has_errors_since(reported) => {
+                    stack.insert(func_pos, self.error_entry(call_span))
+                }
+                None => break,
             }
         }
     }
@@ -3485,7 +4260,8 @@ impl<'a> BlockChecker<'a> {
                 func_pos = outer;
             }
 
-            let (inst_ty, fresh_args) = if !stack[func_pos].type_args.is_empty() {
+            let explicit_args = stack[func_pos].type_args.clone();
+            let (inst_ty, _) = if !stack[func_pos].type_args.is_empty() {
                 (stack[func_pos].ty, stack[func_pos].type_args.clone())
             } else {
                 self.ctx.instantiate(stack[func_pos].ty)
@@ -3531,24 +4307,248 @@ impl<'a> BlockChecker<'a> {
                 if crate::log::is_verbose() {
                     std::eprintln!("    Reducing (guarded): {} at pos {} with {} args, assign={:?}", self.ctx.type_to_string(inst_ty), func_pos, params.len(), func_entry.assign);
                 }
+            let call_span = args.iter().fold(func_entry.expr.span, |sp, a| {
+                sp.join(a.expr.span).unwrap_or(sp)
+            });
+            let reported = self.diagnostics.len();
             let applied = self.apply_function(
                 func_entry,
                 params,
                 result,
                 effect,
                 args,
-                fresh_args,
+                explicit_args,
                 expected_ret,
             );
 
-            if let Some(val) = applied {
-                stack.insert(func_pos, val);
-            } else {
-                break;
+            match applied {
+                Some(val) => stack.insert(func_pos, val),
+                // the failed call has been reported; its value stands in for the result
+                None if self.has_errors_since(reported) => {
+                    stack.insert(func_pos, self.error_entry(call_span))
+                }
+                None => break,
             }
         }
     }
 
+    /// Attach one overload candidate to `diag`: a secondary label at its
+    /// definition, or a note when the candidate has no source location.
+    fn with_overload_candidate(
+        &self,
+        diag: Diagnostic,
+        name: &str,
+        binding: &Binding,
+        status: &str,
+    ) -> Diagnostic {
+        let text = format!(
+            "candidate `{} <{}>` {}",
+            name,
+            self.ctx.type_to_display(binding.ty),
+            status
+        );
+        if binding.span.is_empty() {
+            diag.with_note(text)
+        } else {
+            diag.with_secondary_label(binding.span, Some(text))
+        }
+    }
+
+    /// A value for an expression whose error has already been reported. Its
+    /// type unifies with everything, so the expression around it is still
+    /// checked without reporting the same mistake again.
+    fn error_entry(&self, span: Span) -> StackEntry {
+        let ty = self.ctx.error();
+        StackEntry {
+            ty,
+            expr: HirExpr {
+                ty,
+                kind: HirExprKind::Unit,
+                span,
+            },
+            type_args: Vec::new(),
+            assign: None,
+            auto_call: false,
+        }
+    }
+
+    fn has_errors_since(&self, len: usize) -> bool {
+        self.diagnostics[len..]
+            .iter()
+            .any(|d| matches!(d.severity, Severity::Error))
+    }
+
+    /// Build an "undefined identifier" diagnostic with spelling suggestions
+    /// drawn from visible bindings (values and every overload name), enum
+    /// variants (`Enum::Variant`) and trait methods (`Trait::method`).
+    fn undefined_identifier(&self, name: &str, span: Span) -> Diagnostic {
+        let diag = Diagnostic::error(format!("undefined identifier '{}'", name), span)
+            .with_code(suggest::UNDEFINED_IDENTIFIER);
+        let mut candidates: Vec<String> = Vec::new();
+        if let Some((ns, member)) = parse_variant_name(name) {
+            if let Some(info) = self.enums.get(ns) {
+                candidates.extend(info.variants.iter().map(|v| format!("{}::{}", ns, v.name)));
+            } else if let Some(info) = self.traits.get(ns) {
+                candidates.extend(info.methods.keys().map(|m| format!("{}::{}", ns, m)));
+            } else {
+                candidates.extend(self.enums.keys().map(|e| format!("{}::{}", e, member)));
+                candidates.extend(self.traits.keys().map(|t| format!("{}::{}", t, member)));
+            }
+        }
+        candidates.extend(self.env.visible_names().into_iter().map(|n| n.to_string()));
+        suggest::with_suggestions(diag, name, candidates.iter().map(|c| c.as_str()))
+    }
+
+    /// Name and variants of an enum type, with payloads instantiated for `Enum<args>`.
+    fn enum_variants(&mut self, ty: TypeId) -> Option<(String, Vec<EnumVariantInfo>)> {
+        match self.ctx.get(ty) {
+            TypeKind::Enum { name, variants, .. } => Some((name, variants)),
+            TypeKind::Apply { base, args } => {
+                let base_ty = self.ctx.resolve(base);
+                match self.ctx.get(base_ty) {
+                    TypeKind::Enum {
+                        name,
+                        type_params,
+                        variants,
+                    } if type_params.len() == args.len() => {
+                        let mapping: BTreeMap<TypeId, TypeId> =
+                            type_params.iter().copied().zip(args.iter().copied()).collect();
+                        let variants = variants
+                            .into_iter()
+                            .map(|v| EnumVariantInfo {
+                                name: v.name,
+                                payload: v.payload.map(|p| self.ctx.substitute(p, &mapping)),
+                            })
+                            .collect();
+                        Some((name, variants))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Lowers `? e`: `Option::Some v` / `Result::Ok v` gives `v`; `None` / `Err e` is
+    /// rebuilt as the function's own result type and returned early.
+    fn desugar_try(&mut self, arg: HirExpr, span: Span) -> Option<StackEntry> {
+        let arg_ty = self.ctx.resolve(arg.ty);
+        let (enum_name, variants, ok, fail) = match self.enum_variants(arg_ty) {
+            Some((name, variants)) if name == "Option" => (name, variants, "Some", "None"),
+            Some((name, variants)) if name == "Result" => (name, variants, "Ok", "Err"),
+            _ => {
+                self.diagnostics.push(Diagnostic::error(
+                    format!(
+                        "`?` expects an Option or Result, found {}",
+                        self.ctx.type_to_string(arg_ty)
+                    ),
+                    arg.span,
+                ));
+                return None;
+            }
+        };
+        let enums = self.enums;
+        let info = enums.get(&enum_name)?;
+        let payload = |variants: &[EnumVariantInfo], name: &str| {
+            variants.iter().find(|v| v.name == name).and_then(|v| v.payload)
+        };
+
+        // The enclosing function has to return the same enum with a compatible failure payload.
+        let fresh: Vec<TypeId> = info
+            .type_params
+            .iter()
+            .map(|_| self.ctx.fresh_var(None))
+            .collect();
+        let ret_ty = if fresh.is_empty() {
+            info.ty
+        } else {
+            self.ctx.apply(info.ty, fresh.clone())
+        };
+        if self.ctx.unify(self.current_result, ret_ty).is_err() {
+            self.diagnostics.push(Diagnostic::error(
+                format!(
+                    "`?` on {} requires the function to return {}, found {}",
+                    enum_name,
+                    enum_name,
+                    self.ctx.type_to_string(self.current_result)
+                ),
+                span,
+            ));
+            return None;
+        }
+        let fail_ty = payload(&variants, fail);
+        if let (Some(found), Some(expected)) = (fail_ty, payload(&info.variants, fail)) {
+            let mapping: BTreeMap<TypeId, TypeId> =
+                info.type_params.iter().copied().zip(fresh.iter().copied()).collect();
+            let expected = self.ctx.substitute(expected, &mapping);
+            if self.ctx.unify(expected, found).is_err() {
+                self.diagnostics.push(Diagnostic::error(
+                    format!(
+                        "mismatched error type for `?`: the function returns {}, found {}",
+                        self.ctx.type_to_string(expected),
+                        self.ctx.type_to_string(found)
+                    ),
+                    span,
+                ));
+            }
+        }
+
+        let ok_ty = payload(&variants, ok).unwrap_or(self.ctx.unit());
+        let ok_bind = format!("$try_ok{}", span.start);
+        let fail_bind = format!("$try_err{}", span.start);
+        let failure = HirExpr {
+            ty: ret_ty,
+            kind: HirExprKind::EnumConstruct {
+                name: enum_name,
+                variant: fail.to_string(),
+                type_args: fresh,
+                payload: fail_ty.map(|ty| {
+                    Box::new(HirExpr {
+                        ty,
+                        kind: HirExprKind::Var(fail_bind.clone()),
+                        span,
+                    })
+                }),
+            },
+            span,
+        };
+        let never = self.ctx.never();
+        let arms = vec![
+            HirMatchArm {
+                variant: ok.to_string(),
+                bind_local: Some(ok_bind.clone()),
+                body: HirExpr {
+                    ty: ok_ty,
+                    kind: HirExprKind::Var(ok_bind),
+                    span,
+                },
+            },
+            HirMatchArm {
+                variant: fail.to_string(),
+                bind_local: fail_ty.map(|_| fail_bind),
+                body: HirExpr {
+                    ty: never,
+                    kind: HirExprKind::Return(Box::new(failure)),
+                    span,
+                },
+            },
+        ];
+        Some(StackEntry {
+            ty: ok_ty,
+            expr: HirExpr {
+                ty: ok_ty,
+                kind: HirExprKind::Match {
+                    scrutinee: Box::new(arg),
+                    arms,
+                },
+                span,
+            },
+            type_args: Vec::new(),
+            assign: None,
+            auto_call: true,
+        })
+    }
+
     fn check_match_expr(&mut self, m: &MatchExpr) -> Option<(HirExpr, TypeId)> {
         // evaluate scrutinee
         let mut tmp_stack = Vec::new();
@@ -3556,36 +4556,11 @@ impl<'a> BlockChecker<'a> {
             let scrut_ty = scrut_expr.ty;
             let resolved_ty = self.ctx.resolve(scrut_ty);
             let variants = match self.ctx.get(resolved_ty) {
-                TypeKind::Enum { variants, .. } => Some(variants.clone()),
                 TypeKind::Bool => Some(alloc::vec![
                     EnumVariantInfo { name: "true".to_string(), payload: None },
                     EnumVariantInfo { name: "false".to_string(), payload: None },
                 ]),
-                TypeKind::Apply { base, args } => {
-                    let base_ty = self.ctx.resolve(base);
-                    match self.ctx.get(base_ty) {
-                        TypeKind::Enum { type_params, variants, .. } => {
-                            if type_params.len() == args.len() {
-                                let mut mapping = alloc::collections::BTreeMap::new();
-                                for (tp, arg) in type_params.iter().zip(args.iter()) {
-                                    mapping.insert(*tp, *arg);
-                                }
-                                let mut new_vars = Vec::new();
-                                for v in variants {
-                                    new_vars.push(EnumVariantInfo {
-                                        name: v.name.clone(),
-                                        payload: v.payload.map(|p| self.ctx.substitute(p, &mapping)),
-                                    });
-                                }
-                                Some(new_vars)
-                            } else {
-                                None
-                            }
-                        }
-                        _ => None,
-                    }
-                }
-                _ => None,
+                _ => self.enum_variants(resolved_ty).map(|(_, variants)| variants),
             };
             if variants.is_none() {
                 self.diagnostics
@@ -3609,9 +4584,13 @@ impl<'a> BlockChecker<'a> {
                 }
                 let var_info = variants.iter().find(|v| v.name == arm_var_name);
                 if var_info.is_none() {
-                self.diagnostics.push(Diagnostic::error(
-                    alloc::format!("unknown enum variant '{}' in match", arm.variant.name),
-                    arm.variant.span,
+                self.diagnostics.push(suggest::with_suggestions(
+                    Diagnostic::error(
+                        alloc::format!("unknown enum variant '{}' in match", arm.variant.name),
+                        arm.variant.span,
+                    ),
+                    arm_var_name,
+                    variants.iter().map(|v| v.name.as_str()),
                 ));
                     continue;
                 }
@@ -3694,6 +4673,334 @@ impl<'a> BlockChecker<'a> {
         None
     }
 
+    /// `for x in it: body` を while ループへ展開する。
+    ///
+    /// `Range` は `start`/`end` を読んで数え上げるループになり、それ以外は
+    /// `next &it` が `Option::None` を返すまで回す（`next` はオーバーロードで解決する）。
+    fn check_for_expr(&mut self, f: &ForExpr) -> Option<HirExpr> {
+        let mut tmp_stack = Vec::new();
+        let (iter_expr, _) = self.check_prefix(&f.iter, 0, &mut tmp_stack)?;
+        let span = f.span;
+        let unit = self.ctx.unit();
+        let i32_ty = self.ctx.i32();
+        let iter_ty = self.ctx.resolve(iter_expr.ty);
+        let it_name = format!("$for_it{}", span.start);
+        let var = |name: &str, ty: TypeId| HirExpr {
+            ty,
+            kind: HirExprKind::Var(name.to_string()),
+            span,
+        };
+        let line = |expr: HirExpr| HirLine {
+            expr,
+            drop_result: false,
+        };
+        // Only the `Range` of core/iter becomes a counting loop: the struct, the `Iterator`
+        // trait and the impl `for` would call must all come from one imported file (the entry
+        // file is FileId(0)), so a user's own `Range` keeps going through its `next`.
+        let core_range = match (
+            self.structs.get("Range"),
+            self.traits.get("Iterator"),
+            self.find_impl("Iterator", iter_ty),
+        ) {
+            (Some(range), Some(iter), Some(imp)) => {
+                let file = iter.span.file_id;
+                file != FileId(0)
+                    && range.span.file_id == file
+                    && imp.span.file_id == file
+                    && self.ctx.resolve_id(range.ty) == self.ctx.resolve_id(iter_ty)
+            }
+            _ => false,
+        };
+        let range_offsets = match self.ctx.get(iter_ty) {
+            TypeKind::Struct {
+                fields,
+                field_names,
+                ..
+            } if core_range
+                && field_names == ["start", "end"]
+                && fields.iter().all(|t| {
+                    let t = self.ctx.resolve(*t);
+                    matches!(self.ctx.get(t), TypeKind::I32)
+                }) =>
+            {
+                Some((
+                    composite_field_offset_bytes(self.ctx, &fields, 0),
+                    composite_field_offset_bytes(self.ctx, &fields, 1),
+                ))
+            }
+            _ => None,
+        };
+
+        let (elem_ty, before, make_body): (TypeId, Vec<HirLine>, _);
+        self.env.push_scope();
+        if let Some((start_offset, end_offset)) = range_offsets {
+            // 数え上げ: let i start; let end end; while lt_s i end: let x i; set i add i 1; body
+            let idx_name = format!("$for_i{}", span.start);
+            let end_name = format!("$for_end{}", span.start);
+            let load = |addr: HirExpr| HirExpr {
+                ty: i32_ty,
+                kind: HirExprKind::Intrinsic {
+                    name: "load".to_string(),
+                    type_args: vec![i32_ty],
+                    args: vec![addr],
+                },
+                span,
+            };
+            let add = |a: HirExpr, k: i32| HirExpr {
+                ty: i32_ty,
+                kind: HirExprKind::Intrinsic {
+                    name: "add".to_string(),
+                    type_args: vec![i32_ty],
+                    args: vec![
+                        a,
+                        HirExpr {
+                            ty: i32_ty,
+                            kind: HirExprKind::LiteralI32(k),
+                            span,
+                        },
+                    ],
+                },
+                span,
+            };
+            let let_line = |name: &str, mutable: bool, value: HirExpr| HirLine {
+                expr: HirExpr {
+                    ty: unit,
+                    kind: HirExprKind::Let {
+                        name: name.to_string(),
+                        mutable,
+                        value: Box::new(value),
+                    },
+                    span,
+                },
+                drop_result: false,
+            };
+            let field = |offset: usize| match offset {
+                0 => load(var(&it_name, iter_ty)),
+                _ => load(add(var(&it_name, iter_ty), offset as i32)),
+            };
+            before = vec![
+                let_line(&it_name, false, iter_expr),
+                let_line(&idx_name, true, field(start_offset)),
+                let_line(&end_name, false, field(end_offset)),
+            ];
+            elem_ty = i32_ty;
+            let cond = HirExpr {
+                ty: self.ctx.bool(),
+                kind: HirExprKind::Intrinsic {
+                    name: "lt_s".to_string(),
+                    type_args: vec![i32_ty],
+                    args: vec![var(&idx_name, i32_ty), var(&end_name, i32_ty)],
+                },
+                span,
+            };
+            let step = vec![
+                let_line(&f.var.name, false, var(&idx_name, i32_ty)),
+                line(HirExpr {
+                    ty: unit,
+                    kind: HirExprKind::Set {
+                        name: idx_name.clone(),
+                        value: Box::new(add(var(&idx_name, i32_ty), 1)),
+                    },
+                    span,
+                }),
+            ];
+            make_body = ForBody::Counting { cond, step };
+        } else {
+            // プロトコル: let mut it iter; while true: match Iterator::next &it: Some x: body / None: break
+            let missing = if !self.traits.contains_key("Iterator") {
+                Some(String::from(
+                    "`for` needs the Iterator trait; import core/iter",
+                ))
+            } else if !self.trait_bound_satisfied("Iterator", iter_ty) {
+                Some(format!(
+                    "`for` needs an `Iterator` impl for {}",
+                    self.ctx.type_to_display(iter_ty)
+                ))
+            } else {
+                None
+            };
+            if let Some(message) = missing {
+                self.diagnostics.push(Diagnostic::error(message, f.iter.span));
+                self.env.pop_scope();
+                return None;
+            }
+            let _ = self.env.insert_local(Binding {
+                name: it_name.clone(),
+                ty: iter_expr.ty,
+                mutable: true,
+                no_shadow: false,
+                defined: true,
+                moved: false,
+                span,
+                kind: BindingKind::Var,
+            });
+            let next_call = PrefixExpr {
+                items: vec![
+                    PrefixItem::Symbol(Symbol::Ident(
+                        Ident {
+                            name: "Iterator::next".to_string(),
+                            span,
+                        },
+                        Vec::new(),
+                        false,
+                    )),
+                    PrefixItem::Symbol(Symbol::AddrOf(span)),
+                    PrefixItem::Symbol(Symbol::Ident(
+                        Ident {
+                            name: it_name.clone(),
+                            span,
+                        },
+                        Vec::new(),
+                        false,
+                    )),
+                ],
+                trailing_semis: 0,
+                trailing_semi_span: None,
+                span,
+            };
+            let mut next_stack = Vec::new();
+            let Some((next_expr, _)) = self.check_prefix(&next_call, 0, &mut next_stack) else {
+                self.env.pop_scope();
+                return None;
+            };
+            let next_ty = self.ctx.resolve(next_expr.ty);
+            let item_ty = match self.enum_variants(next_ty) {
+                Some((name, variants)) if name == "Option" => variants
+                    .iter()
+                    .find(|v| v.name == "Some")
+                    .and_then(|v| v.payload),
+                _ => None,
+            };
+            let Some(item_ty) = item_ty else {
+                self.diagnostics.push(Diagnostic::error(
+                    format!(
+                        "`for` needs `Iterator::next` on {} to return an Option, found {}",
+                        self.ctx.type_to_display(iter_ty),
+                        self.ctx.type_to_display(next_ty)
+                    ),
+                    f.iter.span,
+                ));
+                self.env.pop_scope();
+                return None;
+            };
+            before = vec![line(HirExpr {
+                ty: unit,
+                kind: HirExprKind::Let {
+                    name: it_name.clone(),
+                    mutable: true,
+                    value: Box::new(iter_expr),
+                },
+                span,
+            })];
+            elem_ty = item_ty;
+            make_body = ForBody::Protocol { next_expr };
+        }
+
+        emit_shadow_warning(
+            &mut self.diagnostics,
+            self.env,
+            &f.var.name,
+            f.var.span,
+            "for-loop variable",
+        );
+        let _ = self.env.insert_local(Binding {
+            name: f.var.name.clone(),
+            ty: elem_ty,
+            mutable: false,
+            no_shadow: false,
+            defined: true,
+            moved: false,
+            span: f.var.span,
+            kind: BindingKind::Var,
+        });
+        let checked = self.check_block(&f.body, 0, true);
+        self.env.pop_scope();
+        let (blk, val_ty) = checked?;
+        if let Some(t) = val_ty {
+            if self.ctx.unify(t, unit).is_err() {
+                self.diagnostics.push(Diagnostic::error(
+                    format!(
+                        "`for` body must evaluate to (), found {}",
+                        self.ctx.type_to_string(t)
+                    ),
+                    f.body.span,
+                ));
+            }
+        }
+        let body = HirExpr {
+            ty: unit,
+            kind: HirExprKind::Block(blk),
+            span: f.body.span,
+        };
+
+        let (cond, body) = match make_body {
+            ForBody::Counting { cond, mut step } => {
+                step.push(line(body));
+                let body = HirExpr {
+                    ty: unit,
+                    kind: HirExprKind::Block(HirBlock {
+                        lines: step,
+                        ty: unit,
+                        span,
+                    }),
+                    span,
+                };
+                (cond, body)
+            }
+            ForBody::Protocol { next_expr } => {
+                let never = self.ctx.never();
+                let body = HirExpr {
+                    ty: unit,
+                    kind: HirExprKind::Match {
+                        scrutinee: Box::new(next_expr),
+                        arms: vec![
+                            HirMatchArm {
+                                variant: "Some".to_string(),
+                                bind_local: Some(f.var.name.clone()),
+                                body,
+                            },
+                            HirMatchArm {
+                                variant: "None".to_string(),
+                                bind_local: None,
+                                body: HirExpr {
+                                    ty: never,
+                                    kind: HirExprKind::Break(None),
+                                    span,
+                                },
+                            },
+                        ],
+                    },
+                    span,
+                };
+                let cond = HirExpr {
+                    ty: self.ctx.bool(),
+                    kind: HirExprKind::LiteralBool(true),
+                    span,
+                };
+                (cond, body)
+            }
+        };
+        let mut lines = before;
+        lines.push(line(HirExpr {
+            ty: unit,
+            kind: HirExprKind::While {
+                cond: Box::new(cond),
+                body: Box::new(body),
+                label: f.label.clone(),
+            },
+            span,
+        }));
+        Some(HirExpr {
+            ty: unit,
+            kind: HirExprKind::Block(HirBlock {
+                lines,
+                ty: unit,
+                span,
+            }),
+            span,
+        })
+    }
+
     fn split_if_then_else_block_ast(b: &Block) -> Option<(Block, Block)> {
         // Find top-level `else` marker line inside the block
         let mut else_idx: Option<usize> = None;
@@ -3830,13 +5137,24 @@ impl<'a> BlockChecker<'a> {
             args.clear();
         }
 
-        if matches!(self.current_effect, Effect::Pure)
+        // 名前付き関数の呼び出しは overload 解決で候補ごとに effect を検査する
+        let resolves_overloads = matches!(
+            &func.expr.kind,
+            HirExprKind::Var(n) if !self.env.lookup_all_callables(n).is_empty()
+        );
+        if !resolves_overloads
+            && matches!(self.current_effect, Effect::Pure)
             && matches!(effect, Effect::Impure)
         {
-            self.diagnostics.push(Diagnostic::error(
-                "pure context cannot call impure function",
-                func.expr.span,
-            ));
+            let message = match (&func.assign, &func.expr.kind) {
+                (Some(AssignKind::Set), HirExprKind::Var(n)) => {
+                    format!("pure context cannot assign to '{}' with `set`", n)
+                }
+                _ => "pure context cannot call impure function".to_string(),
+            };
+            self.diagnostics.push(
+                Diagnostic::error(message, func.expr.span).with_note(MARK_IMPURE_NOTE),
+            );
             return None;
         }
 
@@ -3892,6 +5210,33 @@ impl<'a> BlockChecker<'a> {
                     assign: None,
                             auto_call: true,
                 });
+            } else if matches!(assign, AssignKind::Return) {
+                let arg = args.remove(0);
+                if self.ctx.unify(self.current_result, arg.ty).is_err() {
+                    self.diagnostics.push(Diagnostic::error(
+                        format!(
+                            "return value has type {}, but the function returns {}",
+                            self.ctx.type_to_string(arg.ty),
+                            self.ctx.type_to_string(self.current_result)
+                        ),
+                        arg.expr.span,
+                    ));
+                }
+                let never = self.ctx.never();
+                return Some(StackEntry {
+                    ty: never,
+                    expr: HirExpr {
+                        ty: never,
+                        kind: HirExprKind::Return(Box::new(arg.expr)),
+                        span: func.expr.span,
+                    },
+                    type_args: Vec::new(),
+                    assign: None,
+                    auto_call: true,
+                });
+            } else if matches!(assign, AssignKind::Try) {
+                let arg = args.remove(0);
+                return self.desugar_try(arg.expr, func.expr.span);
             } else if matches!(assign, AssignKind::Deref) {
                 if args.len() != 1 { return None; }
                 let arg_ty = self.ctx.resolve(args[0].ty);
@@ -3929,6 +5274,10 @@ impl<'a> BlockChecker<'a> {
                 let b_ty = b.ty;
                 let b_mut = b.mutable;
                 let b_defined = b.defined;
+                let b_static = match b.kind {
+                    BindingKind::Static { index } => Some(index),
+                    _ => None,
+                };
                 if let Err(_) = self.ctx.unify(b_ty, args[0].ty) {
                     self.diagnostics.push(Diagnostic::error(
                         "type mismatch in assignment",
@@ -3952,6 +5301,23 @@ impl<'a> BlockChecker<'a> {
                                 span: func.expr.span,
                             },
                             type_args: Vec::new(),
+                            assign: None,
+                            auto_call: true,
+                        });
+                    }
+                    AssignKind::Set if b_static.is_some() => {
+                        // Mutability was already reported at the `set` symbol.
+                        return Some(StackEntry {
+                            ty: self.ctx.unit(),
+                            expr: HirExpr {
+                                ty: self.ctx.unit(),
+                                kind: HirExprKind::GlobalSet {
+                                    index: b_static.unwrap_or(0),
+                                    value: Box::new(args[0].expr.clone()),
+                                },
+                                span: func.expr.span,
+                            },
+                            type_args: Vec::new(),
                             assign: None,
                             auto_call: true,
                         });
@@ -4053,6 +5419,7 @@ impl<'a> BlockChecker<'a> {
                         kind: HirExprKind::While {
                             cond: Box::new(args[0].expr.clone()),
                             body: Box::new(args[1].expr.clone()),
+                            label: self.loop_labels.remove(&func.expr.span.start),
                         },
                         span: func.expr.span,
                     },
@@ -4119,6 +5486,16 @@ impl<'a> BlockChecker<'a> {
                             auto_call: true,
                             });
                         } else if name == "put" && args.len() == 3 {
+                            if matches!(self.current_effect, Effect::Pure) {
+                                self.diagnostics.push(
+                                    Diagnostic::error(
+                                        "pure context cannot call impure function",
+                                        func.expr.span,
+                                    )
+                                    .with_note(MARK_IMPURE_NOTE),
+                                );
+                                return None;
+                            }
                             let val = args[2].expr.clone();
                             let _ = self.ctx.unify(val.ty, f_ty);
                             let addr_expr = if offset == 0 {
@@ -4170,7 +5547,7 @@ impl<'a> BlockChecker<'a> {
                     let explicit_type_args = type_args.clone();
                     let use_expected = expected_ret.is_some() && bindings.len() > 1;
                     let mut candidates: Vec<&Binding> = Vec::new();
-                    let mut mismatch_count = false;
+                    let mut rejected: Vec<(&Binding, OverloadReject)> = Vec::new();
                     for binding in &bindings {
                         let capture_len = match &binding.kind {
                             BindingKind::Func { captures, .. } => captures.len(),
@@ -4193,7 +5570,13 @@ impl<'a> BlockChecker<'a> {
                                 continue;
                             };
                             if type_params.len() != explicit_type_args.len() {
-                                mismatch_count = true;
+                                rejected.push((
+                                    binding,
+                                    OverloadReject::TypeArgCount {
+                                        expected: type_params.len(),
+                                        found: explicit_type_args.len(),
+                                    },
+                                ));
                                 continue;
                             }
                             let mut mapping = BTreeMap::new();
@@ -4212,7 +5595,7 @@ impl<'a> BlockChecker<'a> {
                         };
 
                         let func_ty = tmp_ctx.get(inst_ty);
-                        let (c_params, c_result, _c_effect) = match func_ty {
+                        let (c_params, c_result, c_effect) = match func_ty {
                             TypeKind::Function {
                                 params,
                                 result,
@@ -4226,46 +5609,107 @@ impl<'a> BlockChecker<'a> {
                         }
                         let user_params = &c_params[capture_len..];
                         if user_params.len() != args.len() {
+                            rejected.push((
+                                binding,
+                                OverloadReject::Arity {
+                                    expected: user_params.len(),
+                                    found: args.len(),
+                                },
+                            ));
                             continue;
                         }
-                        let mut ok = true;
-                        for (arg, pty) in args.iter().zip(user_params.iter()) {
+                        let mut reject = None;
+                        for (index, (arg, pty)) in args.iter().zip(user_params.iter()).enumerate() {
                             if tmp_ctx.unify(arg.ty, *pty).is_err() {
-                                ok = false;
+                                reject = Some(OverloadReject::ArgType {
+                                    index,
+                                    expected: tmp_ctx.type_to_display(*pty),
+                                    found: tmp_ctx.type_to_display(arg.ty),
+                                });
                                 break;
                             }
                         }
-                        if ok && use_expected {
+                        if reject.is_none() && use_expected {
                             if let Some(expected) = expected_ret {
                                 if tmp_ctx.unify(c_result, expected).is_err() {
-                                    ok = false;
+                                    reject = Some(OverloadReject::ReturnType {
+                                        expected: tmp_ctx.type_to_display(expected),
+                                        found: tmp_ctx.type_to_display(c_result),
+                                    });
                                 }
                             }
                         }
-                        if ok {
-                            candidates.push(binding);
+                        if reject.is_none()
+                            && matches!(self.current_effect, Effect::Pure)
+                            && matches!(c_effect, Effect::Impure)
+                        {
+                            reject = Some(OverloadReject::Effect);
+                        }
+                        match reject {
+                            Some(reason) => rejected.push((binding, reason)),
+                            None => candidates.push(binding),
                         }
                     }
 
                     if candidates.is_empty() {
-                        if mismatch_count {
-                            self.diagnostics.push(Diagnostic::error(
-                                "type arguments do not match any overload",
-                                func.expr.span,
-                            ));
+                        let message = if !rejected.is_empty()
+                            && rejected
+                                .iter()
+                                .all(|(_, r)| matches!(r, OverloadReject::Effect))
+                        {
+                            "pure context cannot call impure function".to_string()
+                        } else if !rejected.is_empty()
+                            && rejected
+                                .iter()
+                                .all(|(_, r)| matches!(r, OverloadReject::TypeArgCount { .. }))
+                        {
+                            "type arguments do not match any overload".to_string()
+                        } else if bindings.len() == 1 && rejected.len() == 1 {
+                            format!("no matching overload found for `{}`", name)
                         } else {
-                            self.diagnostics.push(Diagnostic::error(
-                                "no matching overload found",
-                                func.expr.span,
-                            ));
+                            format!(
+                                "no matching overload found for `{}` ({} candidates)",
+                                name,
+                                bindings.len()
+                            )
+                        };
+                        let mut diag = Diagnostic::error(message, func.expr.span);
+                        for (binding, reason) in &rejected {
+                            diag = self.with_overload_candidate(
+                                diag,
+                                name,
+                                binding,
+                                &format!("rejected: {}", reason.describe()),
+                            );
+                        }
+                        if rejected
+                            .iter()
+                            .any(|(_, r)| matches!(r, OverloadReject::Effect))
+                        {
+                            diag = diag.with_note(MARK_IMPURE_NOTE);
                         }
+                        self.diagnostics.push(diag);
                         return None;
                     }
+                    if candidates.len() > 1 && args.iter().any(|a| self.ctx.contains_error(a.ty)) {
+                        // an argument that failed to typecheck cannot select an overload
+                        return Some(self.error_entry(func.expr.span));
+                    }
                     if candidates.len() > 1 {
-                        self.diagnostics.push(Diagnostic::error(
-                            "ambiguous overload",
+                        let mut diag = Diagnostic::error(
+                            format!(
+                                "ambiguous overload: {} candidates of `{}` match this call",
+                                candidates.len(),
+                                name
+                            ),
                             func.expr.span,
-                        ));
+                        );
+                        for binding in &candidates {
+                            diag = self.with_overload_candidate(diag, name, binding, "matches");
+                        }
+                        self.diagnostics.push(
+                            diag.with_note("add a type annotation to select one overload"),
+                        );
                         return None;
                     }
 
@@ -4341,7 +5785,11 @@ impl<'a> BlockChecker<'a> {
                     for (arg, param_ty) in args.iter().zip(user_params.iter()) {
                         if self.ctx.unify(arg.ty, *param_ty).is_err() {
                             self.diagnostics.push(Diagnostic::error(
-                                "argument type mismatch",
+                                format!(
+                                    "argument type mismatch: expected `{}`, found `{}`",
+                                    self.ctx.type_to_display(*param_ty),
+                                    self.ctx.type_to_display(arg.ty)
+                                ),
                                 arg.expr.span,
                             ));
                         }
@@ -4467,6 +5915,30 @@ impl<'a> BlockChecker<'a> {
                             auto_call: true,
                         });
                     }
+                    // Newtype constructors and `::inner` only retag the value:
+                    // a newtype shares the representation of its inner type.
+                    let is_newtype = |n: &str| {
+                        self.ctx
+                            .lookup_named(n)
+                            .is_some_and(|t| matches!(self.ctx.get(t), TypeKind::Newtype { .. }))
+                    };
+                    if is_newtype(name)
+                        || parse_variant_name(name)
+                            .is_some_and(|(base, op)| op == "inner" && is_newtype(base))
+                    {
+                        if let Some(arg) = args.into_iter().next() {
+                            let mut expr = arg.expr;
+                            expr.ty = c_result;
+                            return Some(StackEntry {
+                                ty: c_result,
+                                expr,
+                                type_args: Vec::new(),
+                                assign: None,
+                                auto_call: true,
+                            });
+                        }
+                        return None;
+                    }
 
                     let (callee_name, builtin) = match &binding.kind {
                         BindingKind::Func { symbol, builtin, .. } => (symbol.clone(), builtin),
@@ -4544,7 +6016,26 @@ impl<'a> BlockChecker<'a> {
                                 ));
                                 return None;
                             }
-                            let self_ty = self.ctx.resolve_id(args[0].ty);
+                            if params.len() != args.len() {
+                                self.diagnostics.push(Diagnostic::error(
+                                    "argument count mismatch",
+                                    func.expr.span,
+                                ));
+                                return None;
+                            }
+                            for (arg, param_ty) in args.iter().zip(params.iter()) {
+                                if self.ctx.unify(arg.ty, *param_ty).is_err() {
+                                    self.diagnostics.push(Diagnostic::error(
+                                        format!(
+                                            "argument type mismatch: expected `{}`, found `{}`",
+                                            self.ctx.type_to_display(*param_ty),
+                                            self.ctx.type_to_display(arg.ty)
+                                        ),
+                                        arg.expr.span,
+                                    ));
+                                }
+                            }
+                            let self_ty = self.trait_receiver(trait_name, method_name, &args[0]);
                             if !self.trait_bound_satisfied(trait_name, self_ty) {
                                 self.diagnostics.push(Diagnostic::error(
                                     format!(
@@ -4558,12 +6049,16 @@ impl<'a> BlockChecker<'a> {
                             if matches!(self.current_effect, Effect::Pure)
                                 && matches!(effect, Effect::Impure)
                             {
-                                self.diagnostics.push(Diagnostic::error(
-                                    "pure context cannot call impure function",
-                                    func.expr.span,
-                                ));
+                                self.diagnostics.push(
+                                    Diagnostic::error(
+                                        "pure context cannot call impure function",
+                                        func.expr.span,
+                                    )
+                                    .with_note(MARK_IMPURE_NOTE),
+                                );
                                 return None;
                             }
+                            self.refine_trait_call(trait_name, method_name, self_ty, &params, result);
                             let resolved_result = self.ctx.resolve_id(result);
                             return Some(StackEntry {
                                 ty: resolved_result,
@@ -4694,6 +6189,10 @@ struct Binding {
 #[derive(Debug, Clone)]
 enum BindingKind {
     Var,
+    /// A `static` item; `index` points into `HirModule::globals`.
+    Static {
+        index: u32,
+    },
     Func {
         symbol: String,
         effect: Effect,
@@ -4705,8 +6204,9 @@ enum BindingKind {
 }
 
 impl BindingKind {
+    /// Value bindings (locals and statics), as opposed to callables.
     fn is_var(&self) -> bool {
-        matches!(self, BindingKind::Var)
+        matches!(self, BindingKind::Var | BindingKind::Static { .. })
     }
 
     fn is_callable(&self) -> bool {
@@ -4714,6 +6214,48 @@ impl BindingKind {
     }
 }
 
+/// Why an overload candidate was not selected for a call.
+#[derive(Debug, Clone)]
+enum OverloadReject {
+    TypeArgCount { expected: usize, found: usize },
+    Arity { expected: usize, found: usize },
+    ArgType { index: usize, expected: String, found: String },
+    ReturnType { expected: String, found: String },
+    Effect,
+}
+
+/// 純粋関数の中で副作用を検出したときに添える補足
+const MARK_IMPURE_NOTE: &str = "declare the enclosing function with `*>` to allow side effects";
+
+impl OverloadReject {
+    fn describe(&self) -> String {
+        match self {
+            OverloadReject::TypeArgCount { expected, found } => {
+                format!("expects {} type argument(s), {} given", expected, found)
+            }
+            OverloadReject::Arity { expected, found } => {
+                format!("expects {} argument(s), {} given", expected, found)
+            }
+            OverloadReject::ArgType {
+                index,
+                expected,
+                found,
+            } => format!(
+                "argument {} has type `{}`, expected `{}`",
+                index + 1,
+                found,
+                expected
+            ),
+            OverloadReject::ReturnType { expected, found } => {
+                format!("returns `{}`, but `{}` is expected here", found, expected)
+            }
+            OverloadReject::Effect => {
+                "is impure (`*>`) but called from a pure (`->`) function".to_string()
+            }
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 struct Scope {
     values: Vec<Binding>,
@@ -4786,6 +6328,21 @@ impl Env {
         Ok(())
     }
 
+    /// Names of every binding in scope (values and callables), innermost first.
+    /// Hoisted `let` bindings whose initializer is still being checked are left out.
+    fn visible_names(&self) -> Vec<&str> {
+        let mut out: Vec<&str> = Vec::new();
+        for scope in self.scopes.iter().rev() {
+            let values = scope.values.iter().filter(|b| b.defined);
+            for b in values.chain(scope.callables.iter()) {
+                if !out.contains(&b.name.as_str()) {
+                    out.push(b.name.as_str());
+                }
+            }
+        }
+        out
+    }
+
     fn lookup_current(&self, name: &str) -> Option<&Binding> {
         self.scopes.last().and_then(|scope| {
             scope
@@ -4850,7 +6407,7 @@ impl Env {
     fn lookup_value(&self, name: &str) -> Option<&Binding> {
         self.lookup_all_any_defined(name)
             .into_iter()
-            .find(|b| matches!(b.kind, BindingKind::Var))
+            .find(|b| b.kind.is_var())
     }
 
     fn lookup_value_for_read(
@@ -4961,9 +6518,340 @@ impl Env {
     }
 }
 
+/// static 初期化式の中では `*>` を付ける関数が無いので、補足を差し替える。
+fn retarget_static_init_note(mut d: Diagnostic) -> Diagnostic {
+    for note in &mut d.notes {
+        if note == MARK_IMPURE_NOTE {
+            *note = String::from(
+                "static initializers are evaluated at compile time and cannot have side effects",
+            );
+        }
+    }
+    d
+}
+
+/// 純粋関数の結果は可変なグローバル状態に依存してはならない。
+fn static_mut_read_error(name: &str, span: Span) -> Diagnostic {
+    Diagnostic::error(
+        format!("pure context cannot read mutable static '{}'", name),
+        span,
+    )
+    .with_note(MARK_IMPURE_NOTE)
+}
+
+/// 関数型 `ty` の effect だけを差し替える。
+fn with_effect(ctx: &mut TypeCtx, ty: TypeId, effect: Effect) -> TypeId {
+    match ctx.get(ty) {
+        TypeKind::Function {
+            type_params,
+            params,
+            result,
+            effect: old,
+        } if old != effect => ctx.function(type_params, params, result, effect),
+        _ => ty,
+    }
+}
+
+/// シグネチャを省略した関数 (関数リテラルを含む) の effect を本体から推論する。
+///
+/// `set`・書き込み intrinsic・`is_impure` が真を返す名前の参照のいずれかがあれば Impure。
+/// 本体内の関数リテラルも走査する（呼び出されるかどうかは見ない保守的な近似）。
+fn infer_fn_effect(f: &FnDef, is_impure: &dyn Fn(&str) -> bool) -> Effect {
+    let FnBody::Parsed(body) = &f.body else {
+        return Effect::Pure;
+    };
+    let params: BTreeSet<&str> = f.params.iter().map(|p| p.name.as_str()).collect();
+    let check = |name: &str| !params.contains(name) && is_impure(name);
+    if block_has_side_effect(body, &check) {
+        Effect::Impure
+    } else {
+        Effect::Pure
+    }
+}
+
+fn block_has_side_effect(block: &Block, is_impure: &dyn Fn(&str) -> bool) -> bool {
+    block.items.iter().any(|stmt| match stmt {
+        Stmt::Expr(e) | Stmt::ExprSemi(e, _) => prefix_has_side_effect(e, is_impure),
+        Stmt::FnDef(inner) => match &inner.body {
+            FnBody::Parsed(b) => block_has_side_effect(b, is_impure),
+            _ => false,
+        },
+        _ => false,
+    })
+}
+
+/// `check_for_expr` で本体の前後に組み立てる部分。
+enum ForBody {
+    Counting { cond: HirExpr, step: Vec<HirLine> },
+    Protocol { next_expr: HirExpr },
+}
+
+fn prefix_has_side_effect(expr: &PrefixExpr, is_impure: &dyn Fn(&str) -> bool) -> bool {
+    expr.items.iter().any(|item| match item {
+        PrefixItem::Symbol(Symbol::Set { .. }) => true,
+        PrefixItem::Symbol(Symbol::Ident(id, _, _)) => is_impure(&id.name),
+        PrefixItem::Intrinsic(intrin, _) => {
+            intrin.name == "store"
+                || intrin.name == "set_field"
+                || intrin.name == "mem_copy"
+                || intrin.name == "mem_fill"
+                || intrin
+                    .args
+                    .iter()
+                    .any(|a| prefix_has_side_effect(a, is_impure))
+        }
+        PrefixItem::Block(b, _) => block_has_side_effect(b, is_impure),
+        PrefixItem::Match(m, _) => {
+            prefix_has_side_effect(&m.scrutinee, is_impure)
+                || m.arms
+                    .iter()
+                    .any(|arm| block_has_side_effect(&arm.body, is_impure))
+        }
+        PrefixItem::For(f, _) => {
+            is_impure("next")
+                || prefix_has_side_effect(&f.iter, is_impure)
+                || block_has_side_effect(&f.body, is_impure)
+        }
+        PrefixItem::Tuple(items, _) => items.iter().any(|it| prefix_has_side_effect(it, is_impure)),
+        PrefixItem::Group(inner, _) => prefix_has_side_effect(inner, is_impure),
+        _ => false,
+    })
+}
+
+/// トップレベルのシグネチャ省略関数の effect を推論する（名前の span をキーにする）。
+///
+/// 推論済みの関数同士の呼び出しがあるため、Impure への更新がなくなるまで繰り返す。
+/// 同名の候補が混在する場合は overload 解決に任せ、すべて Impure のときだけ伝播させる。
+fn infer_top_level_effects(
+    items: &[Stmt],
+    env: &Env,
+    target: CompileTarget,
+    profile: BuildProfile,
+) -> BTreeMap<(u32, u32), Effect> {
+    let mut declared: BTreeMap<&str, Vec<Effect>> = BTreeMap::new();
+    let mut inferred: Vec<&FnDef> = Vec::new();
+    let mut mutable_statics: BTreeSet<&str> = BTreeSet::new();
+    let mut pending_if: Option<bool> = None;
+    for item in items {
+        if let Stmt::Directive(d) = item {
+            if let Some(allowed) = gate_allows(d, target, profile) {
+                pending_if = Some(allowed);
+                continue;
+            }
+        }
+        let allowed = pending_if.unwrap_or(true);
+        pending_if = None;
+        if !allowed {
+            continue;
+        }
+        if let Stmt::Static(s) = item {
+            if s.mutable {
+                mutable_statics.insert(s.name.name.as_str());
+            }
+        }
+        if let Stmt::FnDef(f) = item {
+            if f.effect_inferred {
+                inferred.push(f);
+            } else if let TypeExpr::Function { effect, .. } = &f.signature {
+                declared.entry(f.name.name.as_str()).or_default().push(*effect);
+            }
+        }
+    }
+    let key = |f: &FnDef| (f.name.span.file_id.0, f.name.span.start);
+    let mut effects: BTreeMap<(u32, u32), Effect> =
+        inferred.iter().map(|f| (key(f), Effect::Pure)).collect();
+    loop {
+        let mut changed = false;
+        for f in &inferred {
+            if effects.get(&key(f)) == Some(&Effect::Impure) {
+                continue;
+            }
+            let is_impure = |name: &str| {
+                // Reading a `static mut` is only allowed in impure functions.
+                if mutable_statics.contains(name) {
+                    return true;
+                }
+                let mut all: Vec<Effect> = declared.get(name).cloned().unwrap_or_default();
+                for g in inferred.iter().filter(|g| g.name.name == name) {
+                    all.push(effects.get(&key(g)).copied().unwrap_or(Effect::Pure));
+                }
+                for b in env.lookup_all_callables(name) {
+                    if let BindingKind::Func { effect, .. } = &b.kind {
+                        all.push(*effect);
+                    }
+                }
+                !all.is_empty() && all.iter().all(|e| matches!(e, Effect::Impure))
+            };
+            if matches!(infer_fn_effect(f, &is_impure), Effect::Impure) {
+                effects.insert(key(f), Effect::Impure);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    effects
+}
+
+/// `break` / `continue` がループの外やラベルの無いループを指していないか検査する。
+///
+/// `labels` は外側から順に、囲んでいる `while` のラベル。
+fn check_loop_exits(expr: &HirExpr, labels: &mut Vec<Option<String>>, diags: &mut Vec<Diagnostic>) {
+    match &expr.kind {
+        HirExprKind::Break(label) | HirExprKind::Continue(label) => {
+            let word = if matches!(expr.kind, HirExprKind::Break(_)) {
+                "break"
+            } else {
+                "continue"
+            };
+            match label {
+                _ if labels.is_empty() => diags.push(Diagnostic::error(
+                    format!("`{}` outside of a loop", word),
+                    expr.span,
+                )),
+                Some(name) if !labels.iter().any(|l| l.as_deref() == Some(name.as_str())) => {
+                    diags.push(Diagnostic::error(
+                        format!("`{}` refers to unknown loop label `.{}`", word, name),
+                        expr.span,
+                    ))
+                }
+                _ => {}
+            }
+        }
+        HirExprKind::While { cond, body, label } => {
+            check_loop_exits(cond, labels, diags);
+            labels.push(label.clone());
+            check_loop_exits(body, labels, diags);
+            labels.pop();
+        }
+        HirExprKind::Call { args, .. } | HirExprKind::Intrinsic { args, .. } => {
+            for a in args {
+                check_loop_exits(a, labels, diags);
+            }
+        }
+        HirExprKind::CallIndirect { callee, args, .. } => {
+            check_loop_exits(callee, labels, diags);
+            for a in args {
+                check_loop_exits(a, labels, diags);
+            }
+        }
+        HirExprKind::If {
+            cond,
+            then_branch,
+            else_branch,
+        } => {
+            check_loop_exits(cond, labels, diags);
+            check_loop_exits(then_branch, labels, diags);
+            check_loop_exits(else_branch, labels, diags);
+        }
+        HirExprKind::Match { scrutinee, arms } => {
+            check_loop_exits(scrutinee, labels, diags);
+            for arm in arms {
+                check_loop_exits(&arm.body, labels, diags);
+            }
+        }
+        HirExprKind::Block(b) => {
+            for line in &b.lines {
+                check_loop_exits(&line.expr, labels, diags);
+            }
+        }
+        HirExprKind::Let { value, .. }
+        | HirExprKind::Set { value, .. }
+        | HirExprKind::GlobalSet { value, .. }
+        | HirExprKind::AddrOf(value)
+        | HirExprKind::Deref(value)
+        | HirExprKind::Return(value) => check_loop_exits(value, labels, diags),
+        HirExprKind::EnumConstruct { payload, .. } => {
+            if let Some(p) = payload {
+                check_loop_exits(p, labels, diags);
+            }
+        }
+        HirExprKind::StructConstruct { fields: items, .. }
+        | HirExprKind::TupleConstruct { items } => {
+            for item in items {
+                check_loop_exits(item, labels, diags);
+            }
+        }
+        HirExprKind::Unit
+        | HirExprKind::LiteralI32(_)
+        | HirExprKind::LiteralF32(_)
+        | HirExprKind::LiteralI64(_)
+        | HirExprKind::LiteralF64(_)
+        | HirExprKind::LiteralBool(_)
+        | HirExprKind::LiteralStr(_)
+        | HirExprKind::StaticAddr(_)
+        | HirExprKind::Var(_)
+        | HirExprKind::FnValue(_)
+        | HirExprKind::GlobalGet(_)
+        | HirExprKind::Drop { .. } => {}
+    }
+}
+
+/// `#wasm` / `#llvmir` 本体からメモリ・グローバル・テーブルへの書き込みを探す。
+///
+/// 呼び出し先の effect はここでは見ない（名前解決が codegen 側のため）。
+fn raw_body_side_effect(body: &HirBody) -> Option<(String, Span)> {
+    match body {
+        HirBody::Block(_) => None,
+        HirBody::Wasm(wb) => {
+            for line in &wb.lines {
+                let code = line.split(";;").next().unwrap_or("");
+                for op in code.split_whitespace() {
+                    let op = op.trim_matches(|c| c == '(' || c == ')');
+                    let writes = op.contains(".store")
+                        || op.contains(".atomic.rmw")
+                        || matches!(
+                            op,
+                            "memory.grow"
+                                | "memory.fill"
+                                | "memory.copy"
+                                | "memory.init"
+                                | "data.drop"
+                                | "global.set"
+                                | "table.set"
+                                | "table.grow"
+                                | "table.fill"
+                                | "table.copy"
+                                | "table.init"
+                                | "elem.drop"
+                        );
+                    if writes {
+                        return Some((op.to_string(), wb.span));
+                    }
+                }
+            }
+            None
+        }
+        HirBody::LlvmIr(lb) => {
+            for line in &lb.lines {
+                let code = line.split(';').next().unwrap_or("");
+                for op in code.split_whitespace() {
+                    let writes = matches!(op, "store" | "atomicrmw" | "cmpxchg")
+                        || op.starts_with("@llvm.memset")
+                        || op.starts_with("@llvm.memcpy")
+                        || op.starts_with("@llvm.memmove");
+                    if writes {
+                        return Some((op.trim_end_matches('(').to_string(), lb.span));
+                    }
+                }
+            }
+            None
+        }
+    }
+}
+
+fn v128_type(ctx: &mut TypeCtx) -> TypeId {
+    match ctx.lookup_named("v128") {
+        Some(id) => id,
+        None => ctx.register_named(String::from("v128"), TypeKind::Named(String::from("v128"))),
+    }
+}
+
 fn type_storage_size_bytes(ctx: &TypeCtx, ty: TypeId) -> usize {
     match ctx.get(ctx.resolve_id(ty)) {
         TypeKind::Named(name) if name == "i64" || name == "u64" || name == "f64" => 8,
+        TypeKind::Named(name) if name == "v128" => 16,
         _ => 4,
     }
 }
@@ -5110,6 +6998,9 @@ fn type_from_expr(ctx: &mut TypeCtx, labels: &mut LabelEnv, t: &TypeExpr) -> Typ
                     if let Some(id) = labels.get(name) {
                         return *id;
                     }
+                    if let Some(id) = expand_type_alias(ctx, name, Vec::new()) {
+                        return id;
+                    }
                     if let Some(id) = ctx.lookup_named(name) {
                         id
                     } else {
@@ -5119,11 +7010,18 @@ fn type_from_expr(ctx: &mut TypeCtx, labels: &mut LabelEnv, t: &TypeExpr) -> Typ
             }
         }
         TypeExpr::Apply(base, args) => {
-            let b = type_from_expr(ctx, labels, base);
             let mut arg_tys = Vec::new();
             for a in args {
                 arg_tys.push(type_from_expr(ctx, labels, a));
             }
+            if let TypeExpr::Named(name) = base.as_ref() {
+                if !labels.contains_key(name) {
+                    if let Some(id) = expand_type_alias(ctx, name, arg_tys.clone()) {
+                        return id;
+                    }
+                }
+            }
+            let b = type_from_expr(ctx, labels, base);
             ctx.apply(b, arg_tys)
         }
         TypeExpr::Label(label) => {
@@ -5169,6 +7067,58 @@ fn type_from_expr(ctx: &mut TypeCtx, labels: &mut LabelEnv, t: &TypeExpr) -> Typ
     }
 }
 
+/// Expand the alias `name` with `args`. Omitted arguments become fresh
+/// inference variables; a wrong argument count is not an alias use.
+fn expand_type_alias(ctx: &mut TypeCtx, name: &str, args: Vec<TypeId>) -> Option<TypeId> {
+    let (params, target) = ctx.lookup_alias(name)?.clone();
+    if !args.is_empty() && args.len() != params.len() {
+        return None;
+    }
+    let mut labels = LabelEnv::new();
+    for (i, p) in params.into_iter().enumerate() {
+        let arg = match args.get(i) {
+            Some(arg) => *arg,
+            None => ctx.fresh_var(None),
+        };
+        labels.insert(p, arg);
+    }
+    Some(type_from_expr(ctx, &mut labels, &target))
+}
+
+/// Whether `t` reaches the alias `name` through alias expansion.
+fn alias_refers_to(ctx: &TypeCtx, t: &TypeExpr, name: &str, visiting: &mut Vec<String>) -> bool {
+    match t {
+        TypeExpr::Named(n) => {
+            if n == name {
+                return true;
+            }
+            if visiting.contains(n) {
+                return false;
+            }
+            match ctx.lookup_alias(n) {
+                Some((_, target)) => {
+                    visiting.push(n.clone());
+                    alias_refers_to(ctx, target, name, visiting)
+                }
+                None => false,
+            }
+        }
+        TypeExpr::Apply(base, args) => {
+            alias_refers_to(ctx, base, name, visiting)
+                || args.iter().any(|a| alias_refers_to(ctx, a, name, visiting))
+        }
+        TypeExpr::Boxed(inner) | TypeExpr::Reference(inner, _) => {
+            alias_refers_to(ctx, inner, name, visiting)
+        }
+        TypeExpr::Tuple(items) => items.iter().any(|i| alias_refers_to(ctx, i, name, visiting)),
+        TypeExpr::Function { params, result, .. } => {
+            params.iter().any(|p| alias_refers_to(ctx, p, name, visiting))
+                || alias_refers_to(ctx, result, name, visiting)
+        }
+        _ => false,
+    }
+}
+
 fn func_arity(ctx: &TypeCtx, ty: TypeId) -> usize {
     match ctx.get(ty) {
         TypeKind::Function { params, .. } => params.len(),
@@ -5261,6 +7211,31 @@ fn type_signature_matches(ctx: &TypeCtx, a: TypeId, b: TypeId) -> bool {
     function_signature_string(ctx, a) == function_signature_string(ctx, b)
 }
 
+/// `ty` with the impl's type parameters replaced by fresh variables.
+fn instantiate_impl_type(ctx: &mut TypeCtx, imp: &ImplInfo, ty: TypeId) -> TypeId {
+    if imp.type_params.is_empty() {
+        return ty;
+    }
+    let mut mapping = BTreeMap::new();
+    for tp in &imp.type_params {
+        let fresh = ctx.fresh_var(None);
+        mapping.insert(ctx.resolve_id(*tp), fresh);
+    }
+    ctx.substitute(ty, &mapping)
+}
+
+/// Whether an impl target mentions type variables other than the impl's own
+/// parameters (`impl <.T> Tr for Box<.T>` is fine, `impl Tr for Box<.T>` is not).
+fn impl_target_is_open(ctx: &mut TypeCtx, ty: TypeId, type_params: &[TypeId]) -> bool {
+    let unit = ctx.unit();
+    let mapping = type_params
+        .iter()
+        .map(|tp| (ctx.resolve_id(*tp), unit))
+        .collect();
+    let closed = ctx.substitute(ty, &mapping);
+    type_contains_unbound_var(ctx, closed)
+}
+
 fn type_contains_unbound_var(ctx: &TypeCtx, ty: TypeId) -> bool {
     let ty = ctx.resolve_id(ty);
     match ctx.get(ty) {
@@ -5271,7 +7246,9 @@ fn type_contains_unbound_var(ctx: &TypeCtx, ty: TypeId) -> bool {
         | TypeKind::Bool
         | TypeKind::Str
         | TypeKind::Never
-        | TypeKind::Named(_) => false,
+        | TypeKind::Error
+        | TypeKind::Named(_)
+        | TypeKind::Newtype { .. } => false,
         TypeKind::Var(tv) => tv.binding.is_none(),
         TypeKind::Enum { type_params, .. } | TypeKind::Struct { type_params, .. } => {
             !type_params.is_empty()
@@ -5342,6 +7319,99 @@ fn profile_allows(profile: &str, active: BuildProfile) -> bool {
     }
 }
 
+/// Checks that an `#export`ed function can be called from the host: it must
+/// be monomorphic and every parameter/result must have a wasm value.
+fn check_export(
+    ctx: &TypeCtx,
+    def: &FnDef,
+    func: &HirFunction,
+    export: &FnExport,
+    entry: Option<&str>,
+    exports: &[HirExport],
+) -> Option<Diagnostic> {
+    // The entry is already exported as `main`, `_start` and its own name.
+    let reserved = matches!(export.name.as_str(), "memory" | "__nepl_alloc")
+        || entry.is_some_and(|e| matches!(export.name.as_str(), "main" | "_start") || e == export.name);
+    if reserved {
+        return Some(Diagnostic::error(
+            format!("export name '{}' is reserved", export.name),
+            export.span,
+        ));
+    }
+    if let Some(prev) = exports.iter().find(|e| e.name == export.name) {
+        return Some(
+            Diagnostic::error(format!("duplicate export name '{}'", export.name), export.span)
+                .with_secondary_label(prev.span, Some("first exported here".to_string())),
+        );
+    }
+    if !def.type_params.is_empty() {
+        return Some(Diagnostic::error(
+            format!("cannot export generic function '{}'", def.name.name),
+            export.span,
+        ));
+    }
+    for p in &func.params {
+        if let Some(reason) = export_abi_violation(ctx, p.ty, false) {
+            return Some(
+                Diagnostic::error(
+                    format!(
+                        "cannot export '{}': parameter '{}' has type '{}'",
+                        def.name.name,
+                        p.name,
+                        ctx.type_to_display(p.ty)
+                    ),
+                    export.span,
+                )
+                .with_note(reason),
+            );
+        }
+    }
+    if let Some(reason) = export_abi_violation(ctx, func.result, true) {
+        return Some(
+            Diagnostic::error(
+                format!(
+                    "cannot export '{}': result type '{}'",
+                    def.name.name,
+                    ctx.type_to_display(func.result)
+                ),
+                export.span,
+            )
+            .with_note(reason),
+        );
+    }
+    None
+}
+
+fn export_abi_violation(ctx: &TypeCtx, ty: TypeId, is_result: bool) -> Option<&'static str> {
+    match ctx.get(ctx.resolve_id(ty)) {
+        TypeKind::I32
+        | TypeKind::U8
+        | TypeKind::F32
+        | TypeKind::Bool
+        | TypeKind::Str
+        | TypeKind::Struct { .. }
+        | TypeKind::Enum { .. }
+        | TypeKind::Tuple { .. }
+        | TypeKind::Apply { .. } => None,
+        TypeKind::Named(name) if name == "i64" || name == "f64" => None,
+        TypeKind::Named(name) if name == "v128" => {
+            Some("v128 has no host representation; pass a pointer to 16 bytes instead")
+        }
+        TypeKind::Unit | TypeKind::Never if is_result => None,
+        TypeKind::Unit | TypeKind::Never => Some("unit parameters have no wasm value"),
+        TypeKind::Function { .. } => {
+            Some("function values are table indices that only make sense inside the module")
+        }
+        TypeKind::Reference(..) | TypeKind::Box(_) => {
+            Some("references and boxes are owned by the module; pass a struct or str instead")
+        }
+        TypeKind::Var(_) | TypeKind::Named(_) | TypeKind::Error => {
+            Some("the type has no fixed wasm layout")
+        }
+        TypeKind::Newtype { inner, .. } => export_abi_violation(ctx, inner, is_result),
+    }
+}
+
 fn gate_allows(
     d: &Directive,
     target: CompileTarget,
@@ -5352,6 +7422,9 @@ fn gate_allows(
         Directive::IfProfile { profile, .. } => {
             Some(profile_allows(profile.as_str(), active_profile))
         }
+        // Gates that hold are removed by `consteval::resolve_const_gates`;
+        // any remaining one excludes its item.
+        Directive::IfConst { .. } => Some(false),
         _ => None,
     }
 }
@@ -5363,6 +7436,8 @@ enum AssignKind {
     Store(HirExpr),
     AddrOf,
     Deref,
+    Return,
+    Try,
 }
 
 #[derive(Debug, Clone)]
@@ -8,7 +8,7 @@ use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
-use crate::ast::Effect;
+use crate::ast::{Effect, TypeExpr};
 
 /// Identifier for a type stored in the arena.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -23,6 +23,10 @@ pub enum TypeKind {
     Bool,
     Str,
     Never,
+    /// Type of an expression that already failed to typecheck. It unifies
+    /// with every type, so the mistake is reported once and not again at
+    /// each place the value flows to.
+    Error,
     Named(String),
     Enum {
         name: String,
@@ -51,6 +55,12 @@ pub enum TypeKind {
     },
     Box(TypeId),
     Reference(TypeId, bool),
+    /// `newtype Name = inner`: distinct from `inner` while typechecking,
+    /// erased to `inner` before monomorphization.
+    Newtype {
+        name: String,
+        inner: TypeId,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -76,7 +86,10 @@ pub struct TypeCtx {
     bool_ty: TypeId,
     str_ty: TypeId,
     never_ty: TypeId,
+    error_ty: TypeId,
     named: alloc::collections::BTreeMap<alloc::string::String, TypeId>,
+    /// `type Name<params> = target`, keyed by alias name.
+    aliases: alloc::collections::BTreeMap<String, (Vec<String>, TypeExpr)>,
 }
 
 static GLOBAL_UNIFY_DEPTH: AtomicUsize = AtomicUsize::new(0);
@@ -105,6 +118,8 @@ impl TypeCtx {
         arena.push(TypeKind::Str);
         let never_ty = TypeId(arena.len());
         arena.push(TypeKind::Never);
+        let error_ty = TypeId(arena.len());
+        arena.push(TypeKind::Error);
 
         Self {
             arena,
@@ -115,7 +130,9 @@ impl TypeCtx {
             bool_ty,
             str_ty,
             never_ty,
+            error_ty,
             named: alloc::collections::BTreeMap::new(),
+            aliases: alloc::collections::BTreeMap::new(),
         }
     }
 
@@ -140,6 +157,23 @@ impl TypeCtx {
     pub fn never(&self) -> TypeId {
         self.never_ty
     }
+    pub fn error(&self) -> TypeId {
+        self.error_ty
+    }
+    /// Whether `ty` is, or contains, the error type.
+    pub fn contains_error(&self, ty: TypeId) -> bool {
+        let ty = self.resolve_id(ty);
+        match &self.arena[ty.0] {
+            TypeKind::Error => true,
+            TypeKind::Tuple { items } => items.iter().any(|t| self.contains_error(*t)),
+            TypeKind::Function { params, result, .. } => {
+                params.iter().any(|t| self.contains_error(*t)) || self.contains_error(*result)
+            }
+            TypeKind::Apply { args, .. } => args.iter().any(|t| self.contains_error(*t)),
+            TypeKind::Box(inner) | TypeKind::Reference(inner, _) => self.contains_error(*inner),
+            _ => false,
+        }
+    }
 
     pub fn fresh_var(&mut self, label: Option<alloc::string::String>) -> TypeId {
         let id = TypeId(self.arena.len());
@@ -173,6 +207,33 @@ impl TypeCtx {
         self.named.get(name).copied()
     }
 
+    /// Register `type name<params> = target`. The target is kept as a type
+    /// expression and expanded at each use.
+    pub fn register_alias(&mut self, name: String, params: Vec<String>, target: TypeExpr) {
+        self.aliases.insert(name, (params, target));
+    }
+
+    pub fn lookup_alias(&self, name: &str) -> Option<&(Vec<String>, TypeExpr)> {
+        self.aliases.get(name)
+    }
+
+    pub fn remove_alias(&mut self, name: &str) {
+        self.aliases.remove(name);
+    }
+
+    /// Replace every newtype with its inner type. Run once typechecking is
+    /// done so that later passes only see the shared representation.
+    pub fn erase_newtypes(&mut self) {
+        for i in 0..self.arena.len() {
+            if let TypeKind::Newtype { inner, .. } = self.arena[i] {
+                self.arena[i] = TypeKind::Var(TypeVar {
+                    label: None,
+                    binding: Some(inner),
+                });
+            }
+        }
+    }
+
     pub fn function(
         &mut self,
         type_params: Vec<TypeId>,
@@ -234,7 +295,8 @@ impl TypeCtx {
             | TypeKind::F32
             | TypeKind::Bool
             | TypeKind::Str
-            | TypeKind::Never => true,
+            | TypeKind::Never
+            | TypeKind::Error => true,
             TypeKind::Reference(_, _) => true,
             TypeKind::Box(_) => false,
             TypeKind::Enum { .. } => false,
@@ -249,7 +311,8 @@ impl TypeCtx {
                     false
                 }
             }
-            TypeKind::Named(name) => matches!(name.as_str(), "i64" | "f64"),
+            TypeKind::Named(name) => matches!(name.as_str(), "i64" | "f64" | "v128"),
+            TypeKind::Newtype { inner, .. } => self.is_copy_inner(*inner, seen),
         }
     }
 
@@ -296,6 +359,10 @@ impl TypeCtx {
         let bk = self.arena[rb.0].clone();
 
         match (ak, bk) {
+            // the error has been reported; keep the other side so later
+            // constraints still see a real type
+            (TypeKind::Error, _) => Ok(b),
+            (_, TypeKind::Error) => Ok(a),
             (TypeKind::Var(_), TypeKind::Never) => Ok(a),
             (TypeKind::Never, TypeKind::Var(_)) => Ok(b),
             (TypeKind::Var(va), TypeKind::Var(vb)) => {
@@ -472,6 +539,14 @@ impl TypeCtx {
                     Err(UnifyError::Mismatch)
                 }
             }
+            (TypeKind::Named(na), TypeKind::Newtype { name: nb, .. })
+            | (TypeKind::Newtype { name: na, .. }, TypeKind::Named(nb)) => {
+                if na == nb {
+                    Ok(a)
+                } else {
+                    Err(UnifyError::Mismatch)
+                }
+            }
             (TypeKind::Box(inner_a), TypeKind::Box(inner_b)) => {
                 self.unify(inner_a, inner_b)?;
                 Ok(a)
@@ -668,8 +743,10 @@ impl TypeCtx {
             | TypeKind::F32
             | TypeKind::Bool
             | TypeKind::Str
-            | TypeKind::Never => ty,
+            | TypeKind::Never
+            | TypeKind::Error => ty,
             TypeKind::Named(_) => ty,
+            TypeKind::Newtype { .. } => ty,
             TypeKind::Var(_) => ty,
             TypeKind::Enum {
                 name,
@@ -866,6 +943,86 @@ impl TypeCtx {
         self.type_to_string_inner(ty, &mut seen)
     }
 
+    /// Render a type in NEPL surface syntax (`Option<i32>`, `(i32,bool)->i32`)
+    /// for diagnostics. Unlike `type_to_string`, this is not used for mangling.
+    pub fn type_to_display(&self, ty: TypeId) -> String {
+        let mut seen = BTreeSet::new();
+        self.type_to_display_inner(ty, &mut seen)
+    }
+
+    fn type_to_display_inner(&self, ty: TypeId, seen: &mut BTreeSet<TypeId>) -> String {
+        let ty = self.resolve_id(ty);
+        if !seen.insert(ty) {
+            return String::from("...");
+        }
+        let join = |this: &Self, items: &[TypeId], seen: &mut BTreeSet<TypeId>| -> String {
+            let parts: Vec<String> = items
+                .iter()
+                .map(|t| this.type_to_display_inner(*t, seen))
+                .collect();
+            parts.join(",")
+        };
+        let res = match self.get(ty) {
+            TypeKind::Unit => String::from("()"),
+            TypeKind::I32 => String::from("i32"),
+            TypeKind::U8 => String::from("u8"),
+            TypeKind::F32 => String::from("f32"),
+            TypeKind::Bool => String::from("bool"),
+            TypeKind::Str => String::from("str"),
+            TypeKind::Never => String::from("never"),
+            TypeKind::Error => String::from("{error}"),
+            TypeKind::Named(name) => name,
+            TypeKind::Newtype { name, .. } => name,
+            TypeKind::Enum {
+                name, type_params, ..
+            }
+            | TypeKind::Struct {
+                name, type_params, ..
+            } => {
+                if type_params.is_empty() {
+                    name
+                } else {
+                    format!("{}<{}>", name, join(self, &type_params, seen))
+                }
+            }
+            TypeKind::Tuple { items } => format!("({})", join(self, &items, seen)),
+            TypeKind::Function {
+                params,
+                result,
+                effect,
+                ..
+            } => {
+                let arrow = match effect {
+                    Effect::Pure => "->",
+                    Effect::Impure => "*>",
+                };
+                format!(
+                    "({}){}{}",
+                    join(self, &params, seen),
+                    arrow,
+                    self.type_to_display_inner(result, seen)
+                )
+            }
+            TypeKind::Var(tv) => tv.label.unwrap_or_else(|| String::from("_")),
+            TypeKind::Apply { base, args } => format!(
+                "{}<{}>",
+                self.type_to_display_inner(base, seen),
+                join(self, &args, seen)
+            ),
+            TypeKind::Box(inner) => format!("Box<{}>", self.type_to_display_inner(inner, seen)),
+            TypeKind::Reference(inner, is_mut) => {
+                let inner = self.type_to_display_inner(inner, seen);
+                if is_mut {
+                    format!("&mut {}", inner)
+                } else {
+                    format!("&{}", inner)
+                }
+            }
+        };
+        seen.remove(&ty);
+        res
+    }
+
     fn type_to_string_inner(&self, ty: TypeId, seen: &mut BTreeSet<TypeId>) -> String {
         let ty = self.resolve_id(ty);
         if !seen.insert(ty) {
@@ -880,7 +1037,9 @@ impl TypeCtx {
             TypeKind::Bool => String::from("bool"),
             TypeKind::Str => String::from("str"),
             TypeKind::Never => String::from("never"),
+            TypeKind::Error => String::from("{error}"),
             TypeKind::Named(name) => name.clone(),
+            TypeKind::Newtype { name, .. } => name.clone(),
             TypeKind::Enum {
                 name,
                 type_params,
@@ -1022,7 +1181,9 @@ impl TypeCtx {
             | TypeKind::Bool
             | TypeKind::Str
             | TypeKind::Never
-            | TypeKind::Named(_) => false,
+            | TypeKind::Error
+            | TypeKind::Named(_)
+            | TypeKind::Newtype { .. } => false,
             TypeKind::Var(tv) => {
                 if let Some(b) = tv.binding {
                     self.occurs_in(var, b, seen)
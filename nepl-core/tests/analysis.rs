@@ -0,0 +1,234 @@
+use nepl_core::analysis::names::{NameDefKind, ShadowEventKind, ShadowSeverity};
+use nepl_core::analysis::{
+    analyze_lex, analyze_loaded_name_resolution, analyze_loaded_semantics, analyze_name_resolution,
+    analyze_semantics, LineIndex, LoadedSources, NameResolutionOptions,
+};
+use nepl_core::loader::Loader;
+
+const SHADOW_SRC: &str = "fn f <(i32)->i32> (x):\n    let y x;\n    let z block:\n        let y 2;\n        y\n    y\n\nfn main <()->i32> ():\n    f 1\n";
+
+#[test]
+fn line_index_counts_chars() {
+    let src = "ab\nあいx\n";
+    let index = LineIndex::new(src);
+    assert_eq!(index.line_col(0), (0, 0));
+    assert_eq!(index.line_col(3), (1, 0));
+    // 'x' starts after two 3-byte chars
+    assert_eq!(index.line_col(9), (1, 2));
+}
+
+#[test]
+fn lex_reports_tokens() {
+    let result = analyze_lex("fn main <()->i32> ():\n    0\n");
+    assert!(result.ok);
+    assert_eq!(result.tokens[0].kind, "KwFn");
+    assert!(result.tokens.iter().any(|t| t.value.as_deref() == Some("main")));
+}
+
+#[test]
+fn references_pick_nearest_scope() {
+    let result = analyze_name_resolution(SHADOW_SRC, NameResolutionOptions::default());
+    assert!(result.ok, "{:?}", result.diagnostics);
+    let res = &result.resolution;
+
+    let ys: Vec<_> = res.definitions.iter().filter(|d| d.name == "y").collect();
+    assert_eq!(ys.len(), 2);
+    assert!(ys.iter().all(|d| d.kind == NameDefKind::LetHoisted));
+    let (outer, inner) = (ys[0], ys[1]);
+    assert!(inner.scope_depth > outer.scope_depth);
+
+    let y_refs: Vec<_> = res.references.iter().filter(|r| r.name == "y").collect();
+    assert_eq!(y_refs.len(), 2);
+    assert_eq!(y_refs[0].resolved_def_id, Some(inner.id));
+    assert_eq!(y_refs[0].candidate_def_ids, vec![inner.id, outer.id]);
+    assert_eq!(y_refs[1].resolved_def_id, Some(outer.id));
+
+    assert!(res.shadows.iter().any(|s| s.event_kind == ShadowEventKind::DefinitionShadow
+        && s.selected_def_id == Some(inner.id)
+        && s.shadowed_def_ids == vec![outer.id]));
+    assert_eq!(res.by_name["y"].definitions, vec![outer.id, inner.id]);
+}
+
+#[test]
+fn scopes_form_a_tree() {
+    let result = analyze_name_resolution(SHADOW_SRC, NameResolutionOptions::default());
+    let res = &result.resolution;
+    assert_eq!(res.scopes[0].parent, None);
+    for scope in &res.scopes[1..] {
+        let parent = &res.scopes[scope.parent.expect("parent")];
+        assert_eq!(parent.depth + 1, scope.depth);
+        assert!(parent.span.contains(&scope.span));
+    }
+    for d in &res.definitions {
+        let scope = &res.scopes[d.scope_id];
+        assert_eq!(scope.depth, d.scope_depth);
+        assert!(scope.span.contains(&d.span), "{} outside its scope", d.name);
+    }
+}
+
+#[test]
+fn important_shadow_warning_is_optional() {
+    let src = "fn main <()->i32> ():\n    let add 1\n    add\n";
+    let warned = analyze_name_resolution(src, NameResolutionOptions::default());
+    assert!(warned.resolution.shadows.iter().any(|s| s.name == "add"
+        && s.event_kind == ShadowEventKind::ImportantName
+        && s.severity == ShadowSeverity::Warning));
+
+    let quiet = analyze_name_resolution(
+        src,
+        NameResolutionOptions {
+            warn_important_shadow: false,
+        },
+    );
+    assert!(quiet.resolution.shadows.is_empty());
+    assert!(!quiet.resolution.policy.warn_important_shadow);
+}
+
+#[test]
+fn semantics_reports_types() {
+    let result = analyze_semantics(SHADOW_SRC);
+    assert!(result.ok, "{:?}", result.diagnostics);
+    assert!(result.functions.iter().any(|f| f.name.starts_with("main")));
+
+    let call = result
+        .expressions
+        .iter()
+        .find(|e| e.kind == "Call")
+        .expect("call to f");
+    assert_eq!(call.inferred_type, "i32");
+    assert_eq!(call.argument_ranges.len(), 1);
+
+    // the `1` in `f 1` is the first argument of that call
+    let one = result
+        .tokens
+        .iter()
+        .rposition(|t| t.value.as_deref() == Some("1"))
+        .expect("literal token");
+    let sem = &result.token_semantics[one];
+    assert_eq!(sem.arg_index, Some(0));
+    assert_eq!(sem.inferred_type.as_deref(), Some("i32"));
+
+    let f_tok = one - 1;
+    let res = &result.token_resolution[f_tok];
+    assert_eq!(res.name.as_deref(), Some("f"));
+    assert_eq!(res.resolved_def_id, Some(0));
+}
+
+#[test]
+fn semantics_serializes_to_json() {
+    let result = analyze_semantics(SHADOW_SRC);
+    let json = serde_json::to_value(&result).unwrap();
+    assert_eq!(json["stage"], "semantics");
+    assert_eq!(json["name_resolution"]["definitions"][0]["kind"], "fn");
+    assert_eq!(
+        json["name_resolution"]["policy"]["selection"],
+        "nearest_scope_first"
+    );
+    assert!(json["tokens"][0]["span"]["start_line"].is_u64());
+}
+
+#[test]
+fn parse_errors_are_reported() {
+    let result = analyze_semantics("fn main <()->i32> (:\n");
+    assert!(!result.ok);
+    assert!(result.diagnostics.iter().any(|d| d.severity == "error"));
+    assert!(result.expressions.is_empty());
+}
+
+fn load_project(files: &[(&str, &str)]) -> LoadedSources {
+    let dir = tempfile::tempdir().unwrap();
+    for (name, src) in files {
+        std::fs::write(dir.path().join(name), src).unwrap();
+    }
+    let mut loader = Loader::new(dir.path().join("stdlib"));
+    let result = loader.load(&dir.path().join(files[0].0));
+    LoadedSources::new(result, &loader)
+}
+
+const UTIL_SRC: &str = "fn one <()->i32> ():\n    1\n";
+const MAIN_SRC: &str = "#import \"./util\" as *\n\nfn main <()->i32> ():\n    let x one;\n    x\n";
+
+#[test]
+fn loaded_resolution_jumps_into_imports() {
+    let loaded = load_project(&[("main.nepl", MAIN_SRC), ("util.nepl", UTIL_SRC)]);
+    let result = analyze_loaded_name_resolution(&loaded, NameResolutionOptions::default());
+    assert!(result.ok, "{:?}", result.diagnostics);
+    let res = &result.resolution;
+    assert_eq!(res.files.len(), 2);
+    assert!(res.files[1].path.ends_with("util.nepl"));
+
+    let one = res.references.iter().find(|r| r.name == "one").expect("ref");
+    let def = one.resolved_def.as_ref().expect("resolved");
+    assert_eq!(def.span.file_id, 1);
+    assert_eq!((def.span.start_line, def.span.start_col), (0, 3));
+    assert!(def.path.ends_with("util.nepl"));
+    // only the entry file is walked
+    assert!(res.references.iter().all(|r| r.span.file_id == 0));
+}
+
+#[test]
+fn loaded_semantics_reports_entry_file() {
+    let loaded = load_project(&[("main.nepl", MAIN_SRC), ("util.nepl", UTIL_SRC)]);
+    let result = analyze_loaded_semantics(&loaded);
+    assert!(result.ok, "{:?}", result.diagnostics);
+    assert!(result.functions.iter().all(|f| f.span.file_id == 0));
+    assert!(result.expressions.iter().all(|e| e.span.file_id == 0));
+
+    let tok = result
+        .tokens
+        .iter()
+        .position(|t| t.value.as_deref() == Some("one"))
+        .expect("token");
+    let target = result.token_resolution[tok]
+        .definition
+        .as_ref()
+        .expect("jump target");
+    assert!(target.path.ends_with("util.nepl"));
+    assert_eq!(target.kind, NameDefKind::Fn);
+}
+
+#[test]
+fn missing_import_keeps_local_names() {
+    let loaded = load_project(&[("main.nepl", MAIN_SRC)]);
+    assert!(!loaded.merged);
+    let result = analyze_loaded_name_resolution(&loaded, NameResolutionOptions::default());
+    assert!(!result.ok);
+    assert!(result
+        .diagnostics
+        .iter()
+        .any(|d| d.message.contains("cannot find module")));
+    let x = result
+        .resolution
+        .references
+        .iter()
+        .find(|r| r.name == "x")
+        .expect("x");
+    assert!(x.resolved_def_id.is_some());
+}
+
+#[test]
+fn parse_errors_keep_imported_names() {
+    // the missing `:` and the unclosed `(` must not hide the import
+    let src = "#import \"./util\" as *\n\nfn f <(i32)->i32> (x)\n    x\n\nfn main <()->i32> ():\n    let x (one;\n    x\n";
+    let loaded = load_project(&[("main.nepl", src), ("util.nepl", UTIL_SRC)]);
+    assert!(loaded.merged);
+    let result = analyze_loaded_semantics(&loaded);
+    assert!(!result.ok);
+    let errors: Vec<&str> = result
+        .diagnostics
+        .iter()
+        .filter(|d| d.severity == "error")
+        .map(|d| d.message.as_str())
+        .collect();
+    assert!(errors.iter().all(|m| !m.contains("undefined identifier")), "{errors:?}");
+    let tok = result
+        .tokens
+        .iter()
+        .position(|t| t.value.as_deref() == Some("one"))
+        .expect("token");
+    let target = result.token_resolution[tok]
+        .definition
+        .as_ref()
+        .expect("jump target");
+    assert!(target.path.ends_with("util.nepl"));
+}
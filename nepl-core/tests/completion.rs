@@ -0,0 +1,139 @@
+use nepl_core::analysis::completion::{
+    blank_cursor_line, complete, complete_loaded, CompletionContext, CompletionKind,
+};
+use nepl_core::analysis::LoadedSources;
+use nepl_core::loader::Loader;
+
+/// Complete at the `|` marker of `src`.
+fn complete_at(src: &str) -> nepl_core::analysis::CompletionAnalysis {
+    let offset = src.find('|').expect("cursor marker");
+    let source = src.replacen('|', "", 1);
+    let modules = vec![
+        "core/math".to_string(),
+        "std/stdio".to_string(),
+        "std/fs".to_string(),
+    ];
+    complete(&source, offset as u32, &modules)
+}
+
+fn labels(result: &nepl_core::analysis::CompletionAnalysis) -> Vec<&str> {
+    result.items.iter().map(|i| i.label.as_str()).collect()
+}
+
+const SRC: &str = "struct Point:
+    x <i32>
+    y <i32>
+
+enum Shape:
+    Dot
+    Circle <i32>
+
+//: twice: 2 倍にする
+fn twice <(i32)->i32> (n):
+    n
+
+fn main <()->i32> ():
+    let p Point 1 2;
+    let count <i32> 3;
+|
+";
+
+#[test]
+fn names_in_scope() {
+    let src = SRC.replace("\n|\n", "\n    c|\n");
+    let result = complete_at(&src);
+    assert_eq!(result.context, CompletionContext::Identifier);
+    assert_eq!(result.prefix, "c");
+    assert_eq!(labels(&result), vec!["count"]);
+    assert_eq!(result.items[0].kind, CompletionKind::Variable);
+    assert_eq!(result.items[0].detail.as_deref(), Some("i32"));
+    assert_eq!(result.range.start_line, 15);
+    assert_eq!((result.range.start_col, result.range.end_col), (4, 5));
+}
+
+#[test]
+fn functions_carry_signature_and_doc() {
+    let src = SRC.replace("\n|\n", "\n    |\n");
+    let result = complete_at(&src);
+    let names = labels(&result);
+    assert!(names.contains(&"p") && names.contains(&"count"));
+    // parameters of other functions are not visible
+    assert!(!names.contains(&"n"));
+    let twice = result
+        .items
+        .iter()
+        .find(|i| i.label == "twice")
+        .expect("twice");
+    assert_eq!(twice.kind, CompletionKind::Function);
+    assert_eq!(twice.detail.as_deref(), Some("<(i32)->i32>"));
+    assert_eq!(twice.documentation.as_deref(), Some("2 倍にする"));
+}
+
+#[test]
+fn fields_after_dot() {
+    let src = SRC.replace("\n|\n", "\n    p.|\n");
+    let result = complete_at(&src);
+    assert_eq!(result.context, CompletionContext::Field);
+    assert_eq!(labels(&result), vec!["x", "y"]);
+    assert_eq!(result.items[0].detail.as_deref(), Some("i32"));
+}
+
+#[test]
+fn variants_after_path_and_in_match_arms() {
+    let src = SRC.replace("\n|\n", "\n    Shape::C|\n");
+    let result = complete_at(&src);
+    assert_eq!(result.context, CompletionContext::Variant);
+    assert_eq!(labels(&result), vec!["Circle"]);
+    assert_eq!(result.items[0].detail.as_deref(), Some("i32"));
+
+    let src = SRC.replace(
+        "\n|\n",
+        "\n    let s Shape::Dot;\n    match s:\n        |\n",
+    );
+    let result = complete_at(&src);
+    assert_eq!(result.context, CompletionContext::Variant);
+    assert_eq!(labels(&result), vec!["Shape::Dot", "Shape::Circle"]);
+}
+
+#[test]
+fn directives_and_module_paths() {
+    let result = complete_at("#im|\n");
+    assert_eq!(result.context, CompletionContext::Directive);
+    assert_eq!(labels(&result), vec!["import"]);
+
+    let result = complete_at("#import \"std/|\n");
+    assert_eq!(result.context, CompletionContext::ModulePath);
+    assert_eq!(result.prefix, "std/");
+    assert_eq!(labels(&result), vec!["std/stdio", "std/fs"]);
+}
+
+#[test]
+fn nothing_inside_comments_and_strings() {
+    assert_eq!(complete_at("// tw|\n").context, CompletionContext::None);
+    assert!(complete_at("fn main <()->str> ():\n    \"tw|\"\n")
+        .items
+        .is_empty());
+}
+
+#[test]
+fn loaded_completion_lists_imported_functions() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("util.nepl"),
+        "//: one: 1 を返す\nfn one <()->i32> ():\n    1\n",
+    )
+    .unwrap();
+    let source = "#import \"./util\" as *\n\nfn main <()->i32> ():\n    o\n";
+    let offset = source.find("    o").unwrap() as u32 + 5;
+    let main = dir.path().join("main.nepl");
+    std::fs::write(&main, blank_cursor_line(source, offset)).unwrap();
+
+    let mut loader = Loader::new(dir.path().join("stdlib"));
+    let result = loader.load(&main);
+    let loaded = LoadedSources::new(result, &loader);
+    assert!(loaded.merged);
+    let result = complete_loaded(source, offset, &loaded, &[]);
+    let one = result.items.iter().find(|i| i.label == "one").expect("one");
+    assert_eq!(one.documentation.as_deref(), Some("1 を返す"));
+    assert!(one.path.as_deref().unwrap().ends_with("util.nepl"));
+}
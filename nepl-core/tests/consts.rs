@@ -0,0 +1,226 @@
+mod harness;
+use harness::{
+    compile_diags, compile_src, find_error, run_main_capture_stdout, run_main_i32,
+    run_main_wasi_i32,
+};
+use wasmparser::{Operator, Parser, Payload};
+
+#[test]
+fn const_items_are_evaluated_through_pure_functions() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasi
+#import "core/math" as *
+
+const BASE <i32> 40
+
+const fn bump <(i32)->i32> (x):
+    add x 1
+
+const ANSWER <i32> bump bump BASE
+
+fn main <()*>i32> ():
+    ANSWER
+"#;
+    assert_eq!(run_main_wasi_i32(src), 42);
+}
+
+#[test]
+fn const_aggregates_are_placed_in_static_data() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasi
+#import "core/math" as *
+#import "core/option" as *
+#import "std/stdio" as *
+
+struct Pos:
+    x <i32>
+    y <i32>
+
+struct Label:
+    name <str>
+    pos <Pos>
+
+const HOME <Label> Label "home" Pos 3 4
+const LIMIT <Option<i32>> Option::Some 5
+
+fn main <()*>i32> ():
+    let l <Label> HOME;
+    print get l "name";
+    let p <Pos> get l "pos";
+    let n <i32> match LIMIT:
+        Some v:
+            v
+        None:
+            0
+    print_i32 add add get p "x" get p "y" n;
+    0
+"#;
+    assert_eq!(run_main_capture_stdout(src), "home12");
+}
+
+#[test]
+fn const_items_are_usable_as_type_arguments() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/mem" as *
+
+const SMALL <i32> 3
+const LARGE <i32> mul SMALL 10
+
+fn scaled <.N> <(i32)->i32> (x):
+    mul x const_value<.N, i32>
+
+fn main <()->i32> ():
+    add scaled<SMALL> 2 scaled<LARGE> 1
+"#;
+    assert_eq!(run_main_i32(src), 36);
+}
+
+#[test]
+fn const_value_rejects_types_that_are_not_consts() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/mem" as *
+
+const FLAG <bool> true
+
+fn scaled <.N> <(i32)->i32> (x):
+    mul x const_value<.N, i32>
+
+fn main <()->i32> ():
+    add scaled<FLAG> 1 scaled<i32> 1
+"#;
+    let diags = compile_diags(src);
+    find_error(&diags, "const_value needs a const item of type 'i32', found 'FLAG'");
+    find_error(&diags, "const_value needs a const item of type 'i32', found 'i32'");
+}
+
+#[test]
+fn wide_scalar_consts_are_folded() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+
+const BIG <i64> i64_mul i64_extend_i32_s 1000000 i64_extend_i32_s 1000000
+const HALF <f64> f64_div f64_convert_i32_s 7 f64_convert_i32_s 2
+
+fn main <()->i32> ():
+    add i32_wrap_i64 i64_div_s BIG i64_extend_i32_s 100000000 i32_trunc_f64_s HALF
+"#;
+    assert_eq!(run_main_i32(src), 10003);
+    let mut consts = Vec::new();
+    for payload in Parser::new(0).parse_all(&compile_src(src)) {
+        if let Ok(Payload::CodeSectionEntry(body)) = payload {
+            let mut reader = body.get_operators_reader().expect("operators");
+            while !reader.eof() {
+                match reader.read().expect("operator") {
+                    Operator::I64Const { value } => consts.push(value as f64),
+                    Operator::F64Const { value } => consts.push(f64::from_bits(value.bits())),
+                    _ => {}
+                }
+            }
+        }
+    }
+    assert!(consts.contains(&1e12), "{consts:?}");
+    assert!(consts.contains(&3.5), "{consts:?}");
+}
+
+#[test]
+fn const_gate_selects_items() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasi
+#import "core/math" as *
+
+const LEVEL <i32> 2
+const VERBOSE <bool> gt LEVEL 1
+const QUIET <bool> lt LEVEL 1
+
+#if[const=VERBOSE]
+fn pick <()->i32> ():
+    7
+
+#if[const=QUIET]
+fn pick <()->i32> ():
+    9
+
+fn main <()*>i32> ():
+    pick
+"#;
+    assert_eq!(run_main_wasi_i32(src), 7);
+}
+
+#[test]
+fn unknown_const_gate_is_reported() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+#if[const=MISSING]
+fn pick <()->i32> ():
+    7
+
+fn main <()->i32> ():
+    0
+"#;
+    let diags = compile_diags(src);
+    find_error(&diags, "unknown const 'MISSING' in `#if[const=...]`");
+}
+
+#[test]
+fn const_fn_must_be_pure() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasi
+#import "std/stdio" as *
+
+const fn noisy <(i32)*>i32> (x):
+    println_i32 x;
+    x
+
+fn main <()*>i32> ():
+    noisy 1
+"#;
+    let diags = compile_diags(src);
+    find_error(&diags, "const fn 'noisy' must be pure");
+}
+
+#[test]
+fn failing_const_evaluation_points_at_the_cause() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+
+const BAD <i32> div_s 1 0
+
+fn main <()->i32> ():
+    BAD
+"#;
+    let diags = compile_diags(src);
+    let d = find_error(&diags, "cannot evaluate const 'BAD' at compile time");
+    assert!(
+        d.secondary.iter().any(|l| l
+            .message
+            .as_deref()
+            .is_some_and(|m| m.contains("division by zero"))),
+        "{:?}",
+        d.secondary
+    );
+}
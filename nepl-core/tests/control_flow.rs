@@ -0,0 +1,163 @@
+mod harness;
+use harness::{compile_diags, compile_src, find_error, run_main_i32};
+
+#[test]
+fn return_break_and_continue_leave_early() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+
+fn find <(i32)*>i32> (target):
+    let mut i 0
+    while lt i 100:
+        do:
+            if eq mul i i target then return i else ()
+            set i add i 1
+    -1
+
+fn sum_odd <(i32)*>i32> (n):
+    let mut i 0
+    let mut acc 0
+    while true:
+        do:
+            set i add i 1
+            if gt i n then break else ()
+            if eq 0 i32_rem_s i 2 then continue else ()
+            set acc add acc i
+    acc
+
+fn main <()*>i32> ():
+    add mul 100 find 49 sum_odd 9
+"#;
+    assert_eq!(run_main_i32(src), 725);
+}
+
+#[test]
+fn labeled_loops_exit_the_named_loop() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+
+fn main <()*>i32> ():
+    let mut count 0
+    let mut a 0
+    while <.outer> lt a 5:
+        do:
+            set a add a 1
+            let mut b 0
+            while lt b 5:
+                do:
+                    set b add b 1
+                    if eq b a then continue <.outer> else ()
+                    if gt add a b 7 then break <.outer> else ()
+                    set count add count 1
+    count
+"#;
+    assert_eq!(run_main_i32(src), 8);
+}
+
+#[test]
+fn loop_exits_need_an_enclosing_loop() {
+    let diags = compile_diags(
+        r#"
+#entry main
+#indent 4
+#target wasm
+
+fn main <()->i32> ():
+    break
+    0
+"#,
+    );
+    find_error(&diags, "`break` outside of a loop");
+    let diags = compile_diags(
+        r#"
+#entry main
+#indent 4
+#target wasm
+
+fn main <()*>i32> ():
+    while true:
+        do:
+            continue <.missing>
+    0
+"#,
+    );
+    find_error(&diags, "`continue` refers to unknown loop label `.missing`");
+    let diags = compile_diags(
+        r#"
+#entry main
+#indent 4
+#target wasm
+
+fn main <()->i32> ():
+    return true
+"#,
+    );
+    find_error(
+        &diags,
+        "return value has type bool, but the function returns i32",
+    );
+}
+
+const MOVE_PRELUDE: &str = r#"
+#entry main
+#indent 4
+#target wasm
+
+struct P:
+    x <i32>
+
+fn take <(P)->i32> (p):
+    p.x
+"#;
+
+#[test]
+fn moving_before_break_does_not_poison_the_loop() {
+    compile_src(&format!(
+        "{MOVE_PRELUDE}
+fn main <()*>i32> ():
+    let p P 3
+    let mut r 0
+    while true:
+        do:
+            set r take p
+            break
+    r
+"
+    ));
+}
+
+#[test]
+fn moves_reaching_continue_or_the_loop_exit_are_reported() {
+    let diags = compile_diags(&format!(
+        "{MOVE_PRELUDE}
+fn main <()*>i32> ():
+    let p P 3
+    let mut r 0
+    while true:
+        do:
+            set r take p
+            continue
+    r
+"
+    ));
+    find_error(&diags, "potentially moved value: `p`");
+    let diags = compile_diags(&format!(
+        "{MOVE_PRELUDE}
+fn main <()*>i32> ():
+    let p P 3
+    let mut r 0
+    while true:
+        do:
+            set r take p
+            break
+    take p
+"
+    ));
+    find_error(&diags, "use of potentially moved value: `p`");
+}
@@ -0,0 +1,155 @@
+mod harness;
+use harness::{compile_diags, find_error, run_main_wasi_i32};
+use nepl_core::diagnostic::Severity;
+
+#[test]
+fn pure_function_cannot_call_extern() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasi
+#extern "wasi_snapshot_preview1" "fd_write" fn fd_write <(i32,i32,i32,i32)->i32>
+
+fn write_nothing <()->i32> ():
+    fd_write 1 0 0 0
+
+fn main <()*>i32> ():
+    write_nothing
+"#;
+    let diags = compile_diags(src);
+    find_error(&diags, "pure context cannot call impure function");
+    assert!(
+        diags.iter().any(|d| d.severity == Severity::Warning
+            && d.message == "extern import 'fd_write' is treated as impure"),
+        "{:?}",
+        diags
+    );
+}
+
+#[test]
+fn pure_function_cannot_use_store_intrinsic() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+fn poke <(i32)->()> (p):
+    #intrinsic "store" <i32> (p, 1)
+
+fn main <()*>i32> ():
+    poke 0;
+    0
+"#;
+    let diags = compile_diags(src);
+    let d = find_error(&diags, "pure context cannot use impure intrinsic 'store'");
+    assert!(d.notes.iter().any(|n| n.contains("`*>`")), "{:?}", d.notes);
+}
+
+#[test]
+fn pure_function_cannot_write_memory_in_wasm_body() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+fn poke <(i32)->()> (p):
+    #wasm:
+        local.get $p
+        i32.const 1
+        i32.store
+
+fn main <()*>i32> ():
+    poke 0;
+    0
+"#;
+    let diags = compile_diags(src);
+    find_error(&diags, "pure function cannot use `i32.store` in a raw body");
+}
+
+#[test]
+fn pure_function_set_names_variable() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+
+fn bump <(i32)->i32> (x):
+    let mut y <i32> x;
+    set y add y 1;
+    y
+
+fn main <()*>i32> ():
+    bump 1
+"#;
+    let diags = compile_diags(src);
+    find_error(&diags, "pure context cannot assign to 'y' with `set`");
+}
+
+#[test]
+fn signature_omitted_functions_infer_effect() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasi
+#import "std/stdio" as *
+#import "core/math" as *
+
+fn shout (x):
+    println_i32 x;
+    x
+
+fn twice (x):
+    add x x
+
+fn main <()*>i32> ():
+    let show (v):
+        print_i32 v;
+        v
+    let r <i32> shout twice 2;
+    show r
+"#;
+    assert_eq!(run_main_wasi_i32(src), 4);
+}
+
+#[test]
+fn inferred_impure_function_rejected_in_pure_caller() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasi
+#import "std/stdio" as *
+
+fn shout (x):
+    println_i32 x;
+    x
+
+fn quiet <(i32)->i32> (x):
+    shout x
+
+fn main <()*>i32> ():
+    quiet 1
+"#;
+    let diags = compile_diags(src);
+    find_error(&diags, "pure context cannot call impure function");
+}
+
+#[test]
+fn impure_function_literal_is_inferred() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasi
+#import "std/stdio" as *
+#import "core/math" as *
+
+fn apply <(i32,(i32)*>i32)*>i32> (x, f):
+    f x
+
+fn main <()*>i32> ():
+    apply 41 (v):
+        println_i32 v;
+        add v 1
+"#;
+    assert_eq!(run_main_wasi_i32(src), 42);
+}
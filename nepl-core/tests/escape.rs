@@ -0,0 +1,115 @@
+mod harness;
+use harness::{compile_src, heap_around_main, run_main_i32};
+
+const PRELUDE: &str = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+
+struct Big:
+    a <i32>
+    b <i32>
+    c <i32>
+    d <i32>
+    e <i32>
+
+enum Shape:
+    Circle <i32>
+    Square <i32>
+
+fn total <(Big)->i32> (v):
+    add add add add v.a v.b v.c v.d v.e
+
+fn area <(Shape)->i32> (s):
+    match s:
+        Shape::Circle r:
+            mul 3 mul r r
+        Shape::Square w:
+            mul w w
+"#;
+
+#[test]
+fn local_aggregates_stay_off_the_heap() {
+    let src = format!(
+        "{PRELUDE}
+fn main <()->i32> ():
+    let b Big 1 2 3 4 5
+    let s Shape::Square 3
+    add total b add area s area Shape::Circle 1
+"
+    );
+    let (before, after, value) = heap_around_main(&compile_src(&src));
+    assert_eq!(value, 27);
+    assert_eq!(before, after, "non-escaping aggregates were heap-allocated");
+}
+
+#[test]
+fn returned_aggregates_still_use_the_heap() {
+    let src = format!(
+        "{PRELUDE}
+fn make <(i32)->Big> (n):
+    let v Big n n n n n
+    v
+
+fn main <()->i32> ():
+    total make 2
+"
+    );
+    let (before, after, value) = heap_around_main(&compile_src(&src));
+    assert_eq!(value, 10);
+    assert_ne!(before, after);
+}
+
+#[test]
+fn aggregates_stored_into_other_aggregates_escape() {
+    let src = format!(
+        "{PRELUDE}
+enum Wrap:
+    Inner <Big>
+
+fn wrap <(i32)->Wrap> (n):
+    let v Big n 0 0 0 0
+    Wrap::Inner v
+
+fn unwrap <(Wrap)->i32> (w):
+    match w:
+        Wrap::Inner v:
+            total v
+
+fn main <()->i32> ():
+    let w wrap 7
+    let noise Big 100 100 100 100 100
+    add unwrap w mul 0 total noise
+"
+    );
+    assert_eq!(run_main_i32(&src), 7);
+}
+
+#[test]
+fn loops_and_recursion_get_fresh_storage() {
+    let src = format!(
+        "{PRELUDE}
+fn fact <(i32)->i32> (n):
+    let b Big n 1 0 0 0
+    if:
+        cond:
+            lt b.a 2
+        then:
+            1
+        else:
+            mul fact sub b.a 1 b.a
+
+fn main <()->i32> ():
+    let mut i 0
+    let mut acc 0
+    while lt i 3:
+        do:
+            let t Big i i i i i
+            set acc add acc total t
+            set i add i 1
+    add acc fact 5
+"
+    );
+    assert_eq!(run_main_i32(&src), 135);
+}
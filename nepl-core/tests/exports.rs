@@ -0,0 +1,170 @@
+mod harness;
+use harness::{compile_diags, compile_src, find_error, instantiate, stdlib_root};
+
+use nepl_core::bindings;
+use nepl_core::loader::Loader;
+use nepl_core::span::FileId;
+use nepl_core::{
+    compile_module, compile_wasm, CompilationArtifact, CompileOptions, CompileTarget, CoreError,
+};
+use std::path::PathBuf;
+
+fn compile(src: &str) -> Result<CompilationArtifact, CoreError> {
+    let mut loader = Loader::new(stdlib_root());
+    let loaded = loader
+        .load_inline(PathBuf::from("test.nepl"), src.to_string())
+        .expect("load");
+    compile_module(
+        loaded.module,
+        CompileOptions {
+            target: Some(CompileTarget::Wasm),
+            verbose: false,
+            profile: None,
+        },
+    )
+}
+
+const POINT_SRC: &str = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+
+struct Point:
+    x <i32>
+    y <i32>
+
+#export "point_sum"
+fn sum <(Point)->i32> (p):
+    add p.x p.y
+
+#export
+fn twice <(i32)->i32> (a):
+    add a a
+
+fn main <()->i32> ():
+    twice 1
+"#;
+
+#[test]
+fn exported_functions_are_callable_from_host() {
+    let wasm = compile_src(POINT_SRC);
+    let (mut store, instance) = instantiate(&wasm);
+
+    let twice = instance
+        .get_typed_func::<i32, i32>(&store, "twice")
+        .expect("twice export");
+    assert_eq!(twice.call(&mut store, 21).unwrap(), 42);
+
+    // Point is passed as a pointer to two i32 fields.
+    let alloc = instance
+        .get_typed_func::<i32, i32>(&store, "__nepl_alloc")
+        .expect("allocator export");
+    let ptr = alloc.call(&mut store, 8).unwrap();
+    let memory = instance.get_memory(&store, "memory").expect("memory");
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&3i32.to_le_bytes());
+    bytes.extend_from_slice(&4i32.to_le_bytes());
+    memory.write(&mut store, ptr as usize, &bytes).unwrap();
+
+    let sum = instance
+        .get_typed_func::<i32, i32>(&store, "point_sum")
+        .expect("point_sum export");
+    assert_eq!(sum.call(&mut store, ptr).unwrap(), 7);
+    assert!(instance.get_func(&store, "sum").is_none());
+}
+
+#[test]
+fn export_bindings_describe_memory_layout() {
+    let artifact = compile(POINT_SRC).expect("compile");
+    let exports = &artifact.exports;
+    assert_eq!(exports.functions.len(), 2);
+    assert_eq!(exports.functions[0].name, "point_sum");
+    assert_eq!(exports.functions[0].signature, "<(Point)->i32>");
+
+    let dts = bindings::typescript(exports);
+    assert!(dts.contains(" * struct Point (8 bytes)\n * - x: i32 @0\n * - y: i32 @4\n"));
+    assert!(dts.contains("  point_sum(p: Point): number;\n"));
+    assert!(dts.contains("  twice(a: number): number;\n"));
+
+    let rs = bindings::rust(exports);
+    assert!(rs.contains("pub struct Point {\n    pub x: i32,\n    pub y: i32,\n}\n"));
+    assert!(rs.contains("    pub const POINT_SUM: &str = \"point_sum\";\n"));
+    assert!(rs.contains("    pub type PointSumParams = (Ptr,);\n"));
+}
+
+#[test]
+fn export_rejects_function_parameters() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+#export
+fn apply <((i32)->i32, i32)->i32> (f, x):
+    f x
+
+fn main <()->i32> ():
+    0
+"#;
+    let diags = compile_diags(src);
+    find_error(
+        &diags,
+        "cannot export 'apply': parameter 'f' has type '(i32)->i32'",
+    );
+}
+
+#[test]
+fn export_names_must_be_unique_and_unreserved() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+#export "one"
+fn a <()->i32> ():
+    1
+
+#export "one"
+fn b <()->i32> ():
+    1
+
+#export "memory"
+fn c <()->i32> ():
+    1
+
+fn main <()->i32> ():
+    0
+"#;
+    let diags = compile_diags(src);
+    find_error(&diags, "duplicate export name 'one'");
+    find_error(&diags, "export name 'memory' is reserved");
+}
+
+#[test]
+fn export_must_precede_a_function() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+#export
+let x 1
+
+fn main <()->i32> ():
+    0
+"#;
+    let options = CompileOptions {
+        target: Some(CompileTarget::Wasm),
+        verbose: false,
+        profile: None,
+    };
+    let diags = match compile_wasm(FileId(0), src, options) {
+        Err(CoreError::Diagnostics(diags)) => diags,
+        other => panic!("expected diagnostics, got {:?}", other.map(|_| ())),
+    };
+    find_error(
+        &diags,
+        "`#export` must be followed by a function definition",
+    );
+}
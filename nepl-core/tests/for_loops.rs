@@ -0,0 +1,229 @@
+mod harness;
+use harness::{compile_diags, find_error, run_main_i32};
+
+#[test]
+fn for_over_range_counts() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/iter" as *
+
+fn main <()*>i32> ():
+    let mut sum 0
+    for i in range 1 5:
+        set sum add sum i
+    sum
+"#;
+    assert_eq!(run_main_i32(src), 10);
+}
+
+#[test]
+fn for_over_vec_uses_the_iterator_impl() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "alloc/vec" as *
+
+fn main <()*>i32> ():
+    let v0 vec_new<i32>
+    let v1 vec_push<i32> v0 3
+    let v vec_push<i32> v1 4
+    let mut sum 0
+    for x in vec_iter &v:
+        set sum add sum x
+    sum
+"#;
+    assert_eq!(run_main_i32(src), 7);
+}
+
+#[test]
+fn for_over_list_btreemap_and_str_bytes() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "alloc/string" as *
+#import "alloc/collections/list" as *
+#import "alloc/collections/btreemap" as *
+
+fn main <()*>i32> ():
+    let l0 list_nil<i32>
+    let l1 list_cons<i32> 2 l0
+    let l list_cons<i32> 1 l1
+    let mut acc 0
+    for x in list_iter<i32> l:
+        set acc add mul acc 10 x
+    let hm btreemap_new<i32>
+    btreemap_insert<i32> hm 4 40
+    btreemap_insert<i32> hm 3 30
+    for k in btreemap_keys hm:
+        set acc add mul acc 10 k
+    for v in btreemap_values<i32> hm:
+        set acc add acc v
+    for b in str_bytes "AB":
+        set acc add acc b
+    acc
+"#;
+    assert_eq!(run_main_i32(src), 1234 + 70 + 131);
+}
+
+#[test]
+fn for_supports_labels_break_and_continue() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/iter" as *
+
+fn main <()*>i32> ():
+    let mut hits 0
+    for <.outer> i in range 0 10:
+        for j in range 0 10:
+            if eq j 2 then continue else ()
+            if eq i 3 then break <.outer> else ()
+            if eq j 4 then break else ()
+            set hits add hits 1
+    hits
+"#;
+    assert_eq!(run_main_i32(src), 9);
+}
+
+#[test]
+fn for_drives_user_iterator_impls() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/field" as *
+#import "core/option" as *
+#import "core/iter" as *
+
+struct Countdown:
+    n <i32>
+
+impl Iterator<i32> for Countdown:
+    fn next <(&Countdown)*>Option<i32>> (r):
+        let c <Countdown> *r
+        let n <i32> get c "n"
+        if:
+            lt 0 n
+            then:
+                put c "n" sub n 1
+                Option::Some n
+            else Option::None
+
+struct Repeat<.T>:
+    left <i32>
+    value <.T>
+
+impl <.T> Iterator<.T> for Repeat<.T>:
+    fn next <(&Repeat<.T>)*>Option<.T>> (r):
+        let it <Repeat<.T>> *r
+        let left <i32> get it "left"
+        if:
+            lt 0 left
+            then:
+                put it "left" sub left 1
+                Option::Some get it "value"
+            else Option::None
+
+fn main <()*>i32> ():
+    let mut acc 0
+    for n in Countdown 3:
+        set acc add mul acc 10 n
+    for b in Repeat<bool> 2 true:
+        if b then set acc add acc 1000 else ()
+    let mut r range 5 7
+    match Iterator::next &r:
+        Option::Some i:
+            set acc add acc mul i 10000
+        Option::None:
+            ()
+    acc
+"#;
+    assert_eq!(run_main_i32(src), 321 + 2000 + 50000);
+}
+
+#[test]
+fn for_counts_only_the_core_range() {
+    // a Range of the user's own is driven by its impl, not the counting loop
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/field" as *
+#import "core/option" as *
+
+trait Iterator <.T>:
+    fn next <(&Self)*>Option<.T>> (it):
+        Option::None
+
+struct Range:
+    start <i32>
+    end <i32>
+
+impl Iterator<i32> for Range:
+    fn next <(&Range)*>Option<i32>> (r):
+        let it <Range> *r
+        let i <i32> get it "start"
+        if:
+            lt i get it "end"
+            then:
+                put it "start" add i 2
+                Option::Some i
+            else Option::None
+
+fn main <()*>i32> ():
+    let mut n 0
+    for i in Range 0 10:
+        set n add mul n 10 i
+    n
+"#;
+    assert_eq!(run_main_i32(src), 2468);
+}
+
+#[test]
+fn for_rejects_value_without_next() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+
+fn main <()*>i32> ():
+    let mut n 0
+    for x in 5:
+        set n add n x
+    n
+"#;
+    find_error(&compile_diags(src), "`for` needs the Iterator trait; import core/iter");
+}
+
+#[test]
+fn for_rejects_types_without_an_iterator_impl() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/iter" as *
+
+struct Bag:
+    n <i32>
+
+fn main <()*>i32> ():
+    let mut n 0
+    for x in Bag 1:
+        set n add n x
+    n
+"#;
+    find_error(&compile_diags(src), "`for` needs an `Iterator` impl for Bag");
+}
@@ -299,3 +299,24 @@ fn main <()->i32> ():
     let v = run_main_i32(src);
     assert_eq!(v, 70);
 }
+
+#[test]
+fn function_param_shadows_function_of_same_name() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "alloc/string" as *
+
+fn twice_len <(i32)->i32> (len):
+    mul len 2
+
+fn pass <(i32)->i32> (len):
+    len
+
+fn main <()->i32> ():
+    add twice_len 5 pass 7
+"#;
+    assert_eq!(run_main_i32(src), 17);
+}
@@ -1,10 +1,11 @@
-use nepl_core::loader::Loader;
-use nepl_core::{compile_module, CompileOptions, CompileTarget};
+use nepl_core::diagnostic::{Diagnostic, Severity};
+use nepl_core::loader::{Loader, LoaderError};
+use nepl_core::{compile_module, CompileOptions, CompileTarget, CoreError};
 use std::collections::BTreeMap;
 use std::io::Write;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use wasmi::{Caller, Engine, Extern, Linker, Module, Store};
+use wasmi::{Caller, Engine, Extern, Instance, Linker, Module, Store};
 
 /// Compile source to wasm bytes.
 pub fn compile_src(src: &str) -> Vec<u8> {
@@ -32,6 +33,38 @@ pub fn compile_src_with_options(src: &str, options: CompileOptions) -> Vec<u8> {
     artifact.wasm
 }
 
+/// Compile source that is expected to fail and return its diagnostics
+/// (the parse errors when loading fails).
+#[allow(dead_code)]
+pub fn compile_diags(src: &str) -> Vec<Diagnostic> {
+    let mut loader = Loader::new(stdlib_root());
+    let loaded = match loader.load_inline(PathBuf::from("test.nepl"), src.to_string()) {
+        Ok(loaded) => loaded,
+        Err(LoaderError::Core(CoreError::Diagnostics(diags))) => return diags,
+        Err(e) => panic!("load: {e}"),
+    };
+    match compile_module(
+        loaded.module,
+        CompileOptions {
+            target: None,
+            verbose: false,
+            profile: None,
+        },
+    ) {
+        Err(CoreError::Diagnostics(diags)) => diags,
+        other => panic!("expected diagnostics, got {:?}", other.map(|_| ())),
+    }
+}
+
+/// The error diagnostic with exactly `message`; panics with all diagnostics if missing.
+#[allow(dead_code)]
+pub fn find_error<'a>(diags: &'a [Diagnostic], message: &str) -> &'a Diagnostic {
+    diags
+        .iter()
+        .find(|d| d.severity == Severity::Error && d.message == message)
+        .unwrap_or_else(|| panic!("no error {:?}: {:?}", message, diags))
+}
+
 struct WasiFile {
     data: Vec<u8>,
     pos: usize,
@@ -247,8 +280,7 @@ pub fn run_main_i32(src: &str) -> i32 {
         .unwrap();
     let mut store = Store::new(&engine, ());
     let instance = linker
-        .instantiate(&mut store, &module)
-        .and_then(|pre| pre.start(&mut store))
+        .instantiate_and_start(&mut store, &module)
         .expect("instantiate");
     if let Ok(f) = instance.get_typed_func::<(), i32>(&store, "main") {
         f.call(&mut store, ()).expect("call")
@@ -260,6 +292,36 @@ pub fn run_main_i32(src: &str) -> i32 {
     }
 }
 
+/// Instantiate a module that needs no imports.
+#[allow(dead_code)]
+pub fn instantiate(wasm: &[u8]) -> (Store<()>, Instance) {
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("module");
+    let mut store = Store::new(&engine, ());
+    let instance = Linker::<()>::new(&engine)
+        .instantiate_and_start(&mut store, &module)
+        .expect("instantiate");
+    (store, instance)
+}
+
+/// Heap pointer (stored at address 0) before and after running `main`, and its result.
+#[allow(dead_code)]
+pub fn heap_around_main(wasm: &[u8]) -> (i32, i32, i32) {
+    let (mut store, instance) = instantiate(wasm);
+    let memory = instance.get_memory(&store, "memory").expect("memory");
+    let heap = |store: &Store<()>| {
+        let mut bytes = [0u8; 4];
+        memory.read(store, 0, &mut bytes).unwrap();
+        i32::from_le_bytes(bytes)
+    };
+    let before = heap(&store);
+    let main = instance
+        .get_typed_func::<(), i32>(&store, "main")
+        .expect("main");
+    let value = main.call(&mut store, ()).unwrap();
+    (before, heap(&store), value)
+}
+
 /// Compile and run `main` with WASI target, returning i32.
 pub fn run_main_wasi_i32(src: &str) -> i32 {
     let wasm = compile_src_with_options(
@@ -712,8 +774,7 @@ pub fn run_main_wasi_i32(src: &str) -> i32 {
         },
     );
     let instance = linker
-        .instantiate(&mut store, &module)
-        .and_then(|pre| pre.start(&mut store))
+        .instantiate_and_start(&mut store, &module)
         .expect("instantiate");
     if let Ok(f) = instance.get_typed_func::<(), i32>(&store, "main") {
         f.call(&mut store, ()).expect("call")
@@ -974,8 +1035,7 @@ pub fn run_main_capture_stdout(src: &str) -> String {
         .unwrap();
     let mut store = Store::new(&engine, ());
     let instance = linker
-        .instantiate(&mut store, &module)
-        .and_then(|pre| pre.start(&mut store))
+        .instantiate_and_start(&mut store, &module)
         .expect("instantiate");
     if let Ok(f) = instance.get_typed_func::<(), i32>(&store, "main") {
         let _ = f.call(&mut store, ()).expect("call");
@@ -989,6 +1049,7 @@ pub fn run_main_capture_stdout(src: &str) -> String {
 }
 
 /// Compile and run `main`, capturing stdout and providing stdin bytes via WASI fd_read.
+#[allow(dead_code)]
 pub fn run_main_capture_stdout_with_stdin(src: &str, stdin: &[u8]) -> String {
     let wasm = compile_src_with_options(
         src,
@@ -1272,10 +1333,8 @@ pub fn run_main_capture_stdout_with_stdin(src: &str, stdin: &[u8]) -> String {
         .unwrap();
     let mut store = Store::new(&engine, ());
     let instance = linker
-        .instantiate(&mut store, &module)
-        .expect("instantiate")
-        .start(&mut store)
-        .expect("start");
+        .instantiate_and_start(&mut store, &module)
+        .expect("instantiate");
     if let Ok(f) = instance.get_typed_func::<(), i32>(&store, "main") {
         f.call(&mut store, ()).expect("call");
     } else if let Ok(fu) = instance.get_typed_func::<(), ()>(&store, "main") {
@@ -1287,7 +1346,7 @@ pub fn run_main_capture_stdout_with_stdin(src: &str, stdin: &[u8]) -> String {
     captured
 }
 
-fn stdlib_root() -> std::path::PathBuf {
+pub fn stdlib_root() -> std::path::PathBuf {
     std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
         .join("..")
         .join("stdlib")
@@ -0,0 +1,143 @@
+use nepl_core::analysis::names::NameDefKind;
+use nepl_core::analysis::{hover, hover_loaded, HoverAnalysis, LoadedSources};
+use nepl_core::loader::Loader;
+
+/// Hover at the `|` marker of `src`.
+fn hover_at(src: &str) -> HoverAnalysis {
+    let offset = src.find('|').expect("cursor marker");
+    let source = src.replacen('|', "", 1);
+    let result = hover(&source, offset as u32);
+    assert!(result.ok, "{:?}", result.diagnostics);
+    result
+}
+
+const SRC: &str = "//: add3: 3 つの和
+//:
+//: - `a + b + c` を返す
+fn add3 <(i32, i32, i32)->i32> (a, b, c):
+    a
+
+fn add3 <(f32, f32, f32)->f32> (a, b, c):
+    a
+
+fn id <.T> <(.T)->.T> (x):
+    x
+
+fn twice (n):
+    n
+
+fn main <()->i32> ():
+    let v <i32> id 1;
+    add3 v twice 2 3
+";
+
+fn with_cursor(line: &str, cursor: &str) -> String {
+    let marked = line.replacen(cursor, &format!("{}|", cursor), 1);
+    SRC.replacen(line, &marked, 1)
+}
+
+#[test]
+fn call_reports_result_type_and_chosen_overload() {
+    let result = hover_at(&with_cursor("    add3 v", "    ad"));
+    let expr = result.expression.expect("expression");
+    assert_eq!((expr.kind, expr.inferred_type.as_str()), ("Call", "i32"));
+
+    let def = result.definition.expect("definition");
+    assert_eq!((def.name.as_str(), def.kind), ("add3", NameDefKind::Fn));
+    // the i32 overload on line 4, not the f32 one
+    assert_eq!(def.span.start_line, 3);
+    let sig = def.signature.expect("signature");
+    assert_eq!(sig.label, "fn add3 <(i32, i32, i32)->i32> (a, b, c)");
+    assert_eq!(sig.effect, "pure");
+    let b = &sig.parameters[1];
+    assert_eq!((b.name.as_str(), b.ty.as_str()), ("b", "i32"));
+    let [start, end] = b.label_offsets;
+    assert_eq!(&sig.label[start as usize..end as usize], "b");
+
+    let doc = def.documentation.expect("doc comment");
+    assert_eq!(doc.markdown, "add3: 3 つの和\n\n- `a + b + c` を返す");
+    assert_eq!(doc.html, None);
+}
+
+#[test]
+fn active_parameter_follows_prefix_arguments() {
+    // on the callee: the call is known but no argument is active yet
+    let help = hover_at(&with_cursor("    add3 v", "    ad"))
+        .signature_help
+        .expect("signature help");
+    assert_eq!(help.active_parameter, None);
+    assert_eq!(help.argument_ranges.len(), 3);
+
+    // `twice 2` is the second argument of add3
+    let help = hover_at(&with_cursor("v twice 2 3", "v tw"))
+        .signature_help
+        .expect("signature help");
+    assert!(help.signature.label.starts_with("fn twice"));
+    assert_eq!(help.active_parameter, None);
+
+    let result = hover_at(&with_cursor("v twice 2 3", "v twice 2 "));
+    let help = result.signature_help.expect("signature help");
+    assert!(help.signature.label.starts_with("fn add3"));
+    assert_eq!(help.active_parameter, Some(2));
+    assert!(help.documentation.is_some());
+
+    let help = hover_at(&with_cursor("v twice 2 3", "v twice 2"))
+        .signature_help
+        .expect("signature help");
+    assert!(help.signature.label.starts_with("fn twice"));
+    assert_eq!(help.active_parameter, Some(0));
+}
+
+#[test]
+fn generic_and_inferred_signatures() {
+    let result = hover_at(&with_cursor("<i32> id 1", "<i32> i"));
+    let sig = result.definition.and_then(|d| d.signature).expect("sig");
+    assert_eq!(sig.type_params, vec![".T"]);
+    assert_eq!(sig.label, "fn id <.T> <(.T)->.T> (x)");
+
+    // parameter and result types of `twice` come from the typechecker
+    let result = hover_at(&with_cursor("fn twice (n)", "fn tw"));
+    let sig = result.definition.and_then(|d| d.signature).expect("sig");
+    assert_eq!(sig.label, "fn twice <(i32)->i32> (n)");
+}
+
+#[test]
+fn variables_show_their_type() {
+    let result = hover_at(&with_cursor("    add3 v", "    add3 v"));
+    let def = result.definition.expect("definition");
+    assert_eq!((def.name.as_str(), def.kind), ("v", NameDefKind::LetHoisted));
+    assert!(def.signature.is_none() && def.documentation.is_none());
+    assert_eq!(result.expression.unwrap().inferred_type, "i32");
+}
+
+#[test]
+fn rendered_documentation_is_kept() {
+    let mut result = hover_at(&with_cursor("    add3 v", "    ad"));
+    result.render_documentation(|md| Some(format!("<p>{}</p>", md.len())));
+    let html = result.definition.unwrap().documentation.unwrap().html;
+    assert!(html.unwrap().starts_with("<p>"));
+}
+
+#[test]
+fn loaded_hover_reads_docs_of_imports() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("util.nepl"),
+        "//: one: 1 を返す\nfn one <()*>i32> ():\n    1\n",
+    )
+    .unwrap();
+    let source = "#import \"./util\" as *\n\nfn main <()*>i32> ():\n    one\n";
+    let main = dir.path().join("main.nepl");
+    std::fs::write(&main, source).unwrap();
+
+    let mut loader = Loader::new(dir.path().join("stdlib"));
+    let result = loader.load(&main);
+    let loaded = LoadedSources::new(result, &loader);
+    let offset = source.find("    one").unwrap() as u32 + 5;
+    let result = hover_loaded(&loaded, offset);
+    assert!(result.ok, "{:?}", result.diagnostics);
+    let def = result.definition.expect("definition");
+    assert!(def.path.ends_with("util.nepl"));
+    assert_eq!(def.signature.unwrap().effect, "impure");
+    assert_eq!(def.documentation.unwrap().markdown, "one: 1 を返す");
+}
@@ -0,0 +1,101 @@
+use nepl_core::analysis::inlay::InlayHintKind;
+use nepl_core::analysis::{analyze_semantics, inlay_hints, parenthesize_line};
+
+const SRC: &str = "struct Point:
+    x <i32>
+    y <i32>
+
+fn add <(i32, i32)->i32> (a, b):
+    a
+
+fn main <()->i32> ():
+    let b 2;
+    let v <i32> add 1 add b 3;
+    let p Point add 1 2 v;
+    if true then add v 1 else b
+";
+
+/// Hints as `(line, col, label)`, 1-based like editors show them.
+fn labels(src: &str) -> Vec<(u32, u32, String)> {
+    let result = inlay_hints(src);
+    assert!(result.ok, "{:?}", result.diagnostics);
+    result
+        .hints
+        .iter()
+        .map(|h| {
+            (
+                h.position.start_line + 1,
+                h.position.start_col + 1,
+                h.label.clone(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn parameter_names_and_parens_follow_arity() {
+    let hints = labels(SRC);
+    let line10: Vec<(u32, &str)> = hints
+        .iter()
+        .filter(|(line, _, _)| *line == 10)
+        .map(|(_, col, label)| (*col, label.as_str()))
+        .collect();
+    // `add 1 add b 3`: the inner call is the second argument of the outer
+    assert_eq!(
+        line10,
+        vec![
+            (21, "a:"),
+            (23, "b:"),
+            (23, "("),
+            (27, "a:"),
+            (29, "b:"),
+            (30, ")"),
+        ]
+    );
+}
+
+#[test]
+fn struct_fields_and_let_types() {
+    let hints = labels(SRC);
+    // `let b 2` gets its type, the annotated `let v <i32>` does not
+    assert!(hints.contains(&(9, 10, "<i32>".into())));
+    assert!(!hints.iter().any(|(line, _, l)| *line == 10 && l.starts_with('<')));
+    assert!(hints.contains(&(11, 10, "<Point>".into())));
+    assert!(hints.contains(&(11, 17, "x:".into())));
+    assert!(hints.contains(&(11, 25, "y:".into())));
+}
+
+#[test]
+fn matching_argument_names_are_not_repeated() {
+    let src = SRC.replace("let b 2;", "let b 2;\n    add 0 b;");
+    let hints = labels(&src);
+    let line10: Vec<&str> = hints
+        .iter()
+        .filter(|(line, _, _)| *line == 10)
+        .map(|(_, _, label)| label.as_str())
+        .collect();
+    assert_eq!(line10, vec!["a:"]);
+}
+
+#[test]
+fn hint_kinds_serialize_in_snake_case() {
+    let result = inlay_hints(SRC);
+    assert!(result
+        .hints
+        .iter()
+        .any(|h| h.kind == InlayHintKind::Paren && h.label == ")"));
+    let json = serde_json::to_value(&result).unwrap();
+    assert_eq!(json["stage"], "inlay_hints");
+    assert_eq!(json["hints"][0]["kind"], "type");
+}
+
+#[test]
+fn lines_are_fully_parenthesized() {
+    let semantics = analyze_semantics(SRC);
+    let line = |n| parenthesize_line(&semantics, SRC, n).unwrap();
+    assert_eq!(line(9), "    let v <i32> (add 1 (add b 3));");
+    assert_eq!(line(10), "    let p (Point (add 1 2) v);");
+    assert_eq!(line(11), "    if true then (add v 1) else b");
+    assert_eq!(line(8), "    let b 2;");
+    assert_eq!(parenthesize_line(&semantics, SRC, 40), None);
+}
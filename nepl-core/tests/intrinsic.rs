@@ -84,3 +84,29 @@ fn main <()->i32> ():
 "#;
     assert_eq!(run_main_i32(src), 0);
 }
+
+#[test]
+fn intrinsic_mem_copy_and_fill() {
+    let src = r#"
+#target wasm
+#entry main
+#indent 4
+#import "core/math" as *
+#import "core/mem" as *
+#import "alloc/string" as *
+
+fn main <()*>i32> ():
+    let p <i32> alloc 16;
+    mem_fill p 7 16;
+    store_i32 p 305419896;
+    // overlapping copy behaves like memmove
+    mem_copy add p 2 p 8;
+    let q <i32> realloc p 16 32;
+    let s <str> concat "ab" "cde";
+    let copied <bool> and eq load_u8 add q 2 120 eq load_u8 add q 5 18;
+    let filled <bool> eq load_u8 add q 15 7;
+    let joined <bool> and eq len s 5 eq load_u8 add s 8 101;
+    if and copied and filled joined 0 1
+"#;
+    assert_eq!(run_main_i32(src), 0);
+}
@@ -0,0 +1,230 @@
+mod harness;
+use harness::{compile_src, heap_around_main, instantiate, run_main_i32};
+
+use wasmparser::{Parser, Payload, ValType};
+
+/// Result types of every function type in the module.
+fn result_types(wasm: &[u8]) -> Vec<Vec<ValType>> {
+    let mut results = Vec::new();
+    for payload in Parser::new(0).parse_all(wasm) {
+        if let Payload::TypeSection(reader) = payload.expect("payload") {
+            for ty in reader.into_iter_err_on_gc_types() {
+                results.push(ty.expect("type").results().to_vec());
+            }
+        }
+    }
+    results
+}
+
+const DIVMOD_SRC: &str = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/field" as *
+
+fn divmod <(i32,i32)->.Pair> (a, b):
+    Tuple:
+        i32_div_s a b
+        i32_rem_s a b
+
+struct Point:
+    x <i32>
+    y <i32>
+
+fn swap <(Point)->Point> (p):
+    Point p.y p.x
+
+fn main <()->i32> ():
+    let t divmod 17 5
+    let q <Point> swap Point 1 2
+    add add mul 10 get t 0 get t 1 mul 100 q.x
+"#;
+
+#[test]
+fn small_aggregates_are_returned_as_multi_value() {
+    let wasm = compile_src(DIVMOD_SRC);
+    assert!(result_types(&wasm).contains(&vec![ValType::I32, ValType::I32]));
+    assert_eq!(run_main_i32(DIVMOD_SRC), 232);
+}
+
+#[test]
+fn small_aggregates_stay_off_the_heap() {
+    let (before, after, value) = heap_around_main(&compile_src(DIVMOD_SRC));
+    assert_eq!(value, 232);
+    assert_eq!(before, after, "tuples / structs were heap-allocated");
+}
+
+#[test]
+fn multi_value_flows_through_if_and_function_values() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/field" as *
+
+struct Span:
+    start <i32>
+    end <i32>
+
+fn pick <(bool,i32)->.Pair> (c, v):
+    if:
+        cond:
+            c
+        then:
+            Tuple:
+                v
+                1
+        else:
+            Tuple:
+                2
+                v
+
+fn width <(Span)->i32> (s):
+    sub s.end s.start
+
+fn widen <(Span,i32)->Span> (s, n):
+    Span sub s.start n add s.end n
+
+fn main <()->i32> ():
+    let t pick true 5
+    let u pick false 7
+    let f width
+    let mut s <Span> Span 10 13
+    set s widen s 2
+    add add get t 0 get u 1 mul 100 f s
+"#;
+    let (before, after, value) = heap_around_main(&compile_src(src));
+    assert_eq!(value, 712);
+    assert_eq!(before, after);
+}
+
+#[test]
+fn address_taken_aggregates_keep_their_memory() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+
+struct Point:
+    x <i32>
+    y <i32>
+
+fn sum <(Point)->i32> (p):
+    add p.x p.y
+
+fn through_ref <(&Point)->i32> (r):
+    sum *r
+
+fn main <()->i32> ():
+    let q <Point> Point 3 4
+    let r &q
+    add mul 10 through_ref r sum q
+"#;
+    assert_eq!(run_main_i32(src), 77);
+}
+
+#[test]
+fn exported_aggregates_use_pointers_at_the_host_boundary() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+
+struct Point:
+    x <i32>
+    y <i32>
+
+#export "point_swap"
+fn swap <(Point)->Point> (p):
+    Point p.y p.x
+
+fn main <()->i32> ():
+    0
+"#;
+    let (mut store, instance) = instantiate(&compile_src(src));
+    let alloc = instance
+        .get_typed_func::<i32, i32>(&store, "__nepl_alloc")
+        .expect("allocator export");
+    let ptr = alloc.call(&mut store, 8).unwrap();
+    let memory = instance.get_memory(&store, "memory").expect("memory");
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&3i32.to_le_bytes());
+    bytes.extend_from_slice(&4i32.to_le_bytes());
+    memory.write(&mut store, ptr as usize, &bytes).unwrap();
+
+    let swap = instance
+        .get_typed_func::<i32, i32>(&store, "point_swap")
+        .expect("point_swap export takes and returns a pointer");
+    let out = swap.call(&mut store, ptr).unwrap();
+    let mut fields = [0u8; 8];
+    memory.read(&store, out as usize, &mut fields).unwrap();
+    assert_eq!(&fields[..4], &4i32.to_le_bytes());
+    assert_eq!(&fields[4..], &3i32.to_le_bytes());
+}
+
+#[test]
+fn wasm_body_sees_aggregates_as_pointers() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+struct Point:
+    x <i32>
+    y <i32>
+
+fn second <(Point)->i32> (p):
+    #wasm:
+        local.get $p
+        i32.const 4
+        i32.add
+        i32.load
+
+fn main <()->i32> ():
+    second Point 1 2
+"#;
+    assert_eq!(run_main_i32(src), 2);
+}
+
+#[test]
+fn bindings_that_alias_memory_are_written_through() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/field" as *
+#import "core/mem" as *
+
+struct Inner:
+    a <i32>
+    b <i32>
+
+struct Outer:
+    tag <i32>
+    inner <Inner>
+
+fn retag <(&Outer)*>()> (r):
+    let d <Outer> *r
+    put d "tag" 10
+
+fn main <()->i32> ():
+    let m <i32> alloc size_of<Outer>
+    store<Outer> m Outer 1 Inner 2 3
+    let i <Inner> get load<Outer> m "inner"
+    put i "a" 20
+    let n <i32> alloc size_of<Inner>
+    store<Inner> n Inner 4 5
+    let l <Inner> load<Inner> n
+    put l "b" 30
+    let mut o <Outer> load<Outer> m
+    retag &o
+    let k <Inner> get load<Outer> m "inner"
+    add add get o "tag" get k "a" get load<Inner> n "b"
+"#;
+    assert_eq!(run_main_i32(src), 60);
+}
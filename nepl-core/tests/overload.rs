@@ -1,5 +1,14 @@
 mod harness;
-use harness::run_main_i32;
+use harness::{compile_diags, find_error, run_main_i32};
+use nepl_core::diagnostic::Diagnostic;
+
+fn candidate_messages(d: &Diagnostic) -> Vec<String> {
+    d.secondary
+        .iter()
+        .filter_map(|l| l.message.clone())
+        .chain(d.notes.iter().cloned())
+        .collect()
+}
 
 // Note: In NEPL, overloaded functions must have the same number of arguments (arity).
 // Overloading is resolved based on the combination of:
@@ -100,3 +109,132 @@ fn main <()*>i32> ():
     let v = run_main_i32(src);
     assert_eq!(v, 11);
 }
+
+#[test]
+fn test_overload_no_match_lists_candidates() {
+    let src = r#"
+#entry main
+#indent 4
+
+fn pick <(i32)->i32> (v):
+    v
+
+fn pick <(bool)->i32> (v):
+    0
+
+fn main <()->i32> ():
+    pick 1.5
+"#;
+    let diags = compile_diags(src);
+    let d = find_error(&diags, "no matching overload found for `pick` (2 candidates)");
+    let msgs = candidate_messages(d);
+    assert_eq!(msgs.len(), 2, "{:?}", msgs);
+    assert!(msgs.iter().any(|m| m.contains("`pick <(i32)->i32>`")
+        && m.contains("argument 1 has type `f32`, expected `i32`")));
+    assert!(msgs.iter().any(|m| m.contains("`pick <(bool)->i32>`")
+        && m.contains("argument 1 has type `f32`, expected `bool`")));
+}
+
+#[test]
+fn test_overload_ambiguity_names_candidates() {
+    let src = r#"
+#entry main
+#indent 4
+
+fn magic <(i32)->i32> (v):
+    v
+
+fn magic <(i32)->bool> (v):
+    true
+
+fn main <()->i32> ():
+    magic 1;
+    0
+"#;
+    let diags = compile_diags(src);
+    let d = find_error(&diags, "ambiguous overload: 2 candidates of `magic` match this call");
+    let msgs = candidate_messages(d);
+    assert!(msgs.iter().any(|m| m.contains("`magic <(i32)->i32>` matches")), "{:?}", msgs);
+    assert!(msgs.iter().any(|m| m.contains("`magic <(i32)->bool>` matches")), "{:?}", msgs);
+}
+
+#[test]
+fn test_overload_effect_rejection_is_reported() {
+    let src = r#"
+#entry main
+#indent 4
+
+fn touch <(i32)*>i32> (v):
+    v
+
+fn pure_caller <(i32)->i32> (v):
+    touch v
+
+fn main <()*>i32> ():
+    pure_caller 1
+"#;
+    let diags = compile_diags(src);
+    let d = find_error(&diags, "pure context cannot call impure function");
+    let msgs = candidate_messages(d);
+    assert!(
+        msgs.iter().any(|m| m.contains("`touch <(i32)*>i32>`") && m.contains("impure")),
+        "{:?}",
+        msgs
+    );
+}
+
+#[test]
+fn test_overload_generic_by_struct_param() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/field" as *
+
+struct One<.T>:
+    v <.T>
+
+struct Two<.T>:
+    a <.T>
+    b <.T>
+
+fn unbox <.T> <(One<.T>)->.T> (x):
+    get x "v"
+
+fn unbox <.T> <(Two<.T>)->.T> (x):
+    get x "b"
+
+fn main <()->i32> ():
+    add mul 10 unbox One 1 unbox Two 2 3
+"#;
+    assert_eq!(run_main_i32(src), 13);
+}
+
+#[test]
+fn test_overload_generic_with_different_type_param_counts() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/field" as *
+
+struct One<.T>:
+    v <.T>
+
+struct Two<.A,.B>:
+    a <.A>
+    b <.B>
+
+fn unbox <.T> <(One<.T>)->.T> (x):
+    get x "v"
+
+fn unbox <.A,.B> <(Two<.A,.B>)->.B> (x):
+    get x "b"
+
+fn main <()->i32> ():
+    add mul 10 unbox One 1 unbox Two true 3
+"#;
+    assert_eq!(run_main_i32(src), 13);
+}
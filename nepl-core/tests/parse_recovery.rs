@@ -0,0 +1,122 @@
+use nepl_core::analysis::{analyze_name_resolution, NameResolutionOptions};
+use nepl_core::ast::{Block, FnBody, PrefixItem, Stmt, TypeExpr};
+use nepl_core::diagnostic::Diagnostic;
+use nepl_core::{lexer, parser, span::FileId};
+
+fn parse(src: &str) -> (Block, Vec<Diagnostic>) {
+    let lex = lexer::lex(FileId(0), src);
+    let parse = parser::parse_tokens(FileId(0), lex);
+    (parse.module.expect("module").root, parse.diagnostics)
+}
+
+/// Error messages with their 1-based line.
+fn errors(src: &str, diags: &[Diagnostic]) -> Vec<(usize, String)> {
+    diags
+        .iter()
+        .map(|d| {
+            let line = src[..d.primary.span.start as usize].matches('\n').count() + 1;
+            (line, d.message.clone())
+        })
+        .collect()
+}
+
+fn fn_body<'a>(root: &'a Block, name: &str) -> &'a Block {
+    root.items
+        .iter()
+        .find_map(|s| match s {
+            Stmt::FnDef(f) if f.name.name == name => match &f.body {
+                FnBody::Parsed(b) => Some(b),
+                _ => None,
+            },
+            _ => None,
+        })
+        .unwrap_or_else(|| panic!("fn {name} is missing"))
+}
+
+#[test]
+fn missing_colon_and_semicolon_are_inserted() {
+    let src = "fn plus add\n\nfn add <(i32, i32)->i32> (a, b)\n    a\n\nstruct P\n    x <i32>\n";
+    let (root, diags) = parse(src);
+    assert_eq!(
+        errors(src, &diags),
+        vec![
+            (1, "expected ';' after function alias".to_string()),
+            (3, "expected ':' before the indented block".to_string()),
+            (6, "expected ':' before the indented block".to_string()),
+        ]
+    );
+    assert!(matches!(&root.items[0], Stmt::FnAlias(a) if a.target.name == "add"));
+    assert_eq!(fn_body(&root, "add").items.len(), 1);
+    assert!(matches!(&root.items[2], Stmt::StructDef(s) if s.fields.len() == 1));
+}
+
+#[test]
+fn broken_expressions_keep_the_rest_of_the_block() {
+    let src = "fn main <()->i32> ():\n    let t <i32 1;\n    let u let;\n    let v 2;\n    v\n";
+    let (root, diags) = parse(src);
+    let lines: Vec<usize> = errors(src, &diags).iter().map(|e| e.0).collect();
+    assert_eq!(lines, vec![2, 3]);
+    let body = fn_body(&root, "main");
+    assert_eq!(body.items.len(), 4);
+    // `let t` is kept in front of the placeholder, with its `;`
+    let Stmt::ExprSemi(expr, _) = &body.items[0] else {
+        panic!("{:?}", body.items[0]);
+    };
+    assert_eq!(expr.items.len(), 2);
+    assert!(matches!(expr.items[1], PrefixItem::Error(_)));
+}
+
+#[test]
+fn broken_fields_and_signatures_are_skipped() {
+    let src = "struct P:\n    x <i32>\n    y <i32\n    z <i32>\n\nfn f <(i32 ->i32> (a):\n    a\n";
+    let (root, diags) = parse(src);
+    let lines: Vec<usize> = errors(src, &diags).iter().map(|e| e.0).collect();
+    assert_eq!(lines, vec![3, 6]);
+    let Stmt::StructDef(def) = &root.items[0] else {
+        panic!("{:?}", root.items[0]);
+    };
+    let fields: Vec<&str> = def.fields.iter().map(|f| f.0.name.as_str()).collect();
+    assert_eq!(fields, vec!["x", "z"]);
+    // the arity comes from the parameter list
+    let Stmt::FnDef(f) = &root.items[1] else {
+        panic!("{:?}", root.items[1]);
+    };
+    assert!(matches!(&f.signature, TypeExpr::Function { params, .. } if params.len() == 1));
+}
+
+#[test]
+fn skipped_lines_become_error_statements() {
+    // the nested function has no name; its body goes with it
+    let src = "fn main <()->i32> ():\n    fn (x):\n        x\n    3\n";
+    let (root, diags) = parse(src);
+    assert_eq!(
+        errors(src, &diags),
+        vec![(2, "expected identifier".to_string())]
+    );
+    let body = fn_body(&root, "main");
+    assert_eq!(body.items.len(), 2);
+    let Stmt::Error(span) = body.items[0] else {
+        panic!("{:?}", body.items[0]);
+    };
+    assert_eq!(
+        &src[span.start as usize..span.end as usize],
+        "(x):\n        x"
+    );
+    assert!(matches!(body.items[1], Stmt::Expr(_)));
+}
+
+#[test]
+fn names_resolve_in_files_with_syntax_errors() {
+    let src =
+        "fn g <()->i32> ()\n    let x 1\n    x\n\nfn main <()->i32> ():\n    let y (g;\n    y\n";
+    let result = analyze_name_resolution(src, NameResolutionOptions::default());
+    assert!(!result.diagnostics.is_empty());
+    let resolved: Vec<&str> = result
+        .resolution
+        .references
+        .iter()
+        .filter(|r| r.resolved_def_id.is_some())
+        .map(|r| r.name.as_str())
+        .collect();
+    assert_eq!(resolved, vec!["x", "g", "y"]);
+}
@@ -0,0 +1,140 @@
+use nepl_core::analysis::rename::ReferenceKind;
+use nepl_core::analysis::{
+    find_references, find_references_loaded, rename, rename_loaded, LoadedSources,
+};
+use nepl_core::loader::Loader;
+
+const SRC: &str = "fn add2 <(i32, i32)->i32> (a, b):
+    a
+
+fn add2 <(f32, f32)->f32> (a, b):
+    a
+
+fn plus add2;
+
+fn keep <(i32)->i32> (add2):
+    add2
+
+fn main <()->i32> ():
+    let x add2 1 2;
+    let y plus x 4;
+    keep y
+";
+
+fn offset_of(src: &str, needle: &str) -> u32 {
+    src.find(needle).expect("needle") as u32
+}
+
+#[test]
+fn renaming_a_call_renames_every_overload_and_alias_target() {
+    let result = rename(SRC, offset_of(SRC, "add2 1"), "sum2");
+    assert!(result.ok, "{:?} {:?}", result.diagnostics, result.conflicts);
+    let symbol = result.symbol.as_ref().unwrap();
+    assert_eq!(symbol.definitions.len(), 2);
+    // the parameter `add2` of `keep` shadows the functions
+    assert_eq!(
+        result.edit.apply(0, SRC),
+        SRC.replacen("fn add2", "fn sum2", 2)
+            .replace("fn plus add2", "fn plus sum2")
+            .replace("x add2 1", "x sum2 1")
+    );
+}
+
+#[test]
+fn aliases_and_parameters_are_their_own_symbols() {
+    let refs = find_references(SRC, offset_of(SRC, "plus x"));
+    assert!(refs.ok);
+    let kinds: Vec<(ReferenceKind, u32)> = refs
+        .references
+        .iter()
+        .map(|r| (r.kind, r.range.start_line + 1))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (ReferenceKind::Definition, 7),
+            (ReferenceKind::Reference, 14)
+        ]
+    );
+
+    let result = rename(SRC, offset_of(SRC, "(add2):") + 1, "n");
+    assert!(result.ok);
+    assert_eq!(
+        result.edit.apply(0, SRC),
+        SRC.replace("(add2):\n    add2", "(n):\n    n")
+    );
+}
+
+#[test]
+fn field_paths_rename_their_head() {
+    let src = "struct P:\n    x <i32>\n\nfn main <()->i32> ():\n    let p P 1;\n    p.x\n";
+    let result = rename(src, offset_of(src, "p P"), "q");
+    assert!(result.ok);
+    assert_eq!(
+        result.edit.apply(0, src),
+        src.replace("p P", "q P").replace("p.x", "q.x")
+    );
+}
+
+#[test]
+fn conflicting_names_are_reported() {
+    // merging into the overloads of another function
+    let result = rename(SRC, offset_of(SRC, "add2 1"), "keep");
+    assert!(!result.ok);
+    assert!(result.conflicts[0].message.contains("overloads"));
+    assert_eq!(result.conflicts[0].range.start_line, 8);
+
+    // the parameter would capture the name used in the body
+    let src = "fn f <(i32)->i32> (n):\n    n\n\nfn g <(i32)->i32> (m):\n    f m\n";
+    let result = rename(src, offset_of(src, "(m)") + 1, "f");
+    assert!(!result.ok);
+    assert!(result.conflicts[0].message.contains("would refer to"));
+
+    // a local would shadow the renamed function at a call site
+    let result = rename(src, offset_of(src, "fn f") + 3, "m");
+    assert!(!result.ok);
+    assert!(result.conflicts[0].message.contains("would shadow"));
+
+    let result = rename(SRC, offset_of(SRC, "x add2"), "let");
+    assert!(!result.ok);
+    assert!(result.diagnostics[0].message.contains("not a valid name"));
+}
+
+#[test]
+fn imports_use_paths_and_qualified_names_follow_the_definition() {
+    let dir = tempfile::tempdir().unwrap();
+    let util = "fn one <()->i32> ():\n    1\n\nfn two <()->i32> ():\n    one\n";
+    std::fs::write(dir.path().join("util.nepl"), util).unwrap();
+    let source = "#import \"./util\" as { one, two as deux }\n#import \"./util\"\n#use util::one\n\nfn main <()->i32> ():\n    util::one\n";
+    let main = dir.path().join("main.nepl");
+    std::fs::write(&main, source).unwrap();
+
+    let mut loader = Loader::new(dir.path().join("stdlib"));
+    let result = loader.load(&main);
+    let loaded = LoadedSources::new(result, &loader);
+
+    // the cursor may also be on a directive
+    let refs = find_references_loaded(&loaded, offset_of(source, "::one\n\n") + 2);
+    assert!(refs.ok, "{:?}", refs.diagnostics);
+    let kinds: Vec<ReferenceKind> = refs.references.iter().map(|r| r.kind).collect();
+    assert!(kinds.contains(&ReferenceKind::ImportItem));
+    assert!(kinds.contains(&ReferenceKind::UsePath));
+    assert!(kinds.contains(&ReferenceKind::QualifiedPath));
+
+    let result = rename_loaded(&loaded, offset_of(source, "    util::one") + 10, "uno");
+    assert!(result.ok, "{:?}", result.conflicts);
+    let util_file = result
+        .edit
+        .files
+        .iter()
+        .find(|f| f.path.ends_with("util.nepl"))
+        .expect("util edits");
+    assert_eq!(
+        result.edit.apply(util_file.file_id, util),
+        util.replace("one", "uno")
+    );
+    assert_eq!(
+        result.edit.apply(0, source),
+        source.replace("{ one,", "{ uno,").replace("::one", "::uno")
+    );
+}
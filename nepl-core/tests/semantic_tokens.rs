@@ -0,0 +1,174 @@
+use nepl_core::analysis::tokens::{SemanticTokenModifiers, SemanticTokenType};
+use nepl_core::analysis::{
+    semantic_tokens, semantic_tokens_delta, semantic_tokens_loaded, LoadedSources,
+};
+use nepl_core::loader::Loader;
+
+const SRC: &str = "struct P:
+    x <i32>
+
+enum E:
+    A
+    B <i32>
+
+//: [非推奨/ひすいしょう]
+//: `id` を使ってください。
+fn same <.T> <(.T)->.T> (v):
+    v
+
+fn main <()*>i32> ():
+    let p P 1;
+    let e E::A;
+    let mut m <i32> 0;
+    set m same p.x;
+    while <.outer> false:
+        break <.outer>;
+    m
+";
+
+/// Tokens as `(line, col, text, kind, modifiers)`, 1-based like editors show them.
+fn classified(src: &str) -> Vec<(u32, u32, String, &'static str, Vec<&'static str>)> {
+    let result = semantic_tokens(src);
+    assert!(result.ok, "{:?}", result.diagnostics);
+    result
+        .tokens
+        .iter()
+        .map(|t| {
+            let r = &t.range;
+            (
+                r.start_line + 1,
+                r.start_col + 1,
+                src[r.start as usize..r.end as usize].to_string(),
+                t.kind.as_str(),
+                t.modifiers.names(),
+            )
+        })
+        .collect()
+}
+
+fn kind_of(
+    tokens: &[(u32, u32, String, &'static str, Vec<&'static str>)],
+    line: u32,
+    text: &str,
+) -> (&'static str, Vec<&'static str>) {
+    let t = tokens
+        .iter()
+        .find(|t| t.0 == line && t.2 == text)
+        .unwrap_or_else(|| panic!("no token {text} on line {line}"));
+    (t.3, t.4.clone())
+}
+
+#[test]
+fn identifiers_are_classified_by_what_they_refer_to() {
+    let tokens = classified(SRC);
+    let k = |line, text| kind_of(&tokens, line, text);
+    assert_eq!(k(1, "P"), ("type", vec!["declaration"]));
+    assert_eq!(k(2, "x"), ("property", vec!["declaration"]));
+    assert_eq!(k(2, "i32"), ("type", vec!["default_library"]));
+    assert_eq!(k(5, "A"), ("enum_member", vec!["declaration"]));
+    assert_eq!(k(10, ".T"), ("type_parameter", vec!["declaration"]));
+    assert_eq!(k(10, "v"), ("parameter", vec!["declaration"]));
+    assert_eq!(k(11, "v"), ("parameter", vec![]));
+    assert_eq!(k(14, "P"), ("type", vec![]));
+    assert_eq!(k(15, "E"), ("type", vec![]));
+    assert_eq!(k(15, "A"), ("enum_member", vec![]));
+    assert_eq!(k(16, "m"), ("variable", vec!["declaration", "mutable"]));
+    assert_eq!(k(17, "m"), ("variable", vec!["mutable"]));
+    assert_eq!(k(17, "p"), ("variable", vec![]));
+    assert_eq!(k(17, "x"), ("property", vec![]));
+    assert_eq!(k(18, ".outer"), ("label", vec![]));
+    assert_eq!(k(19, ".outer"), ("label", vec![]));
+}
+
+#[test]
+fn deprecated_functions_are_marked_at_every_use() {
+    let tokens = classified(SRC);
+    assert_eq!(
+        kind_of(&tokens, 10, "same"),
+        ("function", vec!["declaration", "deprecated"])
+    );
+    assert_eq!(
+        kind_of(&tokens, 17, "same"),
+        ("function", vec!["deprecated"])
+    );
+}
+
+#[test]
+fn data_is_relative_and_follows_the_legend() {
+    let result = semantic_tokens(SRC);
+    assert_eq!(result.data.len(), result.tokens.len() * 5);
+    assert_eq!(
+        result.legend.token_types[SemanticTokenType::EnumMember.index() as usize],
+        "enum_member"
+    );
+    assert_eq!(result.legend.token_modifiers[2], "default_library");
+    // `P` on line 1, then `x` and `i32` on line 2
+    assert_eq!(
+        &result.data[..15],
+        &[0, 7, 1, 3, 1, 1, 4, 1, 7, 1, 0, 3, 3, 3, 4]
+    );
+
+    let visible = result.lines(16, 17);
+    assert!(visible.tokens.iter().all(|t| t.range.start_line == 16));
+    assert_eq!(visible.data[0], 16);
+    assert!(visible.tokens[0]
+        .modifiers
+        .contains(SemanticTokenModifiers::MUTABLE));
+}
+
+#[test]
+fn deltas_replace_only_the_changed_tokens() {
+    let before = semantic_tokens(SRC).data;
+    assert!(semantic_tokens_delta(&before, &before).is_empty());
+
+    let after = semantic_tokens(&SRC.replace("let e E::A;", "let e E::A;\n    let f 1;")).data;
+    let edits = semantic_tokens_delta(&before, &after);
+    assert_eq!(edits.len(), 1);
+    let edit = &edits[0];
+    assert_eq!(edit.start % 5, 0);
+    let mut patched = before.clone();
+    patched.splice(
+        edit.start as usize..(edit.start + edit.delete_count) as usize,
+        edit.data.iter().copied(),
+    );
+    assert_eq!(patched, after);
+    assert!(edit.data.len() < after.len());
+}
+
+#[test]
+fn stdlib_definitions_get_the_default_library_modifier() {
+    let dir = tempfile::tempdir().unwrap();
+    let stdlib = dir.path().join("stdlib");
+    std::fs::create_dir(&stdlib).unwrap();
+    std::fs::write(stdlib.join("lib.nepl"), "fn inc <(i32)->i32> (n):\n    n\n").unwrap();
+    std::fs::write(
+        dir.path().join("util.nepl"),
+        "fn dec <(i32)->i32> (n):\n    n\n",
+    )
+    .unwrap();
+    let source =
+        "#import \"lib\" as *\n#import \"./util\" as *\n\nfn main <()->i32> ():\n    dec inc 1\n";
+    let main = dir.path().join("main.nepl");
+    std::fs::write(&main, source).unwrap();
+
+    let mut loader = Loader::new(stdlib.clone());
+    let result = loader.load(&main);
+    let loaded = LoadedSources::new(result, &loader);
+    let result = semantic_tokens_loaded(&loaded, &stdlib.display().to_string());
+    assert!(result.ok, "{:?}", result.diagnostics);
+    let calls: Vec<(&str, Vec<&str>)> = result
+        .tokens
+        .iter()
+        .filter(|t| t.range.start_line == 4)
+        .map(|t| {
+            (
+                &source[t.range.start as usize..t.range.end as usize],
+                t.modifiers.names(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        calls,
+        vec![("dec", vec![]), ("inc", vec!["default_library"])]
+    );
+}
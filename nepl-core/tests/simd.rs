@@ -0,0 +1,202 @@
+mod harness;
+use harness::{compile_diags, compile_src, find_error, run_main_i32};
+
+use nepl_core::diagnostic::Severity;
+use nepl_core::simd::module_uses_simd;
+use wasmparser::{Operator, Parser, Payload};
+
+/// Every operator in every function body, `Debug`-formatted.
+fn operators(wasm: &[u8]) -> Vec<String> {
+    let mut ops = Vec::new();
+    for payload in Parser::new(0).parse_all(wasm) {
+        if let Payload::CodeSectionEntry(body) = payload.expect("payload") {
+            for op in body.get_operators_reader().expect("operators") {
+                ops.push(format!("{:?}", op.expect("operator")));
+            }
+        }
+    }
+    ops
+}
+
+#[test]
+fn simd_stdlib_lowers_to_wasm_simd_instructions() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/mem" as *
+#import "core/simd" as *
+
+fn main <()*>i32> ():
+    let p <i32> alloc 16
+    v128_store p i32x4_mul i32x4_splat 3 i32x4_add i32x4_splat 1 i32x4_splat 4
+    let f <v128> f32x4_div f32x4_splat 9.0 f32x4_splat 2.0
+    add i32x4_sum i32x4_reverse v128_load p i32_trunc_f32_s f32x4_get f 1
+"#;
+    assert_eq!(run_main_i32(src), 64);
+    let wasm = compile_src(src);
+    assert!(module_uses_simd(&wasm));
+    let ops = operators(&wasm);
+    for expected in [
+        "I32x4Splat",
+        "I32x4Add",
+        "I32x4Mul",
+        "I32x4ExtractLane { lane: 3 }",
+        "F32x4Splat",
+        "F32x4Div",
+        "F32x4ExtractLane { lane: 1 }",
+        "V128Load",
+        "V128Store",
+    ] {
+        assert!(
+            ops.iter().any(|op| op.starts_with(expected)),
+            "missing {}: {:?}",
+            expected,
+            ops
+        );
+    }
+    // i32 lanes 3,2,1,0 become byte lanes 12..15, 8..11, 4..7, 0..3
+    let reverse = Operator::I8x16Shuffle {
+        lanes: [12, 13, 14, 15, 8, 9, 10, 11, 4, 5, 6, 7, 0, 1, 2, 3],
+    };
+    assert!(ops.contains(&format!("{:?}", reverse)));
+}
+
+#[test]
+fn v128_fields_take_sixteen_byte_slots() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/simd" as *
+
+struct Pair:
+    v <v128>
+    tag <i32>
+
+enum Packed:
+    Lanes <v128>
+    Empty
+
+fn main <()*>i32> ():
+    let p <Pair> Pair i32x4_splat 7 5
+    let e <Packed> Packed::Lanes p.v
+    match e:
+        Packed::Lanes v:
+            add p.tag i32x4_sum v
+        Packed::Empty:
+            0
+"#;
+    let wasm = compile_src(src);
+    let ops = operators(&wasm);
+    // `tag` sits right after the 16-byte vector, the enum payload after a padded tag slot
+    assert!(ops.iter().any(|op| op == "I32Const { value: 16 }"));
+    assert!(ops.iter().any(|op| op == "I32Const { value: 32 }"));
+    assert!(ops.iter().any(|op| op.starts_with("V128Store")));
+    assert!(ops.iter().any(|op| op.starts_with("V128Load")));
+    assert_eq!(run_main_i32(src), 33);
+}
+
+#[test]
+fn simd_replace_lane_wrappers_take_runtime_lanes() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/simd" as *
+
+fn main <()*>i32> ():
+    let v <v128> i32x4_replace_lane i32x4_splat 1 2 10
+    let f <v128> f32x4_replace_lane f32x4_splat 0.5 5 6.0
+    add mul i32x4_sum v 10 i32_trunc_f32_s f32x4_get f 1
+"#;
+    // lanes (1, 1, 10, 1) and (0.5, 6.0, 0.5, 0.5): lane 5 wraps to 1
+    assert_eq!(run_main_i32(src), 136);
+}
+
+#[test]
+fn scalar_module_does_not_need_simd() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+fn main <()->i32> ():
+    #intrinsic "size_of" <v128> ()
+"#;
+    assert!(!module_uses_simd(&compile_src(src)));
+    assert_eq!(run_main_i32(src), 16);
+}
+
+#[test]
+fn simd_lane_index_must_be_literal_in_range() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/simd" as *
+
+fn lane <(v128,i32)->i32> (v, i):
+    #intrinsic "i32x4_extract_lane" <> (v, i)
+
+fn high <(v128)->i32> (v):
+    #intrinsic "i32x4_extract_lane" <> (v, 4)
+
+fn main <()->i32> ():
+    high i32x4_splat 1
+"#;
+    let diags = compile_diags(src);
+    let message = "lane index of 'i32x4_extract_lane' must be an integer literal in 0..4";
+    assert_eq!(
+        diags
+            .iter()
+            .filter(|d| d.severity == Severity::Error && d.message == message)
+            .count(),
+        2,
+        "{:?}",
+        diags
+    );
+}
+
+#[test]
+fn simd_intrinsic_rejects_scalar_operand() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+fn bad <(i32,i32)->v128> (a, b):
+    #intrinsic "i32x4_add" <> (a, b)
+
+fn main <()->i32> ():
+    0
+"#;
+    let diags = compile_diags(src);
+    find_error(&diags, "intrinsic argument type mismatch (expected v128)");
+}
+
+#[test]
+fn wasm_block_accepts_v128_instructions() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+fn twice <(i32)->v128> (x):
+    #wasm:
+        local.get $x
+        i32x4.splat
+        local.get $x
+        i32x4.splat
+        i32x4.add
+
+fn main <()->i32> ():
+    #intrinsic "i32x4_extract_lane" <> (twice 21, 0)
+"#;
+    let wasm = compile_src(src);
+    assert!(module_uses_simd(&wasm));
+    assert!(operators(&wasm).iter().any(|op| op == "I32x4Add"));
+    assert_eq!(run_main_i32(src), 42);
+}
@@ -0,0 +1,114 @@
+mod harness;
+use harness::{compile_diags, find_error, run_main_capture_stdout, run_main_wasi_i32};
+
+#[test]
+fn static_mut_keeps_state_across_calls() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasi
+#import "core/math" as *
+
+const BASE <i32> 10
+static START <i32> add BASE 5
+static mut COUNTER <i32> START
+
+fn bump <()*>i32> ():
+    set COUNTER add COUNTER 1;
+    COUNTER
+
+fn main <()*>i32> ():
+    bump;
+    bump;
+    add bump START
+"#;
+    assert_eq!(run_main_wasi_i32(src), 33);
+}
+
+#[test]
+fn str_and_aggregate_statics_live_in_static_data() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasi
+#import "core/math" as *
+#import "std/stdio" as *
+
+struct Pos:
+    x <i32>
+    y <i32>
+
+static mut NAME <str> "nepl"
+static ORIGIN <Pos> Pos 1 2
+
+fn main <()*>i32> ():
+    print NAME;
+    set NAME "done";
+    print NAME;
+    let p <Pos> ORIGIN;
+    print_i32 add get p "x" get p "y";
+    0
+"#;
+    assert_eq!(run_main_capture_stdout(src), "nepldone3");
+}
+
+#[test]
+fn immutable_static_cannot_be_assigned() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+static LIMIT <i32> 3
+
+fn main <()*>i32> ():
+    set LIMIT 4;
+    LIMIT
+"#;
+    let diags = compile_diags(src);
+    find_error(&diags, "cannot assign to immutable static 'LIMIT'");
+}
+
+#[test]
+fn pure_functions_cannot_read_static_mut() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+static mut COUNT <i32> 0
+
+fn peek <()->i32> ():
+    COUNT
+
+fn main <()*>i32> ():
+    peek
+"#;
+    let diags = compile_diags(src);
+    find_error(&diags, "pure context cannot read mutable static 'COUNT'");
+}
+
+#[test]
+fn statics_are_initialized_in_declaration_order() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+static FIRST <i32> SECOND
+static SECOND <i32> 2
+
+fn main <()*>i32> ():
+    FIRST
+"#;
+    let diags = compile_diags(src);
+    let d = find_error(&diags, "cannot evaluate the initializer of static 'FIRST'");
+    assert!(
+        d.secondary.iter().any(|l| l
+            .message
+            .as_deref()
+            .is_some_and(|m| m.contains("read before it is initialized"))),
+        "{:?}",
+        d.secondary
+    );
+}
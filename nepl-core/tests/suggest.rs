@@ -0,0 +1,222 @@
+mod harness;
+use harness::{compile_diags, stdlib_root};
+use nepl_core::diagnostic::Diagnostic;
+use nepl_core::loader::{Loader, LoaderError};
+use nepl_core::suggest::{closest_matches, edit_distance, StdlibIndex, UNDEFINED_IDENTIFIER};
+use nepl_core::{CompileOptions, CompileTarget, CoreError};
+use std::fs;
+use std::path::PathBuf;
+
+fn find<'a>(diags: &'a [Diagnostic], prefix: &str) -> &'a Diagnostic {
+    diags
+        .iter()
+        .find(|d| d.message.starts_with(prefix))
+        .unwrap_or_else(|| panic!("no diagnostic starting with {:?}: {:?}", prefix, diags))
+}
+
+#[test]
+fn edit_distance_and_ranking() {
+    assert_eq!(edit_distance("stdio", "stdoi"), 1);
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+    assert_eq!(edit_distance("", "abc"), 3);
+    assert_eq!(edit_distance("print", "print"), 0);
+    let got = closest_matches("lenght", ["length", "len", "height", "zzz"], 3);
+    assert_eq!(got[0], "length");
+    assert!(!got.contains(&"zzz".to_string()));
+}
+
+#[test]
+fn undefined_identifier_suggests_local() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+fn main <()->i32> ():
+    let counter <i32> 1
+    countr
+"#;
+    let diags = compile_diags(src);
+    let d = find(&diags, "undefined identifier");
+    assert_eq!(d.message, "undefined identifier 'countr'");
+    assert_eq!(d.code, Some(UNDEFINED_IDENTIFIER));
+    assert_eq!(d.notes, vec!["did you mean `counter`?".to_string()]);
+}
+
+#[test]
+fn undefined_identifier_skips_the_binding_being_defined() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+fn main <()->i32> ():
+    let count <i32> coun
+    count
+"#;
+    let diags = compile_diags(src);
+    let d = find(&diags, "undefined identifier");
+    assert_eq!(d.message, "undefined identifier 'coun'");
+    assert!(d.notes.is_empty(), "{:?}", d.notes);
+}
+
+#[test]
+fn undefined_identifier_suggests_function_overload_name() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+fn compute <(i32)->i32> (x):
+    x
+
+fn compute <(bool)->i32> (x):
+    0
+
+fn main <()->i32> ():
+    comptue 1
+"#;
+    let diags = compile_diags(src);
+    let d = find(&diags, "undefined identifier");
+    assert!(
+        d.notes.iter().any(|n| n.contains("`compute`")),
+        "{:?}",
+        d.notes
+    );
+}
+
+#[test]
+fn unknown_field_suggests_struct_field() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+struct Point:
+    x <i32>
+    height <i32>
+
+fn main <()->i32> ():
+    let p <Point> Point 1 2
+    p.hieght
+"#;
+    let diags = compile_diags(src);
+    let d = find(&diags, "struct has no field");
+    assert!(
+        d.notes.iter().any(|n| n.contains("`height`")),
+        "{:?}",
+        d.notes
+    );
+}
+
+#[test]
+fn unknown_match_variant_suggests_variant() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+enum Shape:
+    Circle
+    Square
+
+fn main <()->i32> ():
+    match Shape::Circle:
+        Cirlce:
+            1
+        Square:
+            2
+"#;
+    let diags = compile_diags(src);
+    let d = find(&diags, "unknown enum variant");
+    assert!(
+        d.notes.iter().any(|n| n.contains("`Circle`")),
+        "{:?}",
+        d.notes
+    );
+}
+
+#[test]
+fn missing_std_import_suggests_module() {
+    let dir = tempfile::tempdir().unwrap();
+    let main = dir.path().join("main.nepl");
+    fs::write(
+        &main,
+        "#entry main\n#import \"std/stdoi\" as *\nfn main <()->i32> (): 0\n",
+    )
+    .unwrap();
+    let mut loader = Loader::new(stdlib_root());
+    let diags = match loader.load(&main) {
+        Err(LoaderError::Core(CoreError::Diagnostics(diags))) => diags,
+        other => panic!("expected diagnostics, got {:?}", other.map(|_| ())),
+    };
+    let d = find(&diags, "cannot find module 'std/stdoi'");
+    assert!(
+        d.notes.iter().any(|n| n.contains("`std/stdio`")),
+        "{:?}",
+        d.notes
+    );
+}
+
+#[test]
+fn missing_relative_import_suggests_sibling() {
+    let dir = tempfile::tempdir().unwrap();
+    let main = dir.path().join("main.nepl");
+    fs::write(
+        dir.path().join("helpers.nepl"),
+        "fn helper <()->i32> (): 1\n",
+    )
+    .unwrap();
+    fs::write(
+        &main,
+        "#entry main\n#import \"./helper\" as *\nfn main <()->i32> (): 0\n",
+    )
+    .unwrap();
+    let mut loader = Loader::new(stdlib_root());
+    let diags = match loader.load(&main) {
+        Err(LoaderError::Core(CoreError::Diagnostics(diags))) => diags,
+        other => panic!("expected diagnostics, got {:?}", other.map(|_| ())),
+    };
+    let d = find(&diags, "cannot find module './helper'");
+    assert!(
+        d.notes.iter().any(|n| n.contains("`./helpers`")),
+        "{:?}",
+        d.notes
+    );
+}
+
+#[test]
+fn stdlib_index_lists_exporters() {
+    let index = StdlibIndex::scan(&stdlib_root());
+    assert!(index.modules().iter().any(|m| m == "std/stdio"));
+    assert!(index
+        .exporters_of("print")
+        .contains(&"std/stdio".to_string()));
+}
+
+#[test]
+fn stdlib_index_annotates_undefined_identifier() {
+    let src = "#entry main\n#indent 4\n\nfn main <()*>()> ():\n    print \"hi\"\n";
+    let mut loader = Loader::new(stdlib_root());
+    let res = loader
+        .load_inline(PathBuf::from("main.nepl"), src.to_string())
+        .expect("load");
+    let mut diags = match nepl_core::compile_module(
+        res.module,
+        CompileOptions {
+            target: Some(CompileTarget::Wasi),
+            verbose: false,
+            profile: None,
+        },
+    ) {
+        Err(CoreError::Diagnostics(diags)) => diags,
+        other => panic!("expected diagnostics, got {:?}", other.map(|_| ())),
+    };
+    StdlibIndex::scan(&stdlib_root()).annotate(&mut diags, &res.source_map);
+    let d = find(&diags, "undefined identifier 'print'");
+    assert!(
+        d.notes.iter().any(|n| n.contains("#import \"std/stdio\"")),
+        "{:?}",
+        d.notes
+    );
+}
@@ -0,0 +1,205 @@
+mod harness;
+use harness::{compile_diags, find_error, run_main_i32};
+
+use nepl_core::hir::{HirBody, HirExprKind};
+use nepl_core::span::FileId;
+use nepl_core::{lexer, parser, passes, typecheck, BuildProfile, CompileTarget};
+
+#[test]
+fn try_unwraps_some_and_returns_none() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/option" as *
+
+struct Pair:
+    a <i32>
+    b <i32>
+
+fn half <(i32)->Option<i32>> (n):
+    if:
+        cond:
+            eq 0 i32_rem_s n 2
+        then:
+            Option::Some i32_div_s n 2
+        else:
+            Option::None
+
+fn quarter <(i32)->Option<i32>> (n):
+    let p Pair n 1
+    let h ? half p.a
+    let q ? half h
+    Option::Some add q p.b
+
+fn unopt <(Option<i32>)->i32> (o):
+    match o:
+        Option::Some v:
+            v
+        Option::None:
+            100
+
+fn main <()->i32> ():
+    add unopt quarter 8 unopt quarter 6
+"#;
+    assert_eq!(run_main_i32(src), 103);
+}
+
+#[test]
+fn try_propagates_err_payload() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/result" as *
+
+fn check <(i32)->Result<i32,i32>> (n):
+    if:
+        cond:
+            lt n 0
+        then:
+            Result::Err n
+        else:
+            Result::Ok mul n 10
+
+fn twice <(i32)->Result<i32,i32>> (n):
+    let a ? check n
+    let b ? check sub a 100
+    Result::Ok add a b
+
+fn unres <(Result<i32,i32>)->i32> (r):
+    match r:
+        Result::Ok v:
+            v
+        Result::Err e:
+            mul -1000 e
+
+fn main <()->i32> ():
+    add unres twice 20 unres twice 5
+"#;
+    assert_eq!(run_main_i32(src), 51200);
+}
+
+#[test]
+fn try_requires_matching_function_result() {
+    let diags = compile_diags(
+        r#"
+#entry main
+#indent 4
+#target wasm
+
+enum Option<.T>:
+    None
+    Some <.T>
+
+fn f <()->Option<i32>> ():
+    Option::Some 1
+
+fn main <()->i32> ():
+    let v ? f
+    v
+"#,
+    );
+    find_error(
+        &diags,
+        "`?` on Option requires the function to return Option, found i32",
+    );
+    let diags = compile_diags(
+        r#"
+#entry main
+#indent 4
+#target wasm
+
+enum Result<.T,.E>:
+    Ok <.T>
+    Err <.E>
+
+fn g <()->Result<i32,bool>> ():
+    Result::Ok 1
+
+fn h <()->Result<i32,i32>> ():
+    let v ? g
+    Result::Ok v
+
+fn main <()->i32> ():
+    0
+"#,
+    );
+    find_error(
+        &diags,
+        "mismatched error type for `?`: the function returns i32, found bool",
+    );
+}
+
+#[test]
+fn try_early_return_drops_live_bindings() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+enum Option<.T>:
+    None
+    Some <.T>
+
+struct Pair:
+    a <i32>
+    b <i32>
+
+fn half <(i32)->Option<i32>> (n):
+    Option::Some n
+
+fn f <(i32)->Option<Pair>> (n):
+    let p Pair n 1
+    let q Pair 2 n
+    let h ? half p.a
+    Option::Some q
+
+fn main <()->i32> ():
+    f 1;
+    0
+"#;
+    let module = parser::parse_tokens(FileId(0), lexer::lex(FileId(0), src))
+        .module
+        .expect("parse");
+    let tc = typecheck::typecheck(&module, CompileTarget::Wasm, BuildProfile::Debug);
+    let mut hir = tc.module.unwrap_or_else(|| panic!("{:?}", tc.diagnostics));
+    passes::insert_drops(&mut hir, tc.types.unit());
+
+    let f = hir
+        .functions
+        .iter()
+        .find(|f| f.name.starts_with("f__"))
+        .expect("f");
+    let HirBody::Block(body) = &f.body else {
+        panic!("f has no block body");
+    };
+    let arms = body
+        .lines
+        .iter()
+        .find_map(|line| match &line.expr.kind {
+            HirExprKind::Let { name, value, .. } if name == "h" => match &value.kind {
+                HirExprKind::Match { arms, .. } => Some(arms),
+                _ => None,
+            },
+            _ => None,
+        })
+        .expect("`?` is lowered to a match");
+    let none = arms.iter().find(|a| a.variant == "None").expect("None arm");
+    let HirExprKind::Block(exit) = &none.body.kind else {
+        panic!("no drops before the early return: {:?}", none.body.kind);
+    };
+    let dropped: Vec<&str> = exit
+        .lines
+        .iter()
+        .filter_map(|line| match &line.expr.kind {
+            HirExprKind::Drop { name } => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(dropped, ["q", "p"]);
+    let last = exit.lines.last().expect("return");
+    assert!(matches!(last.expr.kind, HirExprKind::Return(_)));
+}
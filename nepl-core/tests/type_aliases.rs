@@ -0,0 +1,151 @@
+mod harness;
+use harness::{compile_diags, find_error, run_main_i32};
+
+#[test]
+fn type_alias_expands_in_signatures() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "alloc/vec" as *
+
+type Row = Vec<i32>
+type Grid = Vec<Row>
+
+fn width <(Grid)->i32> (g):
+    match vec_get<Row> g 0:
+        Some r:
+            vec_len<i32> r
+        None:
+            0
+
+fn main <()*>i32> ():
+    let mut r <Row> vec_new<i32>
+    set r vec_push<i32> r 1
+    set r vec_push<i32> r 2
+    let g <Grid> vec_push<Row> vec_new<Row> r
+    width g
+"#;
+    assert_eq!(run_main_i32(src), 2);
+}
+
+#[test]
+fn generic_type_alias_substitutes_arguments() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/option" as *
+
+type Maybe <.T> = Option<.T>
+
+fn or_zero <(Maybe<i32>)->i32> (m):
+    match m:
+        Some v:
+            v
+        None:
+            0
+
+fn main <()->i32> ():
+    let a <Maybe<i32>> Option::Some 40
+    let b <Maybe<i32>> Option::None
+    add or_zero a add or_zero b 2
+"#;
+    assert_eq!(run_main_i32(src), 42);
+}
+
+#[test]
+fn newtype_wraps_and_unwraps() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+
+newtype NodeId = i32
+
+fn next_id <(NodeId)->NodeId> (n):
+    NodeId add NodeId::inner n 1
+
+fn main <()->i32> ():
+    let n <NodeId> next_id NodeId 41
+    NodeId::inner n
+"#;
+    assert_eq!(run_main_i32(src), 42);
+}
+
+#[test]
+fn newtype_is_distinct_for_overloads_and_impls() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+
+newtype Meters = i32
+
+trait Describe:
+    fn code <(Self)->i32> (a):
+        0
+
+impl Describe for Meters:
+    fn code <(Meters)->i32> (m):
+        mul Meters::inner m 100
+
+impl Describe for i32:
+    fn code <(i32)->i32> (x):
+        x
+
+fn scale <(Meters)->i32> (m):
+    mul Meters::inner m 2
+
+fn scale <(i32)->i32> (x):
+    x
+
+fn main <()->i32> ():
+    let m <Meters> Meters 3
+    add add scale m scale 4 add Describe::code m Describe::code 5
+"#;
+    assert_eq!(run_main_i32(src), 315);
+}
+
+#[test]
+fn newtype_rejects_inner_type() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+newtype NodeId = i32
+
+fn id_of <(NodeId)->i32> (n):
+    NodeId::inner n
+
+fn main <()->i32> ():
+    id_of 5
+"#;
+    let diags = compile_diags(src);
+    let d = find_error(&diags, "no matching overload found for `id_of`");
+    assert_eq!(
+        d.secondary[0].message.as_deref(),
+        Some("candidate `id_of <(NodeId)->i32>` rejected: argument 1 has type `i32`, expected `NodeId`")
+    );
+}
+
+#[test]
+fn cyclic_type_alias_is_rejected() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+type A = B
+type B = A
+
+fn main <()->i32> ():
+    0
+"#;
+    find_error(&compile_diags(src), "type alias 'A' refers to itself");
+}
@@ -0,0 +1,123 @@
+mod harness;
+use harness::compile_diags;
+use nepl_core::analysis::analyze_semantics;
+use nepl_core::diagnostic::Severity;
+use nepl_core::types::{TypeCtx, TypeKind};
+
+fn compile_errors(src: &str) -> Vec<(usize, String)> {
+    compile_diags(src)
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .map(|d| {
+            let line = src[..d.primary.span.start as usize].matches('\n').count() + 1;
+            (line, d.message.clone())
+        })
+        .collect()
+}
+
+const OVERLOADS: &str = "fn add <(i32, i32)->i32> (a, b):
+    a
+
+fn add <(f32, f32)->f32> (a, b):
+    a
+
+struct P:
+    x <i32>
+";
+
+#[test]
+fn error_type_unifies_with_everything() {
+    let mut ctx = TypeCtx::new();
+    let (err, i32_ty) = (ctx.error(), ctx.i32());
+    assert!(ctx.unify(err, i32_ty).is_ok());
+    assert!(ctx.unify(ctx.bool(), err).is_ok());
+    // a variable is left for the real constraint that comes later
+    let var = ctx.fresh_var(None);
+    ctx.unify(var, err).unwrap();
+    ctx.unify(var, i32_ty).unwrap();
+    let resolved = ctx.resolve(var);
+    assert_eq!(ctx.get(resolved), TypeKind::I32);
+    assert_eq!(ctx.type_to_string(err), "{error}");
+}
+
+#[test]
+fn unknown_functions_are_reported_once() {
+    // the arity of `scale` is unknown, so its arguments are not "extra values"
+    let src = "fn main <()->i32> ():\n    let v scale 1 2;\n    v\n";
+    assert_eq!(
+        compile_errors(src),
+        vec![(2, "undefined identifier 'scale'".to_string())]
+    );
+}
+
+#[test]
+fn independent_mistakes_are_reported_once_each() {
+    let src = format!(
+        "{OVERLOADS}
+fn main <()->i32> ():
+    let p missing;
+    let q add p 1;
+    let r P q;
+    let s r.x;
+    let t add 1 true;
+    add s p.x
+"
+    );
+    let errors = compile_errors(&src);
+    let lines: Vec<usize> = errors.iter().map(|e| e.0).collect();
+    assert_eq!(lines, vec![11, 15], "{errors:?}");
+    assert!(errors[1]
+        .1
+        .starts_with("no matching overload found for `add`"));
+}
+
+#[test]
+fn failed_calls_keep_the_block_value() {
+    // the failed call is the function's value: no "return type" follow-up
+    let src = format!("{OVERLOADS}\nfn main <()->i32> ():\n    add 1 true\n");
+    let errors = compile_errors(&src);
+    assert_eq!(errors.len(), 1, "{errors:?}");
+
+    // an argument of the error type cannot choose between overloads
+    let src =
+        format!("{OVERLOADS}\nfn main <()->i32> ():\n    let v add missing missing;\n    0\n");
+    assert_eq!(
+        compile_errors(&src),
+        vec![
+            (11, "undefined identifier 'missing'".to_string()),
+            (11, "undefined identifier 'missing'".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn failed_field_access_has_the_error_type() {
+    // neither the result type of `main` nor the `add` overloads report the failed access again
+    let src = format!("{OVERLOADS}\nfn main <()->i32> ():\n    let n 1;\n    add n.x 2\n");
+    assert_eq!(
+        compile_errors(&src),
+        vec![(12, "cannot access field on non-composite type".to_string())]
+    );
+}
+
+#[test]
+fn parse_error_placeholders_do_not_cascade() {
+    let src = "fn main <()->i32> ():\n    let x 1;\n    if x:\n    x\n";
+    let errors = compile_errors(src);
+    assert_eq!(errors.len(), 1, "{errors:?}");
+}
+
+#[test]
+fn semantics_cover_functions_with_errors() {
+    let src = "fn main <()->i32> ():\n    let a missing;\n    let b 2;\n    b\n";
+    let result = analyze_semantics(src);
+    assert!(!result.ok);
+    let types: Vec<(u32, &str, &str)> = result
+        .expressions
+        .iter()
+        .map(|e| (e.span.start_line + 1, e.kind, e.inferred_type.as_str()))
+        .collect();
+    assert!(types.contains(&(2, "Unit", "{error}")), "{types:?}");
+    assert!(types.contains(&(3, "LiteralI32", "i32")), "{types:?}");
+    assert!(types.contains(&(4, "Var", "i32")), "{types:?}");
+}
@@ -0,0 +1,233 @@
+mod harness;
+use harness::{compile_diags, find_error, run_main_i32};
+
+#[test]
+fn wasm_loops_calls_and_globals() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+
+static mut HITS <i32> 0
+
+fn triple <(i32)->i32> (x):
+    mul x 3
+
+fn sum_triples <(i32)*>i32> (n):
+    #wasm:
+        local $i i32
+        local $acc i32
+        block $done
+            loop $next
+                local.get $i
+                local.get $n
+                i32.ge_s
+                br_if $done
+                local.get $acc
+                local.get $i
+                call triple
+                i32.add
+                local.set $acc
+                global.get $HITS
+                i32.const 1
+                i32.add
+                global.set $HITS
+                local.get $i
+                i32.const 1
+                i32.add
+                local.tee $i
+                drop
+                br $next
+            end
+        end
+        local.get $acc
+
+fn main <()*>i32> ():
+    let s <i32> sum_triples 5;
+    add s HITS
+"#;
+    assert_eq!(run_main_i32(src), 35);
+}
+
+#[test]
+fn wasm_if_else_and_br_table() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+
+fn sign <(i32)->i32> (x):
+    #wasm:
+        local.get $x
+        i32.const 0
+        i32.lt_s
+        if (result i32)
+            i32.const -1
+        else
+            local.get $x
+            i32.const 0
+            i32.ne
+        end
+
+fn pick <(i32)->i32> (k):
+    #wasm:
+        block $c
+            block $b
+                block $a
+                    local.get $k
+                    br_table $a $b $c
+                end
+                i32.const 10
+                return
+            end
+            i32.const 20
+            return
+        end
+        i32.const 30
+
+fn main <()->i32> ():
+    add add sign -7 sign 9 add pick 0 add pick 1 pick 7
+"#;
+    assert_eq!(run_main_i32(src), 60);
+}
+
+#[test]
+fn wasm_bulk_memory_instructions() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/mem" as *
+
+fn splat <(i32,i32)*>i32> (p, v):
+    #wasm:
+        local.get $p
+        local.get $v
+        i32.const 4
+        memory.fill
+        local.get $p
+        i32.const 4
+        i32.add
+        local.get $p
+        i32.const 4
+        memory.copy
+        local.get $p
+        i32.load offset=4
+
+fn main <()*>i32> ():
+    let p <i32> alloc 8;
+    splat p 1
+"#;
+    assert_eq!(run_main_i32(src), 0x0101_0101);
+}
+
+#[test]
+fn wasm_operand_types_are_checked() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+fn f <(i32)->i32> (x):
+    #wasm:
+        local.get $x
+        i64.const 1
+        i32.add
+
+fn main <()->i32> ():
+    f 1
+"#;
+    let diags = compile_diags(src);
+    find_error(&diags, "type mismatch in #wasm at `i32.add`: expected i32, found i64");
+}
+
+#[test]
+fn wasm_block_results_are_checked() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+fn f <(i32)->i32> (x):
+    #wasm:
+        local.get $x
+        if (result i32)
+            i32.const 1
+        end
+
+fn main <()->i32> ():
+    f 1
+"#;
+    let diags = compile_diags(src);
+    find_error(&diags, "`if` with a result requires an `else` branch in #wasm");
+}
+
+#[test]
+fn wasm_unknown_label_is_reported() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+fn f <(i32)->i32> (x):
+    #wasm:
+        block $out
+            br $missing
+        end
+        local.get $x
+
+fn main <()->i32> ():
+    f 1
+"#;
+    let diags = compile_diags(src);
+    find_error(&diags, "unknown label in #wasm: $missing");
+}
+
+#[test]
+fn wasm_memory_offsets_are_applied() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+#import "core/math" as *
+#import "core/mem" as *
+
+fn second <(i32)*>i32> (p):
+    #wasm:
+        local.get $p
+        i32.const 11
+        i32.store
+        local.get $p
+        i32.const 22
+        i32.store offset=4 align=4
+        local.get $p
+        i32.load offset=4
+
+fn main <()*>i32> ():
+    let p <i32> alloc 8;
+    second p
+"#;
+    assert_eq!(run_main_i32(src), 22);
+}
+
+#[test]
+fn wasm_memory_alignment_is_checked() {
+    let src = r#"
+#entry main
+#indent 4
+#target wasm
+
+fn f <(i32)->i32> (p):
+    #wasm:
+        local.get $p
+        i32.load align=8
+
+fn main <()->i32> ():
+    f 0
+"#;
+    let diags = compile_diags(src);
+    find_error(&diags, "alignment of i32.load must not be larger than 4");
+}
@@ -2,16 +2,18 @@ use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 use js_sys::{Reflect, Uint8Array};
-use nepl_core::ast::{Block, Directive, FnBody, MatchArm, PrefixExpr, PrefixItem, Stmt, Symbol};
+use nepl_core::analysis::{
+    self, DiagnosticInfo, LineIndex, LoadedSources, NameResolutionOptions, SourceIndex, TokenInfo,
+};
+use nepl_core::ast::{Block, Directive, FnBody, PrefixExpr, PrefixItem, Stmt};
 use nepl_core::diagnostic::{Diagnostic, Severity};
 use nepl_core::error::CoreError;
-use nepl_core::hir::{HirBlock, HirExpr, HirExprKind, HirLine};
-use nepl_core::lexer::{lex, Token, TokenKind};
-use nepl_core::loader::{Loader, SourceMap};
+use nepl_core::lexer::lex;
+use nepl_core::loader::{LoadResult, Loader, LoaderError, SourceMap};
 use nepl_core::parser::parse_tokens;
 use nepl_core::span::{FileId, Span};
-use nepl_core::typecheck::typecheck;
-use nepl_core::{compile_module, BuildProfile, CompileOptions, CompileTarget};
+use nepl_core::{compile_module, BuildProfile, CompileOptions};
+use serde::Serialize;
 use wasmprinter::print_bytes;
 use wasm_bindgen::prelude::*;
 
@@ -253,248 +255,15 @@ fn parse_emit_list(emit: JsValue) -> Result<Vec<String>, JsValue> {
     Err(JsValue::from_str("emit must be a string or an array of strings"))
 }
 
-fn line_col_of(source: &str, byte_pos: u32) -> (u32, u32) {
-    let mut line = 0u32;
-    let mut col = 0u32;
-    let mut seen = 0u32;
-    for ch in source.chars() {
-        if seen >= byte_pos {
-            break;
-        }
-        let len = ch.len_utf8() as u32;
-        if seen + len > byte_pos {
-            break;
-        }
-        seen += len;
-        if ch == '\n' {
-            line += 1;
-            col = 0;
-        } else {
-            col += 1;
-        }
-    }
-    (line, col)
+/// 解析結果を JS の plain object に変換します（map は object、`None` は null）。
+fn to_js<T: Serialize + ?Sized>(value: &T) -> JsValue {
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .unwrap_or(JsValue::NULL)
 }
 
 fn span_to_js(source: &str, span: Span) -> JsValue {
-    let obj = js_sys::Object::new();
-    let (start_line, start_col) = line_col_of(source, span.start);
-    let (end_line, end_col) = line_col_of(source, span.end);
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("file_id"),
-        &JsValue::from_f64(span.file_id.0 as f64),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("start"),
-        &JsValue::from_f64(span.start as f64),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("end"),
-        &JsValue::from_f64(span.end as f64),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("start_line"),
-        &JsValue::from_f64(start_line as f64),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("start_col"),
-        &JsValue::from_f64(start_col as f64),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("end_line"),
-        &JsValue::from_f64(end_line as f64),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("end_col"),
-        &JsValue::from_f64(end_col as f64),
-    );
-    obj.into()
-}
-
-fn token_kind_name(kind: &TokenKind) -> &'static str {
-    match kind {
-        TokenKind::Indent => "Indent",
-        TokenKind::Dedent => "Dedent",
-        TokenKind::Newline => "Newline",
-        TokenKind::Eof => "Eof",
-        TokenKind::Colon => "Colon",
-        TokenKind::Semicolon => "Semicolon",
-        TokenKind::Pipe => "Pipe",
-        TokenKind::LParen => "LParen",
-        TokenKind::RParen => "RParen",
-        TokenKind::Comma => "Comma",
-        TokenKind::LAngle => "LAngle",
-        TokenKind::RAngle => "RAngle",
-        TokenKind::Arrow(_) => "Arrow",
-        TokenKind::PathSep => "PathSep",
-        TokenKind::At => "At",
-        TokenKind::Dot => "Dot",
-        TokenKind::Ampersand => "Ampersand",
-        TokenKind::Star => "Star",
-        TokenKind::Minus => "Minus",
-        TokenKind::Equals => "Equals",
-        TokenKind::Ident(_) => "Ident",
-        TokenKind::IntLiteral(_) => "IntLiteral",
-        TokenKind::FloatLiteral(_) => "FloatLiteral",
-        TokenKind::BoolLiteral(_) => "BoolLiteral",
-        TokenKind::StringLiteral(_) => "StringLiteral",
-        TokenKind::UnitLiteral => "UnitLiteral",
-        TokenKind::KwFn => "KwFn",
-        TokenKind::KwLet => "KwLet",
-        TokenKind::KwMut => "KwMut",
-        TokenKind::KwNoShadow => "KwNoShadow",
-        TokenKind::KwSet => "KwSet",
-        TokenKind::KwIf => "KwIf",
-        TokenKind::KwWhile => "KwWhile",
-        TokenKind::KwCond => "KwCond",
-        TokenKind::KwThen => "KwThen",
-        TokenKind::KwElse => "KwElse",
-        TokenKind::KwDo => "KwDo",
-        TokenKind::KwStruct => "KwStruct",
-        TokenKind::KwEnum => "KwEnum",
-        TokenKind::KwMatch => "KwMatch",
-        TokenKind::KwTrait => "KwTrait",
-        TokenKind::KwImpl => "KwImpl",
-        TokenKind::KwFor => "KwFor",
-        TokenKind::KwPub => "KwPub",
-        TokenKind::KwBlock => "KwBlock",
-        TokenKind::KwTuple => "KwTuple",
-        TokenKind::KwMlstr => "KwMlstr",
-        TokenKind::DirEntry(_) => "DirEntry",
-        TokenKind::DirTarget(_) => "DirTarget",
-        TokenKind::DirImport(_) => "DirImport",
-        TokenKind::DirUse(_) => "DirUse",
-        TokenKind::DirIfTarget(_) => "DirIfTarget",
-        TokenKind::DirIfProfile(_) => "DirIfProfile",
-        TokenKind::DirWasm => "DirWasm",
-        TokenKind::DirLlvmIr => "DirLlvmIr",
-        TokenKind::DirIndentWidth(_) => "DirIndentWidth",
-        TokenKind::DirInclude(_) => "DirInclude",
-        TokenKind::DirExtern { .. } => "DirExtern",
-        TokenKind::DirIntrinsic => "DirIntrinsic",
-        TokenKind::DirPrelude(_) => "DirPrelude",
-        TokenKind::DirNoPrelude => "DirNoPrelude",
-        TokenKind::WasmText(_) => "WasmText",
-        TokenKind::LlvmIrText(_) => "LlvmIrText",
-        TokenKind::MlstrLine(_) => "MlstrLine",
-    }
-}
-
-fn token_extra(kind: &TokenKind) -> Option<String> {
-    match kind {
-        TokenKind::Arrow(e) => Some(format!("{:?}", e)),
-        TokenKind::Ident(v)
-        | TokenKind::IntLiteral(v)
-        | TokenKind::FloatLiteral(v)
-        | TokenKind::StringLiteral(v)
-        | TokenKind::DirEntry(v)
-        | TokenKind::DirTarget(v)
-        | TokenKind::DirImport(v)
-        | TokenKind::DirUse(v)
-        | TokenKind::DirIfTarget(v)
-        | TokenKind::DirIfProfile(v)
-        | TokenKind::DirInclude(v)
-        | TokenKind::DirPrelude(v)
-        | TokenKind::WasmText(v)
-        | TokenKind::LlvmIrText(v)
-        | TokenKind::MlstrLine(v) => Some(v.clone()),
-        TokenKind::BoolLiteral(v) => Some(v.to_string()),
-        TokenKind::DirIndentWidth(v) => Some(v.to_string()),
-        TokenKind::DirExtern {
-            module,
-            name,
-            func,
-            signature,
-        } => Some(format!(
-            "module={module}, name={name}, func={func}, signature={signature}"
-        )),
-        _ => None,
-    }
-}
-
-fn token_to_js(source: &str, token: &Token) -> JsValue {
-    let obj = js_sys::Object::new();
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("kind"),
-        &JsValue::from_str(token_kind_name(&token.kind)),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("debug"),
-        &JsValue::from_str(&format!("{:?}", token.kind)),
-    );
-    let _ = Reflect::set(&obj, &JsValue::from_str("span"), &span_to_js(source, token.span));
-    if let Some(extra) = token_extra(&token.kind) {
-        let _ = Reflect::set(
-            &obj,
-            &JsValue::from_str("value"),
-            &JsValue::from_str(&extra),
-        );
-    }
-    obj.into()
-}
-
-fn diagnostics_to_js(source: &str, diagnostics: &[Diagnostic]) -> JsValue {
-    let arr = js_sys::Array::new();
-    for d in diagnostics {
-        let obj = js_sys::Object::new();
-        let severity = match d.severity {
-            Severity::Error => "error",
-            Severity::Warning => "warning",
-        };
-        let _ = Reflect::set(
-            &obj,
-            &JsValue::from_str("severity"),
-            &JsValue::from_str(severity),
-        );
-        let _ = Reflect::set(
-            &obj,
-            &JsValue::from_str("code"),
-            &d.code
-                .map(JsValue::from_str)
-                .unwrap_or(JsValue::NULL),
-        );
-        let _ = Reflect::set(
-            &obj,
-            &JsValue::from_str("message"),
-            &JsValue::from_str(&d.message),
-        );
-        let _ = Reflect::set(
-            &obj,
-            &JsValue::from_str("primary"),
-            &span_to_js(source, d.primary.span),
-        );
-
-        let secondary = js_sys::Array::new();
-        for s in &d.secondary {
-            let sub = js_sys::Object::new();
-            let _ = Reflect::set(
-                &sub,
-                &JsValue::from_str("span"),
-                &span_to_js(source, s.span),
-            );
-            let _ = Reflect::set(
-                &sub,
-                &JsValue::from_str("message"),
-                &s.message
-                    .as_ref()
-                    .map(|m| JsValue::from_str(m))
-                    .unwrap_or(JsValue::NULL),
-            );
-            secondary.push(&sub);
-        }
-        let _ = Reflect::set(&obj, &JsValue::from_str("secondary"), &secondary);
-        arr.push(&obj);
-    }
-    arr.into()
+    to_js(&LineIndex::new(source).range(span))
 }
 
 fn expr_to_js(source: &str, expr: &PrefixExpr) -> JsValue {
@@ -529,6 +298,7 @@ fn directive_name(d: &Directive) -> &'static str {
         Directive::Use { .. } => "Use",
         Directive::IfTarget { .. } => "IfTarget",
         Directive::IfProfile { .. } => "IfProfile",
+        Directive::IfConst { .. } => "IfConst",
         Directive::IndentWidth { .. } => "IndentWidth",
         Directive::Extern { .. } => "Extern",
         Directive::Include { .. } => "Include",
@@ -657,6 +427,40 @@ fn stmt_to_js(source: &str, stmt: &Stmt) -> JsValue {
                 &JsValue::from_str(&format!("{:?}", def)),
             );
         }
+        Stmt::TypeAlias(def) => {
+            let _ = Reflect::set(
+                &obj,
+                &JsValue::from_str("kind"),
+                &JsValue::from_str("TypeAlias"),
+            );
+            let _ = Reflect::set(
+                &obj,
+                &JsValue::from_str("name"),
+                &JsValue::from_str(&def.name.name),
+            );
+        }
+        Stmt::Newtype(def) => {
+            let _ = Reflect::set(&obj, &JsValue::from_str("kind"), &JsValue::from_str("Newtype"));
+            let _ = Reflect::set(
+                &obj,
+                &JsValue::from_str("name"),
+                &JsValue::from_str(&def.name.name),
+            );
+        }
+        Stmt::Static(def) => {
+            let _ = Reflect::set(&obj, &JsValue::from_str("kind"), &JsValue::from_str("Static"));
+            let _ = Reflect::set(
+                &obj,
+                &JsValue::from_str("name"),
+                &JsValue::from_str(&def.name.name),
+            );
+            let _ = Reflect::set(
+                &obj,
+                &JsValue::from_str("mutable"),
+                &JsValue::from_bool(def.mutable),
+            );
+            let _ = Reflect::set(&obj, &JsValue::from_str("value"), &expr_to_js(source, &def.value));
+        }
         Stmt::Expr(expr) => {
             let _ = Reflect::set(&obj, &JsValue::from_str("kind"), &JsValue::from_str("Expr"));
             let _ = Reflect::set(&obj, &JsValue::from_str("expr"), &expr_to_js(source, expr));
@@ -676,6 +480,10 @@ fn stmt_to_js(source: &str, stmt: &Stmt) -> JsValue {
                     .unwrap_or(JsValue::NULL),
             );
         }
+        Stmt::Error(span) => {
+            let _ = Reflect::set(&obj, &JsValue::from_str("kind"), &JsValue::from_str("Error"));
+            let _ = Reflect::set(&obj, &JsValue::from_str("span"), &span_to_js(source, *span));
+        }
     }
     obj.into()
 }
@@ -735,6 +543,12 @@ fn prefix_item_to_js(source: &str, item: &PrefixItem) -> JsValue {
                 &JsValue::from_str(&format!("{:?}", m)),
             );
         }
+        PrefixItem::For(f, span) => {
+            let _ = Reflect::set(&obj, &JsValue::from_str("kind"), &JsValue::from_str("For"));
+            let _ = Reflect::set(&obj, &JsValue::from_str("span"), &span_to_js(source, *span));
+            let _ = Reflect::set(&obj, &JsValue::from_str("iter"), &expr_to_js(source, &f.iter));
+            let _ = Reflect::set(&obj, &JsValue::from_str("block"), &block_to_js(source, &f.body));
+        }
         PrefixItem::Pipe(span) => {
             let _ = Reflect::set(&obj, &JsValue::from_str("kind"), &JsValue::from_str("Pipe"));
             let _ = Reflect::set(&obj, &JsValue::from_str("span"), &span_to_js(source, *span));
@@ -766,893 +580,20 @@ fn prefix_item_to_js(source: &str, item: &PrefixItem) -> JsValue {
                 &JsValue::from_str(&format!("{:?}", expr)),
             );
         }
-    }
-    obj.into()
-}
-
-#[derive(Clone)]
-struct NameDefTrace {
-    id: usize,
-    name: String,
-    kind: &'static str,
-    span: Span,
-    scope_depth: usize,
-}
-
-#[derive(Clone)]
-struct NameRefTrace {
-    name: String,
-    span: Span,
-    scope_depth: usize,
-    resolved_def_id: Option<usize>,
-    candidate_def_ids: Vec<usize>,
-}
-
-#[derive(Clone)]
-struct NameShadowTrace {
-    name: String,
-    event_kind: &'static str,
-    span: Span,
-    scope_depth: usize,
-    selected_def_id: Option<usize>,
-    shadowed_def_ids: Vec<usize>,
-    severity: &'static str,
-    message: String,
-}
-
-#[derive(Clone)]
-struct SemanticExprTrace {
-    id: usize,
-    function_name: String,
-    kind: &'static str,
-    span: Span,
-    ty: String,
-    parent_id: Option<usize>,
-    arg_spans: Vec<Span>,
-}
-
-#[derive(Clone)]
-struct SemanticTokenTrace {
-    token_index: usize,
-    inferred_expr_id: Option<usize>,
-    inferred_type: Option<String>,
-    expr_span: Option<Span>,
-    arg_index: Option<usize>,
-    arg_span: Option<Span>,
-}
-
-#[derive(Default)]
-struct NameResolutionTrace {
-    defs: Vec<NameDefTrace>,
-    refs: Vec<NameRefTrace>,
-    shadows: Vec<NameShadowTrace>,
-    scopes: Vec<BTreeMap<String, Vec<usize>>>,
-    warn_important_shadow: bool,
-}
-
-impl NameResolutionTrace {
-    fn new() -> Self {
-        Self::new_with_options(true)
-    }
-
-    fn new_with_options(warn_important_shadow: bool) -> Self {
-        Self {
-            defs: Vec::new(),
-            refs: Vec::new(),
-            shadows: Vec::new(),
-            scopes: vec![BTreeMap::new()],
-            warn_important_shadow,
-        }
-    }
-
-    fn current_depth(&self) -> usize {
-        self.scopes.len().saturating_sub(1)
-    }
-
-    fn push_scope(&mut self) {
-        self.scopes.push(BTreeMap::new());
-    }
-
-    fn pop_scope(&mut self) {
-        if self.scopes.len() > 1 {
-            self.scopes.pop();
-        }
-    }
-
-    fn define(&mut self, name: String, kind: &'static str, span: Span) -> usize {
-        let existing_candidates = self.lookup_candidates(&name);
-        let id = self.defs.len();
-        let depth = self.current_depth();
-        self.defs.push(NameDefTrace {
-            id,
-            name: name.clone(),
-            kind,
-            span,
-            scope_depth: depth,
-        });
-
-        if !existing_candidates.is_empty() {
-            let severity = if self.warn_important_shadow
-                && is_important_shadow_symbol(&name)
-                && is_variable_def_kind(kind)
-            {
-                "warning"
-            } else {
-                "info"
-            };
-            let message = if severity == "warning" {
-                format!(
-                    "important symbol '{}' is shadowed by {} definition",
-                    name, kind
-                )
-            } else {
-                format!("'{}' shadows an outer definition", name)
-            };
-            self.shadows.push(NameShadowTrace {
-                name: name.clone(),
-                event_kind: "definition_shadow",
-                span,
-                scope_depth: depth,
-                selected_def_id: Some(id),
-                shadowed_def_ids: existing_candidates,
-                severity,
-                message,
-            });
-        } else if self.warn_important_shadow
-            && is_important_shadow_symbol(&name)
-            && is_variable_def_kind(kind)
-        {
-            self.shadows.push(NameShadowTrace {
-                name: name.clone(),
-                event_kind: "important_name",
-                span,
-                scope_depth: depth,
-                selected_def_id: Some(id),
-                shadowed_def_ids: Vec::new(),
-                severity: "warning",
-                message: format!(
-                    "definition '{}' may shadow important stdlib symbol",
-                    name
-                ),
-            });
-        }
-
-        if let Some(scope) = self.scopes.last_mut() {
-            scope.entry(name).or_default().push(id);
-        }
-        id
-    }
-
-    fn lookup_candidates(&self, name: &str) -> Vec<usize> {
-        let mut out = Vec::new();
-        for scope in self.scopes.iter().rev() {
-            if let Some(ids) = scope.get(name) {
-                out.extend(ids.iter().rev().copied());
-            }
-        }
-        out
-    }
-
-    fn reference(&mut self, name: String, span: Span) {
-        let candidates = self.lookup_candidates(&name);
-        let resolved = candidates.first().copied();
-        if candidates.len() > 1 {
-            self.shadows.push(NameShadowTrace {
-                name: name.clone(),
-                event_kind: "reference_shadow",
-                span,
-                scope_depth: self.current_depth(),
-                selected_def_id: resolved,
-                shadowed_def_ids: candidates[1..].to_vec(),
-                severity: "info",
-                message: format!(
-                    "'{}' resolved to nearest definition with {} shadowed candidate(s)",
-                    name,
-                    candidates.len().saturating_sub(1)
-                ),
-            });
-        }
-        self.refs.push(NameRefTrace {
-            name,
-            span,
-            scope_depth: self.current_depth(),
-            resolved_def_id: resolved,
-            candidate_def_ids: candidates,
-        });
-    }
-}
-
-fn is_layout_marker(name: &str) -> bool {
-    matches!(name, "cond" | "then" | "else" | "do" | "block")
-}
-
-fn is_important_shadow_symbol(name: &str) -> bool {
-    matches!(
-        name,
-        "print"
-            | "println"
-            | "print_i32"
-            | "println_i32"
-            | "add"
-            | "sub"
-            | "mul"
-            | "div"
-            | "eq"
-            | "lt"
-            | "le"
-            | "gt"
-            | "ge"
-            | "map"
-            | "len"
-    )
-}
-
-fn is_variable_def_kind(kind: &str) -> bool {
-    matches!(kind, "let_hoisted" | "let_mut" | "param" | "match_bind")
-}
-
-fn hoist_block_defs(trace: &mut NameResolutionTrace, block: &Block) {
-    for stmt in &block.items {
-        match stmt {
-            Stmt::FnDef(def) => {
-                trace.define(def.name.name.clone(), "fn", def.name.span);
-            }
-            Stmt::Expr(expr) | Stmt::ExprSemi(expr, _) => {
-                if let Some(PrefixItem::Symbol(Symbol::Let { name, mutable, .. })) = expr.items.first() {
-                    if !*mutable {
-                        trace.define(name.name.clone(), "let_hoisted", name.span);
-                    }
-                }
-            }
-            _ => {}
-        }
-    }
-}
-
-fn trace_match_arm(trace: &mut NameResolutionTrace, arm: &MatchArm) {
-    trace.push_scope();
-    if let Some(bind) = &arm.bind {
-        trace.define(bind.name.clone(), "match_bind", bind.span);
-    }
-    trace_block(trace, &arm.body);
-    trace.pop_scope();
-}
-
-fn trace_prefix_expr(trace: &mut NameResolutionTrace, expr: &PrefixExpr) {
-    for (idx, item) in expr.items.iter().enumerate() {
-        match item {
-            PrefixItem::Symbol(Symbol::Let { name, mutable, .. }) => {
-                if *mutable {
-                    trace.define(name.name.clone(), "let_mut", name.span);
-                }
-                if idx != 0 {
-                    trace.reference(name.name.clone(), name.span);
-                }
-            }
-            PrefixItem::Symbol(Symbol::Set { name }) => {
-                trace.reference(name.name.clone(), name.span);
-            }
-            PrefixItem::Symbol(Symbol::Ident(id, _, _)) => {
-                if !is_layout_marker(&id.name) {
-                    trace.reference(id.name.clone(), id.span);
-                }
-            }
-            PrefixItem::Block(block, _) => {
-                trace.push_scope();
-                trace_block(trace, block);
-                trace.pop_scope();
-            }
-            PrefixItem::Match(m, _) => {
-                trace_prefix_expr(trace, &m.scrutinee);
-                for arm in &m.arms {
-                    trace_match_arm(trace, arm);
-                }
-            }
-            PrefixItem::Tuple(items, _) => {
-                for item_expr in items {
-                    trace_prefix_expr(trace, item_expr);
-                }
-            }
-            PrefixItem::Group(inner, _) => {
-                trace_prefix_expr(trace, inner);
-            }
-            PrefixItem::Intrinsic(intr, _) => {
-                for arg in &intr.args {
-                    trace_prefix_expr(trace, arg);
-                }
-            }
-            PrefixItem::Literal(_, _) | PrefixItem::TypeAnnotation(_, _) | PrefixItem::Pipe(_) => {}
-            PrefixItem::Symbol(Symbol::If(_))
-            | PrefixItem::Symbol(Symbol::While(_))
-            | PrefixItem::Symbol(Symbol::AddrOf(_))
-            | PrefixItem::Symbol(Symbol::Deref(_)) => {}
-        }
-    }
-}
-
-fn trace_stmt(trace: &mut NameResolutionTrace, stmt: &Stmt) {
-    match stmt {
-        Stmt::FnDef(def) => match &def.body {
-            FnBody::Parsed(body) => {
-                trace.push_scope();
-                for param in &def.params {
-                    trace.define(param.name.clone(), "param", param.span);
-                }
-                trace_block(trace, body);
-                trace.pop_scope();
-            }
-            FnBody::Wasm(_) => {}
-            FnBody::LlvmIr(_) => {}
-        },
-        Stmt::FnAlias(alias) => {
-            trace.reference(alias.target.name.clone(), alias.target.span);
-            trace.define(alias.name.name.clone(), "fn_alias", alias.name.span);
-        }
-        Stmt::Expr(expr) | Stmt::ExprSemi(expr, _) => {
-            trace_prefix_expr(trace, expr);
-        }
-        _ => {}
-    }
-}
-
-fn trace_block(trace: &mut NameResolutionTrace, block: &Block) {
-    hoist_block_defs(trace, block);
-    for stmt in &block.items {
-        trace_stmt(trace, stmt);
-    }
-}
-
-fn def_trace_to_js(source: &str, def: &NameDefTrace) -> JsValue {
-    let obj = js_sys::Object::new();
-    let _ = Reflect::set(&obj, &JsValue::from_str("id"), &JsValue::from_f64(def.id as f64));
-    let _ = Reflect::set(&obj, &JsValue::from_str("name"), &JsValue::from_str(&def.name));
-    let _ = Reflect::set(&obj, &JsValue::from_str("kind"), &JsValue::from_str(def.kind));
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("scope_depth"),
-        &JsValue::from_f64(def.scope_depth as f64),
-    );
-    let _ = Reflect::set(&obj, &JsValue::from_str("span"), &span_to_js(source, def.span));
-    obj.into()
-}
-
-fn ref_trace_to_js(source: &str, rf: &NameRefTrace, defs: &[NameDefTrace]) -> JsValue {
-    let obj = js_sys::Object::new();
-    let _ = Reflect::set(&obj, &JsValue::from_str("name"), &JsValue::from_str(&rf.name));
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("scope_depth"),
-        &JsValue::from_f64(rf.scope_depth as f64),
-    );
-    let _ = Reflect::set(&obj, &JsValue::from_str("span"), &span_to_js(source, rf.span));
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("resolved_def_id"),
-        &rf.resolved_def_id
-            .map(|v| JsValue::from_f64(v as f64))
-            .unwrap_or(JsValue::NULL),
-    );
-    let cand = js_sys::Array::new();
-    for id in &rf.candidate_def_ids {
-        cand.push(&JsValue::from_f64(*id as f64));
-    }
-    let _ = Reflect::set(&obj, &JsValue::from_str("candidate_def_ids"), &cand);
-
-    if let Some(id) = rf.resolved_def_id {
-        if let Some(def) = defs.get(id) {
-            let resolved = js_sys::Object::new();
-            let _ = Reflect::set(
-                &resolved,
-                &JsValue::from_str("id"),
-                &JsValue::from_f64(def.id as f64),
-            );
-            let _ = Reflect::set(
-                &resolved,
-                &JsValue::from_str("name"),
-                &JsValue::from_str(&def.name),
-            );
-            let _ = Reflect::set(
-                &resolved,
-                &JsValue::from_str("kind"),
-                &JsValue::from_str(def.kind),
-            );
-            let _ = Reflect::set(
-                &resolved,
-                &JsValue::from_str("scope_depth"),
-                &JsValue::from_f64(def.scope_depth as f64),
-            );
-            let _ = Reflect::set(
-                &resolved,
-                &JsValue::from_str("span"),
-                &span_to_js(source, def.span),
-            );
-            let _ = Reflect::set(&obj, &JsValue::from_str("resolved_def"), &resolved);
-        } else {
-            let _ = Reflect::set(&obj, &JsValue::from_str("resolved_def"), &JsValue::NULL);
-        }
-    } else {
-        let _ = Reflect::set(&obj, &JsValue::from_str("resolved_def"), &JsValue::NULL);
-    }
-
-    let cand_defs = js_sys::Array::new();
-    for id in &rf.candidate_def_ids {
-        if let Some(def) = defs.get(*id) {
-            let item = js_sys::Object::new();
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("id"),
-                &JsValue::from_f64(def.id as f64),
-            );
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("name"),
-                &JsValue::from_str(&def.name),
-            );
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("kind"),
-                &JsValue::from_str(def.kind),
-            );
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("scope_depth"),
-                &JsValue::from_f64(def.scope_depth as f64),
-            );
-            let _ = Reflect::set(
-                &item,
-                &JsValue::from_str("span"),
-                &span_to_js(source, def.span),
-            );
-            cand_defs.push(&item);
-        }
-    }
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("candidate_definitions"),
-        &cand_defs,
-    );
-    obj.into()
-}
-
-fn shadow_trace_to_js(source: &str, sh: &NameShadowTrace) -> JsValue {
-    let obj = js_sys::Object::new();
-    let _ = Reflect::set(&obj, &JsValue::from_str("name"), &JsValue::from_str(&sh.name));
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("event_kind"),
-        &JsValue::from_str(sh.event_kind),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("scope_depth"),
-        &JsValue::from_f64(sh.scope_depth as f64),
-    );
-    let _ = Reflect::set(&obj, &JsValue::from_str("span"), &span_to_js(source, sh.span));
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("selected_def_id"),
-        &sh.selected_def_id
-            .map(|v| JsValue::from_f64(v as f64))
-            .unwrap_or(JsValue::NULL),
-    );
-    let hidden = js_sys::Array::new();
-    for id in &sh.shadowed_def_ids {
-        hidden.push(&JsValue::from_f64(*id as f64));
-    }
-    let _ = Reflect::set(&obj, &JsValue::from_str("shadowed_def_ids"), &hidden);
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("severity"),
-        &JsValue::from_str(sh.severity),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("message"),
-        &JsValue::from_str(&sh.message),
-    );
-    obj.into()
-}
-
-fn name_resolution_payload_to_js(source: &str, trace: &NameResolutionTrace) -> JsValue {
-    let payload = js_sys::Object::new();
-
-    let defs = js_sys::Array::new();
-    for def in &trace.defs {
-        defs.push(&def_trace_to_js(source, def));
-    }
-    let refs = js_sys::Array::new();
-    for rf in &trace.refs {
-        refs.push(&ref_trace_to_js(source, rf, &trace.defs));
-    }
-    let shadows = js_sys::Array::new();
-    for sh in &trace.shadows {
-        shadows.push(&shadow_trace_to_js(source, sh));
-    }
-    let shadow_diagnostics = js_sys::Array::new();
-    for sh in &trace.shadows {
-        if matches!(sh.severity, "warning" | "info") {
-            shadow_diagnostics.push(&shadow_trace_to_js(source, sh));
-        }
-    }
-
-    let by_name = js_sys::Object::new();
-    let mut names = BTreeMap::<String, (Vec<usize>, Vec<usize>)>::new();
-    for d in &trace.defs {
-        names.entry(d.name.clone()).or_default().0.push(d.id);
-    }
-    for (idx, r) in trace.refs.iter().enumerate() {
-        names.entry(r.name.clone()).or_default().1.push(idx);
-    }
-    for (name, (def_ids, ref_ids)) in names {
-        let name_obj = js_sys::Object::new();
-        let d_arr = js_sys::Array::new();
-        for id in def_ids {
-            d_arr.push(&JsValue::from_f64(id as f64));
-        }
-        let r_arr = js_sys::Array::new();
-        for id in ref_ids {
-            r_arr.push(&JsValue::from_f64(id as f64));
+        PrefixItem::Error(span) => {
+            let _ = Reflect::set(&obj, &JsValue::from_str("kind"), &JsValue::from_str("Error"));
+            let _ = Reflect::set(&obj, &JsValue::from_str("span"), &span_to_js(source, *span));
         }
-        let _ = Reflect::set(&name_obj, &JsValue::from_str("definitions"), &d_arr);
-        let _ = Reflect::set(&name_obj, &JsValue::from_str("references"), &r_arr);
-        let _ = Reflect::set(&by_name, &JsValue::from_str(&name), &name_obj);
     }
-
-    let policy = js_sys::Object::new();
-    let _ = Reflect::set(
-        &policy,
-        &JsValue::from_str("selection"),
-        &JsValue::from_str("nearest_scope_first"),
-    );
-    let _ = Reflect::set(
-        &policy,
-        &JsValue::from_str("hoist"),
-        &JsValue::from_str("fn and non-mut let"),
-    );
-    let _ = Reflect::set(
-        &policy,
-        &JsValue::from_str("warn_important_shadow"),
-        &JsValue::from_bool(trace.warn_important_shadow),
-    );
-
-    let _ = Reflect::set(&payload, &JsValue::from_str("definitions"), &defs);
-    let _ = Reflect::set(&payload, &JsValue::from_str("references"), &refs);
-    let _ = Reflect::set(&payload, &JsValue::from_str("shadows"), &shadows);
-    let _ = Reflect::set(
-        &payload,
-        &JsValue::from_str("shadow_diagnostics"),
-        &shadow_diagnostics,
-    );
-    let _ = Reflect::set(&payload, &JsValue::from_str("by_name"), &by_name);
-    let _ = Reflect::set(&payload, &JsValue::from_str("policy"), &policy);
-    payload.into()
-}
-
-fn semantic_expr_to_js(source: &str, se: &SemanticExprTrace) -> JsValue {
-    let obj = js_sys::Object::new();
-    let _ = Reflect::set(&obj, &JsValue::from_str("id"), &JsValue::from_f64(se.id as f64));
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("function_name"),
-        &JsValue::from_str(&se.function_name),
-    );
-    let _ = Reflect::set(&obj, &JsValue::from_str("kind"), &JsValue::from_str(se.kind));
-    let _ = Reflect::set(&obj, &JsValue::from_str("span"), &span_to_js(source, se.span));
-    let _ = Reflect::set(&obj, &JsValue::from_str("inferred_type"), &JsValue::from_str(&se.ty));
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("parent_id"),
-        &se.parent_id
-            .map(|v| JsValue::from_f64(v as f64))
-            .unwrap_or(JsValue::NULL),
-    );
-    let arg_arr = js_sys::Array::new();
-    for sp in &se.arg_spans {
-        arg_arr.push(&span_to_js(source, *sp));
-    }
-    let _ = Reflect::set(&obj, &JsValue::from_str("argument_ranges"), &arg_arr);
-    obj.into()
-}
-
-fn semantic_token_to_js(source: &str, st: &SemanticTokenTrace) -> JsValue {
-    let obj = js_sys::Object::new();
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("token_index"),
-        &JsValue::from_f64(st.token_index as f64),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("inferred_expr_id"),
-        &st.inferred_expr_id
-            .map(|v| JsValue::from_f64(v as f64))
-            .unwrap_or(JsValue::NULL),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("inferred_type"),
-        &st.inferred_type
-            .as_ref()
-            .map(|s| JsValue::from_str(s))
-            .unwrap_or(JsValue::NULL),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("expression_range"),
-        &st.expr_span
-            .map(|s| span_to_js(source, s))
-            .unwrap_or(JsValue::NULL),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("arg_index"),
-        &st.arg_index
-            .map(|v| JsValue::from_f64(v as f64))
-            .unwrap_or(JsValue::NULL),
-    );
-    let _ = Reflect::set(
-        &obj,
-        &JsValue::from_str("arg_range"),
-        &st.arg_span
-            .map(|s| span_to_js(source, s))
-            .unwrap_or(JsValue::NULL),
-    );
     obj.into()
 }
 
-fn span_contains(outer: Span, inner: Span) -> bool {
-    outer.file_id == inner.file_id && outer.start <= inner.start && inner.end <= outer.end
-}
-
-fn span_width(span: Span) -> usize {
-    span.end.saturating_sub(span.start) as usize
-}
-
-fn hir_kind_name(kind: &HirExprKind) -> &'static str {
-    match kind {
-        HirExprKind::LiteralI32(_) => "LiteralI32",
-        HirExprKind::LiteralF32(_) => "LiteralF32",
-        HirExprKind::LiteralBool(_) => "LiteralBool",
-        HirExprKind::LiteralStr(_) => "LiteralStr",
-        HirExprKind::Unit => "Unit",
-        HirExprKind::Var(_) => "Var",
-        HirExprKind::FnValue(_) => "FnValue",
-        HirExprKind::Call { .. } => "Call",
-        HirExprKind::CallIndirect { .. } => "CallIndirect",
-        HirExprKind::If { .. } => "If",
-        HirExprKind::While { .. } => "While",
-        HirExprKind::Match { .. } => "Match",
-        HirExprKind::EnumConstruct { .. } => "EnumConstruct",
-        HirExprKind::StructConstruct { .. } => "StructConstruct",
-        HirExprKind::TupleConstruct { .. } => "TupleConstruct",
-        HirExprKind::Block(_) => "Block",
-        HirExprKind::Let { .. } => "Let",
-        HirExprKind::Set { .. } => "Set",
-        HirExprKind::Intrinsic { .. } => "Intrinsic",
-        HirExprKind::AddrOf(_) => "AddrOf",
-        HirExprKind::Deref(_) => "Deref",
-        HirExprKind::Drop { .. } => "Drop",
-    }
-}
-
-fn collect_semantic_expr_from_line(
-    line: &HirLine,
-    function_name: &str,
-    types: &nepl_core::types::TypeCtx,
-    out: &mut Vec<SemanticExprTrace>,
-) {
-    collect_semantic_expr(&line.expr, function_name, types, None, out);
-}
-
-fn collect_semantic_expr_from_block(
-    block: &HirBlock,
-    function_name: &str,
-    types: &nepl_core::types::TypeCtx,
-    parent_id: Option<usize>,
-    out: &mut Vec<SemanticExprTrace>,
-) {
-    for line in &block.lines {
-        collect_semantic_expr(&line.expr, function_name, types, parent_id, out);
-    }
-}
-
-fn collect_semantic_expr(
-    expr: &HirExpr,
-    function_name: &str,
-    types: &nepl_core::types::TypeCtx,
-    parent_id: Option<usize>,
-    out: &mut Vec<SemanticExprTrace>,
-) -> usize {
-    let id = out.len();
-    out.push(SemanticExprTrace {
-        id,
-        function_name: function_name.to_string(),
-        kind: hir_kind_name(&expr.kind),
-        span: expr.span,
-        ty: types.type_to_string(expr.ty),
-        parent_id,
-        arg_spans: Vec::new(),
-    });
-
-    let mut arg_spans = Vec::new();
-    match &expr.kind {
-        HirExprKind::Call { args, .. } => {
-            for a in args {
-                arg_spans.push(a.span);
-                collect_semantic_expr(a, function_name, types, Some(id), out);
-            }
-        }
-        HirExprKind::FnValue(_) => {}
-        HirExprKind::CallIndirect { callee, args, .. } => {
-            collect_semantic_expr(callee, function_name, types, Some(id), out);
-            for a in args {
-                arg_spans.push(a.span);
-                collect_semantic_expr(a, function_name, types, Some(id), out);
-            }
-        }
-        HirExprKind::If {
-            cond,
-            then_branch,
-            else_branch,
-        } => {
-            arg_spans.push(cond.span);
-            arg_spans.push(then_branch.span);
-            arg_spans.push(else_branch.span);
-            collect_semantic_expr(cond, function_name, types, Some(id), out);
-            collect_semantic_expr(then_branch, function_name, types, Some(id), out);
-            collect_semantic_expr(else_branch, function_name, types, Some(id), out);
-        }
-        HirExprKind::While { cond, body } => {
-            arg_spans.push(cond.span);
-            arg_spans.push(body.span);
-            collect_semantic_expr(cond, function_name, types, Some(id), out);
-            collect_semantic_expr(body, function_name, types, Some(id), out);
-        }
-        HirExprKind::Match { scrutinee, arms } => {
-            arg_spans.push(scrutinee.span);
-            collect_semantic_expr(scrutinee, function_name, types, Some(id), out);
-            for arm in arms {
-                arg_spans.push(arm.body.span);
-                collect_semantic_expr(&arm.body, function_name, types, Some(id), out);
-            }
-        }
-        HirExprKind::EnumConstruct { payload, .. } => {
-            if let Some(p) = payload {
-                arg_spans.push(p.span);
-                collect_semantic_expr(p, function_name, types, Some(id), out);
-            }
-        }
-        HirExprKind::StructConstruct { fields, .. } => {
-            for f in fields {
-                arg_spans.push(f.span);
-                collect_semantic_expr(f, function_name, types, Some(id), out);
-            }
-        }
-        HirExprKind::TupleConstruct { items } => {
-            for e in items {
-                arg_spans.push(e.span);
-                collect_semantic_expr(e, function_name, types, Some(id), out);
-            }
-        }
-        HirExprKind::Block(b) => {
-            collect_semantic_expr_from_block(b, function_name, types, Some(id), out);
-        }
-        HirExprKind::Let { value, .. } | HirExprKind::Set { value, .. } => {
-            arg_spans.push(value.span);
-            collect_semantic_expr(value, function_name, types, Some(id), out);
-        }
-        HirExprKind::Intrinsic { args, .. } => {
-            for a in args {
-                arg_spans.push(a.span);
-                collect_semantic_expr(a, function_name, types, Some(id), out);
-            }
-        }
-        HirExprKind::AddrOf(inner) | HirExprKind::Deref(inner) => {
-            arg_spans.push(inner.span);
-            collect_semantic_expr(inner, function_name, types, Some(id), out);
-        }
-        HirExprKind::LiteralI32(_)
-        | HirExprKind::LiteralF32(_)
-        | HirExprKind::LiteralBool(_)
-        | HirExprKind::LiteralStr(_)
-        | HirExprKind::Unit
-        | HirExprKind::Var(_)
-        | HirExprKind::Drop { .. } => {}
-    }
-
-    out[id].arg_spans = arg_spans;
-    id
-}
-
-fn resolve_target_for_analysis(module: &nepl_core::ast::Module) -> (CompileTarget, Vec<Diagnostic>) {
-    let mut found: Option<(CompileTarget, Span)> = None;
-    let mut diags = Vec::new();
-
-    for d in &module.directives {
-        if let Directive::Target { target, span } = d {
-            let parsed = match target.as_str() {
-                "wasm" => Some(CompileTarget::Wasm),
-                "wasi" => Some(CompileTarget::Wasi),
-                "llvm" => Some(CompileTarget::Llvm),
-                _ => None,
-            };
-            if let Some(t) = parsed {
-                if let Some((_, prev_span)) = found {
-                    diags.push(
-                        Diagnostic::error("multiple #target directives are not allowed", *span)
-                            .with_secondary_label(prev_span, Some("previous #target here".into())),
-                    );
-                } else {
-                    found = Some((t, *span));
-                }
-            } else {
-                diags.push(Diagnostic::error("unknown target in #target", *span));
-            }
-        }
-    }
-
-    if found.is_none() {
-        for it in &module.root.items {
-            if let Stmt::Directive(Directive::Target { target, span }) = it {
-                let parsed = match target.as_str() {
-                    "wasm" => Some(CompileTarget::Wasm),
-                    "wasi" => Some(CompileTarget::Wasi),
-                    "llvm" => Some(CompileTarget::Llvm),
-                    _ => None,
-                };
-                if let Some(t) = parsed {
-                    if let Some((_, prev_span)) = found {
-                        diags.push(
-                            Diagnostic::error("multiple #target directives are not allowed", *span)
-                                .with_secondary_label(
-                                    prev_span,
-                                    Some("previous #target here".into()),
-                                ),
-                        );
-                    } else {
-                        found = Some((t, *span));
-                    }
-                } else {
-                    diags.push(Diagnostic::error("unknown target in #target", *span));
-                }
-            }
-        }
-    }
-
-    (found.map(|(t, _)| t).unwrap_or(CompileTarget::Wasm), diags)
-}
-
 /// 入力ソースを字句解析し、token 列と診断を JSON で返します。
 ///
 /// VSCode 拡張や LSP 実装で、構文解析前の結果を可視化するための API です。
 #[wasm_bindgen]
 pub fn analyze_lex(source: &str) -> JsValue {
-    let file_id = FileId(0);
-    let lex_result = lex(file_id, source);
-    let out = js_sys::Object::new();
-    let token_arr = js_sys::Array::new();
-    for token in &lex_result.tokens {
-        token_arr.push(&token_to_js(source, token));
-    }
-    let diagnostics = diagnostics_to_js(source, &lex_result.diagnostics);
-    let has_error = lex_result
-        .diagnostics
-        .iter()
-        .any(|d| d.severity == Severity::Error);
-    let _ = Reflect::set(&out, &JsValue::from_str("stage"), &JsValue::from_str("lex"));
-    let _ = Reflect::set(
-        &out,
-        &JsValue::from_str("ok"),
-        &JsValue::from_bool(!has_error),
-    );
-    let _ = Reflect::set(&out, &JsValue::from_str("tokens"), &token_arr);
-    let _ = Reflect::set(
-        &out,
-        &JsValue::from_str("indent_width"),
-        &JsValue::from_f64(lex_result.indent_width as f64),
-    );
-    let _ = Reflect::set(&out, &JsValue::from_str("diagnostics"), &diagnostics);
-    out.into()
+    to_js(&analysis::analyze_lex(source))
 }
 
 /// 入力ソースを構文解析し、token・AST 木構造・診断を JSON で返します。
@@ -1661,14 +602,16 @@ pub fn analyze_lex(source: &str) -> JsValue {
 #[wasm_bindgen]
 pub fn analyze_parse(source: &str) -> JsValue {
     let file_id = FileId(0);
+    let index = SourceIndex::single(analysis::INPUT_PATH, source);
     let lex_result = lex(file_id, source);
-    let token_arr = js_sys::Array::new();
-    for token in &lex_result.tokens {
-        token_arr.push(&token_to_js(source, token));
-    }
-    let lex_diagnostics = diagnostics_to_js(source, &lex_result.diagnostics);
+    let tokens: Vec<TokenInfo> = lex_result
+        .tokens
+        .iter()
+        .map(|t| TokenInfo::new(&index, t))
+        .collect();
+    let lex_diagnostics = DiagnosticInfo::from_all(&index, &lex_result.diagnostics);
     let parse_result = parse_tokens(file_id, lex_result);
-    let diagnostics = diagnostics_to_js(source, &parse_result.diagnostics);
+    let diagnostics = DiagnosticInfo::from_all(&index, &parse_result.diagnostics);
 
     let out = js_sys::Object::new();
     let _ = Reflect::set(
@@ -1676,13 +619,13 @@ pub fn analyze_parse(source: &str) -> JsValue {
         &JsValue::from_str("stage"),
         &JsValue::from_str("parse"),
     );
-    let _ = Reflect::set(&out, &JsValue::from_str("tokens"), &token_arr);
+    let _ = Reflect::set(&out, &JsValue::from_str("tokens"), &to_js(&tokens));
     let _ = Reflect::set(
         &out,
         &JsValue::from_str("lex_diagnostics"),
-        &lex_diagnostics,
+        &to_js(&lex_diagnostics),
     );
-    let _ = Reflect::set(&out, &JsValue::from_str("diagnostics"), &diagnostics);
+    let _ = Reflect::set(&out, &JsValue::from_str("diagnostics"), &to_js(&diagnostics));
 
     if let Some(module) = parse_result.module {
         let module_obj = js_sys::Object::new();
@@ -1720,6 +663,7 @@ pub fn analyze_parse(source: &str) -> JsValue {
 ///
 /// - `definitions`: 解析で見つかった定義点
 /// - `references`: 各参照点の候補と最終選択（最内側優先）
+/// - `scopes`: スコープの木（`parent` で親を辿る）
 /// - 巻き上げは現行仕様に合わせて `fn` と `let`(non-mut) を先行登録します
 #[wasm_bindgen]
 pub fn analyze_name_resolution(source: &str) -> JsValue {
@@ -1728,59 +672,21 @@ pub fn analyze_name_resolution(source: &str) -> JsValue {
 
 #[wasm_bindgen]
 pub fn analyze_name_resolution_with_options(source: &str, options: JsValue) -> JsValue {
-    let file_id = FileId(0);
-    let lex_result = lex(file_id, source);
-    let parse_result = parse_tokens(file_id, lex_result);
-    let diagnostics = diagnostics_to_js(source, &parse_result.diagnostics);
-    let warn_important_shadow = Reflect::get(&options, &JsValue::from_str("warn_important_shadow"))
+    to_js(&analysis::analyze_name_resolution(
+        source,
+        name_resolution_options(&options),
+    ))
+}
+
+fn name_resolution_options(options: &JsValue) -> NameResolutionOptions {
+    let mut opts = NameResolutionOptions::default();
+    if let Some(v) = Reflect::get(options, &JsValue::from_str("warn_important_shadow"))
         .ok()
         .and_then(|v| v.as_bool())
-        .unwrap_or(true);
-
-    let out = js_sys::Object::new();
-    let _ = Reflect::set(
-        &out,
-        &JsValue::from_str("stage"),
-        &JsValue::from_str("name_resolution"),
-    );
-    let _ = Reflect::set(&out, &JsValue::from_str("diagnostics"), &diagnostics);
-
-    if let Some(module) = parse_result.module {
-        let mut trace = NameResolutionTrace::new_with_options(warn_important_shadow);
-        trace_block(&mut trace, &module.root);
-        let payload = name_resolution_payload_to_js(source, &trace);
-        let _ = Reflect::set(&out, &JsValue::from_str("ok"), &JsValue::from_bool(true));
-        let _ = Reflect::set(&out, &JsValue::from_str("definitions"), &Reflect::get(&payload, &JsValue::from_str("definitions")).unwrap_or(JsValue::NULL));
-        let _ = Reflect::set(&out, &JsValue::from_str("references"), &Reflect::get(&payload, &JsValue::from_str("references")).unwrap_or(JsValue::NULL));
-        let _ = Reflect::set(&out, &JsValue::from_str("shadows"), &Reflect::get(&payload, &JsValue::from_str("shadows")).unwrap_or(JsValue::NULL));
-        let _ = Reflect::set(
-            &out,
-            &JsValue::from_str("shadow_diagnostics"),
-            &Reflect::get(&payload, &JsValue::from_str("shadow_diagnostics")).unwrap_or(JsValue::NULL),
-        );
-        let _ = Reflect::set(&out, &JsValue::from_str("by_name"), &Reflect::get(&payload, &JsValue::from_str("by_name")).unwrap_or(JsValue::NULL));
-        let _ = Reflect::set(&out, &JsValue::from_str("policy"), &Reflect::get(&payload, &JsValue::from_str("policy")).unwrap_or(JsValue::NULL));
-    } else {
-        let _ = Reflect::set(&out, &JsValue::from_str("ok"), &JsValue::from_bool(false));
-        let _ = Reflect::set(&out, &JsValue::from_str("definitions"), &js_sys::Array::new());
-        let _ = Reflect::set(&out, &JsValue::from_str("references"), &js_sys::Array::new());
-        let _ = Reflect::set(&out, &JsValue::from_str("shadows"), &js_sys::Array::new());
-        let _ = Reflect::set(
-            &out,
-            &JsValue::from_str("shadow_diagnostics"),
-            &js_sys::Array::new(),
-        );
-        let _ = Reflect::set(&out, &JsValue::from_str("by_name"), &js_sys::Object::new());
-        let policy = js_sys::Object::new();
-        let _ = Reflect::set(
-            &policy,
-            &JsValue::from_str("warn_important_shadow"),
-            &JsValue::from_bool(warn_important_shadow),
-        );
-        let _ = Reflect::set(&out, &JsValue::from_str("policy"), &policy);
+    {
+        opts.warn_important_shadow = v;
     }
-
-    out.into()
+    opts
 }
 
 /// 字句・構文・型検査の情報を統合し、LSP 向けの詳細解析結果を返します。
@@ -1791,198 +697,222 @@ pub fn analyze_name_resolution_with_options(source: &str, options: JsValue) -> J
 /// - `functions`: 関数定義の範囲とシグネチャ
 #[wasm_bindgen]
 pub fn analyze_semantics(source: &str) -> JsValue {
-    let file_id = FileId(0);
-    let lex_result = lex(file_id, source);
-    let tokens = lex_result.tokens.clone();
-    let token_arr = js_sys::Array::new();
-    for token in &tokens {
-        token_arr.push(&token_to_js(source, token));
-    }
+    to_js(&analysis::analyze_semantics(source))
+}
 
-    let parse_result = parse_tokens(file_id, lex_result);
-    let out = js_sys::Object::new();
-    let _ = Reflect::set(
-        &out,
-        &JsValue::from_str("stage"),
-        &JsValue::from_str("semantics"),
-    );
-    let _ = Reflect::set(&out, &JsValue::from_str("tokens"), &token_arr);
+fn load_for_analysis(entry_path: &str, source: &str, vfs: JsValue) -> LoadedSources {
+    let (loader, loaded) = load_with_vfs(entry_path, source, Some(vfs), None);
+    LoadedSources::new(loaded, &loader)
+}
+
+/// `analyze_name_resolution` の import 対応版です。
+///
+/// `compile_source_with_vfs` と同じく同梱 stdlib と `vfs` から import 先を読み込み、
+/// import された定義も候補に含めて解決します。`files` に file_id とパスの対応を返し、
+/// 各定義の `path` / `span` がジャンプ先になります。参照・スコープは entry ファイル分のみ返します。
+#[wasm_bindgen]
+pub fn analyze_name_resolution_with_vfs(
+    entry_path: &str,
+    source: &str,
+    vfs: JsValue,
+    options: JsValue,
+) -> JsValue {
+    let loaded = load_for_analysis(entry_path, source, vfs);
+    to_js(&analysis::analyze_loaded_name_resolution(
+        &loaded,
+        name_resolution_options(&options),
+    ))
+}
+
+/// `analyze_semantics` の import 対応版です。
+///
+/// 式・token の情報は entry ファイル分のみで、`token_resolution[].definition` に
+/// import 先を含む定義ジャンプ先（`path` と `span`）を返します。
+#[wasm_bindgen]
+pub fn analyze_semantics_with_vfs(entry_path: &str, source: &str, vfs: JsValue) -> JsValue {
+    let loaded = load_for_analysis(entry_path, source, vfs);
+    to_js(&analysis::analyze_loaded_semantics(&loaded))
+}
 
-    let mut all_diags = parse_result.diagnostics.clone();
-    let mut has_error = all_diags
+/// 同梱 stdlib のモジュールパス（`std/stdio` など）。`#import "..."` の補完候補に使います。
+fn stdlib_module_paths() -> Vec<String> {
+    let mut paths: Vec<String> = stdlib_entries()
         .iter()
-        .any(|d| matches!(d.severity, Severity::Error));
-
-    if let Some(module) = &parse_result.module {
-        let mut resolve_trace = NameResolutionTrace::new();
-        trace_block(&mut resolve_trace, &module.root);
-        let resolve_payload = name_resolution_payload_to_js(source, &resolve_trace);
-
-        let (target, mut target_diags) = resolve_target_for_analysis(module);
-        has_error |= target_diags
-            .iter()
-            .any(|d| matches!(d.severity, Severity::Error));
-        all_diags.append(&mut target_diags);
-
-        let tc = typecheck(module, target, BuildProfile::Debug);
-        has_error |= tc
-            .diagnostics
-            .iter()
-            .any(|d| matches!(d.severity, Severity::Error));
-        all_diags.extend(tc.diagnostics.clone());
-
-        let diagnostics = diagnostics_to_js(source, &all_diags);
-        let _ = Reflect::set(&out, &JsValue::from_str("diagnostics"), &diagnostics);
-
-        if let Some(hir_module) = tc.module {
-            let mut exprs = Vec::<SemanticExprTrace>::new();
-            let function_arr = js_sys::Array::new();
-            for f in &hir_module.functions {
-                let f_obj = js_sys::Object::new();
-                let _ = Reflect::set(&f_obj, &JsValue::from_str("name"), &JsValue::from_str(&f.name));
-                let _ = Reflect::set(&f_obj, &JsValue::from_str("span"), &span_to_js(source, f.span));
-                let _ = Reflect::set(
-                    &f_obj,
-                    &JsValue::from_str("signature"),
-                    &JsValue::from_str(&tc.types.type_to_string(f.func_ty)),
-                );
-                function_arr.push(&f_obj);
-                if let nepl_core::hir::HirBody::Block(b) = &f.body {
-                    for line in &b.lines {
-                        collect_semantic_expr_from_line(line, &f.name, &tc.types, &mut exprs);
-                    }
-                }
-            }
+        .filter_map(|(path, _)| path.strip_suffix(".nepl"))
+        .map(|path| path.to_string())
+        .collect();
+    paths.sort();
+    paths
+}
 
-            let expr_arr = js_sys::Array::new();
-            for ex in &exprs {
-                expr_arr.push(&semantic_expr_to_js(source, ex));
-            }
+/// カーソル位置（`offset`: UTF-8 の byte offset）の補完候補を返します。
+///
+/// - `context`: `identifier` / `field` / `variant` / `directive` / `module_path` / `none`
+/// - `prefix` / `range`: 入力済みの部分と、候補で置き換える範囲
+/// - `items[]`: `label`, `kind`, `detail`（シグネチャや型）, `documentation`（`//:` の 1 行目）, `path`
+#[wasm_bindgen]
+pub fn analyze_completion(source: &str, offset: u32) -> JsValue {
+    to_js(&analysis::complete(source, offset, &stdlib_module_paths()))
+}
 
-            let token_res_arr = js_sys::Array::new();
-            for (tok_idx, token) in tokens.iter().enumerate() {
-                let mut best_ref: Option<&NameRefTrace> = None;
-                for rf in &resolve_trace.refs {
-                    if span_contains(rf.span, token.span) {
-                        if let Some(prev) = best_ref {
-                            if span_width(rf.span) < span_width(prev.span) {
-                                best_ref = Some(rf);
-                            }
-                        } else {
-                            best_ref = Some(rf);
-                        }
-                    }
-                }
-                let item = js_sys::Object::new();
-                let _ = Reflect::set(
-                    &item,
-                    &JsValue::from_str("token_index"),
-                    &JsValue::from_f64(tok_idx as f64),
-                );
-                if let Some(rf) = best_ref {
-                    let _ = Reflect::set(&item, &JsValue::from_str("name"), &JsValue::from_str(&rf.name));
-                    let _ = Reflect::set(&item, &JsValue::from_str("ref_span"), &span_to_js(source, rf.span));
-                    let _ = Reflect::set(
-                        &item,
-                        &JsValue::from_str("resolved_def_id"),
-                        &rf.resolved_def_id
-                            .map(|v| JsValue::from_f64(v as f64))
-                            .unwrap_or(JsValue::NULL),
-                    );
-                    let cand = js_sys::Array::new();
-                    for id in &rf.candidate_def_ids {
-                        cand.push(&JsValue::from_f64(*id as f64));
-                    }
-                    let _ = Reflect::set(&item, &JsValue::from_str("candidate_def_ids"), &cand);
-                } else {
-                    let _ = Reflect::set(&item, &JsValue::from_str("name"), &JsValue::NULL);
-                    let _ = Reflect::set(&item, &JsValue::from_str("ref_span"), &JsValue::NULL);
-                    let _ = Reflect::set(&item, &JsValue::from_str("resolved_def_id"), &JsValue::NULL);
-                    let _ = Reflect::set(
-                        &item,
-                        &JsValue::from_str("candidate_def_ids"),
-                        &js_sys::Array::new(),
-                    );
-                }
-                token_res_arr.push(&item);
-            }
+/// `analyze_completion` の import 対応版です。import 先の関数も候補に含めます。
+#[wasm_bindgen]
+pub fn analyze_completion_with_vfs(
+    entry_path: &str,
+    source: &str,
+    vfs: JsValue,
+    offset: u32,
+) -> JsValue {
+    let blanked = analysis::completion::blank_cursor_line(source, offset);
+    let loaded = load_for_analysis(entry_path, &blanked, vfs);
+    to_js(&analysis::complete_loaded(
+        source,
+        offset,
+        &loaded,
+        &stdlib_module_paths(),
+    ))
+}
 
-            let mut token_semantics = Vec::<SemanticTokenTrace>::new();
-            for (tok_idx, token) in tokens.iter().enumerate() {
-                let mut best_expr: Option<&SemanticExprTrace> = None;
-                for ex in &exprs {
-                    if span_contains(ex.span, token.span) {
-                        if let Some(prev) = best_expr {
-                            if span_width(ex.span) < span_width(prev.span) {
-                                best_expr = Some(ex);
-                            }
-                        } else {
-                            best_expr = Some(ex);
-                        }
-                    }
-                }
-                let mut arg_hit: Option<(usize, Span)> = None;
-                for ex in &exprs {
-                    for (a_idx, a_sp) in ex.arg_spans.iter().enumerate() {
-                        if span_contains(*a_sp, token.span) {
-                            if let Some((_, prev_sp)) = arg_hit {
-                                if span_width(*a_sp) < span_width(prev_sp) {
-                                    arg_hit = Some((a_idx, *a_sp));
-                                }
-                            } else {
-                                arg_hit = Some((a_idx, *a_sp));
-                            }
-                        }
-                    }
-                }
-                token_semantics.push(SemanticTokenTrace {
-                    token_index: tok_idx,
-                    inferred_expr_id: best_expr.map(|x| x.id),
-                    inferred_type: best_expr.map(|x| x.ty.clone()),
-                    expr_span: best_expr.map(|x| x.span),
-                    arg_index: arg_hit.map(|(idx, _)| idx),
-                    arg_span: arg_hit.map(|(_, sp)| sp),
-                });
-            }
-            let token_sem_arr = js_sys::Array::new();
-            for ts in &token_semantics {
-                token_sem_arr.push(&semantic_token_to_js(source, ts));
-            }
+/// カーソル位置（`offset`: UTF-8 の byte offset）の hover / signature help 情報を返します。
+///
+/// - `expression`: カーソル下の最も内側の式の種類・範囲・推論型
+/// - `definition`: 名前の定義（呼び出しでは型検査が選んだオーバーロード）。関数なら `signature`
+///   （型引数・各引数・戻り値・effect）と `documentation`（`//:` の markdown）を含みます
+/// - `signature_help`: カーソルを含む最も内側の前置記法の呼び出しと `active_parameter`
+///
+/// `documentation.html` は `null` です。`compile_doc_renderer` の wasm で描画してください。
+#[wasm_bindgen]
+pub fn analyze_hover(source: &str, offset: u32) -> JsValue {
+    to_js(&analysis::hover(source, offset))
+}
 
-            let _ = Reflect::set(&out, &JsValue::from_str("ok"), &JsValue::from_bool(!has_error));
-            let _ = Reflect::set(&out, &JsValue::from_str("expressions"), &expr_arr);
-            let _ = Reflect::set(&out, &JsValue::from_str("token_semantics"), &token_sem_arr);
-            let _ = Reflect::set(&out, &JsValue::from_str("functions"), &function_arr);
-            let _ = Reflect::set(&out, &JsValue::from_str("name_resolution"), &resolve_payload);
-            let _ = Reflect::set(&out, &JsValue::from_str("token_resolution"), &token_res_arr);
-        } else {
-            let _ = Reflect::set(&out, &JsValue::from_str("ok"), &JsValue::from_bool(false));
-            let _ = Reflect::set(&out, &JsValue::from_str("expressions"), &js_sys::Array::new());
-            let _ = Reflect::set(
-                &out,
-                &JsValue::from_str("token_semantics"),
-                &js_sys::Array::new(),
-            );
-            let _ = Reflect::set(&out, &JsValue::from_str("functions"), &js_sys::Array::new());
-            let _ = Reflect::set(&out, &JsValue::from_str("name_resolution"), &resolve_payload);
-            let _ = Reflect::set(&out, &JsValue::from_str("token_resolution"), &js_sys::Array::new());
-        }
-    } else {
-        let diagnostics = diagnostics_to_js(source, &all_diags);
-        let _ = Reflect::set(&out, &JsValue::from_str("diagnostics"), &diagnostics);
-        let _ = Reflect::set(&out, &JsValue::from_str("ok"), &JsValue::from_bool(false));
-        let _ = Reflect::set(&out, &JsValue::from_str("expressions"), &js_sys::Array::new());
-        let _ = Reflect::set(
-            &out,
-            &JsValue::from_str("token_semantics"),
-            &js_sys::Array::new(),
-        );
-        let _ = Reflect::set(&out, &JsValue::from_str("functions"), &js_sys::Array::new());
-        let _ = Reflect::set(&out, &JsValue::from_str("name_resolution"), &JsValue::NULL);
-        let _ = Reflect::set(&out, &JsValue::from_str("token_resolution"), &js_sys::Array::new());
-    }
+/// `analyze_hover` の import 対応版です。import 先の関数のシグネチャと doc comment も返します。
+#[wasm_bindgen]
+pub fn analyze_hover_with_vfs(entry_path: &str, source: &str, vfs: JsValue, offset: u32) -> JsValue {
+    let loaded = load_for_analysis(entry_path, source, vfs);
+    to_js(&analysis::hover_loaded(&loaded, offset))
+}
 
-    out.into()
+/// doc comment の markdown を nm（`nm/parser` + `nm/html_gen`）で HTML にする wasm を返します。
+///
+/// `memory`, `nm_alloc <(i32)*>i32>`, `nm_render <(str)*>str>` を export します。
+/// `str` は `[len: i32 LE][UTF-8 bytes]` を指すアドレスです。
+#[wasm_bindgen]
+pub fn compile_doc_renderer() -> Result<Vec<u8>, JsValue> {
+    compile_wasm_with_entry(
+        "/virtual/nm_render.nepl",
+        analysis::hover::DOC_RENDERER_SOURCE,
+        None,
+    )
+    .map(|a| a.wasm)
+    .map_err(|msg| JsValue::from_str(&msg))
+}
+
+/// 前置記法の区切りを示す inlay hint を返します。
+///
+/// - `hints[]`: `kind`（`parameter` / `paren` / `type`）, `label`, `position`（表示位置の空範囲）,
+///   `target`（対象の引数・呼び出し・束縛）, `padding_left`, `padding_right`
+///   - `parameter`: 各引数の前の引数名（`a:`）
+///   - `paren`: 他の式の引数になっている呼び出しを囲む `(` / `)`
+///   - `type`: 型注釈のない `let` の名前の後ろの推論型（`<i32>`）
+///
+/// 呼び出しの区切りは型検査の結果から求めるため、型検査に失敗したソースでは `hints` は空です。
+#[wasm_bindgen]
+pub fn analyze_inlay_hints(source: &str) -> JsValue {
+    to_js(&analysis::inlay_hints(source))
+}
+
+/// `analyze_inlay_hints` の import 対応版です。import 先の関数の引数名も表示します。
+#[wasm_bindgen]
+pub fn analyze_inlay_hints_with_vfs(entry_path: &str, source: &str, vfs: JsValue) -> JsValue {
+    let loaded = load_for_analysis(entry_path, source, vfs);
+    to_js(&analysis::inlay_hints_loaded(&loaded))
+}
+
+/// `line` 行目（0 始まり）の式を、すべての呼び出しを括弧で囲んだ形で返します（`nepl-cli explain-expr` と同じ）。
+///
+/// 行がない場合は `undefined` を返します。
+#[wasm_bindgen]
+pub fn explain_expr_with_vfs(entry_path: &str, source: &str, vfs: JsValue, line: u32) -> Option<String> {
+    let loaded = load_for_analysis(entry_path, source, vfs);
+    let semantics = analysis::analyze_loaded_semantics(&loaded);
+    analysis::parenthesize_line(&semantics, source, line)
+}
+
+/// カーソル位置（`offset`: UTF-8 の byte offset）の名前の参照箇所をすべて返します。
+///
+/// - `symbol`: 名前・種類と `definitions`（関数ならすべてのオーバーロード）
+/// - `references[]`: `kind`（`definition` / `reference` / `import_item` / `use_path` /
+///   `qualified_path` / `entry`）, `path`, `range`（名前の部分だけの範囲）
+///
+/// 同じ名前の変数で遮られた参照は含みません。
+#[wasm_bindgen]
+pub fn analyze_references(source: &str, offset: u32) -> JsValue {
+    to_js(&analysis::find_references(source, offset))
+}
+
+/// `analyze_references` の import 対応版です。import 先のファイルの参照箇所も返します。
+#[wasm_bindgen]
+pub fn analyze_references_with_vfs(entry_path: &str, source: &str, vfs: JsValue, offset: u32) -> JsValue {
+    let loaded = load_for_analysis(entry_path, source, vfs);
+    to_js(&analysis::find_references_loaded(&loaded, offset))
+}
+
+/// カーソル位置の名前を `new_name` に変える編集を返します。
+///
+/// - `edit.files[]`: `file_id`, `path`, `edits[]`（`range`, `new_text`）
+/// - `conflicts[]`: 変更すると別の定義を指すようになる箇所。あれば `ok` は `false` です
+#[wasm_bindgen]
+pub fn analyze_rename(source: &str, offset: u32, new_name: &str) -> JsValue {
+    to_js(&analysis::rename(source, offset, new_name))
+}
+
+/// `analyze_rename` の import 対応版です。import 先のファイルの編集も含みます。
+#[wasm_bindgen]
+pub fn analyze_rename_with_vfs(
+    entry_path: &str,
+    source: &str,
+    vfs: JsValue,
+    offset: u32,
+    new_name: &str,
+) -> JsValue {
+    let loaded = load_for_analysis(entry_path, source, vfs);
+    to_js(&analysis::rename_loaded(&loaded, offset, new_name))
+}
+
+/// 識別子を意味で分類した semantic tokens を返します（型検査の後の情報を使います）。
+///
+/// - `tokens[]`: `range`, `kind`（`function` / `parameter` / `variable` / `type` /
+///   `type_parameter` / `enum_member` / `trait` / `property` / `namespace` / `label`）, `modifiers`
+/// - `modifiers` は `legend.token_modifiers` の順のビット（`declaration`, `mutable`,
+///   `default_library`, `deprecated`）
+/// - `data`: LSP の相対エンコーディング（1 token 5 要素、列は文字単位）
+#[wasm_bindgen]
+pub fn analyze_semantic_tokens(source: &str) -> JsValue {
+    to_js(&analysis::semantic_tokens(source))
+}
+
+/// `analyze_semantic_tokens` の import 対応版です。同梱 stdlib の定義には
+/// `default_library` が付きます。
+#[wasm_bindgen]
+pub fn analyze_semantic_tokens_with_vfs(entry_path: &str, source: &str, vfs: JsValue) -> JsValue {
+    let loaded = load_for_analysis(entry_path, source, vfs);
+    to_js(&analysis::semantic_tokens_loaded(&loaded, "/stdlib"))
+}
+
+/// `start_line` 行から `end_line` 行の手前まで（0 始まり）の semantic tokens だけを返します。
+///
+/// 画面に見えている範囲だけを更新するときに使います。`data` は範囲の最初の token から数えます。
+#[wasm_bindgen]
+pub fn analyze_semantic_tokens_range(source: &str, start_line: u32, end_line: u32) -> JsValue {
+    to_js(&analysis::semantic_tokens(source).lines(start_line, end_line))
+}
+
+/// 前回と今回の `data` の差分を LSP の `SemanticTokensEdit` の形（`start`, `delete_count`,
+/// `data`）の配列で返します。変化がなければ空配列です。
+#[wasm_bindgen]
+pub fn semantic_tokens_delta(previous: Vec<u32>, current: Vec<u32>) -> JsValue {
+    to_js(&analysis::semantic_tokens_delta(&previous, &current))
 }
 
 fn compile_outputs_impl(
@@ -2196,13 +1126,13 @@ fn merge_vfs_sources(
     }
 }
 
-fn compile_wasm_with_entry_and_profile_and_stdlib(
+/// 同梱 stdlib と VFS を source provider として `entry_path` を読み込みます。
+fn load_with_vfs(
     entry_path: &str,
     source: &str,
     vfs: Option<JsValue>,
     stdlib_vfs: Option<JsValue>,
-    profile: Option<BuildProfile>,
-) -> Result<CompiledWasm, String> {
+) -> (Loader, Result<LoadResult, LoaderError>) {
     let stdlib_root = PathBuf::from("/stdlib");
     let mut sources = stdlib_sources(&stdlib_root);
     // stdlib 差し替えが指定された場合は、先に上書きで適用する
@@ -2226,12 +1156,23 @@ fn compile_wasm_with_entry_and_profile_and_stdlib(
                 );
                 #[cfg(target_arch = "wasm32")]
                 web_sys::console::error_1(&msg.clone().into());
-                nepl_core::loader::LoaderError::Io(msg)
+                LoaderError::Io(msg)
             })
     };
-    let loaded = loader
-        .load_inline_with_provider(PathBuf::from(entry_path), source.to_string(), &mut provider)
-        .map_err(|e| e.to_string())?;
+    let loaded =
+        loader.load_inline_with_provider(PathBuf::from(entry_path), source.to_string(), &mut provider);
+    (loader, loaded)
+}
+
+fn compile_wasm_with_entry_and_profile_and_stdlib(
+    entry_path: &str,
+    source: &str,
+    vfs: Option<JsValue>,
+    stdlib_vfs: Option<JsValue>,
+    profile: Option<BuildProfile>,
+) -> Result<CompiledWasm, String> {
+    let (_, loaded) = load_with_vfs(entry_path, source, vfs, stdlib_vfs);
+    let loaded = loaded.map_err(|e| e.to_string())?;
     let artifact = compile_module(
         loaded.module,
         CompileOptions {
@@ -2402,6 +1343,13 @@ fn render_diagnostics(diags: &[Diagnostic], sm: &SourceMap) -> String {
                 col = c + 1
             ));
         }
+        for note in &d.notes {
+            out.push_str(&format!(
+                " {cyan}help:{reset} {note}\n",
+                cyan = CYAN,
+                reset = RESET,
+            ));
+        }
         out.push('\n');
     }
     out
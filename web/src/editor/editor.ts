@@ -90,6 +90,7 @@ class CanvasEditor {
                 'function': '#61afef', 'number': '#d19a66', 'boolean': '#d19a66',
                 'operator': '#56b6c2', 'regex': '#d19a66', 'property': '#e06c75',
                 'punctuation': '#b3a5b0ff', 'variable': '#7da5f0ff',
+                'parameter': '#e5c07b', 'type': '#56b6c2', 'enum-member': '#d19a66',
                 'heading': '#e06c75', 'bold': '#d19a66', 'italic': '#c678dd',
                 'list': '#56b6c2', 'link': '#61afef', 'inline-code': '#98c379',
                 'code-block': '#5c6370', 'default': '#b5b7bbff'
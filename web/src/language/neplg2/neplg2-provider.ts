@@ -8,6 +8,7 @@ class NEPLg2LanguageProvider {
         this.parse = null;
         this.resolve = null;
         this.semantics = null;
+        this.highlight = null;
         this.analysisVersion = 0;
         this.pendingTimer = null;
         this.analyzeDelayMs = 80;
@@ -116,6 +117,7 @@ class NEPLg2LanguageProvider {
             this.parse = null;
             this.resolve = null;
             this.semantics = null;
+            this.highlight = null;
             this.definitionById.clear();
             const payload = {
                 tokens: [],
@@ -135,6 +137,7 @@ class NEPLg2LanguageProvider {
         this.parse = null;
         this.resolve = null;
         this.semantics = null;
+        this.highlight = null;
 
         try {
             this.lex = wasm.analyze_lex(this.text);
@@ -193,6 +196,15 @@ class NEPLg2LanguageProvider {
             }
         }
 
+        // 型検査後の分類（関数・引数・型・型引数など）で識別子の色を決める
+        if (this.parse?.ok && typeof wasm.analyze_semantic_tokens === 'function') {
+            try {
+                this.highlight = wasm.analyze_semantic_tokens(this.text);
+            } catch (e) {
+                console.error('[NEPLg2LanguageProvider] analyze_semantic_tokens failed:', e);
+            }
+        }
+
         if (version !== this.analysisVersion) {
             return;
         }
@@ -280,9 +292,31 @@ class NEPLg2LanguageProvider {
         return 'default';
     }
 
+    _semanticTokenType(kind) {
+        switch (kind) {
+            case 'function': return 'function';
+            case 'parameter': return 'parameter';
+            case 'variable': return 'variable';
+            case 'type':
+            case 'type_parameter':
+            case 'trait':
+            case 'namespace': return 'type';
+            case 'enum_member': return 'enum-member';
+            case 'property': return 'property';
+            case 'label': return 'keyword';
+            default: return null;
+        }
+    }
+
     _buildEditorTokens() {
         const lexTokens = Array.isArray(this.lex?.tokens) ? this.lex.tokens : [];
         const tokenRes = Array.isArray(this.semantics?.token_resolution) ? this.semantics.token_resolution : [];
+        // `.T` の token は `.` から始まるので、終わりの位置で引く
+        const highlightByEnd = new Map();
+        for (const st of Array.isArray(this.highlight?.tokens) ? this.highlight.tokens : []) {
+            const r = st?.range;
+            if (r) highlightByEnd.set(Number(r.end), { start: Number(r.start), kind: st.kind });
+        }
         const skipKinds = new Set(['Indent', 'Dedent', 'Eof', 'Newline']);
         const normalized = [];
 
@@ -302,6 +336,10 @@ class NEPLg2LanguageProvider {
                     t = 'function';
                 }
             }
+            const hl = highlightByEnd.get(Number(tok?.span?.end));
+            if (hl && hl.start <= Number(tok?.span?.start)) {
+                t = this._semanticTokenType(hl.kind) || t;
+            }
             normalized.push({
                 startIndex: span.startIndex,
                 endIndex: span.endIndex,